@@ -0,0 +1,153 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks of the wallet updates: separate `update_wallet` calls against
+//! `update_wallets_batch`, and the throughput of `Transfer` execution.
+
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Bencher, Criterion, ParameterizedBenchmark, Throughput};
+use exonum::{
+    blockchain::Blockchain,
+    crypto::{self, Hash, PublicKey, SecretKey},
+    helpers::Height,
+    messages::{RawTransaction, Signed},
+};
+use exonum_testkit::{TestKit, TestKitBuilder};
+
+use exonum_cryptocurrency_advanced::{
+    transactions::{CreateWallet, Transfer},
+    wallet::Wallet,
+    Amount, Schema, Service,
+};
+
+const SAMPLE_SIZE: usize = 20;
+const WALLET_COUNTS: [usize; 3] = [10, 100, 1_000];
+/// Number of updates of every wallet in the benchmarks of the wallet updates.
+const UPDATES_PER_WALLET: usize = 4;
+
+/// Creates a testkit with the given number of wallets.
+fn create_wallets(count: usize) -> (TestKit, Vec<(PublicKey, SecretKey)>) {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service::default())
+        .create();
+    let keys: Vec<_> = (0..count).map(|_| crypto::gen_keypair()).collect();
+    testkit.create_block_with_transactions(
+        keys.iter()
+            .enumerate()
+            .map(|(i, (pk, sk))| CreateWallet::sign(&format!("wallet {}", i), pk, sk)),
+    );
+    (testkit, keys)
+}
+
+/// Returns successive states of the wallets, each increasing the balance by one,
+/// together with the hashes of the transactions producing them.
+fn wallet_updates(blockchain: &Blockchain, keys: &[(PublicKey, SecretKey)]) -> Vec<(Wallet, Hash)> {
+    let snapshot = blockchain.snapshot();
+    let schema = Schema::new(&snapshot);
+    let mut updates = Vec::new();
+    for (pk, _) in keys {
+        let mut wallet = schema.wallet(pk).unwrap();
+        for i in 0..UPDATES_PER_WALLET {
+            wallet = wallet.try_increase_balance(Amount::from(1)).unwrap();
+            let tx_hash = crypto::hash(&[pk.as_ref(), &i.to_le_bytes()].concat());
+            updates.push((wallet.clone(), tx_hash));
+        }
+    }
+    updates
+}
+
+fn single_updates(b: &mut Bencher, count: usize) {
+    let (testkit, keys) = create_wallets(count);
+    let blockchain = testkit.blockchain().clone();
+    let updates = wallet_updates(&blockchain, &keys);
+    b.iter_with_setup(
+        || blockchain.fork(),
+        |mut fork| {
+            let mut schema = Schema::new(&mut fork);
+            for (wallet, tx_hash) in &updates {
+                let wallet = schema.wallet(&wallet.pub_key).unwrap();
+                let wallet = wallet.try_increase_balance(Amount::from(1)).unwrap();
+                schema.update_wallet(wallet, *tx_hash, Height(2));
+            }
+        },
+    );
+}
+
+fn batched_updates(b: &mut Bencher, count: usize) {
+    let (testkit, keys) = create_wallets(count);
+    let blockchain = testkit.blockchain().clone();
+    let updates = wallet_updates(&blockchain, &keys);
+    b.iter_with_setup(
+        || (blockchain.fork(), updates.clone()),
+        |(mut fork, updates)| {
+            Schema::new(&mut fork).update_wallets_batch(updates, Height(2));
+        },
+    );
+}
+
+fn transfers(b: &mut Bencher, count: usize) {
+    let (mut testkit, keys) = create_wallets(count);
+    let mut seed = 0;
+    b.iter_with_setup(
+        || {
+            seed += 1;
+            keys.iter()
+                .zip(keys.iter().cycle().skip(1))
+                .map(|((from, sk), (to, _))| Transfer::sign(from, to, 1, seed, sk))
+                .collect::<Vec<Signed<RawTransaction>>>()
+        },
+        |txs| {
+            testkit.create_block_with_transactions(txs);
+        },
+    );
+}
+
+fn bench_fn<F>(c: &mut Criterion, name: &str, benchmark: F, elements_per_wallet: usize)
+where
+    F: Fn(&mut Bencher, usize) + 'static,
+{
+    c.bench(
+        name,
+        ParameterizedBenchmark::new(
+            "wallets",
+            move |b: &mut Bencher, &count: &usize| benchmark(b, count),
+            WALLET_COUNTS.iter().cloned(),
+        )
+        .throughput(move |&count| Throughput::Elements((count * elements_per_wallet) as u32))
+        .sample_size(SAMPLE_SIZE),
+    );
+}
+
+fn bench_wallet_updates(c: &mut Criterion) {
+    crypto::init();
+
+    bench_fn(
+        c,
+        "wallet_updates/single",
+        single_updates,
+        UPDATES_PER_WALLET,
+    );
+    bench_fn(
+        c,
+        "wallet_updates/batch",
+        batched_updates,
+        UPDATES_PER_WALLET,
+    );
+    bench_fn(c, "transactions/transfer", transfers, 1);
+}
+
+criterion_group!(benches, bench_wallet_updates);
+criterion_main!(benches);
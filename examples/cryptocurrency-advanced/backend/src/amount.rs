@@ -0,0 +1,411 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fixed-point amounts of the currency.
+//!
+//! An `Amount` counts the currency in units of `10^-Amount::DECIMALS`, so it represents
+//! both whole units and their fractions. The number of decimals the network actually
+//! accepts is limited by `Config::decimals`.
+//!
+//! Amounts written by the previous versions of the service are whole units in `u64`.
+//! Whole amounts fitting into `u64` are still written that way, both in protobuf and
+//! in the storage, while the other amounts are written in full. Thus the data written
+//! before amounts got fractions is read as is, and its hashes do not change.
+
+use exonum::{
+    crypto::{self, CryptoHash, Hash},
+    proto::ProtobufConvert,
+    storage::StorageValue,
+};
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Serializer,
+};
+
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use crate::proto;
+
+/// Number of the smallest fractions in a whole unit of the currency.
+const SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// Amount of the currency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u128);
+
+/// Error of parsing an amount from a decimal string.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum AmountError {
+    /// The string is not a non-negative decimal number, e.g. `12` or `12.5`.
+    #[fail(display = "Invalid amount: {:?}", _0)]
+    Invalid(String),
+    /// The number has more than `Amount::DECIMALS` decimals.
+    #[fail(display = "Amount has more than {} decimals", Amount::DECIMALS)]
+    TooPrecise,
+    /// The number exceeds `Amount::MAX`.
+    #[fail(display = "Amount is too large")]
+    Overflow,
+}
+
+impl Amount {
+    /// Maximum number of decimals of an amount.
+    pub const DECIMALS: u8 = 18;
+    /// Zero amount.
+    pub const ZERO: Amount = Amount(0);
+    /// The largest representable amount.
+    pub const MAX: Amount = Amount(u128::max_value());
+
+    /// Creates an amount of the given number of whole units.
+    pub fn whole(units: u64) -> Self {
+        Amount(u128::from(units) * SCALE)
+    }
+
+    /// Creates an amount from the number of the smallest fractions of a unit,
+    /// i.e., of `10^-DECIMALS` units.
+    pub fn from_raw(raw: u128) -> Self {
+        Amount(raw)
+    }
+
+    /// Returns the number of the smallest fractions of a unit in the amount.
+    pub fn raw(self) -> u128 {
+        self.0
+    }
+
+    /// Creates the fraction of the given number of basis points, i.e., of hundredths
+    /// of a percent, e.g., `0.025` for `250`.
+    pub fn basis_points(basis_points: u32) -> Self {
+        Amount(u128::from(basis_points) * (SCALE / 10_000))
+    }
+
+    /// Returns the number of whole units if the amount has no fractional part
+    /// and the number fits into `u64`.
+    pub fn whole_units(self) -> Option<u64> {
+        if self.0 % SCALE == 0 && self.0 / SCALE <= u128::from(u64::max_value()) {
+            Some((self.0 / SCALE) as u64)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of decimals required to write the amount, i.e., `0` for
+    /// whole amounts, `1` for `12.5`, and so on.
+    pub fn decimals(self) -> u8 {
+        let mut fraction = self.0 % SCALE;
+        if fraction == 0 {
+            return 0;
+        }
+        let mut decimals = Self::DECIMALS;
+        while fraction % 10 == 0 {
+            fraction /= 10;
+            decimals -= 1;
+        }
+        decimals
+    }
+
+    /// Rounds the amount down to the given number of decimals.
+    pub fn round_down(self, decimals: u8) -> Self {
+        if decimals >= Self::DECIMALS {
+            return self;
+        }
+        let unit = 10_u128.pow(u32::from(Self::DECIMALS - decimals));
+        Amount(self.0 - self.0 % unit)
+    }
+
+    /// Returns whether the amount is zero.
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Adds two amounts, returning `None` on overflow.
+    pub fn checked_add(self, other: Amount) -> Option<Self> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    /// Subtracts an amount, returning `None` if `other` is greater than `self`.
+    pub fn checked_sub(self, other: Amount) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    /// Adds two amounts, saturating at `Amount::MAX`.
+    pub fn saturating_add(self, other: Amount) -> Self {
+        Amount(self.0.saturating_add(other.0))
+    }
+
+    /// Subtracts an amount, saturating at zero.
+    pub fn saturating_sub(self, other: Amount) -> Self {
+        Amount(self.0.saturating_sub(other.0))
+    }
+
+    /// Multiplies the amount by a number, saturating at `Amount::MAX`.
+    pub fn saturating_mul(self, factor: u64) -> Self {
+        Amount(self.0.saturating_mul(u128::from(factor)))
+    }
+
+    /// Multiplies the amount by a fraction written as an amount, e.g., by `0.05` to take
+    /// 5% of it. The result is rounded down. Returns `None` on overflow.
+    pub fn checked_mul_fraction(self, fraction: Amount) -> Option<Self> {
+        // `self * fraction / SCALE` is split into the parts which cannot overflow
+        // before the division unless the result does.
+        let (units, rest) = (self.0 / SCALE, self.0 % SCALE);
+        let (fraction_units, fraction_rest) = (fraction.0 / SCALE, fraction.0 % SCALE);
+        units
+            .checked_mul(fraction.0)?
+            .checked_add(rest.checked_mul(fraction_units)?)?
+            .checked_add(rest * fraction_rest / SCALE)
+            .map(Amount)
+    }
+
+    /// Returns the fraction which the amount makes of the other one as an amount,
+    /// e.g., `0.25` for `1` of `4`. The result is rounded down. Returns `None` if
+    /// the other amount is zero or the result overflows.
+    pub fn checked_ratio(self, other: Amount) -> Option<Self> {
+        if other.0 == 0 {
+            return None;
+        }
+        let mut raw = (self.0 / other.0).checked_mul(SCALE)?;
+        let mut rest = self.0 % other.0;
+        let mut digit_scale = SCALE;
+        while digit_scale > 1 {
+            digit_scale /= 10;
+            // Long division: `10 * rest = digit * other + next_rest`. The multiplication
+            // is done by additions modulo `other`, so it never overflows.
+            let (mut digit, mut next_rest) = (0, 0);
+            for _ in 0..10 {
+                if next_rest >= other.0 - rest {
+                    next_rest -= other.0 - rest;
+                    digit += 1;
+                } else {
+                    next_rest += rest;
+                }
+            }
+            raw = raw.checked_add(digit * digit_scale)?;
+            rest = next_rest;
+        }
+        Some(Amount(raw))
+    }
+
+    /// Reads an amount written by `to_pb_fields`. The full amount is never zero when
+    /// written, so a zero (i.e., absent) full amount means that the amount is written
+    /// in whole units.
+    pub fn from_pb_fields(units: u64, exact: &proto::Amount) -> Self {
+        match Amount::from_pb(exact.clone()).expect("Amount conversion never fails") {
+            Amount(0) => Amount::whole(units),
+            exact => exact,
+        }
+    }
+
+    /// Splits the amount into the value of the legacy `u64` field of whole units
+    /// and the full amount, which is written only if the amount cannot be written
+    /// in whole units.
+    pub fn to_pb_fields(self) -> (u64, Option<proto::Amount>) {
+        match self.whole_units() {
+            Some(units) => (units, None),
+            None => (0, Some(self.to_pb())),
+        }
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(units: u64) -> Self {
+        Amount::whole(units)
+    }
+}
+
+/// Compares the amount with a number of whole units.
+impl PartialEq<u64> for Amount {
+    fn eq(&self, units: &u64) -> bool {
+        *self == Amount::whole(*units)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (units, fraction) = (self.0 / SCALE, self.0 % SCALE);
+        if fraction == 0 {
+            return write!(f, "{}", units);
+        }
+        let fraction = format!("{:018}", fraction);
+        write!(f, "{}.{}", units, fraction.trim_end_matches('0'))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AmountError::Invalid(s.to_owned());
+        let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+
+        let (units, fraction) = match s.find('.') {
+            Some(dot) => (&s[..dot], &s[dot + 1..]),
+            None => (s, "0"),
+        };
+        if !is_number(units) || !is_number(fraction) {
+            return Err(invalid());
+        }
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > Self::DECIMALS as usize {
+            return Err(AmountError::TooPrecise);
+        }
+
+        let units = units.parse::<u128>().map_err(|_| AmountError::Overflow)?;
+        let fraction = format!("{:0<18}", fraction)
+            .parse::<u128>()
+            .map_err(|_| invalid())?;
+        units
+            .checked_mul(SCALE)
+            .and_then(|raw| raw.checked_add(fraction))
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+}
+
+/// Amounts are written to JSON as decimal strings, such as `"12.5"`, since JSON numbers
+/// do not hold `u128` values reliably.
+impl serde::Serialize for Amount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Amounts are read from decimal strings as well as from integers, which are the whole
+/// units as written by the previous versions of the service.
+impl<'de> serde::Deserialize<'de> for Amount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct AmountVisitor;
+
+        impl<'de> Visitor<'de> for AmountVisitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a decimal string or a whole number of units")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Amount, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Amount, E> {
+                Ok(Amount::whole(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Amount, E> {
+                if value < 0 {
+                    return Err(E::custom("Amount cannot be negative"));
+                }
+                Ok(Amount::whole(value as u64))
+            }
+        }
+
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+/// The full amount is written as two halves of the `u128` value.
+impl ProtobufConvert for Amount {
+    type ProtoStruct = proto::Amount;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::Amount::new();
+        pb.set_low(self.0 as u64);
+        pb.set_high((self.0 >> 64) as u64);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Amount(
+            u128::from(pb.get_high()) << 64 | u128::from(pb.get_low()),
+        ))
+    }
+}
+
+/// Whole amounts fitting into `u64` are stored as the `u64` number of units, the other
+/// amounts as the 16 bytes of the `u128` value. Both are little-endian.
+impl StorageValue for Amount {
+    fn into_bytes(self) -> Vec<u8> {
+        match self.whole_units() {
+            Some(units) => units.into_bytes(),
+            None => self.0.into_bytes(),
+        }
+    }
+
+    fn from_bytes(value: Cow<[u8]>) -> Self {
+        match value.len() {
+            8 => Amount::whole(u64::from_bytes(value)),
+            16 => Amount(u128::from_bytes(value)),
+            len => panic!("Malformed amount of {} bytes in the storage", len),
+        }
+    }
+}
+
+impl CryptoHash for Amount {
+    fn hash(&self) -> Hash {
+        crypto::hash(&self.into_bytes())
+    }
+}
+
+/// Implements `BinaryForm`, `CryptoHash` and `StorageValue` through `ProtobufConvert`,
+/// the same way `#[derive(ProtobufConvert)]` does, for the types holding amounts.
+/// The conversion of such types is written by hand to read and write the amounts
+/// with `Amount::from_pb_fields` and `Amount::to_pb_fields`.
+macro_rules! impl_pb_storage {
+    ($($name:ident),*) => {
+        $(
+            impl exonum::messages::BinaryForm for $name {
+                fn encode(&self) -> Result<Vec<u8>, failure::Error> {
+                    let pb = exonum::proto::ProtobufConvert::to_pb(self);
+                    protobuf::Message::write_to_bytes(&pb).map_err(failure::Error::from)
+                }
+
+                fn decode(buffer: &[u8]) -> Result<Self, failure::Error> {
+                    let pb = protobuf::parse_from_bytes(buffer)?;
+                    <Self as exonum::proto::ProtobufConvert>::from_pb(pb)
+                }
+            }
+
+            impl exonum::crypto::CryptoHash for $name {
+                fn hash(&self) -> exonum::crypto::Hash {
+                    let pb = exonum::proto::ProtobufConvert::to_pb(self);
+                    let bytes = protobuf::Message::write_to_bytes(&pb)
+                        .expect("Protobuf serialization never fails");
+                    exonum::crypto::hash(&bytes)
+                }
+            }
+
+            impl exonum::storage::StorageValue for $name {
+                fn into_bytes(self) -> Vec<u8> {
+                    let pb = exonum::proto::ProtobufConvert::to_pb(&self);
+                    protobuf::Message::write_to_bytes(&pb).expect(concat!(
+                        "Failed to serialize in StorageValue for ",
+                        stringify!($name)
+                    ))
+                }
+
+                fn from_bytes(value: std::borrow::Cow<[u8]>) -> Self {
+                    let pb = protobuf::parse_from_bytes(value.as_ref()).unwrap();
+                    <Self as exonum::proto::ProtobufConvert>::from_pb(pb).expect(concat!(
+                        "Failed to deserialize in StorageValue for ",
+                        stringify!($name)
+                    ))
+                }
+            }
+        )*
+    };
+}
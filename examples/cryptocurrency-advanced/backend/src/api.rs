@@ -14,22 +14,509 @@
 
 //! Cryptocurrency API.
 
+use actix_web::{
+    http::{header, Method},
+    HttpResponse,
+};
+use bytes::Bytes;
 use exonum::{
-    api::{self, ServiceApiBuilder, ServiceApiState},
-    blockchain::{self, BlockProof, TransactionMessage},
-    crypto::{Hash, PublicKey},
-    explorer::{BlockchainExplorer, TransactionInfo},
+    api::{
+        self,
+        backends::actix::{FutureResponse, HttpRequest, RawHandler, RequestHandler},
+        node::public::explorer::{TransactionHex, TransactionResponse},
+        ServiceApiBackend, ServiceApiBuilder, ServiceApiScope, ServiceApiState,
+    },
+    blockchain::{
+        self, BlockProof, Blockchain, Transaction, TransactionContext, TransactionError,
+        TransactionErrorType, TransactionMessage, TransactionResult,
+    },
+    crypto::{CryptoHash, Hash, HashStream, PublicKey},
+    explorer::BlockchainExplorer,
     helpers::Height,
-    storage::{ListProof, MapProof},
+    messages::{RawTransaction, ServiceTransaction, Signed},
+    proto::ProtobufConvert,
+    storage::{proof_list_index::ListProof, MapProof, Snapshot},
+};
+use futures::{IntoFuture, Stream};
+use protobuf::Message as ProtobufMessage;
+use serde::{
+    de::{self, DeserializeOwned, Visitor},
+    Deserializer, Serialize,
+};
+
+use std::{any::Any, cmp, collections::HashSet, fmt, panic, sync::Arc};
+
+use crate::{
+    amount::Amount,
+    client::{self, ProofError},
+    joint_wallet::JointWallet,
+    key_encoding,
+    metrics::{BlockSummary, ServiceMetrics},
+    multisig_transfer::{Approval, BatchApprovalResult, MultisignatureTransfer, State},
+    proto,
+    stream::TransactionStream,
+    transactions::{
+        self, service_tx, transfer_multisig_tx, ApproveTransferMultisig, IssueTo, OpenStream,
+        ProposeJointSpend, WalletTransactions,
+    },
+    tx_encoding::{parse_signed_hex, ParseError},
+    wallet::{HistoryProof, HistoryRecord, NameChange, Wallet},
+    wallet_id::WalletId,
+    webhooks::{Webhook, WebhookRegistry},
+    Schema, CRYPTOCURRENCY_SERVICE_ID,
 };
 
-use crate::{wallet::Wallet, Schema, CRYPTOCURRENCY_SERVICE_ID};
+/// Media type of protobuf-encoded responses. Wallet endpoints respond with protobuf
+/// instead of JSON if the request's `Accept` header contains this type.
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/protobuf";
+
+/// Media type of the metrics rendered in the Prometheus text format.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Index of the block summaries list among the tables aggregated into the service
+/// state hash.
+const BLOCK_SUMMARIES_TABLE_INDEX: usize = 23;
+
+/// Media type of the server-sent events stream.
+pub const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+
+/// The maximum number of items returned by the paginated endpoints.
+pub const MAX_ITEMS_PER_REQUEST: u64 = 100;
+/// The default maximum number of items in a list returned by the API, see
+/// `Config::max_list_items`.
+pub const MAX_LIST_ITEMS: u64 = 1_000;
+/// The maximum number of public keys accepted by the `wallets_batch` endpoint.
+pub const MAX_KEYS_PER_BATCH: usize = 50;
+/// The maximum number of public keys accepted by the `balance_sum` endpoint.
+pub const MAX_KEYS_PER_BALANCE_SUM: usize = 100;
+
+/// Kind of an error returned by the service API. Each kind has a stable numeric code.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Requested wallet is not found.
+    WalletNotFound,
+    /// Query parameters are malformed or out of the allowed range.
+    InvalidQuery,
+    /// Too many public keys are requested at once.
+    TooManyKeys,
+    /// Submitted transaction is not a valid hex string.
+    MalformedHex,
+    /// Submitted data is not a signed transaction message.
+    MalformedMessage,
+    /// Submitted transaction belongs to another service.
+    WrongServiceId,
+    /// Submitted payload is not a transaction of the service.
+    UndecodablePayload,
+    /// Internal error of the service.
+    Internal,
+    /// Requested block does not exist, or the latest block or its precommits
+    /// are not available yet.
+    BlockNotFound,
+    /// Requested multisignature transfer is not found.
+    MultisigTransferNotFound,
+    /// Query contains parameters not accepted by the endpoint.
+    UnknownQueryParameters,
+    /// Pagination cursor is malformed or does not point into the listing anymore.
+    InvalidCursor,
+    /// Requested wallet history records are pruned by the node.
+    HistoryPruned,
+    /// Requested list exceeds the maximum number of items set in the service configuration.
+    ListTooLarge,
+}
+
+impl ErrorKind {
+    /// Returns the numeric code of the error kind.
+    pub fn code(self) -> u16 {
+        match self {
+            ErrorKind::WalletNotFound => 1,
+            ErrorKind::InvalidQuery => 2,
+            ErrorKind::TooManyKeys => 3,
+            ErrorKind::MalformedHex => 4,
+            ErrorKind::MalformedMessage => 5,
+            ErrorKind::WrongServiceId => 6,
+            ErrorKind::UndecodablePayload => 7,
+            ErrorKind::Internal => 8,
+            ErrorKind::BlockNotFound => 9,
+            ErrorKind::MultisigTransferNotFound => 10,
+            ErrorKind::UnknownQueryParameters => 11,
+            ErrorKind::InvalidCursor => 12,
+            ErrorKind::HistoryPruned => 13,
+            ErrorKind::ListTooLarge => 14,
+        }
+    }
+}
+
+/// Error returned by the service API. It is serialized into the body
+/// of the HTTP error response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiError {
+    /// Numeric code of the error.
+    pub code: u16,
+    /// Kind of the error.
+    pub kind: ErrorKind,
+    /// Human-readable message.
+    pub message: String,
+    /// Additional data specific to the error kind.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    /// Creates an error of the given kind.
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        ApiError {
+            code: kind.code(),
+            kind,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// Attaches additional data to the error.
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Creates an `InvalidCursor` error for the given cursor.
+    pub fn invalid_cursor(cursor: &str) -> Self {
+        ApiError::new(
+            ErrorKind::InvalidCursor,
+            format!(
+                "Cursor `{}` is invalid or expired, restart the listing without a cursor",
+                cursor
+            ),
+        )
+    }
+
+    /// Creates a `HistoryPruned` error for the history retained from the given index.
+    pub fn history_pruned(first_retained: u64) -> Self {
+        ApiError::new(
+            ErrorKind::HistoryPruned,
+            format!(
+                "History records before index {} are pruned by the node",
+                first_retained
+            ),
+        )
+        .with_details(json!({ "first_retained": first_retained }))
+    }
+
+    /// Creates a `ListTooLarge` error for the given maximum number of items.
+    pub fn list_too_large(max_items: u64) -> Self {
+        ApiError::new(
+            ErrorKind::ListTooLarge,
+            format!("Requested list exceeds {} items", max_items),
+        )
+        .with_details(json!({ "max_items": max_items }))
+    }
+
+    /// Creates a `WalletNotFound` error for the given key.
+    pub fn wallet_not_found(pub_key: &PublicKey) -> Self {
+        ApiError::new(
+            ErrorKind::WalletNotFound,
+            format!("Wallet with public key = {} is not found", pub_key),
+        )
+    }
+
+    /// Creates a `WalletNotFound` error for the name which does not refer to a wallet.
+    ///
+    /// The message also explains why the name is not a public key, since malformed keys
+    /// are treated as names.
+    pub fn wallet_name_not_found(name: &str) -> Self {
+        let key_error = key_encoding::parse_public_key(name)
+            .err()
+            .map(|e| format!(" and is not a public key: {}", e))
+            .unwrap_or_default();
+        ApiError::new(
+            ErrorKind::WalletNotFound,
+            format!("Wallet with name = {} is not found{}", name, key_error),
+        )
+    }
+}
+
+impl From<ApiError> for api::error::Error {
+    fn from(e: ApiError) -> Self {
+        let body = serde_json::to_string(&e).expect("Cannot serialize API error");
+        match e.kind {
+            ErrorKind::WalletNotFound
+            | ErrorKind::BlockNotFound
+            | ErrorKind::MultisigTransferNotFound => api::error::Error::NotFound(body),
+            ErrorKind::Internal => api::error::Error::InternalError(format_err!("{}", body)),
+            _ => api::error::Error::BadRequest(body),
+        }
+    }
+}
 
 /// Describes the query parameters for the `get_wallet` endpoint.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct WalletQuery {
-    /// Public key of the queried wallet.
+    /// Public key or name of the queried wallet. Also accepted as `pub_key`
+    /// for compatibility.
+    pub id: WalletId,
+}
+
+/// Describes the query parameters for the `wallet_info` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WalletInfoQuery {
+    /// Public key or name of the queried wallet. Also accepted as `pub_key`
+    /// for compatibility.
+    pub id: WalletId,
+    /// Whether to include the wallet history into the response. `true` by default.
+    #[serde(default = "default_with_history")]
+    pub with_history: bool,
+    /// Index of the first returned history entry. If not set, the latest
+    /// `history_limit` entries are returned.
+    pub history_offset: Option<u64>,
+    /// The maximum number of returned history entries; `0` means no history.
+    /// Should not be greater than `MAX_ITEMS_PER_REQUEST`, which is also the default value.
+    pub history_limit: Option<u64>,
+    /// Whether the node should verify the proofs of the response itself, for clients
+    /// which cannot verify them. `false` by default.
+    #[serde(default)]
+    pub verify: bool,
+}
+
+fn default_with_history() -> bool {
+    true
+}
+
+/// Order in which the wallet history is returned.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryOrder {
+    /// Oldest transactions first.
+    Asc,
+    /// Latest transactions first.
+    Desc,
+}
+
+impl Default for HistoryOrder {
+    fn default() -> Self {
+        HistoryOrder::Asc
+    }
+}
+
+/// Describes the query parameters for the `simple_wallet_info` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SimpleWalletInfoQuery {
+    /// Public key or name of the queried wallet. Also accepted as `pub_key`
+    /// for compatibility.
+    pub id: WalletId,
+    /// Order of the returned transactions.
+    #[serde(default)]
+    pub order: HistoryOrder,
+    /// The number of history entries to skip (counting in the requested order).
+    #[serde(default)]
+    pub offset: u64,
+    /// The maximum number of transactions to return. The whole history is returned
+    /// if not set.
+    pub limit: Option<u64>,
+}
+
+/// Describes the query parameters for the `created_wallets` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CreatedWalletsQuery {
+    /// The lowest creation height (inclusive).
+    pub from_height: u64,
+    /// The highest creation height (inclusive). Not bounded if not set.
+    pub to_height: Option<u64>,
+    /// Cursor returned with the previous page. The listing starts from the beginning
+    /// if not set.
+    pub cursor: Option<String>,
+    /// The maximum number of wallets to return. Should not be greater than
+    /// `MAX_ITEMS_PER_REQUEST`, which is also the default value.
+    pub limit: Option<u64>,
+}
+
+/// Describes the request body for the `issue` endpoint of the private API.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct IssueQuery {
+    /// Public key of the receiver's wallet.
+    #[serde(deserialize_with = "key_encoding::deserialize")]
+    pub to: PublicKey,
+    /// Issued amount of currency.
+    pub amount: Amount,
+    /// Seed of the transaction. Random if not set.
+    pub seed: Option<u64>,
+}
+
+/// Describes the request body for the `register_webhook` and `remove_webhook` endpoints
+/// of the private API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookQuery {
+    /// Public key of the wallet.
+    #[serde(deserialize_with = "key_encoding::deserialize")]
+    pub pub_key: PublicKey,
+    /// URL receiving the notifications of the wallet.
+    pub url: String,
+}
+
+/// Describes the request body for the `wallets_batch` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WalletBatchQuery {
+    /// Public keys of the queried wallets.
+    #[serde(deserialize_with = "key_encoding::deserialize_vec")]
+    pub pub_keys: Vec<PublicKey>,
+}
+
+/// Describes the query parameters for the `wallet_history_range` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WalletHistoryRangeQuery {
+    /// Public key or name of the queried wallet. Also accepted as `pub_key`
+    /// for compatibility.
+    pub id: WalletId,
+    /// Index of the first history entry in the range (inclusive).
+    pub from: u64,
+    /// Index of the last history entry in the range (exclusive).
+    pub to: u64,
+}
+
+/// Describes the query parameters for the `wallet_history_since` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WalletHistorySinceQuery {
+    /// Public key or name of the queried wallet. Also accepted as `pub_key`
+    /// for compatibility.
+    pub id: WalletId,
+    /// Index of the first history entry unknown to the client.
+    pub from_index: u64,
+}
+
+/// Describes the query parameters for the `outgoing_multisig_transfers` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OutgoingMultisigQuery {
+    /// Public key of the sender's wallet.
+    #[serde(deserialize_with = "key_encoding::deserialize")]
     pub pub_key: PublicKey,
+    /// If set, only transfers in this state are returned.
+    pub state: Option<State>,
+    /// The number of matching transfers to skip.
+    #[serde(default)]
+    pub offset: u64,
+    /// The maximum number of transfers to return. Should not be greater than
+    /// `MAX_ITEMS_PER_REQUEST`, which is also the default value.
+    pub limit: Option<u64>,
+}
+
+/// Describes the query parameters for the `multisig_failures` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MultisigFailuresQuery {
+    /// Public key of the wallet.
+    #[serde(deserialize_with = "key_encoding::deserialize")]
+    pub pub_key: PublicKey,
+    /// The number of failed transfers to skip.
+    #[serde(default)]
+    pub offset: u64,
+    /// The maximum number of transfers to return. Should not be greater than
+    /// `MAX_ITEMS_PER_REQUEST`, which is also the default value.
+    pub limit: Option<u64>,
+}
+
+/// Describes the query parameters for the `name_history` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NameHistoryQuery {
+    /// Public key of the wallet.
+    #[serde(deserialize_with = "key_encoding::deserialize")]
+    pub pub_key: PublicKey,
+    /// Height of the block after which the name of the wallet is returned.
+    /// The latest committed height if not set.
+    pub height: Option<u64>,
+    /// The number of name changes to skip.
+    #[serde(default)]
+    pub offset: u64,
+    /// The maximum number of name changes to return. Should not be greater than
+    /// `MAX_ITEMS_PER_REQUEST`, which is also the default value.
+    pub limit: Option<u64>,
+}
+
+/// Describes the query parameters for the `block_summaries` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BlockSummariesQuery {
+    /// Height of the first block in the range (inclusive).
+    pub from: u64,
+    /// Height of the last block in the range (exclusive).
+    pub to: u64,
+}
+
+/// Describes the query parameters for the `balance_at` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceAtQuery {
+    /// Public key or name of the queried wallet. Also accepted as `pub_key`
+    /// for compatibility.
+    pub id: WalletId,
+    /// Height of the block after which the balance is requested.
+    pub height: u64,
+}
+
+/// Describes the query parameters for the `block_activity` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BlockActivityQuery {
+    /// Height of the block.
+    pub height: u64,
+}
+
+/// Describes the query parameters for the `affected_wallets` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AffectedWalletsQuery {
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+}
+
+/// Describes the query parameters for the `approval_batch_results` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ApprovalBatchQuery {
+    /// Hash of the `ApproveTransferMultisigBatch` transaction.
+    pub tx_hash: Hash,
+}
+
+/// Describes the query parameters for the `multisig_proof` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MultisigTransferQuery {
+    /// Hash of the `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+}
+
+/// Describes the query parameters for the `transfers_by_seed` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TransferSeedQuery {
+    /// Public key of the transfer author.
+    #[serde(deserialize_with = "key_encoding::deserialize")]
+    pub pub_key: PublicKey,
+    /// Seed of the transfer.
+    pub seed: u64,
+}
+
+/// Describes the query parameters for the `multisig_list` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MultisigListQuery {
+    /// State of the listed transfers.
+    pub state: State,
+    /// Cursor returned with the previous page. The listing starts from the beginning
+    /// if not set.
+    pub cursor: Option<String>,
+    /// The maximum number of transfers to return. Should not be greater than
+    /// `MAX_ITEMS_PER_REQUEST`, which is also the default value.
+    pub limit: Option<u64>,
 }
 
 /// Proof of existence for specific wallet.
@@ -41,13 +528,302 @@ pub struct WalletProof {
     pub to_wallet: MapProof<PublicKey, Wallet>,
 }
 
-/// Wallet history.
+/// Proof of existence for specific multisignature transfer.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultisigTransferProof {
+    /// Proof of the whole database table.
+    pub to_table: MapProof<Hash, Hash>,
+    /// Proof of the specific transfer in this table.
+    pub to_transfer: MapProof<Hash, MultisignatureTransfer>,
+}
+
+/// Multisignature transfer state together with the proofs linking it
+/// to the latest block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultisigTransferInfo {
+    /// Proof of the last block.
+    pub block_proof: BlockProof,
+    /// Proof of the transfer or of its absence.
+    pub multisig_proof: MultisigTransferProof,
+}
+
+/// Unsigned `ApproveTransferMultisig` transaction. An approver signs it by wrapping
+/// the payload into a transaction message with the given service and message identifiers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalTemplate {
+    /// Identifier of the service.
+    pub service_id: u16,
+    /// Identifier of the `ApproveTransferMultisig` transaction within the service.
+    pub message_id: u16,
+    /// Hash of the approved `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+    /// Hex-encoded payload of the transaction.
+    pub payload: String,
+}
+
+impl ApprovalTemplate {
+    fn new(tx_hash: Hash) -> Self {
+        let service_tx: ServiceTransaction = ApproveTransferMultisig::new(tx_hash).into();
+        let (message_id, payload) = service_tx.into_raw_parts();
+        ApprovalTemplate {
+            service_id: CRYPTOCURRENCY_SERVICE_ID,
+            message_id,
+            tx_hash,
+            payload: hex::encode(payload),
+        }
+    }
+}
+
+/// Details of a multisignature transfer required by its approvers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MultisigTemplate {
+    /// Hash of the `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+    /// `PublicKey` of sender's wallet.
+    pub from: PublicKey,
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Amount of currency being transferred.
+    pub amount: Amount,
+    /// All approvers of the transfer.
+    pub approvers: Vec<PublicKey>,
+    /// Approvers who have not approved the transfer yet.
+    pub pending_approvers: Vec<PublicKey>,
+    /// Approvals of the transfer together with the heights of the approving blocks
+    /// and the comments of the approvers.
+    pub approved_by: Vec<Approval>,
+    /// Number of collected approvals together with the number of required ones.
+    pub progress: (usize, usize),
+    /// Current state of the transfer.
+    pub state: State,
+    /// Approver who rejected the transfer, if the transfer is rejected.
+    pub rejected_by: Option<PublicKey>,
+    /// Height of the block which rejected the transfer, if the transfer is rejected.
+    pub rejected_at_height: Option<Height>,
+    /// Comment of the approver who rejected the transfer, if the transfer is rejected
+    /// with a comment.
+    pub rejection_comment: Option<String>,
+    /// Whether the transfer still awaits approvals. Approvers should not be
+    /// prompted otherwise.
+    pub awaiting_approval: bool,
+    /// Approval to be signed by an approver. Absent unless the transfer awaits approvals.
+    pub approval: Option<ApprovalTemplate>,
+}
+
+/// Lookup result for a single key of the batch request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchWalletEntry {
+    /// Wallet with the requested key exists.
+    Found {
+        /// The wallet itself.
+        wallet: Wallet,
+        /// Currency locked in the pending multisignature transfers of the wallet.
+        pending_outgoing: PendingOutgoing,
+    },
+    /// There is no wallet with the requested key.
+    Missing {
+        /// The requested public key.
+        pub_key: PublicKey,
+    },
+}
+
+/// Information about several wallets.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletBatchInfo {
+    /// Proof covering all the requested keys.
+    pub wallet_proof: WalletProof,
+    /// Lookup results in the order of the requested keys.
+    pub wallets: Vec<BatchWalletEntry>,
+}
+
+/// Balance of a single wallet.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WalletBalance {
+    /// Public key of the wallet.
+    pub pub_key: PublicKey,
+    /// Current balance of the wallet.
+    pub balance: Amount,
+    /// Hash of the latest transaction which changed the wallet. Clients may compare it
+    /// against their local head to skip fetching the wallet.
+    pub last_tx_hash: Hash,
+}
+
+/// Total balance of several wallets.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct BalanceSum {
+    /// Sum of the balances of the found wallets.
+    pub total: Amount,
+    /// Balances of the found wallets in the order of the requested keys.
+    pub balances: Vec<WalletBalance>,
+    /// Requested keys without wallets.
+    pub missing: Vec<PublicKey>,
+}
+
+/// Transaction accepted by the `submit_transaction` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubmittedTransaction {
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+    /// Name of the transaction type, e.g. `Transfer`.
+    pub tx_type: String,
+}
+
+/// Execution status of a transaction, serialized in the same format as the status
+/// returned by the explorer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ExecutionStatus {
+    /// Transaction was executed successfully.
+    Success,
+    /// Transaction execution returned an error.
+    Error {
+        /// Error code of the service.
+        code: u8,
+        /// Error description, empty if not specified.
+        description: String,
+    },
+    /// Transaction execution panicked.
+    Panic {
+        /// Panic description, empty if not specified.
+        description: String,
+    },
+}
+
+impl<'a> From<&'a TransactionResult> for ExecutionStatus {
+    fn from(result: &'a TransactionResult) -> Self {
+        ExecutionStatus::from(result.0.as_ref().map(|_| ()))
+    }
+}
+
+impl<'a> From<Result<(), &'a TransactionError>> for ExecutionStatus {
+    fn from(result: Result<(), &'a TransactionError>) -> Self {
+        match result {
+            Ok(()) => ExecutionStatus::Success,
+            Err(e) => {
+                let description = e.description().unwrap_or_default().to_owned();
+                match e.error_type() {
+                    TransactionErrorType::Code(code) => {
+                        ExecutionStatus::Error { code, description }
+                    }
+                    TransactionErrorType::Panic => ExecutionStatus::Panic { description },
+                }
+            }
+        }
+    }
+}
+
+/// Entry of the error code catalog.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErrorCodeInfo {
+    /// Error code returned in the transaction status.
+    pub code: u8,
+    /// Name of the error.
+    pub name: String,
+    /// Error description returned in the transaction status.
+    pub description: String,
+    /// Names of the transactions which can emit the error.
+    pub emitted_by: Vec<String>,
+}
+
+impl From<transactions::Error> for ErrorCodeInfo {
+    fn from(error: transactions::Error) -> Self {
+        ErrorCodeInfo {
+            code: error.code(),
+            name: error.name().to_owned(),
+            description: error.to_string(),
+            emitted_by: error.emitted_by().iter().map(|&tx| tx.to_owned()).collect(),
+        }
+    }
+}
+
+/// Outcome of a transaction executed by the `dry_run_transaction` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DryRunResult {
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+    /// Name of the transaction type, e.g. `Transfer`.
+    pub tx_type: String,
+    /// Status the transaction would have if it was committed on top of the current state.
+    pub status: ExecutionStatus,
+}
+
+/// Transaction of the service committed in a block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceTransactionActivity {
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+    /// Name of the transaction type, e.g. `Transfer`.
+    pub tx_type: String,
+    /// Author of the transaction.
+    pub author: PublicKey,
+    /// Amount of currency moved by the transaction, if applicable. Approvals and
+    /// rejections report the amount of the referred transfer.
+    pub amount: Option<Amount>,
+    /// Wallet receiving the currency, if applicable.
+    pub receiver: Option<PublicKey>,
+    /// Execution status of the transaction.
+    pub status: ExecutionStatus,
+}
+
+/// Transactions of the service committed in a block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlockActivity {
+    /// Height of the block.
+    pub height: Height,
+    /// Transactions of the service in the order of their execution. Transactions
+    /// of other services are skipped.
+    pub transactions: Vec<ServiceTransactionActivity>,
+}
+
+/// Page of the wallet history.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WalletHistory {
-    /// Proof of the list of transaction hashes.
-    pub proof: ListProof<Hash>,
+    /// Proof of the returned range of history records.
+    pub proof: HistoryProof,
+    /// List of above transactions.
+    pub transactions: Vec<TransactionMessage>,
+    /// Index of the first returned entry in the history.
+    pub from: u64,
+    /// Total length of the wallet history.
+    pub total_len: u64,
+}
+
+/// Sub-range of the wallet history together with the proofs linking it
+/// to the latest block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletHistoryRange {
+    /// Proof of the last block.
+    pub block_proof: BlockProof,
+    /// Proof of the appropriate wallet.
+    pub wallet_proof: WalletProof,
+    /// Proof of the returned range of history records. Absent for an empty range.
+    pub proof: Option<HistoryProof>,
     /// List of above transactions.
     pub transactions: Vec<TransactionMessage>,
+    /// Index of the first entry of the requested range which is not returned because
+    /// of the `MAX_ITEMS_PER_REQUEST` limit, or `None` if the range is returned in full.
+    pub next_index: Option<u64>,
+}
+
+/// Tail of the wallet history starting from the requested index together with
+/// the proofs linking it to the latest block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletHistorySince {
+    /// Proof of the last block.
+    pub block_proof: BlockProof,
+    /// Proof of the appropriate wallet.
+    pub wallet_proof: WalletProof,
+    /// Proof of the returned range of history records. Absent if there are
+    /// no new entries.
+    pub proof: Option<HistoryProof>,
+    /// New history entries.
+    pub transactions: Vec<TransactionMessage>,
+    /// Summaries of the new entries.
+    pub summaries: Vec<SimpleTransactionInfo>,
+    /// Index of the first new entry which is not returned because of
+    /// the `MAX_ITEMS_PER_REQUEST` limit, or `None` if all new entries are returned.
+    pub next_index: Option<u64>,
 }
 
 /// Wallet information.
@@ -57,17 +833,183 @@ pub struct WalletInfo {
     pub block_proof: BlockProof,
     /// Proof of the appropriate wallet.
     pub wallet_proof: WalletProof,
+    /// Whether the wallet exists.
+    pub exists: bool,
     /// History of the appropriate wallet.
     pub wallet_history: Option<WalletHistory>,
+    /// Currency locked in the pending multisignature transfers of the wallet.
+    pub pending_outgoing: PendingOutgoing,
+    /// Owners of the wallet. Absent unless the wallet is joint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub joint: Option<JointWallet>,
+    /// Result of the verification of the proofs by the node. Absent unless requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<ProofVerification>,
+}
+
+/// Result of the verification of the `wallet_info` proofs by the node, as a light
+/// client would verify them. This is only as trustworthy as the node itself, but
+/// it catches responses assembled from inconsistent storage.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProofVerification {
+    /// Whether all the checks have passed.
+    pub ok: bool,
+    /// Hash of the block the proofs are checked against.
+    pub checked_against_block: Hash,
+    /// Number of the validators whose precommits of the block are valid.
+    pub validators_signed: u32,
+    /// Description of the failed check. Absent if `ok` is `true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Multisignature transfer awaiting approvals.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PendingTransfer {
+    /// Hash of the `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+    /// Amount of currency being transferred.
+    pub amount: Amount,
+}
+
+/// Currency deducted from the balance of a wallet by its multisignature transfers
+/// which are still awaiting approvals. The currency is returned if a transfer
+/// is rejected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PendingOutgoing {
+    /// Total amount of the pending transfers.
+    pub total: Amount,
+    /// Pending transfers in the order of their creation.
+    pub transfers: Vec<PendingTransfer>,
+}
+
+/// Head of the wallet history.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WalletHistoryHead {
+    /// Length of the wallet history.
+    pub history_len: u64,
+    /// Root hash of the wallet history.
+    pub history_hash: Hash,
+    /// Hash of the transaction of the latest history entry. Absent for an empty history.
+    pub latest_entry_hash: Option<Hash>,
+    /// Index of the first history entry retained by the node; earlier entries are pruned.
+    pub first_retained: u64,
+}
+
+/// Wallets created or updated by a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AffectedWallets {
+    /// Public keys of the wallets in the order of the updates.
+    pub wallets: Vec<PublicKey>,
+}
+
+/// Outcomes of the approvals carried by an `ApproveTransferMultisigBatch` transaction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalBatchResults {
+    /// Outcomes in the order of the approvals.
+    pub results: Vec<BatchApprovalResult>,
+}
+
+/// Number of counterparties returned by the `wallet_summary` endpoint.
+pub const SUMMARY_TOP_COUNTERPARTIES: usize = 5;
+
+/// Transfer volume between a wallet and one of its counterparties.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct CounterpartyVolume {
+    /// Public key of the counterparty.
+    pub pub_key: PublicKey,
+    /// Total amount transferred in both directions.
+    pub volume: Amount,
+}
+
+/// Balance of a wallet at a past height.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BalanceAt {
+    /// Public key of the wallet.
+    pub pub_key: PublicKey,
+    /// Height of the block after which the balance is calculated.
+    pub height: Height,
+    /// Balance of the wallet after the block. Zero if the wallet did not exist yet.
+    pub balance: Amount,
+}
+
+/// Lifetime transfer totals of a wallet.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WalletSummary {
+    /// Total amount of currency sent by the wallet.
+    pub sent: Amount,
+    /// Total amount of currency received by the wallet.
+    pub received: Amount,
+    /// Number of outgoing transfers.
+    pub outgoing_transfers: u64,
+    /// Number of incoming transfers.
+    pub incoming_transfers: u64,
+    /// Counterparties with the largest transfer volume, largest first.
+    pub top_counterparties: Vec<CounterpartyVolume>,
+}
+
+/// Name of a wallet together with the changes of the name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NameHistory {
+    /// Name of the wallet after the block at the requested height.
+    pub name: String,
+    /// Total number of the changes of the name.
+    pub total_changes: u64,
+    /// Changes of the name in the order of execution.
+    pub changes: Vec<NameChange>,
+}
+
+/// Joint wallet together with the spends awaiting signatures of its owners.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JointWalletInfo {
+    /// Address of the wallet.
+    pub address: PublicKey,
+    /// Public keys of the owners in ascending order.
+    pub owners: Vec<PublicKey>,
+    /// Number of distinct owners who must sign a spend from the wallet.
+    pub threshold: u32,
+    /// Pending spends in the order of the hashes of their proposals.
+    pub pending: Vec<PendingJointSpend>,
+}
+
+/// Spend from a joint wallet awaiting signatures of the owners.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingJointSpend {
+    /// Hash of the `ProposeJointSpend` transaction.
+    pub proposal: Hash,
+    /// Receiver of the spend.
+    pub to: PublicKey,
+    /// Amount of currency to transfer.
+    pub amount: Amount,
+    /// Owners who have signed the spend, starting with the proposer.
+    pub signed_by: Vec<PublicKey>,
+}
+
+/// Proof of existence or absence of a wallet.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletExistence {
+    /// Whether the wallet exists.
+    pub exists: bool,
+    /// Proof of the wallet or of its absence.
+    pub wallet_proof: WalletProof,
 }
 
-/// Transaction hash and block height at which it's been committed.
+/// Transaction hash and block height at which it's been committed together
+/// with a short summary of the transaction.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct SimpleTransactionInfo {
     /// Transaction's hash.
     pub hash: Hash,
     /// Transaction's block height.
     pub height: Height,
+    /// Name of the transaction type, e.g. `Transfer`. Absent if the transaction
+    /// does not belong to the service.
+    pub tx_type: Option<String>,
+    /// Amount of currency moved by the transaction, if applicable.
+    pub amount: Option<Amount>,
+    /// The other party of the transaction: receiver for outgoing transfers
+    /// and sender for incoming ones.
+    pub counterparty: Option<PublicKey>,
 }
 
 /// Simplified wallet information.
@@ -75,102 +1017,2256 @@ pub struct SimpleTransactionInfo {
 pub struct SimpleWalletInfo {
     /// List of transactions for a given wallet.
     pub transactions: Vec<SimpleTransactionInfo>,
+    /// Index of the first history entry retained by the node. Pruned entries
+    /// are skipped.
+    pub first_retained: u64,
 }
 
-/// Public service API description.
-#[derive(Debug, Clone, Copy)]
-pub struct PublicApi;
+/// Multisignature transfer as seen by its sender.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct OutgoingMultisigTransfer {
+    /// Hash of the `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Amount of currency being transferred.
+    pub amount: Amount,
+    /// Number of approvals collected so far.
+    pub approvals: u64,
+    /// Current state of the transfer.
+    pub state: State,
+}
 
-impl PublicApi {
-    /// Endpoint for getting a single wallet.
-    pub fn wallet_info(state: &ServiceApiState, query: WalletQuery) -> api::Result<WalletInfo> {
-        let snapshot = state.snapshot();
-        let general_schema = blockchain::Schema::new(&snapshot);
-        let currency_schema = Schema::new(&snapshot);
+/// Multisignature transfers created by a single wallet.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutgoingMultisigTransfers {
+    /// List of transfers in the order of their creation.
+    pub transfers: Vec<OutgoingMultisigTransfer>,
+}
+
+/// Rejected or expired multisignature transfer involving a wallet.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct MultisigFailure {
+    /// Hash of the `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+    /// `PublicKey` of sender's wallet.
+    pub from: PublicKey,
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Amount of currency being transferred.
+    pub amount: Amount,
+    /// `rejected` or `expired`.
+    pub state: State,
+    /// Approver who rejected the transfer. `None` for expired transfers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected_by: Option<PublicKey>,
+    /// Height of the block which rejected or expired the transfer.
+    pub settled_at_height: Height,
+    /// Expiration height of the transfer. `Height(0)` if the transfer does not expire.
+    pub expires_at: Height,
+}
 
-        let max_height = general_schema.block_hashes_by_height().len() - 1;
+/// Failed multisignature transfers involving a single wallet.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultisigFailures {
+    /// List of transfers in the order of their settlement heights, then hashes.
+    pub transfers: Vec<MultisigFailure>,
+}
 
-        let block_proof = general_schema
-            .block_and_precommits(Height(max_height))
-            .unwrap();
+/// Result of the `register_webhook` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WebhookRegistered {
+    /// Whether the webhook is registered; `false` if it was registered before.
+    pub registered: bool,
+}
 
-        let to_table: MapProof<Hash, Hash> =
-            general_schema.get_proof_to_service_table(CRYPTOCURRENCY_SERVICE_ID, 0);
+/// Result of the `remove_webhook` endpoint.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WebhookRemoved {
+    /// Whether the webhook is removed; `false` if it was not registered.
+    pub removed: bool,
+}
 
-        let to_wallet: MapProof<PublicKey, Wallet> =
-            currency_schema.wallets().get_proof(query.pub_key);
+/// Executed transfer found by its author and seed.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SeedTransfer {
+    /// Hash of the transfer transaction.
+    pub tx_hash: Hash,
+    /// Height of the block containing the transaction.
+    pub height: Height,
+    /// State of the multisignature transfer. Absent for plain transfers,
+    /// which are complete once executed.
+    pub multisig_state: Option<State>,
+}
 
-        let wallet_proof = WalletProof {
-            to_table,
-            to_wallet,
-        };
+/// Executed transfers signed by the same author with the same seed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeedTransfers {
+    /// List of transfers in the order of their execution.
+    pub transfers: Vec<SeedTransfer>,
+}
 
-        let wallet = currency_schema.wallet(&query.pub_key);
+/// Wallet created within the requested height range.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CreatedWallet {
+    /// Public key of the wallet.
+    pub pub_key: PublicKey,
+    /// Name of the wallet.
+    pub name: String,
+    /// Height of the block at which the wallet was created.
+    pub created_at: Height,
+}
 
-        let explorer = BlockchainExplorer::new(state.blockchain());
+/// Page of the wallets created within the requested height range.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedWallets {
+    /// Total number of wallets created within the range.
+    pub total: u64,
+    /// Wallets in the order of their creation.
+    pub wallets: Vec<CreatedWallet>,
+    /// Cursor of the next page, or `None` if this is the last page.
+    pub next_cursor: Option<String>,
+}
 
-        let wallet_history = wallet.map(|_| {
-            let history = currency_schema.wallet_history(&query.pub_key);
-            let proof = history.get_range_proof(0, history.len());
+/// Page of multisignature transfers in the requested state.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct MultisigList {
+    /// Hashes of the `TransferMultisig` transactions in ascending order.
+    pub tx_hashes: Vec<Hash>,
+    /// Cursor of the next page, or `None` if this is the last page.
+    pub next_cursor: Option<String>,
+}
 
-            let transactions = history
-                .iter()
-                .map(|record| explorer.transaction_without_proof(&record).unwrap())
-                .collect::<Vec<_>>();
+/// Aggregate statistics of the cryptocurrency service.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ServiceStats {
+    /// Total amount of currency in all wallets.
+    pub total_supply: Amount,
+    /// Number of created wallets.
+    pub wallet_count: u64,
+    /// Number of executed transfers, including settled multisignature transfers.
+    pub transfer_count: u64,
+    /// Cumulative amount of currency moved by executed transfers.
+    pub transfer_volume: Amount,
+}
 
-            WalletHistory {
-                proof,
-                transactions,
-            }
-        });
+/// Summaries of a range of blocks together with the proofs linking them to the latest
+/// block. The summaries list holds an entry for every block, so its length is
+/// the height of the latest block plus one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockSummaries {
+    /// Proof of the last block.
+    pub block_proof: BlockProof,
+    /// Proof of the root hash of the summaries list in the service tables.
+    pub to_table: MapProof<Hash, Hash>,
+    /// Proof of the returned summaries. Absent for an empty range.
+    pub proof: Option<ListProof<BlockSummary>>,
+    /// Summaries in the order of the block heights.
+    pub summaries: Vec<BlockSummary>,
+}
 
-        Ok(WalletInfo {
-            block_proof,
-            wallet_proof,
-            wallet_history,
-        })
+/// Number of wallets known to the service.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WalletCount {
+    /// Number of created wallets.
+    pub count: u64,
+}
+
+/// Consistent read-only view of the blockchain state for the API handlers.
+///
+/// The context wraps a single snapshot of the storage, so the core schema, the service
+/// schema and the explorer obtained from it never observe different blocks, even if
+/// a block is committed while a handler is running.
+#[derive(Debug)]
+pub struct ReadContext<'a> {
+    explorer: BlockchainExplorer<'a>,
+}
+
+impl<'a> ReadContext<'a> {
+    /// Captures the current state of the blockchain.
+    pub fn new(blockchain: &'a Blockchain) -> Self {
+        Self {
+            explorer: BlockchainExplorer::new(blockchain),
+        }
     }
 
-    /// Endpoint for getting a list of transaction hashes and block height at
+    /// Returns the captured snapshot.
+    pub fn snapshot(&self) -> &(dyn Snapshot + 'static) {
+        self.explorer.snapshot()
+    }
+
+    /// Returns the core schema over the captured snapshot.
+    pub fn general_schema(&self) -> blockchain::Schema<&(dyn Snapshot + 'static)> {
+        blockchain::Schema::new(self.snapshot())
+    }
+
+    /// Returns the service schema over the captured snapshot.
+    pub fn schema(&self) -> Schema<&(dyn Snapshot + 'static)> {
+        Schema::new(self.snapshot())
+    }
+
+    /// Returns the explorer over the captured snapshot.
+    pub fn explorer(&self) -> &BlockchainExplorer<'a> {
+        &self.explorer
+    }
+
+    /// Returns the height of the latest committed block.
+    pub fn height(&self) -> Height {
+        self.explorer.height()
+    }
+
+    /// Returns the proof of the latest committed block.
+    pub fn latest_block_proof(&self) -> Result<BlockProof, ApiError> {
+        latest_block_proof(&self.general_schema())
+    }
+
+    /// Returns the public key of the wallet with the given identifier. Names which
+    /// do not refer to a wallet fail with `WalletNotFound`, while keys are returned
+    /// whether the wallet exists or not.
+    pub fn resolve_wallet(&self, id: &WalletId) -> Result<PublicKey, ApiError> {
+        self.schema().resolve(id).ok_or_else(|| match id {
+            WalletId::Name(name) => ApiError::wallet_name_not_found(name),
+            WalletId::Key(pub_key) => ApiError::wallet_not_found(pub_key),
+        })
+    }
+
+    /// Returns the proof of the wallet (or of its absence) linked to the state hash
+    /// of the latest committed block.
+    pub fn wallet_proof(&self, pub_key: PublicKey) -> WalletProof {
+        wallet_proof(&self.general_schema(), &self.schema(), pub_key)
+    }
+
+    /// Collects the items of a list returned by the API. Fails with `ListTooLarge` as soon
+    /// as there are more items than `Config::max_list_items`, without reading the rest.
+    pub fn collect_list<I: IntoIterator>(&self, items: I) -> Result<Vec<I::Item>, ApiError> {
+        let max_items = self.schema().config().max_list_items;
+        let mut list = Vec::new();
+        for item in items {
+            if list.len() as u64 == max_items {
+                return Err(ApiError::list_too_large(max_items));
+            }
+            list.push(item);
+        }
+        Ok(list)
+    }
+}
+
+/// Public service API description.
+#[derive(Debug, Clone, Copy)]
+pub struct PublicApi;
+
+impl PublicApi {
+    /// Endpoint for getting a single wallet.
+    pub fn wallet_info(state: &ServiceApiState, query: WalletInfoQuery) -> api::Result<WalletInfo> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        let block_proof = context.latest_block_proof()?;
+        let wallet_proof = context.wallet_proof(pub_key);
+
+        let exists = currency_schema.wallets().contains(&pub_key);
+
+        let limit = query.history_limit.unwrap_or(MAX_ITEMS_PER_REQUEST);
+        if limit > MAX_ITEMS_PER_REQUEST {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Max history entries count per request exceeded ({})",
+                    MAX_ITEMS_PER_REQUEST
+                ),
+            )
+            .into());
+        }
+
+        let wallet_history = if exists && query.with_history && limit > 0 {
+            let total_len = currency_schema
+                .wallet(&pub_key)
+                .map_or(0, |wallet| wallet.history_len);
+            let first_retained = currency_schema.first_retained_history_index(&pub_key);
+            let from = match query.history_offset {
+                Some(from) => from,
+                // The latest entries are returned by default, as far as they are retained.
+                None => cmp::max(total_len.saturating_sub(limit), first_retained),
+            };
+            if from >= total_len {
+                return Err(ApiError::new(
+                    ErrorKind::InvalidQuery,
+                    format!(
+                        "Invalid history offset {}, history length = {}",
+                        from, total_len
+                    ),
+                )
+                .into());
+            }
+            if from < first_retained {
+                return Err(ApiError::history_pruned(first_retained).into());
+            }
+            let to = cmp::min(from + limit, total_len);
+
+            let explorer = context.explorer();
+            let proof = currency_schema.wallet_history_proof(&pub_key, from, to);
+            let transactions = context.collect_list(
+                currency_schema
+                    .wallet_history_from(&pub_key, from)
+                    .take((to - from) as usize)
+                    .map(|record| explorer.transaction_without_proof(&record.tx_hash).unwrap()),
+            )?;
+
+            Some(WalletHistory {
+                proof,
+                transactions,
+                from,
+                total_len,
+            })
+        } else {
+            None
+        };
+
+        let mut info = WalletInfo {
+            block_proof,
+            wallet_proof,
+            exists,
+            wallet_history,
+            pending_outgoing: pending_outgoing(&currency_schema, &pub_key),
+            joint: currency_schema.joint_wallet(&pub_key),
+            verification: None,
+        };
+        if query.verify {
+            info.verification = Some(verify_wallet_info(
+                &context.general_schema(),
+                &info,
+                &pub_key,
+            ));
+        }
+        Ok(info)
+    }
+
+    /// Endpoint for getting the owners of a joint wallet and the spends awaiting
+    /// their signatures.
+    pub fn joint_wallet(
+        state: &ServiceApiState,
+        query: WalletQuery,
+    ) -> api::Result<JointWalletInfo> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        let joint = currency_schema
+            .joint_wallet(&pub_key)
+            .ok_or_else(|| ApiError::wallet_not_found(&pub_key))?;
+        let pending = currency_schema
+            .joint_pending(&pub_key)
+            .iter()
+            .filter_map(|proposal| {
+                currency_schema
+                    .joint_spend(&proposal)
+                    .map(|spend| PendingJointSpend {
+                        proposal,
+                        to: spend.to,
+                        amount: spend.amount,
+                        signed_by: spend
+                            .approved_by
+                            .iter()
+                            .map(|approval| approval.approver)
+                            .collect(),
+                    })
+            })
+            .collect();
+
+        Ok(JointWalletInfo {
+            address: pub_key,
+            owners: joint.owners,
+            threshold: joint.threshold,
+            pending,
+        })
+    }
+
+    /// Endpoint for checking whether a wallet exists. Only the proof
+    /// of the wallet (or of its absence) is returned.
+    pub fn wallet_exists(
+        state: &ServiceApiState,
+        query: WalletQuery,
+    ) -> api::Result<WalletExistence> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        Ok(WalletExistence {
+            exists: currency_schema.wallets().contains(&pub_key),
+            wallet_proof: context.wallet_proof(pub_key),
+        })
+    }
+
+    /// Endpoint for getting the length and the root hash of the wallet history
+    /// together with its latest entry.
+    pub fn wallet_history_head(
+        state: &ServiceApiState,
+        query: WalletQuery,
+    ) -> api::Result<WalletHistoryHead> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        let wallet = currency_schema
+            .wallet(&pub_key)
+            .ok_or_else(|| ApiError::wallet_not_found(&pub_key))?;
+        // The latest record is never pruned.
+        let latest_entry_hash = wallet
+            .history_len
+            .checked_sub(1)
+            .and_then(|index| currency_schema.wallet_history_record(&pub_key, index))
+            .map(|record| record.tx_hash);
+
+        Ok(WalletHistoryHead {
+            history_len: wallet.history_len,
+            history_hash: wallet.history_hash,
+            latest_entry_hash,
+            first_retained: currency_schema.first_retained_history_index(&pub_key),
+        })
+    }
+
+    /// Endpoint for getting the name of a wallet at the given height together with
+    /// the history of its changes.
+    pub fn name_history(
+        state: &ServiceApiState,
+        query: NameHistoryQuery,
+    ) -> api::Result<NameHistory> {
+        let limit = query.limit.unwrap_or(MAX_ITEMS_PER_REQUEST);
+        if limit > MAX_ITEMS_PER_REQUEST {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Max name changes count per request exceeded ({})",
+                    MAX_ITEMS_PER_REQUEST
+                ),
+            )
+            .into());
+        }
+
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let height = query
+            .height
+            .map_or_else(|| context.general_schema().height(), Height);
+        let name = currency_schema
+            .wallet_name_at(&query.pub_key, height)
+            .ok_or_else(|| ApiError::wallet_not_found(&query.pub_key))?;
+
+        let history = currency_schema.name_history(&query.pub_key);
+        let changes = history
+            .iter_from(query.offset)
+            .take(limit as usize)
+            .collect();
+        Ok(NameHistory {
+            name,
+            total_changes: history.len(),
+            changes,
+        })
+    }
+
+    /// Endpoint for getting lifetime transfer totals of a wallet together with its
+    /// largest counterparties.
+    pub fn wallet_summary(
+        state: &ServiceApiState,
+        query: WalletQuery,
+    ) -> api::Result<WalletSummary> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        if currency_schema.wallet(&pub_key).is_none() {
+            return Err(ApiError::wallet_not_found(&pub_key).into());
+        }
+        let activity = currency_schema
+            .wallet_activity(&pub_key)
+            .unwrap_or_default();
+
+        let mut top_counterparties = currency_schema
+            .wallet_counterparties(&pub_key)
+            .iter()
+            .map(|(pub_key, volume)| CounterpartyVolume { pub_key, volume })
+            .collect::<Vec<_>>();
+        // Ties are broken by the public key to keep the response deterministic.
+        top_counterparties.sort_by(|a, b| {
+            b.volume
+                .cmp(&a.volume)
+                .then_with(|| a.pub_key.cmp(&b.pub_key))
+        });
+        top_counterparties.truncate(SUMMARY_TOP_COUNTERPARTIES);
+
+        Ok(WalletSummary {
+            sent: activity.sent,
+            received: activity.received,
+            outgoing_transfers: activity.outgoing_count,
+            incoming_transfers: activity.incoming_count,
+            top_counterparties,
+        })
+    }
+
+    /// Endpoint for getting the balance of a wallet after the block at the given height.
+    /// The balance is restored from the nearest balance checkpoint at or before the height
+    /// by replaying the subsequent wallet history entries.
+    pub fn balance_at(state: &ServiceApiState, query: BalanceAtQuery) -> api::Result<BalanceAt> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        currency_schema
+            .wallet(&pub_key)
+            .ok_or_else(|| ApiError::wallet_not_found(&pub_key))?;
+        let height = Height(query.height);
+        let max_height = context.height();
+        if height > max_height {
+            return Err(ApiError::new(
+                ErrorKind::BlockNotFound,
+                format!("Block at height {} does not exist", query.height),
+            )
+            .with_details(json!({ "max_height": max_height }))
+            .into());
+        }
+
+        let checkpoints = currency_schema.balance_checkpoints(&pub_key);
+        let checkpoint_count = partition_point(checkpoints.len(), |index| {
+            checkpoints
+                .get(index)
+                .map_or(false, |checkpoint| checkpoint.height <= height)
+        });
+        let (mut balance, replay_from) = match checkpoint_count {
+            0 => (Amount::ZERO, 0),
+            count => {
+                let checkpoint = checkpoints.get(count - 1).unwrap();
+                (checkpoint.balance, checkpoint.history_len)
+            }
+        };
+        let first_retained = currency_schema.first_retained_history_index(&pub_key);
+        if replay_from < first_retained {
+            return Err(ApiError::history_pruned(first_retained).into());
+        }
+
+        for record in currency_schema
+            .wallet_history_from(&pub_key, replay_from)
+            .take_while(|record| record.height <= height)
+        {
+            balance = record.apply(balance);
+        }
+
+        Ok(BalanceAt {
+            pub_key,
+            height,
+            balance,
+        })
+    }
+
+    /// Endpoint for getting several wallets at once together with a single proof
+    /// covering all of them.
+    pub fn wallets_batch(
+        state: &ServiceApiState,
+        query: WalletBatchQuery,
+    ) -> api::Result<WalletBatchInfo> {
+        if query.pub_keys.len() > MAX_KEYS_PER_BATCH {
+            return Err(ApiError::new(
+                ErrorKind::TooManyKeys,
+                format!("At most {} keys can be requested", MAX_KEYS_PER_BATCH),
+            )
+            .with_details(json!({
+                "max_keys": MAX_KEYS_PER_BATCH,
+                "requested": query.pub_keys.len(),
+            }))
+            .into());
+        }
+
+        let context = ReadContext::new(state.blockchain());
+        let general_schema = context.general_schema();
+        let currency_schema = context.schema();
+
+        let wallet_proof = WalletProof {
+            to_table: general_schema.get_proof_to_service_table(CRYPTOCURRENCY_SERVICE_ID, 0),
+            to_wallet: currency_schema.wallets_multiproof(&query.pub_keys),
+        };
+
+        let entries = query
+            .pub_keys
+            .into_iter()
+            .map(|pub_key| match currency_schema.wallet(&pub_key) {
+                Some(wallet) => BatchWalletEntry::Found {
+                    pending_outgoing: pending_outgoing(&currency_schema, &pub_key),
+                    wallet,
+                },
+                None => BatchWalletEntry::Missing { pub_key },
+            })
+            .collect();
+
+        Ok(WalletBatchInfo {
+            wallet_proof,
+            wallets: entries,
+        })
+    }
+
+    /// Endpoint for getting the total balance of several wallets. Duplicate keys
+    /// are counted once.
+    pub fn balance_sum(
+        state: &ServiceApiState,
+        query: WalletBatchQuery,
+    ) -> api::Result<BalanceSum> {
+        if query.pub_keys.len() > MAX_KEYS_PER_BALANCE_SUM {
+            return Err(ApiError::new(
+                ErrorKind::TooManyKeys,
+                format!("At most {} keys can be requested", MAX_KEYS_PER_BALANCE_SUM),
+            )
+            .with_details(json!({
+                "max_keys": MAX_KEYS_PER_BALANCE_SUM,
+                "requested": query.pub_keys.len(),
+            }))
+            .into());
+        }
+
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+
+        let mut seen = HashSet::new();
+        let mut sum = BalanceSum {
+            total: Amount::ZERO,
+            balances: Vec::new(),
+            missing: Vec::new(),
+        };
+        for pub_key in query.pub_keys {
+            if !seen.insert(pub_key) {
+                continue;
+            }
+            match currency_schema.wallet(&pub_key) {
+                Some(wallet) => {
+                    sum.total = sum.total.saturating_add(wallet.balance);
+                    sum.balances.push(WalletBalance {
+                        pub_key,
+                        balance: wallet.balance,
+                        last_tx_hash: wallet.last_tx_hash,
+                    });
+                }
+                None => sum.missing.push(pub_key),
+            }
+        }
+        Ok(sum)
+    }
+
+    /// Endpoint for getting the `[from, to)` range of the wallet history together with
+    /// the proof which can be checked against the wallet's `history_hash`. At most
+    /// `MAX_ITEMS_PER_REQUEST` entries are returned; the rest of the range is requested
+    /// from `next_index`.
+    pub fn wallet_history_range(
+        state: &ServiceApiState,
+        query: WalletHistoryRangeQuery,
+    ) -> api::Result<WalletHistoryRange> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        let wallet = currency_schema
+            .wallet(&pub_key)
+            .ok_or_else(|| ApiError::wallet_not_found(&pub_key))?;
+
+        if query.from > query.to || query.to > wallet.history_len {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Invalid history range [{}, {}), history length = {}",
+                    query.from, query.to, wallet.history_len
+                ),
+            )
+            .into());
+        }
+
+        let to = cmp::min(query.to, query.from + MAX_ITEMS_PER_REQUEST);
+        let proof = if query.from < to {
+            let first_retained = currency_schema.first_retained_history_index(&pub_key);
+            if query.from < first_retained {
+                return Err(ApiError::history_pruned(first_retained).into());
+            }
+            Some(currency_schema.wallet_history_proof(&pub_key, query.from, to))
+        } else {
+            None
+        };
+
+        let explorer = context.explorer();
+        let transactions = context.collect_list(
+            currency_schema
+                .wallet_history_from(&pub_key, query.from)
+                .take((to - query.from) as usize)
+                .map(|record| explorer.transaction_without_proof(&record.tx_hash).unwrap()),
+        )?;
+
+        Ok(WalletHistoryRange {
+            block_proof: context.latest_block_proof()?,
+            wallet_proof: context.wallet_proof(pub_key),
+            proof,
+            transactions,
+            next_index: if to < query.to { Some(to) } else { None },
+        })
+    }
+
+    /// Endpoint for getting the wallet history entries starting from the given index
+    /// together with the proof which can be checked against the wallet's `history_hash`.
+    /// At most `MAX_ITEMS_PER_REQUEST` entries are returned; the rest are requested
+    /// from `next_index`.
+    pub fn wallet_history_since(
+        state: &ServiceApiState,
+        query: WalletHistorySinceQuery,
+    ) -> api::Result<WalletHistorySince> {
+        let context = ReadContext::new(state.blockchain());
+        let general_schema = context.general_schema();
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        let wallet = currency_schema
+            .wallet(&pub_key)
+            .ok_or_else(|| ApiError::wallet_not_found(&pub_key))?;
+
+        let len = wallet.history_len;
+        if query.from_index > len {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Invalid history index {}, history length = {}",
+                    query.from_index, len
+                ),
+            )
+            .into());
+        }
+
+        let to = cmp::min(len, query.from_index + MAX_ITEMS_PER_REQUEST);
+        let proof = if query.from_index < to {
+            let first_retained = currency_schema.first_retained_history_index(&pub_key);
+            if query.from_index < first_retained {
+                return Err(ApiError::history_pruned(first_retained).into());
+            }
+            Some(currency_schema.wallet_history_proof(&pub_key, query.from_index, to))
+        } else {
+            None
+        };
+
+        let explorer = context.explorer();
+        let records = context.collect_list(
+            currency_schema
+                .wallet_history_from(&pub_key, query.from_index)
+                .take((to - query.from_index) as usize),
+        )?;
+        let transactions = records
+            .iter()
+            .map(|record| explorer.transaction_without_proof(&record.tx_hash).unwrap())
+            .collect::<Vec<_>>();
+        let summaries = transactions
+            .iter()
+            .zip(&records)
+            .map(|(transaction, record)| {
+                simple_transaction_info(
+                    &general_schema,
+                    &pub_key,
+                    transaction.message(),
+                    record.height,
+                )
+            })
+            .collect();
+
+        Ok(WalletHistorySince {
+            block_proof: context.latest_block_proof()?,
+            wallet_proof: context.wallet_proof(pub_key),
+            proof,
+            transactions,
+            summaries,
+            next_index: if to < len { Some(to) } else { None },
+        })
+    }
+
+    /// Endpoint for getting a list of transaction hashes and block height at
     /// which they've been committed for a single wallet identified by public
     /// key.
     pub fn simple_wallet_info(
         state: &ServiceApiState,
-        query: WalletQuery,
+        query: SimpleWalletInfoQuery,
     ) -> api::Result<SimpleWalletInfo> {
-        let snapshot = state.snapshot();
-        let currency_schema = Schema::new(&snapshot);
-
-        // Check if wallet exists.
-        let _wallet = currency_schema.wallet(&query.pub_key).ok_or_else(|| {
-            api::error::Error::NotFound(format!(
-                "Wallet with public key = {} is not found",
-                query.pub_key
-            ))
-        })?;
+        let context = ReadContext::new(state.blockchain());
+        let general_schema = context.general_schema();
+        let currency_schema = context.schema();
+        let pub_key = context.resolve_wallet(&query.id)?;
+
+        let wallet = currency_schema
+            .wallet(&pub_key)
+            .ok_or_else(|| ApiError::wallet_not_found(&pub_key))?;
+
+        let len = wallet.history_len;
+        let first_retained = currency_schema.first_retained_history_index(&pub_key);
+        let limit = query.limit.unwrap_or(len) as usize;
+        let records: Vec<HistoryRecord> = match query.order {
+            HistoryOrder::Asc => currency_schema
+                .wallet_history_from(&pub_key, cmp::max(query.offset, first_retained))
+                .take(limit)
+                .collect(),
+            // Walk the history from its tail so that only the requested entries are read.
+            HistoryOrder::Desc => (first_retained..len.saturating_sub(query.offset))
+                .rev()
+                .take(limit)
+                .filter_map(|index| currency_schema.wallet_history_record(&pub_key, index))
+                .collect(),
+        };
+
+        // Heights are taken from the history records, so only the transactions
+        // themselves are looked up.
+        let messages = general_schema.transactions();
+        let transactions = records
+            .into_iter()
+            .filter_map(|record| {
+                let message = messages.get(&record.tx_hash)?;
+                Some(simple_transaction_info(
+                    &general_schema,
+                    &pub_key,
+                    &message,
+                    record.height,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(SimpleWalletInfo {
+            transactions,
+            first_retained,
+        })
+    }
+
+    /// Endpoint for getting multisignature transfers created by the wallet,
+    /// optionally filtered by their state.
+    pub fn outgoing_multisig_transfers(
+        state: &ServiceApiState,
+        query: OutgoingMultisigQuery,
+    ) -> api::Result<OutgoingMultisigTransfers> {
+        let limit = query.limit.unwrap_or(MAX_ITEMS_PER_REQUEST);
+        if limit > MAX_ITEMS_PER_REQUEST {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Max transfers count per request exceeded ({})",
+                    MAX_ITEMS_PER_REQUEST
+                ),
+            )
+            .into());
+        }
 
-        let explorer = BlockchainExplorer::new(state.blockchain());
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
 
-        let history = currency_schema.wallet_history(&query.pub_key);
-        let transactions = history
+        let transfers = currency_schema
+            .outgoing_multisig_transfers(&query.pub_key)
             .iter()
-            .filter_map(|hash| match explorer.transaction(&hash) {
-                Some(TransactionInfo::Committed(transaction)) => Some(SimpleTransactionInfo {
-                    height: transaction.location().block_height(),
-                    hash,
-                }),
-                _ => None,
+            .filter_map(|tx_hash| {
+                currency_schema
+                    .multisig_transfer(tx_hash)
+                    .map(|transfer| (tx_hash, transfer))
+            })
+            .filter(|(_, transfer)| {
+                query
+                    .state
+                    .as_ref()
+                    .map_or(true, |state| transfer.state == *state)
+            })
+            .skip(query.offset as usize)
+            .take(limit as usize)
+            .map(|(tx_hash, transfer)| OutgoingMultisigTransfer {
+                tx_hash,
+                to: transfer.to,
+                amount: transfer.amount,
+                approvals: transfer.approvals_count() as u64,
+                state: transfer.state,
+            })
+            .collect();
+
+        Ok(OutgoingMultisigTransfers { transfers })
+    }
+
+    /// Endpoint for getting the rejected and expired multisignature transfers
+    /// with the wallet as the sender, the receiver or one of the approvers.
+    pub fn multisig_failures(
+        state: &ServiceApiState,
+        query: MultisigFailuresQuery,
+    ) -> api::Result<MultisigFailures> {
+        let limit = query.limit.unwrap_or(MAX_ITEMS_PER_REQUEST);
+        if limit > MAX_ITEMS_PER_REQUEST {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Max transfers count per request exceeded ({})",
+                    MAX_ITEMS_PER_REQUEST
+                ),
+            )
+            .into());
+        }
+
+        let context = ReadContext::new(state.blockchain());
+        let transfers = context
+            .schema()
+            .multisig_failures(&query.pub_key)
+            .into_iter()
+            .skip(query.offset as usize)
+            .take(limit as usize)
+            .map(|(tx_hash, transfer)| MultisigFailure {
+                tx_hash,
+                from: transfer.from,
+                to: transfer.to,
+                amount: transfer.amount,
+                rejected_by: transfer.rejection().map(|(rejected_by, _)| rejected_by),
+                state: transfer.state,
+                settled_at_height: transfer.settled_at_height,
+                expires_at: transfer.expires_at,
+            })
+            .collect();
+
+        Ok(MultisigFailures { transfers })
+    }
+
+    /// Endpoint for listing multisignature transfers in the given state.
+    pub fn multisig_list(
+        state: &ServiceApiState,
+        query: MultisigListQuery,
+    ) -> api::Result<MultisigList> {
+        let limit = query.limit.unwrap_or(MAX_ITEMS_PER_REQUEST);
+        if limit > MAX_ITEMS_PER_REQUEST {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Max transfers count per request exceeded ({})",
+                    MAX_ITEMS_PER_REQUEST
+                ),
+            )
+            .into());
+        }
+
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+
+        let after = match query.cursor {
+            Some(ref cursor) => {
+                let after = decode_cursor(cursor, Hash::from_slice)?;
+                // The transfer may have been collected since the previous page was
+                // returned; the listing then continues from its tombstone.
+                if !currency_schema.multisig_transfers().contains(&after)
+                    && !currency_schema.multisig_tombstones().contains(&after)
+                {
+                    return Err(ApiError::invalid_cursor(cursor).into());
+                }
+                Some(after)
+            }
+            None => None,
+        };
+        // One more transfer is taken to find out whether there is a next page.
+        let mut tx_hashes = currency_schema.multisig_transfers_by_state_range(
+            &query.state,
+            after.as_ref(),
+            limit as usize + 1,
+        );
+        let next_cursor = if tx_hashes.len() as u64 > limit {
+            tx_hashes.truncate(limit as usize);
+            tx_hashes
+                .last()
+                .map(|tx_hash| encode_cursor(tx_hash.as_ref()))
+        } else {
+            None
+        };
+        Ok(MultisigList {
+            tx_hashes,
+            next_cursor,
+        })
+    }
+
+    /// Endpoint for getting the proof of the multisignature transfer (or of its absence)
+    /// linked to the latest block.
+    pub fn multisig_proof(
+        state: &ServiceApiState,
+        query: MultisigTransferQuery,
+    ) -> api::Result<MultisigTransferInfo> {
+        let context = ReadContext::new(state.blockchain());
+        let general_schema = context.general_schema();
+        let currency_schema = context.schema();
+
+        let multisig_proof = MultisigTransferProof {
+            to_table: general_schema.get_proof_to_service_table(CRYPTOCURRENCY_SERVICE_ID, 1),
+            to_transfer: currency_schema
+                .multisig_transfers()
+                .get_proof(query.tx_hash),
+        };
+
+        Ok(MultisigTransferInfo {
+            block_proof: context.latest_block_proof()?,
+            multisig_proof,
+        })
+    }
+
+    /// Endpoint for looking up executed transfers by their author and seed. Several
+    /// transfers are returned if the seed has been reused for different payloads.
+    pub fn transfers_by_seed(
+        state: &ServiceApiState,
+        query: TransferSeedQuery,
+    ) -> api::Result<SeedTransfers> {
+        let context = ReadContext::new(state.blockchain());
+        let general_schema = context.general_schema();
+        let currency_schema = context.schema();
+        let locations = general_schema.transactions_locations();
+
+        let transfers = context
+            .collect_list(
+                currency_schema
+                    .seed_transfers(&query.pub_key, query.seed)
+                    .iter(),
+            )?
+            .into_iter()
+            .map(|tx_hash| {
+                let location = locations.get(&tx_hash).ok_or_else(|| {
+                    ApiError::new(
+                        ErrorKind::Internal,
+                        format!("Transaction {} is not committed", tx_hash),
+                    )
+                })?;
+
+                Ok(SeedTransfer {
+                    tx_hash,
+                    height: location.block_height(),
+                    multisig_state: currency_schema
+                        .multisig_transfer(tx_hash)
+                        .map(|transfer| transfer.state),
+                })
+            })
+            .collect::<api::Result<Vec<_>>>()?;
+
+        Ok(SeedTransfers { transfers })
+    }
+
+    /// Endpoint for getting wallets created within the given height range.
+    pub fn created_wallets(
+        state: &ServiceApiState,
+        query: CreatedWalletsQuery,
+    ) -> api::Result<CreatedWallets> {
+        let limit = query.limit.unwrap_or(MAX_ITEMS_PER_REQUEST);
+        if limit > MAX_ITEMS_PER_REQUEST {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Max wallets count per request exceeded ({})",
+                    MAX_ITEMS_PER_REQUEST
+                ),
+            )
+            .into());
+        }
+        let to_height = query.to_height.unwrap_or(u64::max_value());
+        if query.from_height > to_height {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Invalid height range [{}, {}]",
+                    query.from_height, to_height
+                ),
+            )
+            .into());
+        }
+
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+        let wallets = currency_schema.wallets();
+        let created = currency_schema.wallets_by_creation();
+
+        let wallet_at = |index| {
+            created
+                .get(index)
+                .and_then(|pub_key| wallets.get(&pub_key))
+                .expect("Wallet from the creation list is missing")
+        };
+        // Creation heights are non-decreasing along the list.
+        let start = partition_point(created.len(), |index| {
+            wallet_at(index).created_at.0 < query.from_height
+        });
+        let end = partition_point(created.len(), |index| {
+            wallet_at(index).created_at.0 <= to_height
+        });
+
+        let from = match query.cursor {
+            Some(ref cursor) => {
+                let invalid = || api::error::Error::from(ApiError::invalid_cursor(cursor));
+                let last_key = decode_cursor(cursor, PublicKey::from_slice)?;
+                let last_wallet = wallets.get(&last_key).ok_or_else(invalid)?;
+                let created_at = last_wallet.created_at.0;
+                if created_at < query.from_height || created_at > to_height {
+                    return Err(invalid());
+                }
+                // Look for the wallet among the wallets created in the same block.
+                let block_start = partition_point(created.len(), |index| {
+                    wallet_at(index).created_at.0 < created_at
+                });
+                let block_end = partition_point(created.len(), |index| {
+                    wallet_at(index).created_at.0 <= created_at
+                });
+                let position = created
+                    .iter_from(block_start)
+                    .take((block_end - block_start) as usize)
+                    .position(|pub_key| pub_key == last_key)
+                    .ok_or_else(invalid)?;
+                block_start + position as u64 + 1
+            }
+            None => start,
+        };
+        let to = cmp::min(from + limit, end);
+        let page = created
+            .iter_from(from)
+            .take((to - from) as usize)
+            .map(|pub_key| {
+                let wallet = wallets
+                    .get(&pub_key)
+                    .expect("Wallet from the creation list is missing");
+                CreatedWallet {
+                    pub_key: wallet.pub_key,
+                    name: wallet.name,
+                    created_at: wallet.created_at,
+                }
             })
             .collect::<Vec<_>>();
+        let next_cursor = if to < end {
+            page.last()
+                .map(|wallet| encode_cursor(wallet.pub_key.as_ref()))
+        } else {
+            None
+        };
+
+        Ok(CreatedWallets {
+            total: end - start,
+            wallets: page,
+            next_cursor,
+        })
+    }
+
+    /// Endpoint for getting the wallets created or updated by the transaction.
+    pub fn affected_wallets(
+        state: &ServiceApiState,
+        query: AffectedWalletsQuery,
+    ) -> api::Result<AffectedWallets> {
+        let context = ReadContext::new(state.blockchain());
+        let wallets =
+            context.collect_list(context.schema().affected_wallets(&query.tx_hash).iter())?;
+        Ok(AffectedWallets { wallets })
+    }
+
+    /// Endpoint for getting the outcomes of the approvals carried by the batch approval.
+    pub fn approval_batch_results(
+        state: &ServiceApiState,
+        query: ApprovalBatchQuery,
+    ) -> api::Result<ApprovalBatchResults> {
+        let context = ReadContext::new(state.blockchain());
+        let results = context.collect_list(
+            context
+                .schema()
+                .approval_batch_results(&query.tx_hash)
+                .iter(),
+        )?;
+        Ok(ApprovalBatchResults { results })
+    }
+
+    /// Endpoint for getting the transactions of the service committed in the given block.
+    pub fn block_activity(
+        state: &ServiceApiState,
+        query: BlockActivityQuery,
+    ) -> api::Result<BlockActivity> {
+        let context = ReadContext::new(state.blockchain());
+        let general_schema = context.general_schema();
+
+        let explorer = context.explorer();
+        let height = Height(query.height);
+        let block = explorer.block(height).ok_or_else(|| {
+            ApiError::new(
+                ErrorKind::BlockNotFound,
+                format!("Block at height {} does not exist", query.height),
+            )
+            .with_details(json!({ "max_height": context.height() }))
+        })?;
+
+        let transactions = block.iter().filter_map(|transaction| {
+            let message = transaction.content().message();
+            let tx = service_tx(message)?;
+            let (amount, receiver) = match tx {
+                WalletTransactions::Transfer(ref tx) => (Some(tx.amount), Some(tx.to)),
+                WalletTransactions::TransferMultisig(ref tx) => (Some(tx.amount), Some(tx.to)),
+                WalletTransactions::Issue(ref tx) => (Some(tx.amount), Some(message.author())),
+                WalletTransactions::IssueTo(ref tx) => (Some(tx.amount), Some(tx.to)),
+                WalletTransactions::CreateWallet(_) => (None, None),
+                WalletTransactions::ApproveTransferMultisig(ref tx) => {
+                    multisig_amount_and_receiver(&general_schema, tx.transfer_hash())
+                }
+                WalletTransactions::RejectTransferMultisig(ref tx) => {
+                    multisig_amount_and_receiver(&general_schema, tx.transfer_hash())
+                }
+                WalletTransactions::CreateJointWallet(_) => (None, None),
+                WalletTransactions::ProposeJointSpend(ref tx) => (Some(tx.amount), Some(tx.to)),
+                WalletTransactions::ApproveJointSpend(ref tx) => {
+                    propose_joint_spend_tx(&general_schema, &tx.proposal)
+                        .map(|(_, tx)| (Some(tx.amount), Some(tx.to)))
+                        .unwrap_or((None, None))
+                }
+                WalletTransactions::CreateWalletFor(ref tx) => (None, Some(tx.owner)),
+                WalletTransactions::OpenStream(ref tx) => (Some(tx.max_total), Some(tx.to)),
+                // The amount paid on closing depends on the height, so it is not shown.
+                WalletTransactions::CloseStream(ref tx) => (
+                    None,
+                    open_stream_tx(&general_schema, &tx.stream).map(|(_, tx)| tx.to),
+                ),
+                WalletTransactions::Stake(ref tx) => (Some(tx.amount), None),
+                WalletTransactions::Unstake(ref tx) => (Some(tx.amount), None),
+                // The claimed rewards are known only once the transaction is executed.
+                WalletTransactions::ClaimRewards(_) => (None, None),
+                WalletTransactions::ApproveTransferMultisigBatch(ref tx) => {
+                    multisig_amount_and_receiver(&general_schema, &tx.tx_hash)
+                }
+                WalletTransactions::SetOverdraft(ref tx) => (None, Some(tx.wallet)),
+                WalletTransactions::SetCashback(_) | WalletTransactions::RenameWallet(_) => {
+                    (None, None)
+                }
+                // Clearings have many receivers, so only the total amount is shown.
+                WalletTransactions::Clearing(ref tx) => {
+                    let total = tx
+                        .legs
+                        .iter()
+                        .fold(Amount::ZERO, |total, leg| total.saturating_add(leg.amount));
+                    (Some(total), None)
+                }
+            };
+
+            Some(ServiceTransactionActivity {
+                tx_hash: message.hash(),
+                tx_type: tx.name().to_owned(),
+                author: message.author(),
+                amount,
+                receiver,
+                status: ExecutionStatus::from(transaction.status()),
+            })
+        });
+        let transactions = context.collect_list(transactions)?;
+
+        Ok(BlockActivity {
+            height,
+            transactions,
+        })
+    }
+
+    /// Endpoint for getting everything an approver needs to approve the multisignature
+    /// transfer.
+    pub fn multisig_template(
+        state: &ServiceApiState,
+        query: MultisigTransferQuery,
+    ) -> api::Result<MultisigTemplate> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+
+        let not_found = || {
+            ApiError::new(
+                ErrorKind::MultisigTransferNotFound,
+                format!(
+                    "Multisignature transfer {} is not found",
+                    query.tx_hash.to_hex()
+                ),
+            )
+        };
+        let transfer = currency_schema
+            .multisig_transfer(query.tx_hash)
+            .ok_or_else(not_found)?;
+
+        let pending_approvers = transfer
+            .approvers
+            .iter()
+            .filter(|approver| !transfer.is_approved_by(approver))
+            .cloned()
+            .collect();
+        let awaiting_approval = !transfer.is_terminal();
+        let progress = transfer.progress();
+        let rejection = transfer.rejection();
+        let rejection_comment = Some(transfer.rejection_comment)
+            .filter(|comment| rejection.is_some() && !comment.is_empty());
+
+        Ok(MultisigTemplate {
+            tx_hash: query.tx_hash,
+            from: transfer.from,
+            to: transfer.to,
+            amount: transfer.amount,
+            approvers: transfer.approvers,
+            pending_approvers,
+            progress,
+            approved_by: transfer.approved_by,
+            state: transfer.state,
+            rejected_by: rejection.map(|(rejecter, _)| rejecter),
+            rejected_at_height: rejection.map(|(_, height)| height),
+            rejection_comment,
+            awaiting_approval,
+            approval: if awaiting_approval {
+                Some(ApprovalTemplate::new(query.tx_hash))
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Endpoint for getting the catalog of error codes emitted by the service transactions.
+    pub fn error_codes(_state: &ServiceApiState, _query: ()) -> api::Result<Vec<ErrorCodeInfo>> {
+        Ok(transactions::Error::ALL
+            .iter()
+            .cloned()
+            .map(ErrorCodeInfo::from)
+            .collect())
+    }
+
+    /// Endpoint for getting aggregate statistics of the service.
+    pub fn stats(state: &ServiceApiState, _query: ()) -> api::Result<ServiceStats> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+
+        Ok(ServiceStats {
+            total_supply: currency_schema.total_supply(),
+            wallet_count: currency_schema.wallet_count(),
+            transfer_count: currency_schema.transfer_count(),
+            transfer_volume: currency_schema.transfer_volume(),
+        })
+    }
+
+    /// Endpoint for getting the summaries of the blocks in the given range of heights
+    /// together with the proof of the range.
+    pub fn block_summaries(
+        state: &ServiceApiState,
+        query: BlockSummariesQuery,
+    ) -> api::Result<BlockSummaries> {
+        let context = ReadContext::new(state.blockchain());
+        let general_schema = context.general_schema();
+        let currency_schema = context.schema();
+        let summaries = currency_schema.block_summaries();
+
+        if query.from > query.to || query.to > summaries.len() {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Invalid block range [{}, {}), latest height = {}",
+                    query.from,
+                    query.to,
+                    context.height()
+                ),
+            )
+            .into());
+        }
+        if query.to - query.from > MAX_ITEMS_PER_REQUEST {
+            return Err(ApiError::new(
+                ErrorKind::InvalidQuery,
+                format!(
+                    "Max blocks count per request exceeded ({})",
+                    MAX_ITEMS_PER_REQUEST
+                ),
+            )
+            .into());
+        }
+
+        let proof = if query.from < query.to {
+            Some(summaries.get_range_proof(query.from, query.to))
+        } else {
+            None
+        };
+        Ok(BlockSummaries {
+            block_proof: context.latest_block_proof()?,
+            to_table: general_schema
+                .get_proof_to_service_table(CRYPTOCURRENCY_SERVICE_ID, BLOCK_SUMMARIES_TABLE_INDEX),
+            proof,
+            summaries: summaries
+                .iter_from(query.from)
+                .take((query.to - query.from) as usize)
+                .collect(),
+        })
+    }
+
+    /// Endpoint for getting the number of created wallets.
+    pub fn wallet_count(state: &ServiceApiState, _query: ()) -> api::Result<WalletCount> {
+        let context = ReadContext::new(state.blockchain());
+        let currency_schema = context.schema();
+
+        Ok(WalletCount {
+            count: currency_schema.wallet_count(),
+        })
+    }
 
-        Ok(SimpleWalletInfo { transactions })
+    /// Endpoint for submitting a signed transaction of the service to the pool
+    /// of unconfirmed transactions. Unlike the explorer endpoint, the transaction
+    /// is checked to belong to the service before it is broadcast.
+    pub fn submit_transaction(
+        state: &ServiceApiState,
+        query: TransactionHex,
+    ) -> api::Result<SubmittedTransaction> {
+        let (signed, tx) = decode_service_tx(&query.tx_body)?;
+        let response = SubmittedTransaction {
+            tx_hash: signed.hash(),
+            tx_type: tx.name().to_owned(),
+        };
+        state.sender().broadcast_transaction(signed)?;
+        Ok(response)
     }
 
-    /// Wires the above endpoint to public scope of the given `ServiceApiBuilder`.
+    /// Endpoint for executing a signed transaction of the service against a throwaway
+    /// fork of the current state. Nothing is persisted and the transaction is not
+    /// broadcast.
+    pub fn dry_run_transaction(
+        state: &ServiceApiState,
+        query: TransactionHex,
+    ) -> api::Result<DryRunResult> {
+        let (signed, tx) = decode_service_tx(&query.tx_body)?;
+        let tx_type = tx.name().to_owned();
+        let tx: Box<dyn Transaction> = tx.into();
+
+        let mut fork = state.blockchain().fork();
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            tx.execute(TransactionContext::new(&mut fork, &signed))
+        }));
+        let status = match result {
+            Ok(result) => {
+                ExecutionStatus::from(&TransactionResult(result.map_err(TransactionError::from)))
+            }
+            Err(panic) => ExecutionStatus::Panic {
+                description: panic_description(&panic),
+            },
+        };
+
+        Ok(DryRunResult {
+            tx_hash: signed.hash(),
+            tx_type,
+            status,
+        })
+    }
+
+    /// Wires the above endpoints to public scope of the given `ServiceApiBuilder`.
     pub fn wire(builder: &mut ServiceApiBuilder) {
         builder
             .public_scope()
-            .endpoint("v1/wallets/info", Self::wallet_info)
-            .endpoint("v1/wallets/info/simple", Self::simple_wallet_info);
+            .endpoint_mut("v1/wallets/info/batch", Self::wallets_batch)
+            .endpoint_mut("v1/wallets/balance/sum", Self::balance_sum)
+            .endpoint_mut("v1/transactions", Self::submit_transaction)
+            .endpoint_mut("v1/transactions/dry_run", Self::dry_run_transaction);
+
+        let scope = builder.public_scope();
+        query_endpoint(scope, "v1/blocks/activity", Self::block_activity);
+        query_endpoint(scope, "v1/wallets/balance_at", Self::balance_at);
+        query_endpoint(scope, "v1/wallets/count", Self::wallet_count);
+        query_endpoint(scope, "v1/wallets/created", Self::created_wallets);
+        query_endpoint(scope, "v1/wallets/exists", Self::wallet_exists);
+        query_endpoint(scope, "v1/wallets/history/head", Self::wallet_history_head);
+        query_endpoint(scope, "v1/wallets/joint", Self::joint_wallet);
+        query_endpoint(
+            scope,
+            "v1/wallets/history/range",
+            Self::wallet_history_range,
+        );
+        query_endpoint(
+            scope,
+            "v1/wallets/history/since",
+            Self::wallet_history_since,
+        );
+        query_endpoint(scope, "v1/wallets/summary", Self::wallet_summary);
+        query_endpoint(scope, "v1/wallets/name_history", Self::name_history);
+        query_endpoint(scope, "v1/errors", Self::error_codes);
+        query_endpoint(scope, "v1/multisig/list", Self::multisig_list);
+        query_endpoint(
+            scope,
+            "v1/multisig/outgoing",
+            Self::outgoing_multisig_transfers,
+        );
+        query_endpoint(scope, "v1/multisig/failures", Self::multisig_failures);
+        query_endpoint(scope, "v1/multisig/proof", Self::multisig_proof);
+        query_endpoint(
+            scope,
+            "v1/multisig/batch_results",
+            Self::approval_batch_results,
+        );
+        query_endpoint(scope, "v1/multisig/template", Self::multisig_template);
+        query_endpoint(scope, "v1/transfers/by_seed", Self::transfers_by_seed);
+        query_endpoint(scope, "v1/stats", Self::stats);
+        query_endpoint(scope, "v1/stats/blocks", Self::block_summaries);
+        query_endpoint(scope, "v1/transactions/affected", Self::affected_wallets);
+        negotiated_endpoint(
+            scope,
+            "v1/wallets/info",
+            |query: &WalletInfoQuery| &query.id,
+            Self::wallet_info,
+        );
+        negotiated_endpoint(
+            scope,
+            "v1/wallets/info/simple",
+            |query: &SimpleWalletInfoQuery| &query.id,
+            Self::simple_wallet_info,
+        );
+    }
+
+    /// Wires the server-sent events stream of committed transactions to public scope
+    /// of the given `ServiceApiBuilder`. Every event is a `TransactionEvent` encoded
+    /// as JSON in the `data` field.
+    pub fn wire_transaction_stream(builder: &mut ServiceApiBuilder, stream: TransactionStream) {
+        let events = move |_request: HttpRequest| -> FutureResponse {
+            let body = stream
+                .subscribe()
+                .map(|event| {
+                    let data = serde_json::to_string(&event).expect("Unable to serialize event");
+                    Bytes::from(format!("data: {}\n\n", data))
+                })
+                .map_err(|()| actix_web::error::ErrorInternalServerError("Stream is closed"));
+            let response = HttpResponse::Ok()
+                .content_type(EVENT_STREAM_CONTENT_TYPE)
+                .header(header::CACHE_CONTROL, "no-cache")
+                .streaming(body);
+            Box::new(Ok(response).into_future())
+        };
+        builder
+            .public_scope()
+            .web_backend()
+            .raw_handler(RequestHandler {
+                name: "v1/stream/transactions".to_owned(),
+                method: Method::GET,
+                inner: Arc::new(events) as Arc<RawHandler>,
+            });
+    }
+}
+
+/// Response which can be encoded as a protobuf message.
+pub trait ProtobufResponse {
+    /// Protobuf message the response is encoded into.
+    type ProtoStruct: ProtobufMessage;
+
+    /// Converts the response into its protobuf representation.
+    fn to_protobuf(&self) -> Self::ProtoStruct;
+}
+
+impl ProtobufResponse for SimpleTransactionInfo {
+    type ProtoStruct = proto::SimpleTransactionInfo;
+
+    fn to_protobuf(&self) -> proto::SimpleTransactionInfo {
+        let mut pb = proto::SimpleTransactionInfo::new();
+        pb.set_hash(self.hash.to_pb());
+        pb.set_height(self.height.0);
+        if let Some(ref tx_type) = self.tx_type {
+            pb.set_tx_type(tx_type.clone());
+        }
+        if let Some(amount) = self.amount {
+            match amount.to_pb_fields() {
+                (_, Some(exact_amount)) => pb.set_exact_amount(exact_amount),
+                (amount, None) => pb.set_amount(amount),
+            }
+        }
+        if let Some(ref counterparty) = self.counterparty {
+            pb.set_counterparty(counterparty.to_pb());
+        }
+        pb
+    }
+}
+
+impl ProtobufResponse for SimpleWalletInfo {
+    type ProtoStruct = proto::SimpleWalletInfo;
+
+    fn to_protobuf(&self) -> proto::SimpleWalletInfo {
+        let transactions = self
+            .transactions
+            .iter()
+            .map(ProtobufResponse::to_protobuf)
+            .collect::<Vec<_>>();
+
+        let mut pb = proto::SimpleWalletInfo::new();
+        pb.set_transactions(transactions.into());
+        pb.set_first_retained(self.first_retained);
+        pb
+    }
+}
+
+impl ProtobufResponse for WalletInfo {
+    type ProtoStruct = proto::WalletInfo;
+
+    fn to_protobuf(&self) -> proto::WalletInfo {
+        let mut pb = proto::WalletInfo::new();
+        let wallet = self
+            .wallet_proof
+            .to_wallet
+            .all_entries_unchecked()
+            .find_map(|(_, wallet)| wallet);
+        if let Some(wallet) = wallet {
+            pb.set_wallet(wallet.to_pb());
+        }
+        pb.set_exists(self.exists);
+        if let Some(ref history) = self.wallet_history {
+            let transactions = history
+                .transactions
+                .iter()
+                .map(|tx| tx.message().clone().serialize())
+                .collect::<Vec<_>>();
+            pb.set_transactions(transactions.into());
+        }
+        pb.set_pending_outgoing(self.pending_outgoing.to_protobuf());
+        if let Some(ref joint) = self.joint {
+            pb.set_joint(joint.to_pb());
+        }
+        if let Some(ref verification) = self.verification {
+            pb.set_verification(verification.to_protobuf());
+        }
+        pb
+    }
+}
+
+impl ProtobufResponse for ProofVerification {
+    type ProtoStruct = proto::ProofVerification;
+
+    fn to_protobuf(&self) -> proto::ProofVerification {
+        let mut pb = proto::ProofVerification::new();
+        pb.set_ok(self.ok);
+        pb.set_checked_against_block(self.checked_against_block.to_pb());
+        pb.set_validators_signed(self.validators_signed);
+        if let Some(ref error) = self.error {
+            pb.set_error(error.clone());
+        }
+        pb
+    }
+}
+
+impl ProtobufResponse for PendingOutgoing {
+    type ProtoStruct = proto::PendingOutgoing;
+
+    fn to_protobuf(&self) -> proto::PendingOutgoing {
+        let transfers = self
+            .transfers
+            .iter()
+            .map(|transfer| {
+                let mut pb = proto::PendingTransfer::new();
+                pb.set_tx_hash(transfer.tx_hash.to_pb());
+                let (amount, exact_amount) = transfer.amount.to_pb_fields();
+                pb.set_amount(amount);
+                if let Some(exact_amount) = exact_amount {
+                    pb.set_exact_amount(exact_amount);
+                }
+                pb
+            })
+            .collect::<Vec<_>>();
+
+        let mut pb = proto::PendingOutgoing::new();
+        let (total, exact_total) = self.total.to_pb_fields();
+        pb.set_total(total);
+        if let Some(exact_total) = exact_total {
+            pb.set_exact_total(exact_total);
+        }
+        pb.set_transfers(transfers.into());
+        pb
+    }
+}
+
+/// Adds a GET endpoint to the scope. Unlike `ServiceApiScope::endpoint`, the query
+/// is parsed with `parse_query`, so malformed queries are reported as `ApiError`s.
+fn query_endpoint<Q, R, F>(scope: &mut ServiceApiScope, name: &'static str, handler: F)
+where
+    Q: DeserializeOwned + 'static,
+    R: Serialize + 'static,
+    F: Fn(&ServiceApiState, Q) -> api::Result<R> + 'static + Send + Sync,
+{
+    let index = move |request: HttpRequest| -> FutureResponse {
+        let response = parse_query(request.query_string())
+            .map_err(api::error::Error::from)
+            .and_then(|query| handler(request.state(), query))
+            .map(|value| HttpResponse::Ok().json(value))
+            .map_err(actix_web::Error::from);
+        Box::new(response.into_future())
+    };
+
+    scope.web_backend().raw_handler(RequestHandler {
+        name: name.to_owned(),
+        method: Method::GET,
+        inner: Arc::new(index) as Arc<RawHandler>,
+    });
+}
+
+/// Deserializes the query string. Parameters which are not fields of `Q` are rejected
+/// with the `UnknownQueryParameters` error listing the unknown and the accepted names.
+///
+/// For the queries with the `id` field of a wallet, the `pub_key` parameter is accepted
+/// as `id`, since it is the name of the parameter before wallet names were accepted.
+fn parse_query<Q: DeserializeOwned>(query_string: &str) -> Result<Q, ApiError> {
+    let invalid = |e: &dyn fmt::Display| {
+        ApiError::new(ErrorKind::InvalidQuery, format!("Invalid query: {}", e))
+    };
+
+    let mut params: Vec<(String, String)> =
+        serde_urlencoded::from_str(query_string).map_err(|e| invalid(&e))?;
+    let accepted = query_fields::<Q>();
+    if accepted.contains(&"id") && !accepted.contains(&"pub_key") {
+        for (name, _) in &mut params {
+            if name == "pub_key" {
+                *name = "id".to_owned();
+            }
+        }
+    }
+    let mut unknown = params
+        .iter()
+        .map(|(name, _)| name.clone())
+        .filter(|name| !accepted.contains(&name.as_str()))
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        unknown.sort();
+        unknown.dedup();
+        let message = format!(
+            "Unknown query parameters: {}; accepted parameters: {}",
+            unknown.join(", "),
+            accepted.join(", ")
+        );
+        return Err(ApiError::new(ErrorKind::UnknownQueryParameters, message)
+            .with_details(json!({ "unknown": unknown, "accepted": accepted })));
+    }
+
+    let query_string = serde_urlencoded::to_string(&params).map_err(|e| invalid(&e))?;
+    serde_urlencoded::from_str(&query_string).map_err(|e| invalid(&e))
+}
+
+/// Returns the field names of `Q`, or an empty slice if `Q` is not a struct.
+///
+/// The names are taken from the `Deserialize` implementation, so they account for
+/// `#[serde(rename)]` attributes.
+fn query_fields<Q: DeserializeOwned>() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de, 'a> Deserializer<'de> for FieldNames<'a> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("field names are collected"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+            identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    // The deserializer always fails after the field names are collected.
+    let _ = Q::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// Adds a GET endpoint for the wallet with the given identifier to the scope. The endpoint
+/// responds with JSON by default, or with protobuf if the request accepts
+/// `PROTOBUF_CONTENT_TYPE`.
+///
+/// Responses carry an `ETag` header derived from the latest block and the wallet
+/// history (see `wallet_etag`); requests with a matching `If-None-Match` header get
+/// `304 Not Modified` without a body.
+fn negotiated_endpoint<Q, R, F>(
+    scope: &mut ServiceApiScope,
+    name: &'static str,
+    wallet_id: fn(&Q) -> &WalletId,
+    handler: F,
+) where
+    Q: DeserializeOwned + 'static,
+    R: Serialize + ProtobufResponse + 'static,
+    F: Fn(&ServiceApiState, Q) -> api::Result<R> + 'static + Send + Sync,
+{
+    let index = move |request: HttpRequest| -> FutureResponse {
+        let header_value = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        let accepts_protobuf = header_value(header::ACCEPT)
+            .map_or(false, |value| value.contains(PROTOBUF_CONTENT_TYPE));
+        let if_none_match = header_value(header::IF_NONE_MATCH);
+
+        let context = request.state();
+        let future = parse_query::<Q>(request.query_string())
+            .map_err(|e| actix_web::Error::from(api::error::Error::from(e)))
+            .and_then(|query| {
+                // The tag is computed before the response so that it is never newer
+                // than the response itself.
+                let etag = wallet_etag(context, wallet_id(&query), accepts_protobuf);
+                if if_none_match.map_or(false, |tags| etag_matches(&tags, &etag)) {
+                    return Ok(HttpResponse::NotModified()
+                        .header(header::ETAG, etag)
+                        .finish());
+                }
+
+                let value = handler(context, query)?;
+                if !accepts_protobuf {
+                    return Ok(HttpResponse::Ok().header(header::ETAG, etag).json(value));
+                }
+
+                let body = value.to_protobuf().write_to_bytes().map_err(|e| {
+                    api::error::Error::from(ApiError::new(
+                        ErrorKind::Internal,
+                        format!("Unable to encode protobuf response: {}", e),
+                    ))
+                })?;
+                Ok(HttpResponse::Ok()
+                    .header(header::ETAG, etag)
+                    .content_type(PROTOBUF_CONTENT_TYPE)
+                    .body(body))
+            })
+            .into_future();
+        Box::new(future)
+    };
+
+    scope.web_backend().raw_handler(RequestHandler {
+        name: name.to_owned(),
+        method: Method::GET,
+        inner: Arc::new(index) as Arc<RawHandler>,
+    });
+}
+
+/// Returns the entity tag of the wallet endpoints' responses. The tag changes
+/// with every new block and with every change of the wallet history; protobuf
+/// and JSON responses have different tags.
+fn wallet_etag(state: &ServiceApiState, id: &WalletId, protobuf: bool) -> String {
+    let context = ReadContext::new(state.blockchain());
+    let block_hash = context
+        .general_schema()
+        .block_hashes_by_height()
+        .last()
+        .unwrap_or_default();
+    let schema = context.schema();
+    let history_hash = schema
+        .resolve(id)
+        .and_then(|pub_key| schema.wallet(&pub_key))
+        .map(|wallet| wallet.history_hash)
+        .unwrap_or_default();
+
+    let tag = HashStream::new()
+        .update(block_hash.as_ref())
+        .update(history_hash.as_ref())
+        .update(&[u8::from(protobuf)])
+        .hash();
+    format!("\"{}\"", tag.to_hex())
+}
+
+/// Checks whether the value of the `If-None-Match` header matches the entity tag.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Private service API description.
+#[derive(Debug, Clone, Copy)]
+pub struct PrivateApi;
+
+impl PrivateApi {
+    /// Endpoint for issuing currency to the given wallet. The `IssueTo` transaction
+    /// is signed with the service key of the node.
+    pub fn issue(state: &ServiceApiState, query: IssueQuery) -> api::Result<TransactionResponse> {
+        let seed = query.seed.unwrap_or_else(transactions::random_seed);
+        let tx = IssueTo::sign(
+            state.public_key(),
+            &query.to,
+            query.amount,
+            seed,
+            state.secret_key(),
+        );
+        let tx_hash = tx.hash();
+        state.sender().broadcast_transaction(tx)?;
+        Ok(TransactionResponse { tx_hash })
+    }
+
+    /// Endpoint for getting the transaction counters of the service.
+    pub fn metrics(state: &ServiceApiState, _query: ()) -> api::Result<ServiceMetrics> {
+        let context = ReadContext::new(state.blockchain());
+        Ok(ServiceMetrics::new(&context.schema()))
+    }
+
+    /// Wires the above endpoints to private scope of the given `ServiceApiBuilder`.
+    pub fn wire(builder: &mut ServiceApiBuilder) {
+        builder
+            .private_scope()
+            .endpoint_mut("v1/admin/issue", Self::issue)
+            .endpoint("v1/metrics/json", Self::metrics);
+
+        let prometheus = |request: HttpRequest| -> FutureResponse {
+            let response = Self::metrics(request.state(), ())
+                .map(|metrics| {
+                    HttpResponse::Ok()
+                        .content_type(PROMETHEUS_CONTENT_TYPE)
+                        .body(metrics.to_prometheus())
+                })
+                .map_err(actix_web::Error::from);
+            Box::new(response.into_future())
+        };
+        builder
+            .private_scope()
+            .web_backend()
+            .raw_handler(RequestHandler {
+                name: "v1/metrics".to_owned(),
+                method: Method::GET,
+                inner: Arc::new(prometheus) as Arc<RawHandler>,
+            });
+    }
+
+    /// Wires the endpoints managing the webhooks of the given registry to private scope
+    /// of the given `ServiceApiBuilder`. The webhooks are local to the node.
+    pub fn wire_webhooks(builder: &mut ServiceApiBuilder, webhooks: WebhookRegistry) {
+        let removed = webhooks.clone();
+        builder
+            .private_scope()
+            .endpoint_mut(
+                "v1/hooks/register",
+                move |_state: &ServiceApiState, query: WebhookQuery| -> api::Result<_> {
+                    webhooks
+                        .register(Webhook {
+                            pub_key: query.pub_key,
+                            url: query.url,
+                        })
+                        .map(|registered| WebhookRegistered { registered })
+                        .map_err(|err| ApiError::new(ErrorKind::InvalidQuery, err).into())
+                },
+            )
+            .endpoint_mut(
+                "v1/hooks/remove",
+                move |_state: &ServiceApiState, query: WebhookQuery| -> api::Result<_> {
+                    let removed = removed.remove(&Webhook {
+                        pub_key: query.pub_key,
+                        url: query.url,
+                    });
+                    Ok(WebhookRemoved { removed })
+                },
+            );
+    }
+}
+
+/// Returns the proof of the latest committed block. Fails if there are no blocks yet
+/// or if precommits of the latest block (other than genesis) are not available.
+fn latest_block_proof<T>(schema: &blockchain::Schema<T>) -> Result<BlockProof, ApiError>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let not_found = |message: &str| ApiError::new(ErrorKind::BlockNotFound, message);
+
+    let len = schema.block_hashes_by_height().len();
+    if len == 0 {
+        return Err(not_found("There are no committed blocks yet"));
+    }
+    let height = Height(len - 1);
+    let block_proof = schema
+        .block_and_precommits(height)
+        .ok_or_else(|| not_found("Latest block is not available"))?;
+    // The genesis block is not precommitted by validators.
+    if height > Height(0) && block_proof.precommits.is_empty() {
+        return Err(not_found(
+            "Precommits for the latest block are not available yet",
+        ));
+    }
+    Ok(block_proof)
+}
+
+/// Checks the proofs of the wallet information against the latest block, including
+/// the precommits of the block, which are checked with the consensus keys of the
+/// actual validators. Failed checks are reported in the result.
+fn verify_wallet_info<T>(
+    general_schema: &blockchain::Schema<T>,
+    info: &WalletInfo,
+    pub_key: &PublicKey,
+) -> ProofVerification
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let validator_keys = general_schema
+        .actual_configuration()
+        .validator_keys
+        .iter()
+        .map(|keys| keys.consensus_key)
+        .collect::<Vec<_>>();
+
+    let mut validators_signed = 0;
+    let result = client::check_block_precommits(&info.block_proof, &validator_keys)
+        .and_then(|signed| {
+            validators_signed = signed;
+            client::check_wallet_proof(&info.block_proof.block, &info.wallet_proof, pub_key)
+        })
+        .and_then(|wallet| match wallet {
+            Some(ref wallet) if info.exists => {
+                info.wallet_history.as_ref().map_or(Ok(()), |history| {
+                    client::check_wallet_history(wallet, history)
+                })
+            }
+            None if !info.exists => Ok(()),
+            _ => Err(ProofError::Malformed(
+                "proof contradicts the existence of the wallet".to_owned(),
+            )),
+        });
+
+    ProofVerification {
+        ok: result.is_ok(),
+        checked_against_block: info.block_proof.block.hash(),
+        validators_signed,
+        error: result.err().map(|err| err.to_string()),
+    }
+}
+
+/// Returns the proof of the wallet (or of its absence) linked to the state hash.
+fn wallet_proof<T>(
+    general_schema: &blockchain::Schema<T>,
+    currency_schema: &Schema<T>,
+    pub_key: PublicKey,
+) -> WalletProof
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let to_table: MapProof<Hash, Hash> =
+        general_schema.get_proof_to_service_table(CRYPTOCURRENCY_SERVICE_ID, 0);
+
+    let to_wallet: MapProof<PublicKey, Wallet> = currency_schema.wallets().get_proof(pub_key);
+
+    WalletProof {
+        to_table,
+        to_wallet,
+    }
+}
+
+/// Encodes the last key of a page as an opaque pagination cursor.
+fn encode_cursor(key: &[u8]) -> String {
+    base64::encode_config(key, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decodes the key encoded in a pagination cursor.
+fn decode_cursor<K>(cursor: &str, from_slice: fn(&[u8]) -> Option<K>) -> Result<K, ApiError> {
+    base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+        .ok()
+        .and_then(|bytes| from_slice(&bytes))
+        .ok_or_else(|| ApiError::invalid_cursor(cursor))
+}
+
+/// Returns the number of leading indices in `0..len` for which the predicate holds.
+/// The predicate must hold for a prefix of the range only.
+fn partition_point<F>(len: u64, pred: F) -> u64
+where
+    F: Fn(u64) -> bool,
+{
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if pred(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+/// Decodes a hex-encoded signed transaction of the cryptocurrency service. The signature
+/// of the message is verified while decoding.
+fn decode_service_tx(
+    tx_body: &str,
+) -> Result<(Signed<RawTransaction>, WalletTransactions), ApiError> {
+    parse_signed_hex(tx_body).map_err(|e| match e {
+        ParseError::Hex(_) => ApiError::new(ErrorKind::MalformedHex, e.to_string()),
+        ParseError::Message(_) => ApiError::new(ErrorKind::MalformedMessage, e.to_string()),
+        ParseError::WrongServiceId(service_id) => {
+            ApiError::new(ErrorKind::WrongServiceId, e.to_string()).with_details(json!({
+                "expected": CRYPTOCURRENCY_SERVICE_ID,
+                "actual": service_id,
+            }))
+        }
+        ParseError::UnknownMessageId(_) | ParseError::Payload(_) => {
+            ApiError::new(ErrorKind::UndecodablePayload, e.to_string())
+        }
+    })
+}
+
+/// Extracts the description of a panic caught during the transaction execution.
+fn panic_description(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::new()
+    }
+}
+
+/// Returns the `ProposeJointSpend` transaction with the given hash from the blockchain
+/// together with its author.
+pub(crate) fn propose_joint_spend_tx<T>(
+    schema: &blockchain::Schema<T>,
+    tx_hash: &Hash,
+) -> Option<(PublicKey, ProposeJointSpend)>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let message = schema.transactions().get(tx_hash)?;
+    match service_tx(&message)? {
+        WalletTransactions::ProposeJointSpend(tx) => Some((message.author(), tx)),
+        _ => None,
+    }
+}
+
+/// Returns the `OpenStream` transaction with the given hash from the blockchain
+/// together with its author.
+fn open_stream_tx<T>(
+    schema: &blockchain::Schema<T>,
+    tx_hash: &Hash,
+) -> Option<(PublicKey, OpenStream)>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let message = schema.transactions().get(tx_hash)?;
+    match service_tx(&message)? {
+        WalletTransactions::OpenStream(tx) => Some((message.author(), tx)),
+        _ => None,
+    }
+}
+
+/// Returns the multisignature transfers of the wallet awaiting approvals.
+fn pending_outgoing<T>(currency_schema: &Schema<T>, pub_key: &PublicKey) -> PendingOutgoing
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let pending = currency_schema.multisig_transfers_by_state(&State::InProcess);
+    let transfers = currency_schema
+        .outgoing_multisig_transfers(pub_key)
+        .iter()
+        .filter(|tx_hash| pending.contains(tx_hash))
+        .filter_map(|tx_hash| {
+            currency_schema
+                .multisig_transfer(tx_hash)
+                .map(|transfer| PendingTransfer {
+                    tx_hash,
+                    amount: transfer.amount,
+                })
+        })
+        .collect::<Vec<_>>();
+
+    PendingOutgoing {
+        total: transfers.iter().fold(Amount::ZERO, |total, transfer| {
+            total.saturating_add(transfer.amount)
+        }),
+        transfers,
+    }
+}
+
+/// Returns the amount and the receiver of the `TransferMultisig` transaction with the given hash.
+fn multisig_amount_and_receiver<T>(
+    schema: &blockchain::Schema<T>,
+    tx_hash: &Hash,
+) -> (Option<Amount>, Option<PublicKey>)
+where
+    T: AsRef<dyn Snapshot>,
+{
+    transfer_multisig_tx(schema, tx_hash)
+        .map(|(_, tx)| (Some(tx.amount), Some(tx.to)))
+        .unwrap_or((None, None))
+}
+
+/// Summarizes the transaction from the point of view of the given wallet.
+fn simple_transaction_info<T>(
+    schema: &blockchain::Schema<T>,
+    wallet: &PublicKey,
+    message: &Signed<RawTransaction>,
+    height: Height,
+) -> SimpleTransactionInfo
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let other_party = |sender: PublicKey, receiver: PublicKey| {
+        if sender == *wallet {
+            receiver
+        } else {
+            sender
+        }
+    };
+    // Approvals and rejections refer to the original transfer for the details.
+    let multisig_details = |transfer_hash: &Hash| {
+        transfer_multisig_tx(schema, transfer_hash)
+            .map(|(sender, tx)| (Some(tx.amount), Some(other_party(sender, tx.to))))
+            .unwrap_or((None, None))
+    };
+    // So do co-signatures of joint spends, which are sent from the joint wallet.
+    let joint_spend_details = |proposal: &Hash| {
+        propose_joint_spend_tx(schema, proposal)
+            .map(|(_, tx)| (Some(tx.amount), Some(other_party(tx.wallet, tx.to))))
+            .unwrap_or((None, None))
+    };
+
+    let (tx_type, amount, counterparty) = match service_tx(message) {
+        Some(tx) => {
+            let (amount, counterparty) = match tx {
+                WalletTransactions::Transfer(ref tx) => {
+                    (Some(tx.amount), Some(other_party(message.author(), tx.to)))
+                }
+                WalletTransactions::TransferMultisig(ref tx) => {
+                    (Some(tx.amount), Some(other_party(message.author(), tx.to)))
+                }
+                WalletTransactions::Issue(ref tx) => (Some(tx.amount), None),
+                WalletTransactions::IssueTo(ref tx) => {
+                    (Some(tx.amount), Some(other_party(message.author(), tx.to)))
+                }
+                WalletTransactions::CreateWallet(_) => (None, None),
+                WalletTransactions::ApproveTransferMultisig(ref tx) => {
+                    multisig_details(tx.transfer_hash())
+                }
+                WalletTransactions::RejectTransferMultisig(ref tx) => {
+                    multisig_details(tx.transfer_hash())
+                }
+                WalletTransactions::CreateJointWallet(_) => (None, None),
+                WalletTransactions::ProposeJointSpend(ref tx) => {
+                    (Some(tx.amount), Some(other_party(tx.wallet, tx.to)))
+                }
+                WalletTransactions::ApproveJointSpend(ref tx) => joint_spend_details(&tx.proposal),
+                // The sponsor and the owner of the new wallet are the counterparties.
+                WalletTransactions::CreateWalletFor(ref tx) => {
+                    (None, Some(other_party(message.author(), tx.owner)))
+                }
+                WalletTransactions::OpenStream(ref tx) => (
+                    Some(tx.max_total),
+                    Some(other_party(message.author(), tx.to)),
+                ),
+                WalletTransactions::CloseStream(ref tx) => (
+                    None,
+                    open_stream_tx(schema, &tx.stream)
+                        .map(|(sender, tx)| other_party(sender, tx.to)),
+                ),
+                WalletTransactions::Stake(ref tx) => (Some(tx.amount), None),
+                WalletTransactions::Unstake(ref tx) => (Some(tx.amount), None),
+                WalletTransactions::ClaimRewards(_) => (None, None),
+                WalletTransactions::ApproveTransferMultisigBatch(ref tx) => {
+                    multisig_details(&tx.tx_hash)
+                }
+                // The balance does not change, so the transaction is not a part of the history.
+                WalletTransactions::SetOverdraft(ref tx) => {
+                    (None, Some(other_party(message.author(), tx.wallet)))
+                }
+                WalletTransactions::SetCashback(_) | WalletTransactions::RenameWallet(_) => {
+                    (None, None)
+                }
+                // The amount of a clearing is the total of the legs of the wallet, which may
+                // have several counterparties.
+                WalletTransactions::Clearing(ref tx) => {
+                    let total = tx
+                        .legs
+                        .iter()
+                        .filter(|leg| leg.from == *wallet || leg.to == *wallet)
+                        .fold(Amount::ZERO, |total, leg| total.saturating_add(leg.amount));
+                    (Some(total), None)
+                }
+            };
+            (Some(tx.name().to_owned()), amount, counterparty)
+        }
+        None => (None, None, None),
+    };
+
+    SimpleTransactionInfo {
+        hash: message.hash(),
+        height,
+        tx_type,
+        amount,
+        counterparty,
     }
 }
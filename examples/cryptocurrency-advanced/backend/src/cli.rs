@@ -0,0 +1,265 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Administrative subcommands of the backend binary.
+//!
+//! `issue` signs an `Issue` or `IssueTo` transaction with the service key of a validator,
+//! submits it through the public API of a node and waits until it is committed:
+//!
+//! ```text
+//! EXONUM_SERVICE_PASS=<passphrase> cryptocurrency issue --to <pubkey> --amount 500 \
+//!     --key-file service.key.toml
+//! ```
+//!
+//! The key file is the encrypted service key file created by `generate-config`.
+
+use clap::{App, AppSettings, Arg};
+use exonum::{
+    api,
+    crypto::{self, Hash, PublicKey, SecretKey},
+    messages::{RawTransaction, Signed},
+};
+
+use std::{
+    env,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    amount::Amount,
+    client::{CryptocurrencyClient, HttpTransport},
+    key_encoding::parse_public_key,
+    transactions::{random_seed, Issue, IssueTo},
+};
+
+pub use crate::client::TxStatus;
+
+/// Name of the subcommand issuing currency.
+pub const ISSUE_COMMAND: &str = "issue";
+/// Address of the node public API used unless `--node` is given.
+pub const DEFAULT_NODE_URL: &str = "http://127.0.0.1:8200";
+/// Environment variable holding the key file passphrase unless `--passphrase-env` is given.
+/// It is the same variable the node reads the passphrase of its service key from.
+pub const DEFAULT_PASSPHRASE_ENV: &str = "EXONUM_SERVICE_PASS";
+/// Number of seconds to wait for the commitment unless `--timeout` is given.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// Interval between the requests of the transaction status.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Error of an administrative subcommand.
+#[derive(Debug, Fail)]
+pub enum CliError {
+    /// The key file cannot be read or decrypted.
+    #[fail(display = "Cannot read key file {}: {}", _0, _1)]
+    KeyFile(String, String),
+    /// The node cannot be reached.
+    #[fail(display = "Node {} is unreachable: {}", _0, _1)]
+    NodeUnreachable(String, String),
+    /// The node has responded with an error.
+    #[fail(display = "Node responded with an error: {}", _0)]
+    Api(String),
+    /// The transaction is not committed in time. It may still be committed later.
+    #[fail(display = "Transaction {} is not committed in {:?}", _0, _1)]
+    Timeout(String, Duration),
+}
+
+/// Arguments of the `issue` subcommand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueCommand {
+    /// Receiver of the currency. The key owner's wallet receives it if absent.
+    pub to: Option<PublicKey>,
+    /// Issued amount of currency.
+    pub amount: Amount,
+    /// Path to the encrypted service key file of a validator.
+    pub key_file: PathBuf,
+    /// Environment variable holding the passphrase of the key file.
+    pub passphrase_env: String,
+    /// Base URL of the node public API.
+    pub node_url: String,
+    /// Seed of the transaction, random if absent.
+    pub seed: Option<u64>,
+    /// Time to wait for the commitment.
+    pub timeout: Duration,
+}
+
+impl IssueCommand {
+    /// Parses the arguments following the binary name, starting with `issue`.
+    /// Returns the clap error for invalid arguments as well as for `--help`.
+    pub fn from_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = App::new(ISSUE_COMMAND)
+            .about("Signs an `Issue` transaction and submits it to the node")
+            .setting(AppSettings::NoBinaryName)
+            .arg(
+                Arg::with_name("to")
+                    .long("to")
+                    .takes_value(true)
+                    .help("Public key of the receiver; the key owner by default"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Issued amount of currency, e.g. `500` or `12.5`"),
+            )
+            .arg(
+                Arg::with_name("key-file")
+                    .long("key-file")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Encrypted service key file of a validator"),
+            )
+            .arg(
+                Arg::with_name("passphrase-env")
+                    .long("passphrase-env")
+                    .takes_value(true)
+                    .default_value(DEFAULT_PASSPHRASE_ENV)
+                    .help("Environment variable holding the passphrase of the key file"),
+            )
+            .arg(
+                Arg::with_name("node")
+                    .long("node")
+                    .takes_value(true)
+                    .default_value(DEFAULT_NODE_URL)
+                    .help("Base URL of the node public API"),
+            )
+            .arg(
+                Arg::with_name("seed")
+                    .long("seed")
+                    .takes_value(true)
+                    .help("Seed of the transaction; random by default"),
+            )
+            .arg(
+                Arg::with_name("timeout")
+                    .long("timeout")
+                    .takes_value(true)
+                    .help("Seconds to wait for the commitment"),
+            )
+            .get_matches_from_safe(args.into_iter().skip(1))?;
+
+        let to = match matches.value_of("to") {
+            Some(to) => Some(parse_public_key(to).map_err(|err| invalid_value("to", err))?),
+            None => None,
+        };
+        let amount = parse_number(&matches, "amount")?.expect("`amount` is required");
+        let seed = parse_number(&matches, "seed")?;
+        let timeout = parse_number(&matches, "timeout")?.unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        Ok(IssueCommand {
+            to,
+            amount,
+            key_file: PathBuf::from(
+                matches
+                    .value_of("key-file")
+                    .expect("`key-file` is required"),
+            ),
+            passphrase_env: matches
+                .value_of("passphrase-env")
+                .unwrap_or(DEFAULT_PASSPHRASE_ENV)
+                .to_owned(),
+            node_url: matches
+                .value_of("node")
+                .unwrap_or(DEFAULT_NODE_URL)
+                .trim_end_matches('/')
+                .to_owned(),
+            seed,
+            timeout: Duration::from_secs(timeout),
+        })
+    }
+
+    /// Builds the transaction signed with the given keys: `IssueTo` if the receiver
+    /// is specified, `Issue` otherwise.
+    pub fn transaction(&self, pk: &PublicKey, sk: &SecretKey) -> Signed<RawTransaction> {
+        let seed = self.seed.unwrap_or_else(random_seed);
+        match self.to {
+            Some(ref to) => IssueTo::sign(pk, to, self.amount, seed, sk),
+            None => Issue::sign(pk, self.amount, seed, sk),
+        }
+    }
+
+    /// Signs and submits the transaction, then waits until it is committed.
+    /// Returns the hash of the transaction together with its execution status.
+    pub fn run(&self) -> Result<(Hash, TxStatus), CliError> {
+        let passphrase = env::var(&self.passphrase_env).map_err(|err| {
+            CliError::KeyFile(
+                self.key_file.display().to_string(),
+                format!("no passphrase in `{}`: {}", self.passphrase_env, err),
+            )
+        })?;
+        let (pk, sk) = read_key_file(&self.key_file, &passphrase)?;
+        let client = CryptocurrencyClient::http(&self.node_url);
+        let tx_hash = client
+            .transaction(&self.transaction(&pk, &sk))
+            .map_err(|err| node_error(client.transport(), err))?;
+
+        let started = Instant::now();
+        loop {
+            match client.transaction_status(tx_hash) {
+                Ok(Some(status)) => return Ok((tx_hash, status)),
+                // The transaction has not reached the pool of the node yet.
+                Ok(None) | Err(api::Error::NotFound(_)) => {}
+                Err(err) => return Err(node_error(client.transport(), err)),
+            }
+            if started.elapsed() >= self.timeout {
+                return Err(CliError::Timeout(tx_hash.to_hex(), self.timeout));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Decrypts the service key file created by `generate-config` with the given passphrase.
+pub fn read_key_file(path: &Path, passphrase: &str) -> Result<(PublicKey, SecretKey), CliError> {
+    crypto::read_keys_from_file(path, passphrase)
+        .map_err(|err| CliError::KeyFile(path.display().to_string(), err.to_string()))
+}
+
+fn node_error(transport: &HttpTransport, err: api::Error) -> CliError {
+    match err {
+        api::Error::Io(err) => {
+            CliError::NodeUnreachable(transport.url().to_owned(), err.to_string())
+        }
+        err => CliError::Api(err.to_string()),
+    }
+}
+
+fn parse_number<T>(matches: &clap::ArgMatches, name: &str) -> Result<Option<T>, clap::Error>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match matches.value_of(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|err| invalid_value(name, err)),
+        None => Ok(None),
+    }
+}
+
+fn invalid_value(name: &str, err: impl fmt::Display) -> clap::Error {
+    clap::Error::with_description(
+        &format!("Invalid value of `--{}`: {}", name, err),
+        clap::ErrorKind::InvalidValue,
+    )
+}
@@ -0,0 +1,922 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client of the cryptocurrency service API.
+//!
+//! `CryptocurrencyClient` wraps the endpoints of the service and of the explorer into
+//! typed methods. Requests are sent through a `Transport`: `HttpTransport` talks to
+//! a real node, and with the `testkit` feature `TestKitApi` serves as a transport
+//! for the testkit blockchains.
+//!
+//! The responses are not trusted by themselves. `verify_wallet_proof` checks that
+//! a wallet proof is rooted in a block with a known hash, and `check_block_precommits`
+//! checks that a block is precommitted by the validators.
+
+use exonum::{
+    api::{
+        self,
+        node::public::explorer::{TransactionQuery, TransactionResponse},
+    },
+    blockchain::{Block, BlockProof, Blockchain},
+    crypto::{self, CryptoHash, Hash, PublicKey, SecretKey},
+    helpers::Height,
+    messages::{Message, RawTransaction, Signed},
+};
+use reqwest::{Client, Response, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use std::{collections::HashSet, fmt, io};
+
+use crate::{
+    api::{
+        AffectedWallets, AffectedWalletsQuery, ApprovalBatchQuery, ApprovalBatchResults, BalanceAt,
+        BalanceAtQuery, BalanceSum, BlockActivity, BlockActivityQuery, BlockSummaries,
+        BlockSummariesQuery, CreatedWallets, CreatedWalletsQuery, DryRunResult, ErrorCodeInfo,
+        HistoryOrder, JointWalletInfo, MultisigFailures, MultisigFailuresQuery, MultisigList,
+        MultisigListQuery, MultisigTemplate, MultisigTransferInfo, MultisigTransferQuery,
+        NameHistory, NameHistoryQuery, OutgoingMultisigQuery, OutgoingMultisigTransfers,
+        SeedTransfers, ServiceStats, SimpleWalletInfo, SimpleWalletInfoQuery, SubmittedTransaction,
+        TransferSeedQuery, WalletBatchInfo, WalletBatchQuery, WalletCount, WalletExistence,
+        WalletHistory, WalletHistoryHead, WalletHistoryRange, WalletHistoryRangeQuery,
+        WalletHistorySince, WalletHistorySinceQuery, WalletInfo, WalletInfoQuery, WalletProof,
+        WalletQuery, WalletSummary,
+    },
+    keys,
+    multisig_transfer::{BatchApprovalResult, State},
+    transactions::CreateWallet,
+    tx_encoding,
+    wallet::Wallet,
+    wallet_id::WalletId,
+    CRYPTOCURRENCY_SERVICE_ID, SERVICE_NAME,
+};
+
+/// Part of the public API of a node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiScope {
+    /// Endpoints of the blockchain explorer, `api/explorer`.
+    Explorer,
+    /// Endpoints of the cryptocurrency service, `api/services/cryptocurrency`.
+    Service,
+}
+
+impl fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiScope::Explorer => write!(f, "api/explorer"),
+            ApiScope::Service => write!(f, "api/services/{}", SERVICE_NAME),
+        }
+    }
+}
+
+/// Transport of the requests to the public API of a node.
+pub trait Transport {
+    /// Sends a GET request with the given query to the endpoint of the scope.
+    fn get<Q, R>(&self, scope: ApiScope, endpoint: &str, query: &Q) -> api::Result<R>
+    where
+        Q: Serialize,
+        R: DeserializeOwned + 'static;
+
+    /// Sends a POST request with the given JSON body to the endpoint of the scope.
+    fn post<B, R>(&self, scope: ApiScope, endpoint: &str, body: &B) -> api::Result<R>
+    where
+        B: Serialize,
+        R: DeserializeOwned + 'static;
+}
+
+/// Query of the endpoints which take no parameters.
+#[derive(Debug, Serialize)]
+struct NoQuery {}
+
+/// Transport sending the requests to a node over HTTP.
+#[derive(Debug)]
+pub struct HttpTransport {
+    url: String,
+    client: Client,
+}
+
+impl HttpTransport {
+    /// Creates a transport to the node with the given base URL of the public API,
+    /// e.g., `http://127.0.0.1:8200`.
+    pub fn new(url: &str) -> Self {
+        HttpTransport {
+            url: url.trim_end_matches('/').to_owned(),
+            client: Client::new(),
+        }
+    }
+
+    /// Returns the base URL of the node public API.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Converts the response to the result the same way `TestKitApi` does, so
+    /// both transports report errors alike. Errors which occur before the node
+    /// responds are reported as `api::Error::Io`.
+    fn response_to_result<R>(&self, response: reqwest::Result<Response>) -> api::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        let mut response = response.map_err(io_error)?;
+        let body = response.text().map_err(io_error)?;
+        match response.status() {
+            status if status.is_success() => serde_json::from_str(&body)
+                .map_err(|err| api::Error::InternalError(format_err!("{}: {}", err, body))),
+            StatusCode::FORBIDDEN => Err(api::Error::Unauthorized),
+            StatusCode::BAD_REQUEST => Err(api::Error::BadRequest(error_description(body))),
+            StatusCode::NOT_FOUND => Err(api::Error::NotFound(error_description(body))),
+            status => Err(api::Error::InternalError(format_err!(
+                "{}: {}",
+                status,
+                error_description(body)
+            ))),
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get<Q, R>(&self, scope: ApiScope, endpoint: &str, query: &Q) -> api::Result<R>
+    where
+        Q: Serialize,
+        R: DeserializeOwned + 'static,
+    {
+        let query = serde_urlencoded::to_string(query)
+            .map_err(|err| api::Error::BadRequest(err.to_string()))?;
+        let mut url = format!("{}/{}/{}", self.url, scope, endpoint);
+        if !query.is_empty() {
+            url = format!("{}?{}", url, query);
+        }
+        self.response_to_result(self.client.get(&url).send())
+    }
+
+    fn post<B, R>(&self, scope: ApiScope, endpoint: &str, body: &B) -> api::Result<R>
+    where
+        B: Serialize,
+        R: DeserializeOwned + 'static,
+    {
+        let url = format!("{}/{}/{}", self.url, scope, endpoint);
+        self.response_to_result(self.client.post(&url).json(body).send())
+    }
+}
+
+#[cfg(feature = "testkit")]
+impl Transport for exonum_testkit::TestKitApi {
+    fn get<Q, R>(&self, scope: ApiScope, endpoint: &str, query: &Q) -> api::Result<R>
+    where
+        Q: Serialize,
+        R: DeserializeOwned + 'static,
+    {
+        self.public(testkit_api_kind(scope))
+            .query(query)
+            .get(endpoint)
+    }
+
+    fn post<B, R>(&self, scope: ApiScope, endpoint: &str, body: &B) -> api::Result<R>
+    where
+        B: Serialize,
+        R: DeserializeOwned + 'static,
+    {
+        self.public(testkit_api_kind(scope))
+            .query(body)
+            .post(endpoint)
+    }
+}
+
+#[cfg(feature = "testkit")]
+fn testkit_api_kind(scope: ApiScope) -> exonum_testkit::ApiKind {
+    match scope {
+        ApiScope::Explorer => exonum_testkit::ApiKind::Explorer,
+        ApiScope::Service => exonum_testkit::ApiKind::Service(SERVICE_NAME),
+    }
+}
+
+/// Execution status of a committed transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxStatus {
+    /// The transaction is executed successfully.
+    Success,
+    /// The transaction is rejected with the given error code of the service.
+    Error {
+        /// Error code, see `transactions::Error`.
+        code: u8,
+        /// Description of the error.
+        description: String,
+    },
+    /// The execution of the transaction has panicked.
+    Panic {
+        /// Description of the panic.
+        description: String,
+    },
+}
+
+impl TxStatus {
+    /// Parses the `status` field of a committed transaction returned by the explorer API.
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let description = value["description"].as_str().unwrap_or_default().to_owned();
+        match value["type"].as_str()? {
+            "success" => Some(TxStatus::Success),
+            "error" => Some(TxStatus::Error {
+                code: value["code"].as_u64()? as u8,
+                description,
+            }),
+            "panic" => Some(TxStatus::Panic { description }),
+            _ => None,
+        }
+    }
+
+    /// Shows if the transaction is executed successfully.
+    pub fn is_success(&self) -> bool {
+        *self == TxStatus::Success
+    }
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TxStatus::Success => write!(f, "success"),
+            TxStatus::Error { code, description } => {
+                write!(f, "rejected with error {}: {}", code, description)
+            }
+            TxStatus::Panic { description } => write!(f, "panicked: {}", description),
+        }
+    }
+}
+
+/// Error of the proof verification.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum ProofError {
+    /// The proof is rooted in a block other than the trusted one.
+    #[fail(display = "Block {:?} is not the trusted block {:?}", actual, trusted)]
+    UntrustedBlock {
+        /// Hash of the block in the response.
+        actual: Hash,
+        /// Hash of the trusted block.
+        trusted: Hash,
+    },
+    /// The proof is not a valid Merkle proof.
+    #[fail(display = "Malformed proof: {}", _0)]
+    Malformed(String),
+    /// The proof does not lead to the state hash of the block.
+    #[fail(display = "Proof does not match the state hash of the block")]
+    StateHashMismatch,
+    /// The proof neither proves the presence nor the absence of the wallet.
+    #[fail(display = "Proof does not cover wallet {:?}", _0)]
+    WalletNotCovered(PublicKey),
+    /// A precommit of the block is not a valid precommit of a validator.
+    #[fail(display = "Invalid precommit: {}", _0)]
+    InvalidPrecommit(String),
+    /// The block is precommitted by too few validators.
+    #[fail(
+        display = "Block is precommitted by {} validators, {} required",
+        signed, required
+    )]
+    NotEnoughPrecommits {
+        /// Number of the validators who precommitted the block.
+        signed: u32,
+        /// Number of the validators required to precommit the block.
+        required: u32,
+    },
+    /// The history records do not match the wallet or the returned transactions.
+    #[fail(display = "History does not match the wallet: {}", _0)]
+    HistoryMismatch(String),
+}
+
+/// Checks that the wallet proof is rooted in the state hash of the block, and returns
+/// the wallet with the given key, or `None` if the proof shows that the wallet does
+/// not exist. The block itself is not verified; see `verify_wallet_proof`.
+pub fn check_wallet_proof(
+    block: &Block,
+    wallet_proof: &WalletProof,
+    pub_key: &PublicKey,
+) -> Result<Option<Wallet>, ProofError> {
+    let to_table = wallet_proof
+        .to_table
+        .check()
+        .map_err(|err| ProofError::Malformed(err.to_string()))?;
+    if to_table.merkle_root() != *block.state_hash() {
+        return Err(ProofError::StateHashMismatch);
+    }
+    let wallets_key = Blockchain::service_table_unique_key(CRYPTOCURRENCY_SERVICE_ID, 0);
+    let wallets_root = to_table
+        .entries()
+        .find(|(key, _)| **key == wallets_key)
+        .map(|(_, root)| *root)
+        .ok_or(ProofError::StateHashMismatch)?;
+
+    let to_wallet = wallet_proof
+        .to_wallet
+        .check()
+        .map_err(|err| ProofError::Malformed(err.to_string()))?;
+    if to_wallet.merkle_root() != wallets_root {
+        return Err(ProofError::StateHashMismatch);
+    }
+    if to_wallet.missing_keys().any(|key| key == pub_key) {
+        return Ok(None);
+    }
+    to_wallet
+        .entries()
+        .find(|(key, _)| *key == pub_key)
+        .map(|(_, wallet)| Some(wallet.clone()))
+        .ok_or(ProofError::WalletNotCovered(*pub_key))
+}
+
+/// Checks the precommits of the block against the consensus keys of the validators,
+/// in the order of their ids, and returns the number of validators who precommitted
+/// the block. More than two thirds of the validators should precommit a block other
+/// than genesis, which is not precommitted at all.
+pub fn check_block_precommits(
+    block_proof: &BlockProof,
+    validator_keys: &[PublicKey],
+) -> Result<u32, ProofError> {
+    let block = &block_proof.block;
+    if block.height() == Height(0) {
+        return Ok(0);
+    }
+
+    let block_hash = block.hash();
+    let mut signed = HashSet::new();
+    for precommit in &block_proof.precommits {
+        let payload = precommit.payload();
+        let validator = payload.validator().0;
+        if validator_keys.get(usize::from(validator)) != Some(&precommit.author()) {
+            return Err(ProofError::InvalidPrecommit(format!(
+                "precommit of validator {} is not signed by its consensus key",
+                validator
+            )));
+        }
+        if *payload.block_hash() != block_hash || payload.height() != block.height() {
+            return Err(ProofError::InvalidPrecommit(format!(
+                "precommit of validator {} is for another block",
+                validator
+            )));
+        }
+        // Parsing the message checks its signature.
+        if Message::from_raw_buffer(precommit.signed_message().raw().to_vec()).is_err() {
+            return Err(ProofError::InvalidPrecommit(format!(
+                "signature of the precommit of validator {} is invalid",
+                validator
+            )));
+        }
+        signed.insert(validator);
+    }
+
+    let signed = signed.len() as u32;
+    let required = validator_keys.len() as u32 * 2 / 3 + 1;
+    if signed < required {
+        return Err(ProofError::NotEnoughPrecommits { signed, required });
+    }
+    Ok(signed)
+}
+
+/// Checks that the history returned with the wallet is proven by the history hash
+/// of the wallet and consists of the proven transactions.
+pub fn check_wallet_history(wallet: &Wallet, history: &WalletHistory) -> Result<(), ProofError> {
+    if history.total_len != wallet.history_len {
+        return Err(ProofError::HistoryMismatch(format!(
+            "history length {} differs from {} in the wallet",
+            history.total_len, wallet.history_len
+        )));
+    }
+    let records = history
+        .proof
+        .validate(wallet.history_hash, wallet.history_len)
+        .map_err(|err| ProofError::Malformed(format!("{:?}", err)))?;
+    if records.len() != history.transactions.len() {
+        return Err(ProofError::HistoryMismatch(format!(
+            "{} transactions are returned for {} proven records",
+            history.transactions.len(),
+            records.len()
+        )));
+    }
+    let expected_indices = history.from..history.from + records.len() as u64;
+    for ((index, record), (expected_index, tx)) in records
+        .iter()
+        .zip(expected_indices.zip(&history.transactions))
+    {
+        if *index != expected_index || record.tx_hash != tx.hash() {
+            return Err(ProofError::HistoryMismatch(format!(
+                "transaction {} is not proven",
+                tx.hash()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Verifies that the wallet proof is rooted in the block with the trusted hash, e.g.,
+/// the one obtained from the precommits of the validators. Returns the wallet with
+/// the given key, or `None` if the proof shows that the wallet does not exist.
+pub fn verify_wallet_proof(
+    block_proof: &BlockProof,
+    wallet_proof: &WalletProof,
+    pub_key: &PublicKey,
+    trusted_block_hash: &Hash,
+) -> Result<Option<Wallet>, ProofError> {
+    let block_hash = block_proof.block.hash();
+    if block_hash != *trusted_block_hash {
+        return Err(ProofError::UntrustedBlock {
+            actual: block_hash,
+            trusted: *trusted_block_hash,
+        });
+    }
+    check_wallet_proof(&block_proof.block, wallet_proof, pub_key)
+}
+
+/// Client of the cryptocurrency service API.
+#[derive(Debug)]
+pub struct CryptocurrencyClient<T> {
+    transport: T,
+}
+
+impl CryptocurrencyClient<HttpTransport> {
+    /// Creates a client of the node with the given base URL of the public API.
+    pub fn http(url: &str) -> Self {
+        Self::new(HttpTransport::new(url))
+    }
+}
+
+impl<T: Transport> CryptocurrencyClient<T> {
+    /// Creates a client sending the requests through the transport.
+    pub fn new(transport: T) -> Self {
+        CryptocurrencyClient { transport }
+    }
+
+    /// Returns the transport of the client, e.g., to call endpoints the client
+    /// has no methods for.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Generates a wallet creation transaction with a random key pair and submits it.
+    /// Note that the transaction is not immediately added to the blockchain, but rather
+    /// is put to the pool of unconfirmed transactions.
+    pub fn create_wallet(&self, name: &str) -> api::Result<(Signed<RawTransaction>, SecretKey)> {
+        let (pub_key, key) = crypto::gen_keypair();
+        self.submit_wallet_creation(name, pub_key, key)
+    }
+
+    /// Generates a wallet creation transaction with the key pair derived from
+    /// the master secret with the given index, and submits it. See the `keys` module
+    /// for the derivation.
+    pub fn create_derived_wallet(
+        &self,
+        name: &str,
+        master_seed: &[u8],
+        index: u32,
+    ) -> api::Result<(Signed<RawTransaction>, SecretKey)> {
+        let (pub_key, key) = keys::derive_keypair(master_seed, index);
+        self.submit_wallet_creation(name, pub_key, key)
+    }
+
+    fn submit_wallet_creation(
+        &self,
+        name: &str,
+        pub_key: PublicKey,
+        key: SecretKey,
+    ) -> api::Result<(Signed<RawTransaction>, SecretKey)> {
+        let tx = CreateWallet::sign(name, &pub_key, &key);
+        self.transaction(&tx)?;
+        Ok((tx, key))
+    }
+
+    /// Submits the signed transaction through the explorer API and returns its hash.
+    /// Fails if the node reports a hash other than the hash of the transaction.
+    pub fn transaction(&self, tx: &Signed<RawTransaction>) -> api::Result<Hash> {
+        let response: TransactionResponse = self.transport.post(
+            ApiScope::Explorer,
+            "v1/transactions",
+            &json!({ "tx_body": tx_encoding::to_hex(tx) }),
+        )?;
+        if response.tx_hash != tx.hash() {
+            return Err(api::Error::InternalError(format_err!(
+                "Node reported hash {:?} for transaction {:?}",
+                response.tx_hash,
+                tx.hash()
+            )));
+        }
+        Ok(response.tx_hash)
+    }
+
+    /// Returns the explorer information about the transaction as is.
+    pub fn transaction_info(&self, tx_hash: Hash) -> api::Result<Value> {
+        self.transport.get(
+            ApiScope::Explorer,
+            "v1/transactions",
+            &TransactionQuery::new(tx_hash),
+        )
+    }
+
+    /// Returns the execution status of the transaction, or `None` if the transaction
+    /// is known to the node, but not committed yet.
+    pub fn transaction_status(&self, tx_hash: Hash) -> api::Result<Option<TxStatus>> {
+        let info = self.transaction_info(tx_hash)?;
+        match info["type"].as_str() {
+            Some("committed") => TxStatus::from_json(&info["status"])
+                .map(Some)
+                .ok_or_else(|| {
+                    api::Error::InternalError(format_err!("Malformed transaction info: {}", info))
+                }),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the wallet information with the proofs. The wallet may be identified
+    /// by its public key or by its name.
+    pub fn wallet_info(&self, id: impl Into<WalletId>) -> api::Result<WalletInfo> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/info",
+            &WalletQuery { id: id.into() },
+        )
+    }
+
+    /// Returns the wallet information for the given query, e.g., with the proofs
+    /// verified by the node.
+    pub fn wallet_info_with(&self, query: WalletInfoQuery) -> api::Result<WalletInfo> {
+        self.transport
+            .get(ApiScope::Service, "v1/wallets/info", &query)
+    }
+
+    /// Returns the wallet, or `None` if it does not exist. The proof of the wallet
+    /// is checked against the block it is returned with, but the block itself is not
+    /// verified; see `get_verified_wallet`.
+    pub fn get_wallet(&self, pub_key: PublicKey) -> api::Result<Option<Wallet>> {
+        let info = self.wallet_info(pub_key)?;
+        check_wallet_proof(&info.block_proof.block, &info.wallet_proof, &pub_key)
+            .map_err(|err| api::Error::InternalError(err.into()))
+    }
+
+    /// Returns the wallet, or `None` if it does not exist, verifying that the response
+    /// is rooted in the block with the trusted hash.
+    pub fn get_verified_wallet(
+        &self,
+        pub_key: PublicKey,
+        trusted_block_hash: &Hash,
+    ) -> api::Result<Option<Wallet>> {
+        let info = self.wallet_info(pub_key)?;
+        verify_wallet_proof(
+            &info.block_proof,
+            &info.wallet_proof,
+            &pub_key,
+            trusted_block_hash,
+        )
+        .map_err(|err| api::Error::InternalError(err.into()))
+    }
+
+    /// Returns the wallet information without proofs.
+    pub fn simple_wallet_info(&self, id: impl Into<WalletId>) -> api::Result<SimpleWalletInfo> {
+        self.simple_wallet_info_with(SimpleWalletInfoQuery {
+            id: id.into(),
+            order: HistoryOrder::Asc,
+            offset: 0,
+            limit: None,
+        })
+    }
+
+    /// Returns the wallet information without proofs for the given history page.
+    pub fn simple_wallet_info_with(
+        &self,
+        query: SimpleWalletInfoQuery,
+    ) -> api::Result<SimpleWalletInfo> {
+        self.transport
+            .get(ApiScope::Service, "v1/wallets/info/simple", &query)
+    }
+
+    /// Returns the information about several wallets.
+    pub fn wallets_batch(&self, pub_keys: Vec<PublicKey>) -> api::Result<WalletBatchInfo> {
+        self.transport.post(
+            ApiScope::Service,
+            "v1/wallets/info/batch",
+            &WalletBatchQuery { pub_keys },
+        )
+    }
+
+    /// Returns the sum of the balances of the wallets.
+    pub fn balance_sum(&self, pub_keys: Vec<PublicKey>) -> api::Result<BalanceSum> {
+        self.transport.post(
+            ApiScope::Service,
+            "v1/wallets/balance/sum",
+            &WalletBatchQuery { pub_keys },
+        )
+    }
+
+    /// Submits the hex-encoded transaction through the service API.
+    pub fn submit_transaction(&self, tx_body: &str) -> api::Result<SubmittedTransaction> {
+        self.transport.post(
+            ApiScope::Service,
+            "v1/transactions",
+            &json!({ "tx_body": tx_body }),
+        )
+    }
+
+    /// Executes the hex-encoded transaction without committing it.
+    pub fn dry_run_transaction(&self, tx_body: &str) -> api::Result<DryRunResult> {
+        self.transport.post(
+            ApiScope::Service,
+            "v1/transactions/dry_run",
+            &json!({ "tx_body": tx_body }),
+        )
+    }
+
+    /// Returns the balance of the wallet after the block at the given height.
+    pub fn balance_at(&self, id: impl Into<WalletId>, height: u64) -> api::Result<BalanceAt> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/balance_at",
+            &BalanceAtQuery {
+                id: id.into(),
+                height,
+            },
+        )
+    }
+
+    /// Returns the service transactions of the block at the given height.
+    pub fn block_activity(&self, height: u64) -> api::Result<BlockActivity> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/blocks/activity",
+            &BlockActivityQuery { height },
+        )
+    }
+
+    /// Returns the catalog of the service errors.
+    pub fn error_codes(&self) -> api::Result<Vec<ErrorCodeInfo>> {
+        self.transport
+            .get(ApiScope::Service, "v1/errors", &NoQuery {})
+    }
+
+    /// Returns the given range of the wallet history with the proofs.
+    pub fn wallet_history_range(
+        &self,
+        id: impl Into<WalletId>,
+        from: u64,
+        to: u64,
+    ) -> api::Result<WalletHistoryRange> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/history/range",
+            &WalletHistoryRangeQuery {
+                id: id.into(),
+                from,
+                to,
+            },
+        )
+    }
+
+    /// Returns the wallet history starting from the given index with the proofs.
+    pub fn wallet_history_since(
+        &self,
+        id: impl Into<WalletId>,
+        from_index: u64,
+    ) -> api::Result<WalletHistorySince> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/history/since",
+            &WalletHistorySinceQuery {
+                id: id.into(),
+                from_index,
+            },
+        )
+    }
+
+    /// Returns the latest entry of the wallet history.
+    pub fn wallet_history_head(&self, id: impl Into<WalletId>) -> api::Result<WalletHistoryHead> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/history/head",
+            &WalletQuery { id: id.into() },
+        )
+    }
+
+    /// Returns the multisignature transfers sent from the wallet.
+    pub fn outgoing_multisig_transfers(
+        &self,
+        pub_key: PublicKey,
+        state: Option<State>,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> api::Result<OutgoingMultisigTransfers> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/multisig/outgoing",
+            &OutgoingMultisigQuery {
+                pub_key,
+                state,
+                offset,
+                limit,
+            },
+        )
+    }
+
+    /// Returns whether the wallet exists, with the proof.
+    pub fn wallet_exists(&self, pub_key: PublicKey) -> api::Result<WalletExistence> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/exists",
+            &WalletQuery { id: pub_key.into() },
+        )
+    }
+
+    /// Returns the owners of the joint wallet and the spends awaiting their signatures.
+    pub fn joint_wallet(&self, pub_key: PublicKey) -> api::Result<JointWalletInfo> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/joint",
+            &WalletQuery { id: pub_key.into() },
+        )
+    }
+
+    /// Returns the summary of the wallet.
+    pub fn wallet_summary(&self, pub_key: PublicKey) -> api::Result<WalletSummary> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/summary",
+            &WalletQuery { id: pub_key.into() },
+        )
+    }
+
+    /// Returns the name of the wallet at the given height, or the current name if
+    /// the height is not set, together with the changes of the name.
+    pub fn name_history(
+        &self,
+        pub_key: PublicKey,
+        height: Option<u64>,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> api::Result<NameHistory> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/name_history",
+            &NameHistoryQuery {
+                pub_key,
+                height,
+                offset,
+                limit,
+            },
+        )
+    }
+
+    /// Returns the number of wallets.
+    pub fn wallet_count(&self) -> api::Result<u64> {
+        self.transport
+            .get::<_, WalletCount>(ApiScope::Service, "v1/wallets/count", &NoQuery {})
+            .map(|count| count.count)
+    }
+
+    /// Returns the wallets created in the given range of heights.
+    pub fn created_wallets(
+        &self,
+        from_height: u64,
+        to_height: Option<u64>,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> api::Result<CreatedWallets> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/wallets/created",
+            &CreatedWalletsQuery {
+                from_height,
+                to_height,
+                cursor,
+                limit,
+            },
+        )
+    }
+
+    /// Returns the hashes of the first page of multisignature transfers in the state.
+    pub fn multisig_list(&self, state: State) -> api::Result<Vec<Hash>> {
+        self.multisig_list_page(state, None, None)
+            .map(|list| list.tx_hashes)
+    }
+
+    /// Returns the page of multisignature transfers in the state.
+    pub fn multisig_list_page(
+        &self,
+        state: State,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> api::Result<MultisigList> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/multisig/list",
+            &MultisigListQuery {
+                state,
+                cursor,
+                limit,
+            },
+        )
+    }
+
+    /// Returns the multisignature transfer in the form of a template.
+    pub fn multisig_template(&self, tx_hash: Hash) -> api::Result<MultisigTemplate> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/multisig/template",
+            &MultisigTransferQuery { tx_hash },
+        )
+    }
+
+    /// Returns the multisignature transfer with the proof.
+    pub fn multisig_proof(&self, tx_hash: Hash) -> api::Result<MultisigTransferInfo> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/multisig/proof",
+            &MultisigTransferQuery { tx_hash },
+        )
+    }
+
+    /// Returns the transfers sent from the wallet with the given seed.
+    pub fn transfers_by_seed(&self, pub_key: PublicKey, seed: u64) -> api::Result<SeedTransfers> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/transfers/by_seed",
+            &TransferSeedQuery { pub_key, seed },
+        )
+    }
+
+    /// Returns the wallets affected by the transaction.
+    pub fn affected_wallets(&self, tx_hash: Hash) -> api::Result<Vec<PublicKey>> {
+        self.transport
+            .get::<_, AffectedWallets>(
+                ApiScope::Service,
+                "v1/transactions/affected",
+                &AffectedWalletsQuery { tx_hash },
+            )
+            .map(|affected| affected.wallets)
+    }
+
+    /// Returns the rejected and expired multisignature transfers with the wallet
+    /// as the sender, the receiver or one of the approvers.
+    pub fn multisig_failures(
+        &self,
+        pub_key: PublicKey,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> api::Result<MultisigFailures> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/multisig/failures",
+            &MultisigFailuresQuery {
+                pub_key,
+                offset,
+                limit,
+            },
+        )
+    }
+
+    /// Returns the outcomes of the approvals carried by the `ApproveTransferMultisigBatch`
+    /// transaction.
+    pub fn approval_batch_results(&self, tx_hash: Hash) -> api::Result<Vec<BatchApprovalResult>> {
+        self.transport
+            .get::<_, ApprovalBatchResults>(
+                ApiScope::Service,
+                "v1/multisig/batch_results",
+                &ApprovalBatchQuery { tx_hash },
+            )
+            .map(|batch| batch.results)
+    }
+
+    /// Returns the summaries of the blocks with heights in `[from, to)` together
+    /// with the proof of the range.
+    pub fn block_summaries(&self, from: u64, to: u64) -> api::Result<BlockSummaries> {
+        self.transport.get(
+            ApiScope::Service,
+            "v1/stats/blocks",
+            &BlockSummariesQuery { from, to },
+        )
+    }
+
+    /// Returns the statistics of the service.
+    pub fn stats(&self) -> api::Result<ServiceStats> {
+        self.transport
+            .get(ApiScope::Service, "v1/stats", &NoQuery {})
+    }
+}
+
+fn io_error(err: reqwest::Error) -> api::Error {
+    api::Error::Io(io::Error::new(io::ErrorKind::Other, err.to_string()))
+}
+
+/// Extracts the description of the error from the response body, like `TestKitApi` does.
+fn error_description(body: String) -> String {
+    match serde_json::from_str::<Value>(&body) {
+        Ok(Value::Object(ref object)) if object.contains_key("description") => object
+            ["description"]
+            .as_str()
+            .map(str::to_owned)
+            .unwrap_or(body),
+        Ok(Value::String(string)) => string,
+        _ => body,
+    }
+}
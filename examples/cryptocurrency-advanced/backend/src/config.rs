@@ -0,0 +1,197 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cryptocurrency service configuration.
+
+use exonum::crypto::PublicKey;
+use serde_json::Value;
+
+use crate::{
+    amount::Amount,
+    api::MAX_LIST_ITEMS,
+    staking::STAKING_REWARD_INTERVAL,
+    transactions::{Error, MAX_APPROVERS, MAX_CLEARING_LEGS, MAX_COMMENT_LEN},
+    INITIAL_BALANCE,
+};
+
+/// Upper bound of `Config::max_comment_len`. Comments are stored along with
+/// the approvals, so their length cannot be raised indefinitely.
+pub const MAX_COMMENT_LEN_LIMIT: usize = 4_096;
+
+/// Configuration of the service. It is a part of the global configuration of
+/// the blockchain, so it is the same on all nodes and its changes take effect
+/// from the height chosen by the validators.
+///
+/// Fields absent from the stored configuration take their default values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Balance of the newly created wallets.
+    pub initial_balance: Amount,
+    /// Maximum number of distinct approvers of a multisignature transfer.
+    pub max_approvers: usize,
+    /// Maximum length of an approver's comment in bytes.
+    pub max_comment_len: usize,
+    /// Number of decimals allowed in the transferred and issued amounts, up to
+    /// `Amount::DECIMALS`. Zero, the default, allows whole amounts only.
+    ///
+    /// Lowering the number does not affect the balances holding more decimals,
+    /// but the extra decimals can no longer be transferred.
+    pub decimals: u8,
+    /// Key of the operator allowed to sign `Clearing` transactions. Clearing is
+    /// disabled if none is set, which is the default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clearing_operator: Option<PublicKey>,
+    /// Maximum number of legs of a `Clearing` transaction.
+    pub max_clearing_legs: usize,
+    /// Number of blocks between the accruals of the staking rewards.
+    pub staking_reward_interval: u64,
+    /// Staking reward accrued every `staking_reward_interval` blocks for every staked
+    /// unit, e.g., `0.001` for 0.1%. Zero, the default, disables the rewards.
+    pub staking_reward_rate: Amount,
+    /// Total supply up to which the staking rewards are issued. The rewards are
+    /// unbounded if none is set, which is the default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub staking_supply_cap: Option<Amount>,
+    /// Key of the admin allowed to sign `SetOverdraft` transactions. Overdrafts are
+    /// disabled if none is set, which is the default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overdraft_admin: Option<PublicKey>,
+    /// Number of blocks after which the settled multisignature transfers are removed,
+    /// leaving only their final states. Settled transfers are kept if none is set,
+    /// which is the default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settled_multisig_retention: Option<u64>,
+    /// Maximum number of items in a list returned by the API. Requests whose lists
+    /// would be longer fail once the limit is exceeded instead of reading the rest
+    /// of the items.
+    pub max_list_items: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            initial_balance: Amount::whole(INITIAL_BALANCE),
+            max_approvers: MAX_APPROVERS,
+            max_comment_len: MAX_COMMENT_LEN,
+            decimals: 0,
+            clearing_operator: None,
+            max_clearing_legs: MAX_CLEARING_LEGS,
+            staking_reward_interval: STAKING_REWARD_INTERVAL,
+            staking_reward_rate: Amount::ZERO,
+            staking_supply_cap: None,
+            overdraft_admin: None,
+            settled_multisig_retention: None,
+            max_list_items: MAX_LIST_ITEMS,
+        }
+    }
+}
+
+/// Error found in the service configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum ConfigError {
+    /// The configuration is not a valid JSON representation of `Config`.
+    #[fail(display = "Malformed configuration: {}", _0)]
+    Malformed(String),
+    /// Multisignature transfers would not allow any approvers.
+    #[fail(display = "`max_approvers` must be positive")]
+    NoApprovers,
+    /// Comments would be allowed to exceed `MAX_COMMENT_LEN_LIMIT`.
+    #[fail(
+        display = "`max_comment_len` must not exceed {}, got {}",
+        MAX_COMMENT_LEN_LIMIT, _0
+    )]
+    CommentLenTooLarge(usize),
+    /// Amounts would be allowed to have more than `Amount::DECIMALS` decimals.
+    #[fail(display = "`decimals` must not exceed `Amount::DECIMALS`, got {}", _0)]
+    TooManyDecimals(u8),
+    /// The initial balance has more decimals than allowed by `decimals`.
+    #[fail(display = "`initial_balance` has more decimals than allowed")]
+    InitialBalanceTooPrecise,
+    /// Clearing transactions would not allow any legs.
+    #[fail(display = "`max_clearing_legs` must be positive")]
+    NoClearingLegs,
+    /// The staking rewards would have no interval to be accrued at.
+    #[fail(display = "`staking_reward_interval` must be positive")]
+    NoStakingRewardInterval,
+    /// Settled multisignature transfers would be removed in the block settling them.
+    #[fail(display = "`settled_multisig_retention` must be positive")]
+    NoSettledMultisigRetention,
+    /// The API would not return any items in lists.
+    #[fail(display = "`max_list_items` must be positive")]
+    NoListItems,
+}
+
+impl Config {
+    /// Parses the configuration stored in the global configuration of the blockchain.
+    /// A missing or `null` value stands for the default configuration, which is
+    /// the case for the blockchains started before the configuration was introduced.
+    pub fn from_stored(value: Option<&Value>) -> Result<Self, Vec<ConfigError>> {
+        let config = match value {
+            None | Some(Value::Null) => Config::default(),
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|err| vec![ConfigError::Malformed(err.to_string())])?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the configuration, returning all the errors found.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        if self.max_approvers == 0 {
+            errors.push(ConfigError::NoApprovers);
+        }
+        if self.max_comment_len > MAX_COMMENT_LEN_LIMIT {
+            errors.push(ConfigError::CommentLenTooLarge(self.max_comment_len));
+        }
+        if self.decimals > Amount::DECIMALS {
+            errors.push(ConfigError::TooManyDecimals(self.decimals));
+        } else if !self.allows(self.initial_balance) {
+            errors.push(ConfigError::InitialBalanceTooPrecise);
+        }
+        if self.max_clearing_legs == 0 {
+            errors.push(ConfigError::NoClearingLegs);
+        }
+        if self.staking_reward_interval == 0 {
+            errors.push(ConfigError::NoStakingRewardInterval);
+        }
+        if self.settled_multisig_retention == Some(0) {
+            errors.push(ConfigError::NoSettledMultisigRetention);
+        }
+        if self.max_list_items == 0 {
+            errors.push(ConfigError::NoListItems);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns whether the amount has no more decimals than allowed by `decimals`.
+    pub fn allows(&self, amount: Amount) -> bool {
+        amount.decimals() <= self.decimals
+    }
+
+    /// Checks that the amount has no more decimals than allowed by `decimals`.
+    pub fn check_amount(&self, amount: Amount) -> Result<(), Error> {
+        if self.allows(amount) {
+            Ok(())
+        } else {
+            Err(Error::AmountTooPrecise)
+        }
+    }
+}
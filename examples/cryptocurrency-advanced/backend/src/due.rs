@@ -0,0 +1,80 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Items scheduled for processing at the given heights.
+
+use exonum::{crypto::Hash, proto::ProtobufConvert};
+
+use super::proto::{self, DueItem_Kind};
+
+/// Kind of a scheduled item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DueKind {
+    /// Expiration of the multisignature transfer created by the transaction.
+    MultisigExpiry,
+    /// Closing of the payment stream opened by the transaction, which has accrued
+    /// its maximum total.
+    PaymentStreamExhaustion,
+}
+
+impl ProtobufConvert for DueKind {
+    type ProtoStruct = DueItem_Kind;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        match self {
+            DueKind::MultisigExpiry => DueItem_Kind::MULTISIG_EXPIRY,
+            DueKind::PaymentStreamExhaustion => DueItem_Kind::PAYMENT_STREAM_EXHAUSTION,
+        }
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        match pb {
+            DueItem_Kind::MULTISIG_EXPIRY => Ok(DueKind::MultisigExpiry),
+            DueItem_Kind::PAYMENT_STREAM_EXHAUSTION => Ok(DueKind::PaymentStreamExhaustion),
+        }
+    }
+}
+
+/// Item processed by the service in the block at the height it is scheduled for.
+/// Items due at the same height are processed in the order of their scheduling,
+/// which follows the order of the transactions in the blocks.
+#[derive(Clone, Debug, ProtobufConvert, PartialEq)]
+#[exonum(pb = "proto::DueItem", serde_pb_convert)]
+pub struct DueItem {
+    /// What is to be done with the item.
+    pub kind: DueKind,
+    /// Hash of the transaction which has scheduled the item.
+    pub tx_hash: Hash,
+}
+
+impl DueItem {
+    /// Creates an item expiring the multisignature transfer created by the transaction
+    /// with the given hash.
+    pub fn multisig_expiry(tx_hash: Hash) -> Self {
+        Self {
+            kind: DueKind::MultisigExpiry,
+            tx_hash,
+        }
+    }
+
+    /// Creates an item closing the payment stream opened by the transaction with
+    /// the given hash once it has accrued its maximum total.
+    pub fn payment_stream_exhaustion(tx_hash: Hash) -> Self {
+        Self {
+            kind: DueKind::PaymentStreamExhaustion,
+            tx_hash,
+        }
+    }
+}
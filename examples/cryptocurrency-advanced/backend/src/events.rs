@@ -0,0 +1,370 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! State changes of the service for external indexers.
+//!
+//! Events are emitted from `after_commit`, so they arrive in block order. They can be
+//! appended to a JSON-lines file and received through channels. Emission never blocks
+//! the commit: events which cannot be delivered are logged and dropped.
+
+use exonum::{
+    blockchain::{self, TransactionSet},
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+    storage::Snapshot,
+};
+use futures::sync::mpsc as channel;
+
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{LineWriter, Write},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use crate::{
+    amount::Amount,
+    joint_wallet::joint_wallet_address,
+    lock,
+    multisig_transfer::State,
+    schema::{parse_settled_multisig_key, settled_multisig_key},
+    transactions::WalletTransactions,
+    wallet::HistoryRecord,
+    Schema, CRYPTOCURRENCY_SERVICE_ID,
+};
+
+pub use crate::stream::EVENT_BUFFER_SIZE;
+
+/// State change made by a committed block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServiceEvent {
+    /// Wallet is created.
+    WalletCreated {
+        /// Height of the block.
+        height: Height,
+        /// Hash of the transaction which created the wallet.
+        tx_hash: Hash,
+        /// Public key of the wallet.
+        pub_key: PublicKey,
+        /// Name of the wallet.
+        name: String,
+    },
+    /// Balance of the wallet is changed.
+    BalanceChanged {
+        /// Height of the block.
+        height: Height,
+        /// Hash of the transaction recorded in the wallet history.
+        tx_hash: Hash,
+        /// Public key of the wallet.
+        pub_key: PublicKey,
+        /// Whether the balance is increased.
+        incoming: bool,
+        /// Amount by which the balance is changed.
+        amount: Amount,
+        /// Balance after the change.
+        balance: Amount,
+    },
+    /// Multisignature transfer is created or settled.
+    MultisigStateChanged {
+        /// Height of the block.
+        height: Height,
+        /// Hash of the `TransferMultisig` transaction.
+        tx_hash: Hash,
+        /// New state of the transfer.
+        state: State,
+    },
+    /// Amount of the expired multisignature transfer cannot be returned to the sender
+    /// and is removed from the total supply.
+    MultisigRefundFailed {
+        /// Height of the block.
+        height: Height,
+        /// Hash of the `TransferMultisig` transaction.
+        tx_hash: Hash,
+        /// Sender of the transfer.
+        from: PublicKey,
+        /// Amount removed from the total supply.
+        amount: Amount,
+    },
+}
+
+/// Emitter of the service events. Clones share the same log file and subscribers.
+#[derive(Debug, Clone, Default)]
+pub struct EventEmitter {
+    log: Option<mpsc::SyncSender<String>>,
+    subscribers: Arc<Mutex<Vec<channel::Sender<ServiceEvent>>>>,
+}
+
+impl EventEmitter {
+    /// Creates an emitter without a log file and subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an emitter appending the events to the file at the given path, one JSON
+    /// object per line. The file is written by a separate thread; if it cannot be opened
+    /// or written, the error is logged and the events are dropped.
+    pub fn with_log_file<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        let (sender, receiver) = mpsc::sync_channel::<String>(EVENT_BUFFER_SIZE);
+        thread::spawn(move || {
+            let file = match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => file,
+                Err(err) => {
+                    error!("Cannot open event log {}: {}", path.display(), err);
+                    return;
+                }
+            };
+            let mut file = LineWriter::new(file);
+            for line in receiver {
+                if let Err(err) = writeln!(file, "{}", line) {
+                    error!("Cannot write event log {}: {}", path.display(), err);
+                }
+            }
+        });
+
+        EventEmitter {
+            log: Some(sender),
+            subscribers: Arc::default(),
+        }
+    }
+
+    /// Subscribes to the events emitted from now on.
+    pub fn subscribe(&self) -> channel::Receiver<ServiceEvent> {
+        let (sender, receiver) = channel::channel(EVENT_BUFFER_SIZE);
+        lock(&self.subscribers).push(sender);
+        receiver
+    }
+
+    /// Returns the number of active subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        lock(&self.subscribers).len()
+    }
+
+    /// Emits the events of the block at the given height. Subscribers with a full buffer
+    /// or a dropped receiver are removed.
+    pub fn emit_block(&self, snapshot: &dyn Snapshot, height: Height) {
+        if self.log.is_none() && self.subscriber_count() == 0 {
+            return;
+        }
+        let events = match panic::catch_unwind(AssertUnwindSafe(|| block_events(snapshot, height)))
+        {
+            Ok(events) => events,
+            Err(_) => {
+                error!("Cannot collect service events of block {}", height);
+                return;
+            }
+        };
+
+        if let Some(ref log) = self.log {
+            let lines = events
+                .iter()
+                .filter_map(|event| serde_json::to_string(event).ok());
+            for line in lines {
+                if let Err(err) = log.try_send(line) {
+                    warn!("Event of block {} is not logged: {}", height, err);
+                }
+            }
+        }
+
+        lock(&self.subscribers).retain(|subscriber| {
+            let mut subscriber = subscriber.clone();
+            events
+                .iter()
+                .all(|event| subscriber.try_send(event.clone()).is_ok())
+        });
+    }
+}
+
+/// Returns the events of the block at the given height in the order of execution
+/// of its transactions, followed by the transfers expired by the block.
+pub fn block_events(snapshot: &dyn Snapshot, height: Height) -> Vec<ServiceEvent> {
+    let general_schema = blockchain::Schema::new(snapshot);
+    let schema = Schema::new(snapshot);
+    let transactions = general_schema.transactions();
+    let results = general_schema.transaction_results();
+    let mut balance_changes = HashMap::new();
+    let mut events = Vec::new();
+
+    for tx_hash in general_schema.block_transactions(height).iter() {
+        let message = match transactions.get(&tx_hash) {
+            Some(message) => message,
+            None => continue,
+        };
+        let raw_tx = message.payload();
+        if raw_tx.service_id() != CRYPTOCURRENCY_SERVICE_ID {
+            continue;
+        }
+        // Failed transactions do not change the state.
+        match results.get(&tx_hash) {
+            Some(ref result) if result.0.is_ok() => {}
+            _ => continue,
+        }
+
+        let settled_transfer = match WalletTransactions::tx_from_raw(raw_tx.clone()) {
+            Ok(WalletTransactions::CreateWallet(tx)) => {
+                events.push(ServiceEvent::WalletCreated {
+                    height,
+                    tx_hash,
+                    pub_key: message.author(),
+                    name: tx.name,
+                });
+                None
+            }
+            Ok(WalletTransactions::CreateWalletFor(tx)) => {
+                events.push(ServiceEvent::WalletCreated {
+                    height,
+                    tx_hash,
+                    pub_key: tx.owner,
+                    name: tx.name,
+                });
+                None
+            }
+            Ok(WalletTransactions::CreateJointWallet(tx)) => {
+                events.push(ServiceEvent::WalletCreated {
+                    height,
+                    tx_hash,
+                    pub_key: joint_wallet_address(&tx.owners),
+                    name: tx.name,
+                });
+                None
+            }
+            Ok(WalletTransactions::TransferMultisig(_)) => {
+                events.push(ServiceEvent::MultisigStateChanged {
+                    height,
+                    tx_hash,
+                    state: State::InProcess,
+                });
+                None
+            }
+            Ok(WalletTransactions::ApproveTransferMultisig(tx)) => Some(*tx.transfer_hash()),
+            Ok(WalletTransactions::RejectTransferMultisig(tx)) => Some(*tx.transfer_hash()),
+            Ok(WalletTransactions::ApproveTransferMultisigBatch(tx)) => Some(tx.tx_hash),
+            _ => None,
+        };
+        if let Some(transfer_hash) = settled_transfer {
+            match schema.multisig_transfer(transfer_hash) {
+                Some(ref transfer)
+                    if transfer.is_terminal() && transfer.settled_at_height == height =>
+                {
+                    events.push(ServiceEvent::MultisigStateChanged {
+                        height,
+                        tx_hash: transfer_hash,
+                        state: transfer.state.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        push_balance_changes(&schema, &tx_hash, height, &mut balance_changes, &mut events);
+    }
+
+    let settled = schema
+        .settled_multisig_transfers()
+        .iter_from(&settled_multisig_key(height, &Hash::zero()))
+        .map(|key| parse_settled_multisig_key(&key))
+        .take_while(|(settled_at, _)| *settled_at == height)
+        .collect::<Vec<_>>();
+    for (_, transfer_hash) in settled {
+        match schema.multisig_transfer(transfer_hash) {
+            Some(ref transfer) if transfer.state == State::Expired => {
+                events.push(ServiceEvent::MultisigStateChanged {
+                    height,
+                    tx_hash: transfer_hash,
+                    state: State::Expired,
+                });
+                if transfer.refund_failed {
+                    events.push(ServiceEvent::MultisigRefundFailed {
+                        height,
+                        tx_hash: transfer_hash,
+                        from: transfer.from,
+                        amount: transfer.amount,
+                    });
+                }
+                push_balance_changes(
+                    &schema,
+                    &transfer_hash,
+                    height,
+                    &mut balance_changes,
+                    &mut events,
+                );
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Pushes the changes of the balances made by the transaction in the block at
+/// the given height. `balance_changes` caches the history records of the block
+/// for every wallet together with the balances after them.
+fn push_balance_changes(
+    schema: &Schema<&dyn Snapshot>,
+    tx_hash: &Hash,
+    height: Height,
+    balance_changes: &mut HashMap<PublicKey, Vec<(HistoryRecord, Amount)>>,
+    events: &mut Vec<ServiceEvent>,
+) {
+    for pub_key in schema.affected_wallets(tx_hash).iter() {
+        let changes = balance_changes
+            .entry(pub_key)
+            .or_insert_with(|| block_history(schema, &pub_key, height));
+        for (record, balance) in changes.iter() {
+            if record.tx_hash == *tx_hash {
+                events.push(ServiceEvent::BalanceChanged {
+                    height,
+                    tx_hash: *tx_hash,
+                    pub_key,
+                    incoming: record.incoming,
+                    amount: record.amount,
+                    balance: *balance,
+                });
+            }
+        }
+    }
+}
+
+/// Returns the history records of the wallet made in the block at the given height
+/// together with the balances after them. The records of the block are the latest
+/// ones, so the balances are restored walking back from the current balance.
+fn block_history(
+    schema: &Schema<&dyn Snapshot>,
+    pub_key: &PublicKey,
+    height: Height,
+) -> Vec<(HistoryRecord, Amount)> {
+    let wallet = match schema.wallet(pub_key) {
+        Some(wallet) => wallet,
+        None => return Vec::new(),
+    };
+    let mut balance = wallet.balance;
+    let mut records = Vec::new();
+    for index in (0..wallet.history_len).rev() {
+        let record = match schema.wallet_history_record(pub_key, index) {
+            Some(ref record) if record.height == height => record.clone(),
+            _ => break,
+        };
+        let balance_before = if record.incoming {
+            balance.saturating_sub(record.amount)
+        } else {
+            balance.saturating_add(record.amount)
+        };
+        records.push((record, balance));
+        balance = balance_before;
+    }
+    records.reverse();
+    records
+}
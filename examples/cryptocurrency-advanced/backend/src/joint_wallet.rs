@@ -0,0 +1,110 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wallets owned jointly by several keys.
+//!
+//! The address of a joint wallet is the hash of its owners, so nobody holds its secret
+//! key and the wallet cannot sign transfers by itself. Instead, an owner proposes
+//! a spend, and the spend is executed once `threshold` distinct owners, including
+//! the proposer, have signed it. Proposals are `MultisignatureTransfer`s with the owners
+//! as approvers, so they are approved the same way as multisignature transfers.
+
+use exonum::{
+    crypto::{HashStream, PublicKey},
+    helpers::Height,
+};
+
+use super::proto;
+use crate::{
+    amount::Amount,
+    multisig_transfer::{MultisigError, MultisignatureTransfer},
+};
+
+/// Returns the address of the wallet owned jointly by the given owners: the hash
+/// of their public keys in ascending order. The order and the duplicates of the keys
+/// do not matter.
+pub fn joint_wallet_address(owners: &[PublicKey]) -> PublicKey {
+    let hash = sorted_owners(owners)
+        .iter()
+        .fold(HashStream::new(), |stream, owner| {
+            stream.update(owner.as_ref())
+        })
+        .hash();
+    PublicKey::from_slice(hash.as_ref()).expect("Hash has the size of a public key")
+}
+
+fn sorted_owners(owners: &[PublicKey]) -> Vec<PublicKey> {
+    let mut owners = owners.to_vec();
+    owners.sort();
+    owners.dedup();
+    owners
+}
+
+/// Owners of a joint wallet stored in the database.
+#[derive(Clone, Debug, ProtobufConvert, PartialEq)]
+#[exonum(pb = "proto::JointWallet", serde_pb_convert)]
+pub struct JointWallet {
+    /// Public keys of the owners in ascending order.
+    pub owners: Vec<PublicKey>,
+    /// Number of distinct owners who must sign a spend from the wallet.
+    pub threshold: u32,
+}
+
+impl JointWallet {
+    /// Creates the owners of a joint wallet. The keys are sorted and deduplicated.
+    pub fn new(owners: &[PublicKey], threshold: u32) -> Self {
+        Self {
+            owners: sorted_owners(owners),
+            threshold,
+        }
+    }
+
+    /// Returns the address of the wallet.
+    pub fn address(&self) -> PublicKey {
+        joint_wallet_address(&self.owners)
+    }
+
+    /// Shows if the threshold can be met by the owners, i.e., it is positive and does
+    /// not exceed the number of the owners.
+    pub fn is_threshold_reachable(&self) -> bool {
+        self.threshold > 0 && self.threshold as usize <= self.owners.len()
+    }
+
+    /// Shows if the key is one of the owners.
+    pub fn is_owner(&self, key: &PublicKey) -> bool {
+        self.owners.binary_search(key).is_ok()
+    }
+
+    /// Creates the proposal of the owner to transfer `amount` from the wallet to `to`
+    /// in the block at the given height. The proposal is signed by the proposer and
+    /// is done at once if the threshold is `1`.
+    pub fn propose(
+        &self,
+        proposer: PublicKey,
+        to: PublicKey,
+        amount: Amount,
+        height: Height,
+    ) -> Result<MultisignatureTransfer, MultisigError> {
+        MultisignatureTransfer::new(
+            self.address(),
+            to,
+            amount,
+            self.owners.clone(),
+            self.threshold,
+            // Proposals do not expire.
+            Height(0),
+        )
+        .approve(proposer, height)
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing of public keys passed to the service API.
+//!
+//! Besides the plain hex encoding used by Exonum, keys may be `0x`-prefixed hex
+//! or base64 (either the standard or the URL-safe alphabet). The `deserialize`
+//! functions are meant for `#[serde(deserialize_with)]` attributes of the query
+//! structs; serialization is left to the default hex encoding.
+
+use exonum::crypto::{PublicKey, PUBLIC_KEY_LENGTH};
+use serde::{de, Deserialize, Deserializer};
+
+use std::fmt;
+
+/// Encoding of a public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEncoding {
+    /// Plain hex.
+    Hex,
+    /// Hex prefixed with `0x`.
+    PrefixedHex,
+    /// Base64 with the standard or the URL-safe alphabet.
+    Base64,
+}
+
+impl fmt::Display for KeyEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            KeyEncoding::Hex => "hex",
+            KeyEncoding::PrefixedHex => "0x-prefixed hex",
+            KeyEncoding::Base64 => "base64",
+        })
+    }
+}
+
+/// Error of parsing a public key.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum KeyParseError {
+    /// The string is not valid in any of the accepted encodings.
+    #[fail(
+        display = "public key `{}` is neither hex, 0x-prefixed hex nor base64",
+        _0
+    )]
+    UnknownEncoding(String),
+    /// The string is valid `0x`-prefixed hex, but the digits are not.
+    #[fail(display = "public key `{}` is not valid 0x-prefixed hex", _0)]
+    InvalidPrefixedHex(String),
+    /// The string is decoded, but the key has a wrong length.
+    #[fail(
+        display = "public key must be {} bytes long, got {} bytes decoded from {}",
+        expected, actual, encoding
+    )]
+    WrongLength {
+        /// Encoding the key was decoded from.
+        encoding: KeyEncoding,
+        /// Expected length of the key in bytes.
+        expected: usize,
+        /// Length of the decoded key in bytes.
+        actual: usize,
+    },
+}
+
+/// Parses a public key in any of the accepted encodings.
+pub fn parse_public_key(s: &str) -> Result<PublicKey, KeyParseError> {
+    let s = s.trim();
+    if s.starts_with("0x") || s.starts_with("0X") {
+        let bytes =
+            hex::decode(&s[2..]).map_err(|_| KeyParseError::InvalidPrefixedHex(s.to_owned()))?;
+        return key_from_bytes(&bytes, KeyEncoding::PrefixedHex);
+    }
+
+    let hex_key = hex::decode(s)
+        .ok()
+        .map(|bytes| key_from_bytes(&bytes, KeyEncoding::Hex));
+    match hex_key {
+        Some(Ok(key)) => Ok(key),
+        // Strings of hex digits are valid base64 as well, so fall back to base64
+        // if it produces a key of the right length.
+        Some(Err(hex_error)) => match decode_base64(s) {
+            Some(bytes) if bytes.len() == PUBLIC_KEY_LENGTH => {
+                key_from_bytes(&bytes, KeyEncoding::Base64)
+            }
+            _ => Err(hex_error),
+        },
+        None => match decode_base64(s) {
+            Some(bytes) => key_from_bytes(&bytes, KeyEncoding::Base64),
+            None => Err(KeyParseError::UnknownEncoding(s.to_owned())),
+        },
+    }
+}
+
+/// Deserializes a public key in any of the accepted encodings.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<PublicKey, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_public_key(&s).map_err(de::Error::custom)
+}
+
+/// Deserializes a list of public keys in any of the accepted encodings.
+pub fn deserialize_vec<'de, D>(deserializer: D) -> Result<Vec<PublicKey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| parse_public_key(s).map_err(de::Error::custom))
+        .collect()
+}
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    base64::decode_config(s, base64::STANDARD)
+        .or_else(|_| base64::decode_config(s, base64::URL_SAFE))
+        .ok()
+}
+
+fn key_from_bytes(bytes: &[u8], encoding: KeyEncoding) -> Result<PublicKey, KeyParseError> {
+    PublicKey::from_slice(bytes).ok_or_else(|| KeyParseError::WrongLength {
+        encoding,
+        expected: PUBLIC_KEY_LENGTH,
+        actual: bytes.len(),
+    })
+}
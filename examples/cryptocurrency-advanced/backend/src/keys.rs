@@ -0,0 +1,82 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic derivation of wallet keys from a master secret.
+//!
+//! The seed of the key pair with index `i` is HKDF-SHA256 (RFC 5869) of the master
+//! secret with
+//!
+//! - salt `DERIVATION_SALT`, i.e., the ASCII string `exonum-cryptocurrency-wallet`,
+//! - info `DERIVATION_INFO` followed by `i` as 4 big-endian bytes,
+//! - output length of 32 bytes.
+//!
+//! The key pair is the Ed25519 key pair generated from this seed, so any HKDF and
+//! Ed25519 implementation derives the same keys.
+
+use exonum::crypto::{self, Hash, HashStream, PublicKey, SecretKey, Seed, HASH_SIZE, SEED_LENGTH};
+
+use std::ops::Range;
+
+/// Salt of the HKDF extraction step.
+pub const DERIVATION_SALT: &[u8] = b"exonum-cryptocurrency-wallet";
+/// Prefix of the HKDF info, followed by the big-endian index of the key pair.
+pub const DERIVATION_INFO: &[u8] = b"wallet";
+
+/// Size of the SHA-256 input block, used by HMAC.
+const BLOCK_SIZE: usize = 64;
+
+/// Derives the key pair with the given index from the master secret.
+pub fn derive_keypair(master_seed: &[u8], index: u32) -> (PublicKey, SecretKey) {
+    crypto::gen_keypair_from_seed(&derive_seed(master_seed, index))
+}
+
+/// Derives the key pairs with the indices from the given range.
+pub fn derive_keypairs(master_seed: &[u8], indices: Range<u32>) -> Vec<(PublicKey, SecretKey)> {
+    let prk = hmac(DERIVATION_SALT, master_seed);
+    indices
+        .map(|index| crypto::gen_keypair_from_seed(&expand(&prk, index)))
+        .collect()
+}
+
+/// Derives the Ed25519 seed of the key pair with the given index.
+pub fn derive_seed(master_seed: &[u8], index: u32) -> Seed {
+    expand(&hmac(DERIVATION_SALT, master_seed), index)
+}
+
+/// HKDF expansion step producing a single block of output.
+fn expand(prk: &Hash, index: u32) -> Seed {
+    let mut info = DERIVATION_INFO.to_vec();
+    info.extend_from_slice(&index.to_be_bytes());
+    info.push(1);
+    let okm = hmac(prk.as_ref(), &info);
+    debug_assert_eq!(HASH_SIZE, SEED_LENGTH);
+    Seed::from_slice(okm.as_ref()).expect("SHA-256 output is a valid seed")
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac(key: &[u8], message: &[u8]) -> Hash {
+    let mut block = [0; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..HASH_SIZE].copy_from_slice(crypto::hash(key).as_ref());
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let pad = |byte: u8| -> Vec<u8> { block.iter().map(|b| b ^ byte).collect() };
+    let inner = HashStream::new().update(&pad(0x36)).update(message).hash();
+    HashStream::new()
+        .update(&pad(0x5c))
+        .update(inner.as_ref())
+        .hash()
+}
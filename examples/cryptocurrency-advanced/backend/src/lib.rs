@@ -26,38 +26,173 @@ extern crate exonum_derive;
 #[macro_use]
 extern crate failure;
 #[macro_use]
+extern crate log;
+#[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 
-pub use crate::schema::Schema;
+pub use crate::{amount::Amount, schema::Schema};
 
+// Goes first, since the other modules use its `impl_pb_storage` macro.
+#[macro_use]
+pub mod amount;
 pub mod api;
+pub mod cli;
+pub mod client;
+pub mod config;
+pub mod due;
+pub mod events;
+pub mod joint_wallet;
+pub mod key_encoding;
+pub mod keys;
+pub mod metrics;
+pub mod migration;
 pub mod multisig_transfer;
+pub mod payment_stream;
 pub mod proto;
 pub mod schema;
+pub mod staking;
+pub mod stream;
 pub mod transactions;
+pub mod tx_encoding;
+pub mod unsigned;
 pub mod wallet;
+pub mod wallet_id;
+pub mod webhooks;
 
 use exonum::{
     api::ServiceApiBuilder,
-    blockchain::{self, Transaction, TransactionSet},
+    blockchain::{self, ServiceContext, Transaction, TransactionSet},
     crypto::Hash,
-    helpers::fabric::{self, Context},
+    helpers::{
+        fabric::{self, Argument, Command, CommandExtension, CommandName, Context},
+        Height,
+    },
     messages::RawTransaction,
-    storage::Snapshot,
+    storage::{Fork, Snapshot},
 };
 
-use crate::transactions::WalletTransactions;
+use serde_json::Value;
+
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{
+    config::Config, events::EventEmitter, metrics::BlockSummary, stream::TransactionStream,
+    transactions::WalletTransactions, webhooks::WebhookRegistry,
+};
 
 /// Unique service ID.
-const CRYPTOCURRENCY_SERVICE_ID: u16 = 128;
+pub const CRYPTOCURRENCY_SERVICE_ID: u16 = 128;
 /// Name of the service.
 const SERVICE_NAME: &str = "cryptocurrency";
-/// Initial balance of the wallet.
+/// Default initial balance of the wallet in whole units. See `Config::initial_balance`.
 const INITIAL_BALANCE: u64 = 100;
 
+/// Default number of blocks between balance checkpoints.
+pub const DEFAULT_BALANCE_CHECKPOINT_INTERVAL: u64 = 1_000;
+
 /// Exonum `Service` implementation.
-#[derive(Default, Debug)]
-pub struct Service;
+#[derive(Debug)]
+pub struct Service {
+    config: Config,
+    balance_checkpoint_interval: u64,
+    max_history_entries: Option<u64>,
+    transaction_stream: TransactionStream,
+    event_emitter: EventEmitter,
+    webhooks: WebhookRegistry,
+}
+
+impl Service {
+    /// Creates a service writing balance checkpoints of the changed wallets every
+    /// `interval` blocks. Checkpoints speed up historical balance queries and do not
+    /// affect the state hash, so the interval may differ between nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn with_balance_checkpoint_interval(interval: u64) -> Self {
+        assert!(interval > 0, "Balance checkpoint interval must be positive");
+        Service {
+            config: Config::default(),
+            balance_checkpoint_interval: interval,
+            max_history_entries: None,
+            transaction_stream: TransactionStream::new(),
+            event_emitter: EventEmitter::new(),
+            webhooks: WebhookRegistry::new(),
+        }
+    }
+
+    /// Enables pruning of the wallet histories: of every history at least
+    /// `max_entries` latest records are kept, while older ones are dropped a chunk
+    /// at a time. Pruning does not change `history_hash` of the wallets and does not
+    /// affect the state hash, so the setting may differ between nodes. Pruned records
+    /// can no longer be retrieved or proven by the node. Nodes built by `ServiceFactory`
+    /// take the setting from the `--max-history-entries` argument of the `run` command.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_entries` is zero.
+    pub fn with_max_history_entries(mut self, max_entries: u64) -> Self {
+        assert!(
+            max_entries > 0,
+            "Number of retained history entries must be positive"
+        );
+        self.max_history_entries = Some(max_entries);
+        self
+    }
+
+    /// Sets the configuration written into the genesis block. Later changes of
+    /// the configuration are made through the global configuration of the blockchain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configuration is invalid.
+    pub fn with_config(mut self, config: Config) -> Self {
+        if let Err(errors) = config.validate() {
+            panic!("Invalid service configuration: {:?}", errors);
+        }
+        self.config = config;
+        self
+    }
+
+    /// Returns the stream of transactions of the service committed by this node.
+    pub fn transaction_stream(&self) -> TransactionStream {
+        self.transaction_stream.clone()
+    }
+
+    /// Emits the state changes of the committed blocks with the given emitter,
+    /// e.g., the one created by `EventEmitter::with_log_file`.
+    pub fn with_event_emitter(mut self, emitter: EventEmitter) -> Self {
+        self.event_emitter = emitter;
+        self
+    }
+
+    /// Returns the emitter of the state changes made by the blocks committed by this
+    /// node, which external indexers can subscribe to.
+    pub fn event_emitter(&self) -> EventEmitter {
+        self.event_emitter.clone()
+    }
+
+    /// Notifies the webhooks of the given registry of the wallets touched by the blocks
+    /// committed by this node, e.g., a registry created with
+    /// `WebhookRegistry::with_storage`.
+    pub fn with_webhooks(mut self, webhooks: WebhookRegistry) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// Returns the webhooks registered with this node.
+    pub fn webhooks(&self) -> WebhookRegistry {
+        self.webhooks.clone()
+    }
+}
+
+impl Default for Service {
+    fn default() -> Self {
+        Self::with_balance_checkpoint_interval(DEFAULT_BALANCE_CHECKPOINT_INTERVAL)
+    }
+}
 
 impl blockchain::Service for Service {
     fn service_id(&self) -> u16 {
@@ -77,12 +212,69 @@ impl blockchain::Service for Service {
         WalletTransactions::tx_from_raw(raw).map(Into::into)
     }
 
+    /// Marks the data of a new blockchain as written in the current layout, summarizes
+    /// the genesis block and returns the configuration of the service. Blockchains
+    /// created by the previous versions of the service are migrated in `before_commit`.
+    fn initialize(&self, fork: &mut Fork) -> Value {
+        let mut schema = Schema::new(fork);
+        schema
+            .schema_version_entry_mut()
+            .set(migration::SCHEMA_VERSION);
+        // `before_commit` is not called for the genesis block.
+        schema.block_summaries_mut().push(BlockSummary {
+            height: Height(0),
+            transactions: 0,
+            failed_transactions: 0,
+            amount_moved: Amount::ZERO,
+        });
+        serde_json::to_value(&self.config).expect("Cannot serialize service configuration")
+    }
+
+    fn before_commit(&self, fork: &mut Fork) {
+        migration::migrate(fork);
+        metrics::record_block(fork);
+
+        let height = blockchain::Schema::new(&*fork).height().next();
+        let mut schema = Schema::new(fork);
+        if height.0 % self.balance_checkpoint_interval == 0 {
+            schema.record_balance_checkpoints(height);
+        }
+        schema.process_due_items(height);
+        let config = schema.config();
+        if height.0 % config.staking_reward_interval == 0 {
+            schema.accrue_staking_rewards(
+                config.staking_reward_rate,
+                config.staking_supply_cap.unwrap_or(Amount::MAX),
+            );
+        }
+        schema.prune_wallet_histories(self.max_history_entries);
+        if let Some(retention) = config.settled_multisig_retention {
+            schema.collect_settled_multisig_transfers(height, retention);
+        }
+        metrics::record_block_summary(fork);
+    }
+
+    fn after_commit(&self, context: &ServiceContext) {
+        self.transaction_stream
+            .publish_block(context.snapshot(), context.height());
+        self.event_emitter
+            .emit_block(context.snapshot(), context.height());
+        self.webhooks
+            .notify_block(context.snapshot(), context.height());
+    }
+
     fn wire_api(&self, builder: &mut ServiceApiBuilder) {
         api::PublicApi::wire(builder);
+        api::PublicApi::wire_transaction_stream(builder, self.transaction_stream());
+        api::PrivateApi::wire(builder);
+        api::PrivateApi::wire_webhooks(builder, self.webhooks());
     }
 }
 
 /// A configuration service creator for the `NodeBuilder`.
+///
+/// The `run` command of the node accepts `--max-history-entries <count>`, which
+/// enables pruning of the wallet histories; see `Service::with_max_history_entries`.
 #[derive(Debug)]
 pub struct ServiceFactory;
 
@@ -91,7 +283,63 @@ impl fabric::ServiceFactory for ServiceFactory {
         SERVICE_NAME
     }
 
-    fn make_service(&mut self, _: &Context) -> Box<dyn blockchain::Service> {
-        Box::new(Service)
+    fn command(&mut self, command: CommandName) -> Option<Box<dyn CommandExtension>> {
+        if command == fabric::Run.name() {
+            Some(Box::new(RunExtension))
+        } else {
+            None
+        }
     }
+
+    fn make_service(&mut self, run_context: &Context) -> Box<dyn blockchain::Service> {
+        let mut service = Service::default();
+        // The value is validated by `RunExtension`.
+        if let Ok(max_entries) = run_context.arg::<u64>(MAX_HISTORY_ENTRIES_ARG) {
+            service = service.with_max_history_entries(max_entries);
+        }
+        Box::new(service)
+    }
+}
+
+/// Name of the `run` command argument holding the number of retained history entries.
+pub const MAX_HISTORY_ENTRIES_ARG: &str = "MAX_HISTORY_ENTRIES";
+
+/// Node-local settings of the service added to the `run` command.
+#[derive(Debug)]
+struct RunExtension;
+
+impl CommandExtension for RunExtension {
+    fn args(&self) -> Vec<Argument> {
+        vec![Argument::new_named(
+            MAX_HISTORY_ENTRIES_ARG,
+            false,
+            "Number of the latest wallet history entries retained by the node; \
+             older entries are pruned. The whole history is retained by default.",
+            None,
+            "max-history-entries",
+            false,
+        )]
+    }
+
+    fn execute(&self, context: Context) -> Result<Context, failure::Error> {
+        if let Ok(value) = context.arg::<String>(MAX_HISTORY_ENTRIES_ARG) {
+            match value.parse::<u64>() {
+                Ok(max_entries) if max_entries > 0 => {}
+                _ => bail!(
+                    "`max-history-entries` should be a positive integer, got `{}`",
+                    value
+                ),
+            }
+        }
+        Ok(context)
+    }
+}
+
+/// Locks the mutex even if a thread has panicked while holding the lock. The
+/// subscriber lists and registrations guarded this way stay consistent anyway, and
+/// the commit path must not panic because of them.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
 }
@@ -16,12 +16,37 @@ use exonum::helpers::fabric::NodeBuilder;
 use exonum_configuration as configuration;
 use exonum_cryptocurrency_advanced as cryptocurrency;
 
+use std::{env, process};
+
+use exonum_cryptocurrency_advanced::cli::{IssueCommand, ISSUE_COMMAND};
+
 fn main() {
     exonum::crypto::init();
     exonum::helpers::init_logger().unwrap();
 
+    // `NodeBuilder` does not accept custom commands, so the administrative ones
+    // are dispatched before it parses the command line.
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some(ISSUE_COMMAND) {
+        issue(args);
+    }
+
     let node = NodeBuilder::new()
         .with_service(Box::new(configuration::ServiceFactory))
         .with_service(Box::new(cryptocurrency::ServiceFactory));
     node.run();
 }
+
+fn issue(args: Vec<String>) -> ! {
+    let command = IssueCommand::from_args(args).unwrap_or_else(|err| err.exit());
+    match command.run() {
+        Ok((tx_hash, status)) => {
+            println!("Transaction {} is committed: {}", tx_hash.to_hex(), status);
+            process::exit(if status.is_success() { 0 } else { 1 })
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1)
+        }
+    }
+}
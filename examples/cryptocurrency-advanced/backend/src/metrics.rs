@@ -0,0 +1,246 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transaction counters and per-block summaries of the cryptocurrency service.
+//!
+//! Counters are updated in `before_commit` for every block rather than in the transactions
+//! themselves, since changes made by a failed transaction are rolled back.
+
+use exonum::{
+    blockchain::{self, TransactionErrorType, TransactionSet},
+    helpers::Height,
+    proto::ProtobufConvert,
+    storage::{Fork, Snapshot},
+};
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    amount::Amount, proto, transactions::WalletTransactions, Schema, CRYPTOCURRENCY_SERVICE_ID,
+};
+
+/// Transaction counters of the service.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ServiceMetrics {
+    /// Number of successfully executed transactions by transaction type.
+    pub transactions: BTreeMap<String, u64>,
+    /// Number of failed transactions by error code. Panics are not counted.
+    pub errors: BTreeMap<u8, u64>,
+}
+
+impl ServiceMetrics {
+    /// Reads the counters from the database.
+    pub fn new<T: AsRef<dyn Snapshot>>(schema: &Schema<T>) -> Self {
+        ServiceMetrics {
+            transactions: schema.transaction_counts().iter().collect(),
+            errors: schema.transaction_error_counts().iter().collect(),
+        }
+    }
+
+    /// Renders the counters in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        writeln!(
+            text,
+            "# HELP cryptocurrency_transactions_total Number of successfully executed transactions."
+        )
+        .unwrap();
+        writeln!(text, "# TYPE cryptocurrency_transactions_total counter").unwrap();
+        for (tx_type, count) in &self.transactions {
+            writeln!(
+                text,
+                "cryptocurrency_transactions_total{{type=\"{}\"}} {}",
+                tx_type, count
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            text,
+            "# HELP cryptocurrency_transaction_errors_total Number of failed transactions."
+        )
+        .unwrap();
+        writeln!(
+            text,
+            "# TYPE cryptocurrency_transaction_errors_total counter"
+        )
+        .unwrap();
+        for (code, count) in &self.errors {
+            writeln!(
+                text,
+                "cryptocurrency_transaction_errors_total{{code=\"{}\"}} {}",
+                code, count
+            )
+            .unwrap();
+        }
+        text
+    }
+}
+
+/// Updates the counters with the results of the service transactions in the block
+/// being committed.
+pub fn record_block(fork: &mut Fork) {
+    let mut executed = Vec::new();
+    let mut errors = Vec::new();
+    {
+        let schema = blockchain::Schema::new(&*fork);
+        let height = schema.height().next();
+        let transactions = schema.transactions();
+        let results = schema.transaction_results();
+
+        for tx_hash in schema.block_transactions(height).iter() {
+            let message = match transactions.get(&tx_hash) {
+                Some(message) => message,
+                None => continue,
+            };
+            let raw_tx = message.payload();
+            if raw_tx.service_id() != CRYPTOCURRENCY_SERVICE_ID {
+                continue;
+            }
+
+            match results.get(&tx_hash).map(|result| result.0) {
+                Some(Ok(())) => {
+                    if let Ok(tx) = WalletTransactions::tx_from_raw(raw_tx.clone()) {
+                        executed.push(tx.name());
+                    }
+                }
+                Some(Err(e)) => {
+                    if let TransactionErrorType::Code(code) = e.error_type() {
+                        errors.push(code);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    let mut schema = Schema::new(fork);
+    for tx_type in executed {
+        schema.record_executed_transaction(tx_type);
+    }
+    for code in errors {
+        schema.record_transaction_error(code);
+    }
+}
+
+/// Activity of the service in a single block. Every block, including the genesis one
+/// and the blocks without service transactions, has a summary, so the summary of
+/// the block at height `h` is stored at index `h`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockSummary {
+    /// Height of the block.
+    pub height: Height,
+    /// Number of the service transactions in the block, including the failed ones.
+    pub transactions: u64,
+    /// Number of the service transactions which failed or panicked.
+    pub failed_transactions: u64,
+    /// Amount moved by the transfers of the block, as counted by `Schema::transfer_volume`.
+    pub amount_moved: Amount,
+}
+
+impl BlockSummary {
+    /// Counts the service transactions of the block at the given height, either committed
+    /// or executed in the block being committed. The moved amount cannot be restored
+    /// from the transactions, so it is supplied by the caller.
+    pub fn new<T>(schema: &blockchain::Schema<T>, height: Height, amount_moved: Amount) -> Self
+    where
+        T: AsRef<dyn Snapshot>,
+    {
+        let transactions = schema.transactions();
+        let results = schema.transaction_results();
+        let service_txs = schema
+            .block_transactions(height)
+            .iter()
+            .filter(|tx_hash| {
+                transactions.get(tx_hash).map_or(false, |message| {
+                    message.payload().service_id() == CRYPTOCURRENCY_SERVICE_ID
+                })
+            })
+            .collect::<Vec<_>>();
+        let failed = service_txs
+            .iter()
+            .filter(|tx_hash| {
+                results
+                    .get(*tx_hash)
+                    .map_or(false, |result| result.0.is_err())
+            })
+            .count();
+
+        Self {
+            height,
+            transactions: service_txs.len() as u64,
+            failed_transactions: failed as u64,
+            amount_moved,
+        }
+    }
+}
+
+impl ProtobufConvert for BlockSummary {
+    type ProtoStruct = proto::BlockSummary;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::BlockSummary::new();
+        pb.set_height(self.height.to_pb());
+        pb.set_transactions(self.transactions);
+        pb.set_failed_transactions(self.failed_transactions);
+        let (amount_moved, exact_amount_moved) = self.amount_moved.to_pb_fields();
+        pb.set_amount_moved(amount_moved);
+        if let Some(exact_amount_moved) = exact_amount_moved {
+            pb.set_exact_amount_moved(exact_amount_moved);
+        }
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            height: Height::from_pb(pb.get_height())?,
+            transactions: pb.get_transactions(),
+            failed_transactions: pb.get_failed_transactions(),
+            amount_moved: Amount::from_pb_fields(
+                pb.get_amount_moved(),
+                pb.get_exact_amount_moved(),
+            ),
+        })
+    }
+}
+
+impl_pb_storage!(BlockSummary);
+
+/// Appends the summary of the block being committed. The summary is written at the end
+/// of `before_commit`, so that it covers the transfers made by the scheduled items.
+/// Blocks whose `before_commit` was rolled back are left without summaries, so they are
+/// summarized first.
+pub fn record_block_summary(fork: &mut Fork) {
+    let summaries = {
+        let general_schema = blockchain::Schema::new(&*fork);
+        let schema = Schema::new(&*fork);
+        let from = schema.block_summaries().len();
+        let to = general_schema.height().next().0;
+        (from..=to)
+            .map(Height)
+            .map(|height| {
+                let amount_moved = schema.block_transfer_volume(height);
+                BlockSummary::new(&general_schema, height, amount_moved)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for summary in summaries {
+        schema
+            .block_transfer_volumes_mut()
+            .remove(&summary.height.0);
+        schema.block_summaries_mut().push(summary);
+    }
+}
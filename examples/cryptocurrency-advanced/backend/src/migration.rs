@@ -0,0 +1,458 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! One-time migrations of the data written by the previous versions of the service.
+//!
+//! Migrations run in `before_commit`, so every node performs them at the same height
+//! and the resulting state hash is the same across the network.
+//!
+//! The layout of the stored data is versioned. Each change of the layout appends
+//! a step to `MIGRATIONS` and bumps `SCHEMA_VERSION`; step `i` converts the data
+//! of version `i` to version `i + 1`. Blockchains created by the current version
+//! of the service start at `SCHEMA_VERSION` and skip the steps altogether.
+
+use exonum::{
+    blockchain,
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+    storage::{Fork, Snapshot},
+};
+
+use std::collections::HashMap;
+
+use crate::{
+    amount::Amount,
+    metrics::BlockSummary,
+    multisig_transfer::MultisignatureTransfer,
+    schema::{settled_multisig_key, wallet_name_key},
+    transactions::{service_tx, transfer_multisig_tx, WalletTransactions},
+    wallet::HistoryRecord,
+    Schema, INITIAL_BALANCE,
+};
+
+/// Version of the layout of the data written by the current version of the service.
+pub const SCHEMA_VERSION: u32 = 14;
+
+/// Migration steps in the order of the schema versions. Steps written before
+/// the data was versioned are additionally guarded by their own flags, since
+/// a blockchain of version `0` may have passed some of them already.
+const MIGRATIONS: [fn(&mut Fork); SCHEMA_VERSION as usize] = [
+    migrate_legacy_histories,
+    migrate_legacy_wallets,
+    migrate_legacy_multisig_transfers,
+    build_balance_index,
+    fill_last_active_heights,
+    index_settled_multisig_transfers,
+    fill_metadata_hashes,
+    chunk_wallet_histories,
+    mark_rejected_multisig_refunded,
+    rebuild_balance_index,
+    index_multisig_participants,
+    fill_block_summaries,
+    index_wallet_name_holders,
+    fill_history_chains,
+];
+
+/// Brings the stored data to the current layout by running the migration steps
+/// newer than the stored schema version. Once the data is migrated, only
+/// the version is checked.
+pub fn migrate(fork: &mut Fork) {
+    let version = Schema::new(&*fork).schema_version();
+    if version >= SCHEMA_VERSION {
+        return;
+    }
+    // The earlier steps read the wallet histories, so the histories are brought
+    // to the chunked layout before them; the step itself finds nothing left.
+    chunk_wallet_histories(fork);
+    for step in &MIGRATIONS[version as usize..] {
+        step(fork);
+    }
+    Schema::new(fork)
+        .schema_version_entry_mut()
+        .set(SCHEMA_VERSION);
+}
+
+/// Converts wallet histories written in the previous formats to history chunks:
+/// lists of bare transaction hashes and single lists of `HistoryRecord`s. Heights
+/// and balance changes of the bare hashes are restored from the committed
+/// transactions. The migration runs once; afterwards only a flag is checked.
+pub fn migrate_legacy_histories(fork: &mut Fork) {
+    let migrated = {
+        let general_schema = blockchain::Schema::new(&*fork);
+        let schema = Schema::new(&*fork);
+        if schema.legacy_history_migrated() {
+            return;
+        }
+
+        schema
+            .wallets()
+            .keys()
+            .filter_map(|pub_key| {
+                // A wallet has a history in at most one of the previous formats.
+                let records = schema
+                    .legacy_wallet_history(&pub_key)
+                    .iter()
+                    .map(|tx_hash| legacy_record(&general_schema, &pub_key, tx_hash))
+                    .chain(schema.unchunked_wallet_history(&pub_key).iter())
+                    .collect::<Vec<_>>();
+                if records.is_empty() {
+                    None
+                } else {
+                    Some((pub_key, records))
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for (pub_key, records) in migrated {
+        schema.migrate_legacy_history(&pub_key, records);
+    }
+    schema.legacy_history_migrated_entry_mut().set(true);
+}
+
+/// Rewrites the wallets written by the previous versions of the service with
+/// `last_tx_hash` restored from the wallet history. Histories are migrated beforehand,
+/// so the latest records are available. The migration runs once; afterwards only
+/// a flag is checked.
+pub fn migrate_legacy_wallets(fork: &mut Fork) {
+    if Schema::new(&*fork).legacy_wallets_migrated() {
+        return;
+    }
+    rewrite_wallets(fork);
+    Schema::new(fork)
+        .legacy_wallets_migrated_entry_mut()
+        .set(true);
+}
+
+/// Rewrites the wallets written before the wallet metadata was introduced with
+/// `metadata_hash` of their (empty) metadata.
+pub fn fill_metadata_hashes(fork: &mut Fork) {
+    rewrite_wallets(fork);
+}
+
+/// Rewrites the wallets written before the chain of the history chunk roots was
+/// introduced with `history_chain` of their filled chunks.
+pub fn fill_history_chains(fork: &mut Fork) {
+    rewrite_wallets(fork);
+}
+
+/// Rewrites all wallets in the current format. Fields missing from the stored values
+/// are restored by `Schema::wallet`.
+fn rewrite_wallets(fork: &mut Fork) {
+    let wallets = {
+        let schema = Schema::new(&*fork);
+        schema
+            .raw_wallets()
+            .keys()
+            .filter_map(|pub_key| schema.wallet(&pub_key))
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for wallet in wallets {
+        let pub_key = wallet.pub_key;
+        schema.wallets_mut().put(&pub_key, wallet);
+    }
+}
+
+/// Fills in `last_active_at` of the wallets not changed since the field was introduced
+/// with the height of the latest record of the wallet history. The latest record is
+/// never pruned, and the histories are migrated beforehand, so the heights are known.
+/// The migration runs once; afterwards only a flag is checked.
+pub fn fill_last_active_heights(fork: &mut Fork) {
+    let wallets = {
+        let schema = Schema::new(&*fork);
+        if schema.last_activity_filled() {
+            return;
+        }
+        schema
+            .wallets()
+            .values()
+            .filter(|wallet| wallet.last_active_at == Height(0))
+            .filter_map(|wallet| {
+                let latest = wallet.history_len.checked_sub(1)?;
+                let record = schema.wallet_history_record(&wallet.pub_key, latest)?;
+                Some(wallet.touch(record.height))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for wallet in wallets {
+        let pub_key = wallet.pub_key;
+        schema.wallets_mut().put(&pub_key, wallet);
+    }
+    schema.last_activity_filled_entry_mut().set(true);
+}
+
+/// Adds the wallets created before the balance index was introduced to the index.
+/// Wallets updated since then are already indexed, and inserting them again is a no-op.
+/// The migration runs once; afterwards only a flag is checked.
+pub fn build_balance_index(fork: &mut Fork) {
+    let balances = {
+        let schema = Schema::new(&*fork);
+        if schema.balance_index_built() {
+            return;
+        }
+        schema
+            .wallets()
+            .values()
+            .map(|wallet| (wallet.pub_key, wallet.balance))
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for (pub_key, balance) in balances {
+        schema.insert_balance(&pub_key, balance);
+    }
+    schema.balance_index_built_entry_mut().set(true);
+}
+
+/// Rebuilds the balance index with the keys holding the full `Amount` balances instead
+/// of the whole units written by the previous versions of the service.
+pub fn rebuild_balance_index(fork: &mut Fork) {
+    let balances = Schema::new(&*fork)
+        .wallets()
+        .values()
+        .map(|wallet| (wallet.pub_key, wallet.balance))
+        .collect::<Vec<_>>();
+
+    let mut schema = Schema::new(fork);
+    schema.balance_index_mut().clear();
+    for (pub_key, balance) in balances {
+        schema.insert_balance(&pub_key, balance);
+    }
+}
+
+/// Adds the multisignature transfers settled before the transfers were collected
+/// to the set of settled transfers. Their settlement heights are not known, so they
+/// are collected as soon as the retention period passes since the genesis block.
+pub fn index_settled_multisig_transfers(fork: &mut Fork) {
+    let settled = {
+        let schema = Schema::new(&*fork);
+        schema
+            .multisig_transfers()
+            .iter()
+            .filter(|(_, transfer)| transfer.is_terminal())
+            .map(|(tx_hash, transfer)| settled_multisig_key(transfer.settled_at_height, &tx_hash))
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for key in settled {
+        schema.settled_multisig_transfers_mut().insert(key);
+    }
+}
+
+/// Marks the multisignature transfers rejected before the refunds were recorded
+/// as refunded, since their amounts were returned to the senders on rejection.
+pub fn mark_rejected_multisig_refunded(fork: &mut Fork) {
+    let rejected = {
+        let schema = Schema::new(&*fork);
+        schema
+            .multisig_transfers()
+            .iter()
+            .filter(|(_, transfer)| transfer.is_rejected() && !transfer.refunded)
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for (tx_hash, transfer) in rejected {
+        schema.multisig_transfers_mut().put(
+            &tx_hash,
+            MultisignatureTransfer {
+                refunded: true,
+                ..transfer
+            },
+        );
+    }
+}
+
+/// Rewrites the multisignature transfers written by the previous versions of the service
+/// in the current format. Fields missing from the stored values are restored by
+/// `Schema::multisig_transfer`. The migration runs once; afterwards only a flag is checked.
+pub fn migrate_legacy_multisig_transfers(fork: &mut Fork) {
+    let migrated = {
+        let schema = Schema::new(&*fork);
+        if schema.legacy_multisig_migrated() {
+            return;
+        }
+
+        schema
+            .raw_multisig_transfers()
+            .keys()
+            .filter_map(|tx_hash| {
+                schema
+                    .multisig_transfer(tx_hash)
+                    .map(|transfer| (tx_hash, transfer))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for (tx_hash, transfer) in migrated {
+        schema.multisig_transfers_mut().put(&tx_hash, transfer);
+    }
+    schema.legacy_multisig_migrated_entry_mut().set(true);
+}
+
+/// Adds the stored multisignature transfers created before the per-receiver and
+/// per-approver sets were introduced to the sets.
+pub fn index_multisig_participants(fork: &mut Fork) {
+    let transfers = Schema::new(&*fork)
+        .multisig_transfers()
+        .iter()
+        .collect::<Vec<_>>();
+
+    let mut schema = Schema::new(fork);
+    for (tx_hash, transfer) in transfers {
+        schema.index_multisig_participants(tx_hash, &transfer);
+    }
+}
+
+/// Summarizes the blocks committed before the summaries were introduced, so that
+/// the summary of every block is stored at the index equal to its height. The amounts
+/// moved by these blocks are not known and are recorded as zero.
+pub fn fill_block_summaries(fork: &mut Fork) {
+    let summaries = {
+        let general_schema = blockchain::Schema::new(&*fork);
+        let from = Schema::new(&*fork).block_summaries().len();
+        let to = general_schema.height().next().0;
+        (from..to)
+            .map(|height| BlockSummary::new(&general_schema, Height(height), Amount::ZERO))
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    schema.block_summaries_mut().extend(summaries);
+}
+
+/// Lists the holders of the indexed wallet names created before the holders were
+/// tracked: the wallet referred by the index goes first, followed by the other wallets
+/// having the name in the order of their creation.
+pub fn index_wallet_name_holders(fork: &mut Fork) {
+    let holders = {
+        let schema = Schema::new(&*fork);
+        let mut holders = schema
+            .wallet_names()
+            .iter()
+            .map(|(name_key, pub_key)| (name_key, vec![pub_key]))
+            .collect::<HashMap<_, _>>();
+        for pub_key in schema.wallets_by_creation().iter() {
+            let wallet = schema.wallet(&pub_key).expect("Indexed wallet is missing");
+            if let Some(keys) = holders.get_mut(&wallet_name_key(&wallet.name)) {
+                if keys[0] != pub_key {
+                    keys.push(pub_key);
+                }
+            }
+        }
+        holders
+    };
+
+    let mut schema = Schema::new(fork);
+    for (name_key, keys) in holders {
+        let mut list = schema.wallet_name_holders_mut(&name_key);
+        list.clear();
+        list.extend(keys);
+    }
+}
+
+/// Converts wallet histories stored in segments of records, with the record hashes
+/// kept in a separate list, to chunks. `history_hash` of the converted wallets
+/// becomes the root hash of the chunk roots.
+pub fn chunk_wallet_histories(fork: &mut Fork) {
+    let pub_keys = {
+        let schema = Schema::new(&*fork);
+        schema
+            .wallets()
+            .keys()
+            .filter(|pub_key| !schema.segmented_history_hashes(pub_key).is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    let mut schema = Schema::new(fork);
+    for pub_key in pub_keys {
+        schema.chunk_segmented_history(&pub_key);
+    }
+}
+
+/// Restores the history record of the committed transaction from the legacy history
+/// of the wallet.
+fn legacy_record<T>(
+    schema: &blockchain::Schema<T>,
+    wallet: &PublicKey,
+    tx_hash: Hash,
+) -> HistoryRecord
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let height = schema
+        .transactions_locations()
+        .get(&tx_hash)
+        .map_or(Height(0), |location| location.block_height());
+    let (credit, debit) =
+        schema
+            .transactions()
+            .get(&tx_hash)
+            .map_or((Amount::ZERO, Amount::ZERO), |message| {
+                let multisig_amount = |transfer_hash: &Hash| {
+                    transfer_multisig_tx(schema, transfer_hash)
+                        .map(|(_, tx)| tx.amount)
+                        .unwrap_or_default()
+                };
+
+                match service_tx(&message) {
+                    Some(WalletTransactions::Transfer(ref tx)) if message.author() == *wallet => {
+                        (Amount::ZERO, tx.amount)
+                    }
+                    Some(WalletTransactions::Transfer(ref tx)) => (tx.amount, Amount::ZERO),
+                    Some(WalletTransactions::Issue(ref tx)) => (tx.amount, Amount::ZERO),
+                    Some(WalletTransactions::IssueTo(ref tx)) => (tx.amount, Amount::ZERO),
+                    Some(WalletTransactions::CreateWallet(_)) => {
+                        (Amount::whole(INITIAL_BALANCE), Amount::ZERO)
+                    }
+                    // The amount is withheld from the sender when the transfer is created...
+                    Some(WalletTransactions::TransferMultisig(ref tx)) => (Amount::ZERO, tx.amount),
+                    // ...and credited either to the receiver once the transfer is approved...
+                    Some(WalletTransactions::ApproveTransferMultisig(ref tx)) => {
+                        (multisig_amount(tx.transfer_hash()), Amount::ZERO)
+                    }
+                    // ...or back to the sender once it is rejected.
+                    Some(WalletTransactions::RejectTransferMultisig(ref tx)) => {
+                        (multisig_amount(tx.transfer_hash()), Amount::ZERO)
+                    }
+                    // Joint wallets, clearings, sponsored wallets, payment streams, staking,
+                    // batch approvals, overdrafts, cashback and renaming postdate the legacy
+                    // histories.
+                    Some(WalletTransactions::CreateJointWallet(_))
+                    | Some(WalletTransactions::ProposeJointSpend(_))
+                    | Some(WalletTransactions::ApproveJointSpend(_))
+                    | Some(WalletTransactions::Clearing(_))
+                    | Some(WalletTransactions::CreateWalletFor(_))
+                    | Some(WalletTransactions::OpenStream(_))
+                    | Some(WalletTransactions::CloseStream(_))
+                    | Some(WalletTransactions::Stake(_))
+                    | Some(WalletTransactions::Unstake(_))
+                    | Some(WalletTransactions::ClaimRewards(_))
+                    | Some(WalletTransactions::ApproveTransferMultisigBatch(_))
+                    | Some(WalletTransactions::SetOverdraft(_))
+                    | Some(WalletTransactions::SetCashback(_))
+                    | Some(WalletTransactions::RenameWallet(_))
+                    | None => (Amount::ZERO, Amount::ZERO),
+                }
+            });
+
+    HistoryRecord::new(tx_hash, height, debit, credit)
+}
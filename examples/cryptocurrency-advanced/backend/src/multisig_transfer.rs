@@ -1,11 +1,33 @@
 //! Multisignature transfer.
 
-use exonum::{crypto::PublicKey, proto::ProtobufConvert};
+use exonum::{
+    crypto::{Hash, HashStream, PublicKey},
+    helpers::Height,
+    proto::ProtobufConvert,
+};
 
-use super::proto::{self, MultisignatureTransfer_State};
+use std::collections::HashSet;
+
+use super::proto::{self, BatchApprovalResult_Outcome, MultisignatureTransfer_State};
+use crate::amount::Amount;
+
+/// Prefix of the approval digests, which keeps them from being valid signed data
+/// of any other kind.
+const APPROVAL_DIGEST_PREFIX: &[u8] = b"cryptocurrency.multisig_approval";
+
+/// Returns the digest which the approver signs to approve the multisignature transfer
+/// created by the transaction with the given hash in an `ApproveTransferMultisigBatch`.
+pub fn approval_digest(tx_hash: &Hash, approver: &PublicKey) -> Hash {
+    HashStream::new()
+        .update(APPROVAL_DIGEST_PREFIX)
+        .update(tx_hash.as_ref())
+        .update(approver.as_ref())
+        .hash()
+}
 
 /// State of multisignature transfer.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[repr(u8)]
 pub enum State {
     /// Transfer is in process.
@@ -14,6 +36,24 @@ pub enum State {
     Rejected = 1,
     /// Transfer was approved by all the approvers.
     Done = 2,
+    /// Transfer was not settled before its expiration height.
+    Expired = 3,
+    /// Transfer was approved by all the approvers, but its receiver no longer existed.
+    Cancelled = 4,
+}
+
+impl State {
+    /// Returns the state with the given code, the inverse of `state as u8`.
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(State::InProcess),
+            1 => Some(State::Rejected),
+            2 => Some(State::Done),
+            3 => Some(State::Expired),
+            4 => Some(State::Cancelled),
+            _ => None,
+        }
+    }
 }
 
 impl ProtobufConvert for State {
@@ -24,6 +64,8 @@ impl ProtobufConvert for State {
             State::InProcess => MultisignatureTransfer_State::IN_PROCESS,
             State::Rejected => MultisignatureTransfer_State::REJECTED,
             State::Done => MultisignatureTransfer_State::DONE,
+            State::Expired => MultisignatureTransfer_State::EXPIRED,
+            State::Cancelled => MultisignatureTransfer_State::CANCELLED,
         }
     }
 
@@ -32,62 +74,286 @@ impl ProtobufConvert for State {
             MultisignatureTransfer_State::IN_PROCESS => Ok(State::InProcess),
             MultisignatureTransfer_State::REJECTED => Ok(State::Rejected),
             MultisignatureTransfer_State::DONE => Ok(State::Done),
+            MultisignatureTransfer_State::EXPIRED => Ok(State::Expired),
+            MultisignatureTransfer_State::CANCELLED => Ok(State::Cancelled),
         }
     }
 }
 
-/// MultisignatureTransfer information stored in the database.
+/// Error returned when the refund of a multisignature transfer is requested
+/// for a transfer which is not rejected or is already refunded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+#[fail(display = "Multisignature transfer {:?} is already settled", tx_hash)]
+pub struct AlreadySettled {
+    /// Hash of the `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+}
+
+/// Error returned when a multisignature transfer cannot be approved or rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+pub enum MultisigError {
+    /// The approver is not on the approvers list of the transfer.
+    #[fail(display = "Approver is not on approvers list")]
+    NotOnApproversList,
+    /// The approver has already approved the transfer.
+    #[fail(display = "Transfer is already approved by the approver")]
+    AlreadyApproved,
+    /// The transfer is done.
+    #[fail(display = "Transfer is done")]
+    Done,
+    /// The transfer is rejected.
+    #[fail(display = "Transfer is rejected")]
+    Rejected,
+    /// The transfer is expired.
+    #[fail(display = "Transfer is expired")]
+    Expired,
+    /// The transfer is cancelled.
+    #[fail(display = "Transfer is cancelled")]
+    Cancelled,
+}
+
+/// Approval of a multisignature transfer.
 #[derive(Clone, Debug, ProtobufConvert, PartialEq)]
-#[exonum(pb = "proto::MultisignatureTransfer", serde_pb_convert)]
+#[exonum(pb = "proto::Approval", serde_pb_convert)]
+pub struct Approval {
+    /// Approver who approved the transfer.
+    pub approver: PublicKey,
+    /// Height of the block which approved the transfer. Approvals executed before
+    /// the heights were recorded report `Height(0)`.
+    pub approved_at_height: Height,
+    /// Comment of the approver. Empty if the approver gave none.
+    pub comment: String,
+}
+
+/// Outcome of an approval carried by an `ApproveTransferMultisigBatch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalOutcome {
+    /// The approval is applied.
+    Applied,
+    /// The signature does not match the approver.
+    InvalidSignature,
+    /// The approver is not on the approvers list of the transfer.
+    NotOnApproversList,
+    /// The approver has already approved the transfer, possibly earlier in the batch.
+    Duplicate,
+    /// The transfer is done by the preceding approvals of the batch, so the approval
+    /// is not needed.
+    TransferDone,
+}
+
+impl ProtobufConvert for ApprovalOutcome {
+    type ProtoStruct = BatchApprovalResult_Outcome;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        match self {
+            ApprovalOutcome::Applied => BatchApprovalResult_Outcome::APPLIED,
+            ApprovalOutcome::InvalidSignature => BatchApprovalResult_Outcome::INVALID_SIGNATURE,
+            ApprovalOutcome::NotOnApproversList => {
+                BatchApprovalResult_Outcome::NOT_ON_APPROVERS_LIST
+            }
+            ApprovalOutcome::Duplicate => BatchApprovalResult_Outcome::DUPLICATE,
+            ApprovalOutcome::TransferDone => BatchApprovalResult_Outcome::TRANSFER_DONE,
+        }
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        match pb {
+            BatchApprovalResult_Outcome::APPLIED => Ok(ApprovalOutcome::Applied),
+            BatchApprovalResult_Outcome::INVALID_SIGNATURE => Ok(ApprovalOutcome::InvalidSignature),
+            BatchApprovalResult_Outcome::NOT_ON_APPROVERS_LIST => {
+                Ok(ApprovalOutcome::NotOnApproversList)
+            }
+            BatchApprovalResult_Outcome::DUPLICATE => Ok(ApprovalOutcome::Duplicate),
+            BatchApprovalResult_Outcome::TRANSFER_DONE => Ok(ApprovalOutcome::TransferDone),
+        }
+    }
+}
+
+/// Outcome of an approval carried by an `ApproveTransferMultisigBatch` stored
+/// in the database.
+#[derive(Clone, Debug, ProtobufConvert, PartialEq, Serialize, Deserialize)]
+#[exonum(pb = "proto::BatchApprovalResult")]
+pub struct BatchApprovalResult {
+    /// Approver of the approval.
+    pub approver: PublicKey,
+    /// What has become of the approval.
+    pub outcome: ApprovalOutcome,
+}
+
+/// MultisignatureTransfer information stored in the database.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MultisignatureTransfer {
-    /// Public keys of approvers approved this transfer.
-    pub approved_by: Vec<PublicKey>,
+    /// Approvals of this transfer in the order of their execution.
+    pub approved_by: Vec<Approval>,
     /// State of transfer.
     pub state: State,
+    /// `PublicKey` of sender's wallet.
+    pub from: PublicKey,
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Amount of currency being transferred.
+    pub amount: Amount,
+    /// Public keys of all approvers of the transfer.
+    pub approvers: Vec<PublicKey>,
+    /// Approver who rejected the transfer. Filled with zeros unless the transfer
+    /// is rejected.
+    pub rejected_by: PublicKey,
+    /// Height of the block which rejected the transfer. `Height(0)` unless the transfer
+    /// is rejected.
+    pub rejected_at_height: Height,
+    /// Height of the block which settled the transfer, i.e., approved it by the last
+    /// approver, rejected or expired it. `Height(0)` while the transfer is in process
+    /// and for transfers settled before the height was recorded.
+    pub settled_at_height: Height,
+    /// Whether the amount of the rejected, expired or cancelled transfer has been
+    /// returned to the sender.
+    /// Set by `Schema::settle_multisig_refund` only.
+    pub refunded: bool,
+    /// Whether the amount of the expired transfer could not be returned to the sender,
+    /// e.g., since it would overflow the balance of the sender. The amount is removed
+    /// from the total supply instead, and the transfer is never refunded.
+    pub refund_failed: bool,
+    /// Number of distinct approvals required to complete the transfer. Zero means
+    /// that all the approvers must approve it.
+    pub threshold: u32,
+    /// Height from which the transfer can no longer be approved or rejected.
+    /// `Height(0)` means that the transfer does not expire.
+    pub expires_at: Height,
+    /// Comment of the approver who rejected the transfer. Empty unless the transfer
+    /// is rejected with a comment.
+    pub rejection_comment: String,
 }
 
-impl Default for MultisignatureTransfer {
-    fn default() -> Self {
-        Self {
-            approved_by: Vec::new(),
-            state: State::InProcess,
+impl ProtobufConvert for MultisignatureTransfer {
+    type ProtoStruct = proto::MultisignatureTransfer;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::MultisignatureTransfer::new();
+        pb.set_approved_by(self.approved_by.to_pb().into());
+        pb.set_state(self.state.to_pb());
+        pb.set_from(self.from.to_pb());
+        pb.set_to(self.to.to_pb());
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
         }
+        pb.set_approvers(self.approvers.to_pb().into());
+        pb.set_rejected_by(self.rejected_by.to_pb());
+        pb.set_rejected_at_height(self.rejected_at_height.to_pb());
+        pb.set_settled_at_height(self.settled_at_height.to_pb());
+        pb.set_refunded(self.refunded);
+        pb.set_refund_failed(self.refund_failed);
+        pb.set_threshold(self.threshold);
+        pb.set_expires_at(self.expires_at.to_pb());
+        pb.set_rejection_comment(self.rejection_comment.clone());
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            approved_by: ProtobufConvert::from_pb(pb.get_approved_by().to_owned())?,
+            state: State::from_pb(pb.get_state())?,
+            from: PublicKey::from_pb(pb.get_from().clone())?,
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            approvers: ProtobufConvert::from_pb(pb.get_approvers().to_owned())?,
+            rejected_by: PublicKey::from_pb(pb.get_rejected_by().clone())?,
+            rejected_at_height: Height::from_pb(pb.get_rejected_at_height())?,
+            settled_at_height: Height::from_pb(pb.get_settled_at_height())?,
+            refunded: pb.get_refunded(),
+            refund_failed: pb.get_refund_failed(),
+            threshold: pb.get_threshold(),
+            expires_at: Height::from_pb(pb.get_expires_at())?,
+            rejection_comment: pb.get_rejection_comment().to_owned(),
+        })
     }
 }
 
+impl_pb_storage!(MultisignatureTransfer);
+
 impl MultisignatureTransfer {
-    /// Create new MultisignatureTransfer.
-    pub fn new() -> Self {
-        Self::default()
+    /// Create new MultisignatureTransfer with the parameters of the `TransferMultisig`
+    /// transaction. See the `threshold` and `expires_at` fields for the meaning
+    /// of their zero values.
+    pub fn new(
+        from: PublicKey,
+        to: PublicKey,
+        amount: Amount,
+        approvers: Vec<PublicKey>,
+        threshold: u32,
+        expires_at: Height,
+    ) -> Self {
+        Self {
+            approved_by: Vec::new(),
+            state: State::InProcess,
+            from,
+            to,
+            amount,
+            approvers,
+            rejected_by: PublicKey::zero(),
+            rejected_at_height: Height(0),
+            settled_at_height: Height(0),
+            refunded: false,
+            refund_failed: false,
+            threshold,
+            expires_at,
+            rejection_comment: String::new(),
+        }
     }
 
-    /// Approve the transfer.
+    /// Approve the transfer in the block at the given height.
     ///
-    /// Fails if approver is not on approver's list.
-    pub fn approve(self, approver: PublicKey, approvers: &[PublicKey]) -> Result<Self, Self> {
-        let in_approvers = approvers.iter().find(|a| **a == approver);
+    /// Fails if the transfer is settled or expired, if approver is not on approver's
+    /// list or if approver has already approved the transfer.
+    pub fn approve(self, approver: PublicKey, height: Height) -> Result<Self, MultisigError> {
+        self.approve_with_comment(approver, height, String::new())
+    }
 
-        if in_approvers.is_some() {
-            let mut approved_by = self.approved_by;
-            approved_by.push(approver);
+    /// Same as `approve`, but records the comment of the approver along with
+    /// the approval.
+    pub fn approve_with_comment(
+        self,
+        approver: PublicKey,
+        height: Height,
+        comment: String,
+    ) -> Result<Self, MultisigError> {
+        self.check_open_for(&approver, height)?;
+        if self.is_approved_by(&approver) {
+            return Err(MultisigError::AlreadyApproved);
+        }
 
-            let approved = Self {
-                approved_by,
-                ..self
-            };
+        let mut approved_by = self.approved_by;
+        approved_by.push(Approval {
+            approver,
+            approved_at_height: height,
+            comment,
+        });
 
-            let state = if approved.is_complete(approvers) {
-                State::Done
-            } else {
-                State::InProcess
-            };
+        let approved = Self {
+            approved_by,
+            ..self
+        };
 
-            Ok(Self { state, ..approved })
+        if approved.approvals_remaining() == 0 {
+            Ok(Self {
+                state: State::Done,
+                settled_at_height: height,
+                ..approved
+            })
         } else {
-            Err(self)
+            Ok(approved)
         }
     }
 
+    /// Shows if the transfer is approved by the given approver.
+    pub fn is_approved_by(&self, approver: &PublicKey) -> bool {
+        self.approved_by
+            .iter()
+            .any(|approval| approval.approver == *approver)
+    }
+
     /// Shows if the transfer is done.
     pub fn is_done(&self) -> bool {
         self.state == State::Done
@@ -98,30 +364,128 @@ impl MultisignatureTransfer {
         self.state == State::Rejected
     }
 
-    /// Reject the transfer.
+    /// Shows if the transfer is in a terminal state, i.e., done, rejected, expired
+    /// or cancelled.
+    /// Transfers in terminal states cannot be approved or rejected anymore.
+    pub fn is_terminal(&self) -> bool {
+        self.state != State::InProcess
+    }
+
+    /// Shows if the transfer is expired in the block at the given height.
+    pub fn is_expired_at(&self, height: Height) -> bool {
+        self.expires_at > Height(0) && height >= self.expires_at
+    }
+
+    /// Returns the number of distinct approvals required to complete the transfer.
+    pub fn required_approvals(&self) -> usize {
+        match self.threshold {
+            0 => self.approvers.iter().collect::<HashSet<_>>().len(),
+            threshold => threshold as usize,
+        }
+    }
+
+    /// Returns the number of distinct approvers on the approvers list who have
+    /// approved the transfer.
+    pub fn approvals_count(&self) -> usize {
+        self.approved_by
+            .iter()
+            .map(|approval| &approval.approver)
+            .filter(|approver| self.approvers.contains(approver))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Returns the number of approvals still required to complete the transfer.
+    pub fn approvals_remaining(&self) -> usize {
+        self.required_approvals()
+            .saturating_sub(self.approvals_count())
+    }
+
+    /// Returns the number of collected approvals together with the number of required
+    /// ones, e.g., `(2, 3)` for a transfer approved by two approvers out of three.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.approvals_count(), self.required_approvals())
+    }
+
+    /// Returns the approver who rejected the transfer together with the height
+    /// of the rejecting block, if the transfer is rejected.
+    pub fn rejection(&self) -> Option<(PublicKey, Height)> {
+        if self.is_rejected() {
+            Some((self.rejected_by, self.rejected_at_height))
+        } else {
+            None
+        }
+    }
+
+    /// Reject the transfer in the block at the given height. Approvers who have
+    /// approved the transfer may still reject it.
     ///
-    /// Fails if approver is not on approver's list.
-    pub fn reject(self, rejecter: PublicKey, approvers: &[PublicKey]) -> Result<Self, Self> {
-        let in_approvers = approvers.iter().find(|a| **a == rejecter);
+    /// Fails if the transfer is settled or expired, or if approver is not on
+    /// approver's list.
+    pub fn reject(self, rejecter: PublicKey, height: Height) -> Result<Self, MultisigError> {
+        self.reject_with_comment(rejecter, height, String::new())
+    }
 
-        if in_approvers.is_some() {
-            Ok(Self {
-                state: State::Rejected,
+    /// Same as `reject`, but records the comment of the rejecter, e.g., the reason
+    /// of the rejection.
+    pub fn reject_with_comment(
+        self,
+        rejecter: PublicKey,
+        height: Height,
+        comment: String,
+    ) -> Result<Self, MultisigError> {
+        self.check_open_for(&rejecter, height)?;
+        Ok(Self {
+            state: State::Rejected,
+            rejected_by: rejecter,
+            rejected_at_height: height,
+            settled_at_height: height,
+            rejection_comment: comment,
+            ..self
+        })
+    }
+
+    /// Expire the transfer in the block at the given height. Returns `None` unless
+    /// the transfer is in process and expired at the height.
+    pub fn expire(self, height: Height) -> Option<Self> {
+        if self.state == State::InProcess && self.is_expired_at(height) {
+            Some(Self {
+                state: State::Expired,
+                settled_at_height: height,
                 ..self
             })
         } else {
-            Err(self)
+            None
         }
     }
 
-    /// Shows if the transfer is approved by all required approvers.
-    fn is_complete(&self, approvers: &[PublicKey]) -> bool {
-        use std::collections::{hash_map::RandomState, HashSet};
-        use std::iter::FromIterator;
-
-        let approvers: HashSet<&PublicKey, RandomState> = HashSet::from_iter(approvers.iter());
-        let approved_by = HashSet::from_iter(self.approved_by.iter());
+    /// Cancel the transfer approved by the last approver, e.g., because its receiver
+    /// no longer exists. The transfer stays settled at the height of the last approval.
+    /// Returns `None` unless the transfer is done.
+    pub fn cancel(self) -> Option<Self> {
+        if self.is_done() {
+            Some(Self {
+                state: State::Cancelled,
+                ..self
+            })
+        } else {
+            None
+        }
+    }
 
-        approved_by == approvers
+    /// Checks that the approver can approve or reject the transfer in the block
+    /// at the given height.
+    fn check_open_for(&self, approver: &PublicKey, height: Height) -> Result<(), MultisigError> {
+        match self.state {
+            State::Done => Err(MultisigError::Done),
+            State::Rejected => Err(MultisigError::Rejected),
+            State::Expired => Err(MultisigError::Expired),
+            State::Cancelled => Err(MultisigError::Cancelled),
+            State::InProcess if self.is_expired_at(height) => Err(MultisigError::Expired),
+            State::InProcess if !self.approvers.contains(approver) => {
+                Err(MultisigError::NotOnApproversList)
+            }
+            State::InProcess => Ok(()),
+        }
     }
 }
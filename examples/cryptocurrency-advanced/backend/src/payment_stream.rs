@@ -0,0 +1,157 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Payment streams paying the receiver for every block while they are open.
+//!
+//! The sender reserves the maximum total of the stream when opening it. Once the stream
+//! is closed, the receiver is credited with the rate for every block since the opening,
+//! up to the maximum total, and the rest of the reserve is refunded to the sender.
+//! Streams which have accrued the maximum total are closed by the service itself.
+
+use exonum::{crypto::PublicKey, helpers::Height, proto::ProtobufConvert};
+
+use super::proto;
+use crate::amount::Amount;
+
+/// Payment stream stored in the database.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaymentStream {
+    /// `PublicKey` of the paying wallet.
+    pub from: PublicKey,
+    /// `PublicKey` of the paid wallet.
+    pub to: PublicKey,
+    /// Amount paid for every block.
+    pub rate_per_block: Amount,
+    /// Amount reserved from the sender; the receiver is never paid more.
+    pub max_total: Amount,
+    /// Height of the block which opened the stream.
+    pub opened_at: Height,
+    /// Height of the block which closed the stream; zero while the stream is open.
+    pub closed_at: Height,
+    /// Amount paid to the receiver when the stream was closed.
+    pub paid: Amount,
+}
+
+impl PaymentStream {
+    /// Creates the stream opened at the given height.
+    pub fn new(
+        from: PublicKey,
+        to: PublicKey,
+        rate_per_block: Amount,
+        max_total: Amount,
+        opened_at: Height,
+    ) -> Self {
+        Self {
+            from,
+            to,
+            rate_per_block,
+            max_total,
+            opened_at,
+            closed_at: Height(0),
+            paid: Amount::ZERO,
+        }
+    }
+
+    /// Shows if the stream is closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed_at > Height(0)
+    }
+
+    /// Shows if the key is the sender or the receiver of the stream.
+    pub fn is_party(&self, key: &PublicKey) -> bool {
+        self.from == *key || self.to == *key
+    }
+
+    /// Returns the amount owed to the receiver if the stream is closed at the given
+    /// height: the rate for every block since the opening, capped at the maximum total.
+    pub fn accrued(&self, height: Height) -> Amount {
+        let blocks = height.0.saturating_sub(self.opened_at.0);
+        self.rate_per_block
+            .saturating_mul(blocks)
+            .min(self.max_total)
+    }
+
+    /// Returns the height at which the stream accrues the maximum total. The rate
+    /// must be positive.
+    pub fn exhausted_at(&self) -> Height {
+        let (max_total, rate) = (self.max_total.raw(), self.rate_per_block.raw());
+        let blocks = max_total / rate + if max_total % rate == 0 { 0 } else { 1 };
+        let blocks = if blocks > u128::from(u64::max_value()) {
+            u64::max_value()
+        } else {
+            blocks as u64
+        };
+        Height(self.opened_at.0.saturating_add(blocks))
+    }
+
+    /// Closes the stream at the given height, paying the receiver the accrued amount.
+    pub fn close(self, height: Height) -> Self {
+        debug_assert!(!self.is_closed());
+        Self {
+            closed_at: height,
+            paid: self.accrued(height),
+            ..self
+        }
+    }
+
+    /// Returns the part of the reserve refunded to the sender on closing.
+    pub fn refund(&self) -> Amount {
+        self.max_total.saturating_sub(self.paid)
+    }
+}
+
+impl ProtobufConvert for PaymentStream {
+    type ProtoStruct = proto::PaymentStream;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::PaymentStream::new();
+        pb.set_from(self.from.to_pb());
+        pb.set_to(self.to.to_pb());
+        let (rate_per_block, exact_rate_per_block) = self.rate_per_block.to_pb_fields();
+        pb.set_rate_per_block(rate_per_block);
+        if let Some(exact_rate_per_block) = exact_rate_per_block {
+            pb.set_exact_rate_per_block(exact_rate_per_block);
+        }
+        let (max_total, exact_max_total) = self.max_total.to_pb_fields();
+        pb.set_max_total(max_total);
+        if let Some(exact_max_total) = exact_max_total {
+            pb.set_exact_max_total(exact_max_total);
+        }
+        pb.set_opened_at(self.opened_at.to_pb());
+        pb.set_closed_at(self.closed_at.to_pb());
+        let (paid, exact_paid) = self.paid.to_pb_fields();
+        pb.set_paid(paid);
+        if let Some(exact_paid) = exact_paid {
+            pb.set_exact_paid(exact_paid);
+        }
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            from: PublicKey::from_pb(pb.get_from().clone())?,
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            rate_per_block: Amount::from_pb_fields(
+                pb.get_rate_per_block(),
+                pb.get_exact_rate_per_block(),
+            ),
+            max_total: Amount::from_pb_fields(pb.get_max_total(), pb.get_exact_max_total()),
+            opened_at: Height::from_pb(pb.get_opened_at())?,
+            closed_at: Height::from_pb(pb.get_closed_at())?,
+            paid: Amount::from_pb_fields(pb.get_paid(), pb.get_exact_paid()),
+        })
+    }
+}
+
+impl_pb_storage!(PaymentStream);
@@ -18,8 +18,15 @@
 #![allow(renamed_and_removed_lints)]
 
 pub use self::cryptocurrency::{
-    ApproveTransferMultisig, CreateWallet, Issue, MultisignatureTransfer,
-    MultisignatureTransfer_State, RejectTransferMultisig, Transfer, TransferMultisig, Wallet,
+    Amount, Approval, ApproveJointSpend, ApproveTransferMultisig, ApproveTransferMultisigBatch,
+    BalanceCheckpoint, BatchApprovalResult, BatchApprovalResult_Outcome, BlockSummary,
+    ClaimRewards, Clearing, ClearingLeg, CloseStream, CreateJointWallet, CreateWallet,
+    CreateWalletFor, HistoryRecord, Issue, IssueTo, JointWallet, MultisignatureTransfer,
+    MultisignatureTransfer_State, NameChange, OpenStream, PaymentStream, PendingOutgoing,
+    PendingTransfer, ProofVerification, ProposeJointSpend, RejectTransferMultisig, RenameWallet,
+    SetCashback, SetOverdraft, SignedApproval, SimpleTransactionInfo, SimpleWalletInfo, Stake,
+    StakePosition, StakingState, Transfer, TransferMultisig, Unstake, Wallet, WalletActivity,
+    WalletInfo, WalletStats,
 };
 
 include!(concat!(env!("OUT_DIR"), "/protobuf_mod.rs"));
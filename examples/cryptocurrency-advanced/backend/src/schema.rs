@@ -15,15 +15,208 @@
 //! Cryptocurrency database schema.
 
 use exonum::{
-    crypto::{Hash, PublicKey},
-    storage::{Fork, ProofListIndex, ProofMapIndex, Snapshot},
+    blockchain,
+    crypto::{self, Hash, HashStream, PublicKey},
+    helpers::Height,
+    proto::ProtobufConvert,
+    storage::{
+        proof_list_index::root_hash, proof_map_index::ProofMapKey, Entry, Fork, KeySetIndex,
+        ListIndex, MapIndex, MapProof, ProofListIndex, ProofMapIndex, Snapshot, StorageValue,
+    },
 };
 
-use crate::{multisig_transfer::MultisignatureTransfer, wallet::Wallet, INITIAL_BALANCE};
+use std::{
+    cmp,
+    collections::{BTreeSet, HashMap},
+};
+
+use crate::{
+    amount::Amount,
+    config::Config,
+    due::{DueItem, DueKind},
+    joint_wallet::JointWallet,
+    metrics::BlockSummary,
+    multisig_transfer::{AlreadySettled, BatchApprovalResult, MultisignatureTransfer, State},
+    payment_stream::PaymentStream,
+    proto,
+    staking::{StakePosition, StakingState},
+    transactions::{transfer_multisig_tx, Error},
+    wallet::{
+        chain_history_chunk, history_chain, BalanceCheckpoint, HistoryProof, HistoryRecord,
+        MetadataTooLarge, NameChange, Wallet, WalletActivity, WalletStats, HISTORY_CHUNK_SIZE,
+        MAX_METADATA_SIZE,
+    },
+    wallet_id::WalletId,
+    SERVICE_NAME,
+};
 
 const WALLET_TABLE: &str = "cryptocurrency.wallets";
-const WALLET_HISTORY_FAMILY: &str = "cryptocurrency.wallet_history";
+const WALLETS_BY_CREATION_LIST: &str = "cryptocurrency.wallets_by_creation";
+const WALLET_NAMES_TABLE: &str = "cryptocurrency.wallet_names";
+const WALLET_NAME_HOLDERS_FAMILY: &str = "cryptocurrency.wallet_name_holders";
+const HISTORY_CHUNKS_FAMILY: &str = "cryptocurrency.wallet_history_chunks";
+const HISTORY_CHUNK_ROOTS_FAMILY: &str = "cryptocurrency.wallet_history_chunk_roots";
+const HISTORY_FIRST_CHUNK_TABLE: &str = "cryptocurrency.wallet_history_first_chunk";
+const PENDING_PRUNING_SET: &str = "cryptocurrency.pending_history_pruning";
+const SEGMENTED_HISTORY_HASHES_FAMILY: &str = "cryptocurrency.wallet_history_hashes";
+const HISTORY_SEGMENTS_FAMILY: &str = "cryptocurrency.wallet_history_segments";
+const HISTORY_FIRST_SEGMENT_TABLE: &str = "cryptocurrency.wallet_history_first_segment";
+const UNCHUNKED_WALLET_HISTORY_FAMILY: &str = "cryptocurrency.wallet_history_records";
+const LEGACY_WALLET_HISTORY_FAMILY: &str = "cryptocurrency.wallet_history";
+const LEGACY_HISTORY_MIGRATED_ENTRY: &str = "cryptocurrency.history_chunks_migrated";
+const BALANCE_CHECKPOINTS_FAMILY: &str = "cryptocurrency.balance_checkpoints";
+const PENDING_CHECKPOINTS_SET: &str = "cryptocurrency.pending_balance_checkpoints";
+const SCHEMA_VERSION_ENTRY: &str = "cryptocurrency.schema_version";
+const LEGACY_WALLETS_MIGRATED_ENTRY: &str = "cryptocurrency.wallet_last_tx_migrated";
+const BALANCE_INDEX_SET: &str = "cryptocurrency.balance_index";
+const BALANCE_INDEX_BUILT_ENTRY: &str = "cryptocurrency.balance_index_built";
+const LAST_ACTIVITY_FILLED_ENTRY: &str = "cryptocurrency.last_activity_filled";
+const WALLET_ACTIVITY_TABLE: &str = "cryptocurrency.wallet_activity";
+const WALLET_STATS_TABLE: &str = "cryptocurrency.wallet_stats";
+const WALLET_METADATA_FAMILY: &str = "cryptocurrency.wallet_metadata";
+const AFFECTED_WALLETS_FAMILY: &str = "cryptocurrency.affected_wallets";
+const WALLET_COUNTERPARTIES_FAMILY: &str = "cryptocurrency.wallet_counterparties";
 const MULTISIG_TRANSFER_TABLE: &str = "cryptocurrency.multisig_transfers";
+const LEGACY_MULTISIG_MIGRATED_ENTRY: &str = "cryptocurrency.multisig_approvals_migrated";
+const MULTISIG_BY_STATE_FAMILY: &str = "cryptocurrency.multisig_by_state";
+const MULTISIG_OUTGOING_FAMILY: &str = "cryptocurrency.multisig_outgoing";
+const MULTISIG_OUTGOING_TABLE: &str = "cryptocurrency.multisig_outgoing_roots";
+const APPROVER_PENDING_FAMILY: &str = "cryptocurrency.approver_pending";
+const MULTISIG_INCOMING_FAMILY: &str = "cryptocurrency.multisig_incoming";
+const APPROVER_MULTISIG_FAMILY: &str = "cryptocurrency.approver_multisig";
+const SETTLED_MULTISIG_SET: &str = "cryptocurrency.settled_multisig";
+const MULTISIG_TOMBSTONE_TABLE: &str = "cryptocurrency.multisig_tombstones";
+const APPROVAL_BATCH_RESULTS_FAMILY: &str = "cryptocurrency.approval_batch_results";
+const JOINT_WALLETS_TABLE: &str = "cryptocurrency.joint_wallets";
+const JOINT_SPENDS_TABLE: &str = "cryptocurrency.joint_spends";
+const JOINT_PENDING_FAMILY: &str = "cryptocurrency.joint_pending";
+const PAYMENT_STREAMS_TABLE: &str = "cryptocurrency.payment_streams";
+const STAKING_STATE_ENTRY: &str = "cryptocurrency.staking_state";
+const STAKE_POSITIONS_TABLE: &str = "cryptocurrency.stake_positions";
+const TRANSFERS_BY_SEED_TABLE: &str = "cryptocurrency.transfers_by_seed";
+const TRANSFERS_BY_SEED_FAMILY: &str = "cryptocurrency.transfers_by_seed_history";
+const DUE_ITEMS_FAMILY: &str = "cryptocurrency.due_items";
+const DUE_ITEMS_PROCESSED_ENTRY: &str = "cryptocurrency.due_items_processed";
+const TX_COUNT_TABLE: &str = "cryptocurrency.metrics.tx_count";
+const TX_ERROR_COUNT_TABLE: &str = "cryptocurrency.metrics.tx_error_count";
+const TOTAL_SUPPLY_ENTRY: &str = "cryptocurrency.total_supply";
+const WALLET_COUNT_ENTRY: &str = "cryptocurrency.wallet_count";
+const TRANSFER_COUNT_ENTRY: &str = "cryptocurrency.transfer_count";
+const TRANSFER_VOLUME_ENTRY: &str = "cryptocurrency.transfer_volume";
+const BLOCK_TRANSFER_VOLUMES_TABLE: &str = "cryptocurrency.block_transfer_volumes";
+const BLOCK_SUMMARIES_LIST: &str = "cryptocurrency.block_summaries";
+const NAME_HISTORY_FAMILY: &str = "cryptocurrency.name_history";
+const NAME_HISTORY_TABLE: &str = "cryptocurrency.name_history_roots";
+
+/// Returns the key of transfers signed by the given author with the given seed.
+pub fn transfer_seed_key(author: &PublicKey, seed: u64) -> Hash {
+    HashStream::new()
+        .update(author.as_ref())
+        .update(&seed.into_bytes())
+        .hash()
+}
+
+/// Returns the key of the chunk of the wallet history with the given number.
+pub fn history_chunk_key(pub_key: &PublicKey, chunk: u64) -> Hash {
+    HashStream::new()
+        .update(pub_key.as_ref())
+        .update(&chunk.into_bytes())
+        .hash()
+}
+
+/// Returns the key of the segment of the wallet history in the segmented layout.
+pub fn history_segment_key(pub_key: &PublicKey, segment: u64) -> Hash {
+    HashStream::new()
+        .update(pub_key.as_ref())
+        .update(&segment.into_bytes())
+        .hash()
+}
+
+/// Returns the key of the wallet name in the `wallet_names` index.
+pub fn wallet_name_key(name: &str) -> Hash {
+    crypto::hash(name.as_bytes())
+}
+
+/// Returns the hash of the wallet metadata: the hash of the length-prefixed keys
+/// and values in the order of the keys, or zeros for empty metadata.
+pub fn metadata_hash<I>(entries: I) -> Hash
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut entries = entries.into_iter().peekable();
+    if entries.peek().is_none() {
+        return Hash::zero();
+    }
+    entries
+        .fold(HashStream::new(), |stream, (key, value)| {
+            stream
+                .update(&(key.len() as u64).into_bytes())
+                .update(key.as_bytes())
+                .update(&(value.len() as u64).into_bytes())
+                .update(value.as_bytes())
+        })
+        .hash()
+}
+
+/// Returns the key of the wallet in the balance index. The keys are ordered
+/// by descending balance, and then by the public key.
+pub fn balance_index_key(pub_key: &PublicKey, balance: Amount) -> Vec<u8> {
+    let mut key = (u128::max_value() - balance.raw()).to_be_bytes().to_vec();
+    key.extend_from_slice(pub_key.as_ref());
+    key
+}
+
+/// Returns the public key and the balance of the wallet from its key
+/// in the balance index.
+pub fn parse_balance_index_key(key: &[u8]) -> (PublicKey, Amount) {
+    let mut inverted_balance = [0; 16];
+    inverted_balance.copy_from_slice(&key[..16]);
+    let pub_key = PublicKey::from_slice(&key[16..]).expect("Malformed balance index key");
+    (
+        pub_key,
+        Amount::from_raw(u128::max_value() - u128::from_be_bytes(inverted_balance)),
+    )
+}
+
+/// Returns the key of the settled multisignature transfer in the set ordered
+/// by the settlement height.
+pub fn settled_multisig_key(settled_at: Height, tx_hash: &Hash) -> Vec<u8> {
+    let mut key = settled_at.0.to_be_bytes().to_vec();
+    key.extend_from_slice(tx_hash.as_ref());
+    key
+}
+
+/// Returns the settlement height and the hash of the multisignature transfer
+/// from its key in the set of settled transfers.
+pub fn parse_settled_multisig_key(key: &[u8]) -> (Height, Hash) {
+    let mut settled_at = [0; 8];
+    settled_at.copy_from_slice(&key[..8]);
+    let tx_hash = Hash::from_slice(&key[8..]).expect("Malformed settled multisig key");
+    (Height(u64::from_be_bytes(settled_at)), tx_hash)
+}
+
+/// Returns up to `limit` entries of the index in the order of the keys, starting
+/// with the first key greater than `after`, or with the first key of the index
+/// if `after` is `None`. `after` does not need to be present in the index.
+fn entries_after<T, K, V>(
+    index: &ProofMapIndex<T, K, V>,
+    after: Option<&K>,
+    limit: usize,
+) -> Vec<(K, V)>
+where
+    T: AsRef<dyn Snapshot>,
+    K: ProofMapKey<Output = K> + PartialEq,
+    V: StorageValue,
+{
+    let entries = match after {
+        Some(after) => index.iter_from(after),
+        None => index.iter(),
+    };
+    entries
+        .skip_while(|(key, _)| Some(key) == after)
+        .take(limit)
+        .collect()
+}
 
 /// Database schema for the cryptocurrency.
 #[derive(Debug)]
@@ -51,14 +244,399 @@ where
         ProofMapIndex::new(WALLET_TABLE, &self.view)
     }
 
-    /// Returns history of the wallet with the given public key.
-    pub fn wallet_history(&self, public_key: &PublicKey) -> ProofListIndex<&T, Hash> {
-        ProofListIndex::new_in_family(WALLET_HISTORY_FAMILY, public_key, &self.view)
+    /// Returns `Entry` with the version of the layout of the stored data. Blockchains
+    /// created before the data was versioned have no version.
+    pub fn schema_version_entry(&self) -> Entry<&T, u32> {
+        Entry::new(SCHEMA_VERSION_ENTRY, &self.view)
+    }
+
+    /// Returns the version of the layout of the stored data, `0` if not set.
+    pub fn schema_version(&self) -> u32 {
+        self.schema_version_entry().get().unwrap_or_default()
+    }
+
+    /// Returns a single proof of the wallets with the given public keys, or of their
+    /// absence, against the root hash of the wallets table.
+    pub fn wallets_multiproof(&self, keys: &[PublicKey]) -> MapProof<PublicKey, Wallet> {
+        self.wallets().get_multiproof(keys.iter().cloned())
+    }
+
+    /// Returns up to `limit` wallets in the order of their public keys, following
+    /// the `after` key if it is given.
+    pub fn wallets_range(
+        &self,
+        after: Option<&PublicKey>,
+        limit: usize,
+    ) -> Vec<(PublicKey, Wallet)> {
+        entries_after(&self.wallets(), after, limit)
+    }
+
+    /// Returns the wallets as raw bytes. Wallets written by the previous versions
+    /// of the service lack `last_tx_hash` and cannot be read from `wallets` until
+    /// migrated.
+    pub fn raw_wallets(&self) -> ProofMapIndex<&T, PublicKey, Vec<u8>> {
+        ProofMapIndex::new(WALLET_TABLE, &self.view)
+    }
+
+    /// Returns `Entry` which is set once the wallets written by the previous versions
+    /// of the service are migrated.
+    pub fn legacy_wallets_migrated_entry(&self) -> Entry<&T, bool> {
+        Entry::new(LEGACY_WALLETS_MIGRATED_ENTRY, &self.view)
+    }
+
+    /// Returns whether the wallets written by the previous versions of the service
+    /// are migrated.
+    pub fn legacy_wallets_migrated(&self) -> bool {
+        self.legacy_wallets_migrated_entry()
+            .get()
+            .unwrap_or_default()
+    }
+
+    /// Returns public keys of the wallets in the order of their creation. Since wallets
+    /// are appended as they are created, their creation heights are non-decreasing
+    /// along the list. Wallets created before the list was introduced are not included.
+    pub fn wallets_by_creation(&self) -> ProofListIndex<&T, PublicKey> {
+        ProofListIndex::new(WALLETS_BY_CREATION_LIST, &self.view)
+    }
+
+    /// Returns the index of wallet names. Names are keyed by `wallet_name_key`, since
+    /// proof map keys have a fixed length. If several wallets have the same name,
+    /// the name refers to the wallet which took it first.
+    pub fn wallet_names(&self) -> ProofMapIndex<&T, Hash, PublicKey> {
+        ProofMapIndex::new(WALLET_NAMES_TABLE, &self.view)
+    }
+
+    /// Returns the wallets having the indexed name with the given key in the order
+    /// in which they took it, so that the first one is referred by `wallet_names`.
+    pub fn wallet_name_holders(&self, name_key: &Hash) -> ListIndex<&T, PublicKey> {
+        ListIndex::new_in_family(WALLET_NAME_HOLDERS_FAMILY, name_key, &self.view)
+    }
+
+    /// Returns the wallet with the given name.
+    pub fn wallet_by_name(&self, name: &str) -> Option<Wallet> {
+        self.wallet_names()
+            .get(&wallet_name_key(name))
+            .and_then(|pub_key| self.wallet(&pub_key))
+    }
+
+    /// Returns the public key of the wallet with the given identifier. Keys are returned
+    /// as is, whether the wallet exists or not, while names are looked up in the index
+    /// of the wallet names.
+    pub fn resolve(&self, id: &WalletId) -> Option<PublicKey> {
+        match id {
+            WalletId::Key(pub_key) => Some(*pub_key),
+            WalletId::Name(name) => self.wallet_names().get(&wallet_name_key(name)),
+        }
+    }
+
+    /// Returns the changes of the wallet name in the order of execution. Entries are
+    /// never removed.
+    pub fn name_history(&self, pub_key: &PublicKey) -> ProofListIndex<&T, NameChange> {
+        ProofListIndex::new_in_family(NAME_HISTORY_FAMILY, pub_key, &self.view)
+    }
+
+    /// Returns `ProofMapIndex` which maps wallets to the root hashes of their
+    /// `name_history` lists. Wallets which have never been renamed are not included.
+    pub fn name_history_roots(&self) -> ProofMapIndex<&T, PublicKey, Hash> {
+        ProofMapIndex::new(NAME_HISTORY_TABLE, &self.view)
+    }
+
+    /// Returns the name of the wallet after the block at the given height, or `None`
+    /// if the wallet did not exist yet.
+    pub fn wallet_name_at(&self, pub_key: &PublicKey, height: Height) -> Option<String> {
+        let wallet = self.wallet(pub_key)?;
+        if wallet.created_at > height {
+            return None;
+        }
+        let history = self.name_history(pub_key);
+        // The first change after the height holds the name the wallet had at the height.
+        let name = history
+            .iter()
+            .find(|change| change.height > height)
+            .map_or(wallet.name, |change| change.old_name);
+        Some(name)
+    }
+
+    /// Returns the chunk of the wallet history with the given number. Chunk `n` holds
+    /// the records with indices `[n * HISTORY_CHUNK_SIZE, (n + 1) * HISTORY_CHUNK_SIZE)`;
+    /// chunks before `first_history_chunk` are pruned and empty.
+    pub fn wallet_history_chunk(
+        &self,
+        pub_key: &PublicKey,
+        chunk: u64,
+    ) -> ProofListIndex<&T, HistoryRecord> {
+        let key = history_chunk_key(pub_key, chunk);
+        ProofListIndex::new_in_family(HISTORY_CHUNKS_FAMILY, &key, &self.view)
     }
 
-    /// Returns wallet for the given public key.
+    /// Returns the root hashes of the wallet history chunks. The root hash of this list
+    /// is stored in the wallet as `history_hash`. Roots of the pruned chunks are kept,
+    /// so pruning does not change the hash.
+    pub fn wallet_history_chunk_roots(&self, pub_key: &PublicKey) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::new_in_family(HISTORY_CHUNK_ROOTS_FAMILY, pub_key, &self.view)
+    }
+
+    /// Returns the numbers of the first retained history chunks of the wallets.
+    ///
+    /// Pruning is a node-local setting, so the index is not included in the state hash.
+    pub fn history_first_chunks(&self) -> MapIndex<&T, PublicKey, u64> {
+        MapIndex::new(HISTORY_FIRST_CHUNK_TABLE, &self.view)
+    }
+
+    /// Returns the number of the first retained chunk of the wallet history.
+    pub fn first_history_chunk(&self, pub_key: &PublicKey) -> u64 {
+        self.history_first_chunks().get(pub_key).unwrap_or_default()
+    }
+
+    /// Returns the index of the first retained record of the wallet history.
+    /// Records before it are pruned.
+    pub fn first_retained_history_index(&self, pub_key: &PublicKey) -> u64 {
+        self.first_history_chunk(pub_key) * HISTORY_CHUNK_SIZE
+    }
+
+    /// Returns the record of the wallet history with the given index.
+    pub fn wallet_history_record(&self, pub_key: &PublicKey, index: u64) -> Option<HistoryRecord> {
+        self.wallet_history_chunk(pub_key, index / HISTORY_CHUNK_SIZE)
+            .get(index % HISTORY_CHUNK_SIZE)
+    }
+
+    /// Returns the records of the wallet history starting from the given index.
+    /// Pruned records are skipped.
+    pub fn wallet_history_from<'a>(
+        &'a self,
+        pub_key: &PublicKey,
+        from: u64,
+    ) -> impl Iterator<Item = HistoryRecord> + 'a {
+        let pub_key = *pub_key;
+        let first_chunk = from / HISTORY_CHUNK_SIZE;
+        let chunk_count = self.wallet_history_chunk_roots(&pub_key).len();
+        (first_chunk..chunk_count).flat_map(move |chunk| {
+            let from = if chunk == first_chunk {
+                from % HISTORY_CHUNK_SIZE
+            } else {
+                0
+            };
+            // Chunks are bounded in size, so a single chunk is read at a time.
+            self.wallet_history_chunk(&pub_key, chunk)
+                .iter_from(from)
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Returns the proof of the `[from, to)` range of the wallet history, which should
+    /// be non-empty and retained.
+    pub fn wallet_history_proof(&self, pub_key: &PublicKey, from: u64, to: u64) -> HistoryProof {
+        debug_assert!(from < to);
+        let (first_chunk, last_chunk) = (from / HISTORY_CHUNK_SIZE, (to - 1) / HISTORY_CHUNK_SIZE);
+        let chunks = (first_chunk..=last_chunk)
+            .map(|chunk| {
+                let offset = chunk * HISTORY_CHUNK_SIZE;
+                let chunk_from = cmp::max(from, offset) - offset;
+                let chunk_to = cmp::min(to, offset + HISTORY_CHUNK_SIZE) - offset;
+                self.wallet_history_chunk(pub_key, chunk)
+                    .get_range_proof(chunk_from, chunk_to)
+            })
+            .collect();
+
+        HistoryProof {
+            chunk_roots: self
+                .wallet_history_chunk_roots(pub_key)
+                .get_range_proof(first_chunk, last_chunk + 1),
+            chunks,
+        }
+    }
+
+    /// Returns wallets whose history has changed since the previous pruning.
+    pub fn wallets_pending_pruning(&self) -> KeySetIndex<&T, PublicKey> {
+        KeySetIndex::new(PENDING_PRUNING_SET, &self.view)
+    }
+
+    /// Returns hashes of the records of the wallet history in the segmented layout,
+    /// which preceded the chunked history: records were stored in segments of
+    /// `HISTORY_CHUNK_SIZE` records, and `history_hash` was the root hash of this list.
+    /// Such histories are converted to chunks by `migration::chunk_wallet_histories`
+    /// and are empty afterwards.
+    pub fn segmented_history_hashes(&self, pub_key: &PublicKey) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::new_in_family(SEGMENTED_HISTORY_HASHES_FAMILY, pub_key, &self.view)
+    }
+
+    /// Returns the segment of the wallet history in the segmented layout.
+    pub fn history_segment(
+        &self,
+        pub_key: &PublicKey,
+        segment: u64,
+    ) -> ListIndex<&T, HistoryRecord> {
+        let key = history_segment_key(pub_key, segment);
+        ListIndex::new_in_family(HISTORY_SEGMENTS_FAMILY, &key, &self.view)
+    }
+
+    /// Returns the numbers of the first retained history segments of the wallets
+    /// in the segmented layout.
+    pub fn history_first_segments(&self) -> MapIndex<&T, PublicKey, u64> {
+        MapIndex::new(HISTORY_FIRST_SEGMENT_TABLE, &self.view)
+    }
+
+    /// Returns history of the wallet in the format with a single list of records,
+    /// which preceded the segmented history. Such histories are converted to chunks
+    /// by `migration::migrate_legacy_histories` and are empty afterwards.
+    pub fn unchunked_wallet_history(
+        &self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&T, HistoryRecord> {
+        ProofListIndex::new_in_family(UNCHUNKED_WALLET_HISTORY_FAMILY, pub_key, &self.view)
+    }
+
+    /// Returns history of the wallet in the legacy format, which contains bare
+    /// transaction hashes. Legacy histories are converted to chunks
+    /// by `migration::migrate_legacy_histories` and are empty afterwards.
+    pub fn legacy_wallet_history(&self, public_key: &PublicKey) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::new_in_family(LEGACY_WALLET_HISTORY_FAMILY, public_key, &self.view)
+    }
+
+    /// Returns `Entry` which is set once legacy wallet histories are migrated.
+    pub fn legacy_history_migrated_entry(&self) -> Entry<&T, bool> {
+        Entry::new(LEGACY_HISTORY_MIGRATED_ENTRY, &self.view)
+    }
+
+    /// Returns `Entry` which is set once legacy multisignature transfers are migrated.
+    pub fn legacy_multisig_migrated_entry(&self) -> Entry<&T, bool> {
+        Entry::new(LEGACY_MULTISIG_MIGRATED_ENTRY, &self.view)
+    }
+
+    /// Returns wallet for the given public key. Wallets written by the previous versions
+    /// of the service are read as well, with `last_tx_hash` restored from the history
+    /// and `metadata_hash` computed from the metadata, so that transactions executed
+    /// before the migration can update them.
     pub fn wallet(&self, pub_key: &PublicKey) -> Option<Wallet> {
-        self.wallets().get(pub_key)
+        let bytes = self.raw_wallets().get(pub_key)?;
+        let mut pb: proto::Wallet =
+            protobuf::parse_from_bytes(&bytes).expect("Malformed wallet in the storage");
+        if !pb.has_metadata_hash() {
+            pb.set_metadata_hash(self.wallet_metadata_hash(pub_key).to_pb());
+        }
+        if !pb.has_last_tx_hash() {
+            let last_tx_hash = pb
+                .get_history_len()
+                .checked_sub(1)
+                .and_then(|index| self.wallet_history_record(pub_key, index))
+                .map_or_else(Hash::zero, |record| record.tx_hash);
+            pb.set_last_tx_hash(last_tx_hash.to_pb());
+        }
+        if !pb.has_history_chain() {
+            let filled_chunks = pb.get_history_len() / HISTORY_CHUNK_SIZE;
+            let chain = history_chain(
+                &self
+                    .wallet_history_chunk_roots(pub_key)
+                    .iter()
+                    .take(filled_chunks as usize)
+                    .collect::<Vec<_>>(),
+            );
+            pb.set_history_chain(chain.to_pb());
+        }
+        Some(Wallet::from_pb(pb).expect("Cannot read wallet from the storage"))
+    }
+
+    /// Returns balance checkpoints of the wallet in the order of increasing height.
+    ///
+    /// Checkpoints are a node-local cache used to answer historical balance queries
+    /// and are not included in the state hash.
+    pub fn balance_checkpoints(&self, pub_key: &PublicKey) -> ListIndex<&T, BalanceCheckpoint> {
+        ListIndex::new_in_family(BALANCE_CHECKPOINTS_FAMILY, pub_key, &self.view)
+    }
+
+    /// Returns the set of wallets ordered by descending balance. See `balance_index_key`
+    /// for the format of the keys.
+    pub fn balance_index(&self) -> KeySetIndex<&T, Vec<u8>> {
+        KeySetIndex::new(BALANCE_INDEX_SET, &self.view)
+    }
+
+    /// Returns the public keys and the balances of the wallets ordered by descending
+    /// balance.
+    pub fn wallets_by_balance<'a>(&'a self) -> impl Iterator<Item = (PublicKey, Amount)> + 'a {
+        self.balance_index()
+            .iter()
+            .map(|key| parse_balance_index_key(&key))
+    }
+
+    /// Returns `Entry` which is set once the balance index is built for the wallets
+    /// created before the index was introduced.
+    pub fn balance_index_built_entry(&self) -> Entry<&T, bool> {
+        Entry::new(BALANCE_INDEX_BUILT_ENTRY, &self.view)
+    }
+
+    /// Returns whether the balance index is built.
+    pub fn balance_index_built(&self) -> bool {
+        self.balance_index_built_entry().get().unwrap_or_default()
+    }
+
+    /// Returns `Entry` which is set once the last activity heights are filled in
+    /// for the wallets not changed since the heights were introduced.
+    pub fn last_activity_filled_entry(&self) -> Entry<&T, bool> {
+        Entry::new(LAST_ACTIVITY_FILLED_ENTRY, &self.view)
+    }
+
+    /// Returns whether the last activity heights are filled in.
+    pub fn last_activity_filled(&self) -> bool {
+        self.last_activity_filled_entry().get().unwrap_or_default()
+    }
+
+    /// Returns wallets changed since the last balance checkpoint.
+    pub fn wallets_pending_checkpoint(&self) -> KeySetIndex<&T, PublicKey> {
+        KeySetIndex::new(PENDING_CHECKPOINTS_SET, &self.view)
+    }
+
+    /// Returns `ProofMapIndex` with lifetime transfer totals of the wallets.
+    pub fn wallet_activities(&self) -> ProofMapIndex<&T, PublicKey, WalletActivity> {
+        ProofMapIndex::new(WALLET_ACTIVITY_TABLE, &self.view)
+    }
+
+    /// Returns transfer totals of the wallet with the given public key.
+    pub fn wallet_activity(&self, pub_key: &PublicKey) -> Option<WalletActivity> {
+        self.wallet_activities().get(pub_key)
+    }
+
+    /// Returns the metadata of the wallet: string values by string keys. The metadata
+    /// is committed to by `metadata_hash` of the wallet.
+    pub fn wallet_metadata(&self, pub_key: &PublicKey) -> MapIndex<&T, String, String> {
+        MapIndex::new_in_family(WALLET_METADATA_FAMILY, pub_key, &self.view)
+    }
+
+    /// Recomputes the hash of the wallet metadata from its entries.
+    pub fn wallet_metadata_hash(&self, pub_key: &PublicKey) -> Hash {
+        metadata_hash(self.wallet_metadata(pub_key).iter())
+    }
+
+    /// Returns public keys of the wallets created or updated by the transaction with
+    /// the given hash, in the order of the updates. Settled multisignature transfers are
+    /// attributed to the settling transactions.
+    pub fn affected_wallets(&self, tx_hash: &Hash) -> ListIndex<&T, PublicKey> {
+        ListIndex::new_in_family(AFFECTED_WALLETS_FAMILY, tx_hash, &self.view)
+    }
+
+    /// Returns the outcomes of the approvals carried by the `ApproveTransferMultisigBatch`
+    /// transaction with the given hash, in the order of the approvals. The list is empty
+    /// if the transaction has failed.
+    pub fn approval_batch_results(&self, tx_hash: &Hash) -> ListIndex<&T, BatchApprovalResult> {
+        ListIndex::new_in_family(APPROVAL_BATCH_RESULTS_FAMILY, tx_hash, &self.view)
+    }
+
+    /// Returns `ProofMapIndex` with lifetime balance movement totals of the wallets.
+    pub fn wallets_stats(&self) -> ProofMapIndex<&T, PublicKey, WalletStats> {
+        ProofMapIndex::new(WALLET_STATS_TABLE, &self.view)
+    }
+
+    /// Returns balance movement totals of the wallet with the given public key.
+    pub fn wallet_stats(&self, pub_key: &PublicKey) -> Option<WalletStats> {
+        self.wallets_stats().get(pub_key)
+    }
+
+    /// Returns the volume of transfers between the wallet and each of its counterparties,
+    /// in both directions.
+    pub fn wallet_counterparties(
+        &self,
+        pub_key: &PublicKey,
+    ) -> ProofMapIndex<&T, PublicKey, Amount> {
+        ProofMapIndex::new_in_family(WALLET_COUNTERPARTIES_FAMILY, pub_key, &self.view)
     }
 
     /// Returns `ProofMapIndex` with multisignature transfers.
@@ -66,9 +644,406 @@ where
         ProofMapIndex::new(MULTISIG_TRANSFER_TABLE, &self.view)
     }
 
-    /// Returns multisignature transfer for the given tx hash.
+    /// Returns multisignature transfer for the given tx hash. Transfers written by
+    /// the previous versions of the service are read as well, so that transactions executed
+    /// before the migration can update them: the sender, receiver, amount and approvers
+    /// are restored from the `TransferMultisig` transaction, the unknown rejecting approver
+    /// is filled with zeros, and the approvals without heights are read at `Height(0)`.
     pub fn multisig_transfer(&self, tx_hash: Hash) -> Option<MultisignatureTransfer> {
-        self.multisig_transfers().get(&tx_hash)
+        let bytes = self.raw_multisig_transfers().get(&tx_hash)?;
+        let mut pb: proto::MultisignatureTransfer = protobuf::parse_from_bytes(&bytes)
+            .expect("Malformed multisignature transfer in the storage");
+        if !pb.has_from() {
+            let general_schema = blockchain::Schema::new(self.view.as_ref());
+            let (from, tx) = transfer_multisig_tx(&general_schema, &tx_hash)
+                .expect("Transaction of the multisignature transfer is not found");
+            pb.set_from(from.to_pb());
+            pb.set_to(tx.to.to_pb());
+            let (amount, exact_amount) = tx.amount.to_pb_fields();
+            pb.set_amount(amount);
+            if let Some(exact_amount) = exact_amount {
+                pb.set_exact_amount(exact_amount);
+            }
+            pb.set_approvers(tx.approvers.to_pb());
+        }
+        if !pb.has_rejected_by() {
+            pb.set_rejected_by(PublicKey::zero().to_pb());
+        }
+        for approver in pb.take_legacy_approved_by() {
+            let mut approval = proto::Approval::new();
+            approval.set_approver(approver);
+            pb.mut_approved_by().push(approval);
+        }
+        Some(
+            MultisignatureTransfer::from_pb(pb)
+                .expect("Cannot read multisignature transfer from the storage"),
+        )
+    }
+
+    /// Returns a single proof of the multisignature transfers with the given hashes,
+    /// or of their absence, against the root hash of the multisignature transfers table.
+    pub fn multisig_transfers_multiproof(
+        &self,
+        tx_hashes: &[Hash],
+    ) -> MapProof<Hash, MultisignatureTransfer> {
+        self.multisig_transfers()
+            .get_multiproof(tx_hashes.iter().cloned())
+    }
+
+    /// Returns up to `limit` multisignature transfers in the order of their hashes,
+    /// following the `after` hash if it is given.
+    pub fn multisig_transfers_range(
+        &self,
+        after: Option<&Hash>,
+        limit: usize,
+    ) -> Vec<(Hash, MultisignatureTransfer)> {
+        entries_after(&self.multisig_transfers(), after, limit)
+    }
+
+    /// Returns up to `limit` hashes of multisignature transfers in the given state
+    /// in ascending order, following the `after` hash if it is given.
+    pub fn multisig_transfers_by_state_range(
+        &self,
+        state: &State,
+        after: Option<&Hash>,
+        limit: usize,
+    ) -> Vec<Hash> {
+        entries_after(&self.multisig_transfers_by_state(state), after, limit)
+            .into_iter()
+            .map(|(tx_hash, ())| tx_hash)
+            .collect()
+    }
+
+    /// Returns the multisignature transfers as raw bytes. Transfers written by the
+    /// previous versions of the service lack the transfer parameters, the rejection
+    /// details or the approval heights and cannot be read as `MultisignatureTransfer`s
+    /// until migrated.
+    pub fn raw_multisig_transfers(&self) -> ProofMapIndex<&T, Hash, Vec<u8>> {
+        ProofMapIndex::new(MULTISIG_TRANSFER_TABLE, &self.view)
+    }
+
+    /// Returns the set of hashes of multisignature transfers in the given state.
+    pub fn multisig_transfers_by_state(&self, state: &State) -> ProofMapIndex<&T, Hash, ()> {
+        let state_id = state.clone() as u8;
+        ProofMapIndex::new_in_family(MULTISIG_BY_STATE_FAMILY, &state_id, &self.view)
+    }
+
+    /// Returns hashes of multisignature transfers created by the given sender,
+    /// in the order of creation. Entries are never removed; the current state
+    /// of a transfer is stored in `multisig_transfers` until the transfer is
+    /// collected.
+    pub fn outgoing_multisig_transfers(&self, sender: &PublicKey) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::new_in_family(MULTISIG_OUTGOING_FAMILY, sender, &self.view)
+    }
+
+    /// Returns `ProofMapIndex` which maps senders to the root hashes of their
+    /// `outgoing_multisig_transfers` lists.
+    pub fn outgoing_multisig_roots(&self) -> ProofMapIndex<&T, PublicKey, Hash> {
+        ProofMapIndex::new(MULTISIG_OUTGOING_TABLE, &self.view)
+    }
+
+    /// Returns settled multisignature transfers ordered by the settlement height.
+    /// Keys are built with `settled_multisig_key`.
+    pub fn settled_multisig_transfers(&self) -> KeySetIndex<&T, Vec<u8>> {
+        KeySetIndex::new(SETTLED_MULTISIG_SET, &self.view)
+    }
+
+    /// Returns `ProofMapIndex` with the final states (as `State` codes) of the settled
+    /// multisignature transfers removed after the retention period.
+    pub fn multisig_tombstones(&self) -> ProofMapIndex<&T, Hash, u8> {
+        ProofMapIndex::new(MULTISIG_TOMBSTONE_TABLE, &self.view)
+    }
+
+    /// Returns the final state of the collected multisignature transfer.
+    pub fn collected_multisig_state(&self, tx_hash: &Hash) -> Option<State> {
+        self.multisig_tombstones()
+            .get(tx_hash)
+            .and_then(State::from_code)
+    }
+
+    /// Returns hashes of multisignature transfers in process which list the given
+    /// public key among their approvers, whether or not it has approved them yet.
+    pub fn approver_pending(&self, approver: &PublicKey) -> KeySetIndex<&T, Hash> {
+        KeySetIndex::new_in_family(APPROVER_PENDING_FAMILY, approver, &self.view)
+    }
+
+    /// Returns hashes of the stored multisignature transfers to the given receiver,
+    /// whatever their state. Entries are removed when the transfers are collected.
+    pub fn incoming_multisig_transfers(&self, receiver: &PublicKey) -> KeySetIndex<&T, Hash> {
+        KeySetIndex::new_in_family(MULTISIG_INCOMING_FAMILY, receiver, &self.view)
+    }
+
+    /// Returns hashes of the stored multisignature transfers which list the given
+    /// public key among their approvers, whatever their state. Unlike `approver_pending`,
+    /// entries are kept once the transfers are settled and removed when they are
+    /// collected.
+    pub fn approver_multisig_transfers(&self, approver: &PublicKey) -> KeySetIndex<&T, Hash> {
+        KeySetIndex::new_in_family(APPROVER_MULTISIG_FAMILY, approver, &self.view)
+    }
+
+    /// Returns the rejected and expired multisignature transfers which involve
+    /// the wallet as the sender, the receiver or one of the approvers, ordered
+    /// by the settlement height and then by the hash. Collected transfers are
+    /// not returned.
+    pub fn multisig_failures(&self, pub_key: &PublicKey) -> Vec<(Hash, MultisignatureTransfer)> {
+        let tx_hashes = self
+            .outgoing_multisig_transfers(pub_key)
+            .iter()
+            .chain(self.incoming_multisig_transfers(pub_key).iter())
+            .chain(self.approver_multisig_transfers(pub_key).iter())
+            .collect::<BTreeSet<_>>();
+        let mut failures = tx_hashes
+            .into_iter()
+            .filter_map(|tx_hash| {
+                self.multisig_transfer(tx_hash)
+                    .map(|transfer| (tx_hash, transfer))
+            })
+            .filter(|(_, transfer)| match transfer.state {
+                State::Rejected | State::Expired => true,
+                State::InProcess | State::Done | State::Cancelled => false,
+            })
+            .collect::<Vec<_>>();
+        failures.sort_by_key(|(tx_hash, transfer)| (transfer.settled_at_height, *tx_hash));
+        failures
+    }
+
+    /// Returns `ProofMapIndex` with the owners of the joint wallets by the wallet addresses.
+    pub fn joint_wallets(&self) -> ProofMapIndex<&T, PublicKey, JointWallet> {
+        ProofMapIndex::new(JOINT_WALLETS_TABLE, &self.view)
+    }
+
+    /// Returns the owners of the joint wallet with the given address, or `None`
+    /// if the wallet does not exist or is not joint.
+    pub fn joint_wallet(&self, address: &PublicKey) -> Option<JointWallet> {
+        self.joint_wallets().get(address)
+    }
+
+    /// Returns `ProofMapIndex` with the spends from the joint wallets by the hashes
+    /// of the `ProposeJointSpend` transactions. The owners of the wallet are the approvers
+    /// of the spend.
+    pub fn joint_spends(&self) -> ProofMapIndex<&T, Hash, MultisignatureTransfer> {
+        ProofMapIndex::new(JOINT_SPENDS_TABLE, &self.view)
+    }
+
+    /// Returns the spend from a joint wallet proposed by the transaction with
+    /// the given hash.
+    pub fn joint_spend(&self, proposal: &Hash) -> Option<MultisignatureTransfer> {
+        self.joint_spends().get(proposal)
+    }
+
+    /// Returns hashes of the proposals of spends from the joint wallet which await
+    /// signatures of the owners.
+    pub fn joint_pending(&self, address: &PublicKey) -> KeySetIndex<&T, Hash> {
+        KeySetIndex::new_in_family(JOINT_PENDING_FAMILY, address, &self.view)
+    }
+
+    /// Returns `ProofMapIndex` with the payment streams by the hashes of the `OpenStream`
+    /// transactions. Closed streams are kept.
+    pub fn payment_streams(&self) -> ProofMapIndex<&T, Hash, PaymentStream> {
+        ProofMapIndex::new(PAYMENT_STREAMS_TABLE, &self.view)
+    }
+
+    /// Returns the payment stream opened by the transaction with the given hash.
+    pub fn payment_stream(&self, stream: &Hash) -> Option<PaymentStream> {
+        self.payment_streams().get(stream)
+    }
+
+    /// Returns `Entry` with the staking totals of the service.
+    pub fn staking_state_entry(&self) -> Entry<&T, StakingState> {
+        Entry::new(STAKING_STATE_ENTRY, &self.view)
+    }
+
+    /// Returns the staking totals of the service.
+    pub fn staking_state(&self) -> StakingState {
+        self.staking_state_entry().get().unwrap_or_default()
+    }
+
+    /// Returns `ProofMapIndex` with the reward accounting of the staking wallets.
+    /// A wallet leaves the index once it has neither a stake nor unclaimed rewards.
+    pub fn stake_positions(&self) -> ProofMapIndex<&T, PublicKey, StakePosition> {
+        ProofMapIndex::new(STAKE_POSITIONS_TABLE, &self.view)
+    }
+
+    /// Returns the reward accounting of the staking wallet.
+    pub fn stake_position(&self, key: &PublicKey) -> Option<StakePosition> {
+        self.stake_positions().get(key)
+    }
+
+    /// Returns the unclaimed staking rewards of the wallet, including the ones earned
+    /// since the last settlement.
+    pub fn staking_rewards(&self, key: &PublicKey) -> Amount {
+        let staked = self
+            .wallet(key)
+            .map_or(Amount::ZERO, |wallet| wallet.staked);
+        let reward_per_token = self.staking_state().reward_per_token;
+        self.stake_position(key).map_or(Amount::ZERO, |position| {
+            position.settle(staked, reward_per_token).pending
+        })
+    }
+
+    /// Returns items scheduled for processing in the block at the given height
+    /// in the order of their scheduling. Processed items are removed.
+    pub fn due_items(&self, height: Height) -> ListIndex<&T, DueItem> {
+        ListIndex::new_in_family(DUE_ITEMS_FAMILY, &height.0, &self.view)
+    }
+
+    /// Returns `Entry` with the height up to which the due items are processed.
+    pub fn due_items_processed_entry(&self) -> Entry<&T, u64> {
+        Entry::new(DUE_ITEMS_PROCESSED_ENTRY, &self.view)
+    }
+
+    /// Returns the height up to which the due items are processed. `None` if the items
+    /// have not been processed since the height was recorded.
+    pub fn due_items_processed(&self) -> Option<Height> {
+        self.due_items_processed_entry().get().map(Height)
+    }
+
+    /// Returns `ProofMapIndex` which maps keys of `(author, seed)` pairs
+    /// (see `transfer_seed_key`) to the root hashes of the `seed_transfers` lists.
+    pub fn transfers_by_seed(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new(TRANSFERS_BY_SEED_TABLE, &self.view)
+    }
+
+    /// Returns hashes of transfers signed by the given author with the given seed,
+    /// in the order of execution.
+    pub fn seed_transfers(&self, author: &PublicKey, seed: u64) -> ProofListIndex<&T, Hash> {
+        let key = transfer_seed_key(author, seed);
+        ProofListIndex::new_in_family(TRANSFERS_BY_SEED_FAMILY, &key, &self.view)
+    }
+
+    /// Returns `MapIndex` with the number of successfully executed transactions
+    /// by transaction type name.
+    pub fn transaction_counts(&self) -> MapIndex<&T, String, u64> {
+        MapIndex::new(TX_COUNT_TABLE, &self.view)
+    }
+
+    /// Returns `MapIndex` with the number of failed transactions by error code.
+    pub fn transaction_error_counts(&self) -> MapIndex<&T, u8, u64> {
+        MapIndex::new(TX_ERROR_COUNT_TABLE, &self.view)
+    }
+
+    /// Returns `Entry` with the total amount of currency in all wallets.
+    pub fn total_supply_entry(&self) -> Entry<&T, Amount> {
+        Entry::new(TOTAL_SUPPLY_ENTRY, &self.view)
+    }
+
+    /// Returns `Entry` with the number of created wallets.
+    pub fn wallet_count_entry(&self) -> Entry<&T, u64> {
+        Entry::new(WALLET_COUNT_ENTRY, &self.view)
+    }
+
+    /// Returns `Entry` with the number of executed transfers.
+    pub fn transfer_count_entry(&self) -> Entry<&T, u64> {
+        Entry::new(TRANSFER_COUNT_ENTRY, &self.view)
+    }
+
+    /// Returns `Entry` with the cumulative amount of transferred currency.
+    pub fn transfer_volume_entry(&self) -> Entry<&T, Amount> {
+        Entry::new(TRANSFER_VOLUME_ENTRY, &self.view)
+    }
+
+    /// Returns whether legacy wallet histories are migrated.
+    pub fn legacy_history_migrated(&self) -> bool {
+        self.legacy_history_migrated_entry()
+            .get()
+            .unwrap_or_default()
+    }
+
+    /// Returns whether legacy multisignature transfers are migrated.
+    pub fn legacy_multisig_migrated(&self) -> bool {
+        self.legacy_multisig_migrated_entry()
+            .get()
+            .unwrap_or_default()
+    }
+
+    /// Returns the total amount of currency in all wallets.
+    pub fn total_supply(&self) -> Amount {
+        self.total_supply_entry().get().unwrap_or_default()
+    }
+
+    /// Returns the configuration of the service actual for the next block.
+    ///
+    /// Changes of the global configuration are not checked by the service, so an invalid
+    /// stored configuration is logged and replaced by the latest valid configuration
+    /// preceding it, or by the default one if there is none.
+    ///
+    /// The configuration is parsed on every call, so transactions read it once and pass
+    /// the returned value to the checks they make.
+    pub fn config(&self) -> Config {
+        let general_schema = blockchain::Schema::new(self.view.as_ref());
+        let next_height = Height(general_schema.block_hashes_by_height().len());
+        let configs = general_schema.configs();
+        let references = general_schema.configs_actual_from();
+        let mut is_actual = true;
+        for index in (0..references.len()).rev() {
+            let reference = references
+                .get(index)
+                .expect("Configuration reference is absent");
+            if reference.actual_from() > next_height {
+                continue;
+            }
+            let configuration = configs
+                .get(reference.cfg_hash())
+                .expect("Referenced configuration is absent");
+            match Config::from_stored(configuration.services.get(SERVICE_NAME)) {
+                Ok(config) => return config,
+                Err(ref errors) if is_actual => {
+                    error!(
+                        "Invalid service configuration actual from height {}, \
+                         the previous valid one is used instead: {:?}",
+                        reference.actual_from(),
+                        errors
+                    );
+                    is_actual = false;
+                }
+                Err(_) => {}
+            }
+        }
+        Config::default()
+    }
+
+    /// Checks that the given amount of currency can be created without overflowing
+    /// the total supply.
+    pub fn check_supply_increase(&self, amount: Amount) -> Result<(), Error> {
+        self.total_supply()
+            .checked_add(amount)
+            .map(drop)
+            .ok_or(Error::SupplyOverflow)
+    }
+
+    /// Returns the number of created wallets.
+    pub fn wallet_count(&self) -> u64 {
+        self.wallet_count_entry().get().unwrap_or_default()
+    }
+
+    /// Returns the number of executed transfers, including settled multisignature ones.
+    pub fn transfer_count(&self) -> u64 {
+        self.transfer_count_entry().get().unwrap_or_default()
+    }
+
+    /// Returns the cumulative amount of currency moved by executed transfers.
+    pub fn transfer_volume(&self) -> Amount {
+        self.transfer_volume_entry().get().unwrap_or_default()
+    }
+
+    /// Returns `MapIndex` with the amounts of currency moved by the transfers of the blocks
+    /// not summarized yet, keyed by the block heights. Entries are removed once their
+    /// blocks are summarized.
+    pub fn block_transfer_volumes(&self) -> MapIndex<&T, u64, Amount> {
+        MapIndex::new(BLOCK_TRANSFER_VOLUMES_TABLE, &self.view)
+    }
+
+    /// Returns the amount of currency moved by the transfers of the block at the given
+    /// height, unless the block is summarized already.
+    pub fn block_transfer_volume(&self, height: Height) -> Amount {
+        self.block_transfer_volumes()
+            .get(&height.0)
+            .unwrap_or_default()
+    }
+
+    /// Returns `ProofListIndex` with the summaries of the blocks indexed by their heights.
+    pub fn block_summaries(&self) -> ProofListIndex<&T, BlockSummary> {
+        ProofListIndex::new(BLOCK_SUMMARIES_LIST, &self.view)
     }
 
     /// Returns the state hash of cryptocurrency service.
@@ -76,6 +1051,33 @@ where
         vec![
             self.wallets().merkle_root(),
             self.multisig_transfers().merkle_root(),
+            self.total_supply_entry().hash(),
+            self.wallet_count_entry().hash(),
+            self.transfer_count_entry().hash(),
+            self.transfer_volume_entry().hash(),
+            self.transfers_by_seed().merkle_root(),
+            self.wallets_by_creation().merkle_root(),
+            self.multisig_transfers_by_state(&State::InProcess)
+                .merkle_root(),
+            self.multisig_transfers_by_state(&State::Rejected)
+                .merkle_root(),
+            self.multisig_transfers_by_state(&State::Done).merkle_root(),
+            self.wallet_activities().merkle_root(),
+            self.wallet_names().merkle_root(),
+            self.wallets_stats().merkle_root(),
+            self.outgoing_multisig_roots().merkle_root(),
+            self.multisig_tombstones().merkle_root(),
+            self.multisig_transfers_by_state(&State::Expired)
+                .merkle_root(),
+            self.multisig_transfers_by_state(&State::Cancelled)
+                .merkle_root(),
+            self.joint_wallets().merkle_root(),
+            self.joint_spends().merkle_root(),
+            self.payment_streams().merkle_root(),
+            self.staking_state_entry().hash(),
+            self.stake_positions().merkle_root(),
+            self.block_summaries().merkle_root(),
+            self.name_history_roots().merkle_root(),
         ]
     }
 }
@@ -87,37 +1089,839 @@ impl<'a> Schema<&'a mut Fork> {
         ProofMapIndex::new(WALLET_TABLE, &mut self.view)
     }
 
-    /// Returns history for the wallet by the given public key.
-    pub fn wallet_history_mut(
+    /// Returns mutable `Entry` with the version of the layout of the stored data.
+    pub fn schema_version_entry_mut(&mut self) -> Entry<&mut Fork, u32> {
+        Entry::new(SCHEMA_VERSION_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `ProofMapIndex` with the wallets as raw bytes.
+    pub fn raw_wallets_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Vec<u8>> {
+        ProofMapIndex::new(WALLET_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` which is set once the wallets written by the previous
+    /// versions of the service are migrated.
+    pub fn legacy_wallets_migrated_entry_mut(&mut self) -> Entry<&mut Fork, bool> {
+        Entry::new(LEGACY_WALLETS_MIGRATED_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable index of wallet names.
+    pub fn wallet_names_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, PublicKey> {
+        ProofMapIndex::new(WALLET_NAMES_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable list of the wallets having the indexed name with the given key.
+    pub fn wallet_name_holders_mut(&mut self, name_key: &Hash) -> ListIndex<&mut Fork, PublicKey> {
+        ListIndex::new_in_family(WALLET_NAME_HOLDERS_FAMILY, name_key, &mut self.view)
+    }
+
+    /// Returns mutable list of the wallets in the order of their creation.
+    pub fn wallets_by_creation_mut(&mut self) -> ProofListIndex<&mut Fork, PublicKey> {
+        ProofListIndex::new(WALLETS_BY_CREATION_LIST, &mut self.view)
+    }
+
+    /// Returns mutable chunk of the wallet history.
+    pub fn wallet_history_chunk_mut(
+        &mut self,
+        pub_key: &PublicKey,
+        chunk: u64,
+    ) -> ProofListIndex<&mut Fork, HistoryRecord> {
+        let key = history_chunk_key(pub_key, chunk);
+        ProofListIndex::new_in_family(HISTORY_CHUNKS_FAMILY, &key, &mut self.view)
+    }
+
+    /// Returns mutable root hashes of the wallet history chunks.
+    pub fn wallet_history_chunk_roots_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, Hash> {
+        ProofListIndex::new_in_family(HISTORY_CHUNK_ROOTS_FAMILY, pub_key, &mut self.view)
+    }
+
+    /// Returns mutable numbers of the first retained history chunks of the wallets.
+    pub fn history_first_chunks_mut(&mut self) -> MapIndex<&mut Fork, PublicKey, u64> {
+        MapIndex::new(HISTORY_FIRST_CHUNK_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable set of wallets whose history has changed since the previous pruning.
+    pub fn wallets_pending_pruning_mut(&mut self) -> KeySetIndex<&mut Fork, PublicKey> {
+        KeySetIndex::new(PENDING_PRUNING_SET, &mut self.view)
+    }
+
+    /// Appends the record to the wallet history with the given chain of the filled chunks
+    /// and returns the new root hash and chain of the history.
+    fn push_history_record(
+        &mut self,
+        pub_key: &PublicKey,
+        history_chain: Hash,
+        record: HistoryRecord,
+    ) -> (Hash, Hash) {
+        self.push_history_records(pub_key, history_chain, vec![record])
+    }
+
+    /// Appends the records to the wallet history with the given chain of the filled chunks
+    /// and returns the new root hash and chain of the history. The root of each changed
+    /// chunk is updated once, after all the records are appended, and is chained if
+    /// the chunk is filled.
+    fn push_history_records(
+        &mut self,
+        pub_key: &PublicKey,
+        mut history_chain: Hash,
+        records: Vec<HistoryRecord>,
+    ) -> (Hash, Hash) {
+        let mut chunk_count = self.wallet_history_chunk_roots(pub_key).len();
+        let mut changed_chunks = Vec::new();
+        for record in records {
+            let last_chunk_full = chunk_count == 0
+                || self.wallet_history_chunk(pub_key, chunk_count - 1).len() == HISTORY_CHUNK_SIZE;
+            if last_chunk_full {
+                chunk_count += 1;
+            }
+            let chunk = chunk_count - 1;
+            self.wallet_history_chunk_mut(pub_key, chunk).push(record);
+            if changed_chunks.last() != Some(&chunk) {
+                changed_chunks.push(chunk);
+            }
+        }
+        self.wallets_pending_pruning_mut().insert(*pub_key);
+
+        for chunk in changed_chunks {
+            let (chunk_root, chunk_len) = {
+                let chunk_records = self.wallet_history_chunk(pub_key, chunk);
+                (chunk_records.merkle_root(), chunk_records.len())
+            };
+            if chunk_len == HISTORY_CHUNK_SIZE {
+                history_chain = chain_history_chunk(history_chain, &chunk_root);
+            }
+            let mut roots = self.wallet_history_chunk_roots_mut(pub_key);
+            if chunk < roots.len() {
+                roots.set(chunk, chunk_root);
+            } else {
+                roots.push(chunk_root);
+            }
+        }
+        let history_hash = self.wallet_history_chunk_roots(pub_key).merkle_root();
+        (history_hash, history_chain)
+    }
+
+    /// Drops the oldest chunks of the wallet histories changed since the previous
+    /// pruning, so that at least `max_entries` latest records of every history are
+    /// retained. If `max_entries` is `None`, nothing is dropped.
+    pub fn prune_wallet_histories(&mut self, max_entries: Option<u64>) {
+        let pending = self.wallets_pending_pruning().iter().collect::<Vec<_>>();
+        self.wallets_pending_pruning_mut().clear();
+        let max_entries = match max_entries {
+            Some(max_entries) => max_entries,
+            None => return,
+        };
+
+        for pub_key in pending {
+            let history_len = self.wallet(&pub_key).map_or(0, |wallet| wallet.history_len);
+            let first_chunk = self.first_history_chunk(&pub_key);
+            let mut retained_from = first_chunk;
+            // The chunk is dropped only if the records after it are enough, so the last
+            // chunk is never dropped.
+            while (retained_from + 1) * HISTORY_CHUNK_SIZE + max_entries <= history_len {
+                self.wallet_history_chunk_mut(&pub_key, retained_from)
+                    .clear();
+                retained_from += 1;
+            }
+            if retained_from != first_chunk {
+                self.history_first_chunks_mut().put(&pub_key, retained_from);
+            }
+        }
+    }
+
+    /// Returns mutable hashes of the records of the wallet history in the segmented layout.
+    pub fn segmented_history_hashes_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, Hash> {
+        ProofListIndex::new_in_family(SEGMENTED_HISTORY_HASHES_FAMILY, pub_key, &mut self.view)
+    }
+
+    /// Returns mutable segment of the wallet history in the segmented layout.
+    pub fn history_segment_mut(
+        &mut self,
+        pub_key: &PublicKey,
+        segment: u64,
+    ) -> ListIndex<&mut Fork, HistoryRecord> {
+        let key = history_segment_key(pub_key, segment);
+        ListIndex::new_in_family(HISTORY_SEGMENTS_FAMILY, &key, &mut self.view)
+    }
+
+    /// Returns mutable numbers of the first retained history segments of the wallets
+    /// in the segmented layout.
+    pub fn history_first_segments_mut(&mut self) -> MapIndex<&mut Fork, PublicKey, u64> {
+        MapIndex::new(HISTORY_FIRST_SEGMENT_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable history of the wallet in the format with a single list of records.
+    pub fn unchunked_wallet_history_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, HistoryRecord> {
+        ProofListIndex::new_in_family(UNCHUNKED_WALLET_HISTORY_FAMILY, pub_key, &mut self.view)
+    }
+
+    /// Returns mutable history of the wallet in the legacy format.
+    pub fn legacy_wallet_history_mut(
         &mut self,
         public_key: &PublicKey,
     ) -> ProofListIndex<&mut Fork, Hash> {
-        ProofListIndex::new_in_family(WALLET_HISTORY_FAMILY, public_key, &mut self.view)
+        ProofListIndex::new_in_family(LEGACY_WALLET_HISTORY_FAMILY, public_key, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` which is set once legacy wallet histories are migrated.
+    pub fn legacy_history_migrated_entry_mut(&mut self) -> Entry<&mut Fork, bool> {
+        Entry::new(LEGACY_HISTORY_MIGRATED_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` which is set once legacy multisignature transfers
+    /// are migrated.
+    pub fn legacy_multisig_migrated_entry_mut(&mut self) -> Entry<&mut Fork, bool> {
+        Entry::new(LEGACY_MULTISIG_MIGRATED_ENTRY, &mut self.view)
+    }
+
+    /// Moves the wallet history stored in segments to chunks and updates the history
+    /// hash of the wallet. Segments pruned by the node stay pruned: the roots of their
+    /// chunks are computed from the kept hashes of the records, which are the hashes
+    /// of the leaves of the chunks.
+    pub fn chunk_segmented_history(&mut self, pub_key: &PublicKey) {
+        let hashes = self
+            .segmented_history_hashes(pub_key)
+            .iter()
+            .collect::<Vec<_>>();
+        let first_segment = self
+            .history_first_segments()
+            .get(pub_key)
+            .unwrap_or_default();
+
+        for (chunk, chunk_hashes) in hashes.chunks(HISTORY_CHUNK_SIZE as usize).enumerate() {
+            let chunk = chunk as u64;
+            let chunk_root = if chunk < first_segment {
+                root_hash(chunk_hashes)
+            } else {
+                let records = self
+                    .history_segment(pub_key, chunk)
+                    .iter()
+                    .collect::<Vec<_>>();
+                let mut records_chunk = self.wallet_history_chunk_mut(pub_key, chunk);
+                records_chunk.extend(records);
+                records_chunk.merkle_root()
+            };
+            self.wallet_history_chunk_roots_mut(pub_key)
+                .push(chunk_root);
+            self.history_segment_mut(pub_key, chunk).clear();
+        }
+        if first_segment > 0 {
+            self.history_first_chunks_mut().put(pub_key, first_segment);
+            self.history_first_segments_mut().remove(pub_key);
+        }
+        self.segmented_history_hashes_mut(pub_key).clear();
+
+        let (history_hash, history_chain) = {
+            let roots = self.wallet_history_chunk_roots(pub_key);
+            let filled_roots = roots
+                .iter()
+                .take(hashes.len() / HISTORY_CHUNK_SIZE as usize)
+                .collect::<Vec<_>>();
+            (roots.merkle_root(), history_chain(&filled_roots))
+        };
+        if let Some(wallet) = self.wallet(pub_key) {
+            let wallet = Wallet {
+                history_hash,
+                history_chain,
+                ..wallet
+            };
+            self.wallets_mut().put(pub_key, wallet);
+        }
+    }
+
+    /// Replaces the legacy history of the wallet with the given records, which are
+    /// prepended to the records written since the legacy format was abandoned.
+    /// The history length and hash of the wallet are updated accordingly, and
+    /// the legacy lists of the wallet are cleared.
+    pub fn migrate_legacy_history(&mut self, pub_key: &PublicKey, legacy: Vec<HistoryRecord>) {
+        let recent = self.wallet_history_from(pub_key, 0).collect::<Vec<_>>();
+        let chunk_count = self.wallet_history_chunk_roots(pub_key).len();
+        for chunk in 0..chunk_count {
+            self.wallet_history_chunk_mut(pub_key, chunk).clear();
+        }
+        self.wallet_history_chunk_roots_mut(pub_key).clear();
+        self.history_first_chunks_mut().remove(pub_key);
+
+        let mut history_len = 0;
+        let (mut history_hash, mut history_chain) = (Hash::zero(), Hash::zero());
+        let mut last_tx_hash = None;
+        for record in legacy.into_iter().chain(recent) {
+            last_tx_hash = Some(record.tx_hash);
+            let hashes = self.push_history_record(pub_key, history_chain, record);
+            history_hash = hashes.0;
+            history_chain = hashes.1;
+            history_len += 1;
+        }
+        self.legacy_wallet_history_mut(pub_key).clear();
+        self.unchunked_wallet_history_mut(pub_key).clear();
+
+        if let Some(wallet) = self.wallet(pub_key) {
+            let wallet = Wallet {
+                history_len,
+                history_hash,
+                history_chain,
+                last_tx_hash: last_tx_hash.unwrap_or_else(Hash::zero),
+                ..wallet
+            };
+            self.wallets_mut().put(pub_key, wallet);
+        }
+    }
+
+    /// Returns mutable `ProofMapIndex` with the roots of the `seed_transfers` lists.
+    pub fn transfers_by_seed_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new(TRANSFERS_BY_SEED_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable list of transfers signed by the given author with the given seed.
+    pub fn seed_transfers_mut(
+        &mut self,
+        author: &PublicKey,
+        seed: u64,
+    ) -> ProofListIndex<&mut Fork, Hash> {
+        let key = transfer_seed_key(author, seed);
+        ProofListIndex::new_in_family(TRANSFERS_BY_SEED_FAMILY, &key, &mut self.view)
+    }
+
+    /// Indexes an executed transfer by its author and seed.
+    pub fn index_transfer_seed(&mut self, author: &PublicKey, seed: u64, transaction: Hash) {
+        let root = {
+            let mut transfers = self.seed_transfers_mut(author, seed);
+            transfers.push(transaction);
+            transfers.merkle_root()
+        };
+        self.transfers_by_seed_mut()
+            .put(&transfer_seed_key(author, seed), root);
+    }
+
+    /// Returns mutable `Entry` with the total amount of currency in all wallets.
+    pub fn total_supply_entry_mut(&mut self) -> Entry<&mut Fork, Amount> {
+        Entry::new(TOTAL_SUPPLY_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` with the number of created wallets.
+    pub fn wallet_count_entry_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new(WALLET_COUNT_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` with the number of executed transfers.
+    pub fn transfer_count_entry_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new(TRANSFER_COUNT_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` with the cumulative amount of transferred currency.
+    pub fn transfer_volume_entry_mut(&mut self) -> Entry<&mut Fork, Amount> {
+        Entry::new(TRANSFER_VOLUME_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `MapIndex` with the amounts of currency moved by the transfers
+    /// of the blocks not summarized yet.
+    pub fn block_transfer_volumes_mut(&mut self) -> MapIndex<&mut Fork, u64, Amount> {
+        MapIndex::new(BLOCK_TRANSFER_VOLUMES_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable list of the changes of the wallet name.
+    pub fn name_history_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, NameChange> {
+        ProofListIndex::new_in_family(NAME_HISTORY_FAMILY, pub_key, &mut self.view)
     }
 
-    /// Create new wallet and append first record to its history.
-    pub fn create_wallet(&mut self, key: &PublicKey, name: &str, transaction: &Hash) {
-        let wallet = {
-            let mut history = self.wallet_history_mut(key);
-            history.push(*transaction);
-            let history_hash = history.merkle_root();
-            Wallet::new(key, name, INITIAL_BALANCE, history.len(), &history_hash)
+    /// Returns mutable `ProofMapIndex` with the roots of the name history lists.
+    pub fn name_history_roots_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Hash> {
+        ProofMapIndex::new(NAME_HISTORY_TABLE, &mut self.view)
+    }
+
+    /// Renames the wallet and records the change in its name history. The name index
+    /// tracks the new name. If the previous name refers to the wallet, it passes to
+    /// the next wallet having it, or is released so that other wallets may take it.
+    /// The balance does not change, so the wallet history is not appended to.
+    pub fn rename_wallet(
+        &mut self,
+        wallet: Wallet,
+        name: &str,
+        transaction: &Hash,
+        height: Height,
+    ) {
+        let pub_key = wallet.pub_key;
+        let old_key = wallet_name_key(&wallet.name);
+        let remaining = self
+            .wallet_name_holders(&old_key)
+            .iter()
+            .filter(|holder| *holder != pub_key)
+            .collect::<Vec<_>>();
+        if self.wallet_names().get(&old_key) == Some(pub_key) {
+            match remaining.first() {
+                Some(holder) => self.wallet_names_mut().put(&old_key, *holder),
+                None => self.wallet_names_mut().remove(&old_key),
+            }
+        }
+        {
+            let mut holders = self.wallet_name_holders_mut(&old_key);
+            holders.clear();
+            holders.extend(remaining);
+        }
+        let new_key = wallet_name_key(name);
+        self.wallet_names_mut().put(&new_key, pub_key);
+        self.wallet_name_holders_mut(&new_key).push(pub_key);
+
+        let change = NameChange {
+            height,
+            old_name: wallet.name.clone(),
+            new_name: name.to_owned(),
+            tx_hash: *transaction,
+        };
+        let history_root = {
+            let mut history = self.name_history_mut(&pub_key);
+            history.push(change);
+            history.merkle_root()
+        };
+        self.name_history_roots_mut().put(&pub_key, history_root);
+
+        let wallet = Wallet {
+            name: name.to_owned(),
+            ..wallet.touch_by(*transaction, height)
         };
-        self.wallets_mut().put(key, wallet);
+        self.wallets_mut().put(&pub_key, wallet);
+        self.record_touch(transaction, &pub_key);
+    }
+
+    /// Returns mutable `ProofListIndex` with the summaries of the blocks.
+    pub fn block_summaries_mut(&mut self) -> ProofListIndex<&mut Fork, BlockSummary> {
+        ProofListIndex::new(BLOCK_SUMMARIES_LIST, &mut self.view)
+    }
+
+    /// Returns mutable `MapIndex` with the number of executed transactions by type name.
+    pub fn transaction_counts_mut(&mut self) -> MapIndex<&mut Fork, String, u64> {
+        MapIndex::new(TX_COUNT_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable `MapIndex` with the number of failed transactions by error code.
+    pub fn transaction_error_counts_mut(&mut self) -> MapIndex<&mut Fork, u8, u64> {
+        MapIndex::new(TX_ERROR_COUNT_TABLE, &mut self.view)
+    }
+
+    /// Records a successfully executed transaction of the given type.
+    pub fn record_executed_transaction(&mut self, tx_type: &str) {
+        let count = self.transaction_counts().get(tx_type).unwrap_or_default();
+        self.transaction_counts_mut()
+            .put(&tx_type.to_owned(), count + 1);
+    }
+
+    /// Records a transaction failed with the given error code.
+    pub fn record_transaction_error(&mut self, code: u8) {
+        let count = self
+            .transaction_error_counts()
+            .get(&code)
+            .unwrap_or_default();
+        self.transaction_error_counts_mut().put(&code, count + 1);
+    }
+
+    /// Increases the total supply by the amount of newly created currency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total supply overflows; use `check_supply_increase` beforehand.
+    pub fn increase_total_supply(&mut self, amount: Amount) {
+        let total_supply = self
+            .total_supply()
+            .checked_add(amount)
+            .expect("Total supply overflow");
+        self.total_supply_entry_mut().set(total_supply);
+    }
+
+    /// Decreases the total supply by the amount of destroyed currency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the amount exceeds the total supply.
+    pub fn decrease_total_supply(&mut self, amount: Amount) {
+        let total_supply = self
+            .total_supply()
+            .checked_sub(amount)
+            .expect("Total supply underflow");
+        self.total_supply_entry_mut().set(total_supply);
+    }
+
+    /// Records an executed transfer of the given amount. The amount is added
+    /// to the volume of the block being committed as well.
+    pub fn record_transfer(&mut self, amount: Amount) {
+        let (count, volume) = (self.transfer_count(), self.transfer_volume());
+        self.transfer_count_entry_mut().set(count + 1);
+        self.transfer_volume_entry_mut()
+            .set(volume.saturating_add(amount));
+        let height = blockchain::Schema::new(&*self.view).height().next();
+        let block_volume = self.block_transfer_volume(height);
+        self.block_transfer_volumes_mut()
+            .put(&height.0, block_volume.saturating_add(amount));
+    }
+
+    /// Create new wallet with the given initial balance created at the given height
+    /// and append first record to its history. Returns the stored wallet.
+    pub fn create_wallet(
+        &mut self,
+        key: &PublicKey,
+        name: &str,
+        balance: Amount,
+        transaction: &Hash,
+        created_at: Height,
+    ) -> Wallet {
+        let wallet = self.insert_wallet(key, name, balance, None, transaction, created_at);
+        self.increase_total_supply(balance);
+        wallet
     }
 
-    /// Update existing wallet after transaction.
-    pub fn update_wallet(&mut self, wallet: Wallet, transaction: Hash) {
-        let wallet = {
-            let mut history = self.wallet_history_mut(&wallet.pub_key);
-            history.push(transaction);
-            let history_hash = history.merkle_root();
+    /// Creates a wallet whose initial balance is paid by the `sponsor`. Unlike
+    /// `create_wallet`, the balance is not issued, so the wallet of the sponsor must be
+    /// debited separately. Returns the stored wallet.
+    pub fn create_sponsored_wallet(
+        &mut self,
+        key: &PublicKey,
+        name: &str,
+        balance: Amount,
+        sponsor: &PublicKey,
+        transaction: &Hash,
+        created_at: Height,
+    ) -> Wallet {
+        self.insert_wallet(key, name, balance, Some(*sponsor), transaction, created_at)
+    }
 
-            wallet.update_history_hash(history_hash)
+    fn insert_wallet(
+        &mut self,
+        key: &PublicKey,
+        name: &str,
+        balance: Amount,
+        sponsor: Option<PublicKey>,
+        transaction: &Hash,
+        created_at: Height,
+    ) -> Wallet {
+        let record = HistoryRecord::new(*transaction, created_at, Amount::ZERO, balance);
+        let (history_hash, history_chain) = self.push_history_record(key, Hash::zero(), record);
+        let wallet = Wallet {
+            sponsor,
+            history_chain,
+            ..Wallet::new(
+                key,
+                name,
+                balance,
+                1,
+                &history_hash,
+                transaction,
+                created_at,
+                created_at,
+            )
         };
+        self.wallets_mut().put(key, wallet.clone());
+        self.insert_balance(key, balance);
+        self.record_touch(transaction, key);
+        self.wallets_by_creation_mut().push(*key);
+        let name_key = wallet_name_key(name);
+        if !self.wallet_names().contains(&name_key) {
+            self.wallet_names_mut().put(&name_key, *key);
+        }
+        self.wallet_name_holders_mut(&name_key).push(*key);
+        self.wallets_pending_checkpoint_mut().insert(*key);
+
+        let wallet_count = self.wallet_count();
+        self.wallet_count_entry_mut().set(wallet_count + 1);
+        wallet
+    }
+
+    /// Creates a joint wallet with a zero balance at the address of its owners and stores
+    /// the owners. Returns the stored wallet.
+    pub fn create_joint_wallet(
+        &mut self,
+        joint: JointWallet,
+        name: &str,
+        transaction: &Hash,
+        created_at: Height,
+    ) -> Wallet {
+        let address = joint.address();
+        self.joint_wallets_mut().put(&address, joint);
+        self.create_wallet(&address, name, Amount::ZERO, transaction, created_at)
+    }
+
+    /// Update existing wallet after transaction executed at the given height.
+    /// The balance change recorded in the history is calculated against the stored
+    /// state of the wallet, and the wallet is marked as active at `height`.
+    pub fn update_wallet(&mut self, wallet: Wallet, transaction: Hash, height: Height) {
+        let stored_balance = self.wallet(&wallet.pub_key).map(|stored| stored.balance);
+        let balance_before = stored_balance.unwrap_or_default();
+        let record = HistoryRecord::new(transaction, height, balance_before, wallet.balance);
+        let (history_hash, history_chain) =
+            self.push_history_record(&wallet.pub_key, wallet.history_chain, record);
+        let wallet = wallet
+            .update_history_hash(history_hash, history_chain, transaction)
+            .touch(height);
 
         let key = wallet.pub_key;
+        if stored_balance != Some(wallet.balance) {
+            if let Some(stored_balance) = stored_balance {
+                self.remove_balance(&key, stored_balance);
+            }
+            self.insert_balance(&key, wallet.balance);
+        }
         self.wallets_mut().put(&key, wallet);
+        self.wallets_pending_checkpoint_mut().insert(key);
+        self.record_touch(&transaction, &key);
+    }
+
+    /// Updates several wallets after transaction executed at the given height.
+    /// The result is the same as of calling `update_wallet` for each update in order,
+    /// provided that the updates of the same wallet follow one another, i.e.,
+    /// every update is derived from the previous state of the wallet. However, the history
+    /// of each wallet is appended to and the wallet is written only once.
+    pub fn update_wallets_batch(&mut self, updates: Vec<(Wallet, Hash)>, height: Height) {
+        let mut grouped: Vec<(PublicKey, Vec<(Wallet, Hash)>)> = Vec::new();
+        let mut positions = HashMap::new();
+        for (wallet, transaction) in updates {
+            let key = wallet.pub_key;
+            let position = *positions.entry(key).or_insert_with(|| {
+                grouped.push((key, Vec::new()));
+                grouped.len() - 1
+            });
+            grouped[position].1.push((wallet, transaction));
+        }
+
+        for (key, updates) in grouped {
+            let stored = self.wallet(&key);
+            let stored_balance = stored.as_ref().map(|stored| stored.balance);
+            let mut history_len = stored.as_ref().map_or(0, |stored| stored.history_len);
+            let history_chain = stored
+                .as_ref()
+                .map_or_else(Hash::zero, |stored| stored.history_chain);
+
+            let mut balance_before = stored_balance.unwrap_or_default();
+            let mut records = Vec::with_capacity(updates.len());
+            for (wallet, transaction) in &updates {
+                records.push(HistoryRecord::new(
+                    *transaction,
+                    height,
+                    balance_before,
+                    wallet.balance,
+                ));
+                balance_before = wallet.balance;
+                self.record_touch(transaction, &key);
+            }
+            history_len += records.len() as u64;
+            let (history_hash, history_chain) =
+                self.push_history_records(&key, history_chain, records);
+
+            let (wallet, transaction) = updates.into_iter().last().expect("No updates in group");
+            let wallet = Wallet {
+                history_len,
+                history_hash,
+                history_chain,
+                last_tx_hash: transaction,
+                last_active_at: height,
+                ..wallet
+            };
+
+            if stored_balance != Some(wallet.balance) {
+                if let Some(stored_balance) = stored_balance {
+                    self.remove_balance(&key, stored_balance);
+                }
+                self.insert_balance(&key, wallet.balance);
+            }
+            self.wallets_mut().put(&key, wallet);
+            self.wallets_pending_checkpoint_mut().insert(key);
+        }
+    }
+
+    /// Returns mutable balance checkpoints of the wallet.
+    pub fn balance_checkpoints_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ListIndex<&mut Fork, BalanceCheckpoint> {
+        ListIndex::new_in_family(BALANCE_CHECKPOINTS_FAMILY, pub_key, &mut self.view)
+    }
+
+    /// Returns mutable set of wallets ordered by descending balance.
+    pub fn balance_index_mut(&mut self) -> KeySetIndex<&mut Fork, Vec<u8>> {
+        KeySetIndex::new(BALANCE_INDEX_SET, &mut self.view)
+    }
+
+    /// Adds the wallet with the given balance to the balance index.
+    pub fn insert_balance(&mut self, pub_key: &PublicKey, balance: Amount) {
+        self.balance_index_mut()
+            .insert(balance_index_key(pub_key, balance));
+    }
+
+    /// Removes the wallet with the given balance from the balance index.
+    pub fn remove_balance(&mut self, pub_key: &PublicKey, balance: Amount) {
+        self.balance_index_mut()
+            .remove(&balance_index_key(pub_key, balance));
+    }
+
+    /// Returns mutable `Entry` which is set once the last activity heights are filled in.
+    pub fn last_activity_filled_entry_mut(&mut self) -> Entry<&mut Fork, bool> {
+        Entry::new(LAST_ACTIVITY_FILLED_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` which is set once the balance index is built.
+    pub fn balance_index_built_entry_mut(&mut self) -> Entry<&mut Fork, bool> {
+        Entry::new(BALANCE_INDEX_BUILT_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable set of wallets changed since the last balance checkpoint.
+    pub fn wallets_pending_checkpoint_mut(&mut self) -> KeySetIndex<&mut Fork, PublicKey> {
+        KeySetIndex::new(PENDING_CHECKPOINTS_SET, &mut self.view)
+    }
+
+    /// Writes a balance checkpoint at the given height for every wallet changed since
+    /// the previous checkpoint.
+    pub fn record_balance_checkpoints(&mut self, height: Height) {
+        let pending = self.wallets_pending_checkpoint().iter().collect::<Vec<_>>();
+        for pub_key in pending {
+            let wallet = self
+                .wallet(&pub_key)
+                .expect("Changed wallet does not exist");
+            self.balance_checkpoints_mut(&pub_key)
+                .push(BalanceCheckpoint {
+                    height,
+                    balance: wallet.balance,
+                    history_len: wallet.history_len,
+                });
+        }
+        self.wallets_pending_checkpoint_mut().clear();
+    }
+
+    /// Returns mutable `ProofMapIndex` with lifetime transfer totals of the wallets.
+    pub fn wallet_activities_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, WalletActivity> {
+        ProofMapIndex::new(WALLET_ACTIVITY_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable metadata of the wallet. Use `set_wallet_metadata` to keep
+    /// `metadata_hash` of the wallet up to date.
+    pub fn wallet_metadata_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> MapIndex<&mut Fork, String, String> {
+        MapIndex::new_in_family(WALLET_METADATA_FAMILY, pub_key, &mut self.view)
+    }
+
+    /// Sets the metadata entry of the wallet, or removes it if `value` is `None`,
+    /// and updates `metadata_hash` of the wallet. Fails without changes if the total size
+    /// of the keys and values would exceed `MAX_METADATA_SIZE`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the wallet does not exist.
+    pub fn set_wallet_metadata(
+        &mut self,
+        pub_key: &PublicKey,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<(), MetadataTooLarge> {
+        let wallet = self.wallet(pub_key).expect("Wallet does not exist");
+        let key = key.to_owned();
+        let size = self
+            .wallet_metadata(pub_key)
+            .iter()
+            .filter(|(entry_key, _)| *entry_key != key)
+            .map(|(entry_key, entry_value)| entry_key.len() + entry_value.len())
+            .sum::<usize>()
+            + value.map_or(0, |value| key.len() + value.len());
+        if size > MAX_METADATA_SIZE {
+            return Err(MetadataTooLarge { size });
+        }
+
+        match value {
+            Some(value) => self
+                .wallet_metadata_mut(pub_key)
+                .put(&key, value.to_owned()),
+            None => self.wallet_metadata_mut(pub_key).remove(&key),
+        }
+        let metadata_hash = self.wallet_metadata_hash(pub_key);
+        self.wallets_mut()
+            .put(pub_key, wallet.update_metadata_hash(metadata_hash));
+        Ok(())
+    }
+
+    /// Returns mutable list of the wallets affected by the transaction.
+    pub fn affected_wallets_mut(&mut self, tx_hash: &Hash) -> ListIndex<&mut Fork, PublicKey> {
+        ListIndex::new_in_family(AFFECTED_WALLETS_FAMILY, tx_hash, &mut self.view)
+    }
+
+    /// Records that the transaction has created or updated the wallet. Each wallet
+    /// is recorded once per transaction.
+    pub fn record_touch(&mut self, tx_hash: &Hash, pub_key: &PublicKey) {
+        let mut affected = self.affected_wallets_mut(tx_hash);
+        if !affected.iter().any(|affected_key| affected_key == *pub_key) {
+            affected.push(*pub_key);
+        }
+    }
+
+    /// Records the outcomes of the approvals carried by the `ApproveTransferMultisigBatch`
+    /// transaction with the given hash.
+    pub fn record_approval_batch(&mut self, tx_hash: &Hash, results: Vec<BatchApprovalResult>) {
+        let mut list: ListIndex<&mut Fork, BatchApprovalResult> =
+            ListIndex::new_in_family(APPROVAL_BATCH_RESULTS_FAMILY, tx_hash, &mut self.view);
+        list.extend(results);
+    }
+
+    /// Returns mutable `ProofMapIndex` with lifetime balance movement totals of the wallets.
+    pub fn wallets_stats_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, WalletStats> {
+        ProofMapIndex::new(WALLET_STATS_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable per-counterparty transfer volumes of the wallet.
+    pub fn wallet_counterparties_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, Amount> {
+        ProofMapIndex::new_in_family(WALLET_COUNTERPARTIES_FAMILY, pub_key, &mut self.view)
+    }
+
+    /// Updates transfer totals of both parties of an executed transfer.
+    pub fn record_wallet_transfer(&mut self, from: &PublicKey, to: &PublicKey, amount: Amount) {
+        for &(wallet, counterparty, outgoing) in &[(from, to, true), (to, from, false)] {
+            let counterparties_hash = {
+                let mut counterparties = self.wallet_counterparties_mut(wallet);
+                let volume = counterparties.get(counterparty).unwrap_or_default();
+                counterparties.put(counterparty, volume.saturating_add(amount));
+                counterparties.merkle_root()
+            };
+
+            let mut activity = self.wallet_activity(wallet).unwrap_or_default();
+            if outgoing {
+                activity.sent = activity.sent.saturating_add(amount);
+                activity.outgoing_count += 1;
+            } else {
+                activity.received = activity.received.saturating_add(amount);
+                activity.incoming_count += 1;
+            }
+            activity.counterparties_hash = counterparties_hash;
+            self.wallet_activities_mut().put(wallet, activity);
+        }
+        self.record_wallet_movement(from, false, amount);
+        self.record_wallet_movement(to, true, amount);
+    }
+
+    /// Updates balance movement totals of the wallet which received issued currency.
+    pub fn record_wallet_issue(&mut self, to: &PublicKey, amount: Amount) {
+        self.record_wallet_movement(to, true, amount);
+    }
+
+    fn record_wallet_movement(&mut self, pub_key: &PublicKey, incoming: bool, amount: Amount) {
+        let mut stats = self.wallet_stats(pub_key).unwrap_or_default();
+        if incoming {
+            stats.received_count += 1;
+            stats.received_total = stats.received_total.saturating_add(amount);
+        } else {
+            stats.sent_count += 1;
+            stats.sent_total = stats.sent_total.saturating_add(amount);
+        }
+        self.wallets_stats_mut().put(pub_key, stats);
     }
 
     /// Returns mutable `ProofMapIndex` with multisignature transactions.
@@ -127,18 +1931,482 @@ impl<'a> Schema<&'a mut Fork> {
         ProofMapIndex::new(MULTISIG_TRANSFER_TABLE, &mut self.view)
     }
 
-    /// Put new pending MultisignatureTransfer into wallet.
-    pub fn create_transfer_multisig(&mut self, transaction: Hash) {
-        self.multisig_transfers_mut()
-            .put(&transaction, MultisignatureTransfer::new());
+    /// Returns mutable `ProofMapIndex` with the multisignature transfers as raw bytes.
+    pub fn raw_multisig_transfers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Vec<u8>> {
+        ProofMapIndex::new(MULTISIG_TRANSFER_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable set of hashes of multisignature transfers in the given state.
+    pub fn multisig_transfers_by_state_mut(
+        &mut self,
+        state: &State,
+    ) -> ProofMapIndex<&mut Fork, Hash, ()> {
+        let state_id = state.clone() as u8;
+        ProofMapIndex::new_in_family(MULTISIG_BY_STATE_FAMILY, &state_id, &mut self.view)
+    }
+
+    /// Returns mutable list of multisignature transfers created by the given sender.
+    pub fn outgoing_multisig_transfers_mut(
+        &mut self,
+        sender: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, Hash> {
+        ProofListIndex::new_in_family(MULTISIG_OUTGOING_FAMILY, sender, &mut self.view)
+    }
+
+    /// Returns mutable `ProofMapIndex` with the roots of the outgoing transfer lists.
+    pub fn outgoing_multisig_roots_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Hash> {
+        ProofMapIndex::new(MULTISIG_OUTGOING_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable set of settled multisignature transfers.
+    pub fn settled_multisig_transfers_mut(&mut self) -> KeySetIndex<&mut Fork, Vec<u8>> {
+        KeySetIndex::new(SETTLED_MULTISIG_SET, &mut self.view)
+    }
+
+    /// Returns mutable `ProofMapIndex` with the final states of the collected
+    /// multisignature transfers.
+    pub fn multisig_tombstones_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, u8> {
+        ProofMapIndex::new(MULTISIG_TOMBSTONE_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable set of multisignature transfers in process pending
+    /// for the given approver.
+    pub fn approver_pending_mut(&mut self, approver: &PublicKey) -> KeySetIndex<&mut Fork, Hash> {
+        KeySetIndex::new_in_family(APPROVER_PENDING_FAMILY, approver, &mut self.view)
+    }
+
+    /// Returns mutable set of the stored multisignature transfers to the given receiver.
+    pub fn incoming_multisig_transfers_mut(
+        &mut self,
+        receiver: &PublicKey,
+    ) -> KeySetIndex<&mut Fork, Hash> {
+        KeySetIndex::new_in_family(MULTISIG_INCOMING_FAMILY, receiver, &mut self.view)
+    }
+
+    /// Returns mutable set of the stored multisignature transfers which list
+    /// the given approver.
+    pub fn approver_multisig_transfers_mut(
+        &mut self,
+        approver: &PublicKey,
+    ) -> KeySetIndex<&mut Fork, Hash> {
+        KeySetIndex::new_in_family(APPROVER_MULTISIG_FAMILY, approver, &mut self.view)
+    }
+
+    /// Adds the stored multisignature transfer to the sets of its receiver
+    /// and approvers.
+    pub fn index_multisig_participants(
+        &mut self,
+        transaction: Hash,
+        transfer: &MultisignatureTransfer,
+    ) {
+        self.incoming_multisig_transfers_mut(&transfer.to)
+            .insert(transaction);
+        for approver in &transfer.approvers {
+            self.approver_multisig_transfers_mut(approver)
+                .insert(transaction);
+        }
+    }
+
+    /// Returns mutable `ProofMapIndex` with the owners of the joint wallets.
+    pub fn joint_wallets_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, JointWallet> {
+        ProofMapIndex::new(JOINT_WALLETS_TABLE, &mut self.view)
+    }
+
+    /// Returns mutable `ProofMapIndex` with the spends from the joint wallets.
+    pub fn joint_spends_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, MultisignatureTransfer> {
+        ProofMapIndex::new(JOINT_SPENDS_TABLE, &mut self.view)
     }
 
-    /// Updates multisignature transfer.
+    /// Returns mutable set of the pending proposals of spends from the joint wallet.
+    pub fn joint_pending_mut(&mut self, address: &PublicKey) -> KeySetIndex<&mut Fork, Hash> {
+        KeySetIndex::new_in_family(JOINT_PENDING_FAMILY, address, &mut self.view)
+    }
+
+    /// Stores the spend from a joint wallet proposed by the transaction with the given
+    /// hash. The proposal is pending while the spend is in process.
+    pub fn update_joint_spend(&mut self, proposal: Hash, spend: MultisignatureTransfer) {
+        if spend.state == State::InProcess {
+            self.joint_pending_mut(&spend.from).insert(proposal);
+        } else {
+            self.joint_pending_mut(&spend.from).remove(&proposal);
+        }
+        self.joint_spends_mut().put(&proposal, spend);
+    }
+
+    /// Returns mutable `ProofMapIndex` with the payment streams.
+    pub fn payment_streams_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, PaymentStream> {
+        ProofMapIndex::new(PAYMENT_STREAMS_TABLE, &mut self.view)
+    }
+
+    /// Stores the payment stream opened by the transaction with the given hash and
+    /// schedules it to close once it accrues its maximum total. The reserve must be
+    /// withheld from the sender separately.
+    pub fn open_payment_stream(&mut self, transaction: Hash, stream: PaymentStream) {
+        self.schedule_at(
+            stream.exhausted_at(),
+            DueItem::payment_stream_exhaustion(transaction),
+        );
+        self.payment_streams_mut().put(&transaction, stream);
+    }
+
+    /// Closes the payment stream opened by the transaction with the given hash at
+    /// the given height. The receiver is credited with the accrued amount and the rest
+    /// of the reserve is refunded to the sender; both changes are recorded under the hash
+    /// of the closing transaction. Returns the closed stream, or `None` if the stream
+    /// does not exist or is already closed.
+    pub fn close_payment_stream(
+        &mut self,
+        stream: Hash,
+        height: Height,
+        transaction: Hash,
+    ) -> Option<PaymentStream> {
+        let closed = self
+            .payment_stream(&stream)
+            .filter(|stream| !stream.is_closed())?
+            .close(height);
+
+        let mut updates = Vec::with_capacity(2);
+        for &(key, amount) in &[(closed.to, closed.paid), (closed.from, closed.refund())] {
+            // The reserve is a part of the total supply, so the balances cannot overflow.
+            if let Some(Ok(wallet)) = self
+                .wallet(&key)
+                .map(|wallet| wallet.try_increase_balance(amount))
+            {
+                updates.push((wallet, transaction));
+            }
+        }
+        self.update_wallets_batch(updates, height);
+        if !closed.paid.is_zero() {
+            self.record_transfer(closed.paid);
+            self.record_wallet_transfer(&closed.from, &closed.to, closed.paid);
+        }
+        self.payment_streams_mut().put(&stream, closed.clone());
+        Some(closed)
+    }
+
+    /// Returns mutable `Entry` with the staking totals of the service.
+    pub fn staking_state_entry_mut(&mut self) -> Entry<&mut Fork, StakingState> {
+        Entry::new(STAKING_STATE_ENTRY, &mut self.view)
+    }
+
+    /// Returns mutable `ProofMapIndex` with the reward accounting of the staking wallets.
+    pub fn stake_positions_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, StakePosition> {
+        ProofMapIndex::new(STAKE_POSITIONS_TABLE, &mut self.view)
+    }
+
+    /// Returns the reward accounting of the wallet with the rewards earned by
+    /// the `staked` amount settled. A wallet which has not staked yet starts
+    /// from the current state of the accumulator.
+    fn settled_stake_position(&self, key: &PublicKey, staked: Amount) -> StakePosition {
+        let reward_per_token = self.staking_state().reward_per_token;
+        self.stake_position(key)
+            .unwrap_or_else(|| StakePosition::new(reward_per_token))
+            .settle(staked, reward_per_token)
+    }
+
+    fn put_stake_position(&mut self, key: &PublicKey, staked: Amount, position: StakePosition) {
+        if staked.is_zero() && position.pending.is_zero() {
+            self.stake_positions_mut().remove(key);
+        } else {
+            self.stake_positions_mut().put(key, position);
+        }
+    }
+
+    /// Updates the wallet whose stake is changed by the transaction executed at
+    /// the given height. The rewards earned by the stored stake are settled beforehand.
+    pub fn update_stake(&mut self, wallet: Wallet, transaction: Hash, height: Height) {
+        let key = wallet.pub_key;
+        let stored_staked = self
+            .wallet(&key)
+            .map_or(Amount::ZERO, |stored| stored.staked);
+        let position = self.settled_stake_position(&key, stored_staked);
+        self.put_stake_position(&key, wallet.staked, position);
+
+        let mut state = self.staking_state();
+        // The stakes are a part of the total supply, so their total cannot overflow.
+        state.total_staked = state
+            .total_staked
+            .saturating_sub(stored_staked)
+            .saturating_add(wallet.staked);
+        self.staking_state_entry_mut().set(state);
+        self.update_wallet(wallet, transaction, height);
+    }
+
+    /// Credits the unclaimed staking rewards of the wallet to its balance by
+    /// the transaction executed at the given height. The rewards are issued, but
+    /// the total supply is not increased over `supply_cap`; the rest of the rewards
+    /// stays unclaimed. Returns the credited amount.
+    pub fn claim_staking_rewards(
+        &mut self,
+        key: &PublicKey,
+        supply_cap: Amount,
+        transaction: Hash,
+        height: Height,
+    ) -> Amount {
+        let wallet = match self.wallet(key) {
+            Some(wallet) => wallet,
+            None => return Amount::ZERO,
+        };
+        let position = self.settled_stake_position(key, wallet.staked);
+        let rewards = cmp::min(
+            position.pending,
+            supply_cap.saturating_sub(self.total_supply()),
+        );
+        if rewards.is_zero() {
+            return Amount::ZERO;
+        }
+
+        // The balance does not exceed the total supply, which stays within the cap.
+        let wallet = wallet
+            .try_increase_balance(rewards)
+            .expect("Balance cannot exceed the supply cap");
+        let position = StakePosition {
+            pending: position.pending.saturating_sub(rewards),
+            ..position
+        };
+        self.put_stake_position(key, wallet.staked, position);
+        let mut state = self.staking_state();
+        state.outstanding_rewards = state.outstanding_rewards.saturating_sub(rewards);
+        self.staking_state_entry_mut().set(state);
+
+        self.increase_total_supply(rewards);
+        self.record_wallet_issue(key, rewards);
+        self.update_wallet(wallet, transaction, height);
+        rewards
+    }
+
+    /// Accrues the staking rewards of an interval at the given rate for every staked
+    /// unit. The rewards are bounded so that, once claimed, they do not take the total
+    /// supply over `supply_cap`.
+    pub fn accrue_staking_rewards(&mut self, rate: Amount, supply_cap: Amount) {
+        let mut state = self.staking_state();
+        if !state
+            .accrue(rate, self.total_supply(), supply_cap)
+            .is_zero()
+        {
+            self.staking_state_entry_mut().set(state);
+        }
+    }
+
+    /// Returns mutable items scheduled for processing in the block at the given height.
+    pub fn due_items_mut(&mut self, height: Height) -> ListIndex<&mut Fork, DueItem> {
+        ListIndex::new_in_family(DUE_ITEMS_FAMILY, &height.0, &mut self.view)
+    }
+
+    /// Returns mutable `Entry` with the height up to which the due items are processed.
+    pub fn due_items_processed_entry_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new(DUE_ITEMS_PROCESSED_ENTRY, &mut self.view)
+    }
+
+    /// Schedules the item for processing in the block at the given height. Items
+    /// scheduled for the height of the block being executed are processed in its
+    /// `before_commit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block at the given height is already committed.
+    pub fn schedule_at(&mut self, height: Height, item: DueItem) {
+        let next_height = blockchain::Schema::new(&*self.view).height().next();
+        assert!(
+            height >= next_height,
+            "Cannot schedule an item at the committed height {}",
+            height
+        );
+        self.due_items_mut(height).push(item);
+    }
+
+    /// Processes the items due in the block at the given height in the order of
+    /// their scheduling and removes them. Items left over from the previous heights,
+    /// e.g., because `before_commit` of their block was rolled back, are processed
+    /// first, in the order of their heights.
+    pub fn process_due_items(&mut self, height: Height) {
+        let first = self.due_items_processed().map_or(height, Height::next);
+        for due_height in (first.0..=height.0).map(Height) {
+            let items = self.due_items(due_height).iter().collect::<Vec<_>>();
+            for item in items {
+                match item.kind {
+                    DueKind::MultisigExpiry => self.expire_multisig_transfer(item.tx_hash, height),
+                    // Streams closed by their parties beforehand are left as is.
+                    DueKind::PaymentStreamExhaustion => {
+                        self.close_payment_stream(item.tx_hash, height, item.tx_hash);
+                    }
+                }
+            }
+            self.due_items_mut(due_height).clear();
+        }
+        self.due_items_processed_entry_mut().set(height.0);
+    }
+
+    /// Expires the multisignature transfer unless it is settled by now, and returns
+    /// its amount to the sender. The refund is recorded in the sender's history
+    /// under the hash of the `TransferMultisig` transaction. If the amount cannot be
+    /// returned, it is removed from the total supply, and the transfer is marked
+    /// with `refund_failed`.
+    fn expire_multisig_transfer(&mut self, tx_hash: Hash, height: Height) {
+        let expired = match self
+            .multisig_transfer(tx_hash)
+            .and_then(|transfer| transfer.expire(height))
+        {
+            Some(expired) => expired,
+            // The transfer is settled or collected before its expiration.
+            None => return,
+        };
+        let (from, amount) = (expired.from, expired.amount);
+        self.update_transfer_multisig(tx_hash, expired);
+
+        let sender = match self
+            .wallet(&from)
+            .map(|wallet| wallet.try_increase_balance(amount))
+        {
+            Some(Ok(sender)) => sender,
+            // The amount was withheld from the sender, so it is still counted in the total
+            // supply and has to be removed from it.
+            _ => {
+                warn!(
+                    "Cannot refund {} of the expired multisignature transfer {:?} to {:?}, \
+                     the amount is removed from the total supply",
+                    amount, tx_hash, from
+                );
+                let transfer = self
+                    .multisig_transfer(tx_hash)
+                    .expect("Expired multisignature transfer is absent");
+                self.update_transfer_multisig(
+                    tx_hash,
+                    MultisignatureTransfer {
+                        refund_failed: true,
+                        ..transfer
+                    },
+                );
+                self.decrease_total_supply(amount);
+                return;
+            }
+        };
+        if self.settle_multisig_refund(tx_hash).is_ok() {
+            self.update_wallet(sender, tx_hash, height);
+        }
+    }
+
+    /// Put new pending MultisignatureTransfer into wallet. Transfers with an expiration
+    /// height are scheduled to expire at it.
+    pub fn create_transfer_multisig(
+        &mut self,
+        transaction: Hash,
+        transfer: MultisignatureTransfer,
+    ) {
+        if transfer.expires_at > Height(0) {
+            self.schedule_at(transfer.expires_at, DueItem::multisig_expiry(transaction));
+        }
+        self.multisig_transfers_by_state_mut(&transfer.state)
+            .put(&transaction, ());
+        let outgoing_root = {
+            let mut outgoing = self.outgoing_multisig_transfers_mut(&transfer.from);
+            outgoing.push(transaction);
+            outgoing.merkle_root()
+        };
+        self.outgoing_multisig_roots_mut()
+            .put(&transfer.from, outgoing_root);
+        for approver in &transfer.approvers {
+            self.approver_pending_mut(approver).insert(transaction);
+        }
+        self.index_multisig_participants(transaction, &transfer);
+        self.multisig_transfers_mut().put(&transaction, transfer);
+    }
+
+    /// Updates multisignature transfer and moves it between the per-state sets
+    /// if its state has changed. Once the transfer is no longer in process, it is
+    /// removed from the pending sets of all its approvers.
     pub fn update_transfer_multisig(
         &mut self,
         transfer_tx: Hash,
         transfer: MultisignatureTransfer,
     ) {
+        let previous_state = self
+            .multisig_transfer(transfer_tx)
+            .map(|previous| previous.state);
+        if previous_state.as_ref() != Some(&transfer.state) {
+            if let Some(ref previous_state) = previous_state {
+                self.multisig_transfers_by_state_mut(previous_state)
+                    .remove(&transfer_tx);
+            }
+            self.multisig_transfers_by_state_mut(&transfer.state)
+                .put(&transfer_tx, ());
+        }
+        if transfer.state != State::InProcess {
+            for approver in &transfer.approvers {
+                self.approver_pending_mut(approver).remove(&transfer_tx);
+            }
+        }
+        if transfer.is_terminal() && previous_state.as_ref() == Some(&State::InProcess) {
+            self.settled_multisig_transfers_mut()
+                .insert(settled_multisig_key(
+                    transfer.settled_at_height,
+                    &transfer_tx,
+                ));
+        }
         self.multisig_transfers_mut().put(&transfer_tx, transfer);
     }
+
+    /// Marks the rejected, expired or cancelled multisignature transfer as refunded.
+    /// The caller returns the amount to the sender only if this succeeds, so the amount
+    /// of a transfer is refunded at most once whatever the execution path. Fails without
+    /// changes if the transfer is neither rejected, expired nor cancelled, is already
+    /// refunded or is already collected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transfer does not exist.
+    pub fn settle_multisig_refund(&mut self, tx_hash: Hash) -> Result<(), AlreadySettled> {
+        let transfer = match self.multisig_transfer(tx_hash) {
+            Some(transfer) => transfer,
+            None if self.collected_multisig_state(&tx_hash).is_some() => {
+                return Err(AlreadySettled { tx_hash });
+            }
+            None => panic!("Multisignature transfer does not exist"),
+        };
+        let refundable = match transfer.state {
+            State::Rejected | State::Expired | State::Cancelled => true,
+            State::InProcess | State::Done => false,
+        };
+        if !refundable || transfer.refunded || transfer.refund_failed {
+            return Err(AlreadySettled { tx_hash });
+        }
+        self.update_transfer_multisig(
+            tx_hash,
+            MultisignatureTransfer {
+                refunded: true,
+                ..transfer
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes the multisignature transfers settled at least `retention` blocks before
+    /// `height`, leaving tombstones with their final states. The transfers are removed
+    /// from the sets of their receivers and approvers as well. Transfers are collected
+    /// in the order of their settlement heights.
+    pub fn collect_settled_multisig_transfers(&mut self, height: Height, retention: u64) {
+        let collected = self
+            .settled_multisig_transfers()
+            .iter()
+            .map(|key| (parse_settled_multisig_key(&key), key))
+            .take_while(|((settled_at, _), _)| settled_at.0.saturating_add(retention) <= height.0)
+            .collect::<Vec<_>>();
+
+        for ((_, tx_hash), key) in collected {
+            if let Some(transfer) = self.multisig_transfer(tx_hash) {
+                self.multisig_transfers_by_state_mut(&transfer.state)
+                    .remove(&tx_hash);
+                self.incoming_multisig_transfers_mut(&transfer.to)
+                    .remove(&tx_hash);
+                for approver in &transfer.approvers {
+                    self.approver_multisig_transfers_mut(approver)
+                        .remove(&tx_hash);
+                }
+                self.multisig_tombstones_mut()
+                    .put(&tx_hash, transfer.state as u8);
+                self.multisig_transfers_mut().remove(&tx_hash);
+            }
+            self.settled_multisig_transfers_mut().remove(&key);
+        }
+    }
 }
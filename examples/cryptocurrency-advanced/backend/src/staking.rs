@@ -0,0 +1,189 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Staking of the currency with rewards issued every `Config::staking_reward_interval`
+//! blocks.
+//!
+//! The rewards are accounted with a reward-per-token accumulator, so that accruing them
+//! does not touch the staking wallets. Every accrual adds the reward rate to
+//! `StakingState::reward_per_token`, and the reward of a wallet is its stake times
+//! the growth of the accumulator since the rewards of the wallet were last settled.
+//! The rewards are settled whenever the stake changes and credited to the balance
+//! by `ClaimRewards`.
+//!
+//! The accrued rewards are reserved against `Config::staking_supply_cap` at once,
+//! but they are added to the total supply only when claimed.
+
+use exonum::proto::ProtobufConvert;
+
+use super::proto;
+use crate::amount::Amount;
+
+/// Default number of blocks between the accruals of the staking rewards.
+pub const STAKING_REWARD_INTERVAL: u64 = 100;
+
+/// Staking totals of the service.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StakingState {
+    /// Total amount staked by all the wallets.
+    pub total_staked: Amount,
+    /// Sum of the reward rates of all the accruals: the reward of a single unit staked
+    /// since the launch of the service.
+    pub reward_per_token: Amount,
+    /// Accrued rewards which are not claimed yet, including the ones not settled yet.
+    pub outstanding_rewards: Amount,
+}
+
+impl StakingState {
+    /// Accrues the rewards of one interval: `rate` for every staked unit. The rate is
+    /// lowered if needed so that the total supply together with the outstanding rewards
+    /// does not exceed `supply_cap`. Returns the amount of the accrued rewards.
+    pub fn accrue(&mut self, rate: Amount, total_supply: Amount, supply_cap: Amount) -> Amount {
+        if self.total_staked.is_zero() || rate.is_zero() {
+            return Amount::ZERO;
+        }
+
+        let available = supply_cap
+            .saturating_sub(total_supply)
+            .saturating_sub(self.outstanding_rewards);
+        let rate = match self.total_staked.checked_mul_fraction(rate) {
+            Some(rewards) if rewards <= available => rate,
+            // Only the available amount is distributed. The rate is rounded down,
+            // so the rewards do not exceed it.
+            _ => available
+                .checked_ratio(self.total_staked)
+                .unwrap_or(Amount::ZERO),
+        };
+        let rewards = self
+            .total_staked
+            .checked_mul_fraction(rate)
+            .map_or(available, |rewards| rewards.min(available));
+
+        self.reward_per_token = self.reward_per_token.saturating_add(rate);
+        self.outstanding_rewards = self.outstanding_rewards.saturating_add(rewards);
+        rewards
+    }
+}
+
+impl ProtobufConvert for StakingState {
+    type ProtoStruct = proto::StakingState;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::StakingState::new();
+        let (total_staked, exact_total_staked) = self.total_staked.to_pb_fields();
+        pb.set_total_staked(total_staked);
+        if let Some(exact_total_staked) = exact_total_staked {
+            pb.set_exact_total_staked(exact_total_staked);
+        }
+        let (reward_per_token, exact_reward_per_token) = self.reward_per_token.to_pb_fields();
+        pb.set_reward_per_token(reward_per_token);
+        if let Some(exact_reward_per_token) = exact_reward_per_token {
+            pb.set_exact_reward_per_token(exact_reward_per_token);
+        }
+        let (outstanding_rewards, exact_outstanding_rewards) =
+            self.outstanding_rewards.to_pb_fields();
+        pb.set_outstanding_rewards(outstanding_rewards);
+        if let Some(exact_outstanding_rewards) = exact_outstanding_rewards {
+            pb.set_exact_outstanding_rewards(exact_outstanding_rewards);
+        }
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            total_staked: Amount::from_pb_fields(
+                pb.get_total_staked(),
+                pb.get_exact_total_staked(),
+            ),
+            reward_per_token: Amount::from_pb_fields(
+                pb.get_reward_per_token(),
+                pb.get_exact_reward_per_token(),
+            ),
+            outstanding_rewards: Amount::from_pb_fields(
+                pb.get_outstanding_rewards(),
+                pb.get_exact_outstanding_rewards(),
+            ),
+        })
+    }
+}
+
+/// Reward accounting of a staking wallet.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StakePosition {
+    /// `StakingState::reward_per_token` when the rewards of the wallet were last settled.
+    pub reward_per_token_paid: Amount,
+    /// Settled rewards which are not claimed yet.
+    pub pending: Amount,
+}
+
+impl StakePosition {
+    /// Creates the position of a wallet which starts staking when the accumulator
+    /// equals `reward_per_token`, so that it earns only the later rewards.
+    pub fn new(reward_per_token: Amount) -> Self {
+        Self {
+            reward_per_token_paid: reward_per_token,
+            pending: Amount::ZERO,
+        }
+    }
+
+    /// Returns the rewards earned by the `staked` amount since the last settlement,
+    /// rounded down.
+    pub fn earned(&self, staked: Amount, reward_per_token: Amount) -> Amount {
+        staked
+            .checked_mul_fraction(reward_per_token.saturating_sub(self.reward_per_token_paid))
+            .unwrap_or(Amount::MAX)
+    }
+
+    /// Settles the rewards earned by the `staked` amount, moving them to `pending`.
+    pub fn settle(self, staked: Amount, reward_per_token: Amount) -> Self {
+        Self {
+            pending: self
+                .pending
+                .saturating_add(self.earned(staked, reward_per_token)),
+            reward_per_token_paid: reward_per_token,
+        }
+    }
+}
+
+impl ProtobufConvert for StakePosition {
+    type ProtoStruct = proto::StakePosition;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::StakePosition::new();
+        let (reward_per_token_paid, exact_reward_per_token_paid) =
+            self.reward_per_token_paid.to_pb_fields();
+        pb.set_reward_per_token_paid(reward_per_token_paid);
+        if let Some(exact_reward_per_token_paid) = exact_reward_per_token_paid {
+            pb.set_exact_reward_per_token_paid(exact_reward_per_token_paid);
+        }
+        let (pending, exact_pending) = self.pending.to_pb_fields();
+        pb.set_pending(pending);
+        if let Some(exact_pending) = exact_pending {
+            pb.set_exact_pending(exact_pending);
+        }
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            reward_per_token_paid: Amount::from_pb_fields(
+                pb.get_reward_per_token_paid(),
+                pb.get_exact_reward_per_token_paid(),
+            ),
+            pending: Amount::from_pb_fields(pb.get_pending(), pb.get_exact_pending()),
+        })
+    }
+}
+
+impl_pb_storage!(StakingState, StakePosition);
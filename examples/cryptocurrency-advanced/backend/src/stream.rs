@@ -0,0 +1,126 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stream of committed transactions of the service.
+//!
+//! Events are published from `after_commit`, so they arrive in block order. Every
+//! subscriber has a bounded buffer; subscribers which do not keep up are disconnected
+//! instead of slowing down the commit.
+
+use exonum::{
+    blockchain::{self, TransactionSet},
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+    storage::Snapshot,
+};
+use futures::sync::mpsc;
+
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    api::ExecutionStatus, lock, transactions::WalletTransactions, CRYPTOCURRENCY_SERVICE_ID,
+};
+
+/// The maximum number of events buffered for a single subscriber, or for the log file
+/// of an `EventEmitter`.
+pub const EVENT_BUFFER_SIZE: usize = 1_024;
+
+/// Committed transaction of the service.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransactionEvent {
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+    /// Height of the block containing the transaction.
+    pub height: Height,
+    /// Name of the transaction type, e.g. `Transfer`.
+    pub tx_type: String,
+    /// Author of the transaction.
+    pub author: PublicKey,
+    /// Execution status of the transaction.
+    pub status: ExecutionStatus,
+}
+
+/// Publisher of the committed transactions. Clones share the same set of subscribers.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionStream {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<TransactionEvent>>>>,
+}
+
+impl TransactionStream {
+    /// Creates a stream without subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to the transactions committed from now on.
+    pub fn subscribe(&self) -> mpsc::Receiver<TransactionEvent> {
+        let (sender, receiver) = mpsc::channel(EVENT_BUFFER_SIZE);
+        lock(&self.subscribers).push(sender);
+        receiver
+    }
+
+    /// Returns the number of active subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        lock(&self.subscribers).len()
+    }
+
+    /// Sends the events to every subscriber. Subscribers with a full buffer
+    /// or a dropped receiver are removed.
+    pub fn publish(&self, events: &[TransactionEvent]) {
+        let mut subscribers = lock(&self.subscribers);
+        subscribers.retain(|subscriber| {
+            let mut subscriber = subscriber.clone();
+            events
+                .iter()
+                .all(|event| subscriber.try_send(event.clone()).is_ok())
+        });
+    }
+
+    /// Publishes transactions of the service committed in the block at the given height.
+    pub fn publish_block(&self, snapshot: &dyn Snapshot, height: Height) {
+        if self.subscriber_count() > 0 {
+            self.publish(&block_events(snapshot, height));
+        }
+    }
+}
+
+/// Returns the events for transactions of the service in the block at the given height
+/// in the order of their execution.
+pub fn block_events(snapshot: &dyn Snapshot, height: Height) -> Vec<TransactionEvent> {
+    let schema = blockchain::Schema::new(snapshot);
+    let transactions = schema.transactions();
+    let results = schema.transaction_results();
+
+    schema
+        .block_transactions(height)
+        .iter()
+        .filter_map(|tx_hash| {
+            let message = transactions.get(&tx_hash)?;
+            let raw_tx = message.payload();
+            if raw_tx.service_id() != CRYPTOCURRENCY_SERVICE_ID {
+                return None;
+            }
+            let tx = WalletTransactions::tx_from_raw(raw_tx.clone()).ok()?;
+            let result = results.get(&tx_hash)?;
+
+            Some(TransactionEvent {
+                tx_hash,
+                height,
+                tx_type: tx.name().to_owned(),
+                author: message.author(),
+                status: ExecutionStatus::from(&result),
+            })
+        })
+        .collect()
+}
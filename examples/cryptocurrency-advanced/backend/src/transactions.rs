@@ -18,92 +18,436 @@
 // ECR-1771 for the details.
 #![allow(bare_trait_objects)]
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use exonum::{
-    blockchain::{self, ExecutionError, ExecutionResult, Transaction, TransactionContext},
-    crypto::{Hash, PublicKey, SecretKey},
+    blockchain::{
+        self, ExecutionError, ExecutionResult, Transaction, TransactionContext, TransactionSet,
+    },
+    crypto::{self, Hash, PublicKey, SecretKey, Signature},
+    helpers::Height,
     messages::{Message, RawTransaction, Signed},
+    proto::ProtobufConvert,
+    storage::{Fork, Snapshot},
 };
 
 use super::proto;
-use crate::{schema::Schema, CRYPTOCURRENCY_SERVICE_ID};
+use crate::{
+    amount::Amount,
+    joint_wallet::JointWallet,
+    multisig_transfer::{
+        approval_digest, ApprovalOutcome, BatchApprovalResult, MultisigError,
+        MultisignatureTransfer, State,
+    },
+    payment_stream::PaymentStream,
+    schema::{wallet_name_key, Schema},
+    unsigned::UnsignedTx,
+    wallet::{BalanceError, Wallet},
+    CRYPTOCURRENCY_SERVICE_ID,
+};
 
-/// Error codes emitted by wallet transactions during execution.
-#[derive(Debug, Fail)]
+/// Error codes emitted by wallet transactions during execution. The transactions
+/// which can emit each error are listed by `Error::emitted_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
 #[repr(u8)]
 pub enum Error {
     /// Wallet already exists.
-    ///
-    /// Can be emitted by `CreateWallet`.
     #[fail(display = "Wallet already exists")]
     WalletAlreadyExists = 0,
 
     /// Sender doesn't exist.
-    ///
-    /// Can be emitted by `Transfer` or `TransferMultisig`.
     #[fail(display = "Sender doesn't exist")]
     SenderNotFound = 1,
 
     /// Receiver doesn't exist.
-    ///
-    /// Can be emitted by `Transfer`, `TransferMultisig` or `Issue`.
     #[fail(display = "Receiver doesn't exist")]
     ReceiverNotFound = 2,
 
     /// Insufficient currency amount.
-    ///
-    /// Can be emitted by `Transfer` or `TransferMultisig`.
     #[fail(display = "Insufficient currency amount")]
     InsufficientCurrencyAmount = 3,
 
     /// Sender same as receiver.
-    ///
-    /// Can be emitted by `Transfer` or `TransferMultisig`.
     #[fail(display = "Sender same as receiver")]
     SenderSameAsReceiver = 4,
 
     /// Empty approvers list.
-    ///
-    /// Can be emitted by `TransferMultisig`.
     #[fail(display = "Empty approvers list")]
     EmptyApproversList = 5,
 
     /// Approvers list is too large.
-    ///
-    /// Can be emitted by `TransferMultisig`.
     #[fail(display = "Approvers list is too large")]
     ApproversListIsTooLarge = 6,
 
     /// Transaction does not exist.
-    ///
-    /// Can be emitted by `ApproveTransferMultisig`.
     #[fail(display = "Transaction does not exist")]
     TransactionDoesNotExist = 7,
 
     /// Referred transaction failed.
-    ///
-    /// Can be emitted by `ApproveTransferMultisig`.
     #[fail(display = "Referred transaction failed")]
     ReferredTransactionFailed = 8,
 
     /// Referred transaction is not `TransferMultisig`.
-    ///
-    /// Can be emitted by `ApproveTransferMultisig`.
     #[fail(display = "Referred transaction is not TransferMultisig")]
     ReferredTransactionIsNotTransferMultisig = 9,
 
     /// Approver is not on approvers list.
-    ///
-    /// Can be emitted by `ApproveTransferMultisig`.
     #[fail(display = "Approver is not on approvers list")]
     ApproverIsNotOnApproversList = 10,
 
     /// Transfer is rejected.
-    ///
-    /// Can be emitted by `ApproveTransferMultisig`.
     #[fail(display = "Transfer is rejected")]
     TransferIsRejected = 11,
+
+    /// Author of the transaction is not allowed to issue currency.
+    #[fail(display = "Issuer is not a validator")]
+    UnauthorizedIssuer = 12,
+
+    /// Issued amount would overflow the total supply.
+    #[fail(display = "Total supply overflow")]
+    SupplyOverflow = 13,
+
+    /// Transfer is done.
+    #[fail(display = "Transfer is done")]
+    TransferIsDone = 14,
+
+    /// Approver has already approved the transfer.
+    #[fail(display = "Transfer is already approved by the approver")]
+    TransferIsAlreadyApproved = 15,
+
+    /// Transfer is expired.
+    #[fail(display = "Transfer is expired")]
+    TransferIsExpired = 16,
+
+    /// Comment of the approver is longer than allowed by the service configuration.
+    #[fail(display = "Comment is too long")]
+    CommentTooLong = 17,
+
+    /// Transfer is cancelled because its receiver no longer exists.
+    #[fail(display = "Transfer is cancelled")]
+    TransferIsCancelled = 18,
+
+    /// Amount has more decimals than allowed by the service configuration.
+    #[fail(display = "Amount is too precise")]
+    AmountTooPrecise = 19,
+
+    /// Author of the transaction is not an owner of the joint wallet.
+    #[fail(display = "Not an owner of the joint wallet")]
+    NotJointWalletOwner = 20,
+
+    /// Owner has already signed the spend from the joint wallet.
+    #[fail(display = "Joint spend is already approved by the owner")]
+    JointSpendAlreadyApproved = 21,
+
+    /// Threshold of the joint wallet is zero or exceeds the number of its owners.
+    #[fail(display = "Joint wallet threshold is unreachable")]
+    JointThresholdUnreachable = 22,
+
+    /// Joint wallet doesn't exist.
+    #[fail(display = "Joint wallet doesn't exist")]
+    JointWalletNotFound = 23,
+
+    /// Author of the transaction is not the clearing operator of the service configuration.
+    #[fail(display = "Author is not the clearing operator")]
+    UnauthorizedClearingOperator = 24,
+
+    /// Clearing has more legs than allowed by the service configuration.
+    #[fail(display = "Clearing has too many legs")]
+    TooManyClearingLegs = 25,
+
+    /// Payment stream is already closed.
+    #[fail(display = "Payment stream is already closed")]
+    StreamAlreadyClosed = 26,
+
+    /// Author of the transaction is neither the sender nor the receiver of the payment stream.
+    #[fail(display = "Not a party of the payment stream")]
+    NotStreamParty = 27,
+
+    /// Rate of the payment stream is zero.
+    #[fail(display = "Payment stream rate is zero")]
+    ZeroStreamRate = 28,
+
+    /// Staked amount of the wallet is less than the unstaked one.
+    #[fail(display = "Insufficient staked amount")]
+    InsufficientStake = 29,
+
+    /// Wallet has no staking rewards which can be claimed.
+    #[fail(display = "No staking rewards to claim")]
+    NoStakingRewards = 30,
+
+    /// Signature of an approval does not match the approver.
+    #[fail(display = "Invalid approval signature")]
+    InvalidApprovalSignature = 31,
+
+    /// Approval batch carries no approvals.
+    #[fail(display = "Approval batch is empty")]
+    EmptyApprovalBatch = 32,
+
+    /// Author of `SetOverdraft` is not the overdraft admin.
+    #[fail(display = "Author is not the overdraft admin")]
+    UnauthorizedOverdraftAdmin = 33,
+
+    /// Overdraft limit is below the amount owed by the wallet.
+    #[fail(display = "Overdraft limit is below the owed amount")]
+    OverdraftBelowDebt = 34,
+
+    /// Cashback exceeds `MAX_CASHBACK_BASIS_POINTS`.
+    #[fail(display = "Cashback exceeds 10000 basis points")]
+    CashbackTooLarge = 35,
+
+    /// Wallet name is already taken.
+    #[fail(display = "Wallet name is already taken")]
+    WalletNameTaken = 36,
+
+    /// Threshold of the multisignature transfer exceeds the number of its approvers.
+    #[fail(display = "Transfer threshold is unreachable")]
+    TransferThresholdUnreachable = 37,
+
+    /// Expiration height of the multisignature transfer is not above the current height.
+    #[fail(display = "Transfer expiration height has passed")]
+    TransferExpirationPassed = 38,
+}
+
+impl Error {
+    /// All errors in the order of their codes.
+    pub const ALL: [Error; 39] = [
+        Error::WalletAlreadyExists,
+        Error::SenderNotFound,
+        Error::ReceiverNotFound,
+        Error::InsufficientCurrencyAmount,
+        Error::SenderSameAsReceiver,
+        Error::EmptyApproversList,
+        Error::ApproversListIsTooLarge,
+        Error::TransactionDoesNotExist,
+        Error::ReferredTransactionFailed,
+        Error::ReferredTransactionIsNotTransferMultisig,
+        Error::ApproverIsNotOnApproversList,
+        Error::TransferIsRejected,
+        Error::UnauthorizedIssuer,
+        Error::SupplyOverflow,
+        Error::TransferIsDone,
+        Error::TransferIsAlreadyApproved,
+        Error::TransferIsExpired,
+        Error::CommentTooLong,
+        Error::TransferIsCancelled,
+        Error::AmountTooPrecise,
+        Error::NotJointWalletOwner,
+        Error::JointSpendAlreadyApproved,
+        Error::JointThresholdUnreachable,
+        Error::JointWalletNotFound,
+        Error::UnauthorizedClearingOperator,
+        Error::TooManyClearingLegs,
+        Error::StreamAlreadyClosed,
+        Error::NotStreamParty,
+        Error::ZeroStreamRate,
+        Error::InsufficientStake,
+        Error::NoStakingRewards,
+        Error::InvalidApprovalSignature,
+        Error::EmptyApprovalBatch,
+        Error::UnauthorizedOverdraftAdmin,
+        Error::OverdraftBelowDebt,
+        Error::CashbackTooLarge,
+        Error::WalletNameTaken,
+        Error::TransferThresholdUnreachable,
+        Error::TransferExpirationPassed,
+    ];
+
+    /// Returns the error code.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the name of the error variant.
+    pub fn name(self) -> &'static str {
+        match self {
+            Error::WalletAlreadyExists => "WalletAlreadyExists",
+            Error::SenderNotFound => "SenderNotFound",
+            Error::ReceiverNotFound => "ReceiverNotFound",
+            Error::InsufficientCurrencyAmount => "InsufficientCurrencyAmount",
+            Error::SenderSameAsReceiver => "SenderSameAsReceiver",
+            Error::EmptyApproversList => "EmptyApproversList",
+            Error::ApproversListIsTooLarge => "ApproversListIsTooLarge",
+            Error::TransactionDoesNotExist => "TransactionDoesNotExist",
+            Error::ReferredTransactionFailed => "ReferredTransactionFailed",
+            Error::ReferredTransactionIsNotTransferMultisig => {
+                "ReferredTransactionIsNotTransferMultisig"
+            }
+            Error::ApproverIsNotOnApproversList => "ApproverIsNotOnApproversList",
+            Error::TransferIsRejected => "TransferIsRejected",
+            Error::UnauthorizedIssuer => "UnauthorizedIssuer",
+            Error::SupplyOverflow => "SupplyOverflow",
+            Error::TransferIsDone => "TransferIsDone",
+            Error::TransferIsAlreadyApproved => "TransferIsAlreadyApproved",
+            Error::TransferIsExpired => "TransferIsExpired",
+            Error::CommentTooLong => "CommentTooLong",
+            Error::TransferIsCancelled => "TransferIsCancelled",
+            Error::AmountTooPrecise => "AmountTooPrecise",
+            Error::NotJointWalletOwner => "NotJointWalletOwner",
+            Error::JointSpendAlreadyApproved => "JointSpendAlreadyApproved",
+            Error::JointThresholdUnreachable => "JointThresholdUnreachable",
+            Error::JointWalletNotFound => "JointWalletNotFound",
+            Error::UnauthorizedClearingOperator => "UnauthorizedClearingOperator",
+            Error::TooManyClearingLegs => "TooManyClearingLegs",
+            Error::StreamAlreadyClosed => "StreamAlreadyClosed",
+            Error::NotStreamParty => "NotStreamParty",
+            Error::ZeroStreamRate => "ZeroStreamRate",
+            Error::InsufficientStake => "InsufficientStake",
+            Error::NoStakingRewards => "NoStakingRewards",
+            Error::InvalidApprovalSignature => "InvalidApprovalSignature",
+            Error::EmptyApprovalBatch => "EmptyApprovalBatch",
+            Error::UnauthorizedOverdraftAdmin => "UnauthorizedOverdraftAdmin",
+            Error::OverdraftBelowDebt => "OverdraftBelowDebt",
+            Error::CashbackTooLarge => "CashbackTooLarge",
+            Error::WalletNameTaken => "WalletNameTaken",
+            Error::TransferThresholdUnreachable => "TransferThresholdUnreachable",
+            Error::TransferExpirationPassed => "TransferExpirationPassed",
+        }
+    }
+
+    /// Returns the names of the transactions which can emit the error.
+    pub fn emitted_by(self) -> &'static [&'static str] {
+        match self {
+            Error::WalletAlreadyExists => &["CreateWallet", "CreateJointWallet", "CreateWalletFor"],
+            Error::SenderNotFound => &[
+                "Transfer",
+                "TransferMultisig",
+                "ApproveTransferMultisig",
+                "RejectTransferMultisig",
+                "Clearing",
+                "CreateWalletFor",
+                "OpenStream",
+                "Stake",
+                "Unstake",
+                "ClaimRewards",
+                "ApproveTransferMultisigBatch",
+                "SetCashback",
+                "RenameWallet",
+            ],
+            Error::ReceiverNotFound => &[
+                "Transfer",
+                "TransferMultisig",
+                "Issue",
+                "IssueTo",
+                "ProposeJointSpend",
+                "ApproveJointSpend",
+                "Clearing",
+                "OpenStream",
+                "SetOverdraft",
+            ],
+            Error::InsufficientCurrencyAmount => &[
+                "Transfer",
+                "TransferMultisig",
+                "ProposeJointSpend",
+                "ApproveJointSpend",
+                "Clearing",
+                "CreateWalletFor",
+                "OpenStream",
+                "Stake",
+            ],
+            Error::SenderSameAsReceiver => &[
+                "Transfer",
+                "TransferMultisig",
+                "ProposeJointSpend",
+                "Clearing",
+                "OpenStream",
+            ],
+            Error::EmptyApproversList => &["TransferMultisig"],
+            Error::ApproversListIsTooLarge => &[
+                "TransferMultisig",
+                "CreateJointWallet",
+                "ApproveTransferMultisigBatch",
+            ],
+            Error::TransactionDoesNotExist => &[
+                "ApproveTransferMultisig",
+                "RejectTransferMultisig",
+                "ApproveJointSpend",
+                "CloseStream",
+                "ApproveTransferMultisigBatch",
+            ],
+            Error::TransferIsDone => &[
+                "ApproveTransferMultisig",
+                "RejectTransferMultisig",
+                "ApproveJointSpend",
+                "ApproveTransferMultisigBatch",
+            ],
+            Error::ReferredTransactionFailed
+            | Error::ReferredTransactionIsNotTransferMultisig
+            | Error::ApproverIsNotOnApproversList => &[
+                "ApproveTransferMultisig",
+                "RejectTransferMultisig",
+                "ApproveTransferMultisigBatch",
+            ],
+            Error::CommentTooLong => &["ApproveTransferMultisig", "RejectTransferMultisig"],
+            Error::TransferIsRejected | Error::TransferIsExpired | Error::TransferIsCancelled => &[
+                "ApproveTransferMultisig",
+                "RejectTransferMultisig",
+                "ApproveTransferMultisigBatch",
+            ],
+            Error::TransferIsAlreadyApproved => {
+                &["ApproveTransferMultisig", "ApproveTransferMultisigBatch"]
+            }
+            Error::UnauthorizedIssuer => &["IssueTo"],
+            Error::SupplyOverflow => &["CreateWallet", "Issue", "IssueTo"],
+            Error::AmountTooPrecise => &[
+                "Transfer",
+                "TransferMultisig",
+                "Issue",
+                "IssueTo",
+                "ProposeJointSpend",
+                "Clearing",
+                "OpenStream",
+                "Stake",
+                "Unstake",
+                "SetOverdraft",
+            ],
+            Error::NotJointWalletOwner => &[
+                "CreateJointWallet",
+                "ProposeJointSpend",
+                "ApproveJointSpend",
+            ],
+            Error::JointSpendAlreadyApproved => &["ApproveJointSpend"],
+            Error::JointThresholdUnreachable => &["CreateJointWallet"],
+            Error::JointWalletNotFound => &["ProposeJointSpend"],
+            Error::UnauthorizedClearingOperator | Error::TooManyClearingLegs => &["Clearing"],
+            Error::StreamAlreadyClosed | Error::NotStreamParty => &["CloseStream"],
+            Error::ZeroStreamRate => &["OpenStream"],
+            Error::InsufficientStake => &["Unstake"],
+            Error::NoStakingRewards => &["ClaimRewards"],
+            Error::InvalidApprovalSignature | Error::EmptyApprovalBatch => {
+                &["ApproveTransferMultisigBatch"]
+            }
+            Error::UnauthorizedOverdraftAdmin | Error::OverdraftBelowDebt => &["SetOverdraft"],
+            Error::CashbackTooLarge => &["SetCashback"],
+            Error::WalletNameTaken => &["RenameWallet"],
+            Error::TransferThresholdUnreachable | Error::TransferExpirationPassed => {
+                &["TransferMultisig"]
+            }
+        }
+    }
+}
+
+impl From<BalanceError> for Error {
+    fn from(value: BalanceError) -> Self {
+        match value {
+            // Wallet balances never exceed the total supply, which is checked
+            // to fit into `Amount` whenever currency is issued.
+            BalanceError::Overflow => Error::SupplyOverflow,
+            BalanceError::Insufficient => Error::InsufficientCurrencyAmount,
+        }
+    }
+}
+
+impl From<MultisigError> for Error {
+    fn from(value: MultisigError) -> Self {
+        match value {
+            MultisigError::NotOnApproversList => Error::ApproverIsNotOnApproversList,
+            MultisigError::AlreadyApproved => Error::TransferIsAlreadyApproved,
+            MultisigError::Done => Error::TransferIsDone,
+            MultisigError::Rejected => Error::TransferIsRejected,
+            MultisigError::Expired => Error::TransferIsExpired,
+            MultisigError::Cancelled => Error::TransferIsCancelled,
+        }
+    }
 }
 
 impl From<Error> for ExecutionError {
@@ -113,14 +457,25 @@ impl From<Error> for ExecutionError {
     }
 }
 
+/// Converts the error found in a leg of a `Clearing`. The description starts with
+/// the index of the leg.
+fn clearing_leg_error(index: usize, error: Error) -> ExecutionError {
+    ExecutionError::with_description(error.code(), format!("Leg {}: {}", index, error))
+}
+
+/// Converts the error of an approval of an `ApproveTransferMultisigBatch`. The description
+/// starts with the index of the approval.
+fn batch_approval_error(index: usize, error: Error) -> ExecutionError {
+    ExecutionError::with_description(error.code(), format!("Approval {}: {}", index, error))
+}
+
 /// Transfer `amount` of the currency from one wallet to another.
-#[derive(Clone, Debug, ProtobufConvert)]
-#[exonum(pb = "proto::Transfer", serde_pb_convert)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Transfer {
     /// `PublicKey` of receiver's wallet.
     pub to: PublicKey,
     /// Amount of currency to transfer.
-    pub amount: u64,
+    pub amount: Amount,
     /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
     ///
     /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
@@ -129,17 +484,24 @@ pub struct Transfer {
 
 /// Transfer 'amount' of the currency from one wallet to another
 /// after approval from all the 'approvers'.
-#[derive(Debug, Clone, ProtobufConvert)]
-#[exonum(pb = "proto::TransferMultisig", serde_pb_convert)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TransferMultisig {
     /// `PublicKey` of receiver's wallet.
     pub to: PublicKey,
     /// Public keys of approvers.
     pub approvers: Vec<PublicKey>,
     /// Amount of currency to transfer.
-    pub amount: u64,
+    pub amount: Amount,
     /// Auxiliary number to guarantee idempotence of transactions.
     pub seed: u64,
+    /// Number of distinct approvals which completes the transfer, `0` meaning all
+    /// the approvers.
+    #[serde(default)]
+    pub threshold: u32,
+    /// Height from which the transfer can no longer be approved or rejected, `0` meaning
+    /// never.
+    #[serde(default = "Height::zero")]
+    pub expires_at: Height,
 }
 
 /// Approve multisignature transfer.
@@ -147,6 +509,32 @@ pub struct TransferMultisig {
 #[exonum(pb = "proto::ApproveTransferMultisig", serde_pb_convert)]
 pub struct ApproveTransferMultisig {
     tx_hash: Hash,
+    comment: String,
+}
+
+/// Approval of a multisignature transfer signed by the approver outside of a transaction,
+/// so that it can be submitted by anyone within an `ApproveTransferMultisigBatch`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::SignedApproval")]
+pub struct SignedApproval {
+    /// Approver who signed the approval.
+    pub approver: PublicKey,
+    /// Signature of the `approval_digest` of the transfer and the approver.
+    pub signature: Signature,
+}
+
+/// Approve multisignature transfer on behalf of several approvers at once, so that
+/// the transfer can be done in a single block. The approvals are applied in order;
+/// the ones which cannot be applied are skipped, and the outcome of each of them is
+/// recorded (see `Schema::approval_batch_results`). The transaction fails only if none
+/// of the approvals is applied.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::ApproveTransferMultisigBatch")]
+pub struct ApproveTransferMultisigBatch {
+    /// Hash of the `TransferMultisig` transaction.
+    pub tx_hash: Hash,
+    /// Approvals of the approvers. There may be at most `Config::max_approvers` of them.
+    pub approvals: Vec<SignedApproval>,
 }
 
 /// Reject multisignature transfer.
@@ -154,14 +542,28 @@ pub struct ApproveTransferMultisig {
 #[exonum(pb = "proto::RejectTransferMultisig", serde_pb_convert)]
 pub struct RejectTransferMultisig {
     tx_hash: Hash,
+    comment: String,
 }
 
 /// Issue `amount` of the currency to the `wallet`.
-#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
-#[exonum(pb = "proto::Issue")]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Issue {
     /// Issued amount of currency.
-    pub amount: u64,
+    pub amount: Amount,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Issue `amount` of the currency to the wallet `to`. Only validators
+/// (identified by their service keys) are allowed to sign this transaction.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IssueTo {
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Issued amount of currency.
+    pub amount: Amount,
     /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
     ///
     /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
@@ -176,6 +578,462 @@ pub struct CreateWallet {
     pub name: String,
 }
 
+/// Create the wallet of the `owner` with the given `name`, paying its initial balance
+/// from the wallet of the author (the sponsor) instead of issuing it. The owner
+/// does not sign the transaction, so it only needs a public key.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::CreateWalletFor")]
+pub struct CreateWalletFor {
+    /// `PublicKey` of the new wallet.
+    pub owner: PublicKey,
+    /// Name of the new wallet.
+    pub name: String,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Open a stream paying `rate_per_block` of the currency to the wallet `to` for every
+/// block until it is closed, reserving `max_total` from the author's wallet. See the
+/// `payment_stream` module.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpenStream {
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Amount paid for every block.
+    pub rate_per_block: Amount,
+    /// Amount reserved for the stream; the receiver is never paid more.
+    pub max_total: Amount,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Close the payment stream opened by the `OpenStream` transaction with the hash `stream`,
+/// paying the receiver for the blocks since the opening and refunding the rest of the
+/// reserve to the sender. Either party of the stream may close it.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::CloseStream")]
+pub struct CloseStream {
+    /// Hash of the `OpenStream` transaction.
+    pub stream: Hash,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Move `amount` of the currency from the balance of the author to its stake, which
+/// earns staking rewards. See the `staking` module.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Stake {
+    /// Staked amount of currency.
+    pub amount: Amount,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Move `amount` of the currency from the stake of the author back to its balance.
+/// The rewards earned so far stay claimable.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Unstake {
+    /// Unstaked amount of currency.
+    pub amount: Amount,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Credit the staking rewards earned by the author to its balance.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::ClaimRewards")]
+pub struct ClaimRewards {
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Set the share of every incoming `Transfer` which the wallet of the author returns
+/// to the sender at once.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::SetCashback")]
+pub struct SetCashback {
+    /// Cashback in basis points, i.e., hundredths of a percent, up to
+    /// `MAX_CASHBACK_BASIS_POINTS`. Zero disables the cashback.
+    pub basis_points: u32,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Rename the wallet of the author. The previous name is released and may be taken
+/// by other wallets.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::RenameWallet")]
+pub struct RenameWallet {
+    /// New name of the wallet, which must not be taken by any wallet.
+    pub name: String,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Set the overdraft limit of the `wallet`, up to which the wallet may spend more than
+/// its balance. Only the overdraft admin of the service configuration is allowed to sign
+/// this transaction.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetOverdraft {
+    /// `PublicKey` of the wallet.
+    pub wallet: PublicKey,
+    /// New overdraft limit. It must not be below the amount owed by the wallet.
+    pub limit: Amount,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Create a wallet owned jointly by the `owners`, whose address is the hash
+/// of the owners. See the `joint_wallet` module.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::CreateJointWallet")]
+pub struct CreateJointWallet {
+    /// Public keys of the owners. The author of the transaction must be one of them.
+    pub owners: Vec<PublicKey>,
+    /// Number of distinct owners who must sign a spend from the wallet.
+    pub threshold: u32,
+    /// Name of the new wallet.
+    pub name: String,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Propose to transfer `amount` of the currency from the joint `wallet` to the wallet `to`.
+/// The proposal is signed by its author, who must be an owner of the wallet, and the other
+/// owners co-sign it with `ApproveJointSpend`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProposeJointSpend {
+    /// Address of the joint wallet.
+    pub wallet: PublicKey,
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Amount of currency to transfer.
+    pub amount: Amount,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+/// Co-sign the spend from a joint wallet proposed by the `ProposeJointSpend` transaction
+/// with the hash `proposal`. The spend is executed once signed by the threshold number
+/// of owners.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::ApproveJointSpend")]
+pub struct ApproveJointSpend {
+    /// Hash of the `ProposeJointSpend` transaction.
+    pub proposal: Hash,
+}
+
+/// Transfer of `amount` of the currency from one wallet to another within a `Clearing`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClearingLeg {
+    /// `PublicKey` of sender's wallet.
+    pub from: PublicKey,
+    /// `PublicKey` of receiver's wallet.
+    pub to: PublicKey,
+    /// Amount of currency to transfer.
+    pub amount: Amount,
+}
+
+/// Apply the transfers of the `legs` all at once, e.g., the ones computed by a netting
+/// system. Only the clearing operator of the service configuration is allowed to sign
+/// this transaction. Either all the legs are applied or none of them; the balances
+/// are checked after netting all the legs, so a wallet may spend what it receives
+/// in the same clearing.
+#[derive(Serialize, Deserialize, Clone, Debug, ProtobufConvert)]
+#[exonum(pb = "proto::Clearing")]
+pub struct Clearing {
+    /// Transfers to apply.
+    pub legs: Vec<ClearingLeg>,
+    /// Auxiliary number to guarantee [non-idempotence][idempotence] of transactions.
+    ///
+    /// [idempotence]: https://en.wikipedia.org/wiki/Idempotence
+    pub seed: u64,
+}
+
+impl ProtobufConvert for Transfer {
+    type ProtoStruct = proto::Transfer;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::Transfer::new();
+        pb.set_to(self.to.to_pb());
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for TransferMultisig {
+    type ProtoStruct = proto::TransferMultisig;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::TransferMultisig::new();
+        pb.set_to(self.to.to_pb());
+        pb.set_approvers(self.approvers.to_pb().into());
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb.set_seed(self.seed);
+        pb.set_threshold(self.threshold);
+        pb.set_expires_at(self.expires_at.to_pb());
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            approvers: ProtobufConvert::from_pb(pb.get_approvers().to_owned())?,
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            seed: pb.get_seed(),
+            threshold: pb.get_threshold(),
+            expires_at: Height::from_pb(pb.get_expires_at())?,
+        })
+    }
+}
+
+impl ProtobufConvert for Issue {
+    type ProtoStruct = proto::Issue;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::Issue::new();
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for IssueTo {
+    type ProtoStruct = proto::IssueTo;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::IssueTo::new();
+        pb.set_to(self.to.to_pb());
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for ProposeJointSpend {
+    type ProtoStruct = proto::ProposeJointSpend;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::ProposeJointSpend::new();
+        pb.set_wallet(self.wallet.to_pb());
+        pb.set_to(self.to.to_pb());
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            wallet: PublicKey::from_pb(pb.get_wallet().clone())?,
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for OpenStream {
+    type ProtoStruct = proto::OpenStream;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::OpenStream::new();
+        pb.set_to(self.to.to_pb());
+        let (rate_per_block, exact_rate_per_block) = self.rate_per_block.to_pb_fields();
+        pb.set_rate_per_block(rate_per_block);
+        if let Some(exact_rate_per_block) = exact_rate_per_block {
+            pb.set_exact_rate_per_block(exact_rate_per_block);
+        }
+        let (max_total, exact_max_total) = self.max_total.to_pb_fields();
+        pb.set_max_total(max_total);
+        if let Some(exact_max_total) = exact_max_total {
+            pb.set_exact_max_total(exact_max_total);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            rate_per_block: Amount::from_pb_fields(
+                pb.get_rate_per_block(),
+                pb.get_exact_rate_per_block(),
+            ),
+            max_total: Amount::from_pb_fields(pb.get_max_total(), pb.get_exact_max_total()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for Stake {
+    type ProtoStruct = proto::Stake;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::Stake::new();
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for Unstake {
+    type ProtoStruct = proto::Unstake;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::Unstake::new();
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for SetOverdraft {
+    type ProtoStruct = proto::SetOverdraft;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::SetOverdraft::new();
+        pb.set_wallet(self.wallet.to_pb());
+        let (limit, exact_limit) = self.limit.to_pb_fields();
+        pb.set_limit(limit);
+        if let Some(exact_limit) = exact_limit {
+            pb.set_exact_limit(exact_limit);
+        }
+        pb.set_seed(self.seed);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            wallet: PublicKey::from_pb(pb.get_wallet().clone())?,
+            limit: Amount::from_pb_fields(pb.get_limit(), pb.get_exact_limit()),
+            seed: pb.get_seed(),
+        })
+    }
+}
+
+impl ProtobufConvert for ClearingLeg {
+    type ProtoStruct = proto::ClearingLeg;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::ClearingLeg::new();
+        pb.set_from(self.from.to_pb());
+        pb.set_to(self.to.to_pb());
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            from: PublicKey::from_pb(pb.get_from().clone())?,
+            to: PublicKey::from_pb(pb.get_to().clone())?,
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+        })
+    }
+}
+
+impl_pb_storage!(
+    Transfer,
+    TransferMultisig,
+    Issue,
+    IssueTo,
+    ProposeJointSpend,
+    OpenStream,
+    Stake,
+    Unstake
+);
+
 /// Transaction group.
 #[derive(Serialize, Deserialize, Clone, Debug, TransactionSet)]
 pub enum WalletTransactions {
@@ -191,31 +1049,254 @@ pub enum WalletTransactions {
     ApproveTransferMultisig(ApproveTransferMultisig),
     /// RejectTransferMultisig tx.
     RejectTransferMultisig(RejectTransferMultisig),
+    /// IssueTo tx.
+    IssueTo(IssueTo),
+    /// CreateJointWallet tx.
+    CreateJointWallet(CreateJointWallet),
+    /// ProposeJointSpend tx.
+    ProposeJointSpend(ProposeJointSpend),
+    /// ApproveJointSpend tx.
+    ApproveJointSpend(ApproveJointSpend),
+    /// Clearing tx.
+    Clearing(Clearing),
+    /// CreateWalletFor tx.
+    CreateWalletFor(CreateWalletFor),
+    /// OpenStream tx.
+    OpenStream(OpenStream),
+    /// CloseStream tx.
+    CloseStream(CloseStream),
+    /// Stake tx.
+    Stake(Stake),
+    /// Unstake tx.
+    Unstake(Unstake),
+    /// ClaimRewards tx.
+    ClaimRewards(ClaimRewards),
+    /// ApproveTransferMultisigBatch tx.
+    ApproveTransferMultisigBatch(ApproveTransferMultisigBatch),
+    /// SetOverdraft tx.
+    SetOverdraft(SetOverdraft),
+    /// SetCashback tx.
+    SetCashback(SetCashback),
+    /// RenameWallet tx.
+    RenameWallet(RenameWallet),
 }
 
-impl CreateWallet {
-    #[doc(hidden)]
-    pub fn sign(name: &str, pk: &PublicKey, sk: &SecretKey) -> Signed<RawTransaction> {
-        Message::sign_transaction(
-            Self {
-                name: name.to_owned(),
-            },
-            CRYPTOCURRENCY_SERVICE_ID,
-            *pk,
+impl WalletTransactions {
+    /// Number of the transaction types. Their message identifiers are `0..MESSAGE_COUNT`
+    /// in the order of the variants.
+    pub const MESSAGE_COUNT: u16 = 21;
+
+    /// Returns the name of the transaction type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WalletTransactions::Transfer(_) => "Transfer",
+            WalletTransactions::Issue(_) => "Issue",
+            WalletTransactions::CreateWallet(_) => "CreateWallet",
+            WalletTransactions::TransferMultisig(_) => "TransferMultisig",
+            WalletTransactions::ApproveTransferMultisig(_) => "ApproveTransferMultisig",
+            WalletTransactions::RejectTransferMultisig(_) => "RejectTransferMultisig",
+            WalletTransactions::IssueTo(_) => "IssueTo",
+            WalletTransactions::CreateJointWallet(_) => "CreateJointWallet",
+            WalletTransactions::ProposeJointSpend(_) => "ProposeJointSpend",
+            WalletTransactions::ApproveJointSpend(_) => "ApproveJointSpend",
+            WalletTransactions::Clearing(_) => "Clearing",
+            WalletTransactions::CreateWalletFor(_) => "CreateWalletFor",
+            WalletTransactions::OpenStream(_) => "OpenStream",
+            WalletTransactions::CloseStream(_) => "CloseStream",
+            WalletTransactions::Stake(_) => "Stake",
+            WalletTransactions::Unstake(_) => "Unstake",
+            WalletTransactions::ClaimRewards(_) => "ClaimRewards",
+            WalletTransactions::ApproveTransferMultisigBatch(_) => "ApproveTransferMultisigBatch",
+            WalletTransactions::SetOverdraft(_) => "SetOverdraft",
+            WalletTransactions::SetCashback(_) => "SetCashback",
+            WalletTransactions::RenameWallet(_) => "RenameWallet",
+        }
+    }
+}
+
+/// Decodes the message as a transaction of the cryptocurrency service.
+pub(crate) fn service_tx(message: &Signed<RawTransaction>) -> Option<WalletTransactions> {
+    let raw_tx = message.payload();
+    if raw_tx.service_id() != CRYPTOCURRENCY_SERVICE_ID {
+        return None;
+    }
+    WalletTransactions::tx_from_raw(raw_tx.clone()).ok()
+}
+
+/// Returns the `TransferMultisig` transaction with the given hash from the blockchain
+/// together with its author.
+pub(crate) fn transfer_multisig_tx<T>(
+    schema: &blockchain::Schema<T>,
+    tx_hash: &Hash,
+) -> Option<(PublicKey, TransferMultisig)>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let message = schema.transactions().get(tx_hash)?;
+    match service_tx(&message)? {
+        WalletTransactions::TransferMultisig(tx) => Some((message.author(), tx)),
+        _ => None,
+    }
+}
+
+/// Returns a seed drawn from the cryptographically secure random number generator of
+/// `exonum::crypto`. Seeds of the transactions signed with the `sign_with_random_seed`
+/// constructors come from it, so that equal transactions get different hashes.
+pub fn random_seed() -> u64 {
+    // The secret key starts with the random seed of the generated keypair.
+    let (_, secret_key) = crypto::gen_keypair();
+    secret_key[..8]
+        .iter()
+        .fold(0, |seed, &byte| seed << 8 | u64::from(byte))
+}
+
+impl CreateWallet {
+    /// Creates the transaction to be signed by the wallet owner outside of the process.
+    pub fn unsigned(name: &str) -> UnsignedTx {
+        UnsignedTx::new(Self {
+            name: name.to_owned(),
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(name: &str, pk: &PublicKey, sk: &SecretKey) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self {
+                name: name.to_owned(),
+            },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+}
+
+impl CreateWalletFor {
+    /// Creates the transaction to be signed by the sponsor outside of the process.
+    pub fn unsigned(&owner: &PublicKey, name: &str, seed: u64) -> UnsignedTx {
+        UnsignedTx::new(Self {
+            owner,
+            name: name.to_owned(),
+            seed,
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        &owner: &PublicKey,
+        name: &str,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self {
+                owner,
+                name: name.to_owned(),
+                seed,
+            },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        owner: &PublicKey,
+        name: &str,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, owner, name, seed, sk), seed)
+    }
+}
+
+impl Issue {
+    /// Creates the transaction to be signed by a validator outside of the process.
+    pub fn unsigned(amount: impl Into<Amount>, seed: u64) -> UnsignedTx {
+        let amount = amount.into();
+        UnsignedTx::new(Self { amount, seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        amount: impl Into<Amount>,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        let amount = amount.into();
+        Message::sign_transaction(Self { amount, seed }, CRYPTOCURRENCY_SERVICE_ID, *pk, sk)
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        amount: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, amount, seed, sk), seed)
+    }
+}
+
+impl IssueTo {
+    /// Creates the transaction to be signed by a validator outside of the process.
+    pub fn unsigned(&to: &PublicKey, amount: impl Into<Amount>, seed: u64) -> UnsignedTx {
+        let amount = amount.into();
+        UnsignedTx::new(Self { to, amount, seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        &to: &PublicKey,
+        amount: impl Into<Amount>,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        let amount = amount.into();
+        Message::sign_transaction(
+            Self { to, amount, seed },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
             sk,
         )
     }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        to: &PublicKey,
+        amount: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, to, amount, seed, sk), seed)
+    }
 }
 
 impl Transfer {
+    /// Creates the transaction to be signed by the sender outside of the process.
+    pub fn unsigned(&to: &PublicKey, amount: impl Into<Amount>, seed: u64) -> UnsignedTx {
+        let amount = amount.into();
+        UnsignedTx::new(Self { to, amount, seed })
+    }
+
     #[doc(hidden)]
     pub fn sign(
         pk: &PublicKey,
         &to: &PublicKey,
-        amount: u64,
+        amount: impl Into<Amount>,
         seed: u64,
         sk: &SecretKey,
     ) -> Signed<RawTransaction> {
+        let amount = amount.into();
         Message::sign_transaction(
             Self { to, amount, seed },
             CRYPTOCURRENCY_SERVICE_ID,
@@ -223,9 +1304,61 @@ impl Transfer {
             sk,
         )
     }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        to: &PublicKey,
+        amount: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, to, amount, seed, sk), seed)
+    }
 }
 
 impl TransferMultisig {
+    /// Creates a transfer completed by the approvals of all the approvers and
+    /// never expiring.
+    pub fn new(
+        to: PublicKey,
+        approvers: HashSet<PublicKey>,
+        amount: impl Into<Amount>,
+        seed: u64,
+    ) -> Self {
+        Self {
+            to,
+            approvers: approvers.into_iter().collect(),
+            amount: amount.into(),
+            seed,
+            threshold: 0,
+            expires_at: Height(0),
+        }
+    }
+
+    /// Completes the transfer once it is approved by `threshold` distinct approvers.
+    pub fn with_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Expires the transfer at the given height unless it is settled before.
+    pub fn with_expiration(mut self, expires_at: Height) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    /// Creates the transaction to be signed by the sender outside of the process.
+    pub fn unsigned(
+        to: PublicKey,
+        approvers: HashSet<PublicKey>,
+        amount: impl Into<Amount>,
+        seed: u64,
+    ) -> UnsignedTx {
+        UnsignedTx::new(Self::new(to, approvers, amount, seed))
+    }
+
     #[doc(hidden)]
     pub fn sign(
         pk: PublicKey,
@@ -233,34 +1366,570 @@ impl TransferMultisig {
         to: PublicKey,
         // HashSet is used to guarantee an absense of duplicates.
         approvers: HashSet<PublicKey>,
-        amount: u64,
+        amount: impl Into<Amount>,
+        seed: u64,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self::new(to, approvers, amount, seed),
+            CRYPTOCURRENCY_SERVICE_ID,
+            pk,
+            sk,
+        )
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: PublicKey,
+        sk: &SecretKey,
+        to: PublicKey,
+        approvers: HashSet<PublicKey>,
+        amount: impl Into<Amount>,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, sk, to, approvers, amount, seed), seed)
+    }
+}
+
+impl ApproveTransferMultisig {
+    /// Creates an approval of the `TransferMultisig` transaction with the given hash.
+    pub fn new(tx_hash: Hash) -> Self {
+        Self::with_comment(tx_hash, String::new())
+    }
+
+    /// Creates an approval of the `TransferMultisig` transaction with the given hash
+    /// and a comment of the approver.
+    pub fn with_comment(tx_hash: Hash, comment: String) -> Self {
+        Self { tx_hash, comment }
+    }
+
+    /// Returns the hash of the approved `TransferMultisig` transaction.
+    pub fn transfer_hash(&self) -> &Hash {
+        &self.tx_hash
+    }
+
+    /// Returns the comment of the approver. Empty if the approver gave none.
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// Creates the approval to be signed by the approver outside of the process.
+    pub fn unsigned(tx_hash: Hash, comment: &str) -> UnsignedTx {
+        UnsignedTx::new(Self::with_comment(tx_hash, comment.to_owned()))
+    }
+
+    #[doc(hidden)]
+    pub fn sign(pk: PublicKey, sk: &SecretKey, tx_hash: Hash) -> Signed<RawTransaction> {
+        Self::sign_with_comment(pk, sk, tx_hash, "")
+    }
+
+    #[doc(hidden)]
+    pub fn sign_with_comment(
+        pk: PublicKey,
+        sk: &SecretKey,
+        tx_hash: Hash,
+        comment: &str,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self::with_comment(tx_hash, comment.to_owned()),
+            CRYPTOCURRENCY_SERVICE_ID,
+            pk,
+            sk,
+        )
+    }
+}
+
+impl SignedApproval {
+    /// Signs the approval of the multisignature transfer created by the transaction
+    /// with the given hash.
+    pub fn new(tx_hash: &Hash, approver: PublicKey, sk: &SecretKey) -> Self {
+        let digest = approval_digest(tx_hash, &approver);
+        Self {
+            approver,
+            signature: crypto::sign(digest.as_ref(), sk),
+        }
+    }
+
+    /// Checks the signature of the approval of the multisignature transfer created by
+    /// the transaction with the given hash.
+    pub fn verify(&self, tx_hash: &Hash) -> bool {
+        let digest = approval_digest(tx_hash, &self.approver);
+        crypto::verify(&self.signature, digest.as_ref(), &self.approver)
+    }
+}
+
+impl ApproveTransferMultisigBatch {
+    /// Creates the transaction to be signed by the submitter outside of the process.
+    pub fn unsigned(tx_hash: Hash, approvals: Vec<SignedApproval>) -> UnsignedTx {
+        UnsignedTx::new(Self { tx_hash, approvals })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        tx_hash: Hash,
+        approvals: Vec<SignedApproval>,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self { tx_hash, approvals },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+}
+
+impl RejectTransferMultisig {
+    /// Returns the hash of the rejected `TransferMultisig` transaction.
+    pub fn transfer_hash(&self) -> &Hash {
+        &self.tx_hash
+    }
+
+    /// Returns the comment of the rejecter. Empty if the rejecter gave none.
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// Creates the rejection to be signed by the rejecter outside of the process.
+    pub fn unsigned(tx_hash: Hash, comment: &str) -> UnsignedTx {
+        UnsignedTx::new(Self {
+            tx_hash,
+            comment: comment.to_owned(),
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(pk: PublicKey, sk: &SecretKey, tx_hash: Hash) -> Signed<RawTransaction> {
+        Self::sign_with_comment(pk, sk, tx_hash, "")
+    }
+
+    #[doc(hidden)]
+    pub fn sign_with_comment(
+        pk: PublicKey,
+        sk: &SecretKey,
+        tx_hash: Hash,
+        comment: &str,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self {
+                tx_hash,
+                comment: comment.to_owned(),
+            },
+            CRYPTOCURRENCY_SERVICE_ID,
+            pk,
+            sk,
+        )
+    }
+}
+
+impl CreateJointWallet {
+    /// Creates the transaction to be signed by one of the owners outside of the process.
+    pub fn unsigned(owners: &[PublicKey], threshold: u32, name: &str, seed: u64) -> UnsignedTx {
+        UnsignedTx::new(Self {
+            owners: owners.to_vec(),
+            threshold,
+            name: name.to_owned(),
+            seed,
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        owners: &[PublicKey],
+        threshold: u32,
+        name: &str,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self {
+                owners: owners.to_vec(),
+                threshold,
+                name: name.to_owned(),
+                seed,
+            },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        owners: &[PublicKey],
+        threshold: u32,
+        name: &str,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, owners, threshold, name, seed, sk), seed)
+    }
+}
+
+impl ProposeJointSpend {
+    /// Creates the transaction to be signed by the proposing owner outside of the process.
+    pub fn unsigned(
+        &wallet: &PublicKey,
+        &to: &PublicKey,
+        amount: impl Into<Amount>,
+        seed: u64,
+    ) -> UnsignedTx {
+        let amount = amount.into();
+        UnsignedTx::new(Self {
+            wallet,
+            to,
+            amount,
+            seed,
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        &wallet: &PublicKey,
+        &to: &PublicKey,
+        amount: impl Into<Amount>,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        let amount = amount.into();
+        Message::sign_transaction(
+            Self {
+                wallet,
+                to,
+                amount,
+                seed,
+            },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        wallet: &PublicKey,
+        to: &PublicKey,
+        amount: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, wallet, to, amount, seed, sk), seed)
+    }
+}
+
+impl ApproveJointSpend {
+    /// Creates the approval to be signed by the owner outside of the process.
+    pub fn unsigned(proposal: Hash) -> UnsignedTx {
+        UnsignedTx::new(Self { proposal })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(pk: PublicKey, sk: &SecretKey, proposal: Hash) -> Signed<RawTransaction> {
+        Message::sign_transaction(Self { proposal }, CRYPTOCURRENCY_SERVICE_ID, pk, sk)
+    }
+}
+
+impl ClearingLeg {
+    /// Creates the transfer of `amount` from the wallet `from` to the wallet `to`.
+    pub fn new(from: PublicKey, to: PublicKey, amount: impl Into<Amount>) -> Self {
+        Self {
+            from,
+            to,
+            amount: amount.into(),
+        }
+    }
+}
+
+impl Clearing {
+    /// Creates the transaction to be signed by the clearing operator outside of the process.
+    pub fn unsigned(legs: Vec<ClearingLeg>, seed: u64) -> UnsignedTx {
+        UnsignedTx::new(Self { legs, seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        legs: Vec<ClearingLeg>,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(Self { legs, seed }, CRYPTOCURRENCY_SERVICE_ID, *pk, sk)
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        legs: Vec<ClearingLeg>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, legs, seed, sk), seed)
+    }
+}
+
+impl OpenStream {
+    /// Creates the transaction to be signed by the sender outside of the process.
+    pub fn unsigned(
+        &to: &PublicKey,
+        rate_per_block: impl Into<Amount>,
+        max_total: impl Into<Amount>,
+        seed: u64,
+    ) -> UnsignedTx {
+        UnsignedTx::new(Self {
+            to,
+            rate_per_block: rate_per_block.into(),
+            max_total: max_total.into(),
+            seed,
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        &to: &PublicKey,
+        rate_per_block: impl Into<Amount>,
+        max_total: impl Into<Amount>,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self {
+                to,
+                rate_per_block: rate_per_block.into(),
+                max_total: max_total.into(),
+                seed,
+            },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        to: &PublicKey,
+        rate_per_block: impl Into<Amount>,
+        max_total: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (
+            Self::sign(pk, to, rate_per_block, max_total, seed, sk),
+            seed,
+        )
+    }
+}
+
+impl CloseStream {
+    /// Creates the transaction to be signed by a party of the stream outside of the process.
+    pub fn unsigned(stream: Hash, seed: u64) -> UnsignedTx {
+        UnsignedTx::new(Self { stream, seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(pk: &PublicKey, stream: Hash, seed: u64, sk: &SecretKey) -> Signed<RawTransaction> {
+        Message::sign_transaction(Self { stream, seed }, CRYPTOCURRENCY_SERVICE_ID, *pk, sk)
+    }
+}
+
+impl Stake {
+    /// Creates the transaction to be signed by the wallet owner outside of the process.
+    pub fn unsigned(amount: impl Into<Amount>, seed: u64) -> UnsignedTx {
+        let amount = amount.into();
+        UnsignedTx::new(Self { amount, seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        amount: impl Into<Amount>,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        let amount = amount.into();
+        Message::sign_transaction(Self { amount, seed }, CRYPTOCURRENCY_SERVICE_ID, *pk, sk)
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        amount: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, amount, seed, sk), seed)
+    }
+}
+
+impl Unstake {
+    /// Creates the transaction to be signed by the wallet owner outside of the process.
+    pub fn unsigned(amount: impl Into<Amount>, seed: u64) -> UnsignedTx {
+        let amount = amount.into();
+        UnsignedTx::new(Self { amount, seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        amount: impl Into<Amount>,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        let amount = amount.into();
+        Message::sign_transaction(Self { amount, seed }, CRYPTOCURRENCY_SERVICE_ID, *pk, sk)
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        amount: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, amount, seed, sk), seed)
+    }
+}
+
+impl ClaimRewards {
+    /// Creates the transaction to be signed by the wallet owner outside of the process.
+    pub fn unsigned(seed: u64) -> UnsignedTx {
+        UnsignedTx::new(Self { seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(pk: &PublicKey, seed: u64, sk: &SecretKey) -> Signed<RawTransaction> {
+        Message::sign_transaction(Self { seed }, CRYPTOCURRENCY_SERVICE_ID, *pk, sk)
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(pk: &PublicKey, sk: &SecretKey) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, seed, sk), seed)
+    }
+}
+
+impl SetCashback {
+    /// Creates the transaction to be signed by the wallet owner outside of the process.
+    pub fn unsigned(basis_points: u32, seed: u64) -> UnsignedTx {
+        UnsignedTx::new(Self { basis_points, seed })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        basis_points: u32,
+        seed: u64,
+        sk: &SecretKey,
+    ) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self { basis_points, seed },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        basis_points: u32,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, basis_points, seed, sk), seed)
+    }
+}
+
+impl RenameWallet {
+    /// Creates the transaction to be signed by the wallet owner outside of the process.
+    pub fn unsigned(name: &str, seed: u64) -> UnsignedTx {
+        UnsignedTx::new(Self {
+            name: name.to_owned(),
+            seed,
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(pk: &PublicKey, name: &str, seed: u64, sk: &SecretKey) -> Signed<RawTransaction> {
+        Message::sign_transaction(
+            Self {
+                name: name.to_owned(),
+                seed,
+            },
+            CRYPTOCURRENCY_SERVICE_ID,
+            *pk,
+            sk,
+        )
+    }
+
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        name: &str,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, name, seed, sk), seed)
+    }
+}
+
+impl SetOverdraft {
+    /// Creates the transaction to be signed by the overdraft admin outside of the process.
+    pub fn unsigned(&wallet: &PublicKey, limit: impl Into<Amount>, seed: u64) -> UnsignedTx {
+        let limit = limit.into();
+        UnsignedTx::new(Self {
+            wallet,
+            limit,
+            seed,
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn sign(
+        pk: &PublicKey,
+        &wallet: &PublicKey,
+        limit: impl Into<Amount>,
         seed: u64,
+        sk: &SecretKey,
     ) -> Signed<RawTransaction> {
+        let limit = limit.into();
         Message::sign_transaction(
             Self {
-                to,
-                approvers: approvers.into_iter().collect(),
-                amount,
+                wallet,
+                limit,
                 seed,
             },
             CRYPTOCURRENCY_SERVICE_ID,
-            pk,
+            *pk,
             sk,
         )
     }
-}
-
-impl ApproveTransferMultisig {
-    #[doc(hidden)]
-    pub fn sign(pk: PublicKey, sk: &SecretKey, tx_hash: Hash) -> Signed<RawTransaction> {
-        Message::sign_transaction(Self { tx_hash }, CRYPTOCURRENCY_SERVICE_ID, pk, sk)
-    }
-}
 
-impl RejectTransferMultisig {
-    #[doc(hidden)]
-    pub fn sign(pk: PublicKey, sk: &SecretKey, tx_hash: Hash) -> Signed<RawTransaction> {
-        Message::sign_transaction(Self { tx_hash }, CRYPTOCURRENCY_SERVICE_ID, pk, sk)
+    /// Signs the transaction with a random seed. The seed is returned along with
+    /// the message.
+    pub fn sign_with_random_seed(
+        pk: &PublicKey,
+        wallet: &PublicKey,
+        limit: impl Into<Amount>,
+        sk: &SecretKey,
+    ) -> (Signed<RawTransaction>, u64) {
+        let seed = random_seed();
+        (Self::sign(pk, wallet, limit, seed, sk), seed)
     }
 }
 
@@ -268,6 +1937,7 @@ impl Transaction for Transfer {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
         let from = &context.author();
         let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
         let mut schema = Schema::new(context.fork());
 
@@ -278,15 +1948,35 @@ impl Transaction for Transfer {
             Err(Error::SenderSameAsReceiver)?;
         }
 
+        let config = schema.config();
+        config.check_amount(amount)?;
         let sender = schema.wallet(from).ok_or(Error::SenderNotFound)?;
         let receiver = schema.wallet(to).ok_or(Error::ReceiverNotFound)?;
 
-        if sender.balance < amount {
-            Err(Error::InsufficientCurrencyAmount)?
+        let cashback = receiver.cashback(amount, config.decimals);
+        let sender = sender.try_decrease_balance(amount).map_err(Error::from)?;
+        let receiver = receiver.try_increase_balance(amount).map_err(Error::from)?;
+        let mut updates = vec![(sender.clone(), hash), (receiver.clone(), hash)];
+
+        // The cashback is returned directly rather than by a transfer, so the cashback
+        // of the sender does not apply to it.
+        if !cashback.is_zero() {
+            let receiver = receiver
+                .try_decrease_balance(cashback)
+                .map_err(Error::from)?;
+            let sender = sender.try_increase_balance(cashback).map_err(Error::from)?;
+            updates.push((receiver, hash));
+            updates.push((sender, hash));
         }
 
-        schema.update_wallet(sender.decrease_balance(amount), hash);
-        schema.update_wallet(receiver.increase_balance(amount), hash);
+        schema.update_wallets_batch(updates, height);
+        schema.record_transfer(amount);
+        schema.record_wallet_transfer(from, to, amount);
+        if !cashback.is_zero() {
+            schema.record_transfer(cashback);
+            schema.record_wallet_transfer(to, from, cashback);
+        }
+        schema.index_transfer_seed(from, self.seed, hash);
 
         Ok(())
     }
@@ -296,11 +1986,20 @@ impl Transaction for Issue {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
         let pub_key = &context.author();
         let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
         let mut schema = Schema::new(context.fork());
 
+        schema.config().check_amount(self.amount)?;
         if let Some(wallet) = schema.wallet(pub_key) {
-            schema.update_wallet(wallet.increase_balance(self.amount), hash);
+            // Wallet balances never exceed the total supply, so the check covers them too.
+            schema.check_supply_increase(self.amount)?;
+            let wallet = wallet
+                .try_increase_balance(self.amount)
+                .map_err(Error::from)?;
+            schema.update_wallet(wallet, hash, height);
+            schema.increase_total_supply(self.amount);
+            schema.record_wallet_issue(pub_key, self.amount);
             Ok(())
         } else {
             Err(Error::ReceiverNotFound)?
@@ -308,16 +2007,53 @@ impl Transaction for Issue {
     }
 }
 
+impl Transaction for IssueTo {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let issuer = context.author();
+        let hash = context.tx_hash();
+
+        let (is_validator, height) = {
+            let blockchain = blockchain::Schema::new(context.fork());
+            let is_validator = blockchain
+                .actual_configuration()
+                .validator_keys
+                .iter()
+                .any(|keys| keys.service_key == issuer);
+            (is_validator, blockchain.height().next())
+        };
+        if !is_validator {
+            return Err(Error::UnauthorizedIssuer.into());
+        }
+
+        let mut schema = Schema::new(context.fork());
+
+        schema.config().check_amount(self.amount)?;
+        let wallet = schema.wallet(&self.to).ok_or(Error::ReceiverNotFound)?;
+        schema.check_supply_increase(self.amount)?;
+        let wallet = wallet
+            .try_increase_balance(self.amount)
+            .map_err(Error::from)?;
+        schema.update_wallet(wallet, hash, height);
+        schema.increase_total_supply(self.amount);
+        schema.record_wallet_issue(&self.to, self.amount);
+
+        Ok(())
+    }
+}
+
 impl Transaction for CreateWallet {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
         let pub_key = &context.author();
         let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
         let mut schema = Schema::new(context.fork());
 
         if schema.wallet(pub_key).is_none() {
-            let name = &self.name;
-            schema.create_wallet(pub_key, name, &hash);
+            let balance = schema.config().initial_balance;
+            schema.check_supply_increase(balance)?;
+            let wallet = schema.create_wallet(pub_key, &self.name, balance, &hash, height);
+            debug!("Created wallet {:?} at height {}", wallet.pub_key, height);
             Ok(())
         } else {
             Err(Error::WalletAlreadyExists)?
@@ -325,13 +2061,58 @@ impl Transaction for CreateWallet {
     }
 }
 
-/// Some arbitrary constraint specifying how large approvers list can be.
+impl Transaction for CreateWalletFor {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let sponsor = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
+
+        if schema.wallet(&self.owner).is_some() {
+            Err(Error::WalletAlreadyExists)?;
+        }
+        let balance = schema.config().initial_balance;
+        let sponsor_wallet = schema.wallet(&sponsor).ok_or(Error::SenderNotFound)?;
+        let sponsor_wallet = sponsor_wallet
+            .try_decrease_balance(balance)
+            .map_err(Error::from)?;
+
+        schema.update_wallet(sponsor_wallet, hash, height);
+        let wallet = schema.create_sponsored_wallet(
+            &self.owner,
+            &self.name,
+            balance,
+            &sponsor,
+            &hash,
+            height,
+        );
+        schema.record_transfer(balance);
+        schema.record_wallet_transfer(&sponsor, &self.owner, balance);
+        debug!(
+            "Created wallet {:?} sponsored by {:?} at height {}",
+            wallet.pub_key, sponsor, height
+        );
+        Ok(())
+    }
+}
+
+/// Some arbitrary constraint specifying how large approvers list can be by default.
+/// See `Config::max_approvers`.
 pub const MAX_APPROVERS: usize = 5;
 
+/// Default maximum length of an approver's comment in bytes.
+/// See `Config::max_comment_len`.
+pub const MAX_COMMENT_LEN: usize = 256;
+
+/// Default maximum number of legs of a clearing. See `Config::max_clearing_legs`.
+pub const MAX_CLEARING_LEGS: usize = 256;
+
 impl Transaction for TransferMultisig {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
         let from = context.author();
         let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
         let mut schema = Schema::new(context.fork());
 
@@ -342,12 +2123,12 @@ impl Transaction for TransferMultisig {
             return Err(Error::SenderSameAsReceiver.into());
         }
 
+        let config = schema.config();
+        config.check_amount(amount)?;
         let sender = schema.wallet(&from).ok_or(Error::SenderNotFound)?;
         let _receiver = schema.wallet(&to).ok_or(Error::ReceiverNotFound)?;
 
-        if sender.balance < amount {
-            return Err(Error::InsufficientCurrencyAmount.into());
-        }
+        let sender = sender.try_decrease_balance(amount).map_err(Error::from)?;
 
         let approvers: HashSet<PublicKey> = self.approvers.iter().cloned().collect();
 
@@ -355,134 +2136,634 @@ impl Transaction for TransferMultisig {
             return Err(Error::EmptyApproversList.into());
         }
 
-        if approvers.len() > MAX_APPROVERS {
+        if approvers.len() > config.max_approvers {
             return Err(Error::ApproversListIsTooLarge.into());
         }
 
-        let sender = sender.decrease_balance(amount);
+        if self.threshold as usize > approvers.len() {
+            return Err(Error::TransferThresholdUnreachable.into());
+        }
+
+        if self.expires_at > Height(0) && self.expires_at <= height {
+            return Err(Error::TransferExpirationPassed.into());
+        }
 
-        schema.update_wallet(sender, hash);
-        schema.create_transfer_multisig(hash);
+        schema.update_wallet(sender, hash, height);
+        schema.create_transfer_multisig(
+            hash,
+            MultisignatureTransfer::new(
+                from,
+                to,
+                amount,
+                self.approvers.clone(),
+                self.threshold,
+                self.expires_at,
+            ),
+        );
+        schema.index_transfer_seed(&from, self.seed, hash);
 
         Ok(())
     }
 }
 
+/// Returns the multisignature transfer in process referred by the approving or
+/// rejecting transaction. Settled transfers are reported by their final state,
+/// whether or not they are collected already. The core schema is only consulted
+/// to tell why the transfer is missing.
+fn referred_transfer(
+    fork: &Fork,
+    tx_hash: &Hash,
+) -> Result<MultisignatureTransfer, ExecutionError> {
+    let schema = Schema::new(fork);
+    let error = match schema.multisig_transfer(*tx_hash) {
+        Some(ref transfer) if transfer.is_done() => Error::TransferIsDone,
+        Some(ref transfer) if transfer.is_rejected() => Error::TransferIsRejected,
+        Some(ref transfer) if transfer.state == State::Expired => Error::TransferIsExpired,
+        Some(ref transfer) if transfer.state == State::Cancelled => Error::TransferIsCancelled,
+        Some(transfer) => return Ok(transfer),
+        None => match schema.collected_multisig_state(tx_hash) {
+            Some(State::Done) => Error::TransferIsDone,
+            Some(State::Rejected) => Error::TransferIsRejected,
+            Some(State::Expired) => Error::TransferIsExpired,
+            Some(State::Cancelled) => Error::TransferIsCancelled,
+            _ => match blockchain::Schema::new(fork)
+                .transaction_results()
+                .get(tx_hash)
+            {
+                None => Error::TransactionDoesNotExist,
+                Some(result) if result.0.is_err() => Error::ReferredTransactionFailed,
+                Some(_) => Error::ReferredTransactionIsNotTransferMultisig,
+            },
+        },
+    };
+    Err(error.into())
+}
+
 impl Transaction for ApproveTransferMultisig {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
-        use exonum::blockchain::TransactionSet;
+        if self.comment.len() > Schema::new(context.fork()).config().max_comment_len {
+            Err(Error::CommentTooLong)?;
+        }
 
-        let original_transfer = {
-            let blockchain = blockchain::Schema::new(context.fork());
+        let approver = context.author();
+        let tx_hash = context.tx_hash();
+        let transfer_in_question = referred_transfer(context.fork(), &self.tx_hash)?;
+        let height = blockchain::Schema::new(context.fork()).height().next();
+        let mut schema = Schema::new(context.fork());
 
-            // Proof (in a sense) that tx was successful.
-            blockchain
-                .transaction_results()
-                .get(&self.tx_hash)
-                .ok_or(Error::TransactionDoesNotExist)?
-                .0
-                .map_err(|_err| Error::ReferredTransactionFailed)?;
-
-            let raw_tx = blockchain
-                .transactions()
-                .get(&self.tx_hash)
-                .ok_or(Error::TransactionDoesNotExist)?
-                .payload()
-                .clone();
-
-            let tx = WalletTransactions::tx_from_raw(raw_tx)
-                .map_err(|_err| Error::ReferredTransactionIsNotTransferMultisig)?;
-
-            match tx {
-                WalletTransactions::TransferMultisig(tx) => tx,
-                _ => return Err(Error::ReferredTransactionIsNotTransferMultisig.into()),
-            }
-        };
+        let approved_transfer = transfer_in_question
+            .approve_with_comment(approver, height, self.comment.clone())
+            .map_err(Error::from)?;
+        store_approved_transfer(
+            &mut schema,
+            self.tx_hash,
+            approved_transfer,
+            tx_hash,
+            height,
+        )
+    }
+}
 
-        let approver = context.author();
+/// Stores the multisignature transfer created by the transaction `transfer_hash` after
+/// it is approved by the transaction `tx_hash`. Once the transfer is done, the receiver
+/// is credited with the amount.
+fn store_approved_transfer(
+    schema: &mut Schema<&mut Fork>,
+    transfer_hash: Hash,
+    approved_transfer: MultisignatureTransfer,
+    tx_hash: Hash,
+    height: Height,
+) -> ExecutionResult {
+    if !approved_transfer.is_done() {
+        schema.update_transfer_multisig(transfer_hash, approved_transfer);
+        return Ok(());
+    }
+
+    let amount = approved_transfer.amount;
+    if let Some(wallet) = schema.wallet(&approved_transfer.to) {
+        let wallet = wallet.try_increase_balance(amount).map_err(Error::from)?;
+        schema.update_wallet(wallet, tx_hash, height);
+        schema.record_transfer(amount);
+        schema.record_wallet_transfer(&approved_transfer.from, &approved_transfer.to, amount);
+        schema.update_transfer_multisig(transfer_hash, approved_transfer);
+        return Ok(());
+    }
+
+    // The receiver no longer exists, so the approval succeeds, but cancels the transfer
+    // and returns the amount to the sender instead of leaving it stranded.
+    let sender = schema
+        .wallet(&approved_transfer.from)
+        .ok_or(Error::SenderNotFound)?;
+    let cancelled_transfer = approved_transfer
+        .cancel()
+        .expect("Approved transfer is done");
+    schema.update_transfer_multisig(transfer_hash, cancelled_transfer);
+    schema
+        .settle_multisig_refund(transfer_hash)
+        .map_err(|_err| Error::TransferIsCancelled)?;
+
+    let sender = sender.try_increase_balance(amount).map_err(Error::from)?;
+    schema.update_wallet(sender, tx_hash, height);
+
+    Ok(())
+}
+
+impl Transaction for ApproveTransferMultisigBatch {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        if self.approvals.is_empty() {
+            Err(Error::EmptyApprovalBatch)?;
+        }
+        if self.approvals.len() > Schema::new(context.fork()).config().max_approvers {
+            Err(Error::ApproversListIsTooLarge)?;
+        }
+
+        let tx_hash = context.tx_hash();
+        let mut transfer = referred_transfer(context.fork(), &self.tx_hash)?;
+        let height = blockchain::Schema::new(context.fork()).height().next();
+        let mut schema = Schema::new(context.fork());
+
+        let mut results = Vec::with_capacity(self.approvals.len());
+        for approval in &self.approvals {
+            // Settlement happens once: approvals after the transfer is done are skipped.
+            let outcome = if transfer.is_done() {
+                ApprovalOutcome::TransferDone
+            } else if !approval.verify(&self.tx_hash) {
+                ApprovalOutcome::InvalidSignature
+            } else {
+                match transfer.clone().approve(approval.approver, height) {
+                    Ok(approved) => {
+                        transfer = approved;
+                        ApprovalOutcome::Applied
+                    }
+                    Err(MultisigError::NotOnApproversList) => ApprovalOutcome::NotOnApproversList,
+                    Err(MultisigError::AlreadyApproved) => ApprovalOutcome::Duplicate,
+                    // The rest of the errors concern the transfer, so they affect
+                    // all the approvals alike.
+                    Err(error) => Err(Error::from(error))?,
+                }
+            };
+            results.push(BatchApprovalResult {
+                approver: approval.approver,
+                outcome,
+            });
+        }
+
+        if results
+            .iter()
+            .all(|result| result.outcome != ApprovalOutcome::Applied)
+        {
+            // None of the approvals is done by the preceding ones, so the first approval
+            // has failed by itself.
+            let error = match results[0].outcome {
+                ApprovalOutcome::InvalidSignature => Error::InvalidApprovalSignature,
+                ApprovalOutcome::NotOnApproversList => Error::ApproverIsNotOnApproversList,
+                _ => Error::TransferIsAlreadyApproved,
+            };
+            return Err(batch_approval_error(0, error));
+        }
+
+        schema.record_approval_batch(&tx_hash, results);
+        store_approved_transfer(&mut schema, self.tx_hash, transfer, tx_hash, height)
+    }
+}
+
+impl Transaction for RejectTransferMultisig {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        if self.comment.len() > Schema::new(context.fork()).config().max_comment_len {
+            Err(Error::CommentTooLong)?;
+        }
+
+        let rejecter = context.author();
         let tx_hash = context.tx_hash();
+        let transfer_in_question = referred_transfer(context.fork(), &self.tx_hash)?;
+        let height = blockchain::Schema::new(context.fork()).height().next();
+        let mut schema = Schema::new(context.fork());
+
+        let sender = schema
+            .wallet(&transfer_in_question.from)
+            .ok_or(Error::SenderNotFound)?;
+
+        let rejected_transfer = transfer_in_question
+            .reject_with_comment(rejecter, height, self.comment.clone())
+            .map_err(Error::from)?;
+        let amount = rejected_transfer.amount;
+
+        schema.update_transfer_multisig(self.tx_hash, rejected_transfer);
+        schema
+            .settle_multisig_refund(self.tx_hash)
+            .map_err(|_err| Error::TransferIsRejected)?;
+
+        let sender = sender.try_increase_balance(amount).map_err(Error::from)?;
+        schema.update_wallet(sender, tx_hash, height);
+
+        Ok(())
+    }
+}
+
+impl Transaction for CreateJointWallet {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
+
+        let joint = JointWallet::new(&self.owners, self.threshold);
+        if !joint.is_owner(&author) {
+            Err(Error::NotJointWalletOwner)?;
+        }
+        if !joint.is_threshold_reachable() {
+            Err(Error::JointThresholdUnreachable)?;
+        }
+        if joint.owners.len() > schema.config().max_approvers {
+            Err(Error::ApproversListIsTooLarge)?;
+        }
+        if schema.wallet(&joint.address()).is_some() {
+            Err(Error::WalletAlreadyExists)?;
+        }
+
+        let wallet = schema.create_joint_wallet(joint, &self.name, &hash, height);
+        debug!(
+            "Created joint wallet {:?} at height {}",
+            wallet.pub_key, height
+        );
+        Ok(())
+    }
+}
+
+/// Converts the error of signing a joint spend, which is a multisignature transfer
+/// between the owners of the wallet.
+fn joint_spend_error(error: MultisigError) -> Error {
+    match error {
+        MultisigError::NotOnApproversList => Error::NotJointWalletOwner,
+        MultisigError::AlreadyApproved => Error::JointSpendAlreadyApproved,
+        other => Error::from(other),
+    }
+}
+
+/// Executes the spend from the joint wallet signed by the threshold number of owners
+/// and stores the settled proposal.
+fn execute_joint_spend(
+    schema: &mut Schema<&mut Fork>,
+    proposal: Hash,
+    spend: MultisignatureTransfer,
+    tx_hash: Hash,
+    height: Height,
+) -> ExecutionResult {
+    debug_assert!(spend.is_done());
+    let (from, to, amount) = (spend.from, spend.to, spend.amount);
+    let sender = schema.wallet(&from).ok_or(Error::JointWalletNotFound)?;
+    let receiver = schema.wallet(&to).ok_or(Error::ReceiverNotFound)?;
+
+    let sender = sender.try_decrease_balance(amount).map_err(Error::from)?;
+    let receiver = receiver.try_increase_balance(amount).map_err(Error::from)?;
+
+    schema.update_wallets_batch(vec![(sender, tx_hash), (receiver, tx_hash)], height);
+    schema.record_transfer(amount);
+    schema.record_wallet_transfer(&from, &to, amount);
+    schema.update_joint_spend(proposal, spend);
+    Ok(())
+}
+
+impl Transaction for ProposeJointSpend {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let proposer = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
         let mut schema = Schema::new(context.fork());
 
+        let joint = schema
+            .joint_wallet(&self.wallet)
+            .ok_or(Error::JointWalletNotFound)?;
+        if !joint.is_owner(&proposer) {
+            Err(Error::NotJointWalletOwner)?;
+        }
+        if self.wallet == self.to {
+            Err(Error::SenderSameAsReceiver)?;
+        }
+
+        schema.config().check_amount(self.amount)?;
         let wallet = schema
-            .wallet(&original_transfer.to)
-            // Highly unlikely (read as impossible) scenario but...
-            .ok_or(Error::ReceiverNotFound)?;
+            .wallet(&self.wallet)
+            .ok_or(Error::JointWalletNotFound)?;
+        schema.wallet(&self.to).ok_or(Error::ReceiverNotFound)?;
+        // The balance is checked again once the spend is signed by enough owners.
+        wallet
+            .try_decrease_balance(self.amount)
+            .map_err(Error::from)?;
+
+        let spend = joint
+            .propose(proposer, self.to, self.amount, height)
+            .map_err(joint_spend_error)?;
+        if spend.is_done() {
+            execute_joint_spend(&mut schema, hash, spend, hash, height)
+        } else {
+            schema.update_joint_spend(hash, spend);
+            Ok(())
+        }
+    }
+}
 
-        let transfer_in_question = schema
-            .multisig_transfer(self.tx_hash)
-            .ok_or(Error::TransactionDoesNotExist)?;
+impl Transaction for ApproveJointSpend {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let owner = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
 
-        if transfer_in_question.is_rejected() {
-            return Err(Error::TransferIsRejected.into());
+        let spend = schema
+            .joint_spend(&self.proposal)
+            .ok_or(Error::TransactionDoesNotExist)?
+            .approve(owner, height)
+            .map_err(joint_spend_error)?;
+        if spend.is_done() {
+            execute_joint_spend(&mut schema, self.proposal, spend, hash, height)
+        } else {
+            schema.update_joint_spend(self.proposal, spend);
+            Ok(())
         }
+    }
+}
 
-        let approved_transfer = transfer_in_question
-            .approve(approver, &original_transfer.approvers)
-            .map_err(|_err| Error::ApproverIsNotOnApproversList)?;
+/// Net position of a wallet taking part in a clearing.
+struct ClearingPosition {
+    wallet: Wallet,
+    credit: Amount,
+    debit: Amount,
+    /// Index of the first leg debiting the wallet.
+    first_debit: Option<usize>,
+}
+
+impl Transaction for Clearing {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let operator = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
 
-        if approved_transfer.is_done() {
-            let wallet = wallet.increase_balance(original_transfer.amount);
-            schema.update_wallet(wallet, tx_hash);
+        let config = schema.config();
+        if config.clearing_operator != Some(operator) {
+            Err(Error::UnauthorizedClearingOperator)?;
+        }
+        if self.legs.len() > config.max_clearing_legs {
+            Err(Error::TooManyClearingLegs)?;
+        }
+
+        // Positions of the wallets in the order of their first appearance in the legs.
+        let mut positions: Vec<ClearingPosition> = Vec::new();
+        let mut indices = HashMap::new();
+        for (index, leg) in self.legs.iter().enumerate() {
+            if leg.from == leg.to {
+                return Err(clearing_leg_error(index, Error::SenderSameAsReceiver));
+            }
+            config
+                .check_amount(leg.amount)
+                .map_err(|err| clearing_leg_error(index, err))?;
+            for &(key, missing) in &[
+                (leg.from, Error::SenderNotFound),
+                (leg.to, Error::ReceiverNotFound),
+            ] {
+                if !indices.contains_key(&key) {
+                    let wallet = schema
+                        .wallet(&key)
+                        .ok_or_else(|| clearing_leg_error(index, missing))?;
+                    indices.insert(key, positions.len());
+                    positions.push(ClearingPosition {
+                        wallet,
+                        credit: Amount::ZERO,
+                        debit: Amount::ZERO,
+                        first_debit: None,
+                    });
+                }
+            }
+
+            let sender = &mut positions[indices[&leg.from]];
+            sender.debit = sender.debit.saturating_add(leg.amount);
+            sender.first_debit.get_or_insert(index);
+            let receiver = &mut positions[indices[&leg.to]];
+            receiver.credit = receiver.credit.saturating_add(leg.amount);
+        }
+
+        // Only the spendable amounts after netting all the legs must cover the debits. Sums
+        // exceeding `Amount::MAX` are never covered.
+        let overdrawn = positions
+            .iter()
+            .filter(|position| {
+                position.wallet.spendable().saturating_add(position.credit) < position.debit
+            })
+            .filter_map(|position| position.first_debit)
+            .min();
+        if let Some(index) = overdrawn {
+            return Err(clearing_leg_error(index, Error::InsufficientCurrencyAmount));
         }
 
-        schema.update_transfer_multisig(self.tx_hash, approved_transfer);
+        let mut updates = Vec::with_capacity(positions.len());
+        for position in positions {
+            let wallet = position
+                .wallet
+                .try_increase_balance(position.credit)
+                .and_then(|wallet| wallet.try_decrease_balance(position.debit))
+                .map_err(Error::from)?;
+            updates.push((wallet, hash));
+        }
+        // Every wallet is updated once, so its history records the clearing once
+        // however many legs it takes part in.
+        schema.update_wallets_batch(updates, height);
+        for leg in &self.legs {
+            schema.record_transfer(leg.amount);
+            schema.record_wallet_transfer(&leg.from, &leg.to, leg.amount);
+        }
 
         Ok(())
     }
 }
 
-impl Transaction for RejectTransferMultisig {
+impl Transaction for OpenStream {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
-        use exonum::blockchain::TransactionSet;
+        let from = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
-        let (original_transfer, original_author) = {
-            let blockchain = blockchain::Schema::new(context.fork());
+        let mut schema = Schema::new(context.fork());
 
-            // Proof (in a sense) that tx was successful.
-            blockchain
-                .transaction_results()
-                .get(&self.tx_hash)
-                .ok_or(Error::TransactionDoesNotExist)?
-                .0
-                .map_err(|_err| Error::ReferredTransactionFailed)?;
+        if from == self.to {
+            Err(Error::SenderSameAsReceiver)?;
+        }
+        let config = schema.config();
+        config.check_amount(self.rate_per_block)?;
+        config.check_amount(self.max_total)?;
+        if self.rate_per_block.is_zero() {
+            Err(Error::ZeroStreamRate)?;
+        }
+
+        let sender = schema.wallet(&from).ok_or(Error::SenderNotFound)?;
+        schema.wallet(&self.to).ok_or(Error::ReceiverNotFound)?;
+        let sender = sender
+            .try_decrease_balance(self.max_total)
+            .map_err(Error::from)?;
+
+        schema.update_wallet(sender, hash, height);
+        schema.open_payment_stream(
+            hash,
+            PaymentStream::new(from, self.to, self.rate_per_block, self.max_total, height),
+        );
+        Ok(())
+    }
+}
 
-            let signed = blockchain
-                .transactions()
-                .get(&self.tx_hash)
-                .ok_or(Error::TransactionDoesNotExist)?;
+impl Transaction for CloseStream {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
-            let raw_tx = signed.payload().clone();
+        let mut schema = Schema::new(context.fork());
 
-            let tx = WalletTransactions::tx_from_raw(raw_tx)
-                .map_err(|_err| Error::ReferredTransactionIsNotTransferMultisig)?;
+        let stream = schema
+            .payment_stream(&self.stream)
+            .ok_or(Error::TransactionDoesNotExist)?;
+        if !stream.is_party(&author) {
+            Err(Error::NotStreamParty)?;
+        }
+        if stream.is_closed() {
+            Err(Error::StreamAlreadyClosed)?;
+        }
 
-            match tx {
-                WalletTransactions::TransferMultisig(tx) => (tx, signed.author()),
-                _ => return Err(Error::ReferredTransactionIsNotTransferMultisig.into()),
-            }
+        schema.close_payment_stream(self.stream, height, hash);
+        Ok(())
+    }
+}
+
+impl Transaction for Stake {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
+
+        schema.config().check_amount(self.amount)?;
+        let wallet = schema.wallet(&author).ok_or(Error::SenderNotFound)?;
+        let wallet = wallet.try_stake(self.amount).map_err(Error::from)?;
+
+        schema.update_stake(wallet, hash, height);
+        Ok(())
+    }
+}
+
+impl Transaction for Unstake {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
+
+        schema.config().check_amount(self.amount)?;
+        let wallet = schema.wallet(&author).ok_or(Error::SenderNotFound)?;
+        // The stake is a part of the total supply, so the balance cannot overflow.
+        let wallet = wallet
+            .try_unstake(self.amount)
+            .map_err(|_| Error::InsufficientStake)?;
+
+        schema.update_stake(wallet, hash, height);
+        Ok(())
+    }
+}
+
+impl Transaction for ClaimRewards {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
+
+        schema.wallet(&author).ok_or(Error::SenderNotFound)?;
+        let supply_cap = schema.config().staking_supply_cap.unwrap_or(Amount::MAX);
+        let rewards = schema.claim_staking_rewards(&author, supply_cap, hash, height);
+        if rewards.is_zero() {
+            Err(Error::NoStakingRewards)?;
+        }
+        Ok(())
+    }
+}
+
+/// Maximum cashback in basis points: the whole incoming amount.
+pub const MAX_CASHBACK_BASIS_POINTS: u32 = 10_000;
+
+impl Transaction for SetCashback {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
+
+        let mut schema = Schema::new(context.fork());
+
+        if self.basis_points > MAX_CASHBACK_BASIS_POINTS {
+            Err(Error::CashbackTooLarge)?;
+        }
+        let wallet = schema.wallet(&author).ok_or(Error::SenderNotFound)?;
+
+        // The balance does not change, so the wallet history is not appended to.
+        let wallet = Wallet {
+            cashback_basis_points: self.basis_points,
+            ..wallet.touch_by(hash, height)
         };
+        schema.wallets_mut().put(&author, wallet);
+        schema.record_touch(&hash, &author);
+        Ok(())
+    }
+}
+
+impl Transaction for RenameWallet {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
-        let rejecter = context.author();
-        let tx_hash = context.tx_hash();
         let mut schema = Schema::new(context.fork());
 
-        let sender = schema
-            .wallet(&original_author)
-            .ok_or(Error::SenderNotFound)?;
+        let wallet = schema.wallet(&author).ok_or(Error::SenderNotFound)?;
+        // The current name of the wallet counts as taken as well, so that every change
+        // recorded in the name history actually changes the name.
+        if schema.wallet_names().contains(&wallet_name_key(&self.name)) {
+            Err(Error::WalletNameTaken)?;
+        }
 
-        let transfer_in_question = schema
-            .multisig_transfer(self.tx_hash)
-            .ok_or(Error::TransactionDoesNotExist)?;
+        schema.rename_wallet(wallet, &self.name, &hash, height);
+        Ok(())
+    }
+}
 
-        let rejected_transfer = transfer_in_question
-            .reject(rejecter, &original_transfer.approvers)
-            .map_err(|_err| Error::ApproverIsNotOnApproversList)?;
+impl Transaction for SetOverdraft {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let admin = context.author();
+        let hash = context.tx_hash();
+        let height = blockchain::Schema::new(context.fork()).height().next();
 
-        let sender = sender.increase_balance(original_transfer.amount);
-        schema.update_wallet(sender, tx_hash);
+        let mut schema = Schema::new(context.fork());
 
-        schema.update_transfer_multisig(self.tx_hash, rejected_transfer);
+        let config = schema.config();
+        if config.overdraft_admin != Some(admin) {
+            Err(Error::UnauthorizedOverdraftAdmin)?;
+        }
+        config.check_amount(self.limit)?;
+        let wallet = schema.wallet(&self.wallet).ok_or(Error::ReceiverNotFound)?;
+        if self.limit < wallet.owed {
+            Err(Error::OverdraftBelowDebt)?;
+        }
 
+        // The balance does not change, so the wallet history is not appended to.
+        let wallet = Wallet {
+            overdraft_limit: self.limit,
+            ..wallet.touch_by(hash, height)
+        };
+        schema.wallets_mut().put(&self.wallet, wallet);
+        schema.record_touch(&hash, &self.wallet);
         Ok(())
     }
 }
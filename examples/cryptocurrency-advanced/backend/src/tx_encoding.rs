@@ -0,0 +1,79 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hex encoding of signed transactions of the service, as accepted by the
+//! `v1/transactions` endpoints and the explorer.
+
+use exonum::{
+    blockchain::TransactionSet,
+    messages::{self, Message, RawTransaction, Service as ServiceMessage, Signed},
+};
+
+use crate::{transactions::WalletTransactions, CRYPTOCURRENCY_SERVICE_ID};
+
+/// Error of parsing a hex-encoded signed transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum ParseError {
+    /// The string is not valid hex.
+    #[fail(display = "Transaction is not valid hex: {}", _0)]
+    Hex(String),
+    /// The bytes are not a signed transaction, e.g., the message is truncated,
+    /// is of another type or its signature is invalid.
+    #[fail(display = "Malformed message: {}", _0)]
+    Message(String),
+    /// The transaction belongs to another service.
+    #[fail(display = "Transaction belongs to service {}", _0)]
+    WrongServiceId(u16),
+    /// The service has no transaction type with the message identifier.
+    #[fail(display = "Unknown message id {}", _0)]
+    UnknownMessageId(u16),
+    /// The payload cannot be decoded as the transaction of its type.
+    #[fail(display = "Cannot decode transaction payload: {}", _0)]
+    Payload(String),
+}
+
+/// Parses a hex-encoded signed transaction of the service. The signature of the message
+/// is verified while parsing.
+pub fn parse_signed_hex(
+    tx_body: &str,
+) -> Result<(Signed<RawTransaction>, WalletTransactions), ParseError> {
+    let buffer = hex::decode(tx_body).map_err(|e| ParseError::Hex(e.to_string()))?;
+    let signed = match Message::from_raw_buffer(buffer) {
+        Ok(Message::Service(ServiceMessage::RawTransaction(signed))) => signed,
+        Ok(_) => {
+            return Err(ParseError::Message(
+                "Message is not a transaction".to_owned(),
+            ))
+        }
+        Err(e) => return Err(ParseError::Message(e.to_string())),
+    };
+
+    let raw_tx = signed.payload().clone();
+    let service_id = raw_tx.service_id();
+    if service_id != CRYPTOCURRENCY_SERVICE_ID {
+        return Err(ParseError::WrongServiceId(service_id));
+    }
+    let message_id = raw_tx.clone().service_transaction().into_raw_parts().0;
+    if message_id >= WalletTransactions::MESSAGE_COUNT {
+        return Err(ParseError::UnknownMessageId(message_id));
+    }
+    let tx =
+        WalletTransactions::tx_from_raw(raw_tx).map_err(|e| ParseError::Payload(e.to_string()))?;
+    Ok((signed, tx))
+}
+
+/// Encodes a signed transaction as hex, the reverse of `parse_signed_hex`.
+pub fn to_hex(tx: &Signed<RawTransaction>) -> String {
+    messages::to_hex_string(tx)
+}
@@ -0,0 +1,99 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transactions prepared for external signers.
+//!
+//! The `sign` constructors of the transactions need the secret key in the process.
+//! With an external signer, e.g., an HSM exposing only the raw ed25519 signing,
+//! a transaction is built with its `unsigned` constructor instead, the bytes returned
+//! by `UnsignedTx::bytes_to_sign` are signed outside, and the signature is attached
+//! with `UnsignedTx::with_signature`.
+
+use exonum::{
+    crypto::{PublicKey, Signature},
+    messages::{
+        BinaryForm, Message, ProtocolMessage, RawTransaction, Service as ServiceMessage,
+        ServiceTransaction, Signed,
+    },
+};
+
+use crate::CRYPTOCURRENCY_SERVICE_ID;
+
+/// Error returned when the signature does not match the transaction and its author.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+#[fail(display = "Signature does not match the transaction and its author")]
+pub struct InvalidSignature;
+
+/// Transaction of the cryptocurrency service which is not signed yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsignedTx {
+    raw: RawTransaction,
+}
+
+impl UnsignedTx {
+    /// Creates an unsigned transaction of the service.
+    pub fn new<T: Into<ServiceTransaction>>(transaction: T) -> Self {
+        UnsignedTx {
+            raw: RawTransaction::new(CRYPTOCURRENCY_SERVICE_ID, transaction.into()),
+        }
+    }
+
+    /// Returns the identifier of the service.
+    pub fn service_id(&self) -> u16 {
+        self.raw.service_id()
+    }
+
+    /// Returns the identifier of the transaction type within the service.
+    pub fn message_id(&self) -> u16 {
+        self.raw.clone().service_transaction().into_raw_parts().0
+    }
+
+    /// Returns the serialized transaction body.
+    pub fn payload(&self) -> Vec<u8> {
+        self.raw.clone().service_transaction().into_raw_parts().1
+    }
+
+    /// Returns the exact bytes the author must sign: the public key of the author,
+    /// the class and the type of the message, the service identifier, the transaction
+    /// identifier and the payload.
+    pub fn bytes_to_sign(&self, author: &PublicKey) -> Vec<u8> {
+        let (class, tag) = RawTransaction::message_type();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(author.as_ref());
+        bytes.push(class);
+        bytes.push(tag);
+        bytes.extend_from_slice(
+            &self
+                .raw
+                .encode()
+                .expect("Raw transaction is always serializable"),
+        );
+        bytes
+    }
+
+    /// Assembles the signed transaction from the signature of `bytes_to_sign(&author)`.
+    /// Fails if the signature is not valid.
+    pub fn with_signature(
+        &self,
+        author: PublicKey,
+        signature: &Signature,
+    ) -> Result<Signed<RawTransaction>, InvalidSignature> {
+        let mut buffer = self.bytes_to_sign(&author);
+        buffer.extend_from_slice(signature.as_ref());
+        match Message::from_raw_buffer(buffer) {
+            Ok(Message::Service(ServiceMessage::RawTransaction(signed))) => Ok(signed),
+            _ => Err(InvalidSignature),
+        }
+    }
+}
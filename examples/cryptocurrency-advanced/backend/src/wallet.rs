@@ -14,34 +14,140 @@
 
 //! Cryptocurrency wallet.
 
-use exonum::crypto::{Hash, PublicKey};
+use exonum::{
+    crypto::{Hash, HashStream, PublicKey},
+    helpers::Height,
+    proto::ProtobufConvert,
+    storage::proof_list_index::{ListProof, ListProofError},
+};
+
+use std::cmp;
 
 use super::proto;
+use crate::amount::Amount;
+
+/// Number of records in a chunk of the wallet history.
+pub const HISTORY_CHUNK_SIZE: u64 = 128;
+
+/// Returns the number of chunks occupied by the history of the given length.
+pub fn history_chunk_count(history_len: u64) -> u64 {
+    (history_len + HISTORY_CHUNK_SIZE - 1) / HISTORY_CHUNK_SIZE
+}
+
+/// Chains the root of the next filled chunk of the history to the chain of the roots
+/// of the previous chunks.
+pub fn chain_history_chunk(history_chain: Hash, chunk_root: &Hash) -> Hash {
+    HashStream::new()
+        .update(history_chain.as_ref())
+        .update(chunk_root.as_ref())
+        .hash()
+}
+
+/// Returns the chain of the given chunk roots, as stored in `history_chain` of the wallet
+/// if the roots are those of its filled chunks. The chain of no roots is filled with zeros.
+pub fn history_chain<'a, I>(chunk_roots: I) -> Hash
+where
+    I: IntoIterator<Item = &'a Hash>,
+{
+    chunk_roots
+        .into_iter()
+        .fold(Hash::zero(), chain_history_chunk)
+}
+
+/// Maximum total size in bytes of the keys and values of the metadata of a wallet.
+pub const MAX_METADATA_SIZE: usize = 1_024;
+
+/// Error returned when the metadata of a wallet would exceed `MAX_METADATA_SIZE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+#[fail(display = "Wallet metadata is too large ({} bytes)", size)]
+pub struct MetadataTooLarge {
+    /// Size of the metadata after the rejected change.
+    pub size: usize,
+}
+
+/// Error returned by the checked balance updates of a wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+pub enum BalanceError {
+    /// The balance would exceed `Amount::MAX`.
+    #[fail(display = "Wallet balance overflow")]
+    Overflow,
+    /// The balance together with the overdraft is less than the withdrawn amount.
+    #[fail(display = "Insufficient wallet balance")]
+    Insufficient,
+}
 
 /// Wallet information stored in the database.
-#[derive(Clone, Debug, ProtobufConvert)]
-#[exonum(pb = "proto::Wallet", serde_pb_convert)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Wallet {
     /// `PublicKey` of the wallet.
     pub pub_key: PublicKey,
     /// Name of the wallet.
     pub name: String,
     /// Current balance of the wallet.
-    pub balance: u64,
-    /// Length of the transactions history.
+    pub balance: Amount,
+    /// Length of the transactions history, including pruned records.
     pub history_len: u64,
-    /// `Hash` of the transactions history.
+    /// `Hash` of the transactions history: the root hash of the list of the history
+    /// chunk roots.
     pub history_hash: Hash,
+    /// Chain of the roots of the filled history chunks, i.e., of the chunks before
+    /// `current_history_chunk`: each root is hashed together with the chain of
+    /// the previous ones. A record in a filled chunk can thus be verified with the proof
+    /// within its chunk and the roots of the filled chunks only.
+    pub history_chain: Hash,
+    /// Height of the block at which the wallet was created. Wallets created
+    /// before this field was introduced report `Height(0)`.
+    pub created_at: Height,
+    /// Height of the block which last changed the wallet.
+    pub last_active_at: Height,
+    /// Hash of the latest transaction which changed the wallet: the latest one
+    /// in the wallet history or a later one changing the wallet settings, such as
+    /// `RenameWallet`. Filled with zeros for an empty history.
+    pub last_tx_hash: Hash,
+    /// Hash of the sorted entries of the wallet metadata. Filled with zeros
+    /// if the wallet has no metadata.
+    pub metadata_hash: Hash,
+    /// Wallet which paid the initial balance of the wallet created by `CreateWalletFor`.
+    /// `None` for the other wallets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sponsor: Option<PublicKey>,
+    /// Amount moved from the balance to the stake of the wallet by `Stake`. It is not
+    /// spendable until it is moved back by `Unstake`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub staked: Amount,
+    /// Amount up to which the wallet may spend more than its balance. Set by `SetOverdraft`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub overdraft_limit: Amount,
+    /// Amount spent in excess of the balance, up to `overdraft_limit`. The balance is zero
+    /// while the wallet owes anything, since the incoming funds repay the debt first.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub owed: Amount,
+    /// Share of every incoming `Transfer` returned to its sender, in basis points.
+    /// Set by `SetCashback`.
+    #[serde(default, skip_serializing_if = "is_no_cashback")]
+    pub cashback_basis_points: u32,
+}
+
+fn is_zero(amount: &Amount) -> bool {
+    amount.is_zero()
+}
+
+fn is_no_cashback(basis_points: &u32) -> bool {
+    *basis_points == 0
 }
 
 impl Wallet {
-    /// Create new Wallet.
+    /// Create new Wallet without metadata.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
     pub fn new(
         &pub_key: &PublicKey,
         name: &str,
-        balance: u64,
+        balance: Amount,
         history_len: u64,
         &history_hash: &Hash,
+        &last_tx_hash: &Hash,
+        created_at: Height,
+        last_active_at: Height,
     ) -> Self {
         Self {
             pub_key,
@@ -49,34 +155,513 @@ impl Wallet {
             balance,
             history_len,
             history_hash,
+            history_chain: Hash::zero(),
+            created_at,
+            last_active_at,
+            last_tx_hash,
+            metadata_hash: Hash::zero(),
+            sponsor: None,
+            staked: Amount::ZERO,
+            overdraft_limit: Amount::ZERO,
+            owed: Amount::ZERO,
+            cashback_basis_points: 0,
         }
     }
 
+    /// Returns the cashback of the wallet on the incoming `amount`, rounded down
+    /// to the given number of decimals.
+    pub fn cashback(&self, amount: Amount, decimals: u8) -> Amount {
+        // The cashback does not exceed the amount, so the multiplication cannot overflow.
+        amount
+            .checked_mul_fraction(Amount::basis_points(self.cashback_basis_points))
+            .unwrap_or(Amount::ZERO)
+            .round_down(decimals)
+    }
+
+    /// Returns the amount the wallet can spend: the balance together with the unused
+    /// part of the overdraft limit.
+    pub fn spendable(&self) -> Amount {
+        self.balance
+            .saturating_add(self.overdraft_limit.saturating_sub(self.owed))
+    }
+
     /// Increase balance on wallet.
-    pub fn increase_balance(self, amount: u64) -> Self {
+    #[doc(hidden)]
+    #[deprecated(note = "use `try_increase_balance` instead")]
+    pub fn increase_balance(self, amount: Amount) -> Self {
         Self {
-            balance: self.balance + amount,
+            balance: self
+                .balance
+                .checked_add(amount)
+                .expect("Wallet balance overflow"),
 
             ..self
         }
     }
 
     /// Decrease balance on wallet.
-    pub fn decrease_balance(self, amount: u64) -> Self {
+    #[doc(hidden)]
+    #[deprecated(note = "use `try_decrease_balance` instead")]
+    pub fn decrease_balance(self, amount: Amount) -> Self {
+        Self {
+            balance: self
+                .balance
+                .checked_sub(amount)
+                .expect("Insufficient wallet balance"),
+
+            ..self
+        }
+    }
+
+    /// Increase balance on wallet. The amount repays the owed amount first, and only
+    /// the rest is added to the balance. Fails if the balance would overflow.
+    pub fn try_increase_balance(self, amount: Amount) -> Result<Self, BalanceError> {
+        let repaid = cmp::min(self.owed, amount);
+        let balance = self
+            .balance
+            .checked_add(amount.saturating_sub(repaid))
+            .ok_or(BalanceError::Overflow)?;
+        Ok(Self {
+            balance,
+            owed: self.owed.saturating_sub(repaid),
+            ..self
+        })
+    }
+
+    /// Decrease balance on wallet. The part of `amount` exceeding the balance is added
+    /// to the owed amount. Fails if the owed amount would exceed the overdraft limit.
+    pub fn try_decrease_balance(self, amount: Amount) -> Result<Self, BalanceError> {
+        let owed = self
+            .owed
+            .checked_add(amount.saturating_sub(self.balance))
+            .filter(|&owed| owed <= self.overdraft_limit)
+            .ok_or(BalanceError::Insufficient)?;
+        Ok(Self {
+            balance: self.balance.saturating_sub(amount),
+            owed,
+            ..self
+        })
+    }
+
+    /// Moves the amount from the balance to the stake. Fails if the balance is less
+    /// than `amount`; the overdraft cannot be staked.
+    pub fn try_stake(self, amount: Amount) -> Result<Self, BalanceError> {
+        let balance = self
+            .balance
+            .checked_sub(amount)
+            .ok_or(BalanceError::Insufficient)?;
+        let staked = self
+            .staked
+            .checked_add(amount)
+            .ok_or(BalanceError::Overflow)?;
+        Ok(Self {
+            balance,
+            staked,
+            ..self
+        })
+    }
+
+    /// Moves the amount from the stake back to the balance. Fails if the stake is less
+    /// than `amount`.
+    pub fn try_unstake(self, amount: Amount) -> Result<Self, BalanceError> {
+        let staked = self
+            .staked
+            .checked_sub(amount)
+            .ok_or(BalanceError::Insufficient)?;
+        let balance = self
+            .balance
+            .checked_add(amount)
+            .ok_or(BalanceError::Overflow)?;
+        Ok(Self {
+            balance,
+            staked,
+            ..self
+        })
+    }
+
+    /// Marks the wallet as changed by the block at the given height.
+    pub fn touch(self, height: Height) -> Self {
+        Self {
+            last_active_at: height,
+
+            ..self
+        }
+    }
+
+    /// Marks the wallet as changed by the transaction with the given hash executed
+    /// in the block at the given height, which is not recorded in the wallet history.
+    pub fn touch_by(self, tx_hash: Hash, height: Height) -> Self {
         Self {
-            balance: self.balance - amount,
+            last_tx_hash: tx_hash,
+            ..self.touch(height)
+        }
+    }
+
+    /// Update metadata hash on wallet.
+    pub fn update_metadata_hash(self, metadata_hash: Hash) -> Self {
+        Self {
+            metadata_hash,
 
             ..self
         }
     }
 
-    /// Update history hash on wallet.
-    pub fn update_history_hash(self, history_hash: Hash) -> Self {
+    /// Returns the index of the history chunk to which the next record is appended.
+    /// The roots of the chunks before it are chained in `history_chain`.
+    pub fn current_history_chunk(&self) -> u64 {
+        self.history_len / HISTORY_CHUNK_SIZE
+    }
+
+    /// Update history hash and chain on wallet after the transaction with the given hash
+    /// is appended to the history.
+    pub fn update_history_hash(
+        self,
+        history_hash: Hash,
+        history_chain: Hash,
+        tx_hash: Hash,
+    ) -> Self {
         Self {
             history_hash,
+            history_chain,
             history_len: self.history_len + 1,
+            last_tx_hash: tx_hash,
 
             ..self
         }
     }
 }
+
+impl ProtobufConvert for Wallet {
+    type ProtoStruct = proto::Wallet;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::Wallet::new();
+        pb.set_pub_key(self.pub_key.to_pb());
+        pb.set_name(self.name.clone());
+        let (balance, exact_balance) = self.balance.to_pb_fields();
+        pb.set_balance(balance);
+        if let Some(exact_balance) = exact_balance {
+            pb.set_exact_balance(exact_balance);
+        }
+        pb.set_history_len(self.history_len);
+        pb.set_history_hash(self.history_hash.to_pb());
+        pb.set_history_chain(self.history_chain.to_pb());
+        pb.set_created_at(self.created_at.to_pb());
+        pb.set_last_active_at(self.last_active_at.to_pb());
+        pb.set_last_tx_hash(self.last_tx_hash.to_pb());
+        pb.set_metadata_hash(self.metadata_hash.to_pb());
+        // The field is absent for the other wallets, so their hashes do not change.
+        if let Some(ref sponsor) = self.sponsor {
+            pb.set_sponsor(sponsor.to_pb());
+        }
+        let (staked, exact_staked) = self.staked.to_pb_fields();
+        pb.set_staked(staked);
+        if let Some(exact_staked) = exact_staked {
+            pb.set_exact_staked(exact_staked);
+        }
+        let (overdraft_limit, exact_overdraft_limit) = self.overdraft_limit.to_pb_fields();
+        pb.set_overdraft_limit(overdraft_limit);
+        if let Some(exact_overdraft_limit) = exact_overdraft_limit {
+            pb.set_exact_overdraft_limit(exact_overdraft_limit);
+        }
+        let (owed, exact_owed) = self.owed.to_pb_fields();
+        pb.set_owed(owed);
+        if let Some(exact_owed) = exact_owed {
+            pb.set_exact_owed(exact_owed);
+        }
+        pb.set_cashback_basis_points(self.cashback_basis_points);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            pub_key: PublicKey::from_pb(pb.get_pub_key().clone())?,
+            name: pb.get_name().to_owned(),
+            balance: Amount::from_pb_fields(pb.get_balance(), pb.get_exact_balance()),
+            history_len: pb.get_history_len(),
+            history_hash: Hash::from_pb(pb.get_history_hash().clone())?,
+            history_chain: Hash::from_pb(pb.get_history_chain().clone())?,
+            created_at: Height::from_pb(pb.get_created_at())?,
+            last_active_at: Height::from_pb(pb.get_last_active_at())?,
+            last_tx_hash: Hash::from_pb(pb.get_last_tx_hash().clone())?,
+            metadata_hash: Hash::from_pb(pb.get_metadata_hash().clone())?,
+            sponsor: if pb.has_sponsor() {
+                Some(PublicKey::from_pb(pb.get_sponsor().clone())?)
+            } else {
+                None
+            },
+            staked: Amount::from_pb_fields(pb.get_staked(), pb.get_exact_staked()),
+            overdraft_limit: Amount::from_pb_fields(
+                pb.get_overdraft_limit(),
+                pb.get_exact_overdraft_limit(),
+            ),
+            owed: Amount::from_pb_fields(pb.get_owed(), pb.get_exact_owed()),
+            cashback_basis_points: pb.get_cashback_basis_points(),
+        })
+    }
+}
+
+/// Lifetime transfer totals of a wallet. Both plain transfers and settled
+/// multisignature transfers are counted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WalletActivity {
+    /// Total amount of currency sent by the wallet.
+    pub sent: Amount,
+    /// Total amount of currency received by the wallet.
+    pub received: Amount,
+    /// Number of outgoing transfers.
+    pub outgoing_count: u64,
+    /// Number of incoming transfers.
+    pub incoming_count: u64,
+    /// Root hash of the per-counterparty transfer volumes.
+    pub counterparties_hash: Hash,
+}
+
+impl ProtobufConvert for WalletActivity {
+    type ProtoStruct = proto::WalletActivity;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::WalletActivity::new();
+        let (sent, exact_sent) = self.sent.to_pb_fields();
+        pb.set_sent(sent);
+        if let Some(exact_sent) = exact_sent {
+            pb.set_exact_sent(exact_sent);
+        }
+        let (received, exact_received) = self.received.to_pb_fields();
+        pb.set_received(received);
+        if let Some(exact_received) = exact_received {
+            pb.set_exact_received(exact_received);
+        }
+        pb.set_outgoing_count(self.outgoing_count);
+        pb.set_incoming_count(self.incoming_count);
+        pb.set_counterparties_hash(self.counterparties_hash.to_pb());
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            sent: Amount::from_pb_fields(pb.get_sent(), pb.get_exact_sent()),
+            received: Amount::from_pb_fields(pb.get_received(), pb.get_exact_received()),
+            outgoing_count: pb.get_outgoing_count(),
+            incoming_count: pb.get_incoming_count(),
+            counterparties_hash: Hash::from_pb(pb.get_counterparties_hash().clone())?,
+        })
+    }
+}
+
+/// Lifetime balance movement totals of a wallet. Unlike `WalletActivity`, issuance
+/// is counted as well. A multisignature transfer is counted once it is settled;
+/// withholding the amount from the sender and refunding a rejected transfer are not
+/// counted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WalletStats {
+    /// Number of movements which decreased the balance of the wallet.
+    pub sent_count: u64,
+    /// Number of movements which increased the balance of the wallet.
+    pub received_count: u64,
+    /// Total amount of currency moved out of the wallet.
+    pub sent_total: Amount,
+    /// Total amount of currency moved into the wallet.
+    pub received_total: Amount,
+}
+
+impl ProtobufConvert for WalletStats {
+    type ProtoStruct = proto::WalletStats;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::WalletStats::new();
+        pb.set_sent_count(self.sent_count);
+        pb.set_received_count(self.received_count);
+        let (sent_total, exact_sent_total) = self.sent_total.to_pb_fields();
+        pb.set_sent_total(sent_total);
+        if let Some(exact_sent_total) = exact_sent_total {
+            pb.set_exact_sent_total(exact_sent_total);
+        }
+        let (received_total, exact_received_total) = self.received_total.to_pb_fields();
+        pb.set_received_total(received_total);
+        if let Some(exact_received_total) = exact_received_total {
+            pb.set_exact_received_total(exact_received_total);
+        }
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            sent_count: pb.get_sent_count(),
+            received_count: pb.get_received_count(),
+            sent_total: Amount::from_pb_fields(pb.get_sent_total(), pb.get_exact_sent_total()),
+            received_total: Amount::from_pb_fields(
+                pb.get_received_total(),
+                pb.get_exact_received_total(),
+            ),
+        })
+    }
+}
+
+/// Balance of a wallet after the block at the given height together with the length
+/// of its history at that point.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceCheckpoint {
+    /// Height of the block.
+    pub height: Height,
+    /// Balance of the wallet after the block.
+    pub balance: Amount,
+    /// Length of the wallet history after the block.
+    pub history_len: u64,
+}
+
+impl ProtobufConvert for BalanceCheckpoint {
+    type ProtoStruct = proto::BalanceCheckpoint;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::BalanceCheckpoint::new();
+        pb.set_height(self.height.to_pb());
+        let (balance, exact_balance) = self.balance.to_pb_fields();
+        pb.set_balance(balance);
+        if let Some(exact_balance) = exact_balance {
+            pb.set_exact_balance(exact_balance);
+        }
+        pb.set_history_len(self.history_len);
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            height: Height::from_pb(pb.get_height())?,
+            balance: Amount::from_pb_fields(pb.get_balance(), pb.get_exact_balance()),
+            history_len: pb.get_history_len(),
+        })
+    }
+}
+
+/// Entry of the wallet history.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+    /// Height of the block containing the transaction.
+    pub height: Height,
+    /// Whether the transaction has increased the balance of the wallet.
+    pub incoming: bool,
+    /// Amount by which the transaction has changed the balance of the wallet.
+    pub amount: Amount,
+}
+
+impl ProtobufConvert for HistoryRecord {
+    type ProtoStruct = proto::HistoryRecord;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut pb = proto::HistoryRecord::new();
+        pb.set_tx_hash(self.tx_hash.to_pb());
+        pb.set_height(self.height.to_pb());
+        pb.set_incoming(self.incoming);
+        let (amount, exact_amount) = self.amount.to_pb_fields();
+        pb.set_amount(amount);
+        if let Some(exact_amount) = exact_amount {
+            pb.set_exact_amount(exact_amount);
+        }
+        pb
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        Ok(Self {
+            tx_hash: Hash::from_pb(pb.get_tx_hash().clone())?,
+            height: Height::from_pb(pb.get_height())?,
+            incoming: pb.get_incoming(),
+            amount: Amount::from_pb_fields(pb.get_amount(), pb.get_exact_amount()),
+        })
+    }
+}
+
+/// Change of the wallet name made by `RenameWallet`.
+#[derive(Clone, Debug, ProtobufConvert, PartialEq, Serialize, Deserialize)]
+#[exonum(pb = "proto::NameChange")]
+pub struct NameChange {
+    /// Height of the block containing the transaction.
+    pub height: Height,
+    /// Name of the wallet before the change.
+    pub old_name: String,
+    /// Name of the wallet after the change.
+    pub new_name: String,
+    /// Hash of the transaction.
+    pub tx_hash: Hash,
+}
+
+impl_pb_storage!(
+    Wallet,
+    WalletActivity,
+    WalletStats,
+    BalanceCheckpoint,
+    HistoryRecord
+);
+
+impl HistoryRecord {
+    /// Creates a record of the transaction which has changed the balance of the wallet
+    /// from `balance_before` to `balance_after`.
+    pub fn new(
+        tx_hash: Hash,
+        height: Height,
+        balance_before: Amount,
+        balance_after: Amount,
+    ) -> Self {
+        let incoming = balance_after >= balance_before;
+        let amount = if incoming {
+            balance_after.saturating_sub(balance_before)
+        } else {
+            balance_before.saturating_sub(balance_after)
+        };
+        Self {
+            tx_hash,
+            height,
+            incoming,
+            amount,
+        }
+    }
+
+    /// Returns the balance of the wallet after the transaction given the balance before it.
+    pub fn apply(&self, balance: Amount) -> Amount {
+        if self.incoming {
+            balance.saturating_add(self.amount)
+        } else {
+            balance.saturating_sub(self.amount)
+        }
+    }
+}
+
+/// Proof of a range of the wallet history records. The history is split into chunks
+/// of `HISTORY_CHUNK_SIZE` records; the proof consists of the proof of the roots of
+/// the chunks covering the range and the proofs of the range within each of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryProof {
+    /// Proof of the roots of the chunks covering the range.
+    pub chunk_roots: ListProof<Hash>,
+    /// Proofs of the range within each of the chunks in the order of the chunks.
+    pub chunks: Vec<ListProof<HistoryRecord>>,
+}
+
+impl HistoryProof {
+    /// Checks the proof against the `history_hash` and `history_len` of the wallet and
+    /// returns the proven records together with their indices in the history.
+    pub fn validate(
+        &self,
+        history_hash: Hash,
+        history_len: u64,
+    ) -> Result<Vec<(u64, HistoryRecord)>, ListProofError> {
+        let roots = self
+            .chunk_roots
+            .validate(history_hash, history_chunk_count(history_len))?;
+        if roots.len() != self.chunks.len() {
+            return Err(ListProofError::UnmatchedRootHash);
+        }
+
+        let mut records = Vec::new();
+        for ((chunk, root), proof) in roots.into_iter().zip(&self.chunks) {
+            let offset = chunk * HISTORY_CHUNK_SIZE;
+            let chunk_len = cmp::min(HISTORY_CHUNK_SIZE, history_len - offset);
+            for (index, record) in proof.validate(*root, chunk_len)? {
+                records.push((offset + index, record.clone()));
+            }
+        }
+        Ok(records)
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Identifiers of the wallets passed to the service API.
+//!
+//! A wallet may be identified either by its public key or by its name. A string which
+//! is a valid public key in any of the encodings accepted by `key_encoding` always
+//! identifies the wallet by the key, even if some wallet has this string as its name;
+//! such wallets can only be found by their keys. Other strings are names.
+
+use exonum::crypto::PublicKey;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use std::{fmt, str::FromStr};
+
+use crate::key_encoding;
+
+/// Public key or name of a wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WalletId {
+    /// Public key of the wallet.
+    Key(PublicKey),
+    /// Name of the wallet, resolved through the index of the wallet names.
+    Name(String),
+}
+
+/// Error of parsing an empty wallet identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+#[fail(display = "wallet identifier is empty")]
+pub struct EmptyWalletId;
+
+impl FromStr for WalletId {
+    type Err = EmptyWalletId;
+
+    /// Parses the public key first and falls back to the name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(EmptyWalletId);
+        }
+        Ok(key_encoding::parse_public_key(s)
+            .map(WalletId::Key)
+            .unwrap_or_else(|_| WalletId::Name(s.to_owned())))
+    }
+}
+
+impl fmt::Display for WalletId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalletId::Key(pub_key) => f.write_str(&hex::encode(pub_key.as_ref())),
+            WalletId::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl From<PublicKey> for WalletId {
+    fn from(pub_key: PublicKey) -> Self {
+        WalletId::Key(pub_key)
+    }
+}
+
+impl Serialize for WalletId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for WalletId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,451 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Node-local webhooks notifying backends of the changes of the wallets.
+//!
+//! Webhooks are registered through the private API of a single node and are not a part
+//! of the blockchain state; they can be persisted to a JSON file. After a block is
+//! committed, every webhook of a wallet touched by the block receives a POST request
+//! with a `WalletNotification`. Requests are sent by a separate thread, so delivery
+//! never delays or affects the commit. Failed requests are retried a bounded number
+//! of times; notifications which still cannot be delivered, or which do not fit
+//! into the queue, are logged and appended to the dead-letter log, if any, by
+//! another thread.
+
+use exonum::{
+    blockchain,
+    crypto::{Hash, PublicKey},
+    helpers::Height,
+    storage::Snapshot,
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    lock,
+    multisig_transfer::State,
+    schema::{parse_settled_multisig_key, settled_multisig_key},
+    Schema,
+};
+
+/// The maximum number of notifications waiting for delivery.
+pub const WEBHOOK_QUEUE_SIZE: usize = 1_024;
+/// Default number of attempts to deliver a notification.
+pub const WEBHOOK_MAX_ATTEMPTS: u32 = 3;
+/// Default delay before the second attempt to deliver a notification. Every next
+/// attempt waits as much longer.
+pub const WEBHOOK_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Timeout of a single delivery request.
+pub const WEBHOOK_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Callback URL registered for a wallet.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Webhook {
+    /// Public key of the wallet.
+    pub pub_key: PublicKey,
+    /// URL receiving the notifications. Only `http` and `https` URLs are accepted.
+    pub url: String,
+}
+
+impl Webhook {
+    /// Checks that the URL of the webhook can receive the notifications.
+    pub fn validate(&self) -> Result<(), String> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|err| format!("Invalid webhook URL {}: {}", self.url, err))?;
+        match url.scheme() {
+            "http" | "https" => Ok(()),
+            scheme => Err(format!("Unsupported webhook URL scheme {}", scheme)),
+        }
+    }
+}
+
+/// Body of the POST request sent to a webhook of a wallet touched by a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletNotification {
+    /// Height of the block.
+    pub height: Height,
+    /// Public key of the wallet.
+    pub pub_key: PublicKey,
+    /// Hashes of the transactions which have touched the wallet, in the order
+    /// of execution. Expired multisignature transfers are represented by the hashes
+    /// of the `TransferMultisig` transactions.
+    pub tx_hashes: Vec<Hash>,
+}
+
+/// Notification which could not be delivered, as written to the dead-letter log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// URL of the webhook.
+    pub url: String,
+    /// Undelivered notification.
+    pub notification: WalletNotification,
+    /// Number of the delivery attempts made.
+    pub attempts: u32,
+    /// Error of the last attempt.
+    pub error: String,
+}
+
+#[derive(Debug)]
+struct Delivery {
+    url: String,
+    notification: WalletNotification,
+}
+
+impl Delivery {
+    fn into_dead_letter(self, attempts: u32, error: String) -> DeadLetter {
+        DeadLetter {
+            url: self.url,
+            notification: self.notification,
+            attempts,
+            error,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RetryPolicy {
+    max_attempts: u32,
+    delay: Duration,
+}
+
+/// Queue of the thread delivering the notifications.
+#[derive(Debug)]
+struct DeliveryQueue {
+    deliveries: mpsc::SyncSender<Delivery>,
+    dead_letters: DeadLetterLog,
+}
+
+impl DeliveryQueue {
+    fn spawn(retry: RetryPolicy, dead_letters: Option<PathBuf>) -> Self {
+        let dead_letters = DeadLetterLog::spawn(dead_letters);
+        Self {
+            deliveries: spawn_delivery_thread(retry, dead_letters.clone()),
+            dead_letters,
+        }
+    }
+}
+
+/// Logs the undelivered notifications and passes them to the thread appending them
+/// to the dead-letter log, if any, so that neither the commit nor the deliveries wait
+/// for the file.
+#[derive(Debug, Clone)]
+struct DeadLetterLog(Option<mpsc::Sender<DeadLetter>>);
+
+impl DeadLetterLog {
+    fn spawn(path: Option<PathBuf>) -> Self {
+        DeadLetterLog(path.map(|path| {
+            let (sender, receiver) = mpsc::channel::<DeadLetter>();
+            thread::spawn(move || {
+                for letter in receiver {
+                    write_dead_letter(&path, &letter);
+                }
+            });
+            sender
+        }))
+    }
+
+    fn write(&self, letter: DeadLetter) {
+        warn!(
+            "Notification of block {} is not delivered to {}: {}",
+            letter.notification.height, letter.url, letter.error
+        );
+        if let Some(ref sender) = self.0 {
+            if sender.send(letter).is_err() {
+                error!("Dead-letter log thread has stopped");
+            }
+        }
+    }
+}
+
+/// Webhooks registered with the node. Clones share the same registrations and
+/// delivery thread.
+#[derive(Debug, Clone)]
+pub struct WebhookRegistry {
+    hooks: Arc<Mutex<BTreeSet<Webhook>>>,
+    storage: Option<PathBuf>,
+    dead_letters: Option<PathBuf>,
+    retry: RetryPolicy,
+    queue: Arc<Mutex<Option<DeliveryQueue>>>,
+}
+
+impl Default for WebhookRegistry {
+    fn default() -> Self {
+        Self {
+            hooks: Arc::default(),
+            storage: None,
+            dead_letters: None,
+            retry: RetryPolicy {
+                max_attempts: WEBHOOK_MAX_ATTEMPTS,
+                delay: WEBHOOK_RETRY_DELAY,
+            },
+            queue: Arc::default(),
+        }
+    }
+}
+
+impl WebhookRegistry {
+    /// Creates a registry keeping the webhooks in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Persists the webhooks to the JSON file at the given path. The webhooks stored
+    /// in the file are loaded; if the file cannot be read, the error is logged and
+    /// the registry starts empty.
+    pub fn with_storage<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        let path = path.into();
+        match load_webhooks(&path) {
+            Ok(hooks) => *lock(&self.hooks) = hooks,
+            Err(err) => error!("Cannot load webhooks from {}: {}", path.display(), err),
+        }
+        self.storage = Some(path);
+        self
+    }
+
+    /// Appends the notifications which could not be delivered to the file at the given
+    /// path, one `DeadLetter` JSON object per line.
+    pub fn with_dead_letter_log<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.dead_letters = Some(path.into());
+        self
+    }
+
+    /// Makes up to `max_attempts` attempts to deliver every notification, waiting
+    /// `delay` before the second attempt and as much longer before every next one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is zero.
+    pub fn with_retries(mut self, max_attempts: u32, delay: Duration) -> Self {
+        assert!(
+            max_attempts > 0,
+            "At least one delivery attempt is required"
+        );
+        self.retry = RetryPolicy {
+            max_attempts,
+            delay,
+        };
+        self
+    }
+
+    /// Registers the webhook. Returns `false` if it is already registered.
+    pub fn register(&self, webhook: Webhook) -> Result<bool, String> {
+        webhook.validate()?;
+        let mut hooks = lock(&self.hooks);
+        if !hooks.insert(webhook) {
+            return Ok(false);
+        }
+        self.store(&hooks);
+        Ok(true)
+    }
+
+    /// Removes the webhook. Returns `false` if it is not registered.
+    pub fn remove(&self, webhook: &Webhook) -> bool {
+        let mut hooks = lock(&self.hooks);
+        if !hooks.remove(webhook) {
+            return false;
+        }
+        self.store(&hooks);
+        true
+    }
+
+    /// Returns the registered webhooks ordered by the wallets.
+    pub fn webhooks(&self) -> Vec<Webhook> {
+        lock(&self.hooks).iter().cloned().collect()
+    }
+
+    /// Queues the notifications of the wallets touched by the block at the given height
+    /// for delivery to their webhooks.
+    pub fn notify_block(&self, snapshot: &dyn Snapshot, height: Height) {
+        let hooks = self.webhooks();
+        if hooks.is_empty() {
+            return;
+        }
+        let touched =
+            match panic::catch_unwind(AssertUnwindSafe(|| touched_wallets(snapshot, height))) {
+                Ok(touched) => touched,
+                Err(_) => {
+                    error!("Cannot collect wallets touched by block {}", height);
+                    return;
+                }
+            };
+
+        for webhook in hooks {
+            if let Some(tx_hashes) = touched.get(&webhook.pub_key) {
+                self.deliver(Delivery {
+                    url: webhook.url,
+                    notification: WalletNotification {
+                        height,
+                        pub_key: webhook.pub_key,
+                        tx_hashes: tx_hashes.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    /// Queues the notification. If the delivery thread has stopped, the notification
+    /// is dead-lettered and the thread is restarted with the next notification.
+    fn deliver(&self, delivery: Delivery) {
+        let mut queue = lock(&self.queue);
+        let current = queue.get_or_insert_with(|| {
+            DeliveryQueue::spawn(self.retry.clone(), self.dead_letters.clone())
+        });
+        match current.deliveries.try_send(delivery) {
+            Ok(()) => {}
+            Err(mpsc::TrySendError::Full(delivery)) => {
+                let letter = delivery.into_dead_letter(0, "Delivery queue is full".to_owned());
+                current.dead_letters.write(letter);
+            }
+            Err(mpsc::TrySendError::Disconnected(delivery)) => {
+                error!("Webhook delivery thread has stopped");
+                let letter = delivery.into_dead_letter(0, "Delivery thread has stopped".to_owned());
+                current.dead_letters.write(letter);
+                *queue = None;
+            }
+        }
+    }
+
+    fn store(&self, hooks: &BTreeSet<Webhook>) {
+        if let Some(ref path) = self.storage {
+            if let Err(err) = store_webhooks(path, hooks) {
+                error!("Cannot store webhooks to {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// Returns the wallets touched by the transactions of the block at the given height
+/// and by the multisignature transfers expired in it, together with the hashes
+/// of the touching transactions.
+pub fn touched_wallets(snapshot: &dyn Snapshot, height: Height) -> BTreeMap<PublicKey, Vec<Hash>> {
+    let general_schema = blockchain::Schema::new(snapshot);
+    let schema = Schema::new(snapshot);
+
+    let expired = schema
+        .settled_multisig_transfers()
+        .iter_from(&settled_multisig_key(height, &Hash::zero()))
+        .map(|key| parse_settled_multisig_key(&key))
+        .take_while(|(settled_at, _)| *settled_at == height)
+        .map(|(_, tx_hash)| tx_hash)
+        .filter(|tx_hash| {
+            schema
+                .multisig_transfer(*tx_hash)
+                .map_or(false, |transfer| transfer.state == State::Expired)
+        })
+        .collect::<Vec<_>>();
+
+    let mut touched = BTreeMap::new();
+    // Failed transactions and transactions of other services touch no wallets.
+    for tx_hash in general_schema
+        .block_transactions(height)
+        .iter()
+        .chain(expired)
+    {
+        for pub_key in schema.affected_wallets(&tx_hash).iter() {
+            touched
+                .entry(pub_key)
+                .or_insert_with(Vec::new)
+                .push(tx_hash);
+        }
+    }
+    touched
+}
+
+fn spawn_delivery_thread(
+    retry: RetryPolicy,
+    dead_letters: DeadLetterLog,
+) -> mpsc::SyncSender<Delivery> {
+    let (sender, receiver) = mpsc::sync_channel::<Delivery>(WEBHOOK_QUEUE_SIZE);
+    thread::spawn(move || {
+        let client = match reqwest::Client::builder()
+            .timeout(WEBHOOK_REQUEST_TIMEOUT)
+            .build()
+        {
+            Ok(client) => client,
+            Err(err) => {
+                error!("Cannot create webhook client: {}", err);
+                return;
+            }
+        };
+        for delivery in receiver {
+            let mut attempts = 0;
+            let result = loop {
+                attempts += 1;
+                match post(&client, &delivery) {
+                    Err(_) if attempts < retry.max_attempts => {
+                        thread::sleep(retry.delay * attempts);
+                    }
+                    result => break result,
+                }
+            };
+            if let Err(error) = result {
+                dead_letters.write(delivery.into_dead_letter(attempts, error));
+            }
+        }
+    });
+    sender
+}
+
+/// Sends the notification once. Responses with non-success statuses are failures.
+fn post(client: &reqwest::Client, delivery: &Delivery) -> Result<(), String> {
+    let response = client
+        .post(&delivery.url)
+        .json(&delivery.notification)
+        .send()
+        .map_err(|err| err.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("Response status {}", response.status()))
+    }
+}
+
+/// Appends the undelivered notification to the dead-letter log.
+fn write_dead_letter(path: &Path, letter: &DeadLetter) {
+    let result = serde_json::to_string(letter)
+        .map_err(io::Error::from)
+        .and_then(|line| {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", line)
+        });
+    if let Err(err) = result {
+        error!("Cannot write dead-letter log {}: {}", path.display(), err);
+    }
+}
+
+fn load_webhooks(path: &Path) -> io::Result<BTreeSet<Webhook>> {
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+    let hooks: Vec<Webhook> = serde_json::from_slice(&fs::read(path)?)?;
+    Ok(hooks.into_iter().collect())
+}
+
+/// Writes the webhooks to a temporary file first, so that a crash does not leave
+/// the storage half-written.
+fn store_webhooks(path: &Path, hooks: &BTreeSet<Webhook>) -> io::Result<()> {
+    let hooks = hooks.iter().collect::<Vec<_>>();
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(&hooks)?)?;
+    fs::rename(&temp_path, path)
+}
@@ -18,29 +18,85 @@
 //! Note how API tests predominantly use `TestKitApi` to send transactions and make assertions
 //! about the storage state.
 
+#[macro_use]
+extern crate assert_matches;
 #[macro_use]
 extern crate serde_json;
 
 use exonum::{
-    api::{
-        self,
-        node::public::explorer::{TransactionQuery, TransactionResponse},
+    api::{self, node::public::explorer::TransactionResponse},
+    blockchain::{self, TransactionSet},
+    crypto::{self, CryptoHash, Hash, PublicKey, SecretKey},
+    helpers::{
+        fabric::{self, Command, Context, ServiceFactory as _},
+        Height,
     },
-    crypto::{self, Hash, PublicKey, SecretKey},
-    helpers::Height,
-    messages::{self, RawTransaction, Signed},
+    messages::{self, Message, ServiceTransaction},
+    proto::ProtobufConvert,
+    storage::StorageValue,
 };
 use exonum_testkit::{ApiKind, TestKit, TestKitApi, TestKitBuilder};
+use futures::{Future, Stream};
+
+use std::{
+    cmp,
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
 
 // Import data types used in tests from the crate where the service is defined.
 use exonum_cryptocurrency_advanced::{
-    api::{SimpleTransactionInfo, SimpleWalletInfo, WalletInfo, WalletQuery},
+    amount::{Amount, AmountError},
+    api::{
+        ApiError, BalanceSum, BatchWalletEntry, CreatedWallet, DryRunResult, ErrorCodeInfo,
+        ErrorKind, ExecutionStatus, HistoryOrder, IssueQuery, MultisigFailure, MultisigTemplate,
+        PendingJointSpend, PendingOutgoing, PendingTransfer, ProofVerification, ReadContext,
+        SeedTransfer, ServiceStats, ServiceTransactionActivity, SimpleTransactionInfo,
+        SimpleWalletInfo, SimpleWalletInfoQuery, SubmittedTransaction, WalletBalance,
+        WalletHistory, WalletHistoryHead, WalletHistorySince, WalletInfo, WalletInfoQuery,
+        WalletQuery, WalletSummary, WebhookQuery, WebhookRegistered, WebhookRemoved,
+        MAX_ITEMS_PER_REQUEST, MAX_KEYS_PER_BALANCE_SUM, MAX_KEYS_PER_BATCH, MAX_LIST_ITEMS,
+        PROMETHEUS_CONTENT_TYPE, PROTOBUF_CONTENT_TYPE,
+    },
+    client::{
+        check_block_precommits, verify_wallet_proof, CryptocurrencyClient, ProofError, TxStatus,
+    },
+    config::{Config, ConfigError, MAX_COMMENT_LEN_LIMIT},
+    due::DueItem,
+    events::{block_events, EventEmitter, ServiceEvent},
+    joint_wallet::{joint_wallet_address, JointWallet},
+    key_encoding::{self, KeyEncoding, KeyParseError},
+    metrics::{BlockSummary, ServiceMetrics},
+    migration::SCHEMA_VERSION,
+    multisig_transfer::{
+        approval_digest, AlreadySettled, Approval, ApprovalOutcome, BatchApprovalResult,
+        MultisigError, MultisignatureTransfer, State,
+    },
+    proto,
+    schema::{metadata_hash, wallet_name_key},
+    staking::{StakePosition, StakingState, STAKING_REWARD_INTERVAL},
     transactions::{
-        ApproveTransferMultisig, CreateWallet, RejectTransferMultisig, Transfer, TransferMultisig,
-        MAX_APPROVERS,
+        ApproveJointSpend, ApproveTransferMultisig, ApproveTransferMultisigBatch, ClaimRewards,
+        Clearing, ClearingLeg, CloseStream, CreateJointWallet, CreateWallet, CreateWalletFor,
+        Error, Issue, IssueTo, OpenStream, ProposeJointSpend, RejectTransferMultisig, RenameWallet,
+        SetCashback, SetOverdraft, SignedApproval, Stake, Transfer, TransferMultisig, Unstake,
+        WalletTransactions, MAX_APPROVERS, MAX_CASHBACK_BASIS_POINTS, MAX_CLEARING_LEGS,
+        MAX_COMMENT_LEN,
     },
-    wallet::Wallet,
-    Service,
+    tx_encoding::{self, ParseError},
+    unsigned::{InvalidSignature, UnsignedTx},
+    wallet::{
+        history_chain, BalanceError, HistoryProof, HistoryRecord, NameChange, Wallet, WalletStats,
+        HISTORY_CHUNK_SIZE, MAX_METADATA_SIZE,
+    },
+    wallet_id::{EmptyWalletId, WalletId},
+    webhooks::{DeadLetter, WalletNotification, Webhook, WebhookRegistry},
+    Schema, Service, ServiceFactory, CRYPTOCURRENCY_SERVICE_ID, MAX_HISTORY_ENTRIES_ARG,
 };
 
 // Imports shared test constants.
@@ -53,32 +109,51 @@ mod constants;
 fn test_create_wallet() {
     let (mut testkit, api) = create_testkit();
     // Create and send a transaction via API
-    let (tx, _) = api.create_wallet(ALICE_NAME);
+    let (tx, _) = api.create_wallet(ALICE_NAME).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
 
     // Check that the user indeed is persisted by the service.
-    let wallet = api.get_wallet(tx.author()).unwrap();
+    let wallet = api.get_wallet(tx.author()).unwrap().unwrap();
     assert_eq!(wallet.pub_key, tx.author());
     assert_eq!(wallet.name, ALICE_NAME);
     assert_eq!(wallet.balance, 100);
 }
 
+/// Check that the schema returns the wallet it has stored.
+#[test]
+fn test_schema_create_wallet() {
+    let (testkit, _) = create_testkit();
+    let (pub_key, _) = crypto::gen_keypair();
+    let tx_hash = crypto::hash(&[1]);
+
+    let mut fork = testkit.blockchain().fork();
+    let mut schema = Schema::new(&mut fork);
+    let wallet = schema.create_wallet(&pub_key, ALICE_NAME, Amount::from(50), &tx_hash, Height(3));
+    assert_eq!(schema.wallet(&pub_key), Some(wallet.clone()));
+    assert_eq!(wallet.name, ALICE_NAME);
+    assert_eq!(wallet.balance, 50);
+    assert_eq!(wallet.history_len, 1);
+    assert_eq!(wallet.last_tx_hash, tx_hash);
+    assert_eq!(wallet.created_at, Height(3));
+    assert_eq!(wallet.last_active_at, Height(3));
+}
+
 /// Check that the transfer transaction works as intended.
 #[test]
 fn test_transfer() {
     // Create 2 wallets.
     let (mut testkit, api) = create_testkit();
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx_alice.hash(), &json!({ "type": "success" }));
-    api.assert_tx_status(tx_bob.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx_alice.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx_bob.hash(), &json!({ "type": "success" }));
 
     // Check that the initial Alice's and Bob's balances persisted by the service.
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
 
     // Transfer funds by invoking the corresponding API method.
@@ -89,15 +164,15 @@ fn test_transfer() {
         0,  // seed
         &key_alice,
     );
-    api.transaction(&tx);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
 
     // After the transfer transaction is included into a block, we may check new wallet
     // balances.
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 90);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 110);
 }
 
@@ -106,14 +181,14 @@ fn test_transfer() {
 fn test_transfer_from_nonexisting_wallet() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     // Do not commit Alice's transaction, so Alice's wallet does not exist
     // when a transfer occurs.
     testkit.create_block_with_tx_hashes(&[tx_bob.hash()]);
 
-    api.assert_no_wallet(tx_alice.author());
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
+    assert_no_wallet(&api, tx_alice.author());
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
 
     let tx = Transfer::sign(
@@ -123,15 +198,16 @@ fn test_transfer_from_nonexisting_wallet() {
         0,  // seed
         &key_alice,
     );
-    api.transaction(&tx);
+    api.transaction(&tx).unwrap();
     testkit.create_block_with_tx_hashes(&[tx.hash()]);
-    api.assert_tx_status(
+    assert_tx_status(
+        &api,
         tx.hash(),
         &json!({ "type": "error", "code": 1, "description": "Sender doesn't exist" }),
     );
 
     // Check that Bob's balance doesn't change.
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
 }
 
@@ -140,15 +216,15 @@ fn test_transfer_from_nonexisting_wallet() {
 fn test_transfer_to_nonexisting_wallet() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     // Do not commit Bob's transaction, so Bob's wallet does not exist
     // when a transfer occurs.
     testkit.create_block_with_tx_hashes(&[tx_alice.hash()]);
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
-    api.assert_no_wallet(tx_bob.author());
+    assert_no_wallet(&api, tx_bob.author());
 
     let tx = Transfer::sign(
         &tx_alice.author(),
@@ -157,15 +233,16 @@ fn test_transfer_to_nonexisting_wallet() {
         0,  // seed
         &key_alice,
     );
-    api.transaction(&tx);
+    api.transaction(&tx).unwrap();
     testkit.create_block_with_tx_hashes(&[tx.hash()]);
-    api.assert_tx_status(
+    assert_tx_status(
+        &api,
         tx.hash(),
         &json!({ "type": "error", "code": 2, "description": "Receiver doesn't exist" }),
     );
 
     // Check that Alice's balance doesn't change.
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
 }
 
@@ -174,8 +251,8 @@ fn test_transfer_to_nonexisting_wallet() {
 fn test_transfer_overcharge() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     testkit.create_block();
 
     // Transfer funds. The transfer amount (110) is more than Alice has (100).
@@ -186,16 +263,17 @@ fn test_transfer_overcharge() {
         0,   // seed
         &key_alice,
     );
-    api.transaction(&tx);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
-    api.assert_tx_status(
+    assert_tx_status(
+        &api,
         tx.hash(),
         &json!({ "type": "error", "code": 3, "description": "Insufficient currency amount" }),
     );
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
 }
 
@@ -204,9 +282,9 @@ fn test_unknown_wallet_request() {
     let (_testkit, api) = create_testkit();
 
     // Transaction is sent by API, but isn't committed.
-    let (tx, _) = api.create_wallet(ALICE_NAME);
+    let (tx, _) = api.create_wallet(ALICE_NAME).unwrap();
 
-    api.assert_no_wallet(tx.author());
+    assert_no_wallet(&api, tx.author());
 }
 
 #[test]
@@ -214,16 +292,16 @@ fn test_simple_wallet_info() {
     let (mut testkit, api) = create_testkit();
 
     // Create 2 wallets.
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _key_bob) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _key_bob) = api.create_wallet(BOB_NAME).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx_alice.hash(), &json!({ "type": "success" }));
-    api.assert_tx_status(tx_bob.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx_alice.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx_bob.hash(), &json!({ "type": "success" }));
 
     // Check that the initial Alice's and Bob's balances persisted by the service.
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
     assert_eq!(wallet.balance, 100);
 
     // Transfer funds by invoking the corresponding API method.
@@ -234,9 +312,9 @@ fn test_simple_wallet_info() {
         0,  // seed
         &key_alice,
     );
-    api.transaction(&tx);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
 
     let response = api.simple_wallet_info(tx_alice.author()).unwrap();
 
@@ -245,10 +323,16 @@ fn test_simple_wallet_info() {
             SimpleTransactionInfo {
                 hash: tx_alice.hash(),
                 height: Height(1),
+                tx_type: Some("CreateWallet".to_owned()),
+                amount: None,
+                counterparty: None,
             },
             SimpleTransactionInfo {
                 hash: tx.hash(),
                 height: Height(2),
+                tx_type: Some("Transfer".to_owned()),
+                amount: Some(Amount::from(10)),
+                counterparty: Some(tx_bob.author()),
             }
         ],
         response.transactions
@@ -261,10 +345,16 @@ fn test_simple_wallet_info() {
             SimpleTransactionInfo {
                 hash: tx_bob.hash(),
                 height: Height(1),
+                tx_type: Some("CreateWallet".to_owned()),
+                amount: None,
+                counterparty: None,
             },
             SimpleTransactionInfo {
                 hash: tx.hash(),
                 height: Height(2),
+                tx_type: Some("Transfer".to_owned()),
+                amount: Some(Amount::from(10)),
+                counterparty: Some(tx_alice.author()),
             }
         ],
         response.transactions
@@ -272,594 +362,8996 @@ fn test_simple_wallet_info() {
 }
 
 #[test]
-fn test_simple_wallet_info_on_unknown_public_key() {
-    let (_testkit, api) = create_testkit();
-    let (public_key, _private_key) = exonum_crypto::gen_keypair();
-    let response = api.simple_wallet_info(public_key);
-
-    assert!(response.is_err());
-
-    if let Err(err) = response {
-        println!("{}", err);
-    }
-}
-
-/// Check that the multisignature transfer transaction works as intended.
-#[test]
-fn test_transfer_multisig() {
+fn test_simple_wallet_info_multisig() {
     let (mut testkit, api) = create_testkit();
 
-    // Create 2 wallets.
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx_alice.hash(), &json!({ "type": "success" }));
-    api.assert_tx_status(tx_bob.hash(), &json!({ "type": "success" }));
-
-    // Check that the initial Alice's and Bob's balances persisted by the service.
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
 
-    // Create approvers.
     let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, dave_private_key) = exonum_crypto::gen_keypair();
-
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
+    let tx_done = TransferMultisig::sign(
         tx_alice.author(),
         &key_alice,
         tx_bob.author(),
-        [carol_public_key, dave_public_key]
-            .iter()
-            .cloned()
-            .collect(),
+        [carol_public_key].iter().cloned().collect(),
         10, // transferred amount
         0,  // seed
     );
-    api.transaction(&tx);
+    let tx_rejected = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key].iter().cloned().collect(),
+        20, // transferred amount
+        1,  // seed
+    );
+    api.transaction(&tx_done).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx.hash(), &json!({ "type": "success" }));
-
-    // Approve transfer.
-
-    let tx_carol = ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
-    api.transaction(&tx_carol);
+    api.transaction(&tx_rejected).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx_carol.hash(), &json!({ "type": "success" }));
 
-    let tx_dave = ApproveTransferMultisig::sign(dave_public_key, &dave_private_key, tx.hash());
-    api.transaction(&tx_dave);
+    let approve =
+        ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx_done.hash());
+    api.transaction(&approve).unwrap();
+    testkit.create_block();
+    let reject =
+        RejectTransferMultisig::sign(carol_public_key, &carol_private_key, tx_rejected.hash());
+    api.transaction(&reject).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx_dave.hash(), &json!({ "type": "success" }));
 
-    // After the multisignature transfer transaction is approved,
-    // we may check new wallet balances.
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 90);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 110);
+    let summary = |info: &SimpleTransactionInfo| {
+        (
+            info.hash,
+            info.tx_type.clone().unwrap(),
+            info.amount,
+            info.counterparty,
+        )
+    };
+
+    let alice = api.simple_wallet_info(tx_alice.author()).unwrap();
+    assert_eq!(
+        alice.transactions[1..]
+            .iter()
+            .map(summary)
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                tx_done.hash(),
+                "TransferMultisig".to_owned(),
+                Some(Amount::from(10)),
+                Some(tx_bob.author()),
+            ),
+            (
+                tx_rejected.hash(),
+                "TransferMultisig".to_owned(),
+                Some(Amount::from(20)),
+                Some(tx_bob.author()),
+            ),
+            (
+                reject.hash(),
+                "RejectTransferMultisig".to_owned(),
+                Some(Amount::from(20)),
+                Some(tx_bob.author()),
+            ),
+        ]
+    );
+
+    let bob = api.simple_wallet_info(tx_bob.author()).unwrap();
+    assert_eq!(
+        bob.transactions[1..]
+            .iter()
+            .map(summary)
+            .collect::<Vec<_>>(),
+        vec![(
+            approve.hash(),
+            "ApproveTransferMultisig".to_owned(),
+            Some(Amount::from(10)),
+            Some(tx_alice.author()),
+        )]
+    );
 }
 
-/// Check that a multisignature transfer from a non-existing wallet fails as expected.
 #[test]
-fn test_transfer_multisig_from_nonexisting_wallet() {
+fn test_simple_wallet_info_long_history() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
-    // Do not commit Alice's transaction, so Alice's wallet does not exist
-    // when a transfer occurs.
-    testkit.create_block_with_tx_hashes(&[tx_bob.hash()]);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    for block in 0..6 {
+        let issues = (0..500)
+            .map(|seed| Issue::sign(&alice, 1, block * 500 + seed, &key_alice))
+            .collect::<Vec<_>>();
+        testkit.create_block_with_transactions(issues);
+    }
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().history_len, 3001);
 
-    api.assert_no_wallet(tx_alice.author());
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    let start = Instant::now();
+    let info = api.simple_wallet_info(alice).unwrap();
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed < Duration::from_secs(2),
+        "simple wallet info took {:?}",
+        elapsed
+    );
 
-    // Create approvers.
-    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+    // The output matches the one built with an explorer lookup per history entry.
+    let snapshot = testkit.snapshot();
+    let context = ReadContext::new(testkit.blockchain());
+    let expected = Schema::new(&snapshot)
+        .wallet_history_from(&alice, 0)
+        .map(|record| {
+            let tx = context.explorer().transaction(&record.tx_hash).unwrap();
+            let tx = tx.as_committed().unwrap();
+            let (tx_type, amount) =
+                match WalletTransactions::tx_from_raw(tx.content().payload().clone()).unwrap() {
+                    WalletTransactions::CreateWallet(_) => ("CreateWallet", None),
+                    WalletTransactions::Issue(issue) => ("Issue", Some(issue.amount)),
+                    other => panic!("Unexpected transaction {:?}", other),
+                };
+            SimpleTransactionInfo {
+                hash: record.tx_hash,
+                height: tx.location().block_height(),
+                tx_type: Some(tx_type.to_owned()),
+                amount,
+                counterparty: None,
+            }
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(info.transactions.len(), 3001);
+    assert_eq!(info.transactions, expected);
+}
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        [carol_public_key, dave_public_key]
-            .iter()
-            .cloned()
-            .collect(),
-        10, // transferred amount
-        0,  // seed
-    );
-    api.transaction(&tx);
-    testkit.create_block_with_tx_hashes(&[tx.hash()]);
-    api.assert_tx_status(
-        tx.hash(),
-        &json!({ "type": "error", "code": 1, "description": "Sender doesn't exist" }),
-    );
+#[test]
+fn test_simple_transaction_info_serde_roundtrip() {
+    let (counterparty, _) = crypto::gen_keypair();
+    let infos = vec![
+        SimpleTransactionInfo {
+            hash: crypto::hash(&[1, 2, 3]),
+            height: Height(5),
+            tx_type: Some("Transfer".to_owned()),
+            amount: Some(Amount::from(42)),
+            counterparty: Some(counterparty),
+        },
+        SimpleTransactionInfo {
+            hash: crypto::hash(&[4, 5, 6]),
+            height: Height(6),
+            tx_type: None,
+            amount: None,
+            counterparty: None,
+        },
+    ];
 
-    // Check that Bob's balance doesn't change.
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    for info in infos {
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["height"], info.height.0);
+        let restored: SimpleTransactionInfo = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, info);
+    }
 }
 
-/// Check that a multisignature transfer to a non-existing wallet fails as expected.
 #[test]
-fn test_transfer_multisig_to_nonexisting_wallet() {
+fn test_simple_wallet_info_order_and_limit() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
-    // Do not commit Bob's transaction, so Bob's wallet does not exist
-    // when a transfer occurs.
-    testkit.create_block_with_tx_hashes(&[tx_alice.hash()]);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    api.assert_no_wallet(tx_bob.author());
+    // Commit transfers in separate blocks to fix their order.
+    let mut alice_history = vec![tx_alice.hash()];
+    for seed in 0..3 {
+        let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 1, seed, &key_alice);
+        api.transaction(&tx).unwrap();
+        testkit.create_block();
+        alice_history.push(tx.hash());
+    }
 
-    // Create approvers.
-    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+    let query = |order, offset, limit| SimpleWalletInfoQuery {
+        id: tx_alice.author().into(),
+        order,
+        offset,
+        limit,
+    };
+    let hashes = |query| {
+        api.simple_wallet_info_with(query)
+            .unwrap()
+            .transactions
+            .into_iter()
+            .map(|info| info.hash)
+            .collect::<Vec<_>>()
+    };
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        [carol_public_key, dave_public_key]
-            .iter()
-            .cloned()
-            .collect(),
-        10, // transferred amount
-        0,  // seed
+    assert_eq!(hashes(query(HistoryOrder::Asc, 0, None)), alice_history);
+    let mut reversed = alice_history.clone();
+    reversed.reverse();
+    assert_eq!(hashes(query(HistoryOrder::Desc, 0, None)), reversed);
+
+    assert_eq!(
+        hashes(query(HistoryOrder::Asc, 1, Some(2))),
+        alice_history[1..3].to_vec()
     );
-    api.transaction(&tx);
-    testkit.create_block_with_tx_hashes(&[tx.hash()]);
-    api.assert_tx_status(
-        tx.hash(),
-        &json!({ "type": "error", "code": 2, "description": "Receiver doesn't exist" }),
+    assert_eq!(
+        hashes(query(HistoryOrder::Desc, 0, Some(2))),
+        vec![alice_history[3], alice_history[2]]
+    );
+    assert_eq!(
+        hashes(query(HistoryOrder::Desc, 3, Some(2))),
+        vec![alice_history[0]]
     );
+    assert!(hashes(query(HistoryOrder::Desc, 10, None)).is_empty());
+}
 
-    // Check that Alice's balance doesn't change.
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+#[test]
+fn test_simple_wallet_info_on_unknown_public_key() {
+    let (_testkit, api) = create_testkit();
+    let (public_key, _private_key) = exonum_crypto::gen_keypair();
+    let err = api.simple_wallet_info(public_key).unwrap_err();
+
+    let error = assert_api_error(err, ErrorKind::WalletNotFound);
+    assert_eq!(error.code, 1);
 }
 
-/// Check that an overcharge does not lead to changes in sender's and receiver's balances.
 #[test]
-fn test_transfer_multisig_overcharge() {
+fn test_wallet_info_before_first_block() {
+    let (testkit, api) = create_testkit();
+    let (pub_key, _) = crypto::gen_keypair();
+
+    // Only the genesis block exists at this point.
+    let info: WalletInfo = api
+        .inner
+        .public(ApiKind::Service("cryptocurrency"))
+        .query(&WalletQuery { id: pub_key.into() })
+        .get("v1/wallets/info")
+        .unwrap();
+    assert_eq!(info.block_proof.block.height(), Height(0));
+    assert_eq!(testkit.height(), Height(0));
+    assert!(!info.exists);
+    assert!(info.wallet_history.is_none());
+
+    // The API is still operational afterwards.
+    assert_no_wallet(&api, pub_key);
+    assert!(api
+        .multisig_proof(crypto::hash(b"unknown"))
+        .block_proof
+        .precommits
+        .is_empty());
+}
+
+#[test]
+fn test_public_key_encodings() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let expected = api.wallet_history_head(alice).unwrap();
 
-    // Create approvers.
-    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+    let encodings = vec![
+        alice.to_hex(),
+        format!("0x{}", alice.to_hex()),
+        format!("0X{}", alice.to_hex().to_uppercase()),
+        base64::encode(alice.as_ref()),
+        base64::encode_config(alice.as_ref(), base64::URL_SAFE),
+    ];
+    for encoded in &encodings {
+        assert_eq!(key_encoding::parse_public_key(encoded), Ok(alice));
+        let head: WalletHistoryHead = api
+            .inner
+            .public(ApiKind::Service("cryptocurrency"))
+            .query(&json!({ "pub_key": encoded }))
+            .get("v1/wallets/history/head")
+            .unwrap();
+        assert_eq!(head, expected);
+    }
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        [carol_public_key, dave_public_key]
-            .iter()
-            .cloned()
-            .collect(),
-        110, // transferred amount
-        0,   // seed
-    );
-    api.transaction(&tx);
-    testkit.create_block();
-    api.assert_tx_status(
-        tx.hash(),
-        &json!({ "type": "error", "code": 3, "description": "Insufficient currency amount" }),
-    );
+    // Keys of a batch request may be mixed.
+    let batch: BalanceSum = api
+        .inner
+        .public(ApiKind::Service("cryptocurrency"))
+        .query(&json!({
+            "pub_keys": [format!("0x{}", alice.to_hex()), base64::encode(bob.as_ref())]
+        }))
+        .post("v1/wallets/balance/sum")
+        .unwrap();
+    assert_eq!(batch.total, 200);
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    let short_key = &alice.as_ref()[..31];
+    let malformed = vec![
+        (
+            hex::encode(short_key),
+            KeyParseError::WrongLength {
+                encoding: KeyEncoding::Hex,
+                expected: 32,
+                actual: 31,
+            },
+        ),
+        (
+            format!("0x{}", hex::encode(short_key)),
+            KeyParseError::WrongLength {
+                encoding: KeyEncoding::PrefixedHex,
+                expected: 32,
+                actual: 31,
+            },
+        ),
+        (
+            base64::encode(short_key),
+            KeyParseError::WrongLength {
+                encoding: KeyEncoding::Base64,
+                expected: 32,
+                actual: 31,
+            },
+        ),
+        (
+            "0xnot hex".to_owned(),
+            KeyParseError::InvalidPrefixedHex("0xnot hex".to_owned()),
+        ),
+        (
+            "not a key!".to_owned(),
+            KeyParseError::UnknownEncoding("not a key!".to_owned()),
+        ),
+    ];
+    for (encoded, error) in malformed {
+        assert_eq!(key_encoding::parse_public_key(&encoded), Err(error.clone()));
+        let err = api
+            .inner
+            .public(ApiKind::Service("cryptocurrency"))
+            .query(&json!({ "pub_key": encoded }))
+            .get::<WalletHistoryHead>("v1/wallets/history/head")
+            .unwrap_err();
+        // Malformed keys are treated as wallet names.
+        let api_error = assert_api_error(err, ErrorKind::WalletNotFound);
+        assert!(api_error.message.contains(&error.to_string()));
+    }
 }
 
 #[test]
-fn test_transfer_multisig_same_sender_and_receiver() {
+fn test_unknown_query_parameters() {
     let (mut testkit, api) = create_testkit();
-
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
     testkit.create_block();
+    let pub_key = tx_alice.author().to_hex();
+    let service_api = api.transport().public(ApiKind::Service("cryptocurrency"));
 
-    // Create approvers.
-    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
-
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        // Specify Alice as sender and receiver.
-        tx_alice.author(),
-        [carol_public_key, dave_public_key]
-            .iter()
-            .cloned()
-            .collect(),
-        10, // transferred amount
-        0,  // seed
+    let err = service_api
+        .query(&json!({ "pubkey": pub_key }))
+        .get::<WalletHistoryHead>("v1/wallets/history/head")
+        .unwrap_err();
+    let error = assert_api_error(err, ErrorKind::UnknownQueryParameters);
+    assert!(error.message.contains("pubkey"));
+    assert_eq!(
+        error.details,
+        Some(json!({ "unknown": ["pubkey"], "accepted": ["id"] }))
     );
-    api.transaction(&tx);
-    testkit.create_block();
-    api.assert_tx_status(
-        tx.hash(),
-        &json!({ "type": "error", "code": 4, "description": "Sender same as receiver" }),
+
+    // Misspelled optional parameters are not ignored.
+    let err = service_api
+        .query(&json!({ "pub_key": pub_key, "with_histroy": false }))
+        .get::<WalletInfo>("v1/wallets/info")
+        .unwrap_err();
+    let error = assert_api_error(err, ErrorKind::UnknownQueryParameters);
+    let details = error.details.unwrap();
+    assert_eq!(details["unknown"], json!(["with_histroy"]));
+    assert!(details["accepted"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("with_history")));
+
+    // Endpoints without parameters accept none.
+    let err = service_api
+        .query(&json!({ "verbose": true }))
+        .get::<ServiceStats>("v1/stats")
+        .unwrap_err();
+    let error = assert_api_error(err, ErrorKind::UnknownQueryParameters);
+    assert_eq!(
+        error.details,
+        Some(json!({ "unknown": ["verbose"], "accepted": [] }))
     );
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    // Other malformed queries are reported as invalid.
+    let err = service_api
+        .query(&json!({}))
+        .get::<WalletHistoryHead>("v1/wallets/history/head")
+        .unwrap_err();
+    let error = assert_api_error(err, ErrorKind::InvalidQuery);
+    assert!(error.message.contains("id"));
 }
 
 #[test]
-fn test_transfer_multisig_empty_approvers_list() {
+fn test_wallet_history_head() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, 0, &key_alice);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        // Send empty approvers list.
-        [].iter().cloned().collect(),
-        10, // transferred amount
-        0,  // seed
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    let head = api.wallet_history_head(tx_alice.author()).unwrap();
+    assert_eq!(
+        head,
+        WalletHistoryHead {
+            history_len: 2,
+            history_hash: wallet.history_hash,
+            latest_entry_hash: Some(tx.hash()),
+            first_retained: 0,
+        }
     );
-    api.transaction(&tx);
-    testkit.create_block();
-    api.assert_tx_status(
-        tx.hash(),
-        &json!({ "type": "error", "code": 5, "description": "Empty approvers list" }),
+
+    // Wallets with an empty history are not created by transactions,
+    // so the wallet is put into the storage directly.
+    let (empty_key, _) = crypto::gen_keypair();
+    let mut fork = testkit.blockchain().fork();
+    Schema::new(&mut fork).wallets_mut().put(
+        &empty_key,
+        Wallet::new(
+            &empty_key,
+            "Empty",
+            Amount::ZERO,
+            0,
+            &Hash::zero(),
+            &Hash::zero(),
+            Height(0),
+            Height(0),
+        ),
     );
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    let head = api.wallet_history_head(empty_key).unwrap();
+    assert_eq!(head.history_len, 0);
+    assert_eq!(head.latest_entry_hash, None);
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    let (unknown_key, _) = crypto::gen_keypair();
+    let err = api.wallet_history_head(unknown_key).unwrap_err();
+    assert_api_error(err, ErrorKind::WalletNotFound);
 }
 
 #[test]
-fn test_transfer_multisig_too_large_approvers_list() {
+fn test_wallet_history_since() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     testkit.create_block();
 
-    let mut approvers = Vec::new();
+    // Verifies the response against the latest block and returns the validated
+    // history entries.
+    let verify = |since: &WalletHistorySince| {
+        let to_table = since.wallet_proof.to_table.check().unwrap();
+        assert_eq!(
+            to_table.merkle_root(),
+            *since.block_proof.block.state_hash()
+        );
+        let (_, wallets_root) = to_table.entries().next().unwrap();
+        let to_wallet = since.wallet_proof.to_wallet.check().unwrap();
+        assert_eq!(to_wallet.merkle_root(), *wallets_root);
+        let (_, wallet) = to_wallet.entries().next().unwrap();
 
-    for _ in 0..=MAX_APPROVERS {
-        let (public_key, _private_key) = exonum_crypto::gen_keypair();
-        approvers.push(public_key);
-    }
+        let entries = since
+            .proof
+            .as_ref()
+            .map(|proof| {
+                proof
+                    .validate(wallet.history_hash, wallet.history_len)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(index, record)| (index, record.tx_hash))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for ((_, hash), tx) in entries.iter().zip(&since.transactions) {
+            assert_eq!(tx.message().hash(), *hash);
+        }
+        (wallet.history_len, entries)
+    };
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        approvers.iter().cloned().collect(),
-        10, // transferred amount
-        0,  // seed
-    );
-    api.transaction(&tx);
+    // Initial sync.
+    let since = api.wallet_history_since(tx_alice.author(), 0).unwrap();
+    let (history_len, entries) = verify(&since);
+    assert_eq!(history_len, 1);
+    assert_eq!(entries, vec![(0, tx_alice.hash())]);
+
+    // Nothing new: the response is empty, but still proves the history length.
+    let since = api
+        .wallet_history_since(tx_alice.author(), history_len)
+        .unwrap();
+    assert!(since.transactions.is_empty());
+    assert_eq!(verify(&since), (1, vec![]));
+
+    let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, 0, &key_alice);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
-    api.assert_tx_status(
-        tx.hash(),
-        &json!({ "type": "error", "code": 6, "description": "Approvers list is too large" }),
+
+    // Incremental sync.
+    let since = api
+        .wallet_history_since(tx_alice.author(), history_len)
+        .unwrap();
+    let (history_len, entries) = verify(&since);
+    assert_eq!(history_len, 2);
+    assert_eq!(entries, vec![(1, tx.hash())]);
+    assert_eq!(
+        since.summaries,
+        vec![SimpleTransactionInfo {
+            hash: tx.hash(),
+            height: Height(2),
+            tx_type: Some("Transfer".to_owned()),
+            amount: Some(Amount::from(10)),
+            counterparty: Some(tx_bob.author()),
+        }]
     );
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    let err = api
+        .wallet_history_since(tx_alice.author(), history_len + 1)
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
 }
 
 #[test]
-fn test_transfer_multisig_approve_non_existent_tx() {
+fn test_wallet_history_range() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
     testkit.create_block();
 
-    // Create approvers.
-    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+    let transfers = (0..3)
+        .map(|seed| {
+            let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, seed, &key_alice);
+            api.transaction(&tx).unwrap();
+            tx
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block();
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        [carol_public_key, dave_public_key]
-            .iter()
-            .cloned()
-            .collect(),
-        10, // transferred amount
-        0,  // seed
+    // Alice's history: wallet creation followed by three transfers.
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.history_len, 4);
+
+    let range = api.wallet_history_range(tx_alice.author(), 1, 3).unwrap();
+    assert_eq!(range.transactions.len(), 2);
+
+    let proof = range.proof.unwrap();
+    let entries = proof
+        .validate(wallet.history_hash, wallet.history_len)
+        .unwrap();
+    let indices = entries.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+    assert_eq!(indices, vec![1, 2]);
+
+    // Transfers share a block, so their relative order in the history is not fixed.
+    let transfer_hashes = transfers.iter().map(|tx| tx.hash()).collect::<Vec<_>>();
+    for ((_, record), tx) in entries.iter().zip(&range.transactions) {
+        assert!(transfer_hashes.contains(&record.tx_hash));
+        assert_eq!(tx.message().hash(), record.tx_hash);
+    }
+
+    // Empty range is valid, but carries no proof.
+    let range = api.wallet_history_range(tx_alice.author(), 4, 4).unwrap();
+    assert!(range.proof.is_none());
+    assert!(range.transactions.is_empty());
+
+    // Out of bounds and inverted ranges are rejected.
+    for &(from, to) in &[(0, 5), (3, 1)] {
+        let err = api
+            .wallet_history_range(tx_alice.author(), from, to)
+            .unwrap_err();
+        assert_api_error(err, ErrorKind::InvalidQuery);
+    }
+}
+
+#[test]
+fn test_wallet_history_pages() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    let issues = (0..MAX_ITEMS_PER_REQUEST + 10)
+        .map(|seed| Issue::sign(&alice, 1, seed, &key_alice))
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(issues);
+
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    let len = wallet.history_len;
+    assert_eq!(len, MAX_ITEMS_PER_REQUEST + 11);
+    let validate = |proof: Option<HistoryProof>| {
+        proof
+            .unwrap()
+            .validate(wallet.history_hash, len)
+            .unwrap()
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>()
+    };
+
+    // Both endpoints return a capped page and continue from `next_index`.
+    let range = api.wallet_history_range(alice, 0, len).unwrap();
+    assert_eq!(range.transactions.len() as u64, MAX_ITEMS_PER_REQUEST);
+    assert_eq!(range.next_index, Some(MAX_ITEMS_PER_REQUEST));
+    assert_eq!(
+        validate(range.proof),
+        (0..MAX_ITEMS_PER_REQUEST).collect::<Vec<_>>()
+    );
+    let range = api
+        .wallet_history_range(alice, MAX_ITEMS_PER_REQUEST, len)
+        .unwrap();
+    assert_eq!(range.transactions.len(), 11);
+    assert_eq!(range.next_index, None);
+    assert_eq!(
+        validate(range.proof),
+        (MAX_ITEMS_PER_REQUEST..len).collect::<Vec<_>>()
     );
-    api.transaction(&tx);
-    // Don't create a block so tx will not exist.
 
-    let tx_carol = ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
-    api.transaction(&tx_carol);
-    // Create block with Carol's tx only.
-    testkit.create_block_with_tx_hashes(&[tx_carol.hash()]);
-    api.assert_tx_status(
-        tx_carol.hash(),
-        &json!({ "type": "error", "code": 7, "description": "Transaction does not exist" }),
+    let since = api.wallet_history_since(alice, 1).unwrap();
+    assert_eq!(since.transactions.len() as u64, MAX_ITEMS_PER_REQUEST);
+    assert_eq!(since.summaries.len() as u64, MAX_ITEMS_PER_REQUEST);
+    assert_eq!(since.next_index, Some(MAX_ITEMS_PER_REQUEST + 1));
+    assert_eq!(
+        validate(since.proof),
+        (1..=MAX_ITEMS_PER_REQUEST).collect::<Vec<_>>()
     );
+    let since = api
+        .wallet_history_since(alice, MAX_ITEMS_PER_REQUEST + 1)
+        .unwrap();
+    assert_eq!(since.transactions.len(), 10);
+    assert_eq!(since.next_index, None);
+}
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+#[test]
+fn test_wallet_history_range_verification_chain() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    for seed in 0..3 {
+        let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, seed, &key_alice);
+        api.transaction(&tx).unwrap();
+        testkit.create_block();
+    }
+
+    // Everything below is verified using the single response only.
+    let range = api.wallet_history_range(tx_alice.author(), 2, 4).unwrap();
+
+    let block = &range.block_proof.block;
+    assert_eq!(block.height(), testkit.height());
+    assert!(!range.block_proof.precommits.is_empty());
+    for precommit in &range.block_proof.precommits {
+        assert_eq!(precommit.block_hash(), &block.hash());
+        assert_eq!(precommit.height(), block.height());
+    }
+
+    let to_table = range.wallet_proof.to_table.check().unwrap();
+    assert_eq!(to_table.merkle_root(), *block.state_hash());
+    let (_, wallets_root) = to_table.entries().next().unwrap();
+
+    let to_wallet = range.wallet_proof.to_wallet.check().unwrap();
+    assert_eq!(to_wallet.merkle_root(), *wallets_root);
+    let (_, wallet) = to_wallet
+        .entries()
+        .find(|(key, _)| **key == tx_alice.author())
+        .unwrap();
+
+    let entries = range
+        .proof
+        .unwrap()
+        .validate(wallet.history_hash, wallet.history_len)
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+    for ((_, record), tx) in entries.iter().zip(&range.transactions) {
+        assert_eq!(tx.message().hash(), record.tx_hash);
+    }
 }
 
 #[test]
-fn test_transfer_multisig_approve_on_failed_tx() {
+fn test_history_records() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let transfer = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 30, 0, &key_alice);
+    api.transaction(&transfer).unwrap();
+    testkit.create_block();
+    let issue = Issue::sign(&tx_alice.author(), 5, 0, &key_alice);
+    api.transaction(&issue).unwrap();
     testkit.create_block();
 
-    // Create approvers.
-    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let history = schema
+        .wallet_history_from(&tx_alice.author(), 0)
+        .collect::<Vec<_>>();
+    let record = |tx_hash, height, incoming, amount: u64| HistoryRecord {
+        tx_hash,
+        height: Height(height),
+        incoming,
+        amount: Amount::from(amount),
+    };
+    assert_eq!(
+        history,
+        vec![
+            record(tx_alice.hash(), 1, true, 100),
+            record(transfer.hash(), 2, false, 30),
+            record(issue.hash(), 3, true, 5),
+        ]
+    );
+    assert_eq!(
+        schema.wallet_history_record(&tx_bob.author(), 1),
+        Some(record(transfer.hash(), 2, true, 30))
+    );
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        [carol_public_key, dave_public_key]
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.history_len, 3);
+    assert_eq!(
+        wallet.history_hash,
+        schema
+            .wallet_history_chunk_roots(&tx_alice.author())
+            .merkle_root()
+    );
+    assert_eq!(
+        history
             .iter()
-            .cloned()
-            .collect(),
-        // Should fail due to overcharge.
-        110, // transferred amount
-        0,   // seed
+            .fold(Amount::ZERO, |balance, record| record.apply(balance)),
+        wallet.balance
     );
-    api.transaction(&tx);
+}
+
+#[test]
+fn test_legacy_history_migration() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, 0, &key_alice);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
 
-    let tx_carol = ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
-    api.transaction(&tx_carol);
+    let pub_keys = [tx_alice.author(), tx_bob.author()];
+    let expected = {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        pub_keys
+            .iter()
+            .map(|pub_key| schema.wallet_history_from(pub_key, 0).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+
+    // Rewrite the histories in the legacy format.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for (pub_key, records) in pub_keys.iter().zip(&expected) {
+            let legacy_hash = {
+                let mut legacy = schema.legacy_wallet_history_mut(pub_key);
+                legacy.extend(records.iter().map(|record| record.tx_hash));
+                legacy.merkle_root()
+            };
+            schema.wallet_history_chunk_mut(pub_key, 0).clear();
+            schema.wallet_history_chunk_roots_mut(pub_key).clear();
+            let wallet = schema.wallet(pub_key).unwrap();
+            schema.wallets_mut().put(
+                pub_key,
+                Wallet {
+                    history_hash: legacy_hash,
+                    ..wallet
+                },
+            );
+        }
+        schema.legacy_history_migrated_entry_mut().remove();
+        schema.schema_version_entry_mut().remove();
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+
+    // The history records of the transactions executed in the migration block
+    // follow the migrated ones.
+    let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 15, 1, &key_alice);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
-    api.assert_tx_status(
-        tx_carol.hash(),
-        &json!({ "type": "error", "code": 8, "description": "Referred transaction failed" }),
-    );
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.legacy_history_migrated());
+    for (pub_key, records) in pub_keys.iter().zip(&expected) {
+        assert!(schema.legacy_wallet_history(pub_key).is_empty());
+
+        let history = schema.wallet_history_from(pub_key, 0).collect::<Vec<_>>();
+        assert_eq!(history.len(), records.len() + 1);
+        assert_eq!(&history[..records.len()], &records[..]);
+        assert_eq!(history.last().unwrap().tx_hash, tx.hash());
+        assert_eq!(history.last().unwrap().amount, 15);
+
+        let wallet = schema.wallet(pub_key).unwrap();
+        assert_eq!(wallet.history_len, history.len() as u64);
+        assert_eq!(
+            wallet.history_hash,
+            schema.wallet_history_chunk_roots(pub_key).merkle_root()
+        );
+    }
 }
 
 #[test]
-fn test_transfer_multisig_approve_on_some_non_related_tx() {
+fn test_unchunked_history_migration() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, _key_alice) = api.create_wallet(ALICE_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, 0, &key_alice);
+    api.transaction(&tx).unwrap();
     testkit.create_block();
 
-    // Create approvers.
-    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let alice = tx_alice.author();
+    let (expected, history_hash) = {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        let wallet = schema.wallet(&alice).unwrap();
+        (
+            schema.wallet_history_from(&alice, 0).collect::<Vec<_>>(),
+            wallet.history_hash,
+        )
+    };
 
-    let tx_carol =
-        ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx_alice.hash());
-    api.transaction(&tx_carol);
+    // Rewrite Alice's history as a single list of records.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let unchunked_hash = {
+            let mut unchunked = schema.unchunked_wallet_history_mut(&alice);
+            unchunked.extend(expected.iter().cloned());
+            unchunked.merkle_root()
+        };
+        schema.wallet_history_chunk_mut(&alice, 0).clear();
+        schema.wallet_history_chunk_roots_mut(&alice).clear();
+        let wallet = schema.wallet(&alice).unwrap();
+        schema.wallets_mut().put(
+            &alice,
+            Wallet {
+                history_hash: unchunked_hash,
+                ..wallet
+            },
+        );
+        schema.legacy_history_migrated_entry_mut().remove();
+        schema.schema_version_entry_mut().remove();
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
     testkit.create_block();
-    api.assert_tx_status(
-        tx_carol.hash(),
-        &json!({ "type": "error", "code": 9, "description": "Referred transaction is not TransferMultisig" }),
-    );
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.legacy_history_migrated());
+    assert!(schema.unchunked_wallet_history(&alice).is_empty());
+    assert_eq!(
+        schema.wallet_history_from(&alice, 0).collect::<Vec<_>>(),
+        expected
+    );
+    assert_eq!(schema.wallet(&alice).unwrap().history_hash, history_hash);
 }
 
 #[test]
-fn test_transfer_multisig_approver_non_eligible_to_approve() {
+fn test_segmented_history_migration() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
     testkit.create_block();
+    let alice = tx_alice.author();
+    let issues = (0..2 * HISTORY_CHUNK_SIZE)
+        .map(|seed| Issue::sign(&alice, 1, seed, &key_alice))
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(issues);
 
-    // Create approvers.
-    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, dave_private_key) = exonum_crypto::gen_keypair();
+    let (expected, wallet) = {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        (
+            schema.wallet_history_from(&alice, 0).collect::<Vec<_>>(),
+            schema.wallet(&alice).unwrap(),
+        )
+    };
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        // Only Carol is allowed to approve the transfer.
-        [carol_public_key].iter().cloned().collect(),
-        10, // transferred amount
-        0,  // seed
-    );
-    api.transaction(&tx);
+    // Rewrite Alice's history in segments with the first segment pruned.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let segmented_hash = {
+            let mut hashes = schema.segmented_history_hashes_mut(&alice);
+            hashes.extend(expected.iter().map(CryptoHash::hash));
+            hashes.merkle_root()
+        };
+        for (segment, records) in expected.chunks(HISTORY_CHUNK_SIZE as usize).enumerate() {
+            let segment = segment as u64;
+            if segment > 0 {
+                schema
+                    .history_segment_mut(&alice, segment)
+                    .extend(records.iter().cloned());
+            }
+            schema.wallet_history_chunk_mut(&alice, segment).clear();
+        }
+        schema.wallet_history_chunk_roots_mut(&alice).clear();
+        schema.history_first_segments_mut().put(&alice, 1);
+        schema.wallets_mut().put(
+            &alice,
+            Wallet {
+                history_hash: segmented_hash,
+                history_chain: Hash::zero(),
+                ..wallet.clone()
+            },
+        );
+        schema.schema_version_entry_mut().set(7);
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
     testkit.create_block();
 
-    let tx_dave = ApproveTransferMultisig::sign(dave_public_key, &dave_private_key, tx.hash());
-    api.transaction(&tx_dave);
+    // The chunks are restored, and the roots of the pruned ones match the records.
+    let wallet_after = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet_after.history_hash, wallet.history_hash);
+    assert_eq!(wallet_after.history_len, wallet.history_len);
+    assert_eq!(wallet_after.history_chain, wallet.history_chain);
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(schema.schema_version(), SCHEMA_VERSION);
+        assert!(schema.segmented_history_hashes(&alice).is_empty());
+        assert!(schema.history_segment(&alice, 1).is_empty());
+        assert!(schema.history_first_segments().get(&alice).is_none());
+        assert!(schema.wallet_history_chunk(&alice, 0).is_empty());
+        assert_eq!(
+            schema.first_retained_history_index(&alice),
+            HISTORY_CHUNK_SIZE
+        );
+        assert_eq!(
+            schema
+                .wallet_history_from(&alice, HISTORY_CHUNK_SIZE)
+                .collect::<Vec<_>>(),
+            &expected[HISTORY_CHUNK_SIZE as usize..]
+        );
+    }
+
+    let (from, to) = (HISTORY_CHUNK_SIZE, HISTORY_CHUNK_SIZE + 10);
+    let range = api.wallet_history_range(alice, from, to).unwrap();
+    let entries = range
+        .proof
+        .unwrap()
+        .validate(wallet.history_hash, wallet.history_len)
+        .unwrap();
+    assert_eq!(
+        entries
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect::<Vec<_>>(),
+        &expected[from as usize..to as usize]
+    );
+}
+
+#[test]
+fn test_history_chunks() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
     testkit.create_block();
-    api.assert_tx_status(
-        tx_dave.hash(),
-        &json!({ "type": "error", "code": 10, "description": "Approver is not on approvers list" }),
+    let alice = tx_alice.author();
+    let issues = (0..HISTORY_CHUNK_SIZE + 10)
+        .map(|seed| Issue::sign(&alice, 1, seed, &key_alice))
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(issues);
+
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet.history_len, HISTORY_CHUNK_SIZE + 11);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let roots = schema.wallet_history_chunk_roots(&alice);
+    assert_eq!(roots.len(), 2);
+    assert_eq!(wallet.history_hash, roots.merkle_root());
+    assert_eq!(
+        schema.wallet_history_chunk(&alice, 0).len(),
+        HISTORY_CHUNK_SIZE
     );
+    assert_eq!(schema.wallet_history_chunk(&alice, 1).len(), 11);
+    let history = schema.wallet_history_from(&alice, 0).collect::<Vec<_>>();
+    assert_eq!(history.len() as u64, wallet.history_len);
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 90);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    // A range spanning the chunk boundary is proven by both chunks.
+    let (from, to) = (HISTORY_CHUNK_SIZE - 3, HISTORY_CHUNK_SIZE + 2);
+    let range = api.wallet_history_range(alice, from, to).unwrap();
+    let proof = range.proof.unwrap();
+    assert_eq!(proof.chunks.len(), 2);
+    let entries = proof
+        .validate(wallet.history_hash, wallet.history_len)
+        .unwrap();
+    assert_eq!(
+        entries.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+        (from..to).collect::<Vec<_>>()
+    );
+    for (index, record) in &entries {
+        assert_eq!(*record, history[*index as usize]);
+    }
+
+    // Proofs are checked against the history length as well.
+    assert!(proof
+        .validate(wallet.history_hash, wallet.history_len + 1)
+        .is_err());
 }
 
 #[test]
-fn test_transfer_multisig_reject() {
+fn test_history_chain() {
     let (mut testkit, api) = create_testkit();
 
-    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
-    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
     testkit.create_block();
+    let alice = tx_alice.author();
+    let issues = (0..2 * HISTORY_CHUNK_SIZE + 5)
+        .map(|seed| Issue::sign(&alice, 1, seed, &key_alice))
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(issues);
 
-    // Create approvers.
-    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
-    let (dave_public_key, dave_private_key) = exonum_crypto::gen_keypair();
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet.current_history_chunk(), 2);
+    let (roots, history) = {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        (
+            schema
+                .wallet_history_chunk_roots(&alice)
+                .iter()
+                .collect::<Vec<_>>(),
+            schema.wallet_history_from(&alice, 0).collect::<Vec<_>>(),
+        )
+    };
+    assert_eq!(roots.len(), 3);
+    assert_eq!(wallet.history_chain, history_chain(&roots[..2]));
+    assert_ne!(wallet.history_chain, history_chain(&roots[..1]));
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TransferMultisig::sign(
-        tx_alice.author(),
-        &key_alice,
-        tx_bob.author(),
-        [carol_public_key, dave_public_key]
+    // An entry of every chunk is proven by the proof within its chunk, which checks
+    // out against `history_hash`, and the entries of the filled chunks are verified
+    // with the chained roots as well.
+    for chunk in 0..3 {
+        let index = chunk * HISTORY_CHUNK_SIZE + 1;
+        let range = api.wallet_history_range(alice, index, index + 1).unwrap();
+        let proof = range.proof.unwrap();
+        assert_eq!(proof.chunks.len(), 1);
+        let entries = proof
+            .validate(wallet.history_hash, wallet.history_len)
+            .unwrap();
+        assert_eq!(entries, vec![(index, history[index as usize].clone())]);
+
+        if chunk < wallet.current_history_chunk() {
+            let entries = proof.chunks[0]
+                .validate(roots[chunk as usize], HISTORY_CHUNK_SIZE)
+                .unwrap();
+            assert_eq!(entries, vec![(1, &history[index as usize])]);
+        }
+    }
+
+    // The chain changes only once the current chunk is filled.
+    let issues = (0..HISTORY_CHUNK_SIZE - 7)
+        .map(|seed| Issue::sign(&alice, 1, 1_000 + seed, &key_alice))
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(issues);
+    let updated = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(updated.current_history_chunk(), 2);
+    assert_eq!(updated.history_chain, wallet.history_chain);
+
+    let issue = Issue::sign(&alice, 1, 2_000, &key_alice);
+    testkit.create_block_with_transactions(vec![issue]);
+    let updated = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(updated.current_history_chunk(), 3);
+    let roots = {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        schema
+            .wallet_history_chunk_roots(&alice)
             .iter()
-            .cloned()
-            .collect(),
-        10, // transferred amount
-        0,  // seed
-    );
-    api.transaction(&tx);
-    testkit.create_block();
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(updated.history_chain, history_chain(&roots));
 
-    let tx_dave = ApproveTransferMultisig::sign(dave_public_key, &dave_private_key, tx.hash());
-    api.transaction(&tx_dave);
+    // Wallets written before the chain was introduced get it from their chunk roots.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let mut pb = updated.to_pb();
+        pb.clear_history_chain();
+        let bytes = protobuf::Message::write_to_bytes(&pb).unwrap();
+        schema.raw_wallets_mut().put(&alice, bytes);
+        schema.schema_version_entry_mut().set(13);
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx_dave.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap(), updated);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.schema_version(), SCHEMA_VERSION);
+    assert!(protobuf::parse_from_bytes::<proto::Wallet>(
+        &schema.raw_wallets().get(&alice).unwrap()
+    )
+    .unwrap()
+    .has_history_chain());
+}
 
-    // Carol decides to reject the transfer.
-    let tx_carol = RejectTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
-    api.transaction(&tx_carol);
+#[test]
+fn test_history_pruning_from_run_arguments() {
+    let mut factory = ServiceFactory;
+    let extension = factory.command(fabric::Run.name()).unwrap();
+    let context_with = |value: &str| {
+        let mut context = Context::default();
+        context.set_arg(MAX_HISTORY_ENTRIES_ARG, value.to_owned());
+        context
+    };
+    assert!(extension.execute(context_with("0")).is_err());
+    assert!(extension.execute(context_with("ten")).is_err());
+    let context = extension.execute(context_with("10")).unwrap();
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(factory.make_service(&context))
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
     testkit.create_block();
-    api.assert_tx_status(tx_carol.hash(), &json!({ "type": "success" }));
+    let alice = tx_alice.author();
+    for chunk in 0..2 {
+        let issues = (0..HISTORY_CHUNK_SIZE)
+            .map(|seed| Issue::sign(&alice, 1, chunk * HISTORY_CHUNK_SIZE + seed, &key_alice))
+            .collect::<Vec<_>>();
+        testkit.create_block_with_transactions(issues);
+    }
+    assert_eq!(
+        api.wallet_history_head(alice).unwrap().first_retained,
+        HISTORY_CHUNK_SIZE
+    );
 
-    let wallet = api.get_wallet(tx_alice.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
-    let wallet = api.get_wallet(tx_bob.author()).unwrap();
-    assert_eq!(wallet.balance, 100);
+    // Histories are not pruned unless the argument is given.
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(factory.make_service(&extension.execute(Context::default()).unwrap()))
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    let issues = (0..2 * HISTORY_CHUNK_SIZE)
+        .map(|seed| Issue::sign(&alice, 1, seed, &key_alice))
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(issues);
+    assert_eq!(api.wallet_history_head(alice).unwrap().first_retained, 0);
 }
 
-/// Wrapper for the cryptocurrency service API allowing to easily use it
-/// (compared to `TestKitApi` calls).
-struct CryptocurrencyApi {
-    pub inner: TestKitApi,
-}
+#[test]
+fn test_history_pruning() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service::default().with_max_history_entries(10))
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
 
-impl CryptocurrencyApi {
-    /// Generates a wallet creation transaction with a random key pair, sends it over HTTP,
-    /// and checks the synchronous result (i.e., the hash of the transaction returned
-    /// within the response).
-    /// Note that the transaction is not immediately added to the blockchain, but rather is put
-    /// to the pool of unconfirmed transactions.
-    fn create_wallet(&self, name: &str) -> (Signed<RawTransaction>, SecretKey) {
-        let (pubkey, key) = crypto::gen_keypair();
-        // Create a pre-signed transaction
-        let tx = CreateWallet::sign(name, &pubkey, &key);
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    let history_hash = |testkit: &TestKit| {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        schema.wallet_history_chunk_roots(&alice).merkle_root()
+    };
 
-        let data = messages::to_hex_string(&tx);
-        let tx_info: TransactionResponse = self
-            .inner
-            .public(ApiKind::Explorer)
-            .query(&json!({ "tx_body": data }))
-            .post("v1/transactions")
-            .unwrap();
-        assert_eq!(tx_info.tx_hash, tx.hash());
-        (tx, key)
+    let mut seeds = 0..;
+    for _ in 0..2 {
+        let issues = (0..HISTORY_CHUNK_SIZE)
+            .map(|_| Issue::sign(&alice, 1, seeds.next().unwrap(), &key_alice))
+            .collect::<Vec<_>>();
+        testkit.create_block_with_transactions(issues);
     }
 
-    /// Sends a transfer transaction over HTTP and checks the synchronous result.
-    fn transaction(&self, tx: &Signed<RawTransaction>) {
-        let data = messages::to_hex_string(&tx);
-        let tx_info: TransactionResponse = self
-            .inner
-            .public(ApiKind::Explorer)
-            .query(&json!({ "tx_body": data }))
-            .post("v1/transactions")
-            .unwrap();
-        assert_eq!(tx_info.tx_hash, tx.hash());
+    // The first chunk is dropped once the later records alone exceed the limit.
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet.history_len, 2 * HISTORY_CHUNK_SIZE + 1);
+    assert_eq!(wallet.balance, 100 + 2 * HISTORY_CHUNK_SIZE);
+    assert_eq!(wallet.history_hash, history_hash(&testkit));
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(
+            schema.first_retained_history_index(&alice),
+            HISTORY_CHUNK_SIZE
+        );
+        assert!(schema.wallet_history_chunk(&alice, 0).is_empty());
+        assert_eq!(schema.wallet_history_chunk_roots(&alice).len(), 3);
+        assert!(schema.wallets_pending_pruning().iter().next().is_none());
     }
 
-    fn get_wallet(&self, pub_key: PublicKey) -> Option<Wallet> {
-        let wallet_info = self
-            .inner
-            .public(ApiKind::Service("cryptocurrency"))
-            .query(&WalletQuery { pub_key })
-            .get::<WalletInfo>("v1/wallets/info")
-            .unwrap();
+    let head = api.wallet_history_head(alice).unwrap();
+    assert_eq!(head.history_len, wallet.history_len);
+    assert_eq!(head.first_retained, HISTORY_CHUNK_SIZE);
 
-        let to_wallet = wallet_info.wallet_proof.to_wallet.check().unwrap();
-        let wallet = to_wallet
-            .all_entries()
-            .find(|(ref k, _)| **k == pub_key)
-            .and_then(|tuple| tuple.1)
-            .cloned();
+    let info = api.simple_wallet_info(alice).unwrap();
+    assert_eq!(info.first_retained, HISTORY_CHUNK_SIZE);
+    assert_eq!(
+        info.transactions.len() as u64,
+        wallet.history_len - HISTORY_CHUNK_SIZE
+    );
 
-        wallet
-    }
+    // Pruned records can no longer be requested.
+    let err = api.wallet_history_range(alice, 0, 5).unwrap_err();
+    let error = assert_api_error(err, ErrorKind::HistoryPruned);
+    assert_eq!(
+        error.details,
+        Some(json!({ "first_retained": HISTORY_CHUNK_SIZE }))
+    );
+    let err = api.wallet_history_since(alice, 1).unwrap_err();
+    assert_api_error(err, ErrorKind::HistoryPruned);
+    let err = api.balance_at(alice, 1).unwrap_err();
+    assert_api_error(err, ErrorKind::HistoryPruned);
 
-    fn simple_wallet_info(&self, pub_key: PublicKey) -> api::Result<SimpleWalletInfo> {
-        self.inner
-            .public(ApiKind::Service("cryptocurrency"))
-            .query(&WalletQuery { pub_key })
-            .get::<SimpleWalletInfo>("v1/wallets/info/simple")
-    }
+    // Retained records are still proven against the unchanged history hash.
+    let (from, to) = (HISTORY_CHUNK_SIZE, HISTORY_CHUNK_SIZE + 5);
+    let range = api.wallet_history_range(alice, from, to).unwrap();
+    let entries = range
+        .proof
+        .unwrap()
+        .validate(wallet.history_hash, wallet.history_len)
+        .unwrap();
+    assert_eq!(
+        entries.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+        (from..to).collect::<Vec<_>>()
+    );
+    let since = api
+        .wallet_history_since(alice, wallet.history_len - 5)
+        .unwrap();
+    assert_eq!(since.transactions.len(), 5);
 
-    /// Asserts that a wallet with the specified public key is not known to the blockchain.
-    fn assert_no_wallet(&self, pub_key: PublicKey) {
-        let wallet_info: WalletInfo = self
-            .inner
-            .public(ApiKind::Service("cryptocurrency"))
-            .query(&WalletQuery { pub_key })
-            .get("v1/wallets/info")
-            .unwrap();
+    // The history keeps growing after pruning.
+    testkit.create_block_with_transactions(vec![Issue::sign(
+        &alice,
+        1,
+        seeds.next().unwrap(),
+        &key_alice,
+    )]);
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet.history_len, 2 * HISTORY_CHUNK_SIZE + 2);
+    assert_eq!(wallet.history_hash, history_hash(&testkit));
+    assert_eq!(
+        api.wallet_history_head(alice).unwrap().first_retained,
+        HISTORY_CHUNK_SIZE
+    );
+}
+
+#[test]
+fn test_balance_index() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    assert_balance_index(&testkit);
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, carol_key) = crypto::gen_keypair();
+
+    let approved = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol].iter().cloned().collect(),
+        20,
+        0,
+    );
+    let rejected = TransferMultisig::sign(
+        bob,
+        &key_bob,
+        alice,
+        [carol].iter().cloned().collect(),
+        5,
+        1,
+    );
+    testkit.create_block_with_transactions(vec![
+        Transfer::sign(&alice, &bob, 10, 2, &key_alice),
+        Issue::sign(&bob, 7, 3, &key_bob),
+        approved.clone(),
+        rejected.clone(),
+        // Fails, so that the index is left intact.
+        Transfer::sign(&alice, &bob, 1_000, 4, &key_alice),
+    ]);
+    assert_balance_index(&testkit);
+
+    testkit.create_block_with_transactions(vec![
+        ApproveTransferMultisig::sign(carol, &carol_key, approved.hash()),
+        RejectTransferMultisig::sign(carol, &carol_key, rejected.hash()),
+    ]);
+    assert_balance_index(&testkit);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema.wallets_by_balance().collect::<Vec<_>>(),
+        vec![(bob, Amount::from(137)), (alice, Amount::from(70))]
+    );
+
+    // Wallets with equal balances are ordered by their keys.
+    let (tx_carol, _) = api.create_wallet("Carol").unwrap();
+    let (tx_dave, _) = api.create_wallet("Dave").unwrap();
+    testkit.create_block();
+    assert_balance_index(&testkit);
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let mut newcomers = vec![tx_carol.author(), tx_dave.author()];
+    newcomers.sort();
+    assert_eq!(
+        schema
+            .wallets_by_balance()
+            .skip(1)
+            .take(2)
+            .map(|(pub_key, _)| pub_key)
+            .collect::<Vec<_>>(),
+        newcomers
+    );
+}
+
+#[test]
+fn test_balance_index_build() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
 
-        let to_wallet = wallet_info.wallet_proof.to_wallet.check().unwrap();
-        assert!(to_wallet.missing_keys().find(|v| **v == pub_key).is_some())
+    // Drop the index as if the wallets were created by a previous version of the service.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        schema.balance_index_mut().clear();
+        schema.balance_index_built_entry_mut().remove();
+        schema.schema_version_entry_mut().remove();
     }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
 
-    /// Asserts that the transaction with the given hash has a specified status.
-    fn assert_tx_status(&self, tx_hash: Hash, expected_status: &serde_json::Value) {
-        let info: serde_json::Value = self
-            .inner
-            .public(ApiKind::Explorer)
-            .query(&TransactionQuery::new(tx_hash))
-            .get("v1/transactions")
-            .unwrap();
+    // Wallets updated in the same block as the index is built are indexed once.
+    testkit.create_block_with_transactions(vec![Transfer::sign(
+        &tx_alice.author(),
+        &tx_bob.author(),
+        10,
+        0,
+        &key_alice,
+    )]);
+    assert_balance_index(&testkit);
+    let snapshot = testkit.snapshot();
+    assert!(Schema::new(&snapshot).balance_index_built());
+}
+
+#[test]
+fn test_balance_index_rebuild() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
 
-        if let serde_json::Value::Object(mut info) = info {
-            let tx_status = info.remove("status").unwrap();
-            assert_eq!(tx_status, *expected_status);
-        } else {
-            panic!("Invalid transaction info format, object expected");
+    // Replace the index with the keys written by the previous versions of the service,
+    // which hold the balances in `u64` units.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        schema.balance_index_mut().clear();
+        for pub_key in &[tx_alice.author(), tx_bob.author()] {
+            let mut key = (u64::max_value() - 100).to_be_bytes().to_vec();
+            key.extend_from_slice(pub_key.as_ref());
+            schema.balance_index_mut().insert(key);
         }
+        schema.schema_version_entry_mut().set(9);
     }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+
+    assert_balance_index(&testkit);
+    let snapshot = testkit.snapshot();
+    assert_eq!(Schema::new(&snapshot).schema_version(), SCHEMA_VERSION);
 }
 
-/// Creates a testkit together with the API wrapper defined above.
-fn create_testkit() -> (TestKit, CryptocurrencyApi) {
-    let testkit = TestKitBuilder::validator().with_service(Service).create();
-    let api = CryptocurrencyApi {
-        inner: testkit.api(),
-    };
-    (testkit, api)
+/// Checks that the balance index lists every wallet exactly once with its current
+/// balance, ordered by descending balance and then by the public key.
+fn assert_balance_index(testkit: &TestKit) {
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+
+    let mut expected = schema
+        .wallets()
+        .values()
+        .map(|wallet| (wallet.pub_key, wallet.balance))
+        .collect::<Vec<_>>();
+    expected.sort_by(|(key_a, balance_a), (key_b, balance_b)| {
+        balance_b.cmp(balance_a).then(key_a.cmp(key_b))
+    });
+    assert_eq!(schema.wallets_by_balance().collect::<Vec<_>>(), expected);
+}
+
+/// Check that the multisignature transfer transaction works as intended.
+#[test]
+fn test_transfer_multisig() {
+    let (mut testkit, api) = create_testkit();
+
+    // Create 2 wallets.
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, tx_alice.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, tx_bob.hash(), &json!({ "type": "success" }));
+
+    // Check that the initial Alice's and Bob's balances persisted by the service.
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+
+    // Create approvers.
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
+
+    // The transfer parameters are stored together with the transfer.
+    let transfer = {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot).multisig_transfer(tx.hash()).unwrap()
+    };
+    assert_eq!(transfer.from, tx_alice.author());
+    assert_eq!(transfer.to, tx_bob.author());
+    assert_eq!(transfer.amount, 10);
+    assert_eq!(transfer.approvers.len(), 2);
+    assert!(transfer.approvers.contains(&carol_public_key));
+    assert!(transfer.approvers.contains(&dave_public_key));
+    assert!(transfer.approved_by.is_empty());
+
+    // Approve transfer.
+
+    let tx_carol = ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
+    api.transaction(&tx_carol).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, tx_carol.hash(), &json!({ "type": "success" }));
+
+    let tx_dave = ApproveTransferMultisig::sign(dave_public_key, &dave_private_key, tx.hash());
+    api.transaction(&tx_dave).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, tx_dave.hash(), &json!({ "type": "success" }));
+
+    // After the multisignature transfer transaction is approved,
+    // we may check new wallet balances.
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 90);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 110);
+}
+
+#[test]
+fn test_legacy_multisig_migration() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (carol, carol_key) = crypto::gen_keypair();
+    let (dave, dave_key) = crypto::gen_keypair();
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol, dave].iter().cloned().collect(),
+        10,
+        0,
+    );
+    testkit.create_block_with_transactions(vec![
+        tx.clone(),
+        ApproveTransferMultisig::sign(carol, &carol_key, tx.hash()),
+    ]);
+
+    let expected = {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot).multisig_transfer(tx.hash()).unwrap()
+    };
+    assert_eq!(
+        expected.approved_by,
+        vec![Approval {
+            approver: carol,
+            approved_at_height: Height(2),
+            comment: String::new(),
+        }]
+    );
+
+    // Rewrite the transfer in the legacy format without the transfer parameters
+    // and the approval heights.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let mut pb = expected.to_pb();
+        pb.clear_from();
+        pb.clear_to();
+        pb.clear_amount();
+        pb.clear_approvers();
+        pb.clear_rejected_by();
+        pb.clear_rejected_at_height();
+        pb.clear_approved_by();
+        pb.set_legacy_approved_by(vec![carol].to_pb());
+        let bytes = protobuf::Message::write_to_bytes(&pb).unwrap();
+        schema.raw_multisig_transfers_mut().put(&tx.hash(), bytes);
+        schema.legacy_multisig_migrated_entry_mut().remove();
+        schema.schema_version_entry_mut().remove();
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+
+    // The approval executed in the migration block sees the restored parameters.
+    let approval = ApproveTransferMultisig::sign(dave, &dave_key, tx.hash());
+    testkit.create_block_with_transactions(vec![approval.clone()]);
+    assert_tx_status(&api, approval.hash(), &json!({ "type": "success" }));
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.legacy_multisig_migrated());
+    let transfer = schema.multisig_transfer(tx.hash()).unwrap();
+    assert_eq!(transfer.state, State::Done);
+    assert_eq!(
+        transfer,
+        MultisignatureTransfer {
+            approved_by: vec![
+                Approval {
+                    approver: carol,
+                    approved_at_height: Height(0),
+                    comment: String::new(),
+                },
+                Approval {
+                    approver: dave,
+                    approved_at_height: Height(3),
+                    comment: String::new(),
+                },
+            ],
+            state: State::Done,
+            settled_at_height: Height(3),
+            ..expected
+        }
+    );
+    assert_eq!(
+        api.get_wallet(tx_bob.author()).unwrap().unwrap().balance,
+        110
+    );
+}
+
+#[test]
+fn test_schema_ranges() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+    let (approver, approver_key) = crypto::gen_keypair();
+    let multisigs = (0..3)
+        .map(|seed| {
+            TransferMultisig::sign(
+                tx_alice.author(),
+                &key_alice,
+                tx_bob.author(),
+                [approver].iter().cloned().collect(),
+                10,
+                seed,
+            )
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(multisigs.clone());
+    testkit.create_block_with_transactions(vec![ApproveTransferMultisig::sign(
+        approver,
+        &approver_key,
+        multisigs[0].hash(),
+    )]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let wallet_keys = |entries: Vec<(PublicKey, Wallet)>| {
+        entries
+            .into_iter()
+            .map(|(pub_key, wallet)| {
+                assert_eq!(pub_key, wallet.pub_key);
+                pub_key
+            })
+            .collect::<Vec<_>>()
+    };
+    let keys = schema.wallets().keys().collect::<Vec<_>>();
+    assert_eq!(keys.len(), 3);
+
+    assert_eq!(wallet_keys(schema.wallets_range(None, 10)), keys);
+    assert_eq!(wallet_keys(schema.wallets_range(None, 2)), &keys[..2]);
+    assert!(schema.wallets_range(None, 0).is_empty());
+    assert!(schema.wallets_range(Some(&keys[0]), 0).is_empty());
+    assert_eq!(
+        wallet_keys(schema.wallets_range(Some(&keys[0]), 10)),
+        &keys[1..]
+    );
+    assert_eq!(
+        wallet_keys(schema.wallets_range(Some(&keys[1]), 1)),
+        &keys[2..]
+    );
+    assert!(schema.wallets_range(Some(&keys[2]), 10).is_empty());
+
+    // The key to start after does not need to be present.
+    let (missing_key, _) = crypto::gen_keypair();
+    let expected = keys
+        .iter()
+        .cloned()
+        .filter(|pub_key| *pub_key > missing_key)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        wallet_keys(schema.wallets_range(Some(&missing_key), 10)),
+        expected
+    );
+
+    let hashes = schema.multisig_transfers().keys().collect::<Vec<_>>();
+    assert_eq!(hashes.len(), 3);
+    let transfers = schema.multisig_transfers_range(None, 10);
+    assert_eq!(
+        transfers
+            .iter()
+            .map(|(tx_hash, _)| *tx_hash)
+            .collect::<Vec<_>>(),
+        hashes
+    );
+    assert_eq!(transfers[0].1, schema.multisig_transfer(hashes[0]).unwrap());
+    assert!(schema.multisig_transfers_range(None, 0).is_empty());
+    assert_eq!(
+        schema.multisig_transfers_range(Some(&hashes[0]), 10).len(),
+        2
+    );
+    assert!(schema
+        .multisig_transfers_range(Some(&hashes[2]), 10)
+        .is_empty());
+    let missing_hash = crypto::hash(b"missing");
+    assert_eq!(
+        schema
+            .multisig_transfers_range(Some(&missing_hash), 10)
+            .len(),
+        hashes
+            .iter()
+            .filter(|&&tx_hash| tx_hash > missing_hash)
+            .count()
+    );
+
+    let mut in_process = vec![multisigs[1].hash(), multisigs[2].hash()];
+    in_process.sort();
+    assert_eq!(
+        schema.multisig_transfers_by_state_range(&State::InProcess, None, 10),
+        in_process
+    );
+    assert_eq!(
+        schema.multisig_transfers_by_state_range(&State::InProcess, Some(&in_process[0]), 10),
+        &in_process[1..]
+    );
+    assert_eq!(
+        schema.multisig_transfers_by_state_range(&State::Done, None, 10),
+        vec![multisigs[0].hash()]
+    );
+    assert!(schema
+        .multisig_transfers_by_state_range(&State::Rejected, None, 10)
+        .is_empty());
+}
+
+#[test]
+fn test_read_context() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let blockchain = testkit.blockchain().clone();
+    let context = ReadContext::new(&blockchain);
+    let height = context.height();
+
+    // A block is committed while the context is in use.
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 90);
+    let latest = ReadContext::new(&blockchain);
+    assert_eq!(latest.height(), height.next());
+    assert!(latest
+        .explorer()
+        .transaction_without_proof(&transfer.hash())
+        .is_some());
+
+    // Everything read through the context still comes from the earlier block.
+    assert_eq!(context.general_schema().height(), height);
+    assert_eq!(context.latest_block_proof().unwrap().block.height(), height);
+    assert!(context.explorer().block(height.next()).is_none());
+    assert!(context
+        .explorer()
+        .transaction_without_proof(&transfer.hash())
+        .is_none());
+    assert_eq!(context.schema().wallet(&alice).unwrap().balance, 100);
+    let to_wallet = context.wallet_proof(alice).to_wallet.check().unwrap();
+    assert_eq!(
+        to_wallet.merkle_root(),
+        context.schema().wallets().merkle_root()
+    );
+    let (_, wallet) = to_wallet.entries().next().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_affected_wallets() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (approver, approver_key) = crypto::gen_keypair();
+    let multisig = |seed| {
+        TransferMultisig::sign(
+            alice,
+            &key_alice,
+            bob,
+            [approver].iter().cloned().collect(),
+            10,
+            seed,
+        )
+    };
+    let (approved, rejected) = (multisig(1), multisig(2));
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    let failed = Transfer::sign(&alice, &bob, 1_000, 3, &key_alice);
+    testkit.create_block_with_transactions(vec![
+        transfer.clone(),
+        failed.clone(),
+        approved.clone(),
+        rejected.clone(),
+    ]);
+    let approve = ApproveTransferMultisig::sign(approver, &approver_key, approved.hash());
+    let reject = RejectTransferMultisig::sign(approver, &approver_key, rejected.hash());
+    testkit.create_block_with_transactions(vec![approve.clone(), reject.clone()]);
+
+    assert_eq!(api.affected_wallets(tx_alice.hash()).unwrap(), vec![alice]);
+    assert_eq!(
+        api.affected_wallets(transfer.hash()).unwrap(),
+        vec![alice, bob]
+    );
+    assert_eq!(api.affected_wallets(approved.hash()).unwrap(), vec![alice]);
+    // The receiver is credited by the settling approval rather than by the transfer.
+    assert_eq!(api.affected_wallets(approve.hash()).unwrap(), vec![bob]);
+    assert_eq!(api.affected_wallets(reject.hash()).unwrap(), vec![alice]);
+    assert!(api.affected_wallets(failed.hash()).unwrap().is_empty());
+    assert!(api
+        .affected_wallets(crypto::hash(b"unknown"))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_schema_multiproofs() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (approver, _) = crypto::gen_keypair();
+    let multisig = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [approver].iter().cloned().collect(),
+        10,
+        0,
+    );
+    testkit.create_block_with_transactions(vec![multisig.clone()]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+
+    let (unknown_key, _) = crypto::gen_keypair();
+    let proof = schema
+        .wallets_multiproof(&[alice, unknown_key, bob])
+        .check()
+        .unwrap();
+    assert_eq!(proof.merkle_root(), schema.wallets().merkle_root());
+    let mut found = proof
+        .entries()
+        .map(|(pub_key, wallet)| {
+            assert_eq!(*wallet, schema.wallet(pub_key).unwrap());
+            *pub_key
+        })
+        .collect::<Vec<_>>();
+    found.sort();
+    let mut expected = vec![alice, bob];
+    expected.sort();
+    assert_eq!(found, expected);
+    assert_eq!(proof.missing_keys().collect::<Vec<_>>(), vec![&unknown_key]);
+
+    let unknown_hash = crypto::hash(b"unknown");
+    let proof = schema
+        .multisig_transfers_multiproof(&[unknown_hash, multisig.hash()])
+        .check()
+        .unwrap();
+    assert_eq!(
+        proof.merkle_root(),
+        schema.multisig_transfers().merkle_root()
+    );
+    let entries = proof.entries().collect::<Vec<_>>();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(*entries[0].0, multisig.hash());
+    assert_eq!(
+        *entries[0].1,
+        schema.multisig_transfer(multisig.hash()).unwrap()
+    );
+    assert_eq!(
+        proof.missing_keys().collect::<Vec<_>>(),
+        vec![&unknown_hash]
+    );
+
+    // A proof of absence only.
+    let proof = schema.wallets_multiproof(&[unknown_key]).check().unwrap();
+    assert_eq!(proof.merkle_root(), schema.wallets().merkle_root());
+    assert!(proof.entries().next().is_none());
+}
+
+#[test]
+fn test_multisig_list() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    let (approver, approver_key) = exonum_crypto::gen_keypair();
+    let transfers = (0..2)
+        .map(|seed| {
+            let tx = TransferMultisig::sign(
+                tx_alice.author(),
+                &key_alice,
+                tx_bob.author(),
+                [approver].iter().cloned().collect(),
+                10,
+                seed,
+            );
+            api.transaction(&tx).unwrap();
+            tx.hash()
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block();
+
+    let mut in_process = transfers.clone();
+    in_process.sort();
+    assert_eq!(api.multisig_list(State::InProcess).unwrap(), in_process);
+    assert!(api.multisig_list(State::Rejected).unwrap().is_empty());
+
+    // Pagination.
+    let first_page = api
+        .multisig_list_page(State::InProcess, None, Some(1))
+        .unwrap();
+    assert_eq!(first_page.tx_hashes, vec![in_process[0]]);
+    let second_page = api
+        .multisig_list_page(State::InProcess, first_page.next_cursor, Some(1))
+        .unwrap();
+    assert_eq!(second_page.tx_hashes, vec![in_process[1]]);
+    assert_eq!(second_page.next_cursor, None);
+
+    let err = api
+        .multisig_list_page(State::InProcess, Some("not a cursor".to_owned()), None)
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidCursor);
+    // A well-formed cursor pointing to an unknown transfer.
+    let unknown = base64::encode_config(Hash::zero().as_ref(), base64::URL_SAFE_NO_PAD);
+    let err = api
+        .multisig_list_page(State::InProcess, Some(unknown), None)
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidCursor);
+
+    api.transaction(&RejectTransferMultisig::sign(
+        approver,
+        &approver_key,
+        transfers[0],
+    ))
+    .unwrap();
+    api.transaction(&ApproveTransferMultisig::sign(
+        approver,
+        &approver_key,
+        transfers[1],
+    ))
+    .unwrap();
+    testkit.create_block();
+
+    assert!(api.multisig_list(State::InProcess).unwrap().is_empty());
+    assert_eq!(
+        api.multisig_list(State::Rejected).unwrap(),
+        vec![transfers[0]]
+    );
+    assert_eq!(api.multisig_list(State::Done).unwrap(), vec![transfers[1]]);
+}
+
+#[test]
+fn test_pending_outgoing() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    assert_eq!(
+        api.wallet_info(alice).unwrap().pending_outgoing,
+        PendingOutgoing::default()
+    );
+
+    let (approver, approver_key) = exonum_crypto::gen_keypair();
+    let transfers = [(10, 0), (25, 1)]
+        .iter()
+        .map(|&(amount, seed)| {
+            TransferMultisig::sign(
+                alice,
+                &key_alice,
+                bob,
+                [approver].iter().cloned().collect(),
+                amount,
+                seed,
+            )
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(transfers.clone());
+
+    // Both transfers are deducted from the balance and reported as pending.
+    let info = api.wallet_info(alice).unwrap();
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 65);
+    assert_eq!(
+        info.pending_outgoing,
+        PendingOutgoing {
+            total: Amount::from(35),
+            transfers: vec![
+                PendingTransfer {
+                    tx_hash: transfers[0].hash(),
+                    amount: Amount::from(10),
+                },
+                PendingTransfer {
+                    tx_hash: transfers[1].hash(),
+                    amount: Amount::from(25),
+                },
+            ],
+        }
+    );
+    // Only the sender has pending outgoing transfers.
+    assert_eq!(
+        api.wallet_info(bob).unwrap().pending_outgoing,
+        PendingOutgoing::default()
+    );
+
+    let batch = api.wallets_batch(vec![alice, bob]).unwrap();
+    assert_matches!(
+        batch.wallets[0],
+        BatchWalletEntry::Found { ref pending_outgoing, .. }
+            if *pending_outgoing == info.pending_outgoing
+    );
+
+    testkit.create_block_with_transactions(vec![ApproveTransferMultisig::sign(
+        approver,
+        &approver_key,
+        transfers[0].hash(),
+    )]);
+    let pending = api.wallet_info(alice).unwrap().pending_outgoing;
+    assert_eq!(pending.total, 25);
+    assert_eq!(pending.transfers.len(), 1);
+    assert_eq!(pending.transfers[0].tx_hash, transfers[1].hash());
+
+    testkit.create_block_with_transactions(vec![RejectTransferMultisig::sign(
+        approver,
+        &approver_key,
+        transfers[1].hash(),
+    )]);
+    assert_eq!(
+        api.wallet_info(alice).unwrap().pending_outgoing,
+        PendingOutgoing::default()
+    );
+}
+
+#[test]
+fn test_approver_pending_index() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let (first, first_key) = exonum_crypto::gen_keypair();
+    let (second, second_key) = exonum_crypto::gen_keypair();
+    let (outsider, outsider_key) = exonum_crypto::gen_keypair();
+    let transfers = (0..3)
+        .map(|seed| {
+            TransferMultisig::sign(
+                alice,
+                &key_alice,
+                bob,
+                [first, second].iter().cloned().collect(),
+                10,
+                seed,
+            )
+        })
+        .collect::<Vec<_>>();
+    // A failed transfer is not indexed.
+    let failed = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [first].iter().cloned().collect(),
+        1_000,
+        3,
+    );
+    let mut txs = transfers.clone();
+    txs.push(failed.clone());
+    testkit.create_block_with_transactions(txs);
+
+    let pending = |testkit: &TestKit, approver: &PublicKey| {
+        let snapshot = testkit.snapshot();
+        let mut hashes = Schema::new(&snapshot)
+            .approver_pending(approver)
+            .iter()
+            .collect::<Vec<_>>();
+        hashes.sort();
+        hashes
+    };
+    let sorted = |hashes: &[Hash]| {
+        let mut hashes = hashes.to_vec();
+        hashes.sort();
+        hashes
+    };
+    let hashes = transfers.iter().map(|tx| tx.hash()).collect::<Vec<_>>();
+    assert_eq!(pending(&testkit, &first), sorted(&hashes));
+    assert_eq!(pending(&testkit, &second), sorted(&hashes));
+    assert!(pending(&testkit, &outsider).is_empty());
+
+    // A partial approval keeps the transfer pending for both approvers, while
+    // failed approvals and rejections change nothing.
+    testkit.create_block_with_transactions(vec![
+        ApproveTransferMultisig::sign(first, &first_key, hashes[0]),
+        ApproveTransferMultisig::sign(outsider, &outsider_key, hashes[0]),
+        RejectTransferMultisig::sign(outsider, &outsider_key, hashes[1]),
+    ]);
+    assert_eq!(pending(&testkit, &first), sorted(&hashes));
+    assert_eq!(pending(&testkit, &second), sorted(&hashes));
+
+    // Completion and rejection remove the transfer for all approvers.
+    testkit.create_block_with_transactions(vec![
+        ApproveTransferMultisig::sign(second, &second_key, hashes[0]),
+        RejectTransferMultisig::sign(second, &second_key, hashes[1]),
+    ]);
+    assert_eq!(pending(&testkit, &first), vec![hashes[2]]);
+    assert_eq!(pending(&testkit, &second), vec![hashes[2]]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.multisig_transfer(hashes[0]).unwrap().is_done());
+    assert!(schema.multisig_transfer(hashes[1]).unwrap().is_rejected());
+    assert!(schema.multisig_transfer(failed.hash()).is_none());
+}
+
+#[test]
+fn test_outgoing_multisig_index() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let (approver, approver_key) = exonum_crypto::gen_keypair();
+    let transfers = (0..3)
+        .map(|seed| {
+            TransferMultisig::sign(
+                alice,
+                &key_alice,
+                bob,
+                [approver].iter().cloned().collect(),
+                10,
+                seed,
+            )
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block_with_transactions(transfers.clone());
+    testkit.create_block_with_transactions(vec![
+        ApproveTransferMultisig::sign(approver, &approver_key, transfers[0].hash()),
+        RejectTransferMultisig::sign(approver, &approver_key, transfers[1].hash()),
+    ]);
+
+    // Settled transfers stay in the index and resolve to their current state.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let outgoing = schema.outgoing_multisig_transfers(&alice);
+    let states = outgoing
+        .iter()
+        .map(|tx_hash| (tx_hash, schema.multisig_transfer(tx_hash).unwrap().state))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        vec![
+            (transfers[0].hash(), State::Done),
+            (transfers[1].hash(), State::Rejected),
+            (transfers[2].hash(), State::InProcess),
+        ]
+    );
+    assert!(schema.outgoing_multisig_transfers(&bob).is_empty());
+
+    // The lists are aggregated into the service state hash.
+    let roots = schema.outgoing_multisig_roots();
+    assert_eq!(roots.iter().count(), 1);
+    assert_eq!(roots.get(&alice), Some(outgoing.merkle_root()));
+    let state_hash = schema.state_hash();
+    assert_eq!(state_hash[14], roots.merkle_root());
+}
+#[test]
+fn test_multisig_template() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let (carol, carol_key) = crypto::gen_keypair();
+    let (dave, dave_key) = crypto::gen_keypair();
+    let transfer = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol, dave].iter().cloned().collect(),
+        10,
+        0,
+    );
+    api.transaction(&transfer).unwrap();
+    testkit.create_block();
+
+    let template = api.multisig_template(transfer.hash()).unwrap();
+    assert_eq!(template.tx_hash, transfer.hash());
+    assert_eq!(
+        (template.from, template.to, template.amount),
+        (alice, bob, Amount::from(10))
+    );
+    let mut approvers = vec![carol, dave];
+    approvers.sort();
+    let sorted = |mut keys: Vec<PublicKey>| {
+        keys.sort();
+        keys
+    };
+    assert_eq!(sorted(template.approvers.clone()), approvers);
+    assert_eq!(sorted(template.pending_approvers.clone()), approvers);
+    assert_eq!(template.progress, (0, 2));
+    assert_eq!(template.state, State::InProcess);
+    assert!(template.awaiting_approval);
+
+    // Approvals are built from the template data only.
+    let sign_approval = |template: MultisigTemplate, pub_key, key| {
+        let approval = template.approval.unwrap();
+        let payload = hex::decode(&approval.payload).unwrap();
+        Message::sign_transaction(
+            ServiceTransaction::from_raw_unchecked(approval.message_id, payload),
+            approval.service_id,
+            pub_key,
+            key,
+        )
+    };
+    let approval = sign_approval(template, carol, &carol_key);
+    assert_eq!(
+        approval.hash(),
+        ApproveTransferMultisig::sign(carol, &carol_key, transfer.hash()).hash()
+    );
+    api.transaction(&approval).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, approval.hash(), &json!({ "type": "success" }));
+
+    let template = api.multisig_template(transfer.hash()).unwrap();
+    assert_eq!(template.pending_approvers, vec![dave]);
+    assert_eq!(template.progress, (1, 2));
+    assert_eq!(
+        template.approved_by,
+        vec![Approval {
+            approver: carol,
+            approved_at_height: Height(3),
+            comment: String::new(),
+        }]
+    );
+    assert!(template.awaiting_approval);
+
+    let approval = sign_approval(template, dave, &dave_key);
+    api.transaction(&approval).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, approval.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 110);
+
+    // Settled transfers do not need approvals anymore.
+    let template = api.multisig_template(transfer.hash()).unwrap();
+    assert_eq!(template.state, State::Done);
+    assert_eq!(template.progress, (2, 2));
+    assert!(template.pending_approvers.is_empty());
+    assert!(!template.awaiting_approval);
+    assert_eq!(template.approval, None);
+    assert_eq!(template.rejected_by, None);
+    assert_eq!(template.rejected_at_height, None);
+
+    let err = api.multisig_template(Hash::zero()).unwrap_err();
+    assert_api_error(err, ErrorKind::MultisigTransferNotFound);
+}
+
+#[test]
+fn test_multisig_comments() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let (carol, carol_key) = crypto::gen_keypair();
+    let (dave, dave_key) = crypto::gen_keypair();
+    let transfer = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol, dave].iter().cloned().collect(),
+        10,
+        0,
+    );
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+
+    // Over-long comments are refused; a comment of the maximum length is fine.
+    let too_long = "x".repeat(MAX_COMMENT_LEN + 1);
+    let txs = vec![
+        ApproveTransferMultisig::sign_with_comment(carol, &carol_key, transfer.hash(), &too_long),
+        RejectTransferMultisig::sign_with_comment(dave, &dave_key, transfer.hash(), &too_long),
+    ];
+    testkit.create_block_with_transactions(txs.clone());
+    let too_long_error =
+        json!({ "type": "error", "code": 17, "description": "Comment is too long" });
+    assert_tx_status(&api, txs[0].hash(), &too_long_error);
+    assert_tx_status(&api, txs[1].hash(), &too_long_error);
+
+    let longest = "y".repeat(MAX_COMMENT_LEN);
+    let approval =
+        ApproveTransferMultisig::sign_with_comment(carol, &carol_key, transfer.hash(), &longest);
+    testkit.create_block_with_transactions(vec![approval.clone()]);
+    assert_tx_status(&api, approval.hash(), &json!({ "type": "success" }));
+
+    let rejection = RejectTransferMultisig::sign_with_comment(
+        dave,
+        &dave_key,
+        transfer.hash(),
+        "Wrong receiver",
+    );
+    testkit.create_block_with_transactions(vec![rejection.clone()]);
+    assert_tx_status(&api, rejection.hash(), &json!({ "type": "success" }));
+
+    let template = api.multisig_template(transfer.hash()).unwrap();
+    assert_eq!(template.state, State::Rejected);
+    assert_eq!(
+        template.approved_by,
+        vec![Approval {
+            approver: carol,
+            approved_at_height: Height(4),
+            comment: longest,
+        }]
+    );
+    assert_eq!(template.rejected_by, Some(dave));
+    assert_eq!(
+        template.rejection_comment,
+        Some("Wrong receiver".to_owned())
+    );
+
+    // Transfers rejected without a comment report none.
+    let transfer = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol, dave].iter().cloned().collect(),
+        10,
+        1,
+    );
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+    let rejection = RejectTransferMultisig::sign(dave, &dave_key, transfer.hash());
+    testkit.create_block_with_transactions(vec![rejection.clone()]);
+    assert_tx_status(&api, rejection.hash(), &json!({ "type": "success" }));
+    let template = api.multisig_template(transfer.hash()).unwrap();
+    assert_eq!(template.rejected_by, Some(dave));
+    assert_eq!(template.rejection_comment, None);
+}
+
+#[test]
+fn test_multisig_comment_round_trip() {
+    let tx_hash = crypto::hash(b"transfer");
+
+    let approval = ApproveTransferMultisig::with_comment(tx_hash, "Looks good".to_owned());
+    let restored = ApproveTransferMultisig::from_pb(approval.to_pb()).unwrap();
+    assert_eq!(restored.transfer_hash(), &tx_hash);
+    assert_eq!(restored.comment(), "Looks good");
+    let json = serde_json::to_value(&approval).unwrap();
+    assert_eq!(json["comment"], json!("Looks good"));
+    let restored = serde_json::from_value::<ApproveTransferMultisig>(json).unwrap();
+    assert_eq!(restored.comment(), "Looks good");
+
+    assert_eq!(ApproveTransferMultisig::new(tx_hash).comment(), "");
+
+    let (pk, sk) = crypto::gen_keypair();
+    let rejection = RejectTransferMultisig::sign_with_comment(pk, &sk, tx_hash, "Wrong amount");
+    let rejection = match WalletTransactions::tx_from_raw(rejection.payload().clone()).unwrap() {
+        WalletTransactions::RejectTransferMultisig(rejection) => rejection,
+        other => panic!("Unexpected transaction {:?}", other),
+    };
+    assert_eq!(rejection.transfer_hash(), &tx_hash);
+    assert_eq!(rejection.comment(), "Wrong amount");
+}
+
+#[test]
+fn test_multisig_proof() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _) = exonum_crypto::gen_keypair();
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        10,
+        0,
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+    let approve = ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
+    api.transaction(&approve).unwrap();
+    testkit.create_block();
+
+    // Everything below is verified using the single response only.
+    let info = api.multisig_proof(tx.hash()).unwrap();
+
+    let block = &info.block_proof.block;
+    assert_eq!(block.height(), testkit.height());
+    for precommit in &info.block_proof.precommits {
+        assert_eq!(precommit.block_hash(), &block.hash());
+    }
+
+    let to_table = info.multisig_proof.to_table.check().unwrap();
+    assert_eq!(to_table.merkle_root(), *block.state_hash());
+    let (_, transfers_root) = to_table.entries().next().unwrap();
+
+    let to_transfer = info.multisig_proof.to_transfer.check().unwrap();
+    assert_eq!(to_transfer.merkle_root(), *transfers_root);
+    let (_, transfer) = to_transfer
+        .entries()
+        .find(|(hash, _)| **hash == tx.hash())
+        .unwrap();
+    assert_eq!(transfer.state, State::InProcess);
+    assert_eq!(transfer.approved_by.len(), 1);
+    assert!(transfer.is_approved_by(&carol_public_key));
+
+    // Unknown transfers yield a verifiable proof of absence.
+    let unknown_hash = crypto::hash(b"unknown");
+    let info = api.multisig_proof(unknown_hash).unwrap();
+    let to_table = info.multisig_proof.to_table.check().unwrap();
+    assert_eq!(to_table.merkle_root(), *info.block_proof.block.state_hash());
+    let (_, transfers_root) = to_table.entries().next().unwrap();
+
+    let to_transfer = info.multisig_proof.to_transfer.check().unwrap();
+    assert_eq!(to_transfer.merkle_root(), *transfers_root);
+    assert!(to_transfer.entries().next().is_none());
+    assert!(to_transfer.missing_keys().any(|hash| *hash == unknown_hash));
+}
+
+/// Check that a multisignature transfer from a non-existing wallet fails as expected.
+#[test]
+fn test_transfer_multisig_from_nonexisting_wallet() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    // Do not commit Alice's transaction, so Alice's wallet does not exist
+    // when a transfer occurs.
+    testkit.create_block_with_tx_hashes(&[tx_bob.hash()]);
+
+    assert_no_wallet(&api, tx_alice.author());
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+
+    // Create approvers.
+    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block_with_tx_hashes(&[tx.hash()]);
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 1, "description": "Sender doesn't exist" }),
+    );
+
+    // Check that Bob's balance doesn't change.
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+/// Check that a multisignature transfer to a non-existing wallet fails as expected.
+#[test]
+fn test_transfer_multisig_to_nonexisting_wallet() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    // Do not commit Bob's transaction, so Bob's wallet does not exist
+    // when a transfer occurs.
+    testkit.create_block_with_tx_hashes(&[tx_alice.hash()]);
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    assert_no_wallet(&api, tx_bob.author());
+
+    // Create approvers.
+    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block_with_tx_hashes(&[tx.hash()]);
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 2, "description": "Receiver doesn't exist" }),
+    );
+
+    // Check that Alice's balance doesn't change.
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+/// Check that an overcharge does not lead to changes in sender's and receiver's balances.
+#[test]
+fn test_transfer_multisig_overcharge() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    // Create approvers.
+    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        110, // transferred amount
+        0,   // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 3, "description": "Insufficient currency amount" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_same_sender_and_receiver() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+
+    // Create approvers.
+    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        // Specify Alice as sender and receiver.
+        tx_alice.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 4, "description": "Sender same as receiver" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_empty_approvers_list() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        // Send empty approvers list.
+        [].iter().cloned().collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 5, "description": "Empty approvers list" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_too_large_approvers_list() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    let mut approvers = Vec::new();
+
+    for _ in 0..=MAX_APPROVERS {
+        let (public_key, _private_key) = exonum_crypto::gen_keypair();
+        approvers.push(public_key);
+    }
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        approvers.iter().cloned().collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 6, "description": "Approvers list is too large" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_approve_non_existent_tx() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    // Create approvers.
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    // Don't create a block so tx will not exist.
+
+    let tx_carol = ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
+    api.transaction(&tx_carol).unwrap();
+    // Create block with Carol's tx only.
+    testkit.create_block_with_tx_hashes(&[tx_carol.hash()]);
+    assert_tx_status(
+        &api,
+        tx_carol.hash(),
+        &json!({ "type": "error", "code": 7, "description": "Transaction does not exist" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_approve_on_failed_tx() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    // Create approvers.
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        // Should fail due to overcharge.
+        110, // transferred amount
+        0,   // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+
+    let tx_carol = ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
+    api.transaction(&tx_carol).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx_carol.hash(),
+        &json!({ "type": "error", "code": 8, "description": "Referred transaction failed" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_approve_on_some_non_related_tx() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, _key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+
+    // Create approvers.
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+
+    let tx_carol =
+        ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx_alice.hash());
+    api.transaction(&tx_carol).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx_carol.hash(),
+        &json!({ "type": "error", "code": 9, "description": "Referred transaction is not TransferMultisig" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_approver_non_eligible_to_approve() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    // Create approvers.
+    let (carol_public_key, _carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        // Only Carol is allowed to approve the transfer.
+        [carol_public_key].iter().cloned().collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+
+    let tx_dave = ApproveTransferMultisig::sign(dave_public_key, &dave_private_key, tx.hash());
+    api.transaction(&tx_dave).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx_dave.hash(),
+        &json!({ "type": "error", "code": 10, "description": "Approver is not on approvers list" }),
+    );
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 90);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+}
+
+#[test]
+fn test_transfer_multisig_reject() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    // Create approvers.
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, dave_private_key) = exonum_crypto::gen_keypair();
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+
+    let tx_dave = ApproveTransferMultisig::sign(dave_public_key, &dave_private_key, tx.hash());
+    api.transaction(&tx_dave).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, tx_dave.hash(), &json!({ "type": "success" }));
+
+    // Carol decides to reject the transfer.
+    let tx_carol = RejectTransferMultisig::sign(carol_public_key, &carol_private_key, tx.hash());
+    api.transaction(&tx_carol).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, tx_carol.hash(), &json!({ "type": "success" }));
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+    let wallet = api.get_wallet(tx_bob.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, 100);
+
+    // The rejection is recorded together with the transfer.
+    let transfer = {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot).multisig_transfer(tx.hash()).unwrap()
+    };
+    assert_eq!(transfer.state, State::Rejected);
+    assert_eq!(transfer.rejection(), Some((carol_public_key, Height(4))));
+    let template = api.multisig_template(tx.hash()).unwrap();
+    assert_eq!(template.rejected_by, Some(carol_public_key));
+    assert_eq!(template.rejected_at_height, Some(Height(4)));
+}
+
+#[test]
+fn test_settled_multisig_collection() {
+    let (mut testkit, api) = create_testkit_with(Config {
+        settled_multisig_retention: Some(3),
+        ..Config::default()
+    });
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, carol_key) = crypto::gen_keypair();
+    let (dave, dave_key) = crypto::gen_keypair();
+    let multisig = |approvers: &[PublicKey], seed| {
+        TransferMultisig::sign(
+            alice,
+            &key_alice,
+            bob,
+            approvers.iter().cloned().collect(),
+            10,
+            seed,
+        )
+    };
+    let done = multisig(&[carol], 0);
+    let rejected = multisig(&[carol, dave], 1);
+    let pending = multisig(&[carol, dave], 2);
+    testkit.create_block_with_transactions(vec![done.clone(), rejected.clone(), pending.clone()]);
+    testkit.create_block_with_transactions(vec![
+        ApproveTransferMultisig::sign(carol, &carol_key, done.hash()),
+        RejectTransferMultisig::sign(dave, &dave_key, rejected.hash()),
+    ]);
+    let transfer = |testkit: &TestKit, tx_hash| {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot).multisig_transfer(tx_hash)
+    };
+    assert_eq!(
+        transfer(&testkit, done.hash()).unwrap().settled_at_height,
+        Height(3)
+    );
+    assert_eq!(
+        transfer(&testkit, rejected.hash())
+            .unwrap()
+            .settled_at_height,
+        Height(3)
+    );
+
+    // Settled transfers cannot be approved or rejected again.
+    let approve_done = ApproveTransferMultisig::sign(carol, &carol_key, done.hash());
+    let reject_rejected = RejectTransferMultisig::sign(carol, &carol_key, rejected.hash());
+    testkit.create_block_with_transactions(vec![approve_done.clone(), reject_rejected.clone()]);
+    assert_tx_status(
+        &api,
+        approve_done.hash(),
+        &json!({ "type": "error", "code": 14, "description": "Transfer is done" }),
+    );
+    assert_tx_status(
+        &api,
+        reject_rejected.hash(),
+        &json!({ "type": "error", "code": 11, "description": "Transfer is rejected" }),
+    );
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 110);
+
+    testkit.create_block();
+    assert!(transfer(&testkit, done.hash()).is_some());
+
+    // Transfers are collected `retention` blocks after the settlement.
+    testkit.create_block();
+    assert!(transfer(&testkit, done.hash()).is_none());
+    assert!(transfer(&testkit, rejected.hash()).is_none());
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(
+            schema.collected_multisig_state(&done.hash()),
+            Some(State::Done)
+        );
+        assert_eq!(
+            schema.collected_multisig_state(&rejected.hash()),
+            Some(State::Rejected)
+        );
+        assert!(!schema
+            .multisig_transfers_by_state(&State::Done)
+            .contains(&done.hash()));
+        assert!(schema.settled_multisig_transfers().iter().next().is_none());
+        assert_eq!(schema.collected_multisig_state(&pending.hash()), None);
+    }
+
+    // Approvals and rejections of the collected transfers fail with the same errors.
+    let txs = vec![
+        ApproveTransferMultisig::sign(carol, &carol_key, done.hash()),
+        RejectTransferMultisig::sign(carol, &carol_key, done.hash()),
+        ApproveTransferMultisig::sign(carol, &carol_key, rejected.hash()),
+        ApproveTransferMultisig::sign(carol, &carol_key, pending.hash()),
+        ApproveTransferMultisig::sign(dave, &dave_key, pending.hash()),
+    ];
+    testkit.create_block_with_transactions(txs.clone());
+    let done_error = json!({ "type": "error", "code": 14, "description": "Transfer is done" });
+    assert_tx_status(&api, txs[0].hash(), &done_error);
+    assert_tx_status(&api, txs[1].hash(), &done_error);
+    assert_tx_status(
+        &api,
+        txs[2].hash(),
+        &json!({ "type": "error", "code": 11, "description": "Transfer is rejected" }),
+    );
+    assert_tx_status(&api, txs[3].hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, txs[4].hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 80);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 120);
+}
+
+#[test]
+fn test_settle_multisig_refund() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, carol_key) = crypto::gen_keypair();
+    let multisig = |seed| {
+        TransferMultisig::sign(
+            alice,
+            &key_alice,
+            bob,
+            [carol].iter().cloned().collect(),
+            10,
+            seed,
+        )
+    };
+    let (done, rejected, pending) = (multisig(0), multisig(1), multisig(2));
+    testkit.create_block_with_transactions(vec![done.clone(), rejected.clone(), pending.clone()]);
+    testkit.create_block_with_transactions(vec![
+        ApproveTransferMultisig::sign(carol, &carol_key, done.hash()),
+        RejectTransferMultisig::sign(carol, &carol_key, rejected.hash()),
+    ]);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 80);
+    let already_settled = |tx_hash| Err(AlreadySettled { tx_hash });
+
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        assert!(schema.multisig_transfer(rejected.hash()).unwrap().refunded);
+        // Done, refunded and pending transfers cannot be refunded.
+        for tx_hash in vec![done.hash(), rejected.hash(), pending.hash()] {
+            assert_eq!(
+                schema.settle_multisig_refund(tx_hash),
+                already_settled(tx_hash)
+            );
+        }
+
+        // A rejected transfer is refunded exactly once.
+        let transfer = schema
+            .multisig_transfer(pending.hash())
+            .unwrap()
+            .reject(carol, Height(4))
+            .unwrap();
+        assert!(!transfer.refunded);
+        schema.update_transfer_multisig(pending.hash(), transfer);
+        assert_eq!(schema.settle_multisig_refund(pending.hash()), Ok(()));
+        assert!(schema.multisig_transfer(pending.hash()).unwrap().refunded);
+        assert_eq!(
+            schema.settle_multisig_refund(pending.hash()),
+            already_settled(pending.hash())
+        );
+
+        // Collected transfers cannot be refunded either.
+        schema.collect_settled_multisig_transfers(Height(10), 0);
+        for tx_hash in vec![done.hash(), rejected.hash(), pending.hash()] {
+            assert_eq!(
+                schema.settle_multisig_refund(tx_hash),
+                already_settled(tx_hash)
+            );
+        }
+    }
+
+    // Transfers rejected before the refunds were recorded are migrated as refunded.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let transfer = schema.multisig_transfer(rejected.hash()).unwrap();
+        schema.multisig_transfers_mut().put(
+            &rejected.hash(),
+            MultisignatureTransfer {
+                refunded: false,
+                ..transfer
+            },
+        );
+        schema.schema_version_entry_mut().set(8);
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.schema_version(), SCHEMA_VERSION);
+    assert!(schema.multisig_transfer(rejected.hash()).unwrap().refunded);
+    assert!(!schema.multisig_transfer(done.hash()).unwrap().refunded);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 80);
+}
+
+#[test]
+fn test_multisig_list_cursor_after_collection() {
+    let (mut testkit, api) = create_testkit_with(Config {
+        settled_multisig_retention: Some(2),
+        ..Config::default()
+    });
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (carol, carol_key) = crypto::gen_keypair();
+    let txs = (0..3)
+        .map(|seed| {
+            TransferMultisig::sign(
+                tx_alice.author(),
+                &key_alice,
+                tx_bob.author(),
+                [carol].iter().cloned().collect(),
+                10,
+                seed,
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut transfers = txs.iter().map(|tx| tx.hash()).collect::<Vec<_>>();
+    transfers.sort();
+    testkit.create_block_with_transactions(txs);
+
+    // The first transfer of the listing is settled a block earlier than the others.
+    testkit.create_block_with_transactions(vec![ApproveTransferMultisig::sign(
+        carol,
+        &carol_key,
+        transfers[0],
+    )]);
+    let first_page = api.multisig_list_page(State::Done, None, Some(1)).unwrap();
+    assert_eq!(first_page.tx_hashes, vec![transfers[0]]);
+    testkit.create_block_with_transactions(vec![
+        ApproveTransferMultisig::sign(carol, &carol_key, transfers[1]),
+        ApproveTransferMultisig::sign(carol, &carol_key, transfers[2]),
+    ]);
+
+    let collected = |testkit: &TestKit, tx_hash| {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot).multisig_transfer(tx_hash).is_none()
+    };
+    while !collected(&testkit, transfers[0]) {
+        testkit.create_block();
+    }
+    assert!(!collected(&testkit, transfers[1]));
+
+    // The cursor pointing to the collected transfer is still accepted.
+    let second_page = api
+        .multisig_list_page(State::Done, first_page.next_cursor, Some(1))
+        .unwrap();
+    assert_eq!(second_page.tx_hashes, vec![transfers[1]]);
+    let third_page = api
+        .multisig_list_page(State::Done, second_page.next_cursor, Some(1))
+        .unwrap();
+    assert_eq!(third_page.tx_hashes, vec![transfers[2]]);
+    assert_eq!(third_page.next_cursor, None);
+}
+
+#[test]
+fn test_multisig_receiver_missing() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let (carol, carol_key) = crypto::gen_keypair();
+    let (dave, dave_key) = crypto::gen_keypair();
+    let transfer = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol, dave].iter().cloned().collect(),
+        10,
+        0,
+    );
+    let approval = ApproveTransferMultisig::sign(carol, &carol_key, transfer.hash());
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+    testkit.create_block_with_transactions(vec![approval.clone()]);
+    assert_tx_status(&api, approval.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 90);
+
+    // The receiver's wallet vanishes between the reservation and the final approval.
+    let mut fork = testkit.blockchain().fork();
+    Schema::new(&mut fork).wallets_mut().remove(&bob);
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+
+    let approval = ApproveTransferMultisig::sign(dave, &dave_key, transfer.hash());
+    testkit.create_block_with_transactions(vec![approval.clone()]);
+    assert_tx_status(&api, approval.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 100);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let cancelled = schema.multisig_transfer(transfer.hash()).unwrap();
+    assert_eq!(cancelled.state, State::Cancelled);
+    assert_eq!(cancelled.settled_at_height, Height(4));
+    assert_eq!(cancelled.progress(), (2, 2));
+    assert!(cancelled.refunded);
+    assert!(cancelled.is_terminal());
+    assert!(schema.wallet(&bob).is_none());
+
+    let template = api.multisig_template(transfer.hash()).unwrap();
+    assert_eq!(template.state, State::Cancelled);
+    assert!(!template.awaiting_approval);
+
+    // Cancelled transfers can be neither rejected nor refunded again.
+    let rejection = RejectTransferMultisig::sign(carol, &carol_key, transfer.hash());
+    testkit.create_block_with_transactions(vec![rejection.clone()]);
+    assert_tx_status(
+        &api,
+        rejection.hash(),
+        &json!({ "type": "error", "code": 18, "description": "Transfer is cancelled" }),
+    );
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 100);
+
+    let mut fork = testkit.blockchain().fork();
+    assert_eq!(
+        Schema::new(&mut fork).settle_multisig_refund(transfer.hash()),
+        Err(AlreadySettled {
+            tx_hash: transfer.hash()
+        })
+    );
+}
+
+#[test]
+fn test_due_items_processing() {
+    let run = || {
+        let mut testkit = TestKitBuilder::validator()
+            .with_validators(4)
+            .with_service(Service::default())
+            .create();
+        let api = CryptocurrencyClient::new(testkit.api());
+        let keypair =
+            |seed| crypto::gen_keypair_from_seed(&crypto::Seed::new([seed; crypto::SEED_LENGTH]));
+        let ((alice, key_alice), (bob, key_bob)) = (keypair(1), keypair(2));
+        let (carol, carol_key) = keypair(3);
+        testkit.create_block_with_transactions(vec![
+            CreateWallet::sign(ALICE_NAME, &alice, &key_alice),
+            CreateWallet::sign(BOB_NAME, &bob, &key_bob),
+        ]);
+
+        // The transfers are scheduled to expire out of the order of their seeds.
+        let expires_at = Height(4);
+        let transfers = (0..4)
+            .map(|seed| {
+                let tx = TransferMultisig::new(bob, [carol].iter().cloned().collect(), 10, seed)
+                    .with_expiration(expires_at);
+                Message::sign_transaction(tx, CRYPTOCURRENCY_SERVICE_ID, alice, &key_alice)
+            })
+            .collect::<Vec<_>>();
+        testkit.create_block_with_transactions(vec![
+            transfers[2].clone(),
+            transfers[0].clone(),
+            transfers[3].clone(),
+            transfers[1].clone(),
+        ]);
+        assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 60);
+
+        // The approved transfer is settled before it expires.
+        let approval = ApproveTransferMultisig::sign(carol, &carol_key, transfers[3].hash());
+        testkit.create_block_with_transactions(vec![approval.clone()]);
+        assert_tx_status(&api, approval.hash(), &json!({ "type": "success" }));
+        assert_eq!(
+            Schema::new(&testkit.snapshot()).due_items(expires_at).len(),
+            4
+        );
+
+        testkit.create_block();
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert!(schema.due_items(expires_at).is_empty());
+        let states = transfers
+            .iter()
+            .map(|tx| schema.multisig_transfer(tx.hash()).unwrap())
+            .map(|transfer| (transfer.state, transfer.refunded))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                (State::Expired, true),
+                (State::Expired, true),
+                (State::Expired, true),
+                (State::Done, false),
+            ]
+        );
+        assert_eq!(schema.wallet(&alice).unwrap().balance, 90);
+        assert_eq!(schema.wallet(&bob).unwrap().balance, 110);
+
+        // Refunds are recorded in the order of scheduling.
+        let refunds = schema
+            .wallet_history_from(&alice, 0)
+            .skip(5)
+            .map(|record| (record.tx_hash, record.height))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            refunds,
+            vec![
+                (transfers[2].hash(), expires_at),
+                (transfers[0].hash(), expires_at),
+                (transfers[1].hash(), expires_at),
+            ]
+        );
+        schema.state_hash()
+    };
+
+    // Nodes processing the same blocks end up in the same state.
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_due_items_catch_up() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, _) = crypto::gen_keypair();
+    let tx = TransferMultisig::new(bob, [carol].iter().cloned().collect(), 10, 0)
+        .with_expiration(Height(3));
+    let transfer = Message::sign_transaction(tx, CRYPTOCURRENCY_SERVICE_ID, alice, &key_alice);
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+    assert_eq!(
+        Schema::new(&testkit.snapshot()).due_items_processed(),
+        Some(Height(2))
+    );
+
+    // The items of a block whose `before_commit` was rolled back are processed
+    // by the next block.
+    let mut fork = testkit.blockchain().fork();
+    Schema::new(&mut fork).process_due_items(Height(4));
+    let schema = Schema::new(&fork);
+    assert!(schema.due_items(Height(3)).is_empty());
+    assert_eq!(schema.due_items_processed(), Some(Height(4)));
+    let expired = schema.multisig_transfer(transfer.hash()).unwrap();
+    assert_eq!(expired.state, State::Expired);
+    assert_eq!(expired.settled_at_height, Height(4));
+    assert_eq!(schema.wallet(&alice).unwrap().balance, 100);
+}
+
+#[test]
+fn test_multisig_expiry_refund_failure() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, _) = crypto::gen_keypair();
+    let tx = TransferMultisig::new(bob, [carol].iter().cloned().collect(), 10, 0)
+        .with_expiration(Height(3));
+    let transfer = Message::sign_transaction(tx, CRYPTOCURRENCY_SERVICE_ID, alice, &key_alice);
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+
+    // The refund would overflow the balance of the sender.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let wallet = schema.wallet(&alice).unwrap();
+        let wallet = Wallet {
+            balance: Amount::MAX,
+            ..wallet
+        };
+        schema.wallets_mut().put(&alice, wallet);
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+
+    // The amount is removed from the total supply rather than silently dropped.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let expired = schema.multisig_transfer(transfer.hash()).unwrap();
+    assert_eq!(expired.state, State::Expired);
+    assert!(expired.refund_failed);
+    assert!(!expired.refunded);
+    assert_eq!(schema.wallet(&alice).unwrap().balance, Amount::MAX);
+    assert_eq!(schema.total_supply(), Amount::from(190));
+    assert!(
+        block_events(&*snapshot, Height(3)).contains(&ServiceEvent::MultisigRefundFailed {
+            height: Height(3),
+            tx_hash: transfer.hash(),
+            from: alice,
+            amount: Amount::from(10),
+        })
+    );
+
+    // The transfer cannot be refunded afterwards.
+    let mut fork = testkit.blockchain().fork();
+    assert!(Schema::new(&mut fork)
+        .settle_multisig_refund(transfer.hash())
+        .is_err());
+}
+
+#[test]
+fn test_wallet_balance_bounds() {
+    let (pub_key, _) = crypto::gen_keypair();
+    // Amounts are given in the smallest fractions of a unit.
+    let raw = Amount::from_raw;
+    let wallet = |balance| {
+        Wallet::new(
+            &pub_key,
+            ALICE_NAME,
+            raw(balance),
+            0,
+            &Hash::zero(),
+            &Hash::zero(),
+            Height(0),
+            Height(0),
+        )
+    };
+    let max = u128::max_value();
+
+    assert_eq!(wallet(0).try_increase_balance(raw(0)), Ok(wallet(0)));
+    assert_eq!(wallet(0).try_increase_balance(raw(max)), Ok(wallet(max)));
+    assert_eq!(
+        wallet(1).try_increase_balance(raw(max - 1)),
+        Ok(wallet(max))
+    );
+    assert_eq!(wallet(max).try_increase_balance(raw(0)), Ok(wallet(max)));
+    assert_eq!(
+        wallet(1).try_increase_balance(raw(max)),
+        Err(BalanceError::Overflow)
+    );
+    assert_eq!(
+        wallet(max).try_increase_balance(raw(1)),
+        Err(BalanceError::Overflow)
+    );
+
+    assert_eq!(wallet(0).try_decrease_balance(raw(0)), Ok(wallet(0)));
+    assert_eq!(wallet(max).try_decrease_balance(raw(max)), Ok(wallet(0)));
+    assert_eq!(wallet(10).try_decrease_balance(raw(10)), Ok(wallet(0)));
+    assert_eq!(wallet(10).try_decrease_balance(raw(3)), Ok(wallet(7)));
+    assert_eq!(
+        wallet(0).try_decrease_balance(raw(1)),
+        Err(BalanceError::Insufficient)
+    );
+    assert_eq!(
+        wallet(max - 1).try_decrease_balance(raw(max)),
+        Err(BalanceError::Insufficient)
+    );
+
+    // The overdraft is spent once the balance is exhausted, and the incoming funds
+    // repay it first.
+    let overdrawn = |balance, limit, owed| Wallet {
+        overdraft_limit: raw(limit),
+        owed: raw(owed),
+        ..wallet(balance)
+    };
+    assert_eq!(wallet(10).spendable(), raw(10));
+    assert_eq!(overdrawn(10, 5, 0).spendable(), raw(15));
+    assert_eq!(overdrawn(0, 5, 3).spendable(), raw(2));
+    assert_eq!(
+        overdrawn(10, 5, 0).try_decrease_balance(raw(3)),
+        Ok(overdrawn(7, 5, 0))
+    );
+    assert_eq!(
+        overdrawn(10, 5, 0).try_decrease_balance(raw(15)),
+        Ok(overdrawn(0, 5, 5))
+    );
+    assert_eq!(
+        overdrawn(0, 5, 3).try_decrease_balance(raw(2)),
+        Ok(overdrawn(0, 5, 5))
+    );
+    assert_eq!(
+        overdrawn(10, 5, 0).try_decrease_balance(raw(16)),
+        Err(BalanceError::Insufficient)
+    );
+    assert_eq!(
+        overdrawn(0, 5, 3).try_increase_balance(raw(2)),
+        Ok(overdrawn(0, 5, 1))
+    );
+    assert_eq!(
+        overdrawn(0, 5, 3).try_increase_balance(raw(10)),
+        Ok(overdrawn(7, 5, 0))
+    );
+    assert_eq!(
+        overdrawn(0, 5, 5).try_increase_balance(raw(max)),
+        Ok(overdrawn(max - 5, 5, 0))
+    );
+    assert_eq!(
+        overdrawn(0, 5, 5).try_stake(raw(1)),
+        Err(BalanceError::Insufficient)
+    );
+
+    assert_eq!(Error::from(BalanceError::Overflow), Error::SupplyOverflow);
+    assert_eq!(
+        Error::from(BalanceError::Insufficient),
+        Error::InsufficientCurrencyAmount
+    );
+}
+
+#[test]
+fn test_multisig_transfer_state_machine() {
+    #[derive(Clone, Copy, Debug)]
+    enum Action {
+        Approve(usize),
+        Reject(usize),
+    }
+
+    let keys = (0..6).map(|_| crypto::gen_keypair().0).collect::<Vec<_>>();
+    // Approvers are `keys[2..5]`; `keys[5]` is not on the list.
+    let actions = (2..6)
+        .map(Action::Approve)
+        .chain((2..6).map(Action::Reject))
+        .collect::<Vec<_>>();
+
+    for &(threshold, required) in &[(0, 3), (2, 2)] {
+        for &expires_at in &[Height(0), Height(3)] {
+            for first in &actions {
+                for second in &actions {
+                    for third in &actions {
+                        let mut transfer = MultisignatureTransfer::new(
+                            keys[0],
+                            keys[1],
+                            Amount::from(10),
+                            keys[2..5].to_vec(),
+                            threshold,
+                            expires_at,
+                        );
+                        assert_eq!(transfer.required_approvals(), required);
+                        let mut approved = HashSet::new();
+
+                        for (i, &action) in [*first, *second, *third].iter().enumerate() {
+                            let height = Height(i as u64 + 1);
+                            let expired = expires_at > Height(0) && height >= expires_at;
+                            assert_eq!(transfer.is_expired_at(height), expired);
+                            let (key, result) = match action {
+                                Action::Approve(key) => {
+                                    (key, transfer.clone().approve(keys[key], height))
+                                }
+                                Action::Reject(key) => {
+                                    (key, transfer.clone().reject(keys[key], height))
+                                }
+                            };
+                            let error = match transfer.state {
+                                State::Done => Some(MultisigError::Done),
+                                State::Rejected => Some(MultisigError::Rejected),
+                                State::Expired => Some(MultisigError::Expired),
+                                State::InProcess if expired => Some(MultisigError::Expired),
+                                State::InProcess if key == 5 => {
+                                    Some(MultisigError::NotOnApproversList)
+                                }
+                                State::InProcess => match action {
+                                    Action::Approve(_) if approved.contains(&key) => {
+                                        Some(MultisigError::AlreadyApproved)
+                                    }
+                                    _ => None,
+                                },
+                            };
+                            if let Some(error) = error {
+                                assert_eq!(result, Err(error), "{:?}", action);
+                                continue;
+                            }
+
+                            let next = result.unwrap();
+                            match action {
+                                Action::Approve(_) => {
+                                    approved.insert(key);
+                                    assert_eq!(
+                                        next.approved_by.len(),
+                                        transfer.approved_by.len() + 1
+                                    );
+                                    if approved.len() >= required {
+                                        assert_eq!(next.state, State::Done);
+                                        assert_eq!(next.settled_at_height, height);
+                                    } else {
+                                        assert_eq!(next.state, State::InProcess);
+                                    }
+                                }
+                                Action::Reject(_) => {
+                                    assert_eq!(next.state, State::Rejected);
+                                    assert_eq!(next.rejection(), Some((keys[key], height)));
+                                    assert_eq!(next.settled_at_height, height);
+                                }
+                            }
+                            transfer = next;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_multisig_transfer_progress() {
+    let keys = (0..5).map(|_| crypto::gen_keypair().0).collect::<Vec<_>>();
+    let (carol, dave, stranger) = (keys[2], keys[3], keys[4]);
+
+    // Duplicate approvers are required to approve once.
+    let transfer = MultisignatureTransfer::new(
+        keys[0],
+        keys[1],
+        Amount::from(10),
+        vec![carol, carol, dave],
+        0,
+        Height(0),
+    );
+    assert_eq!(transfer.progress(), (0, 2));
+    assert_eq!(transfer.approvals_remaining(), 2);
+    let transfer = transfer.approve(carol, Height(1)).unwrap();
+    assert_eq!(transfer.progress(), (1, 2));
+    assert_eq!(
+        transfer.clone().approve(carol, Height(2)),
+        Err(MultisigError::AlreadyApproved)
+    );
+    assert_eq!(
+        transfer.clone().approve(stranger, Height(2)),
+        Err(MultisigError::NotOnApproversList)
+    );
+    let done = transfer.approve(dave, Height(2)).unwrap();
+    assert_eq!(done.progress(), (2, 2));
+    assert_eq!(done.approvals_remaining(), 0);
+    assert_eq!(done.state, State::Done);
+
+    // Approvals stored by the previous versions may repeat approvers or come from
+    // keys which are not on the list; neither of them is counted.
+    let transfer = MultisignatureTransfer::new(
+        keys[0],
+        keys[1],
+        Amount::from(10),
+        vec![carol, dave],
+        0,
+        Height(0),
+    );
+    let legacy = MultisignatureTransfer {
+        approved_by: vec![carol, carol, stranger]
+            .into_iter()
+            .map(|approver| Approval {
+                approver,
+                approved_at_height: Height(0),
+                comment: String::new(),
+            })
+            .collect(),
+        ..transfer
+    };
+    assert_eq!(legacy.approvals_count(), 1);
+    assert_eq!(legacy.progress(), (1, 2));
+    assert_eq!(legacy.approvals_remaining(), 1);
+
+    // A threshold lower than the number of approvers completes the transfer early.
+    let transfer = MultisignatureTransfer::new(
+        keys[0],
+        keys[1],
+        Amount::from(10),
+        vec![carol, dave],
+        1,
+        Height(0),
+    );
+    assert_eq!(transfer.progress(), (0, 1));
+    let done = transfer.approve(dave, Height(1)).unwrap();
+    assert_eq!(done.progress(), (1, 1));
+    assert_eq!(done.state, State::Done);
+}
+
+#[test]
+fn test_multisig_transfer_round_trip() {
+    use exonum::storage::StorageValue;
+
+    let keys = (0..4).map(|_| crypto::gen_keypair().0).collect::<Vec<_>>();
+    let pending = MultisignatureTransfer::new(
+        keys[0],
+        keys[1],
+        Amount::from(10),
+        vec![keys[2], keys[3]],
+        0,
+        Height(0),
+    );
+    let approved = pending.clone().approve(keys[2], Height(3)).unwrap();
+    let rejected = approved.clone().reject(keys[3], Height(5)).unwrap();
+    assert_eq!(pending.rejection(), None);
+    assert_eq!(approved.rejection(), None);
+    assert_eq!(rejected.rejection(), Some((keys[3], Height(5))));
+    assert_eq!(approved.settled_at_height, Height(0));
+    assert_eq!(rejected.settled_at_height, Height(5));
+
+    // Expired transfers cannot be approved or rejected.
+    let expiring = MultisignatureTransfer::new(
+        keys[0],
+        keys[1],
+        Amount::from(10),
+        vec![keys[2], keys[3]],
+        0,
+        Height(7),
+    );
+    assert_eq!(expiring.clone().expire(Height(6)), None);
+    assert_eq!(rejected.clone().expire(Height(7)), None);
+    let expired = expiring.expire(Height(8)).unwrap();
+    assert_eq!(expired.state, State::Expired);
+    assert_eq!(expired.settled_at_height, Height(8));
+    assert!(expired.is_terminal());
+    assert!(!pending.is_terminal());
+    assert_eq!(expired.clone().expire(Height(9)), None);
+    assert_eq!(
+        expired.clone().approve(keys[2], Height(6)),
+        Err(MultisigError::Expired)
+    );
+    assert_eq!(
+        expired.clone().reject(keys[2], Height(6)),
+        Err(MultisigError::Expired)
+    );
+
+    assert_eq!(
+        serde_json::to_value(&State::Expired).unwrap(),
+        json!("expired")
+    );
+    for state in vec![
+        State::InProcess,
+        State::Rejected,
+        State::Done,
+        State::Expired,
+        State::Cancelled,
+    ] {
+        assert_eq!(State::from_code(state.clone() as u8), Some(state.clone()));
+        assert_eq!(State::from_pb(state.to_pb()).unwrap(), state);
+    }
+    assert_eq!(State::from_code(5), None);
+
+    // Only done transfers can be cancelled.
+    assert_eq!(pending.clone().cancel(), None);
+    assert_eq!(rejected.clone().cancel(), None);
+    let cancelled = approved
+        .clone()
+        .approve(keys[3], Height(4))
+        .unwrap()
+        .cancel()
+        .unwrap();
+    assert_eq!(cancelled.state, State::Cancelled);
+    assert_eq!(cancelled.settled_at_height, Height(4));
+    assert_eq!(
+        cancelled.clone().reject(keys[2], Height(5)),
+        Err(MultisigError::Cancelled)
+    );
+
+    let commented = pending
+        .clone()
+        .approve_with_comment(keys[2], Height(3), "Approved".to_owned())
+        .unwrap()
+        .reject_with_comment(keys[3], Height(5), "Rejected".to_owned())
+        .unwrap();
+    assert_eq!(commented.approved_by[0].comment, "Approved");
+    assert_eq!(commented.rejection_comment, "Rejected");
+
+    for transfer in vec![pending, approved, rejected, expired, commented, cancelled] {
+        let pb = transfer.to_pb();
+        assert_eq!(MultisignatureTransfer::from_pb(pb).unwrap(), transfer);
+
+        let bytes = transfer.clone().into_bytes();
+        assert_eq!(MultisignatureTransfer::from_bytes(bytes.into()), transfer);
+
+        let json = serde_json::to_value(&transfer).unwrap();
+        assert_eq!(
+            serde_json::from_value::<MultisignatureTransfer>(json).unwrap(),
+            transfer
+        );
+    }
+}
+
+#[test]
+fn test_transfer_multisig_conditions() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, carol_key) = crypto::gen_keypair();
+    let (dave, _) = crypto::gen_keypair();
+    let approvers: HashSet<_> = [carol, dave].iter().cloned().collect();
+    let sign = |tx: TransferMultisig| {
+        Message::sign_transaction(tx, CRYPTOCURRENCY_SERVICE_ID, alice, &key_alice)
+    };
+
+    let unreachable = sign(TransferMultisig::new(bob, approvers.clone(), 10, 0).with_threshold(3));
+    let passed =
+        sign(TransferMultisig::new(bob, approvers.clone(), 10, 1).with_expiration(Height(2)));
+    let partial = sign(TransferMultisig::new(bob, approvers.clone(), 10, 2).with_threshold(1));
+    let expiring =
+        sign(TransferMultisig::new(bob, approvers.clone(), 10, 3).with_expiration(Height(4)));
+    testkit.create_block_with_transactions(vec![
+        unreachable.clone(),
+        passed.clone(),
+        partial.clone(),
+        expiring.clone(),
+    ]);
+    assert_tx_status(
+        &api,
+        unreachable.hash(),
+        &json!({ "type": "error", "code": 37, "description": "Transfer threshold is unreachable" }),
+    );
+    assert_tx_status(
+        &api,
+        passed.hash(),
+        &json!({
+            "type": "error",
+            "code": 38,
+            "description": "Transfer expiration height has passed",
+        }),
+    );
+    assert_tx_status(&api, partial.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, expiring.hash(), &json!({ "type": "success" }));
+
+    // A single approval reaches the threshold.
+    let approval = ApproveTransferMultisig::sign(carol, &carol_key, partial.hash());
+    testkit.create_block_with_transactions(vec![approval]);
+    let transfer = {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot)
+            .multisig_transfer(partial.hash())
+            .unwrap()
+    };
+    assert_eq!(transfer.state, State::Done);
+    assert_eq!(transfer.threshold, 1);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 110);
+
+    // The other transfer expires in the block at its expiration height.
+    testkit.create_block();
+    let transfer = {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot)
+            .multisig_transfer(expiring.hash())
+            .unwrap()
+    };
+    assert_eq!(transfer.state, State::Expired);
+    assert_eq!(transfer.expires_at, Height(4));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 90);
+
+    // Both fields are optional in the JSON representation.
+    let tx: TransferMultisig = serde_json::from_value(json!({
+        "to": bob.to_hex(),
+        "approvers": [carol.to_hex()],
+        "amount": "10",
+        "seed": 0,
+    }))
+    .unwrap();
+    assert_eq!((tx.threshold, tx.expires_at), (0, Height(0)));
+}
+
+#[test]
+fn test_outgoing_multisig_transfers() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let (dave_public_key, _dave_private_key) = exonum_crypto::gen_keypair();
+
+    // The first transfer needs Carol only, the others need Carol and Dave.
+    let tx_done = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key].iter().cloned().collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    let tx_pending = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        20, // transferred amount
+        1,  // seed
+    );
+    let tx_rejected = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key, dave_public_key]
+            .iter()
+            .cloned()
+            .collect(),
+        30, // transferred amount
+        2,  // seed
+    );
+    // Commit transfers in separate blocks to fix their order.
+    for tx in &[&tx_done, &tx_pending, &tx_rejected] {
+        api.transaction(tx).unwrap();
+        testkit.create_block();
+    }
+
+    let approve =
+        ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx_done.hash());
+    let approve_pending =
+        ApproveTransferMultisig::sign(carol_public_key, &carol_private_key, tx_pending.hash());
+    let reject =
+        RejectTransferMultisig::sign(carol_public_key, &carol_private_key, tx_rejected.hash());
+    api.transaction(&approve).unwrap();
+    api.transaction(&approve_pending).unwrap();
+    api.transaction(&reject).unwrap();
+    testkit.create_block();
+
+    let all = api
+        .outgoing_multisig_transfers(tx_alice.author(), None, 0, None)
+        .unwrap();
+    let hashes = all.transfers.iter().map(|t| t.tx_hash).collect::<Vec<_>>();
+    assert_eq!(
+        hashes,
+        vec![tx_done.hash(), tx_pending.hash(), tx_rejected.hash()]
+    );
+
+    let pending = &all.transfers[1];
+    assert_eq!(pending.to, tx_bob.author());
+    assert_eq!(pending.amount, 20);
+    assert_eq!(pending.approvals, 1);
+    assert_eq!(pending.state, State::InProcess);
+
+    for (state, tx_hash) in &[
+        (State::Done, tx_done.hash()),
+        (State::InProcess, tx_pending.hash()),
+        (State::Rejected, tx_rejected.hash()),
+    ] {
+        let filtered = api
+            .outgoing_multisig_transfers(tx_alice.author(), Some(state.clone()), 0, None)
+            .unwrap();
+        assert_eq!(filtered.transfers.len(), 1);
+        assert_eq!(filtered.transfers[0].tx_hash, *tx_hash);
+    }
+
+    let page = api
+        .outgoing_multisig_transfers(tx_alice.author(), None, 1, Some(1))
+        .unwrap();
+    assert_eq!(page.transfers.len(), 1);
+    assert_eq!(page.transfers[0].tx_hash, tx_pending.hash());
+
+    // Bob hasn't sent anything.
+    let bob = api
+        .outgoing_multisig_transfers(tx_bob.author(), None, 0, None)
+        .unwrap();
+    assert!(bob.transfers.is_empty());
+}
+
+#[test]
+fn test_multisig_failures() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    let (tx_carol, key_carol) = api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+    let (alice, bob, carol) = (tx_alice.author(), tx_bob.author(), tx_carol.author());
+
+    let multisig = |from, key: &SecretKey, to, approvers: &[PublicKey], amount: u64| {
+        TransferMultisig::sign(
+            from,
+            key,
+            to,
+            approvers.iter().cloned().collect(),
+            amount,
+            0,
+        )
+    };
+    // Carol is the sender of the first transfer, the receiver of the second one
+    // and the approver of the next two.
+    let from_carol = multisig(carol, &key_carol, bob, &[alice], 10);
+    let to_carol = multisig(alice, &key_alice, carol, &[bob], 20);
+    let rejected_by_carol = multisig(alice, &key_alice, bob, &[carol], 30);
+    let expired = multisig(bob, &key_bob, alice, &[carol], 40);
+    let done = multisig(carol, &key_carol, bob, &[alice], 5);
+    let pending = multisig(alice, &key_alice, bob, &[bob, carol], 15);
+    testkit.create_block_with_transactions(vec![
+        from_carol.clone(),
+        to_carol.clone(),
+        rejected_by_carol.clone(),
+        expired.clone(),
+        done.clone(),
+        pending.clone(),
+    ]);
+
+    // The transfers are settled in separate blocks to fix their order.
+    testkit.create_block_with_transaction(RejectTransferMultisig::sign(
+        carol,
+        &key_carol,
+        rejected_by_carol.hash(),
+    ));
+    testkit.create_block_with_transactions(vec![
+        RejectTransferMultisig::sign(bob, &key_bob, to_carol.hash()),
+        ApproveTransferMultisig::sign(alice, &key_alice, done.hash()),
+    ]);
+    testkit.create_block_with_transaction(RejectTransferMultisig::sign(
+        alice,
+        &key_alice,
+        from_carol.hash(),
+    ));
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let transfer = schema.multisig_transfer(expired.hash()).unwrap();
+        schema.update_transfer_multisig(
+            expired.hash(),
+            MultisignatureTransfer {
+                expires_at: Height(6),
+                ..transfer
+            },
+        );
+        schema.schedule_at(Height(6), DueItem::multisig_expiry(expired.hash()));
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+
+    let failures = api.multisig_failures(carol, 0, None).unwrap().transfers;
+    assert_eq!(
+        failures.iter().map(|t| t.tx_hash).collect::<Vec<_>>(),
+        vec![
+            rejected_by_carol.hash(),
+            to_carol.hash(),
+            from_carol.hash(),
+            expired.hash(),
+        ]
+    );
+    assert_eq!(
+        failures[0],
+        MultisigFailure {
+            tx_hash: rejected_by_carol.hash(),
+            from: alice,
+            to: bob,
+            amount: Amount::from(30),
+            state: State::Rejected,
+            rejected_by: Some(carol),
+            settled_at_height: Height(3),
+            expires_at: Height(0),
+        }
+    );
+    assert_eq!(failures[1].rejected_by, Some(bob));
+    assert_eq!(failures[2].rejected_by, Some(alice));
+    assert_eq!(failures[2].settled_at_height, Height(5));
+    assert_eq!(failures[3].state, State::Expired);
+    assert_eq!(failures[3].rejected_by, None);
+    assert_eq!(failures[3].settled_at_height, Height(6));
+    assert_eq!(failures[3].expires_at, Height(6));
+    let json = serde_json::to_value(&failures[3]).unwrap();
+    assert!(json.get("rejected_by").is_none());
+
+    // The other wallets take part in the same failed transfers in other roles.
+    for pub_key in &[alice, bob] {
+        let failures = api.multisig_failures(*pub_key, 0, None).unwrap().transfers;
+        assert_eq!(failures.len(), 4);
+    }
+    assert!(api
+        .multisig_failures(crypto::gen_keypair().0, 0, None)
+        .unwrap()
+        .transfers
+        .is_empty());
+
+    let page = api.multisig_failures(carol, 1, Some(2)).unwrap().transfers;
+    assert_eq!(
+        page.iter().map(|t| t.tx_hash).collect::<Vec<_>>(),
+        vec![to_carol.hash(), from_carol.hash()]
+    );
+    let err = api
+        .multisig_failures(carol, 0, Some(MAX_ITEMS_PER_REQUEST + 1))
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+
+    // The receiver and approver sets keep the settled transfers, unlike the pending sets.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(
+        schema
+            .incoming_multisig_transfers(&carol)
+            .iter()
+            .collect::<HashSet<_>>(),
+        vec![to_carol.hash()].into_iter().collect()
+    );
+    assert_eq!(
+        schema
+            .approver_multisig_transfers(&carol)
+            .iter()
+            .collect::<HashSet<_>>(),
+        vec![rejected_by_carol.hash(), expired.hash(), pending.hash()]
+            .into_iter()
+            .collect()
+    );
+    assert_eq!(
+        schema
+            .approver_pending(&carol)
+            .iter()
+            .collect::<HashSet<_>>(),
+        vec![pending.hash()].into_iter().collect()
+    );
+
+    // Collected transfers are removed from the sets.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        schema.collect_settled_multisig_transfers(Height(10), 0);
+        assert!(schema
+            .incoming_multisig_transfers(&carol)
+            .iter()
+            .next()
+            .is_none());
+        assert_eq!(
+            schema
+                .approver_multisig_transfers(&carol)
+                .iter()
+                .collect::<HashSet<_>>(),
+            vec![pending.hash()].into_iter().collect()
+        );
+        assert!(schema.multisig_failures(&carol).is_empty());
+    }
+
+    // The sets are filled in for the transfers created before they were introduced.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for pub_key in &[alice, bob, carol] {
+            schema.incoming_multisig_transfers_mut(pub_key).clear();
+            schema.approver_multisig_transfers_mut(pub_key).clear();
+        }
+        schema.schema_version_entry_mut().set(10);
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+    assert_eq!(
+        api.multisig_failures(carol, 0, None).unwrap().transfers,
+        failures
+    );
+}
+
+#[test]
+fn test_transfers_by_seed() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let first = Transfer::sign(&alice, &bob, 10, 7, &key_alice);
+    api.transaction(&first).unwrap();
+    // Same seed for another author does not interfere.
+    api.transaction(&Transfer::sign(&bob, &alice, 10, 7, &key_bob))
+        .unwrap();
+    testkit.create_block();
+
+    // The seed is reused for a different payload.
+    let second = Transfer::sign(&alice, &bob, 20, 7, &key_alice);
+    let (approver, _) = crypto::gen_keypair();
+    let multisig = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [approver].iter().cloned().collect(),
+        5,
+        8,
+    );
+    // Failed transfers are not indexed.
+    let failed = Transfer::sign(&alice, &bob, 1_000, 9, &key_alice);
+    api.transaction(&second).unwrap();
+    api.transaction(&multisig).unwrap();
+    api.transaction(&failed).unwrap();
+    testkit.create_block();
+
+    let transfers = api.transfers_by_seed(alice, 7).unwrap().transfers;
+    assert_eq!(
+        transfers,
+        vec![
+            SeedTransfer {
+                tx_hash: first.hash(),
+                height: Height(2),
+                multisig_state: None,
+            },
+            SeedTransfer {
+                tx_hash: second.hash(),
+                height: Height(3),
+                multisig_state: None,
+            },
+        ]
+    );
+
+    let transfers = api.transfers_by_seed(alice, 8).unwrap().transfers;
+    assert_eq!(
+        transfers,
+        vec![SeedTransfer {
+            tx_hash: multisig.hash(),
+            height: Height(3),
+            multisig_state: Some(State::InProcess),
+        }]
+    );
+
+    assert!(api
+        .transfers_by_seed(alice, 9)
+        .unwrap()
+        .transfers
+        .is_empty());
+    assert!(api.transfers_by_seed(bob, 8).unwrap().transfers.is_empty());
+}
+
+#[test]
+fn test_metrics() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    api.transaction(&Transfer::sign(&alice, &bob, 10, 0, &key_alice))
+        .unwrap();
+    // Insufficient currency amount (code 3).
+    api.transaction(&Transfer::sign(&alice, &bob, 1_000, 1, &key_alice))
+        .unwrap();
+    api.transaction(&Transfer::sign(&alice, &bob, 2_000, 2, &key_alice))
+        .unwrap();
+    // Wallet already exists (code 0).
+    api.transaction(&CreateWallet::sign("Alice 2", &alice, &key_alice))
+        .unwrap();
+    testkit.create_block();
+
+    let private_api = api.transport().private(ApiKind::Service("cryptocurrency"));
+    let metrics: ServiceMetrics = private_api.get("v1/metrics/json").unwrap();
+    assert_eq!(
+        metrics.transactions,
+        vec![("CreateWallet".to_owned(), 2), ("Transfer".to_owned(), 1)]
+            .into_iter()
+            .collect()
+    );
+    assert_eq!(metrics.errors, vec![(0, 1), (3, 2)].into_iter().collect());
+
+    let response = private_api.get_raw("v1/metrics", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.headers["content-type"], PROMETHEUS_CONTENT_TYPE);
+    let text = String::from_utf8(response.body).unwrap();
+    let samples = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        samples,
+        vec![
+            "cryptocurrency_transactions_total{type=\"CreateWallet\"} 2",
+            "cryptocurrency_transactions_total{type=\"Transfer\"} 1",
+            "cryptocurrency_transaction_errors_total{code=\"0\"} 1",
+            "cryptocurrency_transaction_errors_total{code=\"3\"} 2",
+        ]
+    );
+
+    // Metrics are not exposed publicly.
+    let err = api
+        .inner
+        .public(ApiKind::Service("cryptocurrency"))
+        .get::<ServiceMetrics>("v1/metrics/json")
+        .unwrap_err();
+    assert_matches!(err, api::Error::NotFound(_));
+}
+
+#[test]
+fn test_wallet_activity_heights() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    let (tx_carol, _) = api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+    let (alice, bob, carol) = (tx_alice.author(), tx_bob.author(), tx_carol.author());
+    let heights = |pub_key| {
+        let wallet = api.get_wallet(pub_key).unwrap().unwrap();
+        (wallet.created_at.0, wallet.last_active_at.0)
+    };
+    assert_eq!(heights(alice), (1, 1));
+
+    testkit.create_block();
+    let (approver, approver_key) = crypto::gen_keypair();
+    let multisig = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        carol,
+        [approver].iter().cloned().collect(),
+        10,
+        1,
+    );
+    testkit.create_block_with_transactions(vec![
+        Transfer::sign(&alice, &bob, 10, 0, &key_alice),
+        multisig.clone(),
+    ]);
+    assert_eq!(heights(alice), (1, 3));
+    assert_eq!(heights(bob), (1, 3));
+    assert_eq!(heights(carol), (1, 1));
+
+    // The receiver of a multisignature transfer is changed once the transfer is approved.
+    testkit.create_block_with_transactions(vec![ApproveTransferMultisig::sign(
+        approver,
+        &approver_key,
+        multisig.hash(),
+    )]);
+    assert_eq!(heights(alice), (1, 3));
+    assert_eq!(heights(carol), (1, 4));
+
+    // Wallets stored without the last activity height get it from their history.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for pub_key in &[alice, bob, carol] {
+            let wallet = schema.wallet(pub_key).unwrap();
+            schema.wallets_mut().put(
+                pub_key,
+                Wallet {
+                    last_active_at: Height(0),
+                    ..wallet
+                },
+            );
+        }
+        schema.last_activity_filled_entry_mut().remove();
+        schema.schema_version_entry_mut().remove();
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+    assert_eq!(heights(alice), (1, 3));
+    assert_eq!(heights(bob), (1, 3));
+    assert_eq!(heights(carol), (1, 4));
+}
+
+#[test]
+fn test_wallet_last_tx_hash() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let last_tx_hash = |pub_key| api.get_wallet(pub_key).unwrap().unwrap().last_tx_hash;
+    assert_eq!(last_tx_hash(alice), tx_alice.hash());
+
+    let (approver, approver_key) = crypto::gen_keypair();
+    let multisig = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [approver].iter().cloned().collect(),
+        10,
+        1,
+    );
+    testkit.create_block_with_transactions(vec![multisig.clone()]);
+    assert_eq!(last_tx_hash(alice), multisig.hash());
+    assert_eq!(last_tx_hash(bob), tx_bob.hash());
+
+    let approve = ApproveTransferMultisig::sign(approver, &approver_key, multisig.hash());
+    testkit.create_block_with_transactions(vec![approve.clone()]);
+    assert_eq!(last_tx_hash(alice), multisig.hash());
+    assert_eq!(last_tx_hash(bob), approve.hash());
+    assert_eq!(
+        api.wallet_history_head(bob).unwrap().latest_entry_hash,
+        Some(approve.hash())
+    );
+
+    // Wallets written by the previous versions of the service get the hash
+    // from their history.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for pub_key in &[alice, bob] {
+            let mut pb = schema.wallet(pub_key).unwrap().to_pb();
+            pb.clear_last_tx_hash();
+            let bytes = protobuf::Message::write_to_bytes(&pb).unwrap();
+            schema.raw_wallets_mut().put(pub_key, bytes);
+        }
+        schema.legacy_wallets_migrated_entry_mut().remove();
+        schema.schema_version_entry_mut().remove();
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+    assert_eq!(last_tx_hash(alice), multisig.hash());
+    assert_eq!(last_tx_hash(bob), approve.hash());
+    let snapshot = testkit.snapshot();
+    assert!(Schema::new(&snapshot).legacy_wallets_migrated());
+}
+
+#[test]
+fn test_wallet_metadata() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    assert_eq!(
+        api.get_wallet(alice).unwrap().unwrap().metadata_hash,
+        Hash::zero()
+    );
+
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        schema
+            .set_wallet_metadata(&alice, "nick", Some("alice"))
+            .unwrap();
+        schema
+            .set_wallet_metadata(&alice, "email", Some("alice@example.com"))
+            .unwrap();
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+
+    // Verifies the wallet proof against the latest block and returns the proven wallet
+    // together with the root hash of the wallets table.
+    let proven_wallet = |testkit: &TestKit| {
+        let state_hash = *blockchain::Schema::new(&testkit.snapshot())
+            .last_block()
+            .state_hash();
+        let existence = api.wallet_exists(alice).unwrap();
+        let to_table = existence.wallet_proof.to_table.check().unwrap();
+        assert_eq!(to_table.merkle_root(), state_hash);
+        let to_wallet = existence.wallet_proof.to_wallet.check().unwrap();
+        let wallet = to_wallet.entries().next().unwrap().1.clone();
+        (wallet, to_wallet.merkle_root())
+    };
+    let metadata_hash_of = |entries: &[(&str, &str)]| {
+        metadata_hash(
+            entries
+                .iter()
+                .map(|&(key, value)| (key.to_owned(), value.to_owned())),
+        )
+    };
+
+    let (wallet, wallets_root) = proven_wallet(&testkit);
+    let expected_hash = metadata_hash_of(&[("email", "alice@example.com"), ("nick", "alice")]);
+    assert_eq!(wallet.metadata_hash, expected_hash);
+    {
+        let snapshot = testkit.snapshot();
+        assert_eq!(
+            Schema::new(&snapshot).wallet_metadata_hash(&alice),
+            expected_hash
+        );
+    }
+
+    // Metadata tampered with bypassing the schema no longer matches the proven hash.
+    let mut fork = testkit.blockchain().fork();
+    Schema::new(&mut fork)
+        .wallet_metadata_mut(&alice)
+        .put(&"nick".to_owned(), "mallory".to_owned());
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    {
+        let snapshot = testkit.snapshot();
+        assert_ne!(
+            Schema::new(&snapshot).wallet_metadata_hash(&alice),
+            wallet.metadata_hash
+        );
+    }
+
+    // Changing the metadata through the schema changes the wallet proof.
+    let mut fork = testkit.blockchain().fork();
+    Schema::new(&mut fork)
+        .set_wallet_metadata(&alice, "nick", Some("mallory"))
+        .unwrap();
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+    let (wallet, changed_root) = proven_wallet(&testkit);
+    assert_ne!(changed_root, wallets_root);
+    assert_eq!(
+        wallet.metadata_hash,
+        metadata_hash_of(&[("email", "alice@example.com"), ("nick", "mallory")])
+    );
+
+    // The total size of the metadata is bounded.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let large_value = "x".repeat(MAX_METADATA_SIZE);
+        let err = schema
+            .set_wallet_metadata(&alice, "avatar", Some(&large_value))
+            .unwrap_err();
+        assert_eq!(
+            err.size,
+            "email".len() + 17 + "nick".len() + 7 + 6 + MAX_METADATA_SIZE
+        );
+        assert!(schema
+            .wallet_metadata(&alice)
+            .get(&"avatar".to_owned())
+            .is_none());
+
+        schema.set_wallet_metadata(&alice, "nick", None).unwrap();
+        schema.set_wallet_metadata(&alice, "email", None).unwrap();
+        assert_eq!(schema.wallet(&alice).unwrap().metadata_hash, Hash::zero());
+    }
+}
+
+#[test]
+fn test_schema_migration() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    api.transaction(&Transfer::sign(&alice, &bob, 10, 0, &key_alice))
+        .unwrap();
+    testkit.create_block();
+    {
+        let snapshot = testkit.snapshot();
+        assert_eq!(Schema::new(&snapshot).schema_version(), SCHEMA_VERSION);
+    }
+
+    // Rewrite the data in the layout of version 0: wallets lack the fields introduced
+    // since then, and there is no balance index.
+    let expected = {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        schema.wallets().values().collect::<Vec<_>>()
+    };
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for wallet in &expected {
+            let mut pb = wallet.to_pb();
+            pb.clear_created_at();
+            pb.clear_last_active_at();
+            pb.clear_last_tx_hash();
+            pb.clear_history_chain();
+            let bytes = protobuf::Message::write_to_bytes(&pb).unwrap();
+            schema.raw_wallets_mut().put(&wallet.pub_key, bytes);
+        }
+        schema.balance_index_mut().clear();
+        schema.legacy_history_migrated_entry_mut().remove();
+        schema.legacy_wallets_migrated_entry_mut().remove();
+        schema.legacy_multisig_migrated_entry_mut().remove();
+        schema.balance_index_built_entry_mut().remove();
+        schema.last_activity_filled_entry_mut().remove();
+        schema.schema_version_entry_mut().remove();
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.schema_version(), SCHEMA_VERSION);
+    // Creation heights are not known for wallets of version 0.
+    for wallet in expected {
+        let pub_key = wallet.pub_key;
+        assert_eq!(
+            schema.wallet(&pub_key).unwrap(),
+            Wallet {
+                created_at: Height(0),
+                ..wallet
+            }
+        );
+    }
+    let mut balances = schema.wallets_by_balance().collect::<Vec<_>>();
+    balances.sort();
+    let mut expected_balances = vec![(alice, Amount::from(90)), (bob, Amount::from(110))];
+    expected_balances.sort();
+    assert_eq!(balances, expected_balances);
+
+    // Proofs of the migrated wallets check out against the state hash of the latest
+    // block and against the recomputed root of the wallets table.
+    let state_hash = *blockchain::Schema::new(&snapshot).last_block().state_hash();
+    let existence = api.wallet_exists(alice).unwrap();
+    assert!(existence.exists);
+    let to_table = existence.wallet_proof.to_table.check().unwrap();
+    assert_eq!(to_table.merkle_root(), state_hash);
+    let (_, wallets_root) = to_table.entries().next().unwrap();
+    assert_eq!(*wallets_root, schema.state_hash()[0]);
+    let to_wallet = existence.wallet_proof.to_wallet.check().unwrap();
+    assert_eq!(to_wallet.merkle_root(), *wallets_root);
+    let (_, wallet) = to_wallet.entries().next().unwrap();
+    assert_eq!(*wallet, schema.wallet(&alice).unwrap());
+}
+
+#[test]
+fn test_created_wallets() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    let (tx_carol, _) = api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+    testkit.create_block();
+    let (tx_dave, _) = api.create_wallet("Dave").unwrap();
+    testkit.create_block();
+
+    // Wallets created before the creation height was introduced are stored
+    // without it and are not listed by the endpoint.
+    let (legacy_key, _) = crypto::gen_keypair();
+    let mut fork = testkit.blockchain().fork();
+    Schema::new(&mut fork).wallets_mut().put(
+        &legacy_key,
+        Wallet::new(
+            &legacy_key,
+            "Legacy",
+            Amount::from(100),
+            0,
+            &Hash::zero(),
+            &Hash::zero(),
+            Height(0),
+            Height(0),
+        ),
+    );
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    assert_eq!(
+        api.get_wallet(legacy_key).unwrap().unwrap().created_at,
+        Height(0)
+    );
+    assert_eq!(
+        api.get_wallet(tx_dave.author())
+            .unwrap()
+            .unwrap()
+            .created_at,
+        Height(4)
+    );
+
+    let keys = |wallets: &[CreatedWallet]| {
+        wallets
+            .iter()
+            .map(|wallet| wallet.pub_key)
+            .collect::<Vec<_>>()
+    };
+
+    let created = api.created_wallets(0, None, None, None).unwrap();
+    assert_eq!(created.total, 4);
+    assert_eq!(
+        created.wallets[0],
+        CreatedWallet {
+            pub_key: tx_alice.author(),
+            name: ALICE_NAME.to_owned(),
+            created_at: Height(1),
+        }
+    );
+    assert_eq!(created.wallets[3].pub_key, tx_dave.author());
+    let mut same_block = keys(&created.wallets[1..3]);
+    same_block.sort();
+    let mut expected = vec![tx_bob.author(), tx_carol.author()];
+    expected.sort();
+    assert_eq!(same_block, expected);
+
+    let created = api.created_wallets(2, Some(3), None, None).unwrap();
+    assert_eq!(created.total, 2);
+    assert!(created.wallets.iter().all(|w| w.created_at == Height(2)));
+
+    let created = api.created_wallets(3, Some(3), None, None).unwrap();
+    assert_eq!(created.total, 0);
+    assert!(created.wallets.is_empty());
+
+    // Pagination within the range.
+    let first_page = api.created_wallets(2, None, None, Some(1)).unwrap();
+    assert_eq!(first_page.total, 3);
+    assert_eq!(first_page.wallets.len(), 1);
+    let created = api
+        .created_wallets(2, None, first_page.next_cursor, Some(2))
+        .unwrap();
+    assert_eq!(created.total, 3);
+    assert_eq!(created.wallets.len(), 2);
+    assert_eq!(created.wallets[1].pub_key, tx_dave.author());
+    assert_eq!(created.next_cursor, None);
+
+    // Cursors pointing outside of the range or to unknown wallets are rejected.
+    let alice_cursor = base64::encode_config(tx_alice.author().as_ref(), base64::URL_SAFE_NO_PAD);
+    let err = api
+        .created_wallets(2, None, Some(alice_cursor), None)
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidCursor);
+    let legacy_cursor = base64::encode_config(legacy_key.as_ref(), base64::URL_SAFE_NO_PAD);
+    let err = api
+        .created_wallets(0, None, Some(legacy_cursor), None)
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidCursor);
+
+    let err = api.created_wallets(3, Some(2), None, None).unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+    let err = api
+        .created_wallets(0, None, None, Some(MAX_ITEMS_PER_REQUEST + 1))
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+}
+
+#[test]
+fn test_created_wallets_cursor_stability() {
+    let (mut testkit, api) = create_testkit();
+
+    let mut expected = Vec::new();
+    for i in 0..5 {
+        let (tx, _) = api.create_wallet(&format!("Wallet {}", i)).unwrap();
+        testkit.create_block();
+        expected.push(tx.author());
+    }
+
+    let mut listed = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = api.created_wallets(0, None, cursor, Some(2)).unwrap();
+        listed.extend(page.wallets.iter().map(|wallet| wallet.pub_key));
+        if listed.len() == 2 {
+            // A wallet created between the pages is listed exactly once, at the end.
+            let (tx, _) = api.create_wallet("Latecomer").unwrap();
+            testkit.create_block();
+            expected.push(tx.author());
+        }
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(listed, expected);
+}
+
+#[test]
+fn test_wallet_names() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    // A wallet with a taken name does not replace the indexed one.
+    let (tx_impostor, _) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    // Failed wallet creation is not indexed.
+    let duplicate = CreateWallet::sign("Alice 2", &tx_alice.author(), &key_alice);
+    testkit.create_block_with_transactions(vec![duplicate]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.wallet_names().iter().count(), 2);
+    assert_eq!(
+        schema.wallet_by_name(ALICE_NAME).unwrap().pub_key,
+        tx_alice.author()
+    );
+    assert_eq!(
+        schema.wallet_by_name(BOB_NAME).unwrap().pub_key,
+        tx_bob.author()
+    );
+    assert!(schema.wallet_by_name("Alice 2").is_none());
+    assert_eq!(
+        schema.wallet(&tx_impostor.author()).unwrap().name,
+        ALICE_NAME
+    );
+
+    // The index root is appended to the end of the service state hash.
+    let state_hash = schema.state_hash();
+    assert_eq!(state_hash.len(), 25);
+    assert_eq!(state_hash[0], schema.wallets().merkle_root());
+    assert_eq!(state_hash[1], schema.multisig_transfers().merkle_root());
+    assert_eq!(state_hash[11], schema.wallet_activities().merkle_root());
+    assert_eq!(state_hash[12], schema.wallet_names().merkle_root());
+    assert_eq!(state_hash[23], schema.block_summaries().merkle_root());
+
+    // The holders of the names indexed before the holders were tracked are restored.
+    let alice_holders = |testkit: &TestKit| {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        let holders = schema.wallet_name_holders(&wallet_name_key(ALICE_NAME));
+        holders.iter().collect::<Vec<_>>()
+    };
+    let expected = vec![tx_alice.author(), tx_impostor.author()];
+    assert_eq!(alice_holders(&testkit), expected);
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        schema
+            .wallet_name_holders_mut(&wallet_name_key(ALICE_NAME))
+            .clear();
+        schema.schema_version_entry_mut().set(12);
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block();
+    assert_eq!(alice_holders(&testkit), expected);
+}
+
+#[test]
+fn test_stats() {
+    let (mut testkit, api) = create_testkit();
+    assert_eq!(
+        api.stats().unwrap(),
+        ServiceStats {
+            total_supply: Amount::ZERO,
+            wallet_count: 0,
+            transfer_count: 0,
+            transfer_volume: Amount::ZERO,
+        }
+    );
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    let issue = Issue::sign(&tx_alice.author(), 50, 0, &key_alice);
+    let transfer = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 30, 0, &key_alice);
+    // Fails due to insufficient funds and must not be counted.
+    let overcharge = Transfer::sign(&tx_bob.author(), &tx_alice.author(), 1000, 0, &key_bob);
+    api.transaction(&issue).unwrap();
+    api.transaction(&transfer).unwrap();
+    api.transaction(&overcharge).unwrap();
+    testkit.create_block();
+
+    let (carol_public_key, carol_private_key) = exonum_crypto::gen_keypair();
+    let tx_done = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key].iter().cloned().collect(),
+        10, // transferred amount
+        0,  // seed
+    );
+    let tx_rejected = TransferMultisig::sign(
+        tx_alice.author(),
+        &key_alice,
+        tx_bob.author(),
+        [carol_public_key].iter().cloned().collect(),
+        20, // transferred amount
+        1,  // seed
+    );
+    api.transaction(&tx_done).unwrap();
+    api.transaction(&tx_rejected).unwrap();
+    testkit.create_block();
+
+    api.transaction(&ApproveTransferMultisig::sign(
+        carol_public_key,
+        &carol_private_key,
+        tx_done.hash(),
+    ))
+    .unwrap();
+    api.transaction(&RejectTransferMultisig::sign(
+        carol_public_key,
+        &carol_private_key,
+        tx_rejected.hash(),
+    ))
+    .unwrap();
+    testkit.create_block();
+
+    assert_eq!(
+        api.stats().unwrap(),
+        ServiceStats {
+            total_supply: Amount::from(250),
+            wallet_count: 2,
+            transfer_count: 2,
+            transfer_volume: Amount::from(40),
+        }
+    );
+}
+
+#[test]
+fn test_block_summaries() {
+    let (mut testkit, api) = create_testkit();
+    let summary = |height, transactions, failed_transactions, amount_moved: u64| BlockSummary {
+        height: Height(height),
+        transactions,
+        failed_transactions,
+        amount_moved: Amount::from(amount_moved),
+    };
+    assert_eq!(
+        api.block_summaries(0, 1).unwrap().summaries,
+        vec![summary(0, 0, 0, 0)]
+    );
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (dave, key_dave) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(vec![
+        Transfer::sign(&alice, &bob, 10, 0, &key_alice),
+        Transfer::sign(&alice, &bob, 20, 1, &key_alice),
+        Transfer::sign(&alice, &bob, 1_000, 2, &key_alice),
+        Transfer::sign(&dave, &bob, 10, 0, &key_dave),
+    ]);
+    // Blocks without service transactions are summarized as well.
+    testkit.create_block();
+
+    // Everything below is verified using the single response only.
+    let range = api.block_summaries(1, 4).unwrap();
+    let expected = vec![
+        summary(1, 2, 0, 0),
+        summary(2, 4, 2, 30),
+        summary(3, 0, 0, 0),
+    ];
+    assert_eq!(range.summaries, expected);
+
+    let block = &range.block_proof.block;
+    assert_eq!(block.height(), Height(3));
+    let to_table = range.to_table.check().unwrap();
+    assert_eq!(to_table.merkle_root(), *block.state_hash());
+    let (_, summaries_root) = to_table.entries().next().unwrap();
+    let entries = range
+        .proof
+        .unwrap()
+        .validate(*summaries_root, block.height().next().0)
+        .unwrap()
+        .into_iter()
+        .map(|(index, summary)| (index, summary.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, (1..).zip(expected).collect::<Vec<_>>());
+
+    let empty = api.block_summaries(2, 2).unwrap();
+    assert!(empty.summaries.is_empty());
+    assert!(empty.proof.is_none());
+    for &(from, to) in &[(2, 1), (0, 5)] {
+        let err = api.block_summaries(from, to).unwrap_err();
+        assert_api_error(err, ErrorKind::InvalidQuery);
+    }
+
+    // The blocks committed before the summaries were introduced are summarized
+    // without the moved amounts.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        schema.block_summaries_mut().clear();
+        schema.schema_version_entry_mut().set(11);
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block_with_transaction(Transfer::sign(&alice, &bob, 5, 3, &key_alice));
+    assert_eq!(
+        api.block_summaries(0, 5).unwrap().summaries,
+        vec![
+            summary(0, 0, 0, 0),
+            summary(1, 2, 0, 0),
+            summary(2, 4, 2, 0),
+            summary(3, 0, 0, 0),
+            summary(4, 1, 0, 5),
+        ]
+    );
+
+    // A block whose `before_commit` was rolled back keeps the volume of its transfers
+    // and is summarized by the next block.
+    testkit.create_block_with_transaction(Transfer::sign(&alice, &bob, 7, 4, &key_alice));
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let summaries = schema.block_summaries().iter().take(5).collect::<Vec<_>>();
+        schema.block_summaries_mut().clear();
+        schema.block_summaries_mut().extend(summaries);
+        schema.block_transfer_volumes_mut().put(&5, Amount::from(7));
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    testkit.create_block_with_transaction(Transfer::sign(&alice, &bob, 2, 5, &key_alice));
+    assert_eq!(
+        api.block_summaries(4, 7).unwrap().summaries,
+        vec![
+            summary(4, 1, 0, 5),
+            summary(5, 1, 0, 7),
+            summary(6, 1, 0, 2)
+        ]
+    );
+    assert_eq!(
+        Schema::new(&testkit.snapshot())
+            .block_transfer_volumes()
+            .iter()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn test_total_supply_overflow() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+
+    // The largest whole amount; the initial balance of Alice makes up the rest of it.
+    let unit = Amount::from(1).raw();
+    let max = Amount::from_raw(Amount::MAX.raw() / unit * unit);
+    let issue = Issue::sign(
+        &tx_alice.author(),
+        max.checked_sub(Amount::from(100)).unwrap(),
+        0,
+        &key_alice,
+    );
+    api.transaction(&issue).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, issue.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.stats().unwrap().total_supply, max);
+
+    let overflow = Issue::sign(&tx_alice.author(), 1, 1, &key_alice);
+    api.transaction(&overflow).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    for tx_hash in &[overflow.hash(), tx_bob.hash()] {
+        assert_tx_status(
+            &api,
+            *tx_hash,
+            &json!({ "type": "error", "code": 13, "description": "Total supply overflow" }),
+        );
+    }
+
+    assert_eq!(api.stats().unwrap().total_supply, max);
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.balance, max);
+}
+
+#[test]
+fn test_total_supply_matches_balances() {
+    let (mut testkit, api) = create_testkit();
+
+    let wallets = (0..5)
+        .map(|i| {
+            let (tx, key) = api.create_wallet(&format!("Wallet {}", i)).unwrap();
+            (tx.author(), key)
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block();
+
+    // Linear congruential generator, so that the sequence of operations is reproducible.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = |bound: usize| {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) as usize % bound
+    };
+
+    let mut pending = Vec::new();
+    let mut seed = 0;
+    for _ in 0..30 {
+        for _ in 0..4 {
+            seed += 1;
+            let (from, ref from_key) = wallets[next(wallets.len())];
+            let (to, _) = wallets[next(wallets.len())];
+            let amount = next(150) as u64;
+            // Some of the transactions fail, e.g. due to insufficient funds,
+            // which must not affect the invariant either.
+            match next(4) {
+                0 => {
+                    api.transaction(&Transfer::sign(&from, &to, amount, seed, from_key))
+                        .unwrap();
+                }
+                1 => {
+                    api.transaction(&Issue::sign(&from, amount, seed, from_key))
+                        .unwrap();
+                }
+                2 => {
+                    let approver = next(wallets.len());
+                    let tx = TransferMultisig::sign(
+                        from,
+                        from_key,
+                        to,
+                        [wallets[approver].0].iter().cloned().collect(),
+                        amount,
+                        seed,
+                    );
+                    api.transaction(&tx).unwrap();
+                    pending.push((tx.hash(), approver));
+                }
+                _ => {
+                    if let Some((tx_hash, approver)) = pending.pop() {
+                        let (approver, ref approver_key) = wallets[approver];
+                        let tx = if next(2) == 0 {
+                            ApproveTransferMultisig::sign(approver, approver_key, tx_hash)
+                        } else {
+                            RejectTransferMultisig::sign(approver, approver_key, tx_hash)
+                        };
+                        api.transaction(&tx).unwrap();
+                    }
+                }
+            }
+        }
+        testkit.create_block();
+
+        // Currency of the multisignature transfers in process is withheld from the senders.
+        let (balances, withheld) = wallets.iter().fold(
+            (Amount::ZERO, Amount::ZERO),
+            |(balances, withheld), (pub_key, _)| {
+                let info = api.wallet_info(*pub_key).unwrap();
+                let wallet = api.get_wallet(*pub_key).unwrap().unwrap();
+                (
+                    balances.checked_add(wallet.balance).unwrap(),
+                    withheld.checked_add(info.pending_outgoing.total).unwrap(),
+                )
+            },
+        );
+        assert_eq!(
+            balances.checked_add(withheld),
+            Some(api.stats().unwrap().total_supply)
+        );
+    }
+}
+
+#[test]
+fn test_wallets_batch() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    let (unknown_key, _) = crypto::gen_keypair();
+    let pub_keys = vec![tx_alice.author(), unknown_key, tx_bob.author()];
+    let info = api.wallets_batch(pub_keys.clone()).unwrap();
+
+    assert_eq!(info.wallets.len(), 3);
+    assert_matches!(
+        info.wallets[0],
+        BatchWalletEntry::Found { ref wallet, .. } if wallet.name == ALICE_NAME
+    );
+    assert_matches!(
+        info.wallets[1],
+        BatchWalletEntry::Missing { pub_key } if pub_key == unknown_key
+    );
+    assert_matches!(
+        info.wallets[2],
+        BatchWalletEntry::Found { ref wallet, .. } if wallet.name == BOB_NAME
+    );
+
+    let checked_proof = info.wallet_proof.to_wallet.check().unwrap();
+    let mut found_keys = checked_proof.entries().map(|(k, _)| *k).collect::<Vec<_>>();
+    found_keys.sort();
+    let mut expected_keys = vec![tx_alice.author(), tx_bob.author()];
+    expected_keys.sort();
+    assert_eq!(found_keys, expected_keys);
+    assert_eq!(
+        checked_proof.missing_keys().collect::<Vec<_>>(),
+        vec![&unknown_key]
+    );
+
+    let too_many_keys = (0..=MAX_KEYS_PER_BATCH)
+        .map(|_| crypto::gen_keypair().0)
+        .collect::<Vec<_>>();
+    let err = api.wallets_batch(too_many_keys).unwrap_err();
+    let error = assert_api_error(err, ErrorKind::TooManyKeys);
+    let details = error.details.unwrap();
+    assert_eq!(details["max_keys"], MAX_KEYS_PER_BATCH as u64);
+    assert_eq!(details["requested"], MAX_KEYS_PER_BATCH as u64 + 1);
+}
+
+#[test]
+fn test_balance_sum() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let tx_transfer = Transfer::sign(&alice, &bob, 30, 0, &key_alice);
+    api.transaction(&tx_transfer).unwrap();
+    testkit.create_block();
+
+    let (unknown_key, _) = crypto::gen_keypair();
+    let sum = api
+        .balance_sum(vec![alice, unknown_key, bob, alice])
+        .unwrap();
+    assert_eq!(
+        sum,
+        BalanceSum {
+            total: Amount::from(200),
+            balances: vec![
+                WalletBalance {
+                    pub_key: alice,
+                    balance: Amount::from(70),
+                    last_tx_hash: tx_transfer.hash(),
+                },
+                WalletBalance {
+                    pub_key: bob,
+                    balance: Amount::from(130),
+                    last_tx_hash: tx_transfer.hash(),
+                },
+            ],
+            missing: vec![unknown_key],
+        }
+    );
+
+    // The sum of balances does not fit into `u64` units.
+    let rich_keys = (0..2).map(|_| crypto::gen_keypair().0).collect::<Vec<_>>();
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for key in &rich_keys {
+            let wallet = Wallet::new(
+                key,
+                "Rich",
+                Amount::from(u64::max_value()),
+                0,
+                &Hash::zero(),
+                &Hash::zero(),
+                Height(0),
+                Height(0),
+            );
+            schema.wallets_mut().put(key, wallet);
+        }
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+    let sum = api.balance_sum(rich_keys).unwrap();
+    assert_eq!(
+        sum.total,
+        Amount::from_raw(2 * Amount::from(u64::max_value()).raw())
+    );
+
+    let too_many_keys = (0..=MAX_KEYS_PER_BALANCE_SUM)
+        .map(|_| crypto::gen_keypair().0)
+        .collect::<Vec<_>>();
+    let err = api.balance_sum(too_many_keys).unwrap_err();
+    let error = assert_api_error(err, ErrorKind::TooManyKeys);
+    assert_eq!(
+        error.details.unwrap()["max_keys"],
+        MAX_KEYS_PER_BALANCE_SUM as u64
+    );
+}
+
+#[test]
+fn test_wallet_summary() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    let others = (0..6)
+        .map(|i| api.create_wallet(&format!("Wallet {}", i)).unwrap())
+        .collect::<Vec<_>>();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    // Alice has more than `SUMMARY_TOP_COUNTERPARTIES` counterparties.
+    for (i, (tx, key)) in others.iter().enumerate() {
+        let amount = i as u64 + 1;
+        api.transaction(&Transfer::sign(&alice, &tx.author(), amount, 0, &key_alice))
+            .unwrap();
+        api.transaction(&Transfer::sign(&tx.author(), &alice, amount, 1, key))
+            .unwrap();
+    }
+    api.transaction(&Transfer::sign(&bob, &alice, 15, 0, &key_bob))
+        .unwrap();
+    // Failed transfers are not counted.
+    api.transaction(&Transfer::sign(&bob, &alice, 1_000, 1, &key_bob))
+        .unwrap();
+
+    let (approver, approver_key) = crypto::gen_keypair();
+    let multisigs = (0..2)
+        .map(|seed| {
+            let tx = TransferMultisig::sign(
+                alice,
+                &key_alice,
+                bob,
+                [approver].iter().cloned().collect(),
+                10,
+                seed + 2,
+            );
+            api.transaction(&tx).unwrap();
+            tx.hash()
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block();
+
+    api.transaction(&ApproveTransferMultisig::sign(
+        approver,
+        &approver_key,
+        multisigs[0],
+    ))
+    .unwrap();
+    api.transaction(&RejectTransferMultisig::sign(
+        approver,
+        &approver_key,
+        multisigs[1],
+    ))
+    .unwrap();
+    testkit.create_block();
+
+    let summary = api.wallet_summary(alice).unwrap();
+    assert_eq!(summary.sent, 21 + 10);
+    assert_eq!(summary.received, 21 + 15);
+    assert_eq!(summary.outgoing_transfers, 7);
+    assert_eq!(summary.incoming_transfers, 7);
+    assert_eq!(summary.top_counterparties.len(), 5);
+    assert_eq!(summary.top_counterparties[0].pub_key, bob);
+    assert_eq!(summary.top_counterparties[0].volume, 25);
+    assert_eq!(summary.top_counterparties[1].pub_key, others[5].0.author());
+    assert_eq!(summary.top_counterparties[1].volume, 12);
+
+    // Cross-check the aggregates against a recomputation from the wallet histories.
+    let snapshot = testkit.snapshot();
+    let blockchain_schema = blockchain::Schema::new(&snapshot);
+    let schema = Schema::new(&snapshot);
+    let transactions = blockchain_schema.transactions();
+    for pub_key in schema.wallets().keys() {
+        let mut expected = WalletSummary {
+            sent: Amount::ZERO,
+            received: Amount::ZERO,
+            outgoing_transfers: 0,
+            incoming_transfers: 0,
+            top_counterparties: Vec::new(),
+        };
+        let mut volumes = HashMap::new();
+
+        for record in schema.wallet_history_from(&pub_key, 0) {
+            let tx_hash = record.tx_hash;
+            let message = transactions.get(&tx_hash).unwrap();
+            let tx = WalletTransactions::tx_from_raw(message.payload().clone()).unwrap();
+            let (counterparty, amount, outgoing) = match tx {
+                WalletTransactions::Transfer(tx) if message.author() == pub_key => {
+                    (tx.to, tx.amount, true)
+                }
+                WalletTransactions::Transfer(tx) => (message.author(), tx.amount, false),
+                WalletTransactions::TransferMultisig(tx) => {
+                    if !schema.multisig_transfer(tx_hash).unwrap().is_done() {
+                        continue;
+                    }
+                    (tx.to, tx.amount, true)
+                }
+                WalletTransactions::ApproveTransferMultisig(tx) => {
+                    let original = transactions.get(&tx.tx_hash).unwrap();
+                    match WalletTransactions::tx_from_raw(original.payload().clone()).unwrap() {
+                        WalletTransactions::TransferMultisig(original_tx) => {
+                            (original.author(), original_tx.amount, false)
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                _ => continue,
+            };
+
+            if outgoing {
+                expected.sent = expected.sent.saturating_add(amount);
+                expected.outgoing_transfers += 1;
+            } else {
+                expected.received = expected.received.saturating_add(amount);
+                expected.incoming_transfers += 1;
+            }
+            let volume = volumes.entry(counterparty).or_insert(Amount::ZERO);
+            *volume = volume.saturating_add(amount);
+        }
+
+        let summary = api.wallet_summary(pub_key).unwrap();
+        assert_eq!(summary.sent, expected.sent);
+        assert_eq!(summary.received, expected.received);
+        assert_eq!(summary.outgoing_transfers, expected.outgoing_transfers);
+        assert_eq!(summary.incoming_transfers, expected.incoming_transfers);
+        for entry in &summary.top_counterparties {
+            assert_eq!(volumes.get(&entry.pub_key), Some(&entry.volume));
+        }
+        let mut expected_volumes = volumes.values().cloned().collect::<Vec<_>>();
+        expected_volumes.sort_by(|a, b| b.cmp(a));
+        expected_volumes.truncate(5);
+        let volumes = summary
+            .top_counterparties
+            .iter()
+            .map(|entry| entry.volume)
+            .collect::<Vec<_>>();
+        assert_eq!(volumes, expected_volumes);
+    }
+
+    let (unknown_key, _) = crypto::gen_keypair();
+    let err = api.wallet_summary(unknown_key).unwrap_err();
+    assert_api_error(err, ErrorKind::WalletNotFound);
+}
+
+#[test]
+fn test_wallet_stats() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    api.transaction(&Transfer::sign(&alice, &bob, 5, 0, &key_alice))
+        .unwrap();
+    api.transaction(&Transfer::sign(&bob, &alice, 7, 0, &key_bob))
+        .unwrap();
+    api.transaction(&Issue::sign(&alice, 20, 1, &key_alice))
+        .unwrap();
+    // Failed transactions are not counted.
+    api.transaction(&Transfer::sign(&bob, &alice, 1_000, 1, &key_bob))
+        .unwrap();
+
+    let (approver, approver_key) = crypto::gen_keypair();
+    let multisigs = (0..3)
+        .map(|seed| {
+            let tx = TransferMultisig::sign(
+                alice,
+                &key_alice,
+                bob,
+                [approver].iter().cloned().collect(),
+                10,
+                seed + 2,
+            );
+            api.transaction(&tx).unwrap();
+            tx.hash()
+        })
+        .collect::<Vec<_>>();
+    testkit.create_block();
+
+    // The withheld amount is counted once the transfer is settled.
+    let stats = |testkit: &TestKit, pub_key| {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot).wallet_stats(&pub_key).unwrap()
+    };
+    assert_eq!(stats(&testkit, alice).sent_count, 1);
+    assert_eq!(stats(&testkit, alice).sent_total, 5);
+
+    api.transaction(&ApproveTransferMultisig::sign(
+        approver,
+        &approver_key,
+        multisigs[0],
+    ))
+    .unwrap();
+    api.transaction(&RejectTransferMultisig::sign(
+        approver,
+        &approver_key,
+        multisigs[1],
+    ))
+    .unwrap();
+    testkit.create_block();
+
+    assert_eq!(
+        stats(&testkit, alice),
+        WalletStats {
+            sent_count: 2,
+            received_count: 2,
+            sent_total: Amount::from(5 + 10),
+            received_total: Amount::from(7 + 20),
+        }
+    );
+
+    // Cross-check the aggregates against a recomputation from the wallet histories.
+    let snapshot = testkit.snapshot();
+    let blockchain_schema = blockchain::Schema::new(&snapshot);
+    let schema = Schema::new(&snapshot);
+    let transactions = blockchain_schema.transactions();
+    for pub_key in schema.wallets().keys() {
+        let mut expected = WalletStats::default();
+        for record in schema.wallet_history_from(&pub_key, 0) {
+            let message = transactions.get(&record.tx_hash).unwrap();
+            match WalletTransactions::tx_from_raw(message.payload().clone()).unwrap() {
+                WalletTransactions::CreateWallet(_)
+                | WalletTransactions::RejectTransferMultisig(_) => continue,
+                WalletTransactions::TransferMultisig(_)
+                    if !schema.multisig_transfer(record.tx_hash).unwrap().is_done() =>
+                {
+                    continue
+                }
+                _ => {}
+            }
+
+            if record.incoming {
+                expected.received_count += 1;
+                expected.received_total = expected.received_total.saturating_add(record.amount);
+            } else {
+                expected.sent_count += 1;
+                expected.sent_total = expected.sent_total.saturating_add(record.amount);
+            }
+        }
+        assert_eq!(schema.wallet_stats(&pub_key).unwrap_or_default(), expected);
+    }
+}
+
+#[test]
+fn test_submit_transaction() {
+    let (mut testkit, api) = create_testkit();
+
+    let (pub_key, key) = crypto::gen_keypair();
+    let tx = CreateWallet::sign(ALICE_NAME, &pub_key, &key);
+    let response = api
+        .submit_transaction(&messages::to_hex_string(&tx))
+        .unwrap();
+    assert_eq!(
+        response,
+        SubmittedTransaction {
+            tx_hash: tx.hash(),
+            tx_type: "CreateWallet".to_owned(),
+        }
+    );
+    testkit.create_block();
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
+
+    let assert_error = |tx_body: &str, kind: ErrorKind| {
+        let err = api.submit_transaction(tx_body).unwrap_err();
+        assert_api_error(err, kind)
+    };
+
+    assert_error("not a hex", ErrorKind::MalformedHex);
+    assert_error("0123456789abcdef", ErrorKind::MalformedMessage);
+
+    let foreign_tx = Message::sign_transaction(
+        ServiceTransaction::from_raw_unchecked(0, vec![]),
+        CRYPTOCURRENCY_SERVICE_ID + 1,
+        pub_key,
+        &key,
+    );
+    let error = assert_error(
+        &messages::to_hex_string(&foreign_tx),
+        ErrorKind::WrongServiceId,
+    );
+    assert_eq!(
+        error.details,
+        Some(json!({
+            "expected": CRYPTOCURRENCY_SERVICE_ID,
+            "actual": CRYPTOCURRENCY_SERVICE_ID + 1,
+        }))
+    );
+
+    let unknown_tx = Message::sign_transaction(
+        ServiceTransaction::from_raw_unchecked(100, vec![1, 2, 3]),
+        CRYPTOCURRENCY_SERVICE_ID,
+        pub_key,
+        &key,
+    );
+    assert_error(
+        &messages::to_hex_string(&unknown_tx),
+        ErrorKind::UndecodablePayload,
+    );
+}
+
+#[test]
+fn test_parse_signed_hex() {
+    let (pub_key, key) = crypto::gen_keypair();
+    let (to, _) = crypto::gen_keypair();
+    let tx = Transfer::sign(&pub_key, &to, 10, 1, &key);
+    let tx_hex = tx_encoding::to_hex(&tx);
+    assert_eq!(tx_hex, messages::to_hex_string(&tx));
+
+    let (parsed, transfer) = tx_encoding::parse_signed_hex(&tx_hex).unwrap();
+    assert_eq!(parsed, tx);
+    assert_matches!(
+        transfer,
+        WalletTransactions::Transfer(ref transfer) if transfer.to == to && transfer.amount == 10
+    );
+
+    assert_matches!(
+        tx_encoding::parse_signed_hex("not a hex"),
+        Err(ParseError::Hex(_))
+    );
+    // Truncated messages are either odd-length hex or malformed.
+    for len in 0..tx_hex.len() {
+        match tx_encoding::parse_signed_hex(&tx_hex[..len]) {
+            Err(ParseError::Hex(_)) => assert_eq!(len % 2, 1),
+            Err(ParseError::Message(_)) => assert_eq!(len % 2, 0),
+            other => panic!("Unexpected result for length {}: {:?}", len, other),
+        }
+    }
+    // Any flipped bit breaks the signature.
+    let buffer = hex::decode(&tx_hex).unwrap();
+    for i in 0..buffer.len() {
+        for bit in 0..8 {
+            let mut flipped = buffer.clone();
+            flipped[i] ^= 1 << bit;
+            assert_matches!(
+                tx_encoding::parse_signed_hex(&hex::encode(&flipped)),
+                Err(ParseError::Message(_))
+            );
+        }
+    }
+
+    let sign_raw = |service_id, message_id, payload| {
+        let tx = Message::sign_transaction(
+            ServiceTransaction::from_raw_unchecked(message_id, payload),
+            service_id,
+            pub_key,
+            &key,
+        );
+        tx_encoding::parse_signed_hex(&tx_encoding::to_hex(&tx))
+    };
+    assert_eq!(
+        sign_raw(CRYPTOCURRENCY_SERVICE_ID + 1, 0, vec![]).unwrap_err(),
+        ParseError::WrongServiceId(CRYPTOCURRENCY_SERVICE_ID + 1)
+    );
+    assert_eq!(
+        sign_raw(CRYPTOCURRENCY_SERVICE_ID, 100, vec![]).unwrap_err(),
+        ParseError::UnknownMessageId(100)
+    );
+    assert_eq!(
+        sign_raw(
+            CRYPTOCURRENCY_SERVICE_ID,
+            WalletTransactions::MESSAGE_COUNT,
+            vec![]
+        )
+        .unwrap_err(),
+        ParseError::UnknownMessageId(WalletTransactions::MESSAGE_COUNT)
+    );
+    assert_matches!(
+        sign_raw(CRYPTOCURRENCY_SERVICE_ID, 0, vec![0xff]),
+        Err(ParseError::Payload(_))
+    );
+    // The last known message identifier is `RenameWallet`.
+    let rename = RenameWallet::sign(&pub_key, "Renamed", 0, &key);
+    assert_eq!(
+        rename
+            .payload()
+            .clone()
+            .service_transaction()
+            .into_raw_parts()
+            .0,
+        WalletTransactions::MESSAGE_COUNT - 1
+    );
+}
+
+#[test]
+fn test_balance_at() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service::with_balance_checkpoint_interval(2))
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (approver, approver_key) = crypto::gen_keypair();
+    let multisig = |amount, seed| {
+        TransferMultisig::sign(
+            alice,
+            &key_alice,
+            bob,
+            [approver].iter().cloned().collect(),
+            amount,
+            seed,
+        )
+    };
+
+    // Height 2.
+    testkit.create_block_with_transactions(vec![Transfer::sign(&alice, &bob, 10, 0, &key_alice)]);
+    // Heights 3 and 4: multisignature transfer is created and approved.
+    let approved = multisig(20, 1);
+    testkit.create_block_with_transactions(vec![approved.clone()]);
+    testkit.create_block_with_transactions(vec![ApproveTransferMultisig::sign(
+        approver,
+        &approver_key,
+        approved.hash(),
+    )]);
+    // Height 5.
+    testkit.create_block_with_transactions(vec![Issue::sign(&alice, 5, 2, &key_alice)]);
+    // Heights 6 and 7: multisignature transfer is created and rejected.
+    let rejected = multisig(7, 3);
+    testkit.create_block_with_transactions(vec![rejected.clone()]);
+    testkit.create_block_with_transactions(vec![RejectTransferMultisig::sign(
+        approver,
+        &approver_key,
+        rejected.hash(),
+    )]);
+    // Height 8.
+    testkit.create_block_with_transactions(vec![Transfer::sign(&bob, &alice, 30, 4, &key_bob)]);
+    // Height 9.
+    testkit.create_block();
+
+    let alice_balances = [0, 100, 90, 70, 70, 75, 68, 75, 105, 105];
+    let bob_balances = [0, 100, 110, 110, 130, 130, 130, 130, 100, 100];
+    for height in 0..10 {
+        let balance_at = api.balance_at(alice, height).unwrap();
+        assert_eq!(balance_at.height, Height(height));
+        assert_eq!(balance_at.balance, alice_balances[height as usize]);
+        let balance_at = api.balance_at(bob, height).unwrap();
+        assert_eq!(balance_at.balance, bob_balances[height as usize]);
+    }
+    assert_eq!(
+        api.get_wallet(alice).unwrap().unwrap().balance,
+        alice_balances[9]
+    );
+    assert_eq!(
+        api.get_wallet(bob).unwrap().unwrap().balance,
+        bob_balances[9]
+    );
+
+    // Checkpoints are written every other block for the wallets changed since
+    // the previous checkpoint.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let checkpoint_heights = |pub_key: &PublicKey| {
+        schema
+            .balance_checkpoints(pub_key)
+            .iter()
+            .map(|checkpoint| checkpoint.height.0)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(checkpoint_heights(&alice), vec![2, 4, 6, 8]);
+    assert_eq!(checkpoint_heights(&bob), vec![2, 4, 8]);
+
+    let err = api.balance_at(alice, 10).unwrap_err();
+    let error = assert_api_error(err, ErrorKind::BlockNotFound);
+    assert_eq!(error.details, Some(json!({ "max_height": 9 })));
+
+    let (unknown_key, _) = crypto::gen_keypair();
+    let err = api.balance_at(unknown_key, 1).unwrap_err();
+    assert_api_error(err, ErrorKind::WalletNotFound);
+}
+
+#[test]
+fn test_block_activity() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service::default())
+        .with_service(exonum_configuration::Service::default())
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let activity = api.block_activity(1).unwrap();
+    assert_eq!(activity.height, Height(1));
+    let created = activity
+        .transactions
+        .iter()
+        .map(|tx| (tx.tx_hash, tx.tx_type.as_str(), tx.author, tx.amount))
+        .collect::<Vec<_>>();
+    assert_eq!(created.len(), 2);
+    assert!(created.contains(&(tx_alice.hash(), "CreateWallet", alice, None)));
+    assert!(created.contains(&(tx_bob.hash(), "CreateWallet", bob, None)));
+
+    // Block with transactions of both services.
+    let (voter, voter_key) = crypto::gen_keypair();
+    let vote = exonum_configuration::Vote::sign(&voter, &Hash::zero(), &voter_key);
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    let overcharge = Transfer::sign(&alice, &bob, 1_000, 1, &key_alice);
+    testkit.create_block_with_transactions(vec![vote, transfer.clone()]);
+    testkit.create_block_with_transactions(vec![overcharge]);
+
+    let activity = api.block_activity(2).unwrap();
+    assert_eq!(
+        activity.transactions,
+        vec![ServiceTransactionActivity {
+            tx_hash: transfer.hash(),
+            tx_type: "Transfer".to_owned(),
+            author: alice,
+            amount: Some(Amount::from(10)),
+            receiver: Some(bob),
+            status: ExecutionStatus::Success,
+        }]
+    );
+    let activity = api.block_activity(3).unwrap();
+    assert_matches!(
+        activity.transactions[0].status,
+        ExecutionStatus::Error { code: 3, .. }
+    );
+
+    // Blocks without service transactions.
+    let vote = exonum_configuration::Vote::sign(&voter, &crypto::hash(&[1]), &voter_key);
+    testkit.create_block_with_transactions(vec![vote]);
+    testkit.create_block();
+    assert!(api.block_activity(0).unwrap().transactions.is_empty());
+    assert!(api.block_activity(4).unwrap().transactions.is_empty());
+    assert!(api.block_activity(5).unwrap().transactions.is_empty());
+
+    let err = api.block_activity(6).unwrap_err();
+    let error = assert_api_error(err, ErrorKind::BlockNotFound);
+    assert_eq!(error.details, Some(json!({ "max_height": 5 })));
+}
+
+#[test]
+fn test_transaction_stream() {
+    let service = Service::default();
+    let stream = service.transaction_stream();
+    let events = stream.subscribe();
+    let dropped = stream.subscribe();
+    drop(dropped);
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(service)
+        .with_service(exonum_configuration::Service::default())
+        .create();
+
+    let (alice, key_alice) = crypto::gen_keypair();
+    let (bob, key_bob) = crypto::gen_keypair();
+    let create_alice = CreateWallet::sign(ALICE_NAME, &alice, &key_alice);
+    let create_bob = CreateWallet::sign(BOB_NAME, &bob, &key_bob);
+    testkit.create_block_with_transactions(vec![create_alice.clone(), create_bob.clone()]);
+    // Disconnected subscribers are removed on publishing.
+    assert_eq!(stream.subscriber_count(), 1);
+
+    // Transactions of other services are not streamed.
+    let (voter, voter_key) = crypto::gen_keypair();
+    let vote = exonum_configuration::Vote::sign(&voter, &Hash::zero(), &voter_key);
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    testkit.create_block_with_transactions(vec![vote, transfer.clone()]);
+    testkit.create_block();
+    let overcharge = Transfer::sign(&bob, &alice, 1_000, 1, &key_bob);
+    testkit.create_block_with_transactions(vec![overcharge.clone()]);
+
+    let events = events.take(4).collect().wait().unwrap();
+    let summary = events
+        .iter()
+        .map(|event| (event.height, event.tx_type.as_str(), event.author))
+        .collect::<Vec<_>>();
+    let (first, second) = if events[0].tx_hash == create_alice.hash() {
+        (alice, bob)
+    } else {
+        (bob, alice)
+    };
+    assert_eq!(
+        summary,
+        vec![
+            (Height(1), "CreateWallet", first),
+            (Height(1), "CreateWallet", second),
+            (Height(2), "Transfer", alice),
+            (Height(4), "Transfer", bob),
+        ]
+    );
+    assert_eq!(events[2].tx_hash, transfer.hash());
+    assert_eq!(events[2].status, ExecutionStatus::Success);
+    assert_eq!(events[3].tx_hash, overcharge.hash());
+    assert_matches!(events[3].status, ExecutionStatus::Error { code: 3, .. });
+
+    // Events within a block follow the order of execution.
+    let snapshot = testkit.snapshot();
+    let block_order = blockchain::Schema::new(&snapshot)
+        .block_transactions(Height(1))
+        .iter()
+        .collect::<Vec<_>>();
+    assert_eq!(block_order, vec![events[0].tx_hash, events[1].tx_hash]);
+}
+
+#[test]
+fn test_event_emitter() {
+    let path = env::temp_dir().join(format!(
+        "cryptocurrency-events-{}.jsonl",
+        crypto::gen_keypair().0.to_hex()
+    ));
+    let service = Service::default().with_event_emitter(EventEmitter::with_log_file(&path));
+    let events = service.event_emitter().subscribe();
+    let mut testkit = TestKitBuilder::validator().with_service(service).create();
+
+    let (alice, key_alice) = crypto::gen_keypair();
+    let (bob, key_bob) = crypto::gen_keypair();
+    let (carol, carol_key) = crypto::gen_keypair();
+    let create_alice = CreateWallet::sign(ALICE_NAME, &alice, &key_alice);
+    let create_bob = CreateWallet::sign(BOB_NAME, &bob, &key_bob);
+    testkit.create_block_with_transactions(vec![create_alice.clone(), create_bob.clone()]);
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+    let multisig = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol].iter().cloned().collect(),
+        20,
+        0,
+    );
+    testkit.create_block_with_transactions(vec![multisig.clone()]);
+    let rejection = RejectTransferMultisig::sign(carol, &carol_key, multisig.hash());
+    testkit.create_block_with_transactions(vec![rejection.clone()]);
+    // Failed transactions change nothing.
+    let overcharge = Transfer::sign(&bob, &alice, 1_000, 1, &key_bob);
+    testkit.create_block_with_transactions(vec![overcharge]);
+
+    let snapshot = testkit.snapshot();
+    let mut expected = blockchain::Schema::new(&snapshot)
+        .block_transactions(Height(1))
+        .iter()
+        .flat_map(|tx_hash| {
+            let (pub_key, name) = if tx_hash == create_alice.hash() {
+                (alice, ALICE_NAME)
+            } else {
+                (bob, BOB_NAME)
+            };
+            vec![
+                ServiceEvent::WalletCreated {
+                    height: Height(1),
+                    tx_hash,
+                    pub_key,
+                    name: name.to_owned(),
+                },
+                ServiceEvent::BalanceChanged {
+                    height: Height(1),
+                    tx_hash,
+                    pub_key,
+                    incoming: true,
+                    amount: Amount::from(100),
+                    balance: Amount::from(100),
+                },
+            ]
+        })
+        .collect::<Vec<_>>();
+    let balance_changed = |height, tx_hash, pub_key, incoming, amount: u64, balance: u64| {
+        ServiceEvent::BalanceChanged {
+            height: Height(height),
+            tx_hash,
+            pub_key,
+            incoming,
+            amount: Amount::from(amount),
+            balance: Amount::from(balance),
+        }
+    };
+    expected.extend(vec![
+        balance_changed(2, transfer.hash(), alice, false, 10, 90),
+        balance_changed(2, transfer.hash(), bob, true, 10, 110),
+        ServiceEvent::MultisigStateChanged {
+            height: Height(3),
+            tx_hash: multisig.hash(),
+            state: State::InProcess,
+        },
+        balance_changed(3, multisig.hash(), alice, false, 20, 70),
+        ServiceEvent::MultisigStateChanged {
+            height: Height(4),
+            tx_hash: multisig.hash(),
+            state: State::Rejected,
+        },
+        balance_changed(4, rejection.hash(), alice, true, 20, 90),
+    ]);
+
+    let received = events.take(expected.len() as u64).collect().wait().unwrap();
+    assert_eq!(received, expected);
+
+    // The log file is written asynchronously.
+    let start = Instant::now();
+    let lines = loop {
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let lines = content.lines().map(str::to_owned).collect::<Vec<_>>();
+        if lines.len() >= expected.len() || start.elapsed() > Duration::from_secs(5) {
+            break lines;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let logged = lines
+        .iter()
+        .map(|line| serde_json::from_str::<ServiceEvent>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(logged, expected);
+    let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(first["event"], json!("wallet_created"));
+    fs::remove_file(&path).unwrap();
+}
+
+/// Starts an HTTP server answering every request with the given status line, and
+/// returns the URL of the server together with the notifications it receives.
+fn start_webhook_server(status: &'static str) -> (String, mpsc::Receiver<WalletNotification>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = BufReader::new(stream.unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let mut header = line.splitn(2, ':');
+                if header
+                    .next()
+                    .unwrap()
+                    .eq_ignore_ascii_case("content-length")
+                {
+                    content_length = header.next().unwrap().trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            stream.read_exact(&mut body).unwrap();
+            write!(
+                stream.get_mut(),
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+            if sender.send(serde_json::from_slice(&body).unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    (url, receiver)
+}
+
+#[test]
+fn test_webhooks() {
+    let suffix = crypto::gen_keypair().0.to_hex();
+    let storage = env::temp_dir().join(format!("cryptocurrency-webhooks-{}.json", suffix));
+    let dead_letters =
+        env::temp_dir().join(format!("cryptocurrency-dead-letters-{}.jsonl", suffix));
+    let webhooks = WebhookRegistry::new()
+        .with_storage(&storage)
+        .with_dead_letter_log(&dead_letters)
+        .with_retries(2, Duration::from_millis(10));
+    let service = Service::default().with_webhooks(webhooks);
+    let mut testkit = TestKitBuilder::validator().with_service(service).create();
+    let api = CryptocurrencyClient::new(testkit.api());
+
+    let register = |pub_key: PublicKey, url: &str| {
+        api.inner
+            .private(ApiKind::Service("cryptocurrency"))
+            .query(&WebhookQuery {
+                pub_key,
+                url: url.to_owned(),
+            })
+            .post::<WebhookRegistered>("v1/hooks/register")
+    };
+    let remove = |pub_key: PublicKey, url: &str| -> WebhookRemoved {
+        api.inner
+            .private(ApiKind::Service("cryptocurrency"))
+            .query(&WebhookQuery {
+                pub_key,
+                url: url.to_owned(),
+            })
+            .post("v1/hooks/remove")
+            .unwrap()
+    };
+
+    let (alice, key_alice) = crypto::gen_keypair();
+    let (bob, key_bob) = crypto::gen_keypair();
+    let (carol, key_carol) = crypto::gen_keypair();
+    let (url, deliveries) = start_webhook_server("200 OK");
+    let (failing_url, failed_deliveries) = start_webhook_server("500 Internal Server Error");
+
+    assert!(register(alice, &url).unwrap().registered);
+    assert!(!register(alice, &url).unwrap().registered);
+    assert!(register(bob, &failing_url).unwrap().registered);
+    assert_api_error(
+        register(alice, "ftp://127.0.0.1/hook").unwrap_err(),
+        ErrorKind::InvalidQuery,
+    );
+    assert_api_error(
+        register(alice, "not a url").unwrap_err(),
+        ErrorKind::InvalidQuery,
+    );
+    // The endpoints are not exposed publicly.
+    let err = api
+        .inner
+        .public(ApiKind::Service("cryptocurrency"))
+        .query(&WebhookQuery {
+            pub_key: carol,
+            url: url.clone(),
+        })
+        .post::<WebhookRegistered>("v1/hooks/register")
+        .unwrap_err();
+    assert_matches!(err, api::Error::NotFound(_));
+    // Registrations are persisted.
+    let mut registered = vec![
+        Webhook {
+            pub_key: alice,
+            url: url.clone(),
+        },
+        Webhook {
+            pub_key: bob,
+            url: failing_url.clone(),
+        },
+    ];
+    registered.sort();
+    assert_eq!(
+        WebhookRegistry::new().with_storage(&storage).webhooks(),
+        registered
+    );
+
+    let create_alice = CreateWallet::sign(ALICE_NAME, &alice, &key_alice);
+    let create_bob = CreateWallet::sign(BOB_NAME, &bob, &key_bob);
+    let create_carol = CreateWallet::sign("Carol", &carol, &key_carol);
+    testkit.create_block_with_transactions(vec![create_alice.clone(), create_bob, create_carol]);
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+
+    let timeout = Duration::from_secs(5);
+    assert_eq!(
+        deliveries.recv_timeout(timeout).unwrap(),
+        WalletNotification {
+            height: Height(1),
+            pub_key: alice,
+            tx_hashes: vec![create_alice.hash()],
+        }
+    );
+    assert_eq!(
+        deliveries.recv_timeout(timeout).unwrap(),
+        WalletNotification {
+            height: Height(2),
+            pub_key: alice,
+            tx_hashes: vec![transfer.hash()],
+        }
+    );
+
+    // Undelivered notifications are retried and then written to the dead-letter log.
+    let failed = (0..4)
+        .map(|_| failed_deliveries.recv_timeout(timeout).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(failed[0], failed[1]);
+    assert_eq!(failed[2], failed[3]);
+    assert_eq!(failed[2].height, Height(2));
+    assert_eq!(failed[2].tx_hashes, vec![transfer.hash()]);
+    let start = Instant::now();
+    let letters = loop {
+        let content = fs::read_to_string(&dead_letters).unwrap_or_default();
+        let letters = content
+            .lines()
+            .map(|line| serde_json::from_str::<DeadLetter>(line).unwrap())
+            .collect::<Vec<_>>();
+        if letters.len() >= 2 || start.elapsed() > timeout {
+            break letters;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(letters.len(), 2);
+    for (letter, notification) in letters.iter().zip(&[&failed[0], &failed[2]]) {
+        assert_eq!(letter.url, failing_url);
+        assert_eq!(&letter.notification, *notification);
+        assert_eq!(letter.attempts, 2);
+    }
+
+    // Blocks not touching the registered wallets are not notified of, as well as
+    // the blocks committed after the webhooks are removed.
+    testkit.create_block_with_transactions(vec![Issue::sign(&carol, 10, 0, &key_carol)]);
+    assert!(remove(alice, &url).removed);
+    assert!(!remove(alice, &url).removed);
+    assert!(remove(bob, &failing_url).removed);
+    testkit.create_block_with_transactions(vec![Transfer::sign(&alice, &bob, 10, 1, &key_alice)]);
+    assert!(deliveries.recv_timeout(Duration::from_millis(500)).is_err());
+    assert!(failed_deliveries.try_recv().is_err());
+    assert!(WebhookRegistry::new()
+        .with_storage(&storage)
+        .webhooks()
+        .is_empty());
+
+    fs::remove_file(&storage).unwrap();
+    fs::remove_file(&dead_letters).unwrap();
+}
+
+#[test]
+fn test_error_codes() {
+    let (_testkit, api) = create_testkit();
+
+    let catalog = api.error_codes().unwrap();
+    assert_eq!(catalog.len(), Error::ALL.len());
+    for (index, (entry, error)) in catalog.iter().zip(Error::ALL.iter()).enumerate() {
+        assert_eq!(entry.code as usize, index);
+        assert_eq!(entry.code, *error as u8);
+        assert_eq!(entry.name, format!("{:?}", error));
+        assert_eq!(entry.description, error.to_string());
+        assert!(!entry.emitted_by.is_empty());
+    }
+    assert_eq!(
+        catalog[3],
+        ErrorCodeInfo {
+            code: 3,
+            name: "InsufficientCurrencyAmount".to_owned(),
+            description: "Insufficient currency amount".to_owned(),
+            emitted_by: vec![
+                "Transfer".to_owned(),
+                "TransferMultisig".to_owned(),
+                "ProposeJointSpend".to_owned(),
+                "ApproveJointSpend".to_owned(),
+                "Clearing".to_owned(),
+                "CreateWalletFor".to_owned(),
+                "OpenStream".to_owned(),
+                "Stake".to_owned(),
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_config() {
+    assert_eq!(Config::from_stored(None), Ok(Config::default()));
+    assert_eq!(
+        Config::from_stored(Some(&serde_json::Value::Null)),
+        Ok(Config::default())
+    );
+    assert_eq!(
+        Config::default(),
+        Config {
+            initial_balance: Amount::from(100),
+            max_approvers: MAX_APPROVERS,
+            max_comment_len: MAX_COMMENT_LEN,
+            decimals: 0,
+            clearing_operator: None,
+            max_clearing_legs: MAX_CLEARING_LEGS,
+            staking_reward_interval: STAKING_REWARD_INTERVAL,
+            staking_reward_rate: Amount::ZERO,
+            overdraft_admin: None,
+            settled_multisig_retention: None,
+            max_list_items: MAX_LIST_ITEMS,
+        }
+    );
+
+    // Absent fields take the default values.
+    let partial = json!({ "initial_balance": 50 });
+    assert_eq!(
+        Config::from_stored(Some(&partial)),
+        Ok(Config {
+            initial_balance: Amount::from(50),
+            ..Config::default()
+        })
+    );
+
+    // Amounts are written as decimal strings.
+    let operator = PublicKey::new([1; 32]);
+    let full = Config {
+        initial_balance: "0.25".parse().unwrap(),
+        max_approvers: 10,
+        max_comment_len: MAX_COMMENT_LEN_LIMIT,
+        decimals: 2,
+        clearing_operator: Some(operator),
+        max_clearing_legs: 16,
+        staking_reward_interval: 10,
+        staking_reward_rate: "0.01".parse().unwrap(),
+        staking_supply_cap: Some(Amount::from(1_000)),
+        overdraft_admin: Some(operator),
+        settled_multisig_retention: Some(100),
+        max_list_items: 500,
+    };
+    let value = serde_json::to_value(&full).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "initial_balance": "0.25",
+            "max_approvers": 10,
+            "max_comment_len": 4_096,
+            "decimals": 2,
+            "clearing_operator": operator.to_hex(),
+            "max_clearing_legs": 16,
+            "staking_reward_interval": 10,
+            "staking_reward_rate": "0.01",
+            "staking_supply_cap": "1000",
+            "overdraft_admin": operator.to_hex(),
+            "settled_multisig_retention": 100,
+            "max_list_items": 500,
+        })
+    );
+    assert_eq!(Config::from_stored(Some(&value)), Ok(full));
+
+    // All the errors are reported at once.
+    let invalid = Config {
+        max_approvers: 0,
+        max_comment_len: MAX_COMMENT_LEN_LIMIT + 1,
+        max_clearing_legs: 0,
+        staking_reward_interval: 0,
+        settled_multisig_retention: Some(0),
+        max_list_items: 0,
+        ..Config::default()
+    };
+    assert_eq!(
+        invalid.validate(),
+        Err(vec![
+            ConfigError::NoApprovers,
+            ConfigError::CommentLenTooLarge(MAX_COMMENT_LEN_LIMIT + 1),
+            ConfigError::NoClearingLegs,
+            ConfigError::NoStakingRewardInterval,
+            ConfigError::NoSettledMultisigRetention,
+            ConfigError::NoListItems,
+        ])
+    );
+    let too_precise = Config {
+        initial_balance: "0.125".parse().unwrap(),
+        decimals: 2,
+        ..Config::default()
+    };
+    assert_eq!(
+        too_precise.validate(),
+        Err(vec![ConfigError::InitialBalanceTooPrecise])
+    );
+    let too_many_decimals = Config {
+        decimals: Amount::DECIMALS + 1,
+        ..Config::default()
+    };
+    assert_eq!(
+        too_many_decimals.validate(),
+        Err(vec![ConfigError::TooManyDecimals(Amount::DECIMALS + 1)])
+    );
+    let value = serde_json::to_value(&invalid).unwrap();
+    assert!(Config::from_stored(Some(&value)).is_err());
+
+    // Unknown fields are likely typos, so they are not ignored.
+    let misspelled = json!({ "max_aprovers": 3 });
+    match Config::from_stored(Some(&misspelled)) {
+        Err(ref errors) if errors.len() == 1 => match errors[0] {
+            ConfigError::Malformed(ref message) => assert!(message.contains("max_aprovers")),
+            ref other => panic!("Unexpected error {:?}", other),
+        },
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_max_list_items() {
+    let (mut testkit, api) = create_testkit_with(Config {
+        max_list_items: 2,
+        ..Config::default()
+    });
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    testkit.create_block_with_transactions(vec![transfer.clone()]);
+
+    // Lists of up to `max_list_items` items are returned as usual.
+    assert_eq!(
+        api.affected_wallets(transfer.hash()).unwrap(),
+        vec![alice, bob]
+    );
+    let range = api.wallet_history_range(alice, 0, 2).unwrap();
+    assert_eq!(range.transactions.len(), 2);
+    assert_eq!(api.block_activity(1).unwrap().transactions.len(), 2);
+
+    // Longer lists fail instead of being returned in full.
+    api.create_wallet("Carol").unwrap();
+    api.create_wallet("Dave").unwrap();
+    api.transaction(&Issue::sign(&alice, 1, 0, &key_alice))
+        .unwrap();
+    testkit.create_block();
+    let err = api.wallet_history_range(alice, 0, 3).unwrap_err();
+    let error = assert_api_error(err, ErrorKind::ListTooLarge);
+    assert_eq!(error.details, Some(json!({ "max_items": 2 })));
+    let err = api.wallet_history_since(alice, 0).unwrap_err();
+    assert_api_error(err, ErrorKind::ListTooLarge);
+    let err = api.wallet_info(alice).unwrap_err();
+    assert_api_error(err, ErrorKind::ListTooLarge);
+    let err = api.block_activity(3).unwrap_err();
+    assert_api_error(err, ErrorKind::ListTooLarge);
+
+    // Shorter pages of the same lists are still available.
+    let range = api.wallet_history_range(alice, 1, 3).unwrap();
+    assert_eq!(range.transactions.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "Invalid service configuration")]
+fn test_invalid_config_rejected_at_init() {
+    Service::default().with_config(Config {
+        max_approvers: 0,
+        ..Config::default()
+    });
+}
+
+#[test]
+fn test_config_in_genesis() {
+    let config = Config {
+        initial_balance: Amount::from(50),
+        max_approvers: 1,
+        max_comment_len: 4,
+        ..Config::default()
+    };
+    let (mut testkit, api) = create_testkit_with(config.clone());
+    assert_eq!(Schema::new(&testkit.snapshot()).config(), config);
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 50);
+    assert_eq!(Schema::new(&testkit.snapshot()).total_supply(), 100);
+
+    let (carol, carol_key) = crypto::gen_keypair();
+    let (dave, _) = crypto::gen_keypair();
+    let too_many = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol, dave].iter().cloned().collect(),
+        10,
+        0,
+    );
+    let transfer = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol].iter().cloned().collect(),
+        10,
+        1,
+    );
+    testkit.create_block_with_transactions(vec![too_many.clone(), transfer.clone()]);
+    assert_tx_status(
+        &api,
+        too_many.hash(),
+        &json!({ "type": "error", "code": 6, "description": "Approvers list is too large" }),
+    );
+    assert_tx_status(&api, transfer.hash(), &json!({ "type": "success" }));
+
+    let approval =
+        ApproveTransferMultisig::sign_with_comment(carol, &carol_key, transfer.hash(), "Fine!");
+    testkit.create_block_with_transactions(vec![approval.clone()]);
+    assert_tx_status(
+        &api,
+        approval.hash(),
+        &json!({ "type": "error", "code": 17, "description": "Comment is too long" }),
+    );
+}
+
+#[test]
+fn test_invalid_config_change_ignored() {
+    let config = Config {
+        initial_balance: Amount::from(50),
+        ..Config::default()
+    };
+    let (mut testkit, api) = create_testkit_with(config.clone());
+
+    // The previous valid configuration stays in effect.
+    let mut proposal = testkit.configuration_change_proposal();
+    proposal.set_actual_from(Height(3));
+    proposal.set_service_config("cryptocurrency", json!({ "max_approvers": 0 }));
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(2));
+    assert_eq!(Schema::new(&testkit.snapshot()).config(), config);
+
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, tx_alice.hash(), &json!({ "type": "success" }));
+    let alice = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(alice.balance, 50);
+
+    // A later valid configuration replaces it.
+    let mut proposal = testkit.configuration_change_proposal();
+    proposal.set_actual_from(Height(6));
+    proposal.set_service_config("cryptocurrency", json!({ "initial_balance": "70" }));
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(5));
+    assert_eq!(
+        Schema::new(&testkit.snapshot()).config(),
+        Config {
+            initial_balance: Amount::from(70),
+            ..Config::default()
+        }
+    );
+}
+
+#[test]
+fn test_amount_parsing() {
+    let amount = |s: &str| s.parse::<Amount>();
+    assert_eq!(amount("12"), Ok(Amount::from(12)));
+    assert_eq!(
+        amount("12.50"),
+        Ok(Amount::from_raw(12_500_000_000_000_000_000))
+    );
+    assert_eq!(amount("0.000000000000000001"), Ok(Amount::from_raw(1)));
+    assert_eq!(amount("0.0000000000000000010"), Ok(Amount::from_raw(1)));
+    assert_eq!(
+        amount("0.0000000000000000001"),
+        Err(AmountError::TooPrecise)
+    );
+    assert_eq!(amount(&Amount::MAX.to_string()), Ok(Amount::MAX));
+    assert_eq!(amount("340282366920938463464"), Err(AmountError::Overflow));
+    for &invalid in &["", ".5", "5.", "-1", "1e3", " 1", "1.2.3"] {
+        assert_eq!(
+            amount(invalid),
+            Err(AmountError::Invalid(invalid.to_string()))
+        );
+    }
+
+    assert_eq!(Amount::from(12).to_string(), "12");
+    assert_eq!(amount("12.50").unwrap().to_string(), "12.5");
+    assert_eq!(Amount::from_raw(1).to_string(), "0.000000000000000001");
+    assert_eq!(
+        Amount::MAX.to_string(),
+        "340282366920938463463.374607431768211455"
+    );
+    assert_eq!(amount("0.125").unwrap().decimals(), 3);
+    assert_eq!(Amount::from(12).decimals(), 0);
+
+    // Amounts are written to JSON as strings, but whole numbers are accepted as well.
+    let half = amount("0.5").unwrap();
+    assert_eq!(serde_json::to_value(half).unwrap(), json!("0.5"));
+    assert_eq!(
+        serde_json::from_value::<Amount>(json!("0.5")).unwrap(),
+        half
+    );
+    assert_eq!(
+        serde_json::from_value::<Amount>(json!(7)).unwrap(),
+        Amount::from(7)
+    );
+    assert!(serde_json::from_value::<Amount>(json!(-7)).is_err());
+    assert!(serde_json::from_value::<Amount>(json!(0.5)).is_err());
+
+    assert_eq!(Amount::MAX.checked_add(Amount::from_raw(1)), None);
+    assert_eq!(Amount::ZERO.checked_sub(Amount::from_raw(1)), None);
+    assert_eq!(Amount::MAX.saturating_add(Amount::from(1)), Amount::MAX);
+}
+
+#[test]
+fn test_amount_encoding() {
+    let fraction = "5.5".parse::<Amount>().unwrap();
+    let beyond_u64 = Amount::from(u64::max_value())
+        .checked_add(Amount::from(1))
+        .unwrap();
+    for &amount in &[
+        Amount::ZERO,
+        Amount::from(5),
+        fraction,
+        beyond_u64,
+        Amount::MAX,
+    ] {
+        assert_eq!(Amount::from_bytes(amount.into_bytes().into()), amount);
+        assert_eq!(Amount::from_pb(amount.to_pb()).unwrap(), amount);
+    }
+    // Whole amounts are stored as the `u64` units written by the previous versions.
+    assert_eq!(Amount::from(5).into_bytes(), 5_u64.into_bytes());
+    assert_eq!(fraction.into_bytes().len(), 16);
+    assert_eq!(beyond_u64.into_bytes().len(), 16);
+
+    // Whole amounts are written to the legacy field only, so the messages holding them
+    // are encoded and hashed as before.
+    let (to, _) = crypto::gen_keypair();
+    let transfer = Transfer {
+        to,
+        amount: Amount::from(10),
+        seed: 0,
+    };
+    let pb = transfer.to_pb();
+    assert_eq!(pb.get_amount(), 10);
+    assert!(!pb.has_exact_amount());
+
+    for &amount in &[fraction, beyond_u64] {
+        let transfer = Transfer { amount, ..transfer };
+        let pb = transfer.to_pb();
+        assert_eq!(pb.get_amount(), 0);
+        assert!(pb.has_exact_amount());
+        assert_eq!(Transfer::from_pb(pb).unwrap().amount, amount);
+    }
+}
+
+#[test]
+fn test_fractional_amounts() {
+    let amount = |s: &str| s.parse::<Amount>().unwrap();
+    let config = Config {
+        decimals: 2,
+        ..Config::default()
+    };
+    let (mut testkit, api) = create_testkit_with(config);
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let transfer = Transfer::sign(&alice, &bob, amount("12.5"), 0, &key_alice);
+    let too_precise = Transfer::sign(&alice, &bob, amount("0.125"), 1, &key_alice);
+    let issue = Issue::sign(&alice, amount("0.01"), 2, &key_alice);
+    testkit.create_block_with_transactions(vec![transfer.clone(), too_precise.clone(), issue]);
+    assert_tx_status(&api, transfer.hash(), &json!({ "type": "success" }));
+    assert_tx_status(
+        &api,
+        too_precise.hash(),
+        &json!({ "type": "error", "code": 19, "description": "Amount is too precise" }),
+    );
+
+    assert_eq!(
+        api.get_wallet(alice).unwrap().unwrap().balance,
+        amount("87.51")
+    );
+    assert_eq!(
+        api.get_wallet(bob).unwrap().unwrap().balance,
+        amount("112.5")
+    );
+    assert_eq!(
+        Schema::new(&testkit.snapshot()).total_supply(),
+        amount("200.01")
+    );
+    assert_balance_index(&testkit);
+
+    // Only whole amounts are allowed by default.
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let half = Transfer::sign(
+        &tx_alice.author(),
+        &tx_bob.author(),
+        amount("0.5"),
+        0,
+        &key_alice,
+    );
+    testkit.create_block_with_transactions(vec![half.clone()]);
+    assert_tx_status(
+        &api,
+        half.hash(),
+        &json!({ "type": "error", "code": 19, "description": "Amount is too precise" }),
+    );
+    assert_eq!(
+        api.get_wallet(tx_alice.author()).unwrap().unwrap().balance,
+        100
+    );
+}
+
+#[test]
+fn test_error_registry_is_complete() {
+    // The match stops compiling once a variant is added to `Error`, which forces
+    // updating the list of variants below together with the registry.
+    let variant_name = |error: Error| match error {
+        Error::WalletAlreadyExists
+        | Error::SenderNotFound
+        | Error::ReceiverNotFound
+        | Error::InsufficientCurrencyAmount
+        | Error::SenderSameAsReceiver
+        | Error::EmptyApproversList
+        | Error::ApproversListIsTooLarge
+        | Error::TransactionDoesNotExist
+        | Error::ReferredTransactionFailed
+        | Error::ReferredTransactionIsNotTransferMultisig
+        | Error::ApproverIsNotOnApproversList
+        | Error::TransferIsRejected
+        | Error::UnauthorizedIssuer
+        | Error::SupplyOverflow
+        | Error::TransferIsDone
+        | Error::TransferIsAlreadyApproved
+        | Error::TransferIsExpired
+        | Error::CommentTooLong
+        | Error::TransferIsCancelled
+        | Error::AmountTooPrecise
+        | Error::NotJointWalletOwner
+        | Error::JointSpendAlreadyApproved
+        | Error::JointThresholdUnreachable
+        | Error::JointWalletNotFound
+        | Error::UnauthorizedClearingOperator
+        | Error::TooManyClearingLegs
+        | Error::StreamAlreadyClosed
+        | Error::NotStreamParty
+        | Error::ZeroStreamRate
+        | Error::InsufficientStake
+        | Error::NoStakingRewards
+        | Error::InvalidApprovalSignature
+        | Error::EmptyApprovalBatch
+        | Error::UnauthorizedOverdraftAdmin
+        | Error::OverdraftBelowDebt
+        | Error::CashbackTooLarge
+        | Error::WalletNameTaken
+        | Error::TransferThresholdUnreachable
+        | Error::TransferExpirationPassed => error.name(),
+    };
+    let variants = [
+        Error::WalletAlreadyExists,
+        Error::SenderNotFound,
+        Error::ReceiverNotFound,
+        Error::InsufficientCurrencyAmount,
+        Error::SenderSameAsReceiver,
+        Error::EmptyApproversList,
+        Error::ApproversListIsTooLarge,
+        Error::TransactionDoesNotExist,
+        Error::ReferredTransactionFailed,
+        Error::ReferredTransactionIsNotTransferMultisig,
+        Error::ApproverIsNotOnApproversList,
+        Error::TransferIsRejected,
+        Error::UnauthorizedIssuer,
+        Error::SupplyOverflow,
+        Error::TransferIsDone,
+        Error::TransferIsAlreadyApproved,
+        Error::TransferIsExpired,
+        Error::CommentTooLong,
+        Error::TransferIsCancelled,
+        Error::AmountTooPrecise,
+        Error::NotJointWalletOwner,
+        Error::JointSpendAlreadyApproved,
+        Error::JointThresholdUnreachable,
+        Error::JointWalletNotFound,
+        Error::UnauthorizedClearingOperator,
+        Error::TooManyClearingLegs,
+        Error::StreamAlreadyClosed,
+        Error::NotStreamParty,
+        Error::ZeroStreamRate,
+        Error::InsufficientStake,
+        Error::NoStakingRewards,
+        Error::InvalidApprovalSignature,
+        Error::EmptyApprovalBatch,
+        Error::UnauthorizedOverdraftAdmin,
+        Error::OverdraftBelowDebt,
+        Error::CashbackTooLarge,
+        Error::WalletNameTaken,
+        Error::TransferThresholdUnreachable,
+        Error::TransferExpirationPassed,
+    ];
+
+    assert_eq!(Error::ALL.len(), variants.len());
+    for &variant in &variants {
+        let occurrences = Error::ALL.iter().filter(|&&error| error == variant).count();
+        assert_eq!(
+            occurrences,
+            1,
+            "{} must appear in the registry once",
+            variant_name(variant)
+        );
+    }
+}
+
+#[test]
+fn test_dry_run_transaction() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let overcharge = Transfer::sign(&alice, &bob, 110, 0, &key_alice);
+    let result = api
+        .dry_run_transaction(&messages::to_hex_string(&overcharge))
+        .unwrap();
+    assert_eq!(
+        result,
+        DryRunResult {
+            tx_hash: overcharge.hash(),
+            tx_type: "Transfer".to_owned(),
+            status: ExecutionStatus::Error {
+                code: 3,
+                description: "Insufficient currency amount".to_owned(),
+            },
+        }
+    );
+
+    let transfer = Transfer::sign(&alice, &bob, 10, 0, &key_alice);
+    let result = api
+        .dry_run_transaction(&messages::to_hex_string(&transfer))
+        .unwrap();
+    assert_eq!(result.status, ExecutionStatus::Success);
+
+    // Neither transaction is persisted or sent to the pool.
+    testkit.poll_events();
+    assert!(!testkit.is_tx_in_pool(&overcharge.hash()));
+    assert!(!testkit.is_tx_in_pool(&transfer.hash()));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 100);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 100);
+
+    // The signature is checked.
+    let mut tx_body = messages::to_hex_string(&transfer);
+    let last = if tx_body.ends_with('0') { "1" } else { "0" };
+    tx_body.pop();
+    tx_body.push_str(last);
+    let err = api.dry_run_transaction(&tx_body).unwrap_err();
+    assert_api_error(err, ErrorKind::MalformedMessage);
+}
+
+#[test]
+fn test_admin_issue() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+
+    let query = IssueQuery {
+        to: tx_alice.author(),
+        amount: Amount::from(50),
+        seed: None,
+    };
+    let response: TransactionResponse = api
+        .inner
+        .private(ApiKind::Service("cryptocurrency"))
+        .query(&query)
+        .post("v1/admin/issue")
+        .unwrap();
+    testkit.create_block();
+    assert_tx_status(&api, response.tx_hash, &json!({ "type": "success" }));
+    assert_eq!(
+        api.get_wallet(tx_alice.author()).unwrap().unwrap().balance,
+        150
+    );
+
+    // The endpoint is not exposed publicly.
+    let err = api
+        .inner
+        .public(ApiKind::Service("cryptocurrency"))
+        .query(&query)
+        .post::<TransactionResponse>("v1/admin/issue")
+        .unwrap_err();
+    assert_matches!(err, api::Error::NotFound(_));
+
+    // Only validators may sign `IssueTo`.
+    let tx = IssueTo::sign(&tx_alice.author(), &tx_alice.author(), 50, 0, &key_alice);
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 12, "description": "Issuer is not a validator" }),
+    );
+    assert_eq!(
+        api.get_wallet(tx_alice.author()).unwrap().unwrap().balance,
+        150
+    );
+}
+
+#[test]
+fn test_wallet_exists() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let (unknown_key, _) = crypto::gen_keypair();
+
+    let info: WalletInfo = api
+        .inner
+        .public(ApiKind::Service("cryptocurrency"))
+        .query(&WalletQuery {
+            id: tx_alice.author().into(),
+        })
+        .get("v1/wallets/info")
+        .unwrap();
+    assert!(info.exists);
+
+    let existence = api.wallet_exists(tx_alice.author()).unwrap();
+    assert!(existence.exists);
+
+    let existence = api.wallet_exists(unknown_key).unwrap();
+    assert!(!existence.exists);
+
+    // Verify the proof of absence against the state hash of the latest block.
+    let state_hash = *blockchain::Schema::new(&testkit.snapshot())
+        .last_block()
+        .state_hash();
+    let to_table = existence.wallet_proof.to_table.check().unwrap();
+    assert_eq!(to_table.merkle_root(), state_hash);
+    let (_, wallets_root) = to_table.entries().next().unwrap();
+
+    let to_wallet = existence.wallet_proof.to_wallet.check().unwrap();
+    assert_eq!(to_wallet.merkle_root(), *wallets_root);
+    assert!(to_wallet.entries().next().is_none());
+    assert!(to_wallet.missing_keys().any(|key| *key == unknown_key));
+}
+
+#[test]
+fn test_protobuf_responses() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, 0, &key_alice);
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+
+    let query = WalletQuery {
+        id: tx_alice.author().into(),
+    };
+    let service_api = api.transport().public(ApiKind::Service("cryptocurrency"));
+    let protobuf_accept = [("accept", PROTOBUF_CONTENT_TYPE)];
+
+    // JSON stays the default.
+    let response = service_api
+        .query(&query)
+        .get_raw("v1/wallets/info/simple", &[]);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.headers["content-type"], "application/json");
+
+    let response = service_api
+        .query(&query)
+        .get_raw("v1/wallets/info/simple", &protobuf_accept);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.headers["content-type"], PROTOBUF_CONTENT_TYPE);
+    let simple_info =
+        protobuf::parse_from_bytes::<proto::SimpleWalletInfo>(&response.body).unwrap();
+    let expected = api.simple_wallet_info(tx_alice.author()).unwrap();
+    assert_eq!(
+        simple_info.get_transactions().len(),
+        expected.transactions.len()
+    );
+    for (pb, info) in simple_info
+        .get_transactions()
+        .iter()
+        .zip(&expected.transactions)
+    {
+        assert_eq!(Hash::from_pb(pb.get_hash().clone()).unwrap(), info.hash);
+        assert_eq!(Height(pb.get_height()), info.height);
+        assert_eq!(pb.has_tx_type(), info.tx_type.is_some());
+        assert_eq!(pb.has_amount(), info.amount.is_some());
+        assert_eq!(pb.has_counterparty(), info.counterparty.is_some());
+    }
+    let transfer = &simple_info.get_transactions()[1];
+    assert_eq!(transfer.get_tx_type(), "Transfer");
+    assert_eq!(transfer.get_amount(), 10);
+    assert_eq!(
+        PublicKey::from_pb(transfer.get_counterparty().clone()).unwrap(),
+        tx_bob.author()
+    );
+
+    let response = service_api
+        .query(&query)
+        .get_raw("v1/wallets/info", &protobuf_accept);
+    assert_eq!(response.status, 200);
+    let wallet_info = protobuf::parse_from_bytes::<proto::WalletInfo>(&response.body).unwrap();
+    assert!(wallet_info.get_exists());
+    let wallet = Wallet::from_pb(wallet_info.get_wallet().clone()).unwrap();
+    assert_eq!(wallet.pub_key, tx_alice.author());
+    assert_eq!(wallet.balance, 90);
+    let history = wallet_info
+        .get_transactions()
+        .iter()
+        .map(|bytes| {
+            assert!(Message::from_raw_buffer(bytes.clone()).is_ok());
+            crypto::hash(bytes)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(history, vec![tx_alice.hash(), tx.hash()]);
+}
+
+#[test]
+fn test_wallet_info_etag() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    let query = WalletQuery {
+        id: tx_alice.author().into(),
+    };
+    let service_api = api.transport().public(ApiKind::Service("cryptocurrency"));
+    let get = |endpoint: &str, headers: &[(&str, &str)]| {
+        service_api.query(&query).get_raw(endpoint, headers)
+    };
+
+    let response = get("v1/wallets/info", &[]);
+    assert_eq!(response.status, 200);
+    let etag = response.headers["etag"].clone();
+
+    // Nothing has changed.
+    let response = get("v1/wallets/info", &[("if-none-match", etag.as_str())]);
+    assert_eq!(response.status, 304);
+    assert!(response.body.is_empty());
+    assert_eq!(response.headers["etag"], etag);
+
+    // Protobuf responses are tagged separately.
+    let response = get(
+        "v1/wallets/info",
+        &[
+            ("if-none-match", etag.as_str()),
+            ("accept", PROTOBUF_CONTENT_TYPE),
+        ],
+    );
+    assert_eq!(response.status, 200);
+    assert_ne!(response.headers["etag"], etag);
+
+    let response = get("v1/wallets/info/simple", &[]);
+    let simple_etag = response.headers["etag"].clone();
+    let response = get(
+        "v1/wallets/info/simple",
+        &[("if-none-match", simple_etag.as_str())],
+    );
+    assert_eq!(response.status, 304);
+
+    // The wallet history changes.
+    let tx = Transfer::sign(&tx_bob.author(), &tx_alice.author(), 10, 0, &key_bob);
+    api.transaction(&tx).unwrap();
+    testkit.create_block();
+
+    let response = get("v1/wallets/info", &[("if-none-match", etag.as_str())]);
+    assert_eq!(response.status, 200);
+    let new_etag = response.headers["etag"].clone();
+    assert_ne!(new_etag, etag);
+    let info: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(info["wallet_history"]["total_len"], 2);
+
+    let response = get(
+        "v1/wallets/info/simple",
+        &[("if-none-match", simple_etag.as_str())],
+    );
+    assert_eq!(response.status, 200);
+
+    // A new block changes the tag as the block proof changes.
+    testkit.create_block();
+    let response = get("v1/wallets/info", &[("if-none-match", new_etag.as_str())]);
+    assert_eq!(response.status, 200);
+}
+
+#[test]
+fn test_wallet_info_large_history() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    for block in 0..8 {
+        let issues = (0..500)
+            .map(|seed| Issue::sign(&alice, 1, block * 500 + seed, &key_alice))
+            .collect::<Vec<_>>();
+        testkit.create_block_with_transactions(issues);
+    }
+
+    let wallet_info = |history_offset, history_limit| {
+        api.transport()
+            .public(ApiKind::Service("cryptocurrency"))
+            .query(&WalletInfoQuery {
+                id: alice.into(),
+                with_history: true,
+                history_offset,
+                history_limit,
+                verify: false,
+            })
+            .get::<WalletInfo>("v1/wallets/info")
+    };
+
+    // Only a single page of the history is ever read, wherever it is located.
+    for &offset in &[None, Some(0), Some(2_000), Some(3_950)] {
+        let history = wallet_info(offset, None).unwrap().wallet_history.unwrap();
+        assert_eq!(history.total_len, 4001);
+        let from = offset.unwrap_or(4001 - MAX_ITEMS_PER_REQUEST);
+        let to = cmp::min(from + MAX_ITEMS_PER_REQUEST, 4001);
+        assert_eq!(history.from, from);
+        assert_eq!(history.transactions.len() as u64, to - from);
+    }
+
+    // Requests for more entries than a page holds are refused before any entry is read.
+    let err = wallet_info(Some(0), Some(4001)).unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+}
+
+#[test]
+fn test_wallet_info_without_history() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    for seed in 0..300 {
+        api.transaction(&Issue::sign(&tx_alice.author(), 1, seed, &key_alice))
+            .unwrap();
+    }
+    testkit.create_block();
+
+    let wallet_info = |with_history| {
+        api.transport()
+            .public(ApiKind::Service("cryptocurrency"))
+            .query(&WalletInfoQuery {
+                id: tx_alice.author().into(),
+                with_history,
+                history_offset: None,
+                history_limit: None,
+                verify: false,
+            })
+            .get::<WalletInfo>("v1/wallets/info")
+            .unwrap()
+    };
+
+    let info = wallet_info(false);
+    assert!(info.exists);
+    assert!(info.wallet_history.is_none());
+    let wallet = info.wallet_proof.to_wallet.check().unwrap();
+    let (_, wallet) = wallet.entries().next().unwrap();
+    assert_eq!(wallet.history_len, 301);
+
+    let info = wallet_info(true);
+    let history = info.wallet_history.unwrap();
+    assert_eq!(history.transactions.len(), MAX_ITEMS_PER_REQUEST as usize);
+    assert_eq!(history.total_len, 301);
+
+    // Skipping the history is expected to be much cheaper.
+    let measure = |with_history| {
+        let start = Instant::now();
+        for _ in 0..5 {
+            wallet_info(with_history);
+        }
+        start.elapsed()
+    };
+    let with_history = measure(true);
+    let without_history = measure(false);
+    assert!(
+        without_history * 2 < with_history,
+        "with history: {:?}, without history: {:?}",
+        with_history,
+        without_history
+    );
+}
+
+#[test]
+fn test_wallet_info_history_pagination() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    for seed in 0..150 {
+        api.transaction(&Issue::sign(&tx_alice.author(), 1, seed, &key_alice))
+            .unwrap();
+    }
+    testkit.create_block();
+    let wallet = api.get_wallet(tx_alice.author()).unwrap().unwrap();
+    assert_eq!(wallet.history_len, 151);
+
+    let wallet_info = |history_offset, history_limit| {
+        api.transport()
+            .public(ApiKind::Service("cryptocurrency"))
+            .query(&WalletInfoQuery {
+                id: tx_alice.author().into(),
+                with_history: true,
+                history_offset,
+                history_limit,
+                verify: false,
+            })
+            .get::<WalletInfo>("v1/wallets/info")
+    };
+    let check_page = |history: WalletHistory, from: u64, len: usize| {
+        assert_eq!(history.from, from);
+        assert_eq!(history.total_len, 151);
+        assert_eq!(history.transactions.len(), len);
+        let entries = history
+            .proof
+            .validate(wallet.history_hash, wallet.history_len)
+            .unwrap();
+        let indices = entries.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(indices, (from..from + len as u64).collect::<Vec<_>>());
+        for ((_, record), tx) in entries.iter().zip(&history.transactions) {
+            assert_eq!(tx.message().hash(), record.tx_hash);
+        }
+    };
+
+    // By default the latest 100 entries are returned.
+    let info = wallet_info(None, None).unwrap();
+    check_page(info.wallet_history.unwrap(), 51, 100);
+
+    let info = wallet_info(Some(0), Some(10)).unwrap();
+    check_page(info.wallet_history.unwrap(), 0, 10);
+
+    let info = wallet_info(Some(145), Some(10)).unwrap();
+    check_page(info.wallet_history.unwrap(), 145, 6);
+
+    let info = wallet_info(None, Some(0)).unwrap();
+    assert!(info.exists);
+    assert!(info.wallet_history.is_none());
+
+    let err = wallet_info(None, Some(MAX_ITEMS_PER_REQUEST + 1)).unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+    let err = wallet_info(Some(151), None).unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+}
+
+#[test]
+fn test_update_wallets_batch() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let height = Height(2);
+
+    // Alice gets enough updates to fill the first history chunk and start another one.
+    let mut updates = vec![(alice, crypto::hash(&[0])), (bob, crypto::hash(&[0]))];
+    for i in 1..HISTORY_CHUNK_SIZE + 3 {
+        updates.push((alice, crypto::hash(&i.to_le_bytes())));
+    }
+    updates.push((bob, crypto::hash(&[1])));
+
+    let mut naive_fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut naive_fork);
+        for &(pub_key, tx_hash) in &updates {
+            let wallet = schema.wallet(&pub_key).unwrap();
+            let wallet = if pub_key == alice {
+                wallet.try_decrease_balance(Amount::from(1)).unwrap()
+            } else {
+                wallet.try_increase_balance(Amount::from(1)).unwrap()
+            };
+            schema.update_wallet(wallet, tx_hash, height);
+        }
+    }
+
+    let mut batch_fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut batch_fork);
+        let mut states: HashMap<_, _> = vec![alice, bob]
+            .into_iter()
+            .map(|pub_key| (pub_key, schema.wallet(&pub_key).unwrap()))
+            .collect();
+        let batch = updates
+            .iter()
+            .map(|&(pub_key, tx_hash)| {
+                let wallet = states[&pub_key].clone();
+                let wallet = if pub_key == alice {
+                    wallet.try_decrease_balance(Amount::from(1)).unwrap()
+                } else {
+                    wallet.try_increase_balance(Amount::from(1)).unwrap()
+                };
+                states.insert(pub_key, wallet.clone());
+                (wallet, tx_hash)
+            })
+            .collect();
+        schema.update_wallets_batch(batch, height);
+    }
+
+    let naive = Schema::new(&naive_fork);
+    let batched = Schema::new(&batch_fork);
+    assert_eq!(naive.state_hash(), batched.state_hash());
+    for pub_key in &[alice, bob] {
+        assert_eq!(naive.wallet(pub_key), batched.wallet(pub_key));
+        assert_eq!(
+            naive.wallet_history_chunk_roots(pub_key).merkle_root(),
+            batched.wallet_history_chunk_roots(pub_key).merkle_root()
+        );
+    }
+    let wallet = batched.wallet(&alice).unwrap();
+    assert_eq!(wallet.history_len, HISTORY_CHUNK_SIZE + 4);
+    assert_eq!(wallet.balance, 100 - (HISTORY_CHUNK_SIZE + 3));
+    assert_eq!(batched.wallet_history_chunk_roots(&alice).len(), 2);
+    assert_eq!(
+        naive
+            .affected_wallets(&crypto::hash(&[0]))
+            .iter()
+            .collect::<Vec<_>>(),
+        batched
+            .affected_wallets(&crypto::hash(&[0]))
+            .iter()
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_wallet_count() {
+    let (mut testkit, api) = create_testkit();
+    assert_eq!(api.wallet_count().unwrap(), 0);
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+
+    // A duplicate wallet and transfers must not affect the count.
+    let duplicate = CreateWallet::sign("Alice 2", &tx_alice.author(), &key_alice);
+    let transfer = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, 0, &key_alice);
+    api.transaction(&duplicate).unwrap();
+    api.transaction(&transfer).unwrap();
+    api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+
+    let snapshot = testkit.snapshot();
+    let wallets = Schema::new(&snapshot).wallets().iter().count() as u64;
+    assert_eq!(wallets, 3);
+    assert_eq!(api.wallet_count().unwrap(), wallets);
+}
+
+#[test]
+fn test_verify_wallet_proof() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    let (bob, _) = crypto::gen_keypair();
+
+    let trusted_hash = blockchain::Schema::new(&testkit.snapshot())
+        .last_block()
+        .hash();
+    let wallet = api.get_verified_wallet(alice, &trusted_hash).unwrap();
+    assert_eq!(wallet.unwrap().name, ALICE_NAME);
+    assert_eq!(api.get_verified_wallet(bob, &trusted_hash).unwrap(), None);
+
+    let info = api.wallet_info(alice).unwrap();
+    assert_eq!(
+        verify_wallet_proof(&info.block_proof, &info.wallet_proof, &alice, &trusted_hash)
+            .unwrap()
+            .unwrap()
+            .name,
+        ALICE_NAME
+    );
+    // The proof of one wallet tells nothing about another one.
+    assert_eq!(
+        verify_wallet_proof(&info.block_proof, &info.wallet_proof, &bob, &trusted_hash),
+        Err(ProofError::WalletNotCovered(bob))
+    );
+
+    // Proofs of the former latest block are rejected once another block is trusted.
+    testkit.create_block();
+    let new_trusted_hash = blockchain::Schema::new(&testkit.snapshot())
+        .last_block()
+        .hash();
+    assert_eq!(
+        verify_wallet_proof(
+            &info.block_proof,
+            &info.wallet_proof,
+            &alice,
+            &new_trusted_hash
+        ),
+        Err(ProofError::UntrustedBlock {
+            actual: trusted_hash,
+            trusted: new_trusted_hash,
+        })
+    );
+    assert!(api.get_verified_wallet(alice, &trusted_hash).is_err());
+}
+
+#[test]
+fn test_sign_with_random_seed() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let (first, first_seed) = Transfer::sign_with_random_seed(&alice, &bob, 10, &key_alice);
+    let (second, second_seed) = Transfer::sign_with_random_seed(&alice, &bob, 10, &key_alice);
+    assert_ne!(first.hash(), second.hash());
+    assert_ne!(first_seed, second_seed);
+    assert_eq!(
+        first,
+        Transfer::sign(&alice, &bob, 10, first_seed, &key_alice)
+    );
+
+    let (first, first_seed) = Issue::sign_with_random_seed(&alice, 10, &key_alice);
+    let (second, _) = Issue::sign_with_random_seed(&alice, 10, &key_alice);
+    assert_ne!(first.hash(), second.hash());
+    assert_eq!(first, Issue::sign(&alice, 10, first_seed, &key_alice));
+
+    let (first, first_seed) = IssueTo::sign_with_random_seed(&alice, &bob, 10, &key_alice);
+    let (second, _) = IssueTo::sign_with_random_seed(&alice, &bob, 10, &key_alice);
+    assert_ne!(first.hash(), second.hash());
+    assert_eq!(
+        first,
+        IssueTo::sign(&alice, &bob, 10, first_seed, &key_alice)
+    );
+
+    let approvers: HashSet<_> = vec![bob].into_iter().collect();
+    let (first, first_seed) =
+        TransferMultisig::sign_with_random_seed(alice, &key_alice, bob, approvers.clone(), 10);
+    let (second, _) =
+        TransferMultisig::sign_with_random_seed(alice, &key_alice, bob, approvers.clone(), 10);
+    assert_ne!(first.hash(), second.hash());
+    assert_eq!(
+        first,
+        TransferMultisig::sign(alice, &key_alice, bob, approvers, 10, first_seed)
+    );
+
+    // The returned seed finds the committed transfer.
+    let (transfer, seed) = Transfer::sign_with_random_seed(&alice, &bob, 10, &key_alice);
+    testkit.create_block_with_transaction(transfer.clone());
+    let transfers = api.transfers_by_seed(alice, seed).unwrap();
+    assert_eq!(transfers.transfers.len(), 1);
+    assert_eq!(transfers.transfers[0].tx_hash, transfer.hash());
+}
+
+#[test]
+fn test_unsigned_transactions() {
+    let (pk, sk) = crypto::gen_keypair();
+    let (to, _) = crypto::gen_keypair();
+    let tx_hash = crypto::hash(&[1, 2, 3]);
+    let approvers: HashSet<_> = vec![to].into_iter().collect();
+
+    // Signatures are deterministic, so the signature of the exposed bytes produces
+    // exactly the same message as signing in the process.
+    let cases = vec![
+        (
+            CreateWallet::unsigned(ALICE_NAME),
+            CreateWallet::sign(ALICE_NAME, &pk, &sk),
+        ),
+        (Issue::unsigned(10, 1), Issue::sign(&pk, 10, 1, &sk)),
+        (
+            IssueTo::unsigned(&to, 10, 2),
+            IssueTo::sign(&pk, &to, 10, 2, &sk),
+        ),
+        (
+            Transfer::unsigned(&to, 10, 3),
+            Transfer::sign(&pk, &to, 10, 3, &sk),
+        ),
+        (
+            TransferMultisig::unsigned(to, approvers.clone(), 10, 4),
+            TransferMultisig::sign(pk, &sk, to, approvers, 10, 4),
+        ),
+        (
+            ApproveTransferMultisig::unsigned(tx_hash, "ok"),
+            ApproveTransferMultisig::sign_with_comment(pk, &sk, tx_hash, "ok"),
+        ),
+        (
+            RejectTransferMultisig::unsigned(tx_hash, ""),
+            RejectTransferMultisig::sign(pk, &sk, tx_hash),
+        ),
+    ];
+    for (unsigned, expected) in cases {
+        assert_eq!(unsigned.service_id(), CRYPTOCURRENCY_SERVICE_ID);
+        let signature = crypto::sign(&unsigned.bytes_to_sign(&pk), &sk);
+        let tx = unsigned.with_signature(pk, &signature).unwrap();
+        assert_eq!(tx.author(), pk);
+        assert_eq!(tx, expected);
+        assert!(WalletTransactions::tx_from_raw(tx.payload().clone()).is_ok());
+    }
+
+    // A signature of another author or of other bytes is rejected.
+    let unsigned = Transfer::unsigned(&to, 10, 3);
+    let signature = crypto::sign(&unsigned.bytes_to_sign(&pk), &sk);
+    assert_eq!(
+        unsigned.with_signature(to, &signature),
+        Err(InvalidSignature)
+    );
+    let other_signature = crypto::sign(&Transfer::unsigned(&to, 11, 3).bytes_to_sign(&pk), &sk);
+    assert_eq!(
+        unsigned.with_signature(pk, &other_signature),
+        Err(InvalidSignature)
+    );
+}
+
+#[test]
+fn test_unsigned_transactions_execute() {
+    let (mut testkit, api) = create_testkit();
+    let (alice, alice_sk) = crypto::gen_keypair();
+    let (bob, bob_sk) = crypto::gen_keypair();
+
+    // Signs the exposed bytes as an external signer would.
+    let sign = |unsigned: UnsignedTx, pk: PublicKey, sk: &crypto::SecretKey| {
+        let signature = crypto::sign(&unsigned.bytes_to_sign(&pk), sk);
+        unsigned.with_signature(pk, &signature).unwrap()
+    };
+
+    testkit.create_block_with_transactions(vec![
+        sign(CreateWallet::unsigned(ALICE_NAME), alice, &alice_sk),
+        sign(CreateWallet::unsigned(BOB_NAME), bob, &bob_sk),
+    ]);
+    let transfer = sign(Transfer::unsigned(&bob, 10, 0), alice, &alice_sk);
+    testkit.create_block_with_transaction(transfer.clone());
+    assert_tx_status(&api, transfer.hash(), &json!({ "type": "success" }));
+
+    let wallet = api.get_wallet(bob).unwrap().unwrap();
+    assert_eq!(wallet.balance, 110);
+}
+
+#[test]
+fn test_joint_wallet() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    let (tx_carol, key_carol) = api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+    let (alice, bob, carol) = (tx_alice.author(), tx_bob.author(), tx_carol.author());
+
+    // The order of the owners does not matter.
+    let create = CreateJointWallet::sign(&alice, &[carol, alice, bob], 2, "Joint", 0, &key_alice);
+    testkit.create_block_with_transaction(create.clone());
+    assert_tx_status(&api, create.hash(), &json!({ "type": "success" }));
+
+    let address = joint_wallet_address(&[alice, bob, carol]);
+    let wallet = api.get_wallet(address).unwrap().unwrap();
+    assert_eq!(wallet.name, "Joint");
+    assert_eq!(wallet.balance, 0);
+    let info = api.wallet_info(address).unwrap();
+    let joint = info.joint.unwrap();
+    assert_eq!(joint, JointWallet::new(&[alice, bob, carol], 2));
+    assert!(joint.owners.windows(2).all(|pair| pair[0] < pair[1]));
+    // Plain wallets are not marked as joint.
+    assert_eq!(api.wallet_info(alice).unwrap().joint, None);
+    assert_api_error(
+        api.joint_wallet(alice).unwrap_err(),
+        ErrorKind::WalletNotFound,
+    );
+
+    testkit.create_block_with_transaction(Transfer::sign(&alice, &address, 50, 0, &key_alice));
+    let propose = ProposeJointSpend::sign(&alice, &address, &bob, 20, 0, &key_alice);
+    testkit.create_block_with_transaction(propose.clone());
+    assert_tx_status(&api, propose.hash(), &json!({ "type": "success" }));
+
+    // The spend awaits the second signature.
+    assert_eq!(api.get_wallet(address).unwrap().unwrap().balance, 50);
+    let info = api.joint_wallet(address).unwrap();
+    assert_eq!(info.owners, joint.owners);
+    assert_eq!(info.threshold, 2);
+    assert_eq!(
+        info.pending,
+        vec![PendingJointSpend {
+            proposal: propose.hash(),
+            to: bob,
+            amount: Amount::from(20),
+            signed_by: vec![alice],
+        }]
+    );
+
+    let (outsider, key_outsider) = crypto::gen_keypair();
+    let repeated = ApproveJointSpend::sign(alice, &key_alice, propose.hash());
+    let foreign = ApproveJointSpend::sign(outsider, &key_outsider, propose.hash());
+    testkit.create_block_with_transactions(vec![repeated.clone(), foreign.clone()]);
+    assert_tx_status(
+        &api,
+        repeated.hash(),
+        &json!({
+            "type": "error",
+            "code": 21,
+            "description": "Joint spend is already approved by the owner",
+        }),
+    );
+    assert_tx_status(
+        &api,
+        foreign.hash(),
+        &json!({
+            "type": "error",
+            "code": 20,
+            "description": "Not an owner of the joint wallet",
+        }),
+    );
+
+    let approve = ApproveJointSpend::sign(carol, &key_carol, propose.hash());
+    testkit.create_block_with_transaction(approve.clone());
+    assert_tx_status(&api, approve.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(address).unwrap().unwrap().balance, 30);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 120);
+    assert!(api.joint_wallet(address).unwrap().pending.is_empty());
+    let spend = Schema::new(&testkit.snapshot())
+        .joint_spend(&propose.hash())
+        .unwrap();
+    assert!(spend.is_done());
+    assert_eq!(spend.progress(), (2, 2));
+
+    let late = ApproveJointSpend::sign(bob, &key_bob, propose.hash());
+    testkit.create_block_with_transaction(late.clone());
+    assert_tx_status(
+        &api,
+        late.hash(),
+        &json!({ "type": "error", "code": 14, "description": "Transfer is done" }),
+    );
+}
+
+#[test]
+fn test_joint_wallet_errors() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, _) = crypto::gen_keypair();
+
+    let error_code = |testkit: &mut TestKit, tx: messages::Signed<messages::RawTransaction>| {
+        testkit.create_block_with_transaction(tx.clone());
+        match api.transaction_status(tx.hash()).unwrap().unwrap() {
+            TxStatus::Error { code, .. } => Some(code),
+            status => {
+                assert_eq!(status, TxStatus::Success);
+                None
+            }
+        }
+    };
+
+    // The author must be one of the owners.
+    let tx = CreateJointWallet::sign(&alice, &[bob, carol], 1, "Joint", 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(20));
+    for threshold in &[0, 3] {
+        let tx = CreateJointWallet::sign(&alice, &[alice, bob], *threshold, "Joint", 0, &key_alice);
+        assert_eq!(error_code(&mut testkit, tx), Some(22));
+    }
+    let owners = (0..=MAX_APPROVERS)
+        .map(|_| crypto::gen_keypair().0)
+        .chain(Some(alice))
+        .collect::<Vec<_>>();
+    let tx = CreateJointWallet::sign(&alice, &owners, 1, "Joint", 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(6));
+
+    let tx = CreateJointWallet::sign(&alice, &[alice, bob], 1, "Joint", 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    // Duplicate owners are ignored, so the owner set and the address are the same.
+    let tx = CreateJointWallet::sign(&bob, &[bob, alice, bob], 2, "Joint 2", 0, &key_bob);
+    assert_eq!(error_code(&mut testkit, tx), Some(0));
+
+    let address = joint_wallet_address(&[alice, bob]);
+    let tx = ProposeJointSpend::sign(&alice, &alice, &bob, 1, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(23));
+    let tx = ProposeJointSpend::sign(&alice, &address, &address, 1, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(4));
+    let tx = ProposeJointSpend::sign(&alice, &address, &carol, 1, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(2));
+    let tx = ProposeJointSpend::sign(&alice, &address, &bob, 1, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(3));
+    let tx = ApproveJointSpend::sign(bob, &key_bob, Hash::zero());
+    assert_eq!(error_code(&mut testkit, tx), Some(7));
+
+    // With the threshold of one, the proposal is executed at once.
+    testkit.create_block_with_transaction(Transfer::sign(&bob, &address, 10, 0, &key_bob));
+    let tx = ProposeJointSpend::sign(&bob, &address, &alice, 4, 0, &key_bob);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    assert_eq!(api.get_wallet(address).unwrap().unwrap().balance, 6);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 104);
+    assert!(Schema::new(&testkit.snapshot())
+        .joint_pending(&address)
+        .iter()
+        .next()
+        .is_none());
+}
+
+#[test]
+fn test_create_wallet_for() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    let (bob, _) = crypto::gen_keypair();
+
+    let tx = CreateWalletFor::sign(&alice, &bob, BOB_NAME, 0, &key_alice);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
+
+    let wallet = api.get_wallet(bob).unwrap().unwrap();
+    assert_eq!(wallet.name, BOB_NAME);
+    assert_eq!(wallet.balance, 100);
+    assert_eq!(wallet.sponsor, Some(alice));
+    let sponsor = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(sponsor.balance, 0);
+    assert_eq!(sponsor.sponsor, None);
+    // The initial balance is moved from the sponsor rather than issued.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.total_supply(), 100);
+    // Both histories record the transaction.
+    assert_eq!(sponsor.history_len, 2);
+    assert_eq!(sponsor.last_tx_hash, tx.hash());
+    assert_eq!(wallet.history_len, 1);
+    assert_eq!(wallet.last_tx_hash, tx.hash());
+
+    let (carol, _) = crypto::gen_keypair();
+    let (dave, key_dave) = crypto::gen_keypair();
+    let existing = CreateWalletFor::sign(&alice, &bob, "Bob again", 1, &key_alice);
+    let insufficient = CreateWalletFor::sign(&alice, &carol, "Carol", 2, &key_alice);
+    let unknown = CreateWalletFor::sign(&dave, &carol, "Carol", 3, &key_dave);
+    testkit.create_block_with_transactions(vec![
+        existing.clone(),
+        insufficient.clone(),
+        unknown.clone(),
+    ]);
+    assert_tx_status(
+        &api,
+        existing.hash(),
+        &json!({ "type": "error", "code": 0, "description": "Wallet already exists" }),
+    );
+    assert_tx_status(
+        &api,
+        insufficient.hash(),
+        &json!({ "type": "error", "code": 3, "description": "Insufficient currency amount" }),
+    );
+    assert_tx_status(
+        &api,
+        unknown.hash(),
+        &json!({ "type": "error", "code": 1, "description": "Sender doesn't exist" }),
+    );
+    assert!(api.get_wallet(carol).unwrap().is_none());
+}
+
+#[test]
+fn test_payment_stream() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let open = OpenStream::sign(&alice, &bob, 3, 10, 0, &key_alice);
+    testkit.create_block_with_transaction(open.clone());
+    assert_tx_status(&api, open.hash(), &json!({ "type": "success" }));
+    let opened_at = testkit.height();
+    // The maximum total is reserved from the sender.
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 90);
+    let stream = Schema::new(&testkit.snapshot())
+        .payment_stream(&open.hash())
+        .unwrap();
+    assert_eq!(stream.opened_at, opened_at);
+    assert!(!stream.is_closed());
+    assert_eq!(stream.exhausted_at(), Height(opened_at.0 + 4));
+
+    // The receiver is paid for two blocks and the rest is refunded.
+    testkit.create_block();
+    let close = CloseStream::sign(&bob, open.hash(), 0, &key_bob);
+    testkit.create_block_with_transaction(close.clone());
+    assert_tx_status(&api, close.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 94);
+    let bob_wallet = api.get_wallet(bob).unwrap().unwrap();
+    assert_eq!(bob_wallet.balance, 106);
+    assert_eq!(bob_wallet.last_tx_hash, close.hash());
+    let stream = Schema::new(&testkit.snapshot())
+        .payment_stream(&open.hash())
+        .unwrap();
+    assert_eq!(stream.closed_at, Height(opened_at.0 + 2));
+    assert_eq!(stream.paid, 6);
+
+    let again = CloseStream::sign(&alice, open.hash(), 1, &key_alice);
+    testkit.create_block_with_transaction(again.clone());
+    assert_tx_status(
+        &api,
+        again.hash(),
+        &json!({ "type": "error", "code": 26, "description": "Payment stream is already closed" }),
+    );
+
+    // The stream which has accrued the maximum total is closed by the service.
+    let open = OpenStream::sign(&alice, &bob, 3, 10, 1, &key_alice);
+    testkit.create_block_with_transaction(open.clone());
+    let opened_at = testkit.height();
+    testkit.create_blocks_until(Height(opened_at.0 + 3));
+    assert!(!Schema::new(&testkit.snapshot())
+        .payment_stream(&open.hash())
+        .unwrap()
+        .is_closed());
+    testkit.create_block();
+    let stream = Schema::new(&testkit.snapshot())
+        .payment_stream(&open.hash())
+        .unwrap();
+    assert_eq!(stream.closed_at, Height(opened_at.0 + 4));
+    assert_eq!(stream.paid, 10);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 84);
+    let bob_wallet = api.get_wallet(bob).unwrap().unwrap();
+    assert_eq!(bob_wallet.balance, 116);
+    // The closing is recorded under the hash of the opening transaction.
+    assert_eq!(bob_wallet.last_tx_hash, open.hash());
+}
+
+#[test]
+fn test_payment_stream_errors() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    let (tx_carol, key_carol) = api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+    let (alice, bob, carol) = (tx_alice.author(), tx_bob.author(), tx_carol.author());
+    let (dave, _) = crypto::gen_keypair();
+
+    let error_code = |testkit: &mut TestKit, tx: messages::Signed<messages::RawTransaction>| {
+        testkit.create_block_with_transaction(tx.clone());
+        match api.transaction_status(tx.hash()).unwrap().unwrap() {
+            TxStatus::Error { code, .. } => Some(code),
+            status => {
+                assert_eq!(status, TxStatus::Success);
+                None
+            }
+        }
+    };
+
+    let tx = OpenStream::sign(&alice, &alice, 1, 10, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(4));
+    let tx = OpenStream::sign(&alice, &bob, 0, 10, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(28));
+    let tx = OpenStream::sign(&alice, &dave, 1, 10, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(2));
+    let tx = OpenStream::sign(&alice, &bob, 1, 101, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(3));
+
+    let open = OpenStream::sign(&alice, &bob, 1, 10, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, open.clone()), None);
+    let tx = CloseStream::sign(&carol, open.hash(), 0, &key_carol);
+    assert_eq!(error_code(&mut testkit, tx), Some(27));
+    let tx = CloseStream::sign(&alice, Hash::zero(), 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(7));
+    let tx = CloseStream::sign(&alice, open.hash(), 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), None);
+}
+
+#[test]
+fn test_clearing() {
+    let (operator, operator_key) = crypto::gen_keypair();
+    let (mut testkit, api) = create_testkit_with(Config {
+        clearing_operator: Some(operator),
+        max_clearing_legs: 4,
+        ..Config::default()
+    });
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    let (tx_carol, _) = api.create_wallet("Carol").unwrap();
+    testkit.create_block();
+    let (alice, bob, carol) = (tx_alice.author(), tx_bob.author(), tx_carol.author());
+
+    // Bob spends more than his balance, which is covered by what he receives.
+    let legs = vec![
+        ClearingLeg::new(bob, carol, 150),
+        ClearingLeg::new(alice, bob, 80),
+        ClearingLeg::new(carol, alice, 30),
+    ];
+    let clearing = Clearing::sign(&operator, legs, 0, &operator_key);
+    testkit.create_block_with_transaction(clearing.clone());
+    assert_tx_status(&api, clearing.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 50);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 30);
+    assert_eq!(api.get_wallet(carol).unwrap().unwrap().balance, 220);
+
+    // Every wallet records the clearing once, although it takes part in two legs.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    for key in &[alice, bob, carol] {
+        let wallet = schema.wallet(key).unwrap();
+        assert_eq!(wallet.history_len, 2);
+        assert_eq!(wallet.last_tx_hash, clearing.hash());
+    }
+    let mut affected = schema
+        .affected_wallets(&clearing.hash())
+        .iter()
+        .collect::<Vec<_>>();
+    affected.sort();
+    let mut expected = vec![alice, bob, carol];
+    expected.sort();
+    assert_eq!(affected, expected);
+    assert_eq!(schema.transfer_count(), 3);
+}
+
+#[test]
+fn test_clearing_errors() {
+    let (operator, operator_key) = crypto::gen_keypair();
+    let (mut testkit, api) = create_testkit_with(Config {
+        clearing_operator: Some(operator),
+        max_clearing_legs: 4,
+        ..Config::default()
+    });
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, _) = crypto::gen_keypair();
+
+    let check = |testkit: &mut TestKit, tx: messages::Signed<messages::RawTransaction>, status| {
+        testkit.create_block_with_transaction(tx.clone());
+        assert_tx_status(&api, tx.hash(), &status);
+    };
+
+    let legs = vec![ClearingLeg::new(alice, bob, 10)];
+    let tx = Clearing::sign(&alice, legs.clone(), 0, &key_alice);
+    check(
+        &mut testkit,
+        tx,
+        json!({ "type": "error", "code": 24, "description": "Author is not the clearing operator" }),
+    );
+    let tx = Clearing::sign(&operator, vec![legs[0].clone(); 5], 0, &operator_key);
+    check(
+        &mut testkit,
+        tx,
+        json!({ "type": "error", "code": 25, "description": "Clearing has too many legs" }),
+    );
+
+    // The first offending leg is identified by its index.
+    let tx = Clearing::sign(
+        &operator,
+        vec![legs[0].clone(), ClearingLeg::new(bob, bob, 1)],
+        0,
+        &operator_key,
+    );
+    check(
+        &mut testkit,
+        tx,
+        json!({ "type": "error", "code": 4, "description": "Leg 1: Sender same as receiver" }),
+    );
+    let tx = Clearing::sign(
+        &operator,
+        vec![legs[0].clone(), ClearingLeg::new(carol, bob, 1)],
+        0,
+        &operator_key,
+    );
+    check(
+        &mut testkit,
+        tx,
+        json!({ "type": "error", "code": 1, "description": "Leg 1: Sender doesn't exist" }),
+    );
+    let tx = Clearing::sign(
+        &operator,
+        vec![ClearingLeg::new(alice, carol, 1)],
+        0,
+        &operator_key,
+    );
+    check(
+        &mut testkit,
+        tx,
+        json!({ "type": "error", "code": 2, "description": "Leg 0: Receiver doesn't exist" }),
+    );
+    let tx = Clearing::sign(
+        &operator,
+        vec![ClearingLeg::new(
+            alice,
+            bob,
+            "0.5".parse::<Amount>().unwrap(),
+        )],
+        0,
+        &operator_key,
+    );
+    check(
+        &mut testkit,
+        tx,
+        json!({ "type": "error", "code": 19, "description": "Leg 0: Amount is too precise" }),
+    );
+
+    // Balances are checked after netting, and the first leg debiting an overdrawn
+    // wallet is reported.
+    let tx = Clearing::sign(
+        &operator,
+        vec![
+            ClearingLeg::new(alice, bob, 60),
+            ClearingLeg::new(bob, alice, 20),
+            ClearingLeg::new(alice, bob, 70),
+        ],
+        0,
+        &operator_key,
+    );
+    check(
+        &mut testkit,
+        tx,
+        json!({ "type": "error", "code": 3, "description": "Leg 0: Insufficient currency amount" }),
+    );
+
+    // Nothing is applied by the failed clearings.
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 100);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 100);
+}
+
+#[test]
+fn test_staking_arithmetic() {
+    let amount = |s: &str| s.parse::<Amount>().unwrap();
+
+    // Fractions of amounts are rounded down.
+    assert_eq!(
+        Amount::from(200).checked_mul_fraction(amount("0.05")),
+        Some(Amount::from(10))
+    );
+    assert_eq!(
+        amount("0.5").checked_mul_fraction(Amount::from(3)),
+        Some(amount("1.5"))
+    );
+    assert_eq!(
+        Amount::from(2).checked_mul_fraction(Amount::from(1_000)),
+        Some(Amount::from(2_000))
+    );
+    assert_eq!(
+        Amount::from_raw(3).checked_mul_fraction(amount("0.5")),
+        Some(Amount::from_raw(1))
+    );
+    assert_eq!(
+        Amount::from(3).checked_mul_fraction(Amount::ZERO),
+        Some(Amount::ZERO)
+    );
+    assert_eq!(
+        Amount::MAX.checked_mul_fraction(Amount::from(1)),
+        Some(Amount::MAX)
+    );
+    assert_eq!(
+        Amount::MAX.checked_mul_fraction(amount("1.000000000000000001")),
+        None
+    );
+
+    // So are ratios, which are computed without overflowing for any divisor.
+    assert_eq!(
+        Amount::from(1).checked_ratio(Amount::from(4)),
+        Some(amount("0.25"))
+    );
+    assert_eq!(
+        Amount::from(10).checked_ratio(Amount::from(4)),
+        Some(amount("2.5"))
+    );
+    assert_eq!(
+        Amount::from(1).checked_ratio(Amount::from(3)),
+        Some(amount("0.333333333333333333"))
+    );
+    assert_eq!(
+        Amount::MAX.checked_ratio(Amount::MAX),
+        Some(Amount::from(1))
+    );
+    assert_eq!(
+        Amount::MAX
+            .saturating_sub(Amount::from_raw(1))
+            .checked_ratio(Amount::MAX),
+        Some(amount("0.999999999999999999"))
+    );
+    assert_eq!(Amount::from(1).checked_ratio(Amount::ZERO), None);
+    assert_eq!(Amount::MAX.checked_ratio(Amount::from_raw(1)), None);
+    for &(numerator, denominator) in &[("2", "3"), ("5", "0.7"), ("0.000000000000000001", "9")] {
+        let (numerator, denominator) = (amount(numerator), amount(denominator));
+        let ratio = numerator.checked_ratio(denominator).unwrap();
+        assert!(denominator.checked_mul_fraction(ratio).unwrap() <= numerator);
+    }
+
+    // Rewards are accrued for every staked unit.
+    let rate = amount("0.01");
+    let mut state = StakingState {
+        total_staked: Amount::from(300),
+        ..StakingState::default()
+    };
+    assert_eq!(
+        state.accrue(rate, Amount::from(1_000), Amount::MAX),
+        Amount::from(3)
+    );
+    assert_eq!(state.reward_per_token, rate);
+    assert_eq!(state.outstanding_rewards, Amount::from(3));
+    // The rate is lowered to distribute the 2 units left under the cap, since the outstanding
+    // rewards count against it as well.
+    assert_eq!(
+        state.accrue(rate, Amount::from(1_000), Amount::from(1_005)),
+        amount("1.9999999999999998")
+    );
+    assert_eq!(state.reward_per_token, amount("0.016666666666666666"));
+    assert_eq!(state.outstanding_rewards, amount("4.9999999999999998"));
+    // The dust left under the cap is not enough for any rate.
+    let before = state.clone();
+    assert_eq!(
+        state.accrue(rate, Amount::from(1_000), Amount::from(1_005)),
+        Amount::ZERO
+    );
+    assert_eq!(state, before);
+    // Nothing is accrued without stakes.
+    let mut empty = StakingState::default();
+    assert_eq!(empty.accrue(rate, Amount::ZERO, Amount::MAX), Amount::ZERO);
+    assert_eq!(empty, StakingState::default());
+
+    // A position earns only the rewards accrued after it is opened.
+    let position = StakePosition::new(amount("0.01"));
+    assert_eq!(
+        position.earned(Amount::from(100), amount("0.01")),
+        Amount::ZERO
+    );
+    assert_eq!(
+        position.earned(Amount::from(100), amount("0.035")),
+        amount("2.5")
+    );
+    let position = position.settle(Amount::from(100), amount("0.035"));
+    assert_eq!(position.pending, amount("2.5"));
+    assert_eq!(position.reward_per_token_paid, amount("0.035"));
+    // Settling twice at the same accumulator adds nothing.
+    assert_eq!(
+        position.clone().settle(Amount::from(100), amount("0.035")),
+        position
+    );
+    assert_eq!(
+        position.earned(Amount::from_raw(1), amount("0.535")),
+        Amount::ZERO
+    );
+
+    // Positions settled at different accumulators earn no more than was accrued.
+    let mut state = StakingState {
+        total_staked: Amount::from(3),
+        ..StakingState::default()
+    };
+    let first = StakePosition::new(state.reward_per_token);
+    state.accrue(amount("0.333333333333333333"), Amount::ZERO, Amount::MAX);
+    let second = StakePosition::new(state.reward_per_token);
+    state.accrue(amount("0.333333333333333333"), Amount::ZERO, Amount::MAX);
+    let earned = first
+        .earned(Amount::from(2), state.reward_per_token)
+        .checked_add(second.earned(Amount::from(1), state.reward_per_token))
+        .unwrap();
+    assert!(earned <= state.outstanding_rewards);
+}
+
+#[test]
+fn test_staking() {
+    let (mut testkit, api) = create_testkit_with(Config {
+        staking_reward_interval: 2,
+        staking_reward_rate: "0.1".parse().unwrap(),
+        ..Config::default()
+    });
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let rewards =
+        |testkit: &TestKit, key: &PublicKey| Schema::new(&testkit.snapshot()).staking_rewards(key);
+
+    // The rewards are accrued in the blocks at even heights for the stakes at their end.
+    let stake = Stake::sign(&alice, 50, 0, &key_alice);
+    testkit.create_block_with_transaction(stake.clone());
+    assert_eq!(testkit.height(), Height(2));
+    assert_tx_status(&api, stake.hash(), &json!({ "type": "success" }));
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(
+        (wallet.balance, wallet.staked),
+        (Amount::from(50), Amount::from(50))
+    );
+    assert_eq!(rewards(&testkit, &alice), 5);
+
+    testkit.create_block_with_transaction(Stake::sign(&bob, 100, 0, &key_bob));
+    assert_eq!(rewards(&testkit, &bob), 0);
+    testkit.create_block();
+    assert_eq!(rewards(&testkit, &alice), 10);
+    assert_eq!(rewards(&testkit, &bob), 10);
+    let snapshot = testkit.snapshot();
+    let state = Schema::new(&snapshot).staking_state();
+    assert_eq!(state.total_staked, 150);
+    assert_eq!(state.reward_per_token, "0.2".parse::<Amount>().unwrap());
+    assert_eq!(state.outstanding_rewards, 20);
+    // The rewards are not a part of the total supply until claimed.
+    assert_eq!(Schema::new(&snapshot).total_supply(), 200);
+
+    // Unstaking settles the rewards, which are then claimed.
+    let unstake = Unstake::sign(&alice, 20, 0, &key_alice);
+    let claim = ClaimRewards::sign(&alice, 0, &key_alice);
+    testkit.create_block_with_transactions(vec![unstake.clone(), claim.clone()]);
+    assert_tx_status(&api, unstake.hash(), &json!({ "type": "success" }));
+    assert_tx_status(&api, claim.hash(), &json!({ "type": "success" }));
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(
+        (wallet.balance, wallet.staked),
+        (Amount::from(80), Amount::from(30))
+    );
+    assert_eq!(wallet.last_tx_hash, claim.hash());
+    assert_eq!(rewards(&testkit, &alice), 0);
+    assert_eq!(Schema::new(&testkit.snapshot()).total_supply(), 210);
+
+    // The lowered stake earns less.
+    testkit.create_block();
+    assert_eq!(rewards(&testkit, &alice), 3);
+    assert_eq!(rewards(&testkit, &bob), 20);
+
+    let claim = ClaimRewards::sign(&bob, 0, &key_bob);
+    testkit.create_block_with_transaction(claim.clone());
+    let wallet = api.get_wallet(bob).unwrap().unwrap();
+    assert_eq!(
+        (wallet.balance, wallet.staked),
+        (Amount::from(20), Amount::from(100))
+    );
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.total_supply(), 230);
+    assert_eq!(schema.staking_state().outstanding_rewards, 3);
+    assert_eq!(schema.wallet_stats(&bob).unwrap().received_total, 20);
+
+    // The wallet without a stake and unclaimed rewards leaves the index.
+    testkit.create_block_with_transaction(Unstake::sign(&bob, 100, 1, &key_bob));
+    let wallet = api.get_wallet(bob).unwrap().unwrap();
+    assert_eq!(
+        (wallet.balance, wallet.staked),
+        (Amount::from(120), Amount::ZERO)
+    );
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.stake_position(&bob).is_none());
+    assert!(schema.stake_position(&alice).is_some());
+    assert_eq!(schema.staking_state().total_staked, 30);
+    assert_balance_index(&testkit);
+}
+
+#[test]
+fn test_staking_supply_cap() {
+    let (mut testkit, api) = create_testkit_with(Config {
+        staking_reward_interval: 2,
+        staking_reward_rate: "0.1".parse().unwrap(),
+        staking_supply_cap: Some(Amount::from(205)),
+        ..Config::default()
+    });
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+
+    // Only 5 of the 10 units of the rewards fit under the cap.
+    testkit.create_block_with_transaction(Stake::sign(&alice, 100, 0, &key_alice));
+    testkit.create_blocks_until(Height(6));
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.staking_rewards(&alice), 5);
+    assert_eq!(schema.staking_state().outstanding_rewards, 5);
+
+    let claim = ClaimRewards::sign(&alice, 0, &key_alice);
+    testkit.create_block_with_transaction(claim.clone());
+    assert_tx_status(&api, claim.hash(), &json!({ "type": "success" }));
+    assert_eq!(Schema::new(&testkit.snapshot()).total_supply(), 205);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 5);
+
+    testkit.create_blocks_until(Height(10));
+    let claim = ClaimRewards::sign(&alice, 1, &key_alice);
+    testkit.create_block_with_transaction(claim.clone());
+    assert_tx_status(
+        &api,
+        claim.hash(),
+        &json!({ "type": "error", "code": 30, "description": "No staking rewards to claim" }),
+    );
+    assert_eq!(Schema::new(&testkit.snapshot()).total_supply(), 205);
+}
+
+#[test]
+fn test_staking_errors() {
+    let (mut testkit, api) = create_testkit_with(Config {
+        staking_reward_interval: 2,
+        staking_reward_rate: "0.1".parse().unwrap(),
+        ..Config::default()
+    });
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    let (dave, key_dave) = crypto::gen_keypair();
+
+    let error_code = |testkit: &mut TestKit, tx: messages::Signed<messages::RawTransaction>| {
+        testkit.create_block_with_transaction(tx.clone());
+        match api.transaction_status(tx.hash()).unwrap().unwrap() {
+            TxStatus::Error { code, .. } => Some(code),
+            status => {
+                assert_eq!(status, TxStatus::Success);
+                None
+            }
+        }
+    };
+
+    let tx = Stake::sign(&dave, 1, 0, &key_dave);
+    assert_eq!(error_code(&mut testkit, tx), Some(1));
+    let tx = Unstake::sign(&dave, 1, 0, &key_dave);
+    assert_eq!(error_code(&mut testkit, tx), Some(1));
+    let tx = ClaimRewards::sign(&dave, 0, &key_dave);
+    assert_eq!(error_code(&mut testkit, tx), Some(1));
+    let tx = Stake::sign(&alice, 101, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(3));
+    let tx = Stake::sign(&alice, "0.5".parse::<Amount>().unwrap(), 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(19));
+    let tx = ClaimRewards::sign(&alice, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(30));
+
+    let tx = Stake::sign(&alice, 10, 1, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    let tx = Unstake::sign(&alice, 11, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(29));
+    let tx = Unstake::sign(&alice, 10, 1, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    // The rewards earned before unstaking stay claimable.
+    let tx = ClaimRewards::sign(&alice, 1, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 101);
+}
+
+#[test]
+fn test_approval_batch() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, key_carol) = crypto::gen_keypair();
+    let (dave, key_dave) = crypto::gen_keypair();
+    let (erin, key_erin) = crypto::gen_keypair();
+
+    let transfer = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol, dave].iter().cloned().collect(),
+        10,
+        0,
+    );
+    testkit.create_block_with_transaction(transfer.clone());
+
+    let approval = SignedApproval::new(&transfer.hash(), carol, &key_carol);
+    assert!(approval.verify(&transfer.hash()));
+    assert!(!approval.verify(&tx_alice.hash()));
+    assert_ne!(
+        approval_digest(&transfer.hash(), &carol),
+        approval_digest(&transfer.hash(), &dave)
+    );
+    let forged = SignedApproval {
+        approver: dave,
+        ..SignedApproval::new(&transfer.hash(), erin, &key_erin)
+    };
+    assert!(!forged.verify(&transfer.hash()));
+
+    // The batch is submitted by a key which is neither a party nor an approver.
+    let (submitter, key_submitter) = crypto::gen_keypair();
+    let batch = ApproveTransferMultisigBatch::sign(
+        &submitter,
+        transfer.hash(),
+        vec![
+            forged,
+            approval.clone(),
+            approval,
+            SignedApproval::new(&transfer.hash(), dave, &key_dave),
+            SignedApproval::new(&transfer.hash(), erin, &key_erin),
+        ],
+        &key_submitter,
+    );
+    testkit.create_block_with_transaction(batch.clone());
+    assert_tx_status(&api, batch.hash(), &json!({ "type": "success" }));
+
+    let outcomes: Vec<_> = api
+        .approval_batch_results(batch.hash())
+        .unwrap()
+        .into_iter()
+        .map(|result| (result.approver, result.outcome))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            (dave, ApprovalOutcome::InvalidSignature),
+            (carol, ApprovalOutcome::Applied),
+            (carol, ApprovalOutcome::Duplicate),
+            (dave, ApprovalOutcome::Applied),
+            (erin, ApprovalOutcome::TransferDone),
+        ]
+    );
+    assert_eq!(
+        serde_json::to_value(BatchApprovalResult {
+            approver: erin,
+            outcome: ApprovalOutcome::TransferDone,
+        })
+        .unwrap()["outcome"],
+        json!("transfer_done")
+    );
+
+    // The transfer is settled once, by the batch.
+    let stored = {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot)
+            .multisig_transfer(transfer.hash())
+            .unwrap()
+    };
+    assert_eq!(stored.state, State::Done);
+    assert_eq!(stored.approved_by.len(), 2);
+    assert_eq!(api.affected_wallets(batch.hash()).unwrap(), vec![bob]);
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 90);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 110);
+
+    let late = ApproveTransferMultisig::sign(dave, &key_dave, transfer.hash());
+    testkit.create_block_with_transaction(late.clone());
+    assert_tx_status(
+        &api,
+        late.hash(),
+        &json!({ "type": "error", "code": 14, "description": "Transfer is done" }),
+    );
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 110);
+    assert!(api.approval_batch_results(late.hash()).unwrap().is_empty());
+}
+
+#[test]
+fn test_approval_batch_errors() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, key_carol) = crypto::gen_keypair();
+    let (dave, key_dave) = crypto::gen_keypair();
+
+    let transfer = TransferMultisig::sign(
+        alice,
+        &key_alice,
+        bob,
+        [carol, dave].iter().cloned().collect(),
+        10,
+        0,
+    );
+    testkit.create_block_with_transaction(transfer.clone());
+
+    let check = |testkit: &mut TestKit, approvals: Vec<SignedApproval>, status| {
+        let tx = ApproveTransferMultisigBatch::sign(&alice, transfer.hash(), approvals, &key_alice);
+        testkit.create_block_with_transaction(tx.clone());
+        assert_tx_status(&api, tx.hash(), &status);
+        tx
+    };
+
+    check(
+        &mut testkit,
+        vec![],
+        json!({ "type": "error", "code": 32, "description": "Approval batch is empty" }),
+    );
+    check(
+        &mut testkit,
+        vec![SignedApproval::new(&transfer.hash(), carol, &key_carol); MAX_APPROVERS + 1],
+        json!({ "type": "error", "code": 6, "description": "Approvers list is too large" }),
+    );
+    // The approvals signed for another transaction are invalid.
+    check(
+        &mut testkit,
+        vec![SignedApproval::new(&tx_alice.hash(), carol, &key_carol)],
+        json!({
+            "type": "error",
+            "code": 31,
+            "description": "Approval 0: Invalid approval signature",
+        }),
+    );
+    check(
+        &mut testkit,
+        vec![SignedApproval::new(&transfer.hash(), alice, &key_alice)],
+        json!({
+            "type": "error",
+            "code": 10,
+            "description": "Approval 0: Approver is not on approvers list",
+        }),
+    );
+
+    // A partial batch leaves the transfer in process.
+    let partial = check(
+        &mut testkit,
+        vec![SignedApproval::new(&transfer.hash(), carol, &key_carol)],
+        json!({ "type": "success" }),
+    );
+    assert_eq!(
+        api.approval_batch_results(partial.hash()).unwrap(),
+        vec![BatchApprovalResult {
+            approver: carol,
+            outcome: ApprovalOutcome::Applied,
+        }]
+    );
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 100);
+    check(
+        &mut testkit,
+        vec![
+            SignedApproval::new(&transfer.hash(), carol, &key_carol),
+            SignedApproval::new(&transfer.hash(), carol, &key_carol),
+        ],
+        json!({
+            "type": "error",
+            "code": 15,
+            "description": "Approval 0: Transfer is already approved by the approver",
+        }),
+    );
+
+    check(
+        &mut testkit,
+        vec![SignedApproval::new(&transfer.hash(), dave, &key_dave)],
+        json!({ "type": "success" }),
+    );
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 110);
+    check(
+        &mut testkit,
+        vec![
+            SignedApproval::new(&transfer.hash(), dave, &key_dave),
+            SignedApproval::new(&transfer.hash(), carol, &key_carol),
+        ],
+        json!({ "type": "error", "code": 14, "description": "Transfer is done" }),
+    );
+
+    let unknown = ApproveTransferMultisigBatch::sign(
+        &alice,
+        crypto::hash(b"unknown"),
+        vec![SignedApproval::new(
+            &crypto::hash(b"unknown"),
+            carol,
+            &key_carol,
+        )],
+        &key_alice,
+    );
+    testkit.create_block_with_transaction(unknown.clone());
+    assert_tx_status(
+        &api,
+        unknown.hash(),
+        &json!({ "type": "error", "code": 7, "description": "Transaction does not exist" }),
+    );
+}
+
+#[test]
+fn test_overdraft() {
+    let (admin, admin_key) = crypto::gen_keypair();
+    let (mut testkit, api) = create_testkit_with(Config {
+        overdraft_admin: Some(admin),
+        ..Config::default()
+    });
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let tx = SetOverdraft::sign(&admin, &alice, 50, 0, &admin_key);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet.overdraft_limit, 50);
+    assert_eq!(wallet.owed, 0);
+    // Setting the limit does not change the balance, so it is not recorded in the history.
+    assert_eq!(wallet.history_len, 1);
+    assert_eq!(wallet.last_tx_hash, tx.hash());
+    assert_eq!(wallet.last_active_at, Height(2));
+
+    // Alice spends 30 more than her balance.
+    let tx = Transfer::sign(&alice, &bob, 130, 0, &key_alice);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(&api, tx.hash(), &json!({ "type": "success" }));
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(
+        (wallet.balance, wallet.owed),
+        (Amount::ZERO, Amount::from(30))
+    );
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 230);
+    let value = serde_json::to_value(&wallet).unwrap();
+    assert_eq!(value["owed"], json!("30"));
+    assert_eq!(value["overdraft_limit"], json!("50"));
+    // The total supply is the sum of the balances less the owed amounts.
+    assert_eq!(api.stats().unwrap().total_supply, 200);
+
+    // The spending stops at the limit.
+    let tx = Transfer::sign(&alice, &bob, 21, 1, &key_alice);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 3, "description": "Insufficient currency amount" }),
+    );
+
+    // The incoming funds repay the owed amount first.
+    let tx = Transfer::sign(&bob, &alice, 20, 0, &key_bob);
+    testkit.create_block_with_transaction(tx.clone());
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(
+        (wallet.balance, wallet.owed),
+        (Amount::ZERO, Amount::from(10))
+    );
+    let tx = Transfer::sign(&bob, &alice, 25, 1, &key_bob);
+    testkit.create_block_with_transaction(tx.clone());
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(
+        (wallet.balance, wallet.owed),
+        (Amount::from(15), Amount::ZERO)
+    );
+    assert!(serde_json::to_value(&wallet).unwrap().get("owed").is_none());
+    assert_balance_index(&testkit);
+}
+
+#[test]
+fn test_overdraft_errors() {
+    let (admin, admin_key) = crypto::gen_keypair();
+    let (mut testkit, api) = create_testkit_with(Config {
+        overdraft_admin: Some(admin),
+        ..Config::default()
+    });
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (carol, _) = crypto::gen_keypair();
+
+    let error_code = |testkit: &mut TestKit, tx: messages::Signed<messages::RawTransaction>| {
+        testkit.create_block_with_transaction(tx.clone());
+        match api.transaction_status(tx.hash()).unwrap().unwrap() {
+            TxStatus::Error { code, .. } => Some(code),
+            status => {
+                assert_eq!(status, TxStatus::Success);
+                None
+            }
+        }
+    };
+
+    // Wallet owners cannot grant overdrafts to themselves.
+    let tx = SetOverdraft::sign(&alice, &alice, 50, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(33));
+    let tx = SetOverdraft::sign(&admin, &carol, 50, 0, &admin_key);
+    assert_eq!(error_code(&mut testkit, tx), Some(2));
+    let tx = SetOverdraft::sign(
+        &admin,
+        &alice,
+        "0.5".parse::<Amount>().unwrap(),
+        0,
+        &admin_key,
+    );
+    assert_eq!(error_code(&mut testkit, tx), Some(19));
+
+    let tx = SetOverdraft::sign(&admin, &alice, 50, 1, &admin_key);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    let tx = Transfer::sign(&alice, &bob, 140, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    // The overdraft cannot be staked.
+    let tx = Stake::sign(&alice, 1, 0, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(3));
+
+    // The limit cannot be reduced below the owed amount, but it can be reduced down to it.
+    let tx = SetOverdraft::sign(&admin, &alice, 39, 2, &admin_key);
+    assert_eq!(error_code(&mut testkit, tx), Some(34));
+    let tx = SetOverdraft::sign(&admin, &alice, 40, 3, &admin_key);
+    assert_eq!(error_code(&mut testkit, tx), None);
+    let tx = Transfer::sign(&alice, &bob, 1, 1, &key_alice);
+    assert_eq!(error_code(&mut testkit, tx), Some(3));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().owed, 40);
+
+    // Without the admin in the configuration, overdrafts cannot be granted.
+    let (mut testkit, api) = create_testkit();
+    api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let tx = SetOverdraft::sign(&admin, &alice, 50, 0, &admin_key);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 33, "description": "Author is not the overdraft admin" }),
+    );
+}
+
+#[test]
+fn test_cashback() {
+    let amount = |s: &str| s.parse::<Amount>().unwrap();
+    assert_eq!(Amount::basis_points(250), amount("0.025"));
+    assert_eq!(
+        Amount::basis_points(MAX_CASHBACK_BASIS_POINTS),
+        Amount::from(1)
+    );
+    assert_eq!(amount("1.259").round_down(2), amount("1.25"));
+    assert_eq!(amount("1.259").round_down(0), Amount::from(1));
+    assert_eq!(amount("1.259").round_down(18), amount("1.259"));
+
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    // Both wallets return cashback, but the cashback does not earn cashback in turn.
+    let bob_cashback = SetCashback::sign(&bob, 250, 0, &key_bob);
+    let alice_cashback = SetCashback::sign(&alice, MAX_CASHBACK_BASIS_POINTS, 0, &key_alice);
+    testkit.create_block_with_transactions(vec![bob_cashback.clone(), alice_cashback.clone()]);
+    assert_tx_status(&api, bob_cashback.hash(), &json!({ "type": "success" }));
+    let wallet = api.get_wallet(bob).unwrap().unwrap();
+    assert_eq!(wallet.cashback_basis_points, 250);
+    assert_eq!(wallet.history_len, 1);
+    assert_eq!(wallet.last_tx_hash, bob_cashback.hash());
+    assert_eq!(wallet.last_active_at, Height(2));
+    assert_eq!(
+        serde_json::to_value(&wallet).unwrap()["cashback_basis_points"],
+        json!(250)
+    );
+
+    // 2.5% of 50 is 1.25, which is rounded down to a whole amount in Bob's favor.
+    let transfer = Transfer::sign(&alice, &bob, 50, 0, &key_alice);
+    testkit.create_block_with_transaction(transfer.clone());
+    assert_tx_status(&api, transfer.hash(), &json!({ "type": "success" }));
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 51);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 149);
+
+    // Both movements are recorded in both histories.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let record = |incoming, amount: u64| HistoryRecord {
+        tx_hash: transfer.hash(),
+        height: Height(3),
+        incoming,
+        amount: Amount::from(amount),
+    };
+    assert_eq!(
+        schema.wallet_history_from(&alice, 1).collect::<Vec<_>>(),
+        vec![record(false, 50), record(true, 1)]
+    );
+    assert_eq!(
+        schema.wallet_history_from(&bob, 1).collect::<Vec<_>>(),
+        vec![record(true, 50), record(false, 1)]
+    );
+    assert_eq!(schema.transfer_count(), 2);
+    assert_eq!(
+        schema.wallet_activity(&alice).unwrap().received,
+        Amount::from(1)
+    );
+
+    // Cashback rounded down to zero is not returned.
+    let transfer = Transfer::sign(&alice, &bob, 10, 1, &key_alice);
+    testkit.create_block_with_transaction(transfer.clone());
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 41);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().history_len, 4);
+
+    // Alice returns the whole amount.
+    let transfer = Transfer::sign(&bob, &alice, 40, 0, &key_bob);
+    testkit.create_block_with_transaction(transfer.clone());
+    assert_eq!(api.get_wallet(alice).unwrap().unwrap().balance, 41);
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().balance, 159);
+    assert_balance_index(&testkit);
+}
+
+#[test]
+fn test_cashback_errors() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let alice = tx_alice.author();
+    let (dave, key_dave) = crypto::gen_keypair();
+
+    let tx = SetCashback::sign(&alice, MAX_CASHBACK_BASIS_POINTS + 1, 0, &key_alice);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({
+            "type": "error",
+            "code": 35,
+            "description": "Cashback exceeds 10000 basis points",
+        }),
+    );
+    let tx = SetCashback::sign(&dave, 100, 0, &key_dave);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 1, "description": "Sender doesn't exist" }),
+    );
+    assert_eq!(
+        api.get_wallet(alice)
+            .unwrap()
+            .unwrap()
+            .cashback_basis_points,
+        0
+    );
+
+    // Zero disables the cashback.
+    let tx = SetCashback::sign(&alice, 100, 1, &key_alice);
+    testkit.create_block_with_transaction(tx);
+    let tx = SetCashback::sign(&alice, 0, 2, &key_alice);
+    testkit.create_block_with_transaction(tx);
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet.cashback_basis_points, 0);
+    assert!(serde_json::to_value(&wallet)
+        .unwrap()
+        .get("cashback_basis_points")
+        .is_none());
+}
+
+#[test]
+fn test_rename_wallet() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    let rename = RenameWallet::sign(&alice, "Alicia", 0, &key_alice);
+    testkit.create_block_with_transaction(rename.clone());
+    assert_tx_status(&api, rename.hash(), &json!({ "type": "success" }));
+    let wallet = api.get_wallet(alice).unwrap().unwrap();
+    assert_eq!(wallet.name, "Alicia");
+    assert_eq!(wallet.balance, 100);
+    assert_eq!(wallet.history_len, 1);
+    assert_eq!(wallet.last_tx_hash, rename.hash());
+    assert_eq!(wallet.last_active_at, Height(2));
+    // The history head still refers to the latest history entry.
+    assert_eq!(
+        api.wallet_history_head(alice).unwrap().latest_entry_hash,
+        Some(tx_alice.hash())
+    );
+
+    // The previous name is released and may be taken by another wallet.
+    let (tx_carol, _) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let second_rename = RenameWallet::sign(&alice, "Alice 2", 1, &key_alice);
+    testkit.create_block_with_transaction(second_rename.clone());
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(
+            schema.wallet_by_name(ALICE_NAME).unwrap().pub_key,
+            tx_carol.author()
+        );
+        assert_eq!(schema.wallet_by_name("Alice 2").unwrap().pub_key, alice);
+        assert!(schema.wallet_by_name("Alicia").is_none());
+
+        // The name histories are covered by the state hash.
+        let root = schema.name_history(&alice).merkle_root();
+        assert_eq!(schema.name_history_roots().get(&alice), Some(root));
+        assert!(schema.name_history_roots().get(&bob).is_none());
+        assert_eq!(
+            schema.state_hash()[24],
+            schema.name_history_roots().merkle_root()
+        );
+    }
+
+    let history = api.name_history(alice, None, 0, None).unwrap();
+    assert_eq!(history.name, "Alice 2");
+    assert_eq!(history.total_changes, 2);
+    assert_eq!(
+        history.changes,
+        vec![
+            NameChange {
+                height: Height(2),
+                old_name: ALICE_NAME.to_owned(),
+                new_name: "Alicia".to_owned(),
+                tx_hash: rename.hash(),
+            },
+            NameChange {
+                height: Height(4),
+                old_name: "Alicia".to_owned(),
+                new_name: "Alice 2".to_owned(),
+                tx_hash: second_rename.hash(),
+            },
+        ]
+    );
+    let names = (1..=4)
+        .map(|height| api.name_history(alice, Some(height), 0, Some(0)).unwrap())
+        .map(|history| history.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![ALICE_NAME, "Alicia", "Alicia", "Alice 2"]);
+    let page = api.name_history(alice, None, 1, Some(1)).unwrap();
+    assert_eq!(page.total_changes, 2);
+    assert_eq!(page.changes, history.changes[1..].to_vec());
+
+    let history = api.name_history(bob, None, 0, None).unwrap();
+    assert_eq!(history.name, BOB_NAME);
+    assert_eq!(history.total_changes, 0);
+    assert!(history.changes.is_empty());
+
+    // The wallet does not exist before its creation.
+    let err = api.name_history(alice, Some(0), 0, None).unwrap_err();
+    assert_api_error(err, ErrorKind::WalletNotFound);
+    let err = api
+        .name_history(crypto::gen_keypair().0, None, 0, None)
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::WalletNotFound);
+    let err = api
+        .name_history(alice, None, 0, Some(MAX_ITEMS_PER_REQUEST + 1))
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+}
+
+#[test]
+fn test_rename_wallet_shared_name() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let (tx_impostor, key_impostor) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_carol, key_carol) = api.create_wallet(ALICE_NAME).unwrap();
+    testkit.create_block();
+    let (alice, impostor, carol) = (tx_alice.author(), tx_impostor.author(), tx_carol.author());
+
+    // The name passes to the wallets which share it in the order they took it.
+    let rename = RenameWallet::sign(&alice, "Alicia", 0, &key_alice);
+    testkit.create_block_with_transaction(rename.clone());
+    assert_tx_status(&api, rename.hash(), &json!({ "type": "success" }));
+    let owner = |testkit: &TestKit| {
+        let snapshot = testkit.snapshot();
+        Schema::new(&snapshot)
+            .wallet_by_name(ALICE_NAME)
+            .map(|wallet| wallet.pub_key)
+    };
+    assert_eq!(owner(&testkit), Some(impostor));
+    assert_eq!(api.get_wallet(impostor).unwrap().unwrap().name, ALICE_NAME);
+
+    let rename = RenameWallet::sign(&impostor, "Not Alice", 0, &key_impostor);
+    testkit.create_block_with_transaction(rename.clone());
+    assert_tx_status(&api, rename.hash(), &json!({ "type": "success" }));
+    assert_eq!(owner(&testkit), Some(carol));
+
+    // The name is released once no wallet has it, and wallets which gave it up
+    // do not get it back.
+    let rename = RenameWallet::sign(&carol, "Carol", 0, &key_carol);
+    testkit.create_block_with_transaction(rename.clone());
+    assert_tx_status(&api, rename.hash(), &json!({ "type": "success" }));
+    assert_eq!(owner(&testkit), None);
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert!(schema
+            .wallet_name_holders(&wallet_name_key(ALICE_NAME))
+            .is_empty());
+    }
+    let rename = RenameWallet::sign(&alice, ALICE_NAME, 1, &key_alice);
+    testkit.create_block_with_transaction(rename.clone());
+    assert_tx_status(&api, rename.hash(), &json!({ "type": "success" }));
+    assert_eq!(owner(&testkit), Some(alice));
+}
+
+#[test]
+fn test_rename_wallet_errors() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, key_bob) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    let (dave, key_dave) = crypto::gen_keypair();
+
+    let name_taken = json!({
+        "type": "error",
+        "code": 36,
+        "description": "Wallet name is already taken",
+    });
+    // Both the names of other wallets and the current name count as taken.
+    let tx = RenameWallet::sign(&bob, ALICE_NAME, 0, &key_bob);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(&api, tx.hash(), &name_taken);
+    let tx = RenameWallet::sign(&alice, ALICE_NAME, 0, &key_alice);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(&api, tx.hash(), &name_taken);
+
+    let tx = RenameWallet::sign(&dave, "Dave", 0, &key_dave);
+    testkit.create_block_with_transaction(tx.clone());
+    assert_tx_status(
+        &api,
+        tx.hash(),
+        &json!({ "type": "error", "code": 1, "description": "Sender doesn't exist" }),
+    );
+
+    assert_eq!(
+        api.name_history(alice, None, 0, None)
+            .unwrap()
+            .total_changes,
+        0
+    );
+    assert_eq!(
+        api.name_history(bob, None, 0, None).unwrap().total_changes,
+        0
+    );
+    assert_eq!(api.get_wallet(bob).unwrap().unwrap().name, BOB_NAME);
+}
+
+#[test]
+fn test_wallet_id() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, _) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+
+    // Strings which are valid keys are parsed as keys, other non-empty strings as names.
+    assert_eq!(alice.to_hex().parse(), Ok(WalletId::Key(alice)));
+    assert_eq!(
+        base64::encode(alice.as_ref()).parse(),
+        Ok(WalletId::Key(alice))
+    );
+    assert_eq!(
+        ALICE_NAME.parse(),
+        Ok(WalletId::Name(ALICE_NAME.to_owned()))
+    );
+    assert_eq!(
+        hex::encode(&alice.as_ref()[..31]).parse(),
+        Ok(WalletId::Name(hex::encode(&alice.as_ref()[..31])))
+    );
+    assert_eq!("".parse::<WalletId>(), Err(EmptyWalletId));
+    assert_eq!(WalletId::Key(alice).to_string(), alice.to_hex());
+
+    // Wallets may be queried by name or key, using either parameter.
+    let expected = api.wallet_history_head(alice).unwrap();
+    let by_name = api
+        .wallet_history_head(WalletId::Name(ALICE_NAME.to_owned()))
+        .unwrap();
+    assert_eq!(by_name, expected);
+    let service_api = api.transport().public(ApiKind::Service("cryptocurrency"));
+    for query in &[
+        json!({ "id": ALICE_NAME }),
+        json!({ "id": alice.to_hex() }),
+        json!({ "pub_key": ALICE_NAME }),
+        json!({ "pub_key": alice.to_hex() }),
+    ] {
+        let head: WalletHistoryHead = service_api
+            .query(query)
+            .get("v1/wallets/history/head")
+            .unwrap();
+        assert_eq!(head, expected);
+    }
+    let balance = api
+        .balance_at(WalletId::Name(BOB_NAME.to_owned()), 1)
+        .unwrap();
+    assert_eq!(balance.pub_key, bob);
+    assert_eq!(balance.balance, 100);
+
+    // A wallet named after the key of another wallet is only reachable by its key.
+    let (tx_impostor, _) = api.create_wallet(&bob.to_hex()).unwrap();
+    testkit.create_block();
+    let impostor = tx_impostor.author();
+    {
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(
+            schema.wallet_by_name(&bob.to_hex()).unwrap().pub_key,
+            impostor
+        );
+        let id: WalletId = bob.to_hex().parse().unwrap();
+        assert_eq!(schema.resolve(&id), Some(bob));
+        assert_eq!(
+            schema.resolve(&WalletId::Name(ALICE_NAME.to_owned())),
+            Some(alice)
+        );
+        assert_eq!(schema.resolve(&WalletId::Name("Carol".to_owned())), None);
+    }
+    let head: WalletHistoryHead = service_api
+        .query(&json!({ "id": bob.to_hex() }))
+        .get("v1/wallets/history/head")
+        .unwrap();
+    assert_eq!(head, api.wallet_history_head(bob).unwrap());
+    assert_ne!(head, api.wallet_history_head(impostor).unwrap());
+
+    // Unknown names are reported as missing wallets.
+    let err = api
+        .wallet_history_head(WalletId::Name("Carol".to_owned()))
+        .unwrap_err();
+    let error = assert_api_error(err, ErrorKind::WalletNotFound);
+    assert!(error.message.contains("Carol"));
+    let err = service_api
+        .query(&json!({ "id": "" }))
+        .get::<WalletHistoryHead>("v1/wallets/history/head")
+        .unwrap_err();
+    assert_api_error(err, ErrorKind::InvalidQuery);
+}
+
+#[test]
+fn test_verified_wallet_info() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(4)
+        .with_service(Service::default())
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME).unwrap();
+    let (tx_bob, _) = api.create_wallet(BOB_NAME).unwrap();
+    testkit.create_block();
+    let (alice, bob) = (tx_alice.author(), tx_bob.author());
+    testkit.create_block_with_transaction(Transfer::sign(&alice, &bob, 10, 0, &key_alice));
+    let query = |pub_key: PublicKey, verify| WalletInfoQuery {
+        id: pub_key.into(),
+        with_history: true,
+        history_offset: None,
+        history_limit: None,
+        verify,
+    };
+
+    assert!(api
+        .wallet_info_with(query(alice, false))
+        .unwrap()
+        .verification
+        .is_none());
+    let latest_block = blockchain::Schema::new(&testkit.snapshot())
+        .last_block()
+        .hash();
+    let info = api.wallet_info_with(query(alice, true)).unwrap();
+    assert_eq!(info.wallet_history.as_ref().unwrap().transactions.len(), 2);
+    assert_eq!(
+        info.verification,
+        Some(ProofVerification {
+            ok: true,
+            checked_against_block: latest_block,
+            validators_signed: 4,
+            error: None,
+        })
+    );
+    let (unknown, _) = crypto::gen_keypair();
+    let verification = api
+        .wallet_info_with(query(unknown, true))
+        .unwrap()
+        .verification
+        .unwrap();
+    assert!(verification.ok);
+
+    // Block proofs with too few or foreign precommits are rejected.
+    let validator_keys = blockchain::Schema::new(&testkit.snapshot())
+        .actual_configuration()
+        .validator_keys
+        .iter()
+        .map(|keys| keys.consensus_key)
+        .collect::<Vec<_>>();
+    let mut block_proof = info.block_proof;
+    assert_eq!(check_block_precommits(&block_proof, &validator_keys), Ok(4));
+    let mut reversed_keys = validator_keys.clone();
+    reversed_keys.reverse();
+    assert_matches!(
+        check_block_precommits(&block_proof, &reversed_keys),
+        Err(ProofError::InvalidPrecommit(_))
+    );
+    block_proof.precommits.truncate(2);
+    assert_eq!(
+        check_block_precommits(&block_proof, &validator_keys),
+        Err(ProofError::NotEnoughPrecommits {
+            signed: 2,
+            required: 3,
+        })
+    );
+
+    // Tamper with the wallet bypassing the transactions, so that the stored wallet
+    // no longer matches the state hash of the latest block.
+    let mut fork = testkit.blockchain().fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        let wallet = schema.wallet(&alice).unwrap();
+        schema.wallets_mut().put(
+            &alice,
+            Wallet {
+                name: "Mallory".to_owned(),
+                ..wallet
+            },
+        );
+    }
+    testkit.blockchain_mut().merge(fork.into_patch()).unwrap();
+
+    let verification = api
+        .wallet_info_with(query(alice, true))
+        .unwrap()
+        .verification
+        .unwrap();
+    assert!(!verification.ok);
+    assert_eq!(verification.checked_against_block, latest_block);
+    assert_eq!(verification.validators_signed, 4);
+    assert_eq!(
+        verification.error,
+        Some(ProofError::StateHashMismatch.to_string())
+    );
+    // The whole table of wallets no longer matches, so other wallets fail as well.
+    let verification = api
+        .wallet_info_with(query(bob, true))
+        .unwrap()
+        .verification
+        .unwrap();
+    assert!(!verification.ok);
+}
+
+/// Client of the service API backed by `TestKitApi`.
+type CryptocurrencyApi = CryptocurrencyClient<TestKitApi>;
+
+/// Creates a testkit together with the API client.
+fn create_testkit() -> (TestKit, CryptocurrencyApi) {
+    let testkit = TestKitBuilder::validator()
+        .with_service(Service::default())
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
+    (testkit, api)
+}
+
+/// Creates a testkit with the service using the given configuration together with
+/// the API client.
+fn create_testkit_with(config: Config) -> (TestKit, CryptocurrencyApi) {
+    let testkit = TestKitBuilder::validator()
+        .with_service(Service::default().with_config(config))
+        .create();
+    let api = CryptocurrencyClient::new(testkit.api());
+    (testkit, api)
+}
+
+/// Asserts that the API call failed with an error of the specified kind
+/// and returns the decoded error.
+fn assert_api_error(err: api::Error, kind: ErrorKind) -> ApiError {
+    let body = match err {
+        api::Error::BadRequest(body) | api::Error::NotFound(body) => body,
+        api::Error::InternalError(e) => e.to_string(),
+        other => panic!("Unexpected API error: {}", other),
+    };
+    let error: ApiError = serde_json::from_str(&body).unwrap();
+    assert_eq!(error.kind, kind);
+    assert_eq!(error.code, kind.code());
+    error
+}
+
+/// Asserts that the transaction with the given hash has a specified status.
+fn assert_tx_status(api: &CryptocurrencyApi, tx_hash: Hash, expected_status: &serde_json::Value) {
+    let mut info = api.transaction_info(tx_hash).unwrap();
+    let tx_status = info
+        .as_object_mut()
+        .expect("Invalid transaction info format, object expected")
+        .remove("status")
+        .unwrap();
+    assert_eq!(tx_status, *expected_status);
+}
+
+/// Asserts that a wallet with the specified public key is not known to the blockchain.
+fn assert_no_wallet(api: &CryptocurrencyApi, pub_key: PublicKey) {
+    let wallet_info = api.wallet_info(pub_key).unwrap();
+    let to_wallet = wallet_info.wallet_proof.to_wallet.check().unwrap();
+    assert!(to_wallet.missing_keys().any(|v| *v == pub_key))
 }
@@ -0,0 +1,287 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the administrative subcommands. The commands are run against the public API
+//! of a testkit blockchain served over HTTP.
+
+#[macro_use]
+extern crate assert_matches;
+
+use actix_web::{test::TestServer, App};
+use exonum::{
+    api::{ApiAccess, ApiAggregator, ServiceApiState},
+    blockchain::{SharedNodeState, TransactionSet},
+    crypto::{self, PublicKey},
+};
+use exonum_testkit::{TestKit, TestKitBuilder};
+
+use std::{
+    env,
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use exonum_cryptocurrency_advanced::{
+    cli::{
+        read_key_file, CliError, IssueCommand, TxStatus, DEFAULT_NODE_URL, DEFAULT_PASSPHRASE_ENV,
+    },
+    client::CryptocurrencyClient,
+    transactions::{CreateWallet, Error, WalletTransactions},
+    Amount, Schema, Service,
+};
+
+const PASSPHRASE: &str = "issuer passphrase";
+
+#[test]
+fn test_issue_command_args() {
+    let (to, _) = crypto::gen_keypair();
+    let command = IssueCommand::from_args(vec![
+        "issue".to_owned(),
+        "--to".to_owned(),
+        to.to_hex(),
+        "--amount".to_owned(),
+        "500".to_owned(),
+        "--key-file".to_owned(),
+        "issuer.key.toml".to_owned(),
+        "--node".to_owned(),
+        "http://node:8200/".to_owned(),
+        "--seed".to_owned(),
+        "7".to_owned(),
+        "--timeout".to_owned(),
+        "5".to_owned(),
+    ])
+    .unwrap();
+    assert_eq!(
+        command,
+        IssueCommand {
+            to: Some(to),
+            amount: Amount::from(500),
+            key_file: PathBuf::from("issuer.key.toml"),
+            passphrase_env: DEFAULT_PASSPHRASE_ENV.to_owned(),
+            node_url: "http://node:8200".to_owned(),
+            seed: Some(7),
+            timeout: Duration::from_secs(5),
+        }
+    );
+
+    let command =
+        IssueCommand::from_args(vec!["issue", "--amount", "1", "--key-file", "key.toml"]).unwrap();
+    assert_eq!(command.to, None);
+    assert_eq!(command.node_url, DEFAULT_NODE_URL);
+    assert_eq!(command.seed, None);
+
+    let command =
+        IssueCommand::from_args(vec!["issue", "--amount", "2.75", "--key-file", "key.toml"])
+            .unwrap();
+    assert_eq!(command.amount, Amount::from_raw(2_750_000_000_000_000_000));
+
+    // Missing amount, malformed amount and malformed receiver.
+    assert!(IssueCommand::from_args(vec!["issue", "--key-file", "key.toml"]).is_err());
+    assert!(
+        IssueCommand::from_args(vec!["issue", "--amount", "many", "--key-file", "key.toml"])
+            .is_err()
+    );
+    assert!(IssueCommand::from_args(vec![
+        "issue",
+        "--to",
+        "alice",
+        "--amount",
+        "1",
+        "--key-file",
+        "key.toml"
+    ])
+    .is_err());
+}
+
+#[test]
+fn test_issue_command_transaction() {
+    let (pk, sk) = crypto::gen_keypair();
+    let (to, _) = crypto::gen_keypair();
+    let mut command =
+        IssueCommand::from_args(vec!["issue", "--amount", "5", "--key-file", "key.toml"]).unwrap();
+    command.seed = Some(3);
+
+    let tx = command.transaction(&pk, &sk);
+    assert_eq!(tx.author(), pk);
+    assert_matches!(
+        WalletTransactions::tx_from_raw(tx.payload().clone()).unwrap(),
+        WalletTransactions::Issue(ref issue) if issue.amount == 5 && issue.seed == 3
+    );
+    // The same seed produces the same transaction.
+    assert_eq!(command.transaction(&pk, &sk), tx);
+
+    command.to = Some(to);
+    let tx = command.transaction(&pk, &sk);
+    assert_matches!(
+        WalletTransactions::tx_from_raw(tx.payload().clone()).unwrap(),
+        WalletTransactions::IssueTo(ref issue) if issue.to == to && issue.amount == 5
+    );
+}
+
+#[test]
+fn test_issue_command_key_file() {
+    let path = temp_key_file();
+    let pk = crypto::generate_keys_file(&path, PASSPHRASE).unwrap();
+    let (read_pk, _) = read_key_file(&path, PASSPHRASE).unwrap();
+    assert_eq!(read_pk, pk);
+
+    assert_matches!(
+        read_key_file(&path, "wrong passphrase"),
+        Err(CliError::KeyFile(..))
+    );
+    assert_matches!(
+        read_key_file(&temp_key_file(), PASSPHRASE),
+        Err(CliError::KeyFile(..))
+    );
+}
+
+#[test]
+fn test_issue_command_commits_transaction() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service::default())
+        .create();
+    let server = serve_public_api(&testkit);
+    let (path, pk) = issuer_key_file("CRYPTOCURRENCY_TEST_ISSUE_PASS");
+    let (_, sk) = read_key_file(&path, PASSPHRASE).unwrap();
+    testkit.create_block_with_transaction(CreateWallet::sign("Issuer", &pk, &sk));
+
+    let (tx_hash, status) = run_issue(
+        &mut testkit,
+        vec![
+            "issue".to_owned(),
+            "--amount".to_owned(),
+            "500".to_owned(),
+            "--key-file".to_owned(),
+            path.to_string_lossy().into_owned(),
+            "--passphrase-env".to_owned(),
+            "CRYPTOCURRENCY_TEST_ISSUE_PASS".to_owned(),
+            "--node".to_owned(),
+            server.url(""),
+        ],
+    )
+    .unwrap();
+    assert_eq!(status, TxStatus::Success);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.wallet(&pk).unwrap().balance, 600);
+    let client = CryptocurrencyClient::http(&server.url(""));
+    assert_eq!(client.get_wallet(pk).unwrap().unwrap().balance, 600);
+    assert!(exonum::blockchain::Schema::new(&snapshot)
+        .transactions()
+        .contains(&tx_hash));
+}
+
+#[test]
+fn test_issue_command_reports_rejection() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service::default())
+        .create();
+    let server = serve_public_api(&testkit);
+    // The issuer has no wallet to receive the currency.
+    let (path, _) = issuer_key_file("CRYPTOCURRENCY_TEST_REJECTED_ISSUE_PASS");
+
+    let (_, status) = run_issue(
+        &mut testkit,
+        vec![
+            "issue".to_owned(),
+            "--amount".to_owned(),
+            "500".to_owned(),
+            "--key-file".to_owned(),
+            path.to_string_lossy().into_owned(),
+            "--passphrase-env".to_owned(),
+            "CRYPTOCURRENCY_TEST_REJECTED_ISSUE_PASS".to_owned(),
+            "--node".to_owned(),
+            server.url(""),
+        ],
+    )
+    .unwrap();
+    assert_matches!(
+        status,
+        TxStatus::Error { code, .. } if code == Error::ReceiverNotFound as u8
+    );
+    assert!(!status.is_success());
+}
+
+#[test]
+fn test_issue_command_unreachable_node() {
+    let (path, _) = issuer_key_file("CRYPTOCURRENCY_TEST_UNREACHABLE_ISSUE_PASS");
+    let command = IssueCommand::from_args(vec![
+        "issue".to_owned(),
+        "--amount".to_owned(),
+        "1".to_owned(),
+        "--key-file".to_owned(),
+        path.to_string_lossy().into_owned(),
+        "--passphrase-env".to_owned(),
+        "CRYPTOCURRENCY_TEST_UNREACHABLE_ISSUE_PASS".to_owned(),
+        "--node".to_owned(),
+        "http://127.0.0.1:1".to_owned(),
+    ])
+    .unwrap();
+    assert_matches!(
+        command.run(),
+        Err(CliError::NodeUnreachable(ref url, _)) if url == "http://127.0.0.1:1"
+    );
+}
+
+/// Serves the public API of the testkit blockchain under `api`, like a node does.
+fn serve_public_api(testkit: &TestKit) -> TestServer {
+    let blockchain = testkit.blockchain().clone();
+    let aggregator = ApiAggregator::new(blockchain.clone(), SharedNodeState::new(10_000));
+    TestServer::with_factory(move || {
+        App::with_state(ServiceApiState::new(blockchain.clone())).scope("api", |scope| {
+            aggregator.extend_backend(ApiAccess::Public, scope)
+        })
+    })
+}
+
+/// Runs the `issue` command in a separate thread, creating blocks until it returns.
+fn run_issue(
+    testkit: &mut TestKit,
+    args: Vec<String>,
+) -> Result<(crypto::Hash, TxStatus), CliError> {
+    let command = IssueCommand::from_args(args).unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || sender.send(command.run()).unwrap());
+
+    let started = Instant::now();
+    loop {
+        if let Ok(result) = receiver.recv_timeout(Duration::from_millis(100)) {
+            return result;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "issue command does not return"
+        );
+        testkit.create_block();
+    }
+}
+
+/// Creates a key file encrypted with `PASSPHRASE`, which is put to the given
+/// environment variable.
+fn issuer_key_file(passphrase_env: &str) -> (PathBuf, PublicKey) {
+    env::set_var(passphrase_env, PASSPHRASE);
+    let path = temp_key_file();
+    let pk = crypto::generate_keys_file(&path, PASSPHRASE).unwrap();
+    (path, pk)
+}
+
+fn temp_key_file() -> PathBuf {
+    env::temp_dir().join(format!(
+        "cryptocurrency-issuer-{}.key.toml",
+        crypto::gen_keypair().0.to_hex()
+    ))
+}
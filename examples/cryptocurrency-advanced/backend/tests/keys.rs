@@ -0,0 +1,118 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test vectors of the wallet key derivation. Implementations of the derivation
+//! in other languages must produce the same seeds and public keys.
+
+use exonum::crypto;
+use exonum_testkit::TestKitBuilder;
+
+use exonum_cryptocurrency_advanced::{client::CryptocurrencyClient, keys, Schema, Service};
+
+/// Master secret, index, hex-encoded seed and public key.
+const VECTORS: &[(&[u8], u32, &str, &str)] = &[
+    (
+        &[0; 32],
+        0,
+        "8a046e987926ce98e8d2c1bae1502d18a47c66b40c3ad009afd14276ccd669a7",
+        "5fb9359bdd0bc70f329c0a55f151d97633947218a244090b8d568a89e922ced8",
+    ),
+    (
+        &[0; 32],
+        1,
+        "acbab1ebe9cfb484ac1417abeff23f2d0f11af9814f8b77c04c3bf3145dd13de",
+        "dfae58054b0a8a014ccb07e487a577a6f859b458c7547474bbef4e83d05bd535",
+    ),
+    (
+        &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+            24, 25, 26, 27, 28, 29, 30, 31,
+        ],
+        0,
+        "b787bb027dd70dfd20a2e769a3e62f5f2b84ab5056f2a852fea2a000a3ecee95",
+        "6f9c5b3abe7230484fe2f726bf4f20c3949888b28d3ebbfa4be8a56672d94c70",
+    ),
+    (
+        &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+            24, 25, 26, 27, 28, 29, 30, 31,
+        ],
+        7,
+        "92f4bc8c883cd779ff513a672e9057f7c20b77a4588cfd85477d9e1d5c5d00df",
+        "60e444d57778d8e9b82d60e02d85f0ca1a9f41e1c63a1a5985321d3a5a23765f",
+    ),
+    (
+        b"correct horse battery staple",
+        u32::max_value(),
+        "78ac6cbd63511f63ef615693183aec6e08a3c14dbecc1aaa51894a35d6d646f7",
+        "4e90db2c817b9e7242aa6366a9875c867a2ce8bcf39a565d4640366f4d5546a4",
+    ),
+];
+
+#[test]
+fn test_derivation_vectors() {
+    for &(master_seed, index, seed, pub_key) in VECTORS {
+        assert_eq!(
+            hex::encode(&keys::derive_seed(master_seed, index)[..]),
+            seed
+        );
+        let (derived_pub_key, derived_key) = keys::derive_keypair(master_seed, index);
+        assert_eq!(derived_pub_key.to_hex(), pub_key);
+        // The secret key is the seed followed by the public key.
+        assert_eq!(
+            hex::encode(&derived_key[..]),
+            format!("{}{}", seed, pub_key)
+        );
+    }
+}
+
+#[test]
+fn test_batch_derivation() {
+    let master_seed = [0; 32];
+    let keypairs = keys::derive_keypairs(&master_seed, 0..3);
+    assert_eq!(keypairs.len(), 3);
+    for (index, keypair) in (0..3).zip(keypairs.iter()) {
+        assert_eq!(*keypair, keys::derive_keypair(&master_seed, index));
+    }
+    assert_eq!(keypairs[0].0.to_hex(), VECTORS[0].3);
+    assert_eq!(keypairs[1].0.to_hex(), VECTORS[1].3);
+    assert!(keys::derive_keypairs(&master_seed, 5..5).is_empty());
+
+    // Keys of different indices and of different master secrets differ.
+    assert_ne!(keypairs[0].0, keypairs[2].0);
+    assert_ne!(keypairs[0].0, keys::derive_keypair(&[1; 32], 0).0);
+}
+
+#[test]
+fn test_create_derived_wallet() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service::default())
+        .create();
+    let client = CryptocurrencyClient::new(testkit.api());
+    let master_seed = b"exchange master secret";
+
+    let (tx, key) = client
+        .create_derived_wallet("Deposit 42", master_seed, 42)
+        .unwrap();
+    testkit.create_block();
+    let (pub_key, derived_key) = keys::derive_keypair(master_seed, 42);
+    assert_eq!(tx.author(), pub_key);
+    assert_eq!(key, derived_key);
+
+    let wallet = Schema::new(&testkit.snapshot()).wallet(&pub_key).unwrap();
+    assert_eq!(wallet.name, "Deposit 42");
+    // The key signs on behalf of the derived wallet.
+    let signature = crypto::sign(b"message", &key);
+    assert!(crypto::verify(&signature, b"message", &pub_key));
+}
@@ -1,5 +1,6 @@
 import * as Exonum from 'exonum-client'
 import axios from 'axios'
+import bigInt from 'big-integer'
 import * as proto from '../../proto/stubs.js'
 
 const TRANSACTION_URL = '/api/explorer/v1/transactions'
@@ -10,6 +11,81 @@ const TX_ISSUE_ID = 1
 const TX_WALLET_ID = 2
 const TABLE_INDEX = 0
 const Wallet = Exonum.newType(proto.exonum.examples.cryptocurrency_advanced.Wallet)
+const HistoryRecord = Exonum.newType(proto.exonum.examples.cryptocurrency_advanced.HistoryRecord)
+const HISTORY_CHUNK_SIZE = 128
+const AMOUNT_DECIMALS = 18
+const AMOUNT_SCALE = bigInt(10).pow(AMOUNT_DECIMALS)
+const UINT64_LIMIT = bigInt(2).pow(64)
+
+// Whole amounts fitting into `uint64` are written to the legacy field of whole units,
+// e.g. `amount`, and the other ones to the `exact_*` field in units of 10^-18
+// split into two `uint64` halves.
+function amountFields(field, amount) {
+  if (!/^[0-9]+(\.[0-9]*)?$/.test(amount.toString())) {
+    throw new Error(`Invalid amount: ${amount}`)
+  }
+  const [units, fraction = ''] = amount.toString().split('.')
+  if (fraction.length > AMOUNT_DECIMALS) {
+    throw new Error(`Amount has more than ${AMOUNT_DECIMALS} decimals`)
+  }
+  const raw = bigInt(units).multiply(AMOUNT_SCALE).add(bigInt(fraction.padEnd(AMOUNT_DECIMALS, '0')))
+  if (raw.mod(AMOUNT_SCALE).isZero() && bigInt(units).lesser(UINT64_LIMIT)) {
+    return { [field]: bigInt(units).toString() }
+  }
+  const exact = raw.divmod(UINT64_LIMIT)
+  return { [`exact_${field}`]: { low: exact.remainder.toString(), high: exact.quotient.toString() } }
+}
+
+// Reads an amount written by `amountFields` as a decimal string; an amount
+// written in whole units is returned as is.
+function readAmount(units, exact) {
+  if (!exact) {
+    return units
+  }
+  const raw = bigInt(exact.high.toString()).multiply(UINT64_LIMIT).add(bigInt(exact.low.toString()))
+  if (raw.isZero()) {
+    return units
+  }
+  const { quotient, remainder } = raw.divmod(AMOUNT_SCALE)
+  const fraction = remainder.toString().padStart(AMOUNT_DECIMALS, '0').replace(/0+$/, '')
+  return fraction ? `${quotient}.${fraction}` : quotient.toString()
+}
+
+// Converts the JSON representation of a transaction, where amounts are decimal
+// strings, to the fields of its protobuf message.
+function transactionMessage(data) {
+  if (typeof data.amount === 'undefined') {
+    return data
+  }
+  const message = Object.assign({}, data)
+  delete message.amount
+  return Object.assign(message, amountFields('amount', data.amount))
+}
+
+// The wallet history is split into chunks of `HISTORY_CHUNK_SIZE` records,
+// and `history_hash` of the wallet is the root hash of the list of the chunk roots.
+function verifyHistory(historyHash, historyLen, proof, from, to) {
+  if (from === to) {
+    return []
+  }
+  const firstChunk = Math.floor(from / HISTORY_CHUNK_SIZE)
+  const lastChunk = Math.floor((to - 1) / HISTORY_CHUNK_SIZE)
+  const chunkCount = Math.ceil(historyLen / HISTORY_CHUNK_SIZE)
+  const chunkRoots = Exonum.merkleProof(historyHash, chunkCount, proof.chunk_roots, [firstChunk, lastChunk + 1])
+  if (chunkRoots.length !== proof.chunks.length) {
+    throw new Error('History proof is corrupted')
+  }
+
+  let records = []
+  chunkRoots.forEach((root, index) => {
+    const chunk = firstChunk + index
+    const offset = chunk * HISTORY_CHUNK_SIZE
+    const chunkLen = Math.min(HISTORY_CHUNK_SIZE, historyLen - offset)
+    const range = [Math.max(from, offset) - offset, Math.min(to, offset + chunkLen) - offset]
+    records = records.concat(Exonum.merkleProof(root, chunkLen, proof.chunks[index], range, HistoryRecord))
+  })
+  return records
+}
 
 function TransferTransaction(publicKey) {
   return Exonum.newTransaction({
@@ -79,10 +155,7 @@ module.exports = {
         const transaction = new IssueTransaction(keyPair.publicKey)
 
         // Transaction data
-        const data = {
-          amount: amountToAdd.toString(),
-          seed: seed
-        }
+        const data = Object.assign({ seed: seed }, amountFields('amount', amountToAdd))
 
         // Send transaction into blockchain
         return transaction.send(TRANSACTION_URL, data, keyPair.secretKey)
@@ -93,11 +166,10 @@ module.exports = {
         const transaction = new TransferTransaction(keyPair.publicKey)
 
         // Transaction data
-        const data = {
+        const data = Object.assign({
           to: { data: Exonum.hexadecimalToUint8Array(receiver) },
-          amount: amountToTransfer,
           seed: seed
-        }
+        }, amountFields('amount', amountToTransfer))
 
         // Send transaction into blockchain
         return transaction.send(TRANSACTION_URL, data, keyPair.secretKey)
@@ -126,14 +198,16 @@ module.exports = {
                 if (typeof wallet === 'undefined') {
                   throw new Error('Wallet not found')
                 }
+                wallet.balance = readAmount(wallet.balance, wallet.exact_balance)
 
-                // get transactions
-                const transactionsMetaData = Exonum.merkleProof(
+                // get transactions (the latest page of the history is returned by default)
+                const historyFrom = data.wallet_history.from
+                const transactionsMetaData = verifyHistory(
                   Exonum.uint8ArrayToHexadecimal(new Uint8Array(wallet.history_hash.data)),
                   wallet.history_len,
                   data.wallet_history.proof,
-                  [0, wallet.history_len],
-                  Exonum.Hash
+                  historyFrom,
+                  historyFrom + data.wallet_history.transactions.length
                 )
 
                 if (data.wallet_history.transactions.length !== transactionsMetaData.length) {
@@ -147,26 +221,28 @@ module.exports = {
                 let index = 0
 
                 for (let transaction of data.wallet_history.transactions) {
-                  const hash = transactionsMetaData[index++]
+                  const record = transactionsMetaData[index++]
+                  const hash = Exonum.uint8ArrayToHexadecimal(new Uint8Array(record.tx_hash.data))
                   const buffer = Exonum.hexadecimalToUint8Array(transaction.message)
                   const bufferWithoutSignature = buffer.subarray(0, buffer.length - 64)
                   const author = Exonum.uint8ArrayToHexadecimal(buffer.subarray(0, 32))
                   const signature = Exonum.uint8ArrayToHexadecimal(buffer.subarray(buffer.length - 64, buffer.length));
 
                   const Transaction = getTransaction(transaction.debug, author)
+                  const message = transactionMessage(transaction.debug)
 
                   if (Exonum.hash(buffer) !== hash) {
                     throw new Error('Invalid transaction hash')
                   }
 
                   // serialize transaction and compare with message
-                  if (!Transaction.serialize(transaction.debug).every(function (el, i) {
+                  if (!Transaction.serialize(message).every(function (el, i) {
                     return el === bufferWithoutSignature[i]
                   })) {
                     throw new Error('Invalid transaction message')
                   }
 
-                  if (!Transaction.verifySignature(signature, author, transaction.debug)) {
+                  if (!Transaction.verifySignature(signature, author, message)) {
                     throw new Error('Invalid transaction signature')
                   }
 
@@ -682,6 +682,11 @@ impl<'a> BlockchainExplorer<'a> {
         }
     }
 
+    /// Returns the snapshot of the blockchain state wrapped by the explorer.
+    pub fn snapshot(&self) -> &(dyn Snapshot + 'static) {
+        &*self.snapshot
+    }
+
     /// Returns information about the transaction identified by the hash.
     pub fn transaction(&self, tx_hash: &Hash) -> Option<TransactionInfo> {
         let schema = Schema::new(&self.snapshot);
@@ -20,7 +20,11 @@ use actix_web::{test::TestServer, App};
 use reqwest::{Client, Response, StatusCode};
 use serde::{de::DeserializeOwned, Serialize};
 
-use std::fmt::{self, Display};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    io::Read,
+};
 
 use exonum::{
     api::{self, ApiAggregator, ServiceApiState},
@@ -182,6 +186,54 @@ where
     where
         R: DeserializeOwned + 'static,
     {
+        let url = self.get_url(endpoint);
+
+        trace!("GET {}", url);
+
+        let response = self
+            .test_client
+            .get(&url)
+            .send()
+            .expect("Unable to send request");
+        Self::response_to_api_result(response)
+    }
+
+    /// Sends a get request with the given headers to the testing API endpoint and
+    /// returns the response as is, without decoding its body.
+    pub fn get_raw(&self, endpoint: &str, headers: &[(&str, &str)]) -> RawResponse {
+        let url = self.get_url(endpoint);
+
+        trace!("GET {} with headers {:?}", url, headers);
+
+        let builder = headers
+            .iter()
+            .fold(self.test_client.get(&url), |builder, (name, value)| {
+                builder.header(*name, *value)
+            });
+        let mut response = builder.send().expect("Unable to send request");
+
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = value.to_str().ok()?;
+                Some((name.as_str().to_owned(), value.to_owned()))
+            })
+            .collect();
+        let mut body = Vec::new();
+        response
+            .read_to_end(&mut body)
+            .expect("Unable to read response body");
+
+        RawResponse {
+            status: response.status().as_u16(),
+            headers,
+            body,
+        }
+    }
+
+    /// Returns the URL of the get request to the given endpoint.
+    fn get_url(&self, endpoint: &str) -> String {
         let params = self
             .query
             .as_ref()
@@ -192,23 +244,14 @@ where
                 )
             })
             .unwrap_or_default();
-        let url = format!(
+        format!(
             "{url}{access}/{prefix}/{endpoint}{query}",
             url = self.test_server_url,
             access = self.access,
             prefix = self.prefix,
             endpoint = endpoint,
             query = params
-        );
-
-        trace!("GET {}", url);
-
-        let response = self
-            .test_client
-            .get(&url)
-            .send()
-            .expect("Unable to send request");
-        Self::response_to_api_result(response)
+        )
     }
 
     /// Sends a post request to the testing API endpoint and decodes response as
@@ -279,6 +322,17 @@ where
     }
 }
 
+/// Response of the testing API returned by `RequestBuilder::get_raw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawResponse {
+    /// HTTP status code.
+    pub status: u16,
+    /// Response headers. Header names are lowercase.
+    pub headers: HashMap<String, String>,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
 /// Creates a test server.
 fn create_test_server(aggregator: ApiAggregator) -> TestServer {
     let server = TestServer::with_factory(move || {
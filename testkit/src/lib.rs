@@ -158,7 +158,7 @@ extern crate serde_derive;
 #[cfg(test)]
 extern crate exonum_derive;
 
-pub use crate::api::{ApiKind, TestKitApi};
+pub use crate::api::{ApiKind, RawResponse, TestKitApi};
 pub use crate::compare::ComparableSnapshot;
 pub use crate::network::{TestNetwork, TestNetworkConfiguration, TestNode};
 